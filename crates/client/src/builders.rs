@@ -0,0 +1,390 @@
+//! Typed instruction builders
+//!
+//! Each builder takes what its instruction requires up front, optional
+//! arguments through `with_*`, and derives the program addresses itself.
+//! `TransferBuilder::from_spend` and `UnshieldBuilder::from_withdrawal` take
+//! a proof from `veil_core::proof` and read the nullifier, root, fee and
+//! amount from its public inputs, so they match what the proof binds.
+
+use veil_core::proof::{field_to_bytes_be, SpendProof, WithdrawalProof};
+use veil_core::transaction::{Instruction, PoolAccounts, Pubkey};
+
+use crate::error::{ClientError, ClientResult};
+use crate::pda::nullifier_accounts;
+
+/// `shield_sol`: deposit lamports under a commitment
+#[derive(Clone, Debug)]
+pub struct ShieldSolBuilder {
+    accounts: PoolAccounts,
+    depositor: Pubkey,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+}
+
+impl ShieldSolBuilder {
+    /// Deposit `amount` lamports from `depositor`, who signs, under
+    /// `commitment`
+    pub fn new(
+        accounts: PoolAccounts,
+        depositor: Pubkey,
+        commitment: [u8; 32],
+        amount: u64,
+    ) -> Self {
+        Self {
+            accounts,
+            depositor,
+            commitment,
+            amount,
+            encrypted_note: Vec::new(),
+        }
+    }
+
+    /// Publish `encrypted_note` (from `crypto::encrypt_note`) with the
+    /// commitment
+    pub fn with_encrypted_note(mut self, encrypted_note: Vec<u8>) -> Self {
+        self.encrypted_note = encrypted_note;
+        self
+    }
+
+    /// The account paying the deposit
+    pub fn depositor(&self) -> Pubkey {
+        self.depositor
+    }
+
+    /// Build the instruction
+    pub fn instruction(&self) -> Instruction {
+        self.accounts
+            .shield_sol(self.depositor, &self.commitment, self.amount, &self.encrypted_note)
+    }
+}
+
+/// `transfer`: spend a note into a new commitment
+#[derive(Clone, Debug)]
+pub struct TransferBuilder {
+    accounts: PoolAccounts,
+    relayer: Pubkey,
+    nullifier: [u8; 32],
+    new_commitment: [u8; 32],
+    root: [u8; 32],
+    proof: Vec<u8>,
+    fee: u64,
+    tree_epoch: u32,
+    encrypted_note: Vec<u8>,
+}
+
+impl TransferBuilder {
+    /// Spend `nullifier` into `new_commitment`, proven by `proof` (the
+    /// 256-byte `SolanaProof` encoding) against `root`, paid by `relayer`
+    pub fn new(
+        accounts: PoolAccounts,
+        relayer: Pubkey,
+        nullifier: [u8; 32],
+        new_commitment: [u8; 32],
+        root: [u8; 32],
+        proof: Vec<u8>,
+    ) -> Self {
+        Self {
+            accounts,
+            relayer,
+            nullifier,
+            new_commitment,
+            root,
+            proof,
+            fee: 0,
+            tree_epoch: 0,
+            encrypted_note: Vec::new(),
+        }
+    }
+
+    /// Transfer proven by `spend`
+    pub fn from_spend(accounts: PoolAccounts, relayer: Pubkey, spend: &SpendProof) -> Self {
+        let [root, _, _, _, fee] = spend.public_inputs;
+        Self::new(
+            accounts,
+            relayer,
+            spend.nullifier_bytes(),
+            spend.new_commitment_bytes(),
+            field_to_bytes_be(&root),
+            spend.solana_proof.to_bytes().to_vec(),
+        )
+        .with_fee(field_to_u64(&field_to_bytes_be(&fee)))
+    }
+
+    /// Pay `fee` lamports to the relayer, as the proof binds (default 0)
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Spend from the tree of `tree_epoch` (default 0, the pool's first)
+    pub fn with_tree_epoch(mut self, tree_epoch: u32) -> Self {
+        self.tree_epoch = tree_epoch;
+        self
+    }
+
+    /// Deliver `encrypted_note` to the output's recipient
+    pub fn with_encrypted_note(mut self, encrypted_note: Vec<u8>) -> Self {
+        self.encrypted_note = encrypted_note;
+        self
+    }
+
+    /// The account paying for the transaction
+    pub fn relayer(&self) -> Pubkey {
+        self.relayer
+    }
+
+    /// Build the instruction
+    pub fn instruction(&self) -> ClientResult<Instruction> {
+        let spend = nullifier_accounts(&self.accounts, self.tree_epoch, &self.nullifier, self.root)
+            .ok_or(ClientError::NoProgramAddress("nullifier accounts"))?;
+        Ok(self.accounts.transfer(
+            self.relayer,
+            spend,
+            &self.nullifier,
+            &self.new_commitment,
+            self.fee,
+            &self.proof,
+            &self.encrypted_note,
+        ))
+    }
+}
+
+/// `unshield_sol`: spend a note and withdraw its lamports
+#[derive(Clone, Debug)]
+pub struct UnshieldBuilder {
+    accounts: PoolAccounts,
+    relayer: Pubkey,
+    recipient: Pubkey,
+    nullifier: [u8; 32],
+    amount: u64,
+    root: [u8; 32],
+    proof: Vec<u8>,
+    tree_epoch: u32,
+    memo: Vec<u8>,
+}
+
+impl UnshieldBuilder {
+    /// Spend `nullifier` and withdraw `amount` lamports, less the proven
+    /// relayer fee, to `recipient`, proven by `proof` against `root`, paid by
+    /// `relayer`
+    pub fn new(
+        accounts: PoolAccounts,
+        relayer: Pubkey,
+        recipient: Pubkey,
+        nullifier: [u8; 32],
+        amount: u64,
+        root: [u8; 32],
+        proof: Vec<u8>,
+    ) -> Self {
+        Self {
+            accounts,
+            relayer,
+            recipient,
+            nullifier,
+            amount,
+            root,
+            proof,
+            tree_epoch: 0,
+            memo: Vec::new(),
+        }
+    }
+
+    /// Withdrawal to `recipient` proven by `withdrawal`
+    ///
+    /// The proof binds a hash of the recipient, not its address, so the
+    /// recipient is passed separately.
+    pub fn from_withdrawal(
+        accounts: PoolAccounts,
+        relayer: Pubkey,
+        recipient: Pubkey,
+        withdrawal: &WithdrawalProof,
+    ) -> Self {
+        let [root, _, _, amount, _, _] = withdrawal.public_inputs;
+        Self::new(
+            accounts,
+            relayer,
+            recipient,
+            withdrawal.nullifier_bytes(),
+            field_to_u64(&field_to_bytes_be(&amount)),
+            field_to_bytes_be(&root),
+            withdrawal.solana_proof.to_bytes().to_vec(),
+        )
+    }
+
+    /// Spend from the tree of `tree_epoch` (default 0, the pool's first)
+    pub fn with_tree_epoch(mut self, tree_epoch: u32) -> Self {
+        self.tree_epoch = tree_epoch;
+        self
+    }
+
+    /// Log `memo` (UTF-8, such as an exchange deposit reference) with the
+    /// withdrawal
+    pub fn with_memo(mut self, memo: Vec<u8>) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    /// The account paying for the transaction
+    pub fn relayer(&self) -> Pubkey {
+        self.relayer
+    }
+
+    /// Build the instruction
+    pub fn instruction(&self) -> ClientResult<Instruction> {
+        let spend = nullifier_accounts(&self.accounts, self.tree_epoch, &self.nullifier, self.root)
+            .ok_or(ClientError::NoProgramAddress("nullifier accounts"))?;
+        Ok(self.accounts.unshield_sol(
+            self.relayer,
+            spend,
+            self.recipient,
+            &self.nullifier,
+            self.amount,
+            &self.proof,
+            &self.memo,
+        ))
+    }
+}
+
+/// A public input below 2^64 as a `u64`, from its big-endian encoding
+fn field_to_u64(bytes: &[u8; 32]) -> u64 {
+    u64::from_be_bytes(bytes[24..].try_into().expect("8 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey as ProgramPubkey;
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use veil_core::transaction::{AccountMeta, MEMO_PROGRAM_ID};
+
+    use super::*;
+    use crate::pda::pool_accounts;
+
+    fn key(bytes: Pubkey) -> ProgramPubkey {
+        ProgramPubkey::new_from_array(bytes)
+    }
+
+    /// Account metas as the program's `Accounts` structs generate them
+    fn metas(metas: Vec<anchor_lang::prelude::AccountMeta>) -> Vec<AccountMeta> {
+        metas
+            .into_iter()
+            .map(|m| AccountMeta {
+                pubkey: m.pubkey.to_bytes(),
+                is_signer: m.is_signer,
+                is_writable: m.is_writable,
+            })
+            .collect()
+    }
+
+    fn accounts() -> PoolAccounts {
+        pool_accounts(veil_program::ID.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_shield_sol() {
+        let accounts = accounts();
+        let ix = ShieldSolBuilder::new(accounts, [5; 32], [7; 32], 1_000)
+            .with_encrypted_note(vec![1, 2, 3])
+            .instruction();
+        let expected = veil_program::instruction::ShieldSol {
+            commitment: [7; 32],
+            amount: 1_000,
+            encrypted_note: vec![1, 2, 3],
+        };
+        assert_eq!(ix.data, expected.data());
+        let expected = veil_program::accounts::ShieldSol {
+            pool: key(accounts.pool),
+            root_history: key(accounts.root_history),
+            vault: key(accounts.vault),
+            depositor: key([5; 32]),
+            system_program: ProgramPubkey::default(),
+        };
+        assert_eq!(ix.accounts, metas(expected.to_account_metas(None)));
+    }
+
+    #[test]
+    fn test_transfer() {
+        let accounts = accounts();
+        let builder =
+            TransferBuilder::new(accounts, [5; 32], [1; 32], [2; 32], [3; 32], vec![4; 256]);
+        let builder = builder
+            .with_fee(5_000)
+            .with_tree_epoch(1)
+            .with_encrypted_note(vec![6; 10]);
+        let ix = builder.instruction().unwrap();
+        let expected = veil_program::instruction::Transfer {
+            tree_epoch: 1,
+            root: [3; 32],
+            nullifier: [1; 32],
+            new_commitment: [2; 32],
+            fee: 5_000,
+            proof: vec![4; 256],
+            encrypted_note: vec![6; 10],
+            extra_nullifiers: Vec::new(),
+        };
+        assert_eq!(ix.data, expected.data());
+
+        let spend = nullifier_accounts(&accounts, 1, &[1; 32], [3; 32]).unwrap();
+        let expected = veil_program::accounts::Transfer {
+            pool: key(accounts.pool),
+            verifying_key: key(accounts.verifying_key),
+            root_history: key(accounts.root_history),
+            nullifier_marker: key(spend.marker),
+            nullifier_archive: key(spend.archive),
+            archived_root: key(spend.archived_root),
+            relayer: key([5; 32]),
+            system_program: ProgramPubkey::default(),
+            vault: key(accounts.vault),
+            mint: None,
+            vault_token_account: None,
+            relayer_token_account: None,
+            token_program: None,
+        };
+        assert_eq!(ix.accounts, metas(expected.to_account_metas(None)));
+    }
+
+    #[test]
+    fn test_unshield() {
+        let accounts = accounts();
+        let builder =
+            UnshieldBuilder::new(accounts, [5; 32], [6; 32], [1; 32], 9_000, [3; 32], vec![4; 256]);
+        let ix = builder.clone().with_memo(b"ref 12".to_vec()).instruction().unwrap();
+        let expected = veil_program::instruction::UnshieldSol {
+            tree_epoch: 0,
+            root: [3; 32],
+            nullifier: [1; 32],
+            amount: 9_000,
+            proof: vec![4; 256],
+            memo: b"ref 12".to_vec(),
+        };
+        assert_eq!(ix.data, expected.data());
+
+        let spend = nullifier_accounts(&accounts, 0, &[1; 32], [3; 32]).unwrap();
+        let expected = |memo_program: Option<ProgramPubkey>| veil_program::accounts::UnshieldSol {
+            pool: key(accounts.pool),
+            verifying_key: key(accounts.verifying_key),
+            root_history: key(accounts.root_history),
+            nullifier_marker: key(spend.marker),
+            nullifier_archive: key(spend.archive),
+            archived_root: key(spend.archived_root),
+            pending_withdrawal: key(spend.pending_withdrawal),
+            vault: key(accounts.vault),
+            treasury: key(accounts.treasury),
+            recipient: key([6; 32]),
+            relayer: key([5; 32]),
+            system_program: ProgramPubkey::default(),
+            memo_program,
+        };
+        let with_memo = expected(Some(key(MEMO_PROGRAM_ID))).to_account_metas(None);
+        assert_eq!(ix.accounts, metas(with_memo));
+        let without = builder.instruction().unwrap();
+        assert_eq!(without.accounts, metas(expected(None).to_account_metas(None)));
+    }
+
+    #[test]
+    fn test_field_to_u64() {
+        let mut bytes = [0; 32];
+        bytes[24..].copy_from_slice(&5_000u64.to_be_bytes());
+        assert_eq!(field_to_u64(&bytes), 5_000);
+    }
+}
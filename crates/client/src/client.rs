@@ -0,0 +1,183 @@
+//! Sending pool instructions
+//!
+//! `VeilClient` wraps the blocking `veil_core::rpc::RpcClient` for async
+//! callers: each RPC call runs on tokio's blocking pool, while building and
+//! signing stay on the caller's task, so keypairs never leave it.
+
+use std::sync::Arc;
+
+use veil_core::rpc::{sign_transaction, Keypair, RpcClient};
+use veil_core::transaction::{Instruction, PoolAccounts, Pubkey, TransactionAssembler};
+
+use crate::builders::{ShieldSolBuilder, TransferBuilder, UnshieldBuilder};
+use crate::error::{ClientError, ClientResult};
+use crate::pda::pool_accounts;
+
+/// Compute units requested for proof-carrying instructions
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 400_000;
+
+/// Async client of the SOL pool of one program
+pub struct VeilClient {
+    rpc: Arc<RpcClient>,
+    accounts: PoolAccounts,
+    compute_unit_limit: u32,
+}
+
+impl VeilClient {
+    /// Client of the SOL pool of `program_id` through the RPC endpoint at
+    /// `rpc_url`
+    pub fn new(rpc_url: impl Into<String>, program_id: Pubkey) -> ClientResult<Self> {
+        let accounts =
+            pool_accounts(program_id).ok_or(ClientError::NoProgramAddress("pool accounts"))?;
+        Ok(Self {
+            rpc: Arc::new(RpcClient::new(rpc_url)),
+            accounts,
+            compute_unit_limit: DEFAULT_COMPUTE_UNIT_LIMIT,
+        })
+    }
+
+    /// Request `units` compute units for transfers and unshields
+    pub fn with_compute_unit_limit(mut self, units: u32) -> Self {
+        self.compute_unit_limit = units;
+        self
+    }
+
+    /// The pool's addresses, for the instruction builders
+    pub fn accounts(&self) -> &PoolAccounts {
+        &self.accounts
+    }
+
+    /// The underlying RPC client
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    /// Shield `amount` lamports from `depositor` under `commitment`
+    pub fn shield_sol(
+        &self,
+        depositor: Pubkey,
+        commitment: [u8; 32],
+        amount: u64,
+    ) -> ShieldSolBuilder {
+        ShieldSolBuilder::new(self.accounts, depositor, commitment, amount)
+    }
+
+    /// Send a shield signed by `depositor`, returning its signature
+    pub async fn send_shield_sol(
+        &self,
+        depositor: &Keypair,
+        shield: &ShieldSolBuilder,
+    ) -> ClientResult<String> {
+        self.send(depositor, None, vec![shield.instruction()]).await
+    }
+
+    /// Send a transfer paid and signed by `relayer`, returning its signature
+    pub async fn send_transfer(
+        &self,
+        relayer: &Keypair,
+        transfer: &TransferBuilder,
+    ) -> ClientResult<String> {
+        let instruction = transfer.instruction()?;
+        self.send(relayer, Some(self.compute_unit_limit), vec![instruction]).await
+    }
+
+    /// Send an unshield paid and signed by `relayer`, returning its signature
+    pub async fn send_unshield(
+        &self,
+        relayer: &Keypair,
+        unshield: &UnshieldBuilder,
+    ) -> ClientResult<String> {
+        let instruction = unshield.instruction()?;
+        self.send(relayer, Some(self.compute_unit_limit), vec![instruction]).await
+    }
+
+    /// Send `instructions` in one transaction paid and signed by `payer`,
+    /// returning its signature
+    pub async fn send_instructions(
+        &self,
+        payer: &Keypair,
+        instructions: Vec<Instruction>,
+    ) -> ClientResult<String> {
+        self.send(payer, None, instructions).await
+    }
+
+    async fn send(
+        &self,
+        payer: &Keypair,
+        compute_unit_limit: Option<u32>,
+        instructions: Vec<Instruction>,
+    ) -> ClientResult<String> {
+        let blockhash = self.blocking(|rpc| rpc.get_latest_blockhash()).await?;
+        let transaction = sign(payer, compute_unit_limit, instructions, blockhash)?;
+        self.blocking(move |rpc| rpc.send_transaction(&transaction)).await
+    }
+
+    /// Run `call` on the blocking pool
+    async fn blocking<T, F>(&self, call: F) -> ClientResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&RpcClient) -> veil_core::error::VeilResult<T> + Send + 'static,
+    {
+        let rpc = self.rpc.clone();
+        tokio::task::spawn_blocking(move || call(&rpc))
+            .await
+            .map_err(|e| ClientError::Task(e.to_string()))?
+            .map_err(ClientError::from)
+    }
+}
+
+/// Assemble `instructions` against `blockhash`, after a compute budget if
+/// given, and sign them as `payer`
+fn sign(
+    payer: &Keypair,
+    compute_unit_limit: Option<u32>,
+    instructions: Vec<Instruction>,
+    blockhash: [u8; 32],
+) -> ClientResult<Vec<u8>> {
+    let mut asm = TransactionAssembler::new(payer.public.to_bytes());
+    if let Some(units) = compute_unit_limit {
+        asm.set_compute_unit_limit(units);
+    }
+    for instruction in instructions {
+        asm.add_instruction(instruction);
+    }
+    let transaction = asm.assemble(blockhash)?;
+    Ok(sign_transaction(&transaction, &[payer])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use veil_core::error::VeilError;
+
+    use super::*;
+
+    fn keypair() -> Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[3; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+
+    #[test]
+    fn test_sign() {
+        let payer = keypair();
+        let client = VeilClient::new("http://127.0.0.1:1", veil_program::ID.to_bytes()).unwrap();
+        let shield = client.shield_sol(payer.public.to_bytes(), [7; 32], 1_000);
+        let transaction =
+            sign(&payer, Some(300_000), vec![shield.instruction()], [9; 32]).unwrap();
+
+        // Signed by the payer, requesting the budget
+        assert_eq!(transaction[0], 1);
+        assert_eq!(&transaction[69..101], payer.public.as_bytes());
+        let limit = [&[2][..], &300_000u32.to_le_bytes()].concat();
+        assert!(transaction.windows(limit.len()).any(|w| w == limit));
+        assert!(transaction.windows(32).any(|w| w == client.accounts().vault));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_rpc() {
+        let client = VeilClient::new("http://127.0.0.1:1", veil_program::ID.to_bytes()).unwrap();
+        let shield = client.shield_sol(keypair().public.to_bytes(), [7; 32], 1_000);
+        let error = client.send_shield_sol(&keypair(), &shield).await.unwrap_err();
+        assert!(matches!(error, ClientError::Core(VeilError::Rpc(_))), "{}", error);
+    }
+}
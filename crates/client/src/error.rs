@@ -0,0 +1,23 @@
+//! Client errors
+
+use thiserror::Error;
+use veil_core::error::VeilError;
+
+/// Errors building or sending pool instructions
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// An RPC call, assembly or signing failed
+    #[error(transparent)]
+    Core(#[from] VeilError),
+
+    /// No bump seed gives a program address for the account
+    #[error("no program address for the {0}")]
+    NoProgramAddress(&'static str),
+
+    /// The blocking RPC task panicked or was cancelled
+    #[error("rpc task: {0}")]
+    Task(String),
+}
+
+/// Result type for client operations
+pub type ClientResult<T> = Result<T, ClientError>;
@@ -0,0 +1,36 @@
+//! Veil Client SDK
+//!
+//! Typed builders for the privacy pool program's instructions, the program
+//! addresses they touch, and an async client sending them:
+//!
+//! ```no_run
+//! # async fn run(payer: veil_core::rpc::Keypair) -> veil_client::ClientResult<()> {
+//! use veil_client::VeilClient;
+//! use veil_core::rpc::{decode_pubkey, DEVNET_URL};
+//!
+//! let program_id = decode_pubkey("Vei1111111111111111111111111111111111111111")?;
+//! let client = VeilClient::new(DEVNET_URL, program_id)?;
+//! let shield = client.shield_sol(payer.public.to_bytes(), [7; 32], 1_000_000);
+//! let signature = client.send_shield_sol(&payer, &shield).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Instruction data and account lists come from `veil_core::transaction`,
+//! and the tests check each builder against the program's Anchor-generated
+//! instruction and `Accounts` structs.
+//!
+//! # Modules
+//! - `builders`: `ShieldSolBuilder`, `TransferBuilder`, `UnshieldBuilder`
+//! - `client`: `VeilClient` and its async `send_*` methods
+//! - `error`: `ClientError`
+//! - `pda`: Program address derivation
+
+pub mod builders;
+pub mod client;
+pub mod error;
+pub mod pda;
+
+pub use builders::{ShieldSolBuilder, TransferBuilder, UnshieldBuilder};
+pub use client::VeilClient;
+pub use error::{ClientError, ClientResult};
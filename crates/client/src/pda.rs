@@ -0,0 +1,142 @@
+//! Program address derivation
+//!
+//! Every account the program owns is a program address of the seeds in
+//! `veil_core::transaction`. Each function returns the address with its bump
+//! seed, as `Pubkey::find_program_address` does; `pool_accounts` and
+//! `nullifier_accounts` gather what the instruction builders take.
+
+use veil_core::rpc::find_program_address;
+use veil_core::transaction::{
+    NullifierAccounts, PoolAccounts, Pubkey, ARCHIVED_ROOT_SEED, ARCHIVE_SEED, NATIVE_MINT,
+    NULLIFIER_SEED, PENDING_WITHDRAWAL_SEED, POOL_SEED, ROOT_HISTORY_SEED, TREASURY_SEED,
+    VAULT_SEED, VK_SEED,
+};
+
+/// Pool of `mint` (`NATIVE_MINT` for SOL)
+pub fn pool_address(program_id: &Pubkey, mint: &Pubkey) -> Option<(Pubkey, u8)> {
+    find_program_address(&[POOL_SEED, mint], program_id)
+}
+
+/// SOL vault of `pool`
+pub fn vault_address(program_id: &Pubkey, pool: &Pubkey) -> Option<(Pubkey, u8)> {
+    find_program_address(&[VAULT_SEED, pool], program_id)
+}
+
+/// Verifying key of `pool`
+pub fn verifying_key_address(program_id: &Pubkey, pool: &Pubkey) -> Option<(Pubkey, u8)> {
+    find_program_address(&[VK_SEED, pool], program_id)
+}
+
+/// Root history of `pool`
+pub fn root_history_address(program_id: &Pubkey, pool: &Pubkey) -> Option<(Pubkey, u8)> {
+    find_program_address(&[ROOT_HISTORY_SEED, pool], program_id)
+}
+
+/// Treasury of `pool`
+pub fn treasury_address(program_id: &Pubkey, pool: &Pubkey) -> Option<(Pubkey, u8)> {
+    find_program_address(&[TREASURY_SEED, pool], program_id)
+}
+
+/// Marker created by spending `nullifier` from the tree of `tree_epoch`
+pub fn nullifier_marker_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tree_epoch: u32,
+    nullifier: &[u8; 32],
+) -> Option<(Pubkey, u8)> {
+    find_program_address(&[NULLIFIER_SEED, pool, &tree_epoch.to_le_bytes(), nullifier], program_id)
+}
+
+/// Archive bucket holding `nullifier` once its tree is rotated out, keyed by
+/// the nullifier's last byte
+pub fn nullifier_archive_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tree_epoch: u32,
+    nullifier: &[u8; 32],
+) -> Option<(Pubkey, u8)> {
+    let epoch = tree_epoch.to_le_bytes();
+    find_program_address(&[ARCHIVE_SEED, pool, &epoch, &nullifier[31..]], program_id)
+}
+
+/// Final root of the tree of `tree_epoch`, once rotated out
+pub fn archived_root_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tree_epoch: u32,
+) -> Option<(Pubkey, u8)> {
+    find_program_address(&[ARCHIVED_ROOT_SEED, pool, &tree_epoch.to_le_bytes()], program_id)
+}
+
+/// Pending withdrawal of an unshield spending `nullifier`
+pub fn pending_withdrawal_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tree_epoch: u32,
+    nullifier: &[u8; 32],
+) -> Option<(Pubkey, u8)> {
+    let epoch = tree_epoch.to_le_bytes();
+    find_program_address(&[PENDING_WITHDRAWAL_SEED, pool, &epoch, nullifier], program_id)
+}
+
+/// Addresses of the SOL pool of the program at `program_id`
+pub fn pool_accounts(program_id: Pubkey) -> Option<PoolAccounts> {
+    let (pool, _) = pool_address(&program_id, &NATIVE_MINT)?;
+    Some(PoolAccounts {
+        program_id,
+        pool,
+        vault: vault_address(&program_id, &pool)?.0,
+        verifying_key: verifying_key_address(&program_id, &pool)?.0,
+        root_history: root_history_address(&program_id, &pool)?.0,
+        treasury: treasury_address(&program_id, &pool)?.0,
+    })
+}
+
+/// Accounts spending `nullifier` from the tree of `tree_epoch` touches
+pub fn nullifier_accounts(
+    accounts: &PoolAccounts,
+    tree_epoch: u32,
+    nullifier: &[u8; 32],
+    root: [u8; 32],
+) -> Option<NullifierAccounts> {
+    let (program_id, pool) = (&accounts.program_id, &accounts.pool);
+    Some(NullifierAccounts {
+        tree_epoch,
+        root,
+        marker: nullifier_marker_address(program_id, pool, tree_epoch, nullifier)?.0,
+        archive: nullifier_archive_address(program_id, pool, tree_epoch, nullifier)?.0,
+        archived_root: archived_root_address(program_id, pool, tree_epoch)?.0,
+        pending_withdrawal: pending_withdrawal_address(program_id, pool, tree_epoch, nullifier)?.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey as ProgramPubkey;
+    use veil_program::cpi_builder;
+
+    use super::*;
+
+    #[test]
+    fn test_pool_accounts() {
+        let program_id = veil_program::ID.to_bytes();
+        let accounts = pool_accounts(program_id).unwrap();
+        let (pool, vault) = cpi_builder::sol_pool_addresses(&veil_program::ID);
+        assert_eq!((accounts.pool, accounts.vault), (pool.to_bytes(), vault.to_bytes()));
+        let root_history = cpi_builder::root_history_address(&veil_program::ID, &pool);
+        assert_eq!(accounts.root_history, root_history.to_bytes());
+
+        let (marker, bump) =
+            nullifier_marker_address(&program_id, &accounts.pool, 2, &[1; 32]).unwrap();
+        let expected = ProgramPubkey::find_program_address(
+            &[NULLIFIER_SEED, pool.as_ref(), &2u32.to_le_bytes(), &[1; 32]],
+            &veil_program::ID,
+        );
+        assert_eq!((marker, bump), (expected.0.to_bytes(), expected.1));
+
+        let spend = nullifier_accounts(&accounts, 2, &[1; 32], [9; 32]).unwrap();
+        assert_eq!((spend.marker, spend.root, spend.tree_epoch), (marker, [9; 32], 2));
+        let next_epoch = nullifier_accounts(&accounts, 3, &[1; 32], [9; 32]).unwrap();
+        assert_ne!(spend.archive, next_epoch.archive);
+    }
+}
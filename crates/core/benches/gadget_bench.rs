@@ -0,0 +1,84 @@
+//! Benchmarks for circuit gadget synthesis
+//!
+//! Compares a depth-20 Merkle path built with a fresh Poseidon gadget per
+//! hash against one gadget shared by the whole path, and times synthesizing
+//! the transfer circuit. Constraint counts are printed once per benchmark.
+//! Gadget constants are not variables, so sharing leaves the constraint
+//! count unchanged and only saves copying them, which is small next to
+//! synthesizing the S-boxes.
+
+use ark_bn254::Fr;
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use veil_core::crypto::{Note, PoseidonMerkleTree};
+use veil_core::proof::gadgets::merkle::MerklePathGadget;
+use veil_core::proof::gadgets::poseidon::{poseidon_hash2_gadget, PoseidonGadget};
+use veil_core::proof::TransferCircuit;
+
+fn path_fixture() -> (Fr, Vec<Fr>, Vec<bool>) {
+    let mut tree = PoseidonMerkleTree::new();
+    for i in 1..=5u64 {
+        tree.insert(Fr::from(i)).unwrap();
+    }
+    let path = tree.generate_proof(3).unwrap();
+    (Fr::from(4u64), path.siblings, path.indices)
+}
+
+/// Hash up the path as circuits did before sharing the gadget
+fn root_per_hash(cs: ConstraintSystemRef<Fr>, leaf: &FpVar<Fr>, path: &MerklePathGadget) -> FpVar<Fr> {
+    let mut current = leaf.clone();
+    for (sibling, is_right) in path.siblings.iter().zip(&path.indices) {
+        let left = is_right.select(sibling, &current).unwrap();
+        let right = is_right.select(&current, sibling).unwrap();
+        current = poseidon_hash2_gadget(cs.clone(), &left, &right).unwrap();
+    }
+    current
+}
+
+fn bench_merkle_path(c: &mut Criterion) {
+    let (leaf, siblings, indices) = path_fixture();
+    let synthesize = |shared: bool| {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let leaf_var = FpVar::new_witness(cs.clone(), || Ok(leaf)).unwrap();
+        let path = MerklePathGadget::new_witness(cs.clone(), siblings.len(), &siblings, &indices)
+            .unwrap();
+        let root = if shared {
+            let poseidon = PoseidonGadget::new(cs.clone()).unwrap();
+            path.compute_root_with(&poseidon, cs.clone(), &leaf_var).unwrap()
+        } else {
+            root_per_hash(cs.clone(), &leaf_var, &path)
+        };
+        let _ = black_box(root);
+        cs.num_constraints()
+    };
+
+    println!(
+        "merkle_path constraints: per hash {}, shared {}",
+        synthesize(false),
+        synthesize(true)
+    );
+    let mut group = c.benchmark_group("merkle_path");
+    group.bench_function("shared_gadget", |b| b.iter(|| synthesize(true)));
+    group.bench_function("gadget_per_hash", |b| b.iter(|| synthesize(false)));
+    group.finish();
+}
+
+fn bench_transfer_circuit(c: &mut Criterion) {
+    let mut tree = PoseidonMerkleTree::new();
+    let mut note = Note::new([7u8; 32], 1_000, Fr::from(0u64), Fr::from(11u64));
+    note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+    let path = tree.generate_proof(0).unwrap();
+    let circuit = TransferCircuit::spend(&note, &path, tree.root(), Fr::from(13u64), 10).unwrap();
+    let synthesize = || {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        cs.num_constraints()
+    };
+
+    println!("transfer_circuit constraints: {}", synthesize());
+    c.bench_function("transfer_circuit_synthesis", |b| b.iter(synthesize));
+}
+
+criterion_group!(benches, bench_merkle_path, bench_transfer_circuit);
+criterion_main!(benches);
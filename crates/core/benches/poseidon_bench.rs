@@ -0,0 +1,34 @@
+//! Benchmarks for batch Poseidon hashing
+//!
+//! Compares hashing a batch of pairs through the thread-local
+//! `poseidon_hash2` against `Poseidon::hash2_many`, which shares one
+//! hasher's parameters across the batch and, with the `parallel` feature,
+//! spreads it over rayon's thread pool.
+
+use ark_bn254::Fr;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use veil_core::crypto::poseidon::{poseidon_hash2, Poseidon};
+
+fn bench_hash2_batch(c: &mut Criterion) {
+    let poseidon = Poseidon::new();
+    let mut group = c.benchmark_group("poseidon_hash2_batch");
+    for size in [1_000u64, 10_000] {
+        let pairs: Vec<(Fr, Fr)> = (0..size).map(|i| (Fr::from(i), Fr::from(!i))).collect();
+        group.throughput(Throughput::Elements(size));
+        group.bench_with_input(BenchmarkId::new("thread_local", size), &pairs, |b, pairs| {
+            b.iter(|| {
+                pairs
+                    .iter()
+                    .map(|(l, r)| poseidon_hash2(l, r))
+                    .collect::<Vec<_>>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("hash2_many", size), &pairs, |b, pairs| {
+            b.iter(|| poseidon.hash2_many(black_box(pairs)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hash2_batch);
+criterion_main!(benches);
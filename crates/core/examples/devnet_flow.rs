@@ -0,0 +1,187 @@
+//! Shield -> transfer -> unshield against a live cluster
+//!
+//! Assembles, signs and submits real transactions with the instruction
+//! builders in `veil_core::transaction`. The fee payer also acts as the
+//! relayer and as the unshield recipient.
+//!
+//! ```text
+//! cargo run --release -p veil-core --features allow-insecure,rpc --example devnet_flow
+//! ```
+//!
+//! Configuration (environment):
+//! - `VEIL_RPC_URL`: RPC endpoint (default: public devnet)
+//! - `VEIL_PROGRAM_ID`: deployed program (default: the program's declared id)
+//! - `VEIL_KEYPAIR`: fee payer keypair file (default: `~/.config/solana/id.json`)
+//! - `VEIL_AMOUNT`: lamports to shield (default: 0.01 SOL)
+//!
+//! The example tracks a local commitment tree holding only its own notes, so
+//! its proofs are against that tree's root. Against a pool with other
+//! deposits, sync the tree from program events with
+//! `veil_core::indexer::CommitmentIndexer` first.
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use rand::rngs::OsRng;
+
+use veil_core::crypto::{Note, PoseidonMerkleTree};
+use veil_core::proof::{field_to_bytes_be, TransferProofSystem};
+use veil_core::rpc::{
+    decode_pubkey, find_program_address, read_keypair_file, sign_transaction, Keypair, RpcClient,
+    DEVNET_URL,
+};
+use veil_core::transaction::{
+    Instruction, NullifierAccounts, PoolAccounts, Pubkey, TransactionAssembler,
+    ARCHIVED_ROOT_SEED, ARCHIVE_SEED, NATIVE_MINT, NULLIFIER_SEED, PENDING_WITHDRAWAL_SEED,
+    POOL_SEED, ROOT_HISTORY_SEED, TREASURY_SEED, VAULT_SEED, VK_SEED,
+};
+
+/// The program's `declare_id!`
+const DEFAULT_PROGRAM_ID: &str = "Vei1111111111111111111111111111111111111111";
+
+/// 0.01 SOL
+const DEFAULT_AMOUNT: u64 = 10_000_000;
+
+/// Compute units requested for proof-carrying instructions
+const COMPUTE_UNIT_LIMIT: u32 = 400_000;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn main() -> Result<()> {
+    let client = RpcClient::new(env::var("VEIL_RPC_URL").unwrap_or_else(|_| DEVNET_URL.into()));
+    let program_id = decode_pubkey(
+        &env::var("VEIL_PROGRAM_ID").unwrap_or_else(|_| DEFAULT_PROGRAM_ID.into()),
+    )?;
+    let keypair_path = env::var("VEIL_KEYPAIR").unwrap_or_else(|_| {
+        format!("{}/.config/solana/id.json", env::var("HOME").unwrap_or_default())
+    });
+    let payer = read_keypair_file(&keypair_path)?;
+    let payer_key = payer.public.to_bytes();
+    let amount = match env::var("VEIL_AMOUNT") {
+        Ok(v) => v.parse()?,
+        Err(_) => DEFAULT_AMOUNT,
+    };
+
+    let (pool, _) =
+        find_program_address(&[POOL_SEED, &NATIVE_MINT], &program_id).ok_or("no pool address")?;
+    let (vault, _) = find_program_address(&[VAULT_SEED, &pool], &program_id).ok_or("no vault address")?;
+    let (verifying_key, _) =
+        find_program_address(&[VK_SEED, &pool], &program_id).ok_or("no verifying key address")?;
+    let (root_history, _) = find_program_address(&[ROOT_HISTORY_SEED, &pool], &program_id)
+        .ok_or("no root history address")?;
+    let (treasury, _) =
+        find_program_address(&[TREASURY_SEED, &pool], &program_id).ok_or("no treasury address")?;
+    let accounts = PoolAccounts {
+        program_id,
+        pool,
+        vault,
+        verifying_key,
+        root_history,
+        treasury,
+    };
+
+    println!("rpc: {}", client.url());
+    println!("payer: {} ({} lamports)", encode(&payer_key), client.get_balance(&payer_key)?);
+    println!("pool: {}", encode(&pool));
+    println!("vault: {}", encode(&vault));
+    println!("verifying key: {}", encode(&verifying_key));
+
+    let proof_system = TransferProofSystem::setup()?;
+    let mut tree = PoseidonMerkleTree::new();
+
+    println!("\n== Shield ==");
+    let mut note = Note::new_random(amount, Fr::from(0u64), Fr::rand(&mut OsRng));
+    let commitment = field_to_bytes_be(&note.commitment());
+    println!("commitment: {}", hex::encode(commitment));
+    submit(&client, &payer, accounts.shield_sol(payer_key, &commitment, amount, &[]))?;
+    note.set_leaf_index(tree.insert(note.commitment())?);
+
+    println!("\n== Transfer ==");
+    let output_blinding = Fr::rand(&mut OsRng);
+    let path = tree.generate_proof(note.leaf_index.unwrap_or_default())?;
+    let spend = proof_system.prove_spend(&note, &path, tree.root(), output_blinding, 0)?;
+    let nullifier = spend.nullifier_bytes();
+    println!("nullifier: {}", hex::encode(nullifier));
+    println!("new commitment: {}", hex::encode(spend.new_commitment_bytes()));
+    submit(
+        &client,
+        &payer,
+        accounts.transfer(
+            payer_key,
+            nullifier_accounts(&accounts, &nullifier, tree.root_bytes())?,
+            &nullifier,
+            &spend.new_commitment_bytes(),
+            0,
+            &spend.solana_proof.to_bytes(),
+            &[],
+        ),
+    )?;
+
+    let mut output = Note::new(note.secret, amount, note.asset_id, output_blinding);
+    output.set_leaf_index(tree.insert(output.commitment())?);
+
+    println!("\n== Unshield ==");
+    let path = tree.generate_proof(output.leaf_index.unwrap_or_default())?;
+    let spend = proof_system.prove_spend(&output, &path, tree.root(), Fr::rand(&mut OsRng), 0)?;
+    let nullifier = spend.nullifier_bytes();
+    println!("nullifier: {}", hex::encode(nullifier));
+    submit(
+        &client,
+        &payer,
+        accounts.unshield_sol(
+            payer_key,
+            nullifier_accounts(&accounts, &nullifier, tree.root_bytes())?,
+            payer_key,
+            &nullifier,
+            amount,
+            &spend.solana_proof.to_bytes(),
+            &[],
+        ),
+    )?;
+
+    // Give the last transaction a moment before reporting the balance
+    thread::sleep(Duration::from_secs(2));
+    println!("\npayer balance: {} lamports", client.get_balance(&payer_key)?);
+    Ok(())
+}
+
+/// Assemble, sign with `payer` and send a single-instruction transaction
+fn submit(client: &RpcClient, payer: &Keypair, instruction: Instruction) -> Result<()> {
+    let mut asm = TransactionAssembler::new(payer.public.to_bytes());
+    asm.set_compute_unit_limit(COMPUTE_UNIT_LIMIT).add_instruction(instruction);
+    let tx = asm.assemble(client.get_latest_blockhash()?)?;
+    let wire = sign_transaction(&tx, &[payer])?;
+    println!("sending {} bytes", wire.len());
+    println!("signature: {}", client.send_transaction(&wire)?);
+    Ok(())
+}
+
+/// Nullifier PDAs for `nullifier`, in the pool's first tree, proven against `root`
+fn nullifier_accounts(
+    accounts: &PoolAccounts,
+    nullifier: &[u8; 32],
+    root: [u8; 32],
+) -> Result<NullifierAccounts> {
+    let tree_epoch = 0u32;
+    let epoch = tree_epoch.to_le_bytes();
+    let pda = |seeds: &[&[u8]]| {
+        find_program_address(seeds, &accounts.program_id)
+            .map(|(address, _)| address)
+            .ok_or("no nullifier account address")
+    };
+    Ok(NullifierAccounts {
+        tree_epoch,
+        root,
+        marker: pda(&[NULLIFIER_SEED, &accounts.pool, &epoch, nullifier])?,
+        archive: pda(&[ARCHIVE_SEED, &accounts.pool, &epoch, &nullifier[31..]])?,
+        archived_root: pda(&[ARCHIVED_ROOT_SEED, &accounts.pool, &epoch])?,
+        pending_withdrawal: pda(&[PENDING_WITHDRAWAL_SEED, &accounts.pool, &epoch, nullifier])?,
+    })
+}
+
+fn encode(key: &Pubkey) -> String {
+    bs58::encode(key).into_string()
+}
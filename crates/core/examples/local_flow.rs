@@ -0,0 +1,169 @@
+//! Shield -> transfer -> unshield, entirely in-process
+//!
+//! Plays the program's part with a local `PoseidonMerkleTree` and prints
+//! every artifact a client derives along the way: notes, commitments,
+//! nullifiers, roots, proofs in both arkworks and groth16-solana encodings,
+//! and the instruction payloads that would be sent on chain.
+//!
+//! ```text
+//! cargo run --release -p veil-core --features allow-insecure --example local_flow
+//! ```
+//!
+//! Debug builds skip the feature but Groth16 setup and proving are much slower.
+
+use std::time::Instant;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField, UniformRand};
+use rand::rngs::OsRng;
+
+use veil_core::crypto::{
+    decrypt_note, encrypt_note, EncryptionKeypair, Note, NoteData, PoseidonMerkleTree,
+};
+use veil_core::proof::{field_to_bytes_be, SpendProof, TransferProofSystem};
+use veil_core::transaction::{
+    Instruction, NullifierAccounts, PoolAccounts, Pubkey, TransactionAssembler,
+};
+
+/// 1 SOL
+const AMOUNT: u64 = 1_000_000_000;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Placeholder addresses; only used to size the instructions
+    let accounts = PoolAccounts {
+        program_id: [1u8; 32],
+        pool: [2u8; 32],
+        vault: [3u8; 32],
+        verifying_key: [8u8; 32],
+        root_history: [13u8; 32],
+        treasury: [12u8; 32],
+    };
+    let user: Pubkey = [4u8; 32];
+    let relayer: Pubkey = [5u8; 32];
+
+    println!("== Setup ==");
+    let started = Instant::now();
+    let proof_system = TransferProofSystem::setup()?;
+    let vk = proof_system.export_solana_vk()?;
+    println!("groth16 setup: {:?}", started.elapsed());
+    println!("vk alpha_g1: {}", hex::encode(vk.alpha_g1));
+    println!("vk ic points: {}", vk.ic.len());
+
+    // The pool's commitment tree, as the program would maintain it
+    let mut tree = PoseidonMerkleTree::new();
+
+    println!("\n== Shield ==");
+    let mut note = Note::new_random(AMOUNT, Fr::from(0u64), Fr::rand(&mut OsRng));
+    let commitment = field_to_bytes_be(&note.commitment());
+    println!("note secret: {}", hex::encode(note.secret));
+    println!("spending key: {}", hex::encode(note.spending_key().to_bytes()));
+    println!("commitment: {}", hex::encode(commitment));
+
+    let shield = accounts.shield_sol(user, &commitment, AMOUNT, &[]);
+    println!("shield_sol data: {}", hex::encode(&shield.data));
+    report_size(user, shield)?;
+
+    note.set_leaf_index(tree.insert(note.commitment())?);
+    println!("leaf index: {}", note.leaf_index.unwrap_or_default());
+    println!("root: {}", hex::encode(tree.root_bytes()));
+
+    println!("\n== Transfer ==");
+    let (output_blinding, transfer) = spend(&proof_system, &tree, &note)?;
+    println!("new commitment: {}", hex::encode(transfer.new_commitment_bytes()));
+
+    // The recipient (here: the same owner) learns the output note from the
+    // encrypted note published next to the commitment
+    let recipient_keys = EncryptionKeypair::generate();
+    let mut blinding_bytes = [0u8; 32];
+    blinding_bytes.copy_from_slice(&output_blinding.into_bigint().to_bytes_le());
+    let encrypted = encrypt_note(
+        &NoteData::new(AMOUNT, blinding_bytes, 0),
+        &recipient_keys.public_key_bytes(),
+    )?;
+    println!("encrypted note: {}", hex::encode(encrypted.to_bytes()));
+
+    let ix = accounts.transfer(
+        relayer,
+        NullifierAccounts {
+            tree_epoch: 0,
+            root: tree.root_bytes(),
+            marker: [6u8; 32],
+            archive: [9u8; 32],
+            archived_root: [10u8; 32],
+            pending_withdrawal: [11u8; 32],
+        },
+        &transfer.nullifier_bytes(),
+        &transfer.new_commitment_bytes(),
+        0,
+        &transfer.solana_proof.to_bytes(),
+        &encrypted.to_bytes(),
+    );
+    report_size(relayer, ix)?;
+
+    let received = decrypt_note(&encrypted.into(), &recipient_keys.private_key_bytes())?;
+    println!("decrypted amount: {}", received.amount);
+
+    let mut output = Note::new(note.secret, AMOUNT, note.asset_id, output_blinding);
+    output.set_leaf_index(tree.insert(output.commitment())?);
+    println!("output leaf index: {}", output.leaf_index.unwrap_or_default());
+    println!("root: {}", hex::encode(tree.root_bytes()));
+
+    println!("\n== Unshield ==");
+    let (_, unshield) = spend(&proof_system, &tree, &output)?;
+    let ix = accounts.unshield_sol(
+        relayer,
+        NullifierAccounts {
+            tree_epoch: 0,
+            root: tree.root_bytes(),
+            marker: [7u8; 32],
+            archive: [9u8; 32],
+            archived_root: [10u8; 32],
+            pending_withdrawal: [11u8; 32],
+        },
+        user,
+        &unshield.nullifier_bytes(),
+        AMOUNT,
+        &unshield.solana_proof.to_bytes(),
+        &[],
+    );
+    println!("unshield_sol data: {} bytes", ix.data.len());
+    report_size(relayer, ix)?;
+
+    Ok(())
+}
+
+/// Prove and verify a spend of `note` into a re-blinded output note
+fn spend(
+    proof_system: &TransferProofSystem,
+    tree: &PoseidonMerkleTree,
+    note: &Note,
+) -> Result<(Fr, SpendProof), Box<dyn std::error::Error>> {
+    let leaf_index = note.leaf_index.ok_or("note has no leaf index")?;
+    let path = tree.generate_proof(leaf_index)?;
+    let output_blinding = Fr::rand(&mut OsRng);
+
+    let started = Instant::now();
+    let spend = proof_system.prove_spend(note, &path, tree.root(), output_blinding, 0)?;
+    println!("proved in {:?}", started.elapsed());
+    println!("nullifier: {}", hex::encode(spend.nullifier_bytes()));
+    println!("proof (arkworks, compressed): {}", hex::encode(spend.proof.as_bytes()));
+    println!("proof (groth16-solana): {}", hex::encode(spend.solana_proof.to_bytes()));
+
+    let valid = proof_system.verify(spend.proof.as_bytes(), &spend.public_inputs)?;
+    println!("verified: {}", valid);
+    if !valid {
+        return Err("proof did not verify".into());
+    }
+    Ok((output_blinding, spend))
+}
+
+/// Print the signed size of a transaction carrying `instruction`
+fn report_size(
+    payer: Pubkey,
+    instruction: Instruction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut asm = TransactionAssembler::new(payer);
+    asm.set_compute_unit_limit(400_000).add_instruction(instruction);
+    println!("transaction size: {} bytes", asm.assemble([0u8; 32])?.serialized_size());
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Write the program's `vk` module from verifying key files
+//!
+//! ```text
+//! cargo run -p veil-core --bin veil-keygen -- \
+//!     transfer.vk unshield.vk crates/program/src/vk.rs
+//! ```
+//!
+//! Keys are arkworks-compressed (as `KeyStore::save` writes them) or snarkjs
+//! `verification_key.json`. Without an output path the module goes to
+//! stdout; an existing output is only rewritten when it changes, so cargo
+//! does not rebuild the program needlessly.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use ark_bn254::Bn254;
+use ark_groth16::VerifyingKey;
+use ark_serialize::CanonicalDeserialize;
+
+use veil_core::proof::codegen::program_vk_module;
+use veil_core::proof::zkey::read_verification_key_json;
+use veil_core::proof::SolanaVerifyingKey;
+
+const USAGE: &str = "usage: veil-keygen <transfer vk> <unshield vk> [output .rs]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (transfer, unshield, output) = match args.as_slice() {
+        [transfer, unshield] => (transfer, unshield, None),
+        [transfer, unshield, output] => (transfer, unshield, Some(output)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(transfer, unshield, output.map(Path::new)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("veil-keygen: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(
+    transfer: &str,
+    unshield: &str,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let code = program_vk_module(&load_key(transfer)?, &load_key(unshield)?)?;
+    let Some(output) = output else {
+        print!("{}", code);
+        return Ok(());
+    };
+    if std::fs::read_to_string(output).is_ok_and(|existing| existing == code) {
+        eprintln!("{} is up to date", output.display());
+    } else {
+        std::fs::write(output, &code)?;
+        eprintln!("wrote {}", output.display());
+    }
+    Ok(())
+}
+
+fn load_key(path: &str) -> Result<SolanaVerifyingKey, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let key = if path.ends_with(".json") {
+        read_verification_key_json(std::str::from_utf8(&bytes)?)?
+    } else {
+        VerifyingKey::<Bn254>::deserialize_compressed(bytes.as_slice())
+            .map_err(|e| format!("{}: {}", path, e))?
+    };
+    Ok(SolanaVerifyingKey::from_arkworks(&key)?)
+}
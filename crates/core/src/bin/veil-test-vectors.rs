@@ -0,0 +1,48 @@
+//! Write the cross-implementation test vectors fixture
+//!
+//! ```text
+//! cargo run -p veil-core --bin veil-test-vectors -- \
+//!     crates/core/fixtures/test_vectors.json
+//! ```
+//!
+//! Without an output path the vectors go to stdout; an existing output is
+//! only rewritten when it changes.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use veil_core::crypto::test_vectors::TestVectors;
+
+const USAGE: &str = "usage: veil-test-vectors [output .json]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let output = match args.as_slice() {
+        [] => None,
+        [output] => Some(Path::new(output)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let json = TestVectors::generate().to_json();
+    let Some(output) = output else {
+        print!("{}", json);
+        return ExitCode::SUCCESS;
+    };
+    if std::fs::read_to_string(output).is_ok_and(|existing| existing == json) {
+        eprintln!("{} is up to date", output.display());
+        return ExitCode::SUCCESS;
+    }
+    match std::fs::write(output, &json) {
+        Ok(()) => {
+            eprintln!("wrote {}", output.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("veil-test-vectors: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Diversified addresses
+//!
+//! A single encryption public key links every note sent to it. Instead, a
+//! recipient can hand out any number of diversified addresses (d, pk_d)
+//! derived from one incoming viewing key, as in Sapling:
+//! 1. g_d = DiversifyHash(d), a point with unknown discrete log
+//! 2. pk_d = ivk * g_d
+//!
+//! A sender picks an ephemeral secret esk, publishes epk = esk * g_d and
+//! derives the shared secret esk * pk_d. The recipient computes the same
+//! secret as ivk * epk for every diversifier, so trial decryption with the
+//! viewing key's `EncryptionKeypair` finds notes sent to any of them.
+//! Without the viewing key, two addresses of the same recipient cannot be
+//! linked.
+
+use ark_bn254::{Fq, G1Affine, G1Projective as G1};
+use ark_ec::CurveGroup;
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use sha2::{Digest, Sha256};
+
+use super::encryption::EncryptionError;
+use super::nullifier::ViewingKey;
+
+/// Domain separator for hashing a diversifier to a point
+const DIVERSIFY_DOMAIN: &[u8] = b"NYX_DIVERSIFY_HASH";
+/// Domain separator for diversifier derivation
+const DIVERSIFIER_DOMAIN: &[u8] = b"NYX_DIVERSIFIER";
+
+/// Size of a diversifier
+pub const DIVERSIFIER_SIZE: usize = 11;
+
+/// Size of a serialized diversified address
+pub const DIVERSIFIED_ADDRESS_SIZE: usize = DIVERSIFIER_SIZE + 32;
+
+/// Diversifier selecting one of a recipient's addresses
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Diversifier(pub [u8; DIVERSIFIER_SIZE]);
+
+impl Diversifier {
+    /// Derive the `index`-th diversifier of a viewing key
+    ///
+    /// Diversifiers only look random to those without the viewing key.
+    pub fn derive(viewing_key: &ViewingKey, index: u64) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(DIVERSIFIER_DOMAIN);
+        hasher.update(viewing_key.to_bytes());
+        hasher.update(index.to_le_bytes());
+        let hash = hasher.finalize();
+
+        let mut d = [0u8; DIVERSIFIER_SIZE];
+        d.copy_from_slice(&hash[..DIVERSIFIER_SIZE]);
+        Self(d)
+    }
+
+    /// The diversified base point g_d
+    ///
+    /// Try-and-increment over SHA-256: the first counter whose hash is the
+    /// x-coordinate of a curve point gives the base. BN254 G1 has cofactor 1,
+    /// so every such point is in the group.
+    pub fn base(&self) -> G1Affine {
+        (0u32..)
+            .find_map(|counter| {
+                let mut hasher = Sha256::new();
+                hasher.update(DIVERSIFY_DOMAIN);
+                hasher.update(self.0);
+                hasher.update(counter.to_le_bytes());
+                let x = Fq::from_be_bytes_mod_order(&hasher.finalize());
+                G1Affine::get_point_from_x_unchecked(x, false)
+            })
+            .expect("about half of all x-coordinates are on the curve")
+    }
+}
+
+/// A diversified address: what a recipient hands to a sender
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiversifiedAddress {
+    /// Diversifier
+    pub diversifier: Diversifier,
+    /// Transmission key pk_d = ivk * g_d (compressed)
+    pub pk_d: [u8; 32],
+}
+
+impl DiversifiedAddress {
+    /// Derive the `index`-th address of a viewing key
+    pub fn derive(viewing_key: &ViewingKey, index: u64) -> Self {
+        Self::from_diversifier(viewing_key, Diversifier::derive(viewing_key, index))
+    }
+
+    /// The address of a viewing key for a given diversifier
+    pub fn from_diversifier(viewing_key: &ViewingKey, diversifier: Diversifier) -> Self {
+        let pk_d = (diversifier.base() * viewing_key.as_field()).into_affine();
+        let mut bytes = Vec::new();
+        pk_d.serialize_compressed(&mut bytes).expect("serialization failed");
+
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&bytes);
+        Self { diversifier, pk_d: result }
+    }
+
+    /// The transmission key as a point
+    pub(crate) fn transmission_key(&self) -> Result<G1, EncryptionError> {
+        G1Affine::deserialize_compressed(self.pk_d.as_slice())
+            .map(G1::from)
+            .map_err(|_| EncryptionError::InvalidPublicKey)
+    }
+
+    /// Serialize to bytes: diversifier || pk_d
+    pub fn to_bytes(&self) -> [u8; DIVERSIFIED_ADDRESS_SIZE] {
+        let mut bytes = [0u8; DIVERSIFIED_ADDRESS_SIZE];
+        bytes[..DIVERSIFIER_SIZE].copy_from_slice(&self.diversifier.0);
+        bytes[DIVERSIFIER_SIZE..].copy_from_slice(&self.pk_d);
+        bytes
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() != DIVERSIFIED_ADDRESS_SIZE {
+            return Err(EncryptionError::InvalidCiphertextLength);
+        }
+        let address = Self {
+            diversifier: Diversifier(bytes[..DIVERSIFIER_SIZE].try_into().unwrap()),
+            pk_d: bytes[DIVERSIFIER_SIZE..].try_into().unwrap(),
+        };
+        // Reject transmission keys that are not curve points
+        address.transmission_key().map(|_| address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diversified_addresses_unlinkable() {
+        let ivk = ViewingKey::from_secret(&[1u8; 32]);
+        let a = DiversifiedAddress::derive(&ivk, 0);
+        let b = DiversifiedAddress::derive(&ivk, 1);
+
+        assert_eq!(a, DiversifiedAddress::derive(&ivk, 0));
+        assert_ne!(a.diversifier, b.diversifier);
+        assert_ne!(a.pk_d, b.pk_d);
+        assert_ne!(a.diversifier.base(), b.diversifier.base());
+        assert!(a.diversifier.base().is_on_curve());
+
+        let restored = DiversifiedAddress::from_bytes(&a.to_bytes()).unwrap();
+        assert_eq!(restored, a);
+        assert!(DiversifiedAddress::from_bytes(&a.to_bytes()[1..]).is_err());
+    }
+}
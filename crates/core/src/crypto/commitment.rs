@@ -12,6 +12,7 @@ use ark_ff::{BigInteger, PrimeField, UniformRand};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use rand::rngs::OsRng;
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
 #[derive(Error, Debug)]
 pub enum CommitmentError {
@@ -117,6 +118,7 @@ impl Commitment {
 
         // Map hash to scalar and multiply by generator
         // This is a simple construction; production should use proper hash-to-curve
+        // (tracked as `pedersen-h` in `security::KNOWN_WEAK`)
         let scalar = Fr::from_le_bytes_mod_order(hash.as_bytes());
         G1::generator() * scalar
     }
@@ -149,10 +151,10 @@ impl Commitment {
         result
     }
 
-    /// Serialize blinding factor to 32 bytes
-    pub fn blinding_to_bytes(&self) -> [u8; 32] {
-        let bytes = self.blinding_factor.into_bigint().to_bytes_le();
-        let mut result = [0u8; 32];
+    /// Serialize blinding factor to 32 bytes, wiped when dropped
+    pub fn blinding_to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        let bytes = Zeroizing::new(self.blinding_factor.into_bigint().to_bytes_le());
+        let mut result = Zeroizing::new([0u8; 32]);
         result.copy_from_slice(&bytes[..32]);
         result
     }
@@ -182,6 +184,20 @@ impl Commitment {
     }
 }
 
+// As for the keys in `nullifier`, zeroize below 1.4 has no `ZeroizeOnDrop`
+impl Zeroize for Commitment {
+    fn zeroize(&mut self) {
+        self.amount.zeroize();
+        self.blinding_factor.zeroize();
+    }
+}
+
+impl Drop for Commitment {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl CommitmentPoint {
     /// Serialize to compressed bytes
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -280,6 +296,15 @@ mod tests {
         assert!(point.verify(amount, &commitment.blinding_factor));
     }
 
+    #[test]
+    fn test_zeroize() {
+        let mut commitment = Commitment::with_blinding(1000, Fr::from(7u64));
+        commitment.zeroize();
+        assert_eq!(commitment.amount, 0);
+        assert_eq!(commitment.blinding_factor, Fr::from(0u64));
+        assert_eq!(*commitment.blinding_to_bytes(), [0u8; 32]);
+    }
+
     #[test]
     fn test_generators_consistency() {
         let (g, h) = Commitment::generators();
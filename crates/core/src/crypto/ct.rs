@@ -0,0 +1,36 @@
+//! Constant-time comparisons
+//!
+//! `==` on byte arrays stops at the first differing byte, so its running time
+//! tells an attacker how much of a guess was right. MACs, keys, nullifiers
+//! and pinned hashes are compared with these helpers instead. Lengths are
+//! not secret: inputs of different lengths compare unequal immediately.
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use subtle::ConstantTimeEq;
+
+/// Whether two byte strings are equal, in time independent of their contents
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Whether two field elements are equal, in time independent of their values
+pub fn ct_eq_field(a: &Fr, b: &Fr) -> bool {
+    a.into_bigint().0[..].ct_eq(&b.into_bigint().0[..]).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ct_eq() {
+        assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2]));
+        assert!(ct_eq(&[], &[]));
+
+        assert!(ct_eq_field(&Fr::from(7u64), &Fr::from(7u64)));
+        assert!(!ct_eq_field(&Fr::from(7u64), &Fr::from(8u64)));
+    }
+}
@@ -12,28 +12,55 @@
 //! 1. Sender generates ephemeral keypair (r, R = r*G)
 //! 2. Shared secret = ECDH(r, recipient_pubkey) = r * recipient_pubkey
 //! 3. Derive symmetric key from shared secret using HKDF
-//! 4. Encrypt note data using ChaCha20-Poly1305
-//! 5. Publish (R, ciphertext) alongside the commitment
+//! 4. Encrypt note data using ChaCha20-Poly1305 under a random nonce, with
+//!    R as associated data
+//! 5. Publish (R, nonce, ciphertext) alongside the commitment
 //!
 //! Decryption:
 //! 1. Recipient computes shared secret = ECDH(private_key, R)
 //! 2. Derive symmetric key from shared secret
 //! 3. Decrypt ciphertext using ChaCha20-Poly1305
+//!
+//! Diversified addresses (see `crypto::address`) replace G with the
+//! address's base g_d in step 1; decryption is unchanged.
+//!
+//! Outgoing copies:
+//! `encrypt_note_with_ovk` also seals the recipient key and ephemeral secret
+//! under the sender's outgoing viewing key, as in Sapling, so
+//! `decrypt_outgoing` can later recover what was sent and to whom.
+//!
+//! Versioning:
+//! Serialized notes start with a version byte, and `decrypt_note` takes a
+//! `NoteCiphertext` and dispatches on it, so a later format can be added
+//! without orphaning notes already on chain:
+//! - V1: the SHA-256 stream format from before ChaCha20-Poly1305 (see
+//!   `legacy`); notes from before the version byte are 96 unprefixed bytes
+//! - V2: the ChaCha20-Poly1305 format above (`EncryptedNote`)
 
 use ark_bn254::Fr;
 use ark_ec::{CurveGroup, Group};
 use ark_ff::PrimeField;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
 use rand::rngs::OsRng;
+use rand::RngCore;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::address::DiversifiedAddress;
+pub use super::nullifier::{OutgoingViewingKey, ViewingKey};
 
 /// The curve used for encryption (same as commitment curve)
 type G1 = ark_bn254::G1Projective;
 type G1Affine = ark_bn254::G1Affine;
 
 /// Domain separator for key derivation
-const ENCRYPTION_DOMAIN: &[u8] = b"NYX_NOTE_ENCRYPTION_V1";
+const ENCRYPTION_DOMAIN: &[u8] = b"NYX_NOTE_ENCRYPTION_V2";
+
+/// Domain separator for outgoing key derivation
+const OUTGOING_DOMAIN: &[u8] = b"NYX_NOTE_OUTGOING_V1";
 
 /// Size of encrypted note data (before padding)
 pub const NOTE_DATA_SIZE: usize = 48; // amount(8) + blinding(32) + asset_id(8)
@@ -41,11 +68,26 @@ pub const NOTE_DATA_SIZE: usize = 48; // amount(8) + blinding(32) + asset_id(8)
 /// Size of the encrypted note ciphertext
 pub const CIPHERTEXT_SIZE: usize = NOTE_DATA_SIZE + 16; // + auth tag
 
+/// Size of the ChaCha20-Poly1305 nonce
+pub const NONCE_SIZE: usize = 12;
+
 /// Size of the ephemeral public key
 pub const EPHEMERAL_KEY_SIZE: usize = 32;
 
-/// Total size of an encrypted note
-pub const ENCRYPTED_NOTE_SIZE: usize = EPHEMERAL_KEY_SIZE + CIPHERTEXT_SIZE;
+/// Version byte of the legacy SHA-256 stream format
+pub const NOTE_VERSION_V1: u8 = 1;
+
+/// Version byte of the ChaCha20-Poly1305 format
+pub const NOTE_VERSION_V2: u8 = 2;
+
+/// Total size of an encrypted note, including its version byte
+pub const ENCRYPTED_NOTE_SIZE: usize = 1 + EPHEMERAL_KEY_SIZE + NONCE_SIZE + CIPHERTEXT_SIZE;
+
+/// Size of the outgoing ciphertext
+pub const OUTGOING_CIPHERTEXT_SIZE: usize = 64 + 16; // recipient(32) + esk(32) + auth tag
+
+/// Total size of an outgoing note
+pub const OUTGOING_NOTE_SIZE: usize = NONCE_SIZE + OUTGOING_CIPHERTEXT_SIZE;
 
 /// Errors for encryption operations
 #[derive(Error, Debug)]
@@ -58,6 +100,8 @@ pub enum EncryptionError {
     DecryptionFailed,
     #[error("Invalid ciphertext length")]
     InvalidCiphertextLength,
+    #[error("Unsupported note version: {0}")]
+    UnsupportedVersion(u8),
     #[error("Serialization error: {0}")]
     SerializationError(String),
 }
@@ -79,9 +123,9 @@ impl NoteData {
         Self { amount, blinding, asset_id }
     }
 
-    /// Serialize to bytes
-    pub fn to_bytes(&self) -> [u8; NOTE_DATA_SIZE] {
-        let mut bytes = [0u8; NOTE_DATA_SIZE];
+    /// Serialize to bytes, wiped when dropped
+    pub fn to_bytes(&self) -> Zeroizing<[u8; NOTE_DATA_SIZE]> {
+        let mut bytes = Zeroizing::new([0u8; NOTE_DATA_SIZE]);
         bytes[0..8].copy_from_slice(&self.amount.to_le_bytes());
         bytes[8..40].copy_from_slice(&self.blinding);
         bytes[40..48].copy_from_slice(&self.asset_id.to_le_bytes());
@@ -103,37 +147,153 @@ impl NoteData {
     }
 }
 
+impl Zeroize for NoteData {
+    fn zeroize(&mut self) {
+        self.amount.zeroize();
+        self.blinding.zeroize();
+        self.asset_id.zeroize();
+    }
+}
+
+impl Drop for NoteData {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Encrypted note structure
 #[derive(Clone, Debug)]
 pub struct EncryptedNote {
     /// Ephemeral public key (R = r*G)
     pub ephemeral_key: [u8; EPHEMERAL_KEY_SIZE],
+    /// ChaCha20-Poly1305 nonce
+    pub nonce: [u8; NONCE_SIZE],
     /// Encrypted data + auth tag
     pub ciphertext: [u8; CIPHERTEXT_SIZE],
 }
 
 impl EncryptedNote {
-    /// Serialize to bytes
+    /// Serialize to bytes: version || ephemeral key || nonce || ciphertext
     pub fn to_bytes(&self) -> [u8; ENCRYPTED_NOTE_SIZE] {
         let mut bytes = [0u8; ENCRYPTED_NOTE_SIZE];
-        bytes[0..EPHEMERAL_KEY_SIZE].copy_from_slice(&self.ephemeral_key);
-        bytes[EPHEMERAL_KEY_SIZE..].copy_from_slice(&self.ciphertext);
+        bytes[0] = NOTE_VERSION_V2;
+        let (ephemeral_key, rest) = bytes[1..].split_at_mut(EPHEMERAL_KEY_SIZE);
+        let (nonce, ciphertext) = rest.split_at_mut(NONCE_SIZE);
+        ephemeral_key.copy_from_slice(&self.ephemeral_key);
+        nonce.copy_from_slice(&self.nonce);
+        ciphertext.copy_from_slice(&self.ciphertext);
         bytes
     }
 
-    /// Deserialize from bytes
+    /// Deserialize from bytes, which must be a V2 note
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
         if bytes.len() < ENCRYPTED_NOTE_SIZE {
             return Err(EncryptionError::InvalidCiphertextLength);
         }
+        if bytes[0] != NOTE_VERSION_V2 {
+            return Err(EncryptionError::UnsupportedVersion(bytes[0]));
+        }
+
+        let (ephemeral_key, rest) = bytes[1..].split_at(EPHEMERAL_KEY_SIZE);
+        let (nonce, rest) = rest.split_at(NONCE_SIZE);
+        Ok(Self {
+            ephemeral_key: ephemeral_key.try_into().unwrap(),
+            nonce: nonce.try_into().unwrap(),
+            ciphertext: rest[..CIPHERTEXT_SIZE].try_into().unwrap(),
+        })
+    }
+}
 
-        let mut ephemeral_key = [0u8; EPHEMERAL_KEY_SIZE];
-        ephemeral_key.copy_from_slice(&bytes[0..EPHEMERAL_KEY_SIZE]);
+/// The sender's copy of an encrypted note, sealed under their outgoing
+/// viewing key
+#[derive(Clone, Debug)]
+pub struct OutgoingNote {
+    /// ChaCha20-Poly1305 nonce
+    pub nonce: [u8; NONCE_SIZE],
+    /// Encrypted recipient key and ephemeral secret + auth tag
+    pub ciphertext: [u8; OUTGOING_CIPHERTEXT_SIZE],
+}
 
-        let mut ciphertext = [0u8; CIPHERTEXT_SIZE];
-        ciphertext.copy_from_slice(&bytes[EPHEMERAL_KEY_SIZE..ENCRYPTED_NOTE_SIZE]);
+impl OutgoingNote {
+    /// Serialize to bytes: nonce || ciphertext
+    pub fn to_bytes(&self) -> [u8; OUTGOING_NOTE_SIZE] {
+        let mut bytes = [0u8; OUTGOING_NOTE_SIZE];
+        bytes[..NONCE_SIZE].copy_from_slice(&self.nonce);
+        bytes[NONCE_SIZE..].copy_from_slice(&self.ciphertext);
+        bytes
+    }
 
-        Ok(Self { ephemeral_key, ciphertext })
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() != OUTGOING_NOTE_SIZE {
+            return Err(EncryptionError::InvalidCiphertextLength);
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_SIZE);
+        Ok(Self {
+            nonce: nonce.try_into().unwrap(),
+            ciphertext: ciphertext.try_into().unwrap(),
+        })
+    }
+}
+
+/// A sent note recovered with the outgoing viewing key
+#[derive(Clone, Debug)]
+pub struct SentNoteData {
+    /// Recipient's encryption public key
+    pub recipient: [u8; 32],
+    /// Note contents
+    pub note: NoteData,
+}
+
+/// An encrypted note in any supported format
+#[derive(Clone, Debug)]
+pub enum NoteCiphertext {
+    /// Legacy SHA-256 stream note, without its version byte
+    V1([u8; legacy::ENCRYPTED_NOTE_SIZE]),
+    /// ChaCha20-Poly1305 note
+    V2(EncryptedNote),
+}
+
+impl NoteCiphertext {
+    /// Format version
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::V1(_) => NOTE_VERSION_V1,
+            Self::V2(_) => NOTE_VERSION_V2,
+        }
+    }
+
+    /// Serialize to bytes, prefixed with the version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::V1(body) => [&[NOTE_VERSION_V1], body.as_slice()].concat(),
+            Self::V2(note) => note.to_bytes().to_vec(),
+        }
+    }
+
+    /// Deserialize from bytes, dispatching on the version byte
+    ///
+    /// Exactly `legacy::ENCRYPTED_NOTE_SIZE` bytes are read as a V1 note from
+    /// before the version byte.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() == legacy::ENCRYPTED_NOTE_SIZE {
+            return Ok(Self::V1(bytes.try_into().unwrap()));
+        }
+        match bytes.first() {
+            Some(&NOTE_VERSION_V1) => bytes[1..]
+                .try_into()
+                .map(Self::V1)
+                .map_err(|_| EncryptionError::InvalidCiphertextLength),
+            Some(&NOTE_VERSION_V2) => EncryptedNote::from_bytes(bytes).map(Self::V2),
+            Some(&version) => Err(EncryptionError::UnsupportedVersion(version)),
+            None => Err(EncryptionError::InvalidCiphertextLength),
+        }
+    }
+}
+
+impl From<EncryptedNote> for NoteCiphertext {
+    fn from(note: EncryptedNote) -> Self {
+        Self::V2(note)
     }
 }
 
@@ -161,6 +321,16 @@ impl EncryptionKeypair {
         Self { private_key, public_key }
     }
 
+    /// Create from an incoming viewing key
+    ///
+    /// Notes encrypted to the public key decrypt with the viewing key alone,
+    /// which cannot spend them.
+    pub fn from_viewing_key(viewing_key: &ViewingKey) -> Self {
+        let private_key = *viewing_key.as_field();
+        let public_key = G1::generator() * private_key;
+        Self { private_key, public_key }
+    }
+
     /// Get the public key as bytes (compressed)
     pub fn public_key_bytes(&self) -> [u8; 32] {
         let affine = self.public_key.into_affine();
@@ -177,8 +347,8 @@ impl EncryptionKeypair {
     /// Get the private key as bytes
     pub fn private_key_bytes(&self) -> [u8; 32] {
         use ark_serialize::CanonicalSerialize;
-        let mut bytes = Vec::new();
-        self.private_key.serialize_compressed(&mut bytes).expect("serialization failed");
+        let mut bytes = Zeroizing::new(Vec::new());
+        self.private_key.serialize_compressed(&mut *bytes).expect("serialization failed");
 
         let mut result = [0u8; 32];
         let len = bytes.len().min(32);
@@ -187,6 +357,18 @@ impl EncryptionKeypair {
     }
 }
 
+impl Zeroize for EncryptionKeypair {
+    fn zeroize(&mut self) {
+        self.private_key.zeroize();
+    }
+}
+
+impl Drop for EncryptionKeypair {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Encrypt note data for a recipient
 ///
 /// # Arguments
@@ -201,24 +383,121 @@ pub fn encrypt_note(
 ) -> Result<EncryptedNote, EncryptionError> {
     use ark_ff::UniformRand;
 
-    // Parse recipient public key
-    let recipient = G1Affine::deserialize_compressed(recipient_pubkey.as_slice())
+    let recipient_point = parse_public_key(recipient_pubkey)?;
+    encrypt_with_ephemeral(note_data, G1::generator(), recipient_point, Fr::rand(&mut OsRng))
+}
+
+/// Encrypt note data for a diversified address
+///
+/// The recipient decrypts with `decrypt_note` under the `EncryptionKeypair`
+/// of the viewing key the address was derived from.
+pub fn encrypt_note_to_address(
+    note_data: &NoteData,
+    address: &DiversifiedAddress,
+) -> Result<EncryptedNote, EncryptionError> {
+    use ark_ff::UniformRand;
+
+    let base = G1::from(address.diversifier.base());
+    let recipient_point = address.transmission_key()?;
+    encrypt_with_ephemeral(note_data, base, recipient_point, Fr::rand(&mut OsRng))
+}
+
+/// Encrypt note data for a recipient, plus a copy for the sender
+///
+/// The `OutgoingNote` holds the recipient key and ephemeral secret under a
+/// key derived from `ovk` and the ephemeral key, so `decrypt_outgoing` can
+/// recover the note from the pair.
+///
+/// # Returns
+/// * The `EncryptedNote` to publish and the sender's `OutgoingNote`
+pub fn encrypt_note_with_ovk(
+    note_data: &NoteData,
+    recipient_pubkey: &[u8; 32],
+    ovk: &OutgoingViewingKey,
+) -> Result<(EncryptedNote, OutgoingNote), EncryptionError> {
+    use ark_ff::UniformRand;
+
+    let ephemeral_private = Zeroizing::new(Fr::rand(&mut OsRng));
+    let recipient_point = parse_public_key(recipient_pubkey)?;
+    let encrypted =
+        encrypt_with_ephemeral(note_data, G1::generator(), recipient_point, *ephemeral_private)?;
+
+    let mut plaintext = Zeroizing::new([0u8; 64]);
+    plaintext[..32].copy_from_slice(recipient_pubkey);
+    ephemeral_private
+        .serialize_compressed(&mut plaintext[32..])
+        .map_err(|e| EncryptionError::SerializationError(e.to_string()))?;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    let payload = Payload {
+        msg: &*plaintext,
+        aad: &encrypted.ephemeral_key,
+    };
+    let key = derive_outgoing_key(ovk, &encrypted.ephemeral_key);
+    let ciphertext = ChaCha20Poly1305::new(&(*key).into())
+        .encrypt(&nonce.into(), payload)
+        .map_err(|_| EncryptionError::SerializationError("encryption failed".to_string()))?;
+
+    let outgoing = OutgoingNote {
+        nonce,
+        ciphertext: ciphertext
+            .try_into()
+            .map_err(|_| EncryptionError::InvalidCiphertextLength)?,
+    };
+    Ok((encrypted, outgoing))
+}
+
+/// Recover a sent note from its published ciphertext and the sender's copy
+pub fn decrypt_outgoing(
+    encrypted_note: &EncryptedNote,
+    outgoing: &OutgoingNote,
+    ovk: &OutgoingViewingKey,
+) -> Result<SentNoteData, EncryptionError> {
+    let payload = Payload {
+        msg: &outgoing.ciphertext,
+        aad: &encrypted_note.ephemeral_key,
+    };
+    let key = derive_outgoing_key(ovk, &encrypted_note.ephemeral_key);
+    let plaintext = ChaCha20Poly1305::new(&(*key).into())
+        .decrypt(&outgoing.nonce.into(), payload)
+        .map(Zeroizing::new)
+        .map_err(|_| EncryptionError::DecryptionFailed)?;
+
+    // The ephemeral secret and recipient key give the same shared secret
+    // the recipient computes
+    let (recipient, ephemeral_private) = plaintext.split_at(32);
+    let recipient_point = G1Affine::deserialize_compressed(recipient)
         .map_err(|_| EncryptionError::InvalidPublicKey)?;
-    let recipient_point = G1::from(recipient);
+    let ephemeral_private = Fr::deserialize_compressed(ephemeral_private)
+        .map(Zeroizing::new)
+        .map_err(|_| EncryptionError::InvalidPrivateKey)?;
+    let shared_secret = G1::from(recipient_point) * *ephemeral_private;
+
+    Ok(SentNoteData {
+        recipient: recipient.try_into().unwrap(),
+        note: open_v2(encrypted_note, &shared_secret)?,
+    })
+}
 
-    // Generate ephemeral keypair
-    let ephemeral_private = Fr::rand(&mut OsRng);
-    let ephemeral_public = G1::generator() * ephemeral_private;
+/// Encrypt note data under a given ephemeral secret
+///
+/// `base` is the generator for ordinary keys, or g_d for a diversified
+/// address.
+fn encrypt_with_ephemeral(
+    note_data: &NoteData,
+    base: G1,
+    recipient_point: G1,
+    ephemeral_private: Fr,
+) -> Result<EncryptedNote, EncryptionError> {
+    // Generate ephemeral public key
+    let ephemeral_public = base * ephemeral_private;
 
     // Compute shared secret via ECDH
     let shared_secret = recipient_point * ephemeral_private;
 
     // Derive symmetric key
-    let symmetric_key = derive_symmetric_key(&shared_secret);
-
-    // Encrypt note data
-    let plaintext = note_data.to_bytes();
-    let ciphertext = chacha20_poly1305_encrypt(&symmetric_key, &plaintext)?;
+    let symmetric_key = derive_symmetric_key(&shared_secret, ENCRYPTION_DOMAIN);
 
     // Serialize ephemeral public key
     let mut ephemeral_key = [0u8; EPHEMERAL_KEY_SIZE];
@@ -229,13 +508,28 @@ pub fn encrypt_note(
     let len = key_bytes.len().min(EPHEMERAL_KEY_SIZE);
     ephemeral_key[..len].copy_from_slice(&key_bytes[..len]);
 
+    // Encrypt note data, binding the ephemeral key
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    let plaintext = note_data.to_bytes();
+    let payload = Payload {
+        msg: &*plaintext,
+        aad: &ephemeral_key,
+    };
+    let ciphertext = ChaCha20Poly1305::new(&(*symmetric_key).into())
+        .encrypt(&nonce.into(), payload)
+        .map_err(|_| EncryptionError::SerializationError("encryption failed".to_string()))?;
+
     Ok(EncryptedNote {
         ephemeral_key,
-        ciphertext,
+        nonce,
+        ciphertext: ciphertext
+            .try_into()
+            .map_err(|_| EncryptionError::InvalidCiphertextLength)?,
     })
 }
 
-/// Decrypt an encrypted note
+/// Decrypt an encrypted note in any supported format
 ///
 /// # Arguments
 /// * `encrypted_note` - The encrypted note
@@ -244,112 +538,183 @@ pub fn encrypt_note(
 /// # Returns
 /// * `NoteData` if decryption succeeds
 pub fn decrypt_note(
-    encrypted_note: &EncryptedNote,
+    encrypted_note: &NoteCiphertext,
     private_key: &[u8; 32],
 ) -> Result<NoteData, EncryptionError> {
-    // Parse private key
-    let sk = Fr::from_le_bytes_mod_order(private_key);
-
-    // Parse ephemeral public key
-    let ephemeral = G1Affine::deserialize_compressed(encrypted_note.ephemeral_key.as_slice())
-        .map_err(|_| EncryptionError::InvalidPublicKey)?;
-    let ephemeral_point = G1::from(ephemeral);
+    match encrypted_note {
+        NoteCiphertext::V1(body) => legacy::decrypt_note(body, private_key),
+        NoteCiphertext::V2(note) => decrypt_v2(note, private_key),
+    }
+}
 
+/// Decrypt a ChaCha20-Poly1305 note
+fn decrypt_v2(
+    encrypted_note: &EncryptedNote,
+    private_key: &[u8; 32],
+) -> Result<NoteData, EncryptionError> {
     // Compute shared secret via ECDH
-    let shared_secret = ephemeral_point * sk;
+    let shared_secret = shared_secret(&encrypted_note.ephemeral_key, private_key)?;
+    open_v2(encrypted_note, &shared_secret)
+}
 
+/// Decrypt a ChaCha20-Poly1305 note given its ECDH shared secret
+fn open_v2(
+    encrypted_note: &EncryptedNote,
+    shared_secret: &G1,
+) -> Result<NoteData, EncryptionError> {
     // Derive symmetric key
-    let symmetric_key = derive_symmetric_key(&shared_secret);
+    let symmetric_key = derive_symmetric_key(shared_secret, ENCRYPTION_DOMAIN);
 
     // Decrypt ciphertext
-    let plaintext = chacha20_poly1305_decrypt(&symmetric_key, &encrypted_note.ciphertext)?;
+    let payload = Payload {
+        msg: &encrypted_note.ciphertext,
+        aad: &encrypted_note.ephemeral_key,
+    };
+    let plaintext = ChaCha20Poly1305::new(&(*symmetric_key).into())
+        .decrypt(&encrypted_note.nonce.into(), payload)
+        .map(Zeroizing::new)
+        .map_err(|_| EncryptionError::DecryptionFailed)?;
 
     // Parse note data
     NoteData::from_bytes(&plaintext)
 }
 
+/// Parse a compressed public key
+fn parse_public_key(bytes: &[u8; 32]) -> Result<G1, EncryptionError> {
+    G1Affine::deserialize_compressed(bytes.as_slice())
+        .map(G1::from)
+        .map_err(|_| EncryptionError::InvalidPublicKey)
+}
+
+/// ECDH shared secret of a private key and a serialized ephemeral key
+fn shared_secret(
+    ephemeral_key: &[u8; EPHEMERAL_KEY_SIZE],
+    private_key: &[u8; 32],
+) -> Result<G1, EncryptionError> {
+    let sk = Fr::from_le_bytes_mod_order(private_key);
+    let ephemeral = G1Affine::deserialize_compressed(ephemeral_key.as_slice())
+        .map_err(|_| EncryptionError::InvalidPublicKey)?;
+    Ok(G1::from(ephemeral) * sk)
+}
+
 /// Derive a 32-byte symmetric key from an ECDH shared secret
-fn derive_symmetric_key(shared_secret: &G1) -> [u8; 32] {
-    let mut point_bytes = Vec::new();
-    shared_secret.into_affine().serialize_compressed(&mut point_bytes)
+fn derive_symmetric_key(shared_secret: &G1, domain: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut point_bytes = Zeroizing::new(Vec::new());
+    shared_secret.into_affine().serialize_compressed(&mut *point_bytes)
         .expect("serialization failed");
 
     // HKDF-like derivation using SHA256
     let mut hasher = Sha256::new();
-    hasher.update(ENCRYPTION_DOMAIN);
-    hasher.update(&point_bytes);
+    hasher.update(domain);
+    hasher.update(&*point_bytes);
     hasher.update(b"symmetric_key");
 
     let hash = hasher.finalize();
-    let mut key = [0u8; 32];
+    let mut key = Zeroizing::new([0u8; 32]);
     key.copy_from_slice(&hash);
     key
 }
 
-/// Encrypt using ChaCha20-Poly1305 (simplified implementation)
-///
-/// Note: In production, use a proper ChaCha20-Poly1305 implementation
-/// from a cryptography library like `chacha20poly1305`.
-fn chacha20_poly1305_encrypt(
-    key: &[u8; 32],
-    plaintext: &[u8; NOTE_DATA_SIZE],
-) -> Result<[u8; CIPHERTEXT_SIZE], EncryptionError> {
-    // Simplified: XOR with key-derived stream + append MAC
-    // In production, use proper ChaCha20-Poly1305
-    let mut ciphertext = [0u8; CIPHERTEXT_SIZE];
-
-    // Derive stream from key
+/// Derive the key sealing an outgoing note
+fn derive_outgoing_key(
+    ovk: &OutgoingViewingKey,
+    ephemeral_key: &[u8; EPHEMERAL_KEY_SIZE],
+) -> Zeroizing<[u8; 32]> {
     let mut hasher = Sha256::new();
-    hasher.update(key);
-    hasher.update(b"stream");
-    let stream = hasher.finalize();
-
-    // XOR plaintext with stream (simplified encryption)
-    for i in 0..NOTE_DATA_SIZE {
-        ciphertext[i] = plaintext[i] ^ stream[i % 32];
-    }
-
-    // Compute MAC
-    let mut mac_hasher = Sha256::new();
-    mac_hasher.update(key);
-    mac_hasher.update(&ciphertext[..NOTE_DATA_SIZE]);
-    let mac = mac_hasher.finalize();
+    hasher.update(OUTGOING_DOMAIN);
+    hasher.update(Zeroizing::new(ovk.to_bytes()).as_slice());
+    hasher.update(ephemeral_key);
 
-    // Append MAC (truncated to 16 bytes)
-    ciphertext[NOTE_DATA_SIZE..].copy_from_slice(&mac[..16]);
-
-    Ok(ciphertext)
+    let hash = hasher.finalize();
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&hash);
+    key
 }
 
-/// Decrypt using ChaCha20-Poly1305 (simplified implementation)
-fn chacha20_poly1305_decrypt(
-    key: &[u8; 32],
-    ciphertext: &[u8; CIPHERTEXT_SIZE],
-) -> Result<[u8; NOTE_DATA_SIZE], EncryptionError> {
-    // Verify MAC first
-    let mut mac_hasher = Sha256::new();
-    mac_hasher.update(key);
-    mac_hasher.update(&ciphertext[..NOTE_DATA_SIZE]);
-    let computed_mac = mac_hasher.finalize();
+/// Notes encrypted before the switch to ChaCha20-Poly1305
+///
+/// A SHA-256 XOR stream with a truncated SHA-256 MAC, laid out as ephemeral
+/// key (32) || ciphertext (48) || MAC (16). Only decryption remains, for
+/// notes already on chain; nothing produces this format anymore.
+/// `decrypt_note` reaches it through `NoteCiphertext::V1`.
+pub mod legacy {
+    use sha2::{Digest, Sha256};
+    use zeroize::Zeroizing;
+
+    use super::{
+        derive_symmetric_key, shared_secret, EncryptionError, NoteData, EPHEMERAL_KEY_SIZE,
+        NOTE_DATA_SIZE,
+    };
+    use crate::crypto::ct::ct_eq;
+
+    /// Domain separator for key derivation
+    const ENCRYPTION_DOMAIN: &[u8] = b"NYX_NOTE_ENCRYPTION_V1";
+
+    /// Total size of an encrypted note, without a version byte
+    pub const ENCRYPTED_NOTE_SIZE: usize = EPHEMERAL_KEY_SIZE + NOTE_DATA_SIZE + 16;
+
+    /// Decrypt a legacy encrypted note
+    pub fn decrypt_note(bytes: &[u8], private_key: &[u8; 32]) -> Result<NoteData, EncryptionError> {
+        if bytes.len() != ENCRYPTED_NOTE_SIZE {
+            return Err(EncryptionError::InvalidCiphertextLength);
+        }
+        let (ephemeral_key, ciphertext) = bytes.split_at(EPHEMERAL_KEY_SIZE);
+        let shared_secret = shared_secret(ephemeral_key.try_into().unwrap(), private_key)?;
+        let key = derive_symmetric_key(&shared_secret, ENCRYPTION_DOMAIN);
+        let (data, mac) = ciphertext.split_at(NOTE_DATA_SIZE);
+
+        // Verify MAC first
+        let mut mac_hasher = Sha256::new();
+        mac_hasher.update(key.as_slice());
+        mac_hasher.update(data);
+        if !ct_eq(&mac_hasher.finalize()[..16], mac) {
+            return Err(EncryptionError::DecryptionFailed);
+        }
+
+        // XOR ciphertext with the key-derived stream
+        let mut hasher = Sha256::new();
+        hasher.update(key.as_slice());
+        hasher.update(b"stream");
+        let stream = hasher.finalize();
+        let plaintext: Zeroizing<Vec<u8>> =
+            Zeroizing::new(data.iter().zip(stream.iter().cycle()).map(|(c, s)| c ^ s).collect());
 
-    // Compare MACs (constant time would be better in production)
-    if &computed_mac[..16] != &ciphertext[NOTE_DATA_SIZE..] {
-        return Err(EncryptionError::DecryptionFailed);
+        NoteData::from_bytes(&plaintext)
     }
 
-    // Derive stream from key
-    let mut hasher = Sha256::new();
-    hasher.update(key);
-    hasher.update(b"stream");
-    let stream = hasher.finalize();
+    /// Encrypt in the legacy format, to make test data
+    #[cfg(test)]
+    pub(super) fn encrypt_note(
+        note_data: &NoteData,
+        ephemeral_private: ark_bn254::Fr,
+        recipient_private: &[u8; 32],
+    ) -> Vec<u8> {
+        use ark_ec::{CurveGroup, Group};
+        use ark_serialize::CanonicalSerialize;
 
-    // XOR ciphertext with stream
-    let mut plaintext = [0u8; NOTE_DATA_SIZE];
-    for i in 0..NOTE_DATA_SIZE {
-        plaintext[i] = ciphertext[i] ^ stream[i % 32];
+        let ephemeral_public = super::G1::generator() * ephemeral_private;
+        let mut bytes = Vec::new();
+        ephemeral_public.into_affine().serialize_compressed(&mut bytes).unwrap();
+        let shared_secret = shared_secret(bytes.as_slice().try_into().unwrap(), recipient_private);
+        let key = derive_symmetric_key(&shared_secret.unwrap(), ENCRYPTION_DOMAIN);
+
+        let mut hasher = Sha256::new();
+        hasher.update(key.as_slice());
+        hasher.update(b"stream");
+        let stream = hasher.finalize();
+        let data: Vec<u8> = note_data
+            .to_bytes()
+            .iter()
+            .zip(stream.iter().cycle())
+            .map(|(p, s)| p ^ s)
+            .collect();
+        let mut mac_hasher = Sha256::new();
+        mac_hasher.update(key.as_slice());
+        mac_hasher.update(&data);
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&mac_hasher.finalize()[..16]);
+        bytes
     }
-
-    Ok(plaintext)
 }
 
 #[cfg(test)]
@@ -360,7 +725,7 @@ mod tests {
     fn test_note_data_serialization() {
         let note = NoteData::new(1000, [42u8; 32], 0);
         let bytes = note.to_bytes();
-        let decoded = NoteData::from_bytes(&bytes).unwrap();
+        let decoded = NoteData::from_bytes(bytes.as_slice()).unwrap();
 
         assert_eq!(note.amount, decoded.amount);
         assert_eq!(note.blinding, decoded.blinding);
@@ -381,7 +746,7 @@ mod tests {
         let encrypted = encrypt_note(&note, &recipient_pubkey).unwrap();
 
         // Decrypt
-        let decrypted = decrypt_note(&encrypted, &recipient_privkey).unwrap();
+        let decrypted = decrypt_note(&encrypted.into(), &recipient_privkey).unwrap();
 
         assert_eq!(note.amount, decrypted.amount);
         assert_eq!(note.blinding, decrypted.blinding);
@@ -397,7 +762,7 @@ mod tests {
         let encrypted = encrypt_note(&note, &recipient.public_key_bytes()).unwrap();
 
         // Decrypting with wrong key should fail
-        let result = decrypt_note(&encrypted, &wrong_key.private_key_bytes());
+        let result = decrypt_note(&encrypted.into(), &wrong_key.private_key_bytes());
         assert!(result.is_err());
     }
 
@@ -412,6 +777,144 @@ mod tests {
         let restored = EncryptedNote::from_bytes(&bytes).unwrap();
 
         assert_eq!(encrypted.ephemeral_key, restored.ephemeral_key);
+        assert_eq!(encrypted.nonce, restored.nonce);
         assert_eq!(encrypted.ciphertext, restored.ciphertext);
     }
+
+    #[test]
+    fn test_viewing_key_decrypts() {
+        let secret = [3u8; 32];
+        let recipient = EncryptionKeypair::from_viewing_key(&ViewingKey::from_secret(&secret));
+        let note = NoteData::new(250, [4u8; 32], 0);
+        let encrypted = encrypt_note(&note, &recipient.public_key_bytes()).unwrap();
+
+        // A watch-only wallet holding only the viewing key finds the note
+        let watch_only = ViewingKey::from_bytes(&ViewingKey::from_secret(&secret).to_bytes());
+        let keys = EncryptionKeypair::from_viewing_key(&watch_only);
+        let decrypted = decrypt_note(&encrypted.into(), &keys.private_key_bytes()).unwrap();
+        assert_eq!(decrypted.amount, 250);
+    }
+
+    #[test]
+    fn test_outgoing_roundtrip() {
+        let sender = OutgoingViewingKey::from_secret(&[1u8; 32]);
+        let recipient = EncryptionKeypair::generate();
+        let note = NoteData::new(600, [6u8; 32], 4);
+        let (encrypted, outgoing) =
+            encrypt_note_with_ovk(&note, &recipient.public_key_bytes(), &sender).unwrap();
+
+        // The recipient decrypts as usual
+        let received = decrypt_note(&encrypted.clone().into(), &recipient.private_key_bytes());
+        assert_eq!(received.unwrap().amount, 600);
+
+        // The sender recovers the note and recipient
+        let outgoing = OutgoingNote::from_bytes(&outgoing.to_bytes()).unwrap();
+        let sent = decrypt_outgoing(&encrypted, &outgoing, &sender).unwrap();
+        assert_eq!(sent.recipient, recipient.public_key_bytes());
+        assert_eq!(sent.note.amount, 600);
+        assert_eq!(sent.note.blinding, [6u8; 32]);
+        assert_eq!(sent.note.asset_id, 4);
+
+        // Another outgoing key, or another note, does not open it
+        let other = OutgoingViewingKey::from_secret(&[2u8; 32]);
+        assert!(decrypt_outgoing(&encrypted, &outgoing, &other).is_err());
+        let unrelated = encrypt_note(&note, &recipient.public_key_bytes()).unwrap();
+        assert!(decrypt_outgoing(&unrelated, &outgoing, &sender).is_err());
+    }
+
+    #[test]
+    fn test_diversified_address_decrypts() {
+        use crate::crypto::address::DiversifiedAddress;
+
+        let ivk = ViewingKey::from_secret(&[8u8; 32]);
+        let keys = EncryptionKeypair::from_viewing_key(&ivk);
+        for index in 0..3 {
+            let address = DiversifiedAddress::derive(&ivk, index);
+            let note = NoteData::new(index, [index as u8; 32], 0);
+            let encrypted = encrypt_note_to_address(&note, &address).unwrap();
+            let decrypted = decrypt_note(&encrypted.into(), &keys.private_key_bytes()).unwrap();
+            assert_eq!(decrypted.amount, index);
+        }
+
+        let other = EncryptionKeypair::from_viewing_key(&ViewingKey::from_secret(&[9u8; 32]));
+        let address = DiversifiedAddress::derive(&ivk, 0);
+        let encrypted = encrypt_note_to_address(&NoteData::new(1, [0u8; 32], 0), &address);
+        assert!(decrypt_note(&encrypted.unwrap().into(), &other.private_key_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_tampering_fails() {
+        let recipient = EncryptionKeypair::generate();
+        let note = NoteData::new(500, [5u8; 32], 1);
+        let encrypted = encrypt_note(&note, &recipient.public_key_bytes()).unwrap();
+
+        // Each part is authenticated: the ciphertext and tag, the nonce, and
+        // the ephemeral key as associated data
+        let nonce_start = 1 + EPHEMERAL_KEY_SIZE;
+        let ciphertext_start = nonce_start + NONCE_SIZE;
+        for index in [1, nonce_start, ciphertext_start, ENCRYPTED_NOTE_SIZE - 1] {
+            let mut bytes = encrypted.to_bytes();
+            bytes[index] ^= 1;
+            let tampered = EncryptedNote::from_bytes(&bytes).unwrap();
+            assert!(decrypt_note(&tampered.into(), &recipient.private_key_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_legacy_note_decrypts() {
+        use ark_ff::UniformRand;
+
+        let recipient = EncryptionKeypair::generate();
+        let note = NoteData::new(750, [7u8; 32], 2);
+        let secret = recipient.private_key_bytes();
+        let bytes = legacy::encrypt_note(&note, Fr::rand(&mut OsRng), &secret);
+        assert_eq!(bytes.len(), legacy::ENCRYPTED_NOTE_SIZE);
+
+        let decrypted = legacy::decrypt_note(&bytes, &recipient.private_key_bytes()).unwrap();
+        assert_eq!(decrypted.amount, 750);
+        assert_eq!(decrypted.blinding, [7u8; 32]);
+        assert_eq!(decrypted.asset_id, 2);
+
+        let wrong_key = EncryptionKeypair::generate();
+        assert!(legacy::decrypt_note(&bytes, &wrong_key.private_key_bytes()).is_err());
+        assert!(legacy::decrypt_note(&bytes[1..], &recipient.private_key_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_note_versions() {
+        use ark_ff::UniformRand;
+
+        let recipient = EncryptionKeypair::generate();
+        let secret = recipient.private_key_bytes();
+        let note = NoteData::new(900, [9u8; 32], 3);
+
+        let v2 = encrypt_note(&note, &recipient.public_key_bytes()).unwrap();
+        let bytes = NoteCiphertext::from(v2.clone()).to_bytes();
+        assert_eq!(bytes, v2.to_bytes());
+        assert_eq!(bytes[0], NOTE_VERSION_V2);
+        let parsed = NoteCiphertext::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.version(), NOTE_VERSION_V2);
+        assert_eq!(decrypt_note(&parsed, &secret).unwrap().amount, 900);
+
+        // Legacy notes parse with and without the version byte
+        let legacy = legacy::encrypt_note(&note, Fr::rand(&mut OsRng), &secret);
+        let unprefixed = NoteCiphertext::from_bytes(&legacy).unwrap();
+        assert_eq!(unprefixed.version(), NOTE_VERSION_V1);
+        let prefixed = unprefixed.to_bytes();
+        assert_eq!(prefixed.len(), 1 + legacy::ENCRYPTED_NOTE_SIZE);
+        let parsed = NoteCiphertext::from_bytes(&prefixed).unwrap();
+        assert_eq!(decrypt_note(&parsed, &secret).unwrap().amount, 900);
+
+        let mut unknown = bytes;
+        unknown[0] = 3;
+        assert!(matches!(
+            NoteCiphertext::from_bytes(&unknown),
+            Err(EncryptionError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            EncryptedNote::from_bytes(&prefixed),
+            Err(EncryptionError::InvalidCiphertextLength)
+        ));
+        assert!(NoteCiphertext::from_bytes(&[]).is_err());
+    }
 }
@@ -0,0 +1,276 @@
+//! Frontier Checkpoints
+//!
+//! A light client syncing from genesis hashes every leaf the pool ever
+//! received. A `FrontierCheckpoint` lets it start from a recent state
+//! instead: the tree's frontier (`next_index`, the filled subtrees and the
+//! last leaf) as of a slot, plus the roots the pool still accepts proofs
+//! against, kept as the program keeps its root history.
+//!
+//! Exports are hash-committed. The last 32 bytes are the blake3 hash of the
+//! rest, which is the checkpoint's `commitment`; whoever publishes a
+//! checkpoint publishes that hash through a channel the client trusts, and
+//! `import_committed` refuses anything else. Before relying on an imported
+//! checkpoint, `verify_onchain` checks its root against the pool's current
+//! root and root history as read from chain.
+
+use std::collections::VecDeque;
+
+use ark_bn254::Fr;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::merkle::{MerkleError, MerkleFrontier, PoseidonMerkleTree};
+use super::serialization::{read_frs, write_frs};
+use crate::verify::encoding::field_to_bytes_be;
+
+/// Magic bytes leading an exported checkpoint
+const CHECKPOINT_MAGIC: &[u8; 4] = b"VFCK";
+/// Version of the exported layout
+const CHECKPOINT_VERSION: u8 = 1;
+/// Size of the trailing commitment
+const COMMITMENT_SIZE: usize = 32;
+
+/// Roots kept by default, as the program's `DEFAULT_ROOT_HISTORY_SIZE`
+pub const DEFAULT_RECENT_ROOTS: u16 = 30;
+
+/// Frontier of a pool's tree at a slot, with its recent roots
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrontierCheckpoint {
+    slot: u64,
+    frontier: MerkleFrontier,
+    /// Replaced roots, oldest first
+    recent_roots: VecDeque<Fr>,
+    history_size: u16,
+}
+
+impl FrontierCheckpoint {
+    /// Checkpoint a frontier taken at `slot`
+    ///
+    /// `recent_roots` are the roots it replaced, oldest first; only the
+    /// newest `history_size` are kept.
+    pub fn new(
+        slot: u64,
+        frontier: MerkleFrontier,
+        recent_roots: impl IntoIterator<Item = Fr>,
+        history_size: u16,
+    ) -> Self {
+        let mut checkpoint = Self {
+            slot,
+            frontier,
+            recent_roots: recent_roots.into_iter().collect(),
+            history_size: history_size.max(1),
+        };
+        checkpoint.trim();
+        checkpoint
+    }
+
+    /// Checkpoint a full tree taken at `slot`
+    pub fn from_tree(
+        slot: u64,
+        tree: &PoseidonMerkleTree,
+        recent_roots: impl IntoIterator<Item = Fr>,
+        history_size: u16,
+    ) -> Self {
+        Self::new(slot, tree.frontier(), recent_roots, history_size)
+    }
+
+    /// Append the next leaf, keeping the replaced root among the recent ones
+    pub fn append(&mut self, leaf: Fr) -> Result<u64, MerkleError> {
+        let replaced = self.frontier.root();
+        let index = self.frontier.append(leaf)?;
+        self.recent_roots.push_back(replaced);
+        self.trim();
+        Ok(index)
+    }
+
+    /// Mark the checkpoint as current up to `slot`
+    pub fn set_slot(&mut self, slot: u64) {
+        self.slot = slot;
+    }
+
+    /// Slot the checkpoint is current up to
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// The tree's frontier
+    pub fn frontier(&self) -> &MerkleFrontier {
+        &self.frontier
+    }
+
+    /// Current root
+    pub fn root(&self) -> Fr {
+        self.frontier.root()
+    }
+
+    /// Replaced roots still kept, oldest first
+    pub fn recent_roots(&self) -> impl Iterator<Item = &Fr> {
+        self.recent_roots.iter()
+    }
+
+    /// Whether a proof against `root` would still be accepted
+    pub fn is_known_root(&self, root: &Fr) -> bool {
+        *root == self.root() || self.recent_roots.contains(root)
+    }
+
+    /// Check the checkpoint against the pool as read from chain
+    ///
+    /// `current_root` and `history` are big-endian, as the program stores
+    /// them; unused (zero) history slots are ignored. The checkpoint's root
+    /// must be the pool's current root, or one of its history if the pool
+    /// has moved on since.
+    pub fn verify_onchain(
+        &self,
+        current_root: &[u8; 32],
+        history: &[[u8; 32]],
+    ) -> Result<(), MerkleError> {
+        let root = field_to_bytes_be(&self.root());
+        if root == *current_root || history.iter().any(|r| *r == root && *r != [0u8; 32]) {
+            Ok(())
+        } else {
+            Err(MerkleError::UnknownRoot)
+        }
+    }
+
+    /// Serialize as magic || version || slot || history size || frontier ||
+    /// recent roots || commitment
+    pub fn export(&self) -> Vec<u8> {
+        let mut bytes = self.body();
+        let commitment = *blake3::hash(&bytes).as_bytes();
+        bytes.extend_from_slice(&commitment);
+        bytes
+    }
+
+    /// Hash committing to the exported checkpoint
+    pub fn commitment(&self) -> [u8; 32] {
+        *blake3::hash(&self.body()).as_bytes()
+    }
+
+    /// Parse an exported checkpoint, checking it against its own commitment
+    ///
+    /// This only catches corruption; use `import_committed` for a checkpoint
+    /// from someone else.
+    pub fn import(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let corrupt = |what: &str| MerkleError::Corrupt(format!("checkpoint {}", what));
+        if bytes.len() < CHECKPOINT_MAGIC.len() + 1 + COMMITMENT_SIZE {
+            return Err(corrupt("truncated"));
+        }
+        let (body, commitment) = bytes.split_at(bytes.len() - COMMITMENT_SIZE);
+        if blake3::hash(body).as_bytes() != commitment {
+            return Err(corrupt("commitment"));
+        }
+        if &body[..4] != CHECKPOINT_MAGIC || body[4] != CHECKPOINT_VERSION {
+            return Err(corrupt("header"));
+        }
+
+        let mut reader = &body[5..];
+        let read = |reader: &mut &[u8]| -> std::io::Result<Self> {
+            let slot = u64::deserialize_reader(reader)?;
+            let history_size = u16::deserialize_reader(reader)?;
+            let frontier = MerkleFrontier::deserialize_reader(reader)?;
+            let recent_roots = read_frs(reader)?;
+            Ok(Self::new(slot, frontier, recent_roots, history_size))
+        };
+        let checkpoint = read(&mut reader).map_err(|e| corrupt(&e.to_string()))?;
+        if !reader.is_empty() || checkpoint.body() != body {
+            return Err(corrupt("encoding"));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Parse a checkpoint published with `commitment`
+    pub fn import_committed(bytes: &[u8], commitment: &[u8; 32]) -> Result<Self, MerkleError> {
+        let checkpoint = Self::import(bytes)?;
+        if checkpoint.commitment() != *commitment {
+            return Err(MerkleError::Corrupt("checkpoint does not match its commitment".into()));
+        }
+        Ok(checkpoint)
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(CHECKPOINT_MAGIC);
+        bytes.push(CHECKPOINT_VERSION);
+        let roots: Vec<Fr> = self.recent_roots.iter().copied().collect();
+        // Writing to a Vec cannot fail
+        BorshSerialize::serialize(&self.slot, &mut bytes).unwrap();
+        BorshSerialize::serialize(&self.history_size, &mut bytes).unwrap();
+        BorshSerialize::serialize(&self.frontier, &mut bytes).unwrap();
+        write_frs(&roots, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn trim(&mut self) {
+        let excess = self.recent_roots.len().saturating_sub(self.history_size as usize);
+        self.recent_roots.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint_after(leaves: u64) -> (PoseidonMerkleTree, FrontierCheckpoint) {
+        let mut tree = PoseidonMerkleTree::new();
+        let mut checkpoint = FrontierCheckpoint::new(0, MerkleFrontier::new(), [], 4);
+        for i in 1..=leaves {
+            tree.insert(Fr::from(i)).unwrap();
+            checkpoint.append(Fr::from(i)).unwrap();
+        }
+        checkpoint.set_slot(77);
+        (tree, checkpoint)
+    }
+
+    #[test]
+    fn test_export_import_and_resume() {
+        let (mut tree, checkpoint) = checkpoint_after(9);
+        assert_eq!(checkpoint.root(), tree.root());
+        assert_eq!(checkpoint.recent_roots().count(), 4);
+
+        let bytes = checkpoint.export();
+        let commitment = checkpoint.commitment();
+        let mut imported = FrontierCheckpoint::import_committed(&bytes, &commitment).unwrap();
+        assert_eq!(imported, checkpoint);
+        assert_eq!(imported.slot(), 77);
+
+        // Proofs against the last few roots stay acceptable
+        let old_root = tree.root();
+        tree.insert(Fr::from(10u64)).unwrap();
+        imported.append(Fr::from(10u64)).unwrap();
+        assert_eq!(imported.root(), tree.root());
+        assert!(imported.is_known_root(&old_root));
+        assert!(!imported.is_known_root(&Fr::from(5u64)));
+    }
+
+    #[test]
+    fn test_rejects_tampering() {
+        let (_, checkpoint) = checkpoint_after(5);
+        let bytes = checkpoint.export();
+
+        let mut flipped = bytes.clone();
+        flipped[10] ^= 1;
+        assert!(FrontierCheckpoint::import(&flipped).is_err());
+
+        // A well-formed checkpoint from someone else
+        let (_, other) = checkpoint_after(6);
+        assert!(FrontierCheckpoint::import(&other.export()).is_ok());
+        assert!(FrontierCheckpoint::import_committed(&other.export(), &checkpoint.commitment())
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_onchain() {
+        let (tree, checkpoint) = checkpoint_after(3);
+        let root = field_to_bytes_be(&tree.root());
+        let mut history = [[0u8; 32]; 3];
+
+        checkpoint.verify_onchain(&root, &history).unwrap();
+        assert!(matches!(
+            checkpoint.verify_onchain(&[1u8; 32], &history),
+            Err(MerkleError::UnknownRoot)
+        ));
+
+        // The pool moved on; the checkpoint's root is now in its history
+        history[1] = root;
+        checkpoint.verify_onchain(&[1u8; 32], &history).unwrap();
+    }
+}
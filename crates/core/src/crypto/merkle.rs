@@ -4,22 +4,41 @@
 //! which is efficient to verify inside zkSNARK circuits.
 //!
 //! Tree Structure:
-//! - Depth: 20 levels (supports ~1 million leaves)
-//! - Uses Poseidon hash for all internal nodes
+//! - Depth: 20 levels (~1 million leaves) by default; pools may also use 24
+//!   or 26 (`SUPPORTED_DEPTHS`)
+//! - Uses Poseidon hash for all internal nodes, or Poseidon2 for pools on
+//!   `ProtocolVersion::V2`
 //! - Compatible with circom and arkworks circuits
 
+use std::sync::OnceLock;
+
 use ark_bn254::Fr;
-use ark_ff::{BigInteger, PrimeField};
+use ark_ff::{BigInteger, MontFp, PrimeField};
 use thiserror::Error;
 
-use super::poseidon::poseidon_hash2;
+use super::protocol::ProtocolVersion;
+use crate::verify::merkle::compute_root;
 
-/// Merkle tree depth (20 levels = 2^20 = ~1 million leaves)
-pub const TREE_DEPTH: usize = 20;
+pub use crate::verify::merkle::TREE_DEPTH;
 
-/// Maximum number of leaves
+/// Maximum number of leaves at the default depth
 pub const MAX_LEAVES: u64 = 1 << TREE_DEPTH;
 
+/// Depth of the largest supported tree
+pub const MAX_TREE_DEPTH: usize = 26;
+
+/// Tree depths a pool may use
+pub const SUPPORTED_DEPTHS: [usize; 3] = [TREE_DEPTH, 24, MAX_TREE_DEPTH];
+
+/// Fail unless `depth` is one of `SUPPORTED_DEPTHS`
+pub fn check_depth(depth: usize) -> Result<(), MerkleError> {
+    if SUPPORTED_DEPTHS.contains(&depth) {
+        Ok(())
+    } else {
+        Err(MerkleError::UnsupportedDepth(depth))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum MerkleError {
     #[error("Tree is full")]
@@ -28,16 +47,69 @@ pub enum MerkleError {
     InvalidLeafIndex(u64),
     #[error("Invalid proof length")]
     InvalidProofLength,
+    #[error("Storage error: {0}")]
+    Storage(String),
+    #[error("Corrupt tree data: {0}")]
+    Corrupt(String),
+    #[error("Unsupported tree depth: {0}")]
+    UnsupportedDepth(usize),
+    #[error("Tree depth mismatch: expected {expected}, got {actual}")]
+    DepthMismatch { expected: usize, actual: usize },
+    #[error("Root is not the pool's current or a recent root")]
+    UnknownRoot,
+}
+
+/// Zero hashes of a Poseidon tree for each level
+/// ZERO_HASHES[0] = 0 (empty leaf)
+/// ZERO_HASHES[i] = Poseidon(ZERO_HASHES[i-1], ZERO_HASHES[i-1])
+///
+/// The first `TREE_DEPTH + 1` match the program's `ZERO_HASHES`.
+pub const ZERO_HASHES: [Fr; MAX_TREE_DEPTH + 1] = [
+    MontFp!("0"),
+    MontFp!("14744269619966411208579211824598458697587494354926760081771325075741142829156"),
+    MontFp!("7423237065226347324353380772367382631490014989348495481811164164159255474657"),
+    MontFp!("11286972368698509976183087595462810875513684078608517520839298933882497716792"),
+    MontFp!("3607627140608796879659380071776844901612302623152076817094415224584923813162"),
+    MontFp!("19712377064642672829441595136074946683621277828620209496774504837737984048981"),
+    MontFp!("20775607673010627194014556968476266066927294572720319469184847051418138353016"),
+    MontFp!("3396914609616007258851405644437304192397291162432396347162513310381425243293"),
+    MontFp!("21551820661461729022865262380882070649935529853313286572328683688269863701601"),
+    MontFp!("6573136701248752079028194407151022595060682063033565181951145966236778420039"),
+    MontFp!("12413880268183407374852357075976609371175688755676981206018884971008854919922"),
+    MontFp!("14271763308400718165336499097156975241954733520325982997864342600795471836726"),
+    MontFp!("20066985985293572387227381049700832219069292839614107140851619262827735677018"),
+    MontFp!("9394776414966240069580838672673694685292165040808226440647796406499139370960"),
+    MontFp!("11331146992410411304059858900317123658895005918277453009197229807340014528524"),
+    MontFp!("15819538789928229930262697811477882737253464456578333862691129291651619515538"),
+    MontFp!("19217088683336594659449020493828377907203207941212636669271704950158751593251"),
+    MontFp!("21035245323335827719745544373081896983162834604456827698288649288827293579666"),
+    MontFp!("6939770416153240137322503476966641397417391950902474480970945462551409848591"),
+    MontFp!("10941962436777715901943463195175331263348098796018438960955633645115732864202"),
+    MontFp!("15019797232609675441998260052101280400536945603062888308240081994073687793470"),
+    MontFp!("11702828337982203149177882813338547876343922920234831094975924378932809409969"),
+    MontFp!("11217067736778784455593535811108456786943573747466706329920902520905755780395"),
+    MontFp!("16072238744996205792852194127671441602062027943016727953216607508365787157389"),
+    MontFp!("17681057402012993898104192736393849603097507831571622013521167331642182653248"),
+    MontFp!("21694045479371014653083846597424257852691458318143380497809004364947786214945"),
+    MontFp!("8163447297445169709687354538480474434591144168767135863541048304198280615192"),
+];
+
+/// Zero hashes of a tree hashed as `version`, computed once per version
+pub fn zero_hashes(version: ProtocolVersion) -> &'static [Fr; MAX_TREE_DEPTH + 1] {
+    static POSEIDON2: OnceLock<[Fr; MAX_TREE_DEPTH + 1]> = OnceLock::new();
+    match version {
+        ProtocolVersion::V1 => &ZERO_HASHES,
+        ProtocolVersion::V2 => POSEIDON2.get_or_init(|| compute_zero_hashes(version)),
+    }
 }
 
-/// Precomputed zero hashes for each level (Poseidon-based)
 /// zeros[0] = 0 (empty leaf)
-/// zeros[i] = Poseidon(zeros[i-1], zeros[i-1])
-fn compute_zero_hashes() -> [Fr; TREE_DEPTH + 1] {
-    let mut zeros = [Fr::from(0u64); TREE_DEPTH + 1];
+/// zeros[i] = H(zeros[i-1], zeros[i-1]), with the version's hash H
+fn compute_zero_hashes(version: ProtocolVersion) -> [Fr; MAX_TREE_DEPTH + 1] {
+    let mut zeros = [Fr::from(0u64); MAX_TREE_DEPTH + 1];
 
-    for i in 1..=TREE_DEPTH {
-        zeros[i] = poseidon_hash2(&zeros[i - 1], &zeros[i - 1]);
+    for i in 1..=MAX_TREE_DEPTH {
+        zeros[i] = version.hash2(&zeros[i - 1], &zeros[i - 1]);
     }
 
     zeros
@@ -45,9 +117,7 @@ fn compute_zero_hashes() -> [Fr; TREE_DEPTH + 1] {
 
 /// Get zero hash for a specific level
 pub fn get_zero_hash(level: usize) -> Fr {
-    // We compute all zeros each time for simplicity
-    // In production, these would be cached constants
-    compute_zero_hashes()[level]
+    ZERO_HASHES[level]
 }
 
 /// A Merkle path (proof) for a leaf
@@ -64,26 +134,53 @@ pub struct MerklePath {
 impl MerklePath {
     /// Verify the path leads to the expected root
     pub fn verify(&self, leaf: &Fr, expected_root: &Fr) -> bool {
-        if self.siblings.len() != TREE_DEPTH || self.indices.len() != TREE_DEPTH {
+        self.verify_with(ProtocolVersion::V1, leaf, expected_root)
+    }
+
+    /// Verify the path leads to the expected root of a tree hashed as
+    /// `version`
+    pub fn verify_with(&self, version: ProtocolVersion, leaf: &Fr, expected_root: &Fr) -> bool {
+        if self.indices.len() != self.siblings.len() || check_depth(self.depth()).is_err() {
             return false;
         }
 
-        let mut current = *leaf;
+        let root = match version {
+            ProtocolVersion::V1 => {
+                compute_root(leaf, &self.siblings, self.indices.iter().copied())
+            }
+            _ => self.siblings.iter().zip(&self.indices).fold(
+                *leaf,
+                |current, (sibling, &is_right)| {
+                    if is_right {
+                        version.hash2(sibling, &current)
+                    } else {
+                        version.hash2(&current, sibling)
+                    }
+                },
+            ),
+        };
+        root == *expected_root
+    }
 
-        for (sibling, &is_right) in self.siblings.iter().zip(self.indices.iter()) {
-            current = if is_right {
-                poseidon_hash2(sibling, &current)
-            } else {
-                poseidon_hash2(&current, sibling)
-            };
-        }
+    /// Depth of the tree the path is for
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
 
-        current == *expected_root
+    /// Fail unless the path is for a tree of depth `expected`
+    pub fn check_depth(&self, expected: usize) -> Result<(), MerkleError> {
+        if self.siblings.len() != expected || self.indices.len() != expected {
+            return Err(MerkleError::DepthMismatch {
+                expected,
+                actual: self.siblings.len(),
+            });
+        }
+        Ok(())
     }
 
     /// Convert to bytes for serialization
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(8 + TREE_DEPTH * 32 + TREE_DEPTH);
+        let mut bytes = Vec::with_capacity(8 + self.depth() * 32 + 4);
 
         // Leaf index
         bytes.extend_from_slice(&self.leaf_index.to_le_bytes());
@@ -110,23 +207,52 @@ impl MerklePath {
 
         bytes
     }
+
+    /// Parse a path serialized with `to_bytes`, taking the depth from its
+    /// length
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let depth = bytes.len().checked_sub(12)? / 32;
+        if bytes.len() != 8 + depth * 32 + 4 || check_depth(depth).is_err() {
+            return None;
+        }
+        let leaf_index = u64::from_le_bytes(bytes[..8].try_into().ok()?);
+        let siblings = bytes[8..8 + depth * 32]
+            .chunks(32)
+            .map(Fr::from_le_bytes_mod_order)
+            .collect();
+        let bits = u32::from_le_bytes(bytes[8 + depth * 32..].try_into().ok()?);
+        let indices = (0..depth).map(|i| bits & (1 << i) != 0).collect();
+        Some(Self {
+            siblings,
+            indices,
+            leaf_index,
+        })
+    }
 }
 
 /// Incremental Merkle Tree using Poseidon hash
 ///
 /// Optimized for O(log n) insertions using the "filled subtrees" technique.
+/// Every non-empty node below the root is kept, so proofs take O(depth)
+/// lookups; nodes of empty subtrees come from the zero hashes. Memory is
+/// about twice the number of leaves.
 #[derive(Clone, Debug)]
 pub struct PoseidonMerkleTree {
+    /// Number of levels below the root
+    depth: usize,
+    /// Hash the tree is built with
+    version: ProtocolVersion,
     /// Current number of leaves
     pub next_index: u64,
     /// Filled subtrees at each level
     filled_subtrees: Vec<Fr>,
     /// Current root
     current_root: Fr,
-    /// All leaves (for proof generation)
-    leaves: Vec<Fr>,
-    /// Precomputed zero hashes
-    zeros: Vec<Fr>,
+    /// Non-empty nodes by level, leaves first; the last node of a level may
+    /// still change as leaves are added below it
+    nodes: Vec<Vec<Fr>>,
+    /// Zero hashes for each level
+    zeros: &'static [Fr],
 }
 
 impl Default for PoseidonMerkleTree {
@@ -136,59 +262,191 @@ impl Default for PoseidonMerkleTree {
 }
 
 impl PoseidonMerkleTree {
-    /// Create a new empty tree
+    /// Create a new empty tree of the default depth
     pub fn new() -> Self {
-        let zeros: Vec<Fr> = compute_zero_hashes().to_vec();
+        Self::empty(TREE_DEPTH, ProtocolVersion::V1)
+    }
+
+    /// Create a new empty tree of one of the `SUPPORTED_DEPTHS`
+    pub fn with_depth(depth: usize) -> Result<Self, MerkleError> {
+        Self::with_version(depth, ProtocolVersion::V1)
+    }
+
+    /// Create a new empty tree hashed as `version`
+    pub fn with_version(depth: usize, version: ProtocolVersion) -> Result<Self, MerkleError> {
+        check_depth(depth)?;
+        Ok(Self::empty(depth, version))
+    }
+
+    fn empty(depth: usize, version: ProtocolVersion) -> Self {
+        let zeros = &zero_hashes(version)[..=depth];
 
         // Initialize filled_subtrees with zero hashes
-        let filled_subtrees: Vec<Fr> = (0..TREE_DEPTH).map(|i| zeros[i]).collect();
+        let filled_subtrees: Vec<Fr> = (0..depth).map(|i| zeros[i]).collect();
 
         // Initial root is zero hash at top level
-        let current_root = zeros[TREE_DEPTH];
+        let current_root = zeros[depth];
 
         Self {
+            depth,
+            version,
             next_index: 0,
             filled_subtrees,
             current_root,
-            leaves: Vec::new(),
+            nodes: vec![Vec::new(); depth],
             zeros,
         }
     }
 
+    /// Number of levels below the root
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Hash the tree is built with
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Maximum number of leaves
+    pub fn capacity(&self) -> u64 {
+        1 << self.depth
+    }
+
     /// Insert a new leaf into the tree
     ///
     /// Returns the index of the inserted leaf
     pub fn insert(&mut self, leaf: Fr) -> Result<u64, MerkleError> {
-        if self.next_index >= MAX_LEAVES {
+        if self.next_index >= self.capacity() {
             return Err(MerkleError::TreeFull);
         }
 
         let leaf_index = self.next_index;
-        self.leaves.push(leaf);
-
-        let mut current = leaf;
-        let mut index = leaf_index;
+        let nodes = &mut self.nodes;
+        self.current_root = append_to_frontier(
+            self.version,
+            &mut self.filled_subtrees,
+            self.zeros,
+            leaf_index,
+            leaf,
+            |level, node_index, hash| {
+                let level_nodes = &mut nodes[level];
+                if node_index as usize == level_nodes.len() {
+                    level_nodes.push(hash);
+                } else {
+                    level_nodes[node_index as usize] = hash;
+                }
+            },
+        );
+        self.next_index += 1;
 
-        for level in 0..TREE_DEPTH {
-            let is_left = index % 2 == 0;
+        Ok(leaf_index)
+    }
 
-            if is_left {
-                // Store this as the filled subtree
-                self.filled_subtrees[level] = current;
-                // Hash with zero on the right
-                current = poseidon_hash2(&current, &self.zeros[level]);
-            } else {
-                // Hash with filled subtree on the left
-                current = poseidon_hash2(&self.filled_subtrees[level], &current);
+    /// Rebuild a tree from the nodes of another, as returned by `nodes`
+    ///
+    /// The depth is the number of levels. Checks the shape of the levels but
+    /// not the hashes in them.
+    pub fn from_nodes(nodes: Vec<Vec<Fr>>) -> Result<Self, MerkleError> {
+        let mut tree = Self::with_depth(nodes.len())?;
+        let next_index = nodes[0].len() as u64;
+        if next_index > tree.capacity() {
+            return Err(MerkleError::TreeFull);
+        }
+        for level in 1..tree.depth {
+            if nodes[level].len() != (nodes[level - 1].len() + 1) / 2 {
+                return Err(MerkleError::Corrupt(format!("level {} size", level)));
             }
+        }
 
-            index /= 2;
+        tree.next_index = next_index;
+        tree.nodes = nodes;
+        tree.refresh_frontier();
+        Ok(tree)
+    }
+
+    /// Build a tree of the default depth from its leaves, hashing each
+    /// node once
+    pub fn from_leaves(leaves: &[Fr]) -> Result<Self, MerkleError> {
+        let mut tree = Self::new();
+        tree.insert_batch(leaves)?;
+        Ok(tree)
+    }
+
+    /// Insert several leaves at once
+    ///
+    /// Each level is rebuilt once for the whole batch, so this hashes about
+    /// one node per leaf instead of one per level, spread across threads with
+    /// the `parallel` feature. Returns the index of the first inserted leaf.
+    pub fn insert_batch(&mut self, leaves: &[Fr]) -> Result<u64, MerkleError> {
+        let first_index = self.next_index;
+        if leaves.len() as u64 > self.capacity() - first_index {
+            return Err(MerkleError::TreeFull);
+        }
+        if leaves.is_empty() {
+            return Ok(first_index);
         }
 
-        self.current_root = current;
-        self.next_index += 1;
+        self.nodes[0].extend_from_slice(leaves);
+        let mut first = first_index as usize;
+        for level in 1..self.depth {
+            // Parents of the changed nodes below, from the first one onward
+            first /= 2;
+            let (below, rest) = self.nodes.split_at_mut(level);
+            let below = &below[level - 1];
+            let zero = self.zeros[level - 1];
+            let level_nodes = &mut rest[0];
+            level_nodes.truncate(first);
+            level_nodes.extend(hash_pairs(self.version, &below[first * 2..], &zero));
+        }
+        self.next_index += leaves.len() as u64;
+        self.refresh_frontier();
 
-        Ok(leaf_index)
+        Ok(first_index)
+    }
+
+    /// Derive the filled subtrees and root from `nodes`
+    fn refresh_frontier(&mut self) {
+        // The last left node of each level is its filled subtree
+        for (level, level_nodes) in self.nodes.iter().enumerate() {
+            if let Some(last) = level_nodes.len().checked_sub(1) {
+                self.filled_subtrees[level] = level_nodes[last & !1];
+            }
+        }
+        let top = &self.nodes[self.depth - 1];
+        if let Some(left) = top.first() {
+            let right = top.get(1).unwrap_or(&self.zeros[self.depth - 1]);
+            self.current_root = self.version.hash2(left, right);
+        }
+    }
+
+    /// Non-empty nodes by level, leaves first
+    pub fn nodes(&self) -> &[Vec<Fr>] {
+        &self.nodes
+    }
+
+    /// The tree's frontier, enough to keep appending and tracking the root
+    pub fn frontier(&self) -> MerkleFrontier {
+        MerkleFrontier {
+            version: self.version,
+            next_index: self.next_index,
+            filled_subtrees: self.filled_subtrees.clone(),
+            last_leaf: self.nodes[0].last().copied().unwrap_or(self.zeros[0]),
+            root: self.current_root,
+            zeros: self.zeros,
+        }
+    }
+
+    /// Start an incremental witness for the leaf at `leaf_index`
+    ///
+    /// Keep it current by appending every leaf inserted from now on.
+    pub fn witness(&self, leaf_index: u64) -> Result<IncrementalWitness, MerkleError> {
+        let path = self.generate_proof(leaf_index)?;
+        Ok(IncrementalWitness {
+            leaf: self.nodes[0][leaf_index as usize],
+            path,
+            frontier: self.frontier(),
+        })
     }
 
     /// Get the current root
@@ -210,40 +468,15 @@ impl PoseidonMerkleTree {
             return Err(MerkleError::InvalidLeafIndex(leaf_index));
         }
 
-        let mut siblings = Vec::with_capacity(TREE_DEPTH);
-        let mut indices = Vec::with_capacity(TREE_DEPTH);
-
-        // Build the full tree to get siblings
-        let mut level_nodes = self.leaves.clone();
-
-        // Pad to next power of 2 with zeros
-        while level_nodes.len() < (1 << TREE_DEPTH) {
-            level_nodes.push(self.zeros[0]);
-        }
-
-        let mut current_index = leaf_index as usize;
-
-        for level in 0..TREE_DEPTH {
-            let is_right = current_index % 2 == 1;
-            indices.push(is_right);
-
-            let sibling_index = if is_right {
-                current_index - 1
-            } else {
-                current_index + 1
-            };
-
-            siblings.push(level_nodes[sibling_index]);
+        let mut siblings = Vec::with_capacity(self.depth);
+        let mut indices = Vec::with_capacity(self.depth);
+        let mut index = leaf_index as usize;
 
-            // Compute next level
-            let mut next_level = Vec::with_capacity(level_nodes.len() / 2);
-            for i in (0..level_nodes.len()).step_by(2) {
-                let hash = poseidon_hash2(&level_nodes[i], &level_nodes[i + 1]);
-                next_level.push(hash);
-            }
-            level_nodes = next_level;
-
-            current_index /= 2;
+        for level in 0..self.depth {
+            indices.push(index % 2 == 1);
+            let sibling = self.nodes[level].get(index ^ 1).copied();
+            siblings.push(sibling.unwrap_or(self.zeros[level]));
+            index /= 2;
         }
 
         Ok(MerklePath {
@@ -260,7 +493,7 @@ impl PoseidonMerkleTree {
 
     /// Get the leaf at a given index
     pub fn get_leaf(&self, index: u64) -> Option<Fr> {
-        self.leaves.get(index as usize).copied()
+        self.nodes[0].get(index as usize).copied()
     }
 
     /// Get the number of leaves in the tree
@@ -274,41 +507,353 @@ impl PoseidonMerkleTree {
     }
 }
 
-/// Verify a Merkle proof
+/// Smallest share of a level handed to one rayon task; below this the
+/// scheduling costs more than the Poseidon calls it spreads
+#[cfg(feature = "parallel")]
+const MIN_PAIRS_PER_TASK: usize = 256;
+
+/// Hash adjacent nodes into their parents, pairing a trailing odd node
+/// with `zero`
+#[cfg(feature = "parallel")]
+fn hash_pairs(version: ProtocolVersion, nodes: &[Fr], zero: &Fr) -> Vec<Fr> {
+    use rayon::prelude::*;
+
+    nodes
+        .par_chunks(2)
+        .with_min_len(MIN_PAIRS_PER_TASK)
+        .map(|pair| version.hash2(&pair[0], pair.get(1).unwrap_or(zero)))
+        .collect()
+}
+
+/// Hash adjacent nodes into their parents, pairing a trailing odd node
+/// with `zero`
+#[cfg(not(feature = "parallel"))]
+fn hash_pairs(version: ProtocolVersion, nodes: &[Fr], zero: &Fr) -> Vec<Fr> {
+    nodes
+        .chunks(2)
+        .map(|pair| version.hash2(&pair[0], pair.get(1).unwrap_or(zero)))
+        .collect()
+}
+
+/// Append a leaf at `index` to a frontier of filled subtrees, one per level
+///
+/// Calls `on_node(level, node_index, hash)` for each node on the new leaf's
+/// path, with unfilled right subtrees taken as empty, and returns the root.
+fn append_to_frontier(
+    version: ProtocolVersion,
+    filled_subtrees: &mut [Fr],
+    zeros: &[Fr],
+    index: u64,
+    leaf: Fr,
+    mut on_node: impl FnMut(usize, u64, Fr),
+) -> Fr {
+    let mut current = leaf;
+    let mut index = index;
+
+    for level in 0..filled_subtrees.len() {
+        on_node(level, index, current);
+        if index % 2 == 0 {
+            // Store this as the filled subtree and hash with zero on the right
+            filled_subtrees[level] = current;
+            current = version.hash2(&current, &zeros[level]);
+        } else {
+            // Hash with filled subtree on the left
+            current = version.hash2(&filled_subtrees[level], &current);
+        }
+        index /= 2;
+    }
+
+    current
+}
+
+/// Right edge of a tree: what appending needs, without the leaves
+///
+/// Holds the leaf count, the last leaf and the filled subtree at each level,
+/// from which the root follows. Light clients follow the pool with a
+/// frontier alone and keep `IncrementalWitness`es for their own notes.
+#[derive(Clone, Debug)]
+pub struct MerkleFrontier {
+    version: ProtocolVersion,
+    next_index: u64,
+    filled_subtrees: Vec<Fr>,
+    last_leaf: Fr,
+    root: Fr,
+    zeros: &'static [Fr],
+}
+
+impl Default for MerkleFrontier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for MerkleFrontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.next_index == other.next_index
+            && self.filled_subtrees == other.filled_subtrees
+            && self.last_leaf == other.last_leaf
+    }
+}
+
+impl Eq for MerkleFrontier {}
+
+impl MerkleFrontier {
+    /// Frontier of an empty tree of the default depth
+    pub fn new() -> Self {
+        Self::empty(TREE_DEPTH, ProtocolVersion::V1)
+    }
+
+    /// Frontier of an empty tree of one of the `SUPPORTED_DEPTHS`
+    pub fn with_depth(depth: usize) -> Result<Self, MerkleError> {
+        Self::with_version(depth, ProtocolVersion::V1)
+    }
+
+    /// Frontier of an empty tree hashed as `version`
+    pub fn with_version(depth: usize, version: ProtocolVersion) -> Result<Self, MerkleError> {
+        check_depth(depth)?;
+        Ok(Self::empty(depth, version))
+    }
+
+    fn empty(depth: usize, version: ProtocolVersion) -> Self {
+        let zeros = &zero_hashes(version)[..=depth];
+        Self {
+            version,
+            next_index: 0,
+            filled_subtrees: zeros[..depth].to_vec(),
+            last_leaf: zeros[0],
+            root: zeros[depth],
+            zeros,
+        }
+    }
+
+    /// Rebuild a frontier from its parts, recomputing the root
+    ///
+    /// The depth is the number of filled subtrees. Fails if those on the
+    /// last leaf's path do not match it.
+    pub fn from_parts(
+        next_index: u64,
+        last_leaf: Fr,
+        filled_subtrees: Vec<Fr>,
+    ) -> Result<Self, MerkleError> {
+        Self::from_versioned_parts(ProtocolVersion::V1, next_index, last_leaf, filled_subtrees)
+    }
+
+    /// Rebuild a frontier of a tree hashed as `version` from its parts
+    pub fn from_versioned_parts(
+        version: ProtocolVersion,
+        next_index: u64,
+        last_leaf: Fr,
+        filled_subtrees: Vec<Fr>,
+    ) -> Result<Self, MerkleError> {
+        let mut frontier = Self::with_version(filled_subtrees.len(), version)?;
+        if next_index > frontier.capacity() {
+            return Err(MerkleError::TreeFull);
+        }
+        if next_index == 0 {
+            return if filled_subtrees == frontier.filled_subtrees && last_leaf == frontier.zeros[0] {
+                Ok(frontier)
+            } else {
+                Err(MerkleError::Corrupt("empty frontier with subtrees".into()))
+            };
+        }
+
+        // Walk the last leaf's path; where it is a left child it is itself
+        // the filled subtree
+        let mut current = last_leaf;
+        let mut index = next_index - 1;
+        for (level, filled) in filled_subtrees.iter().enumerate() {
+            if index % 2 == 0 {
+                if *filled != current {
+                    return Err(MerkleError::Corrupt(format!("filled subtree {}", level)));
+                }
+                current = version.hash2(&current, &frontier.zeros[level]);
+            } else {
+                current = version.hash2(filled, &current);
+            }
+            index /= 2;
+        }
+
+        frontier.next_index = next_index;
+        frontier.filled_subtrees = filled_subtrees;
+        frontier.last_leaf = last_leaf;
+        frontier.root = current;
+        Ok(frontier)
+    }
+
+    /// Append the next leaf, returning its index
+    pub fn append(&mut self, leaf: Fr) -> Result<u64, MerkleError> {
+        self.append_with(leaf, |_, _, _| {})
+    }
+
+    fn append_with(
+        &mut self,
+        leaf: Fr,
+        on_node: impl FnMut(usize, u64, Fr),
+    ) -> Result<u64, MerkleError> {
+        if self.next_index >= self.capacity() {
+            return Err(MerkleError::TreeFull);
+        }
+        let leaf_index = self.next_index;
+        self.root = append_to_frontier(
+            self.version,
+            &mut self.filled_subtrees,
+            self.zeros,
+            leaf_index,
+            leaf,
+            on_node,
+        );
+        self.last_leaf = leaf;
+        self.next_index += 1;
+        Ok(leaf_index)
+    }
+
+    /// Current root
+    pub fn root(&self) -> Fr {
+        self.root
+    }
+
+    /// Number of leaves appended
+    pub fn len(&self) -> u64 {
+        self.next_index
+    }
+
+    /// Number of levels below the root
+    pub fn depth(&self) -> usize {
+        self.filled_subtrees.len()
+    }
+
+    /// Hash the tree is built with
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Maximum number of leaves
+    pub fn capacity(&self) -> u64 {
+        1 << self.depth()
+    }
+
+    /// Whether no leaves have been appended
+    pub fn is_empty(&self) -> bool {
+        self.next_index == 0
+    }
+
+    /// The most recent leaf, or the empty leaf
+    pub fn last_leaf(&self) -> Fr {
+        self.last_leaf
+    }
+
+    /// Filled subtree at each level, leaves first
+    pub fn filled_subtrees(&self) -> &[Fr] {
+        &self.filled_subtrees
+    }
+
+    /// Start a witness for the most recently appended leaf
+    pub fn witness_last(&self) -> Option<IncrementalWitness> {
+        let leaf_index = self.next_index.checked_sub(1)?;
+        let (siblings, indices) = (0..self.depth())
+            .map(|level| {
+                // Left siblings are filled subtrees, right ones still empty
+                let is_right = (leaf_index >> level) % 2 == 1;
+                let sibling = if is_right { self.filled_subtrees[level] } else { self.zeros[level] };
+                (sibling, is_right)
+            })
+            .unzip();
+        Some(IncrementalWitness {
+            leaf: self.last_leaf,
+            path: MerklePath {
+                siblings,
+                indices,
+                leaf_index,
+            },
+            frontier: self.clone(),
+        })
+    }
+}
+
+/// Authentication path of one leaf, kept current as the tree grows
+///
+/// Holds the leaf's path and a copy of the tree's frontier. Appending a leaf
+/// advances the frontier and refreshes the one sibling the new leaf's path
+/// changes, in O(depth) hashes, so a wallet keeps spendable paths for its
+/// notes without re-walking the tree.
+#[derive(Clone, Debug)]
+pub struct IncrementalWitness {
+    leaf: Fr,
+    path: MerklePath,
+    frontier: MerkleFrontier,
+}
+
+impl IncrementalWitness {
+    /// Append the next leaf of the tree
+    pub fn append(&mut self, leaf: Fr) -> Result<(), MerkleError> {
+        // Only right siblings can change once the witnessed leaf exists
+        let position = self.path.leaf_index;
+        let siblings = &mut self.path.siblings;
+        self.frontier.append_with(leaf, |level, node_index, hash| {
+            if node_index == (position >> level) ^ 1 {
+                siblings[level] = hash;
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Current path of the witnessed leaf
+    pub fn path(&self) -> MerklePath {
+        self.path.clone()
+    }
+
+    /// The witnessed leaf
+    pub fn leaf(&self) -> Fr {
+        self.leaf
+    }
+
+    /// Index of the witnessed leaf
+    pub fn leaf_index(&self) -> u64 {
+        self.path.leaf_index
+    }
+
+    /// Root of the tree as of the last appended leaf
+    pub fn root(&self) -> Fr {
+        self.frontier.root()
+    }
+
+    /// Number of leaves in the tree as of the last appended leaf
+    pub fn tree_size(&self) -> u64 {
+        self.frontier.len()
+    }
+}
+
+/// Verify a Merkle proof for a tree of any supported depth, taken from the
+/// number of siblings
 pub fn verify_merkle_proof(
     leaf: &Fr,
     leaf_index: u64,
     siblings: &[Fr],
     root: &Fr,
 ) -> bool {
-    if siblings.len() != TREE_DEPTH {
+    if check_depth(siblings.len()).is_err() || leaf_index >> siblings.len() != 0 {
         return false;
     }
-
-    let mut current = *leaf;
-    let mut index = leaf_index;
-
-    for sibling in siblings.iter() {
-        let is_right = index % 2 == 1;
-
-        current = if is_right {
-            poseidon_hash2(sibling, &current)
-        } else {
-            poseidon_hash2(&current, sibling)
-        };
-
-        index /= 2;
-    }
-
-    current == *root
+    let indices = (0..siblings.len()).map(|level| (leaf_index >> level) & 1 == 1);
+    compute_root(leaf, siblings, indices) == *root
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::poseidon::poseidon_hash2;
     use ark_ff::UniformRand;
     use rand::rngs::OsRng;
 
+    #[test]
+    fn test_cached_zero_hashes() {
+        for version in [ProtocolVersion::V1, ProtocolVersion::V2] {
+            assert_eq!(*zero_hashes(version), compute_zero_hashes(version));
+        }
+        assert_eq!(ZERO_HASHES[0], Fr::from(0u64));
+    }
+
     #[test]
     fn test_empty_tree() {
         let tree = PoseidonMerkleTree::new();
@@ -376,6 +921,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_proof_serialization() {
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..3 {
+            tree.insert(Fr::from(i as u64)).unwrap();
+        }
+
+        let proof = tree.generate_proof(2).unwrap();
+        let parsed = MerklePath::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(parsed.leaf_index, 2);
+        assert_eq!(parsed.siblings, proof.siblings);
+        assert_eq!(parsed.indices, proof.indices);
+        assert!(MerklePath::from_bytes(&proof.to_bytes()[1..]).is_none());
+    }
+
     #[test]
     fn test_proof_fails_with_wrong_leaf() {
         let mut tree = PoseidonMerkleTree::new();
@@ -436,6 +996,190 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_proofs_match_full_tree() {
+        // Rebuild a small tree level by level and compare every sibling
+        let mut tree = PoseidonMerkleTree::new();
+        let leaves: Vec<Fr> = (0..13).map(|i| Fr::from(i as u64 + 7)).collect();
+        for leaf in &leaves {
+            tree.insert(*leaf).unwrap();
+        }
+
+        let mut levels = vec![leaves.clone()];
+        for level in 0..TREE_DEPTH {
+            let mut nodes = levels[level].clone();
+            if nodes.len() % 2 == 1 {
+                nodes.push(ZERO_HASHES[level]);
+            }
+            levels.push(nodes.chunks(2).map(|p| poseidon_hash2(&p[0], &p[1])).collect());
+        }
+        assert_eq!(levels[TREE_DEPTH], vec![tree.root()]);
+
+        for i in 0..leaves.len() {
+            let proof = tree.generate_proof(i as u64).unwrap();
+            for (level, nodes) in levels.iter().enumerate().take(TREE_DEPTH) {
+                let sibling = (i >> level) ^ 1;
+                let expected = nodes.get(sibling).copied().unwrap_or(ZERO_HASHES[level]);
+                assert_eq!(proof.siblings[level], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_witness() {
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..5 {
+            tree.insert(Fr::from(i as u64)).unwrap();
+        }
+        let mut early = tree.witness(2).unwrap();
+        let mut latest = tree.witness(4).unwrap();
+
+        for i in 5..40 {
+            let leaf = Fr::from(i as u64);
+            tree.insert(leaf).unwrap();
+            early.append(leaf).unwrap();
+            latest.append(leaf).unwrap();
+
+            for witness in [&early, &latest] {
+                assert_eq!(witness.root(), tree.root());
+                assert_eq!(witness.tree_size(), tree.len());
+                let path = witness.path();
+                assert_eq!(path.siblings, tree.generate_proof(witness.leaf_index()).unwrap().siblings);
+                assert!(path.verify(&witness.leaf(), &tree.root()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_frontier_tracks_tree() {
+        let mut tree = PoseidonMerkleTree::new();
+        let mut frontier = MerkleFrontier::new();
+        let mut witness = None;
+        for i in 0..12u64 {
+            tree.insert(Fr::from(i)).unwrap();
+            assert_eq!(frontier.append(Fr::from(i)).unwrap(), i);
+            assert_eq!(frontier, tree.frontier());
+            assert_eq!(frontier.root(), tree.root());
+            if i == 5 {
+                witness = frontier.witness_last();
+            }
+            if let Some(witness) = &mut witness {
+                if i > 5 {
+                    witness.append(Fr::from(i)).unwrap();
+                }
+                assert!(witness.path().verify(&Fr::from(5u64), &tree.root()));
+            }
+
+            let rebuilt = MerkleFrontier::from_parts(
+                frontier.len(),
+                frontier.last_leaf(),
+                frontier.filled_subtrees().to_vec(),
+            )
+            .unwrap();
+            assert_eq!(rebuilt.root(), tree.root());
+        }
+
+        let mut subtrees = frontier.filled_subtrees().to_vec();
+        subtrees[2] = Fr::from(99u64);
+        assert!(MerkleFrontier::from_parts(12, frontier.last_leaf(), subtrees).is_err());
+    }
+
+    #[test]
+    fn test_insert_batch_matches_sequential() {
+        let leaves: Vec<Fr> = (0..37u64).map(Fr::from).collect();
+        let mut sequential = PoseidonMerkleTree::new();
+        for leaf in &leaves {
+            sequential.insert(*leaf).unwrap();
+        }
+
+        let built = PoseidonMerkleTree::from_leaves(&leaves).unwrap();
+        assert_eq!(built.root(), sequential.root());
+        assert_eq!(built.nodes(), sequential.nodes());
+        assert_eq!(built.frontier(), sequential.frontier());
+
+        // Uneven batches onto a partly filled tree
+        let mut batched = PoseidonMerkleTree::new();
+        let mut start = 0;
+        for size in [1, 0, 2, 5, 8, 21] {
+            assert_eq!(batched.insert_batch(&leaves[start..start + size]).unwrap(), start as u64);
+            start += size;
+        }
+        assert_eq!(batched.len(), 37);
+        assert_eq!(batched.root(), sequential.root());
+        assert_eq!(batched.nodes(), sequential.nodes());
+
+        batched.insert(Fr::from(99u64)).unwrap();
+        sequential.insert(Fr::from(99u64)).unwrap();
+        assert_eq!(batched.root(), sequential.root());
+        assert_eq!(batched.generate_proof(20).unwrap().siblings, sequential.generate_proof(20).unwrap().siblings);
+    }
+
+    #[test]
+    fn test_poseidon2_tree() {
+        let v2 = ProtocolVersion::V2;
+        let mut tree = PoseidonMerkleTree::with_version(TREE_DEPTH, v2).unwrap();
+        let mut frontier = MerkleFrontier::with_version(TREE_DEPTH, v2).unwrap();
+        let mut v1 = PoseidonMerkleTree::new();
+        assert_ne!(tree.root(), v1.root());
+
+        let leaves: Vec<Fr> = (0..9u64).map(Fr::from).collect();
+        tree.insert_batch(&leaves[..4]).unwrap();
+        for leaf in &leaves[4..] {
+            tree.insert(*leaf).unwrap();
+        }
+        for leaf in &leaves {
+            frontier.append(*leaf).unwrap();
+            v1.insert(*leaf).unwrap();
+        }
+        assert_eq!(frontier, tree.frontier());
+        assert_eq!(frontier.root(), tree.root());
+        assert_ne!(tree.root(), v1.root());
+
+        let path = tree.generate_proof(6).unwrap();
+        assert!(path.verify_with(v2, &leaves[6], &tree.root()));
+        assert!(!path.verify(&leaves[6], &tree.root()));
+
+        let rebuilt = MerkleFrontier::from_versioned_parts(
+            v2,
+            frontier.len(),
+            frontier.last_leaf(),
+            frontier.filled_subtrees().to_vec(),
+        )
+        .unwrap();
+        assert_eq!(rebuilt.root(), tree.root());
+    }
+
+    #[test]
+    fn test_other_depths() {
+        assert!(matches!(PoseidonMerkleTree::with_depth(21), Err(MerkleError::UnsupportedDepth(21))));
+
+        for depth in [24, MAX_TREE_DEPTH] {
+            let mut tree = PoseidonMerkleTree::with_depth(depth).unwrap();
+            assert_eq!(tree.root(), get_zero_hash(depth));
+            let mut frontier = MerkleFrontier::with_depth(depth).unwrap();
+            for i in 1..6u64 {
+                tree.insert(Fr::from(i)).unwrap();
+                frontier.append(Fr::from(i)).unwrap();
+            }
+            assert_eq!(frontier.root(), tree.root());
+            assert_eq!(PoseidonMerkleTree::from_nodes(tree.nodes().to_vec()).unwrap().root(), tree.root());
+
+            let path = tree.generate_proof(3).unwrap();
+            assert_eq!(path.depth(), depth);
+            assert!(path.verify(&Fr::from(4u64), &tree.root()));
+            assert!(verify_merkle_proof(&Fr::from(4u64), 3, &path.siblings, &tree.root()));
+            assert!(path.check_depth(depth).is_ok());
+            assert!(matches!(
+                path.check_depth(TREE_DEPTH),
+                Err(MerkleError::DepthMismatch { expected: TREE_DEPTH, actual }) if actual == depth
+            ));
+
+            let parsed = MerklePath::from_bytes(&path.to_bytes()).unwrap();
+            assert_eq!(parsed.siblings, path.siblings);
+            assert_eq!(parsed.indices, path.indices);
+        }
+    }
+
     #[test]
     fn test_verify_merkle_proof_function() {
         let mut tree = PoseidonMerkleTree::new();
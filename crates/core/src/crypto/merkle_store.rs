@@ -0,0 +1,525 @@
+//! Persistent Commitment Tree
+//!
+//! `PersistentMerkleTree` keeps a `PoseidonMerkleTree` in memory and writes
+//! every leaf through to a `TreeStorage` backend, so a restarted client
+//! reopens its tree instead of re-downloading and re-hashing the pool.
+//!
+//! Storage holds two things:
+//! - The leaves, appended durably before they enter the in-memory tree
+//! - A snapshot of the tree's nodes, rewritten every `snapshot_interval`
+//!   leaves, so reopening only hashes the leaves added since
+//!
+//! On open the snapshot is used if it is intact and agrees with the stored
+//! leaves; otherwise the tree is rebuilt from the leaves alone. Leaves are
+//! the source of truth, the snapshot is a cache.
+//!
+//! `FileStorage` keeps both in a directory. Other backends (sled, RocksDB,
+//! IndexedDB) only need to implement the four methods of `TreeStorage`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+
+use super::merkle::{check_depth, MerkleError, PoseidonMerkleTree, TREE_DEPTH};
+
+/// Leaves between automatic node snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1 << 16;
+
+/// Leading bytes of a node snapshot
+const SNAPSHOT_MAGIC: &[u8; 4] = b"VMTN";
+
+/// Version of the node snapshot format
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// Size of the checksum closing a snapshot or following each stored leaf
+const CHECKSUM_SIZE: usize = 4;
+
+/// Size of one leaf record in `FileStorage`
+const LEAF_RECORD_SIZE: usize = 32 + CHECKSUM_SIZE;
+
+/// Backend for a `PersistentMerkleTree`
+///
+/// Leaves are little-endian field elements. Snapshots are opaque bytes.
+pub trait TreeStorage {
+    /// Append leaves after the stored ones; they must be durable on return
+    fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<(), MerkleError>;
+
+    /// All stored leaves in order
+    ///
+    /// A backend may drop a partially written final leaf, which was never
+    /// acknowledged by `append_leaves`.
+    fn load_leaves(&mut self) -> Result<Vec<[u8; 32]>, MerkleError>;
+
+    /// Replace the stored snapshot atomically
+    fn save_snapshot(&mut self, snapshot: &[u8]) -> Result<(), MerkleError>;
+
+    /// The stored snapshot, if any
+    fn load_snapshot(&self) -> Result<Option<Vec<u8>>, MerkleError>;
+}
+
+/// Storage that lives and dies with the process, for tests and browsers
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    leaves: Vec<[u8; 32]>,
+    snapshot: Option<Vec<u8>>,
+}
+
+impl TreeStorage for MemoryStorage {
+    fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<(), MerkleError> {
+        self.leaves.extend_from_slice(leaves);
+        Ok(())
+    }
+
+    fn load_leaves(&mut self) -> Result<Vec<[u8; 32]>, MerkleError> {
+        Ok(self.leaves.clone())
+    }
+
+    fn save_snapshot(&mut self, snapshot: &[u8]) -> Result<(), MerkleError> {
+        self.snapshot = Some(snapshot.to_vec());
+        Ok(())
+    }
+
+    fn load_snapshot(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self.snapshot.clone())
+    }
+}
+
+/// Storage in a directory: `leaves.bin` and `nodes.bin`
+///
+/// Leaves are appended as fixed-size records, each followed by a checksum,
+/// and synced before `append_leaves` returns. A torn final record left by a
+/// crash is cut off on load. The snapshot is replaced via a temporary file
+/// and a rename.
+#[derive(Debug)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Use `dir`, creating it if needed
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, MerkleError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(storage_error)?;
+        Ok(Self { dir })
+    }
+
+    fn leaves_path(&self) -> PathBuf {
+        self.dir.join("leaves.bin")
+    }
+
+    fn snapshot_path(&self) -> PathBuf {
+        self.dir.join("nodes.bin")
+    }
+}
+
+impl TreeStorage for FileStorage {
+    fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<(), MerkleError> {
+        let mut records = Vec::with_capacity(leaves.len() * LEAF_RECORD_SIZE);
+        for leaf in leaves {
+            records.extend_from_slice(leaf);
+            records.extend_from_slice(&checksum(leaf));
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.leaves_path())
+            .map_err(storage_error)?;
+        file.write_all(&records).map_err(storage_error)?;
+        file.sync_data().map_err(storage_error)
+    }
+
+    fn load_leaves(&mut self) -> Result<Vec<[u8; 32]>, MerkleError> {
+        let path = self.leaves_path();
+        let mut bytes = Vec::new();
+        match File::open(&path) {
+            Ok(mut file) => file.read_to_end(&mut bytes).map_err(storage_error)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(e)),
+        };
+
+        let complete = bytes.len() - bytes.len() % LEAF_RECORD_SIZE;
+        if complete < bytes.len() {
+            let file = OpenOptions::new().write(true).open(&path).map_err(storage_error)?;
+            file.set_len(complete as u64).map_err(storage_error)?;
+            file.sync_data().map_err(storage_error)?;
+        }
+
+        bytes[..complete]
+            .chunks(LEAF_RECORD_SIZE)
+            .enumerate()
+            .map(|(i, record)| {
+                let (leaf, sum) = record.split_at(32);
+                if checksum(leaf) != sum {
+                    return Err(MerkleError::Corrupt(format!("leaf {} checksum", i)));
+                }
+                Ok(leaf.try_into().unwrap())
+            })
+            .collect()
+    }
+
+    fn save_snapshot(&mut self, snapshot: &[u8]) -> Result<(), MerkleError> {
+        let path = self.snapshot_path();
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp).map_err(storage_error)?;
+        file.write_all(snapshot).map_err(storage_error)?;
+        file.sync_data().map_err(storage_error)?;
+        fs::rename(&tmp, &path).map_err(storage_error)
+    }
+
+    fn load_snapshot(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        match fs::read(self.snapshot_path()) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+}
+
+/// A commitment tree written through to storage
+#[derive(Debug)]
+pub struct PersistentMerkleTree<S: TreeStorage> {
+    tree: PoseidonMerkleTree,
+    storage: S,
+    snapshot_interval: u64,
+    /// Leaves covered by the stored snapshot
+    snapshot_len: u64,
+}
+
+impl<S: TreeStorage> PersistentMerkleTree<S> {
+    /// Open the tree of the default depth held by `storage`, which may be
+    /// empty
+    pub fn open(storage: S) -> Result<Self, MerkleError> {
+        Self::with_options(storage, TREE_DEPTH, DEFAULT_SNAPSHOT_INTERVAL)
+    }
+
+    /// Open with a snapshot every `snapshot_interval` leaves; 0 only
+    /// snapshots on `checkpoint`
+    pub fn with_snapshot_interval(storage: S, snapshot_interval: u64) -> Result<Self, MerkleError> {
+        Self::with_options(storage, TREE_DEPTH, snapshot_interval)
+    }
+
+    /// Open a tree of `depth`, one of the supported depths, with a snapshot
+    /// every `snapshot_interval` leaves
+    pub fn with_options(
+        mut storage: S,
+        depth: usize,
+        snapshot_interval: u64,
+    ) -> Result<Self, MerkleError> {
+        let mut tree = PoseidonMerkleTree::with_depth(depth)?;
+        let leaves = storage.load_leaves()?;
+        if leaves.len() as u64 > tree.capacity() {
+            return Err(MerkleError::TreeFull);
+        }
+        let leaves: Vec<Fr> = leaves.iter().map(|l| Fr::from_le_bytes_mod_order(l)).collect();
+
+        // A snapshot is only usable if it is of this depth and its leaves
+        // are a prefix of ours
+        let cached = match storage.load_snapshot()? {
+            Some(bytes) => decode_snapshot(&bytes).ok().filter(|cached| {
+                let cached_leaves = &cached.nodes()[0];
+                cached.depth() == depth
+                    && cached_leaves.len() <= leaves.len()
+                    && cached_leaves[..] == leaves[..cached_leaves.len()]
+            }),
+            None => None,
+        };
+        if let Some(cached) = cached {
+            tree = cached;
+        }
+        let snapshot_len = tree.len();
+        tree.insert_batch(&leaves[snapshot_len as usize..])?;
+
+        Ok(Self {
+            tree,
+            storage,
+            snapshot_interval,
+            snapshot_len,
+        })
+    }
+
+    /// Append a leaf, durably, returning its index
+    pub fn insert(&mut self, leaf: Fr) -> Result<u64, MerkleError> {
+        if self.tree.len() >= self.tree.capacity() {
+            return Err(MerkleError::TreeFull);
+        }
+        self.storage.append_leaves(&[fr_to_bytes(&leaf)])?;
+        let index = self.tree.insert(leaf)?;
+
+        if self.snapshot_interval != 0
+            && self.tree.len() - self.snapshot_len >= self.snapshot_interval
+        {
+            self.checkpoint()?;
+        }
+        Ok(index)
+    }
+
+    /// Append several leaves with one storage write, returning the first index
+    pub fn insert_batch(&mut self, leaves: &[Fr]) -> Result<u64, MerkleError> {
+        if leaves.len() as u64 > self.tree.capacity() - self.tree.len() {
+            return Err(MerkleError::TreeFull);
+        }
+        let bytes: Vec<[u8; 32]> = leaves.iter().map(fr_to_bytes).collect();
+        self.storage.append_leaves(&bytes)?;
+        let index = self.tree.insert_batch(leaves)?;
+
+        if self.snapshot_interval != 0
+            && self.tree.len() - self.snapshot_len >= self.snapshot_interval
+        {
+            self.checkpoint()?;
+        }
+        Ok(index)
+    }
+
+    /// Snapshot the current nodes, so the next open starts from here
+    pub fn checkpoint(&mut self) -> Result<(), MerkleError> {
+        self.storage.save_snapshot(&encode_snapshot(&self.tree))?;
+        self.snapshot_len = self.tree.len();
+        Ok(())
+    }
+
+    /// Check the tree against its stored leaves
+    ///
+    /// Rehashes every stored leaf, so it costs as much as a full rebuild.
+    /// Fails if the stored leaves, the nodes in memory or the stored
+    /// snapshot disagree.
+    pub fn verify_integrity(&mut self) -> Result<(), MerkleError> {
+        let leaves: Vec<Fr> = self
+            .storage
+            .load_leaves()?
+            .iter()
+            .map(|l| Fr::from_le_bytes_mod_order(l))
+            .collect();
+        let mut rebuilt = PoseidonMerkleTree::with_depth(self.tree.depth())?;
+        rebuilt.insert_batch(&leaves)?;
+        if rebuilt.nodes() != self.tree.nodes() {
+            return Err(MerkleError::Corrupt("nodes differ from stored leaves".into()));
+        }
+
+        if let Some(bytes) = self.storage.load_snapshot()? {
+            let snapshot = decode_snapshot(&bytes)?;
+            let len = snapshot.len();
+            let nodes = self.tree.nodes();
+            let consistent = snapshot.depth() == self.tree.depth()
+                && len <= self.tree.len()
+                && snapshot
+                    .nodes()
+                    .iter()
+                    .zip(nodes)
+                    .enumerate()
+                    .all(|(level, (cached, current))| {
+                        // A level's last node can still change after the snapshot
+                        let settled = ((len >> level) as usize).min(cached.len());
+                        cached[..settled] == current[..settled]
+                    });
+            if !consistent {
+                return Err(MerkleError::Corrupt("snapshot differs from stored leaves".into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The tree in memory
+    pub fn tree(&self) -> &PoseidonMerkleTree {
+        &self.tree
+    }
+
+    /// Current root
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Number of leaves
+    pub fn len(&self) -> u64 {
+        self.tree.len()
+    }
+
+    /// Whether the tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// The storage backend
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+}
+
+fn storage_error(e: std::io::Error) -> MerkleError {
+    MerkleError::Storage(e.to_string())
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    blake3::hash(bytes).as_bytes()[..CHECKSUM_SIZE].try_into().unwrap()
+}
+
+fn fr_to_bytes(value: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&value.into_bigint().to_bytes_le());
+    bytes
+}
+
+/// magic || version || depth || per level: count (u64 LE) || nodes || checksum
+fn encode_snapshot(tree: &PoseidonMerkleTree) -> Vec<u8> {
+    let nodes = tree.nodes();
+    let count: usize = nodes.iter().map(Vec::len).sum();
+    let mut bytes = Vec::with_capacity(6 + nodes.len() * 8 + count * 32 + CHECKSUM_SIZE);
+    bytes.extend_from_slice(SNAPSHOT_MAGIC);
+    bytes.push(SNAPSHOT_VERSION);
+    bytes.push(tree.depth() as u8);
+    for level in nodes {
+        bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        for node in level {
+            bytes.extend_from_slice(&fr_to_bytes(node));
+        }
+    }
+    let sum = checksum(&bytes);
+    bytes.extend_from_slice(&sum);
+    bytes
+}
+
+fn decode_snapshot(bytes: &[u8]) -> Result<PoseidonMerkleTree, MerkleError> {
+    let corrupt = |what: &str| MerkleError::Corrupt(format!("snapshot {}", what));
+    if bytes.len() < 6 + CHECKSUM_SIZE {
+        return Err(corrupt("truncated"));
+    }
+    let (body, sum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+    if checksum(body) != sum {
+        return Err(corrupt("checksum"));
+    }
+    if &body[..4] != SNAPSHOT_MAGIC || body[4] != SNAPSHOT_VERSION {
+        return Err(corrupt("header"));
+    }
+    let depth = body[5] as usize;
+    check_depth(depth).map_err(|_| corrupt("depth"))?;
+
+    let mut rest = &body[6..];
+    let mut nodes = Vec::with_capacity(depth);
+    for _ in 0..depth {
+        if rest.len() < 8 {
+            return Err(corrupt("truncated"));
+        }
+        let count = u64::from_le_bytes(rest[..8].try_into().unwrap());
+        rest = &rest[8..];
+        let size = usize::try_from(count)
+            .ok()
+            .and_then(|n| n.checked_mul(32))
+            .filter(|&size| size <= rest.len())
+            .ok_or_else(|| corrupt("truncated"))?;
+        nodes.push(rest[..size].chunks(32).map(Fr::from_le_bytes_mod_order).collect());
+        rest = &rest[size..];
+    }
+    if !rest.is_empty() {
+        return Err(corrupt("trailing bytes"));
+    }
+    PoseidonMerkleTree::from_nodes(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("veil_tree_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn reference(n: u64) -> PoseidonMerkleTree {
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..n {
+            tree.insert(Fr::from(i + 1)).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn test_reopen_from_snapshot_and_tail() {
+        let dir = temp_dir("reopen");
+        let mut tree =
+            PersistentMerkleTree::with_snapshot_interval(FileStorage::open(&dir).unwrap(), 4)
+                .unwrap();
+        for i in 0..11 {
+            assert_eq!(tree.insert(Fr::from(i + 1)).unwrap(), i);
+        }
+        assert_eq!(tree.root(), reference(11).root());
+
+        // Snapshot covers 8 leaves; the other 3 are replayed
+        let mut reopened = PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(reopened.snapshot_len, 8);
+        assert_eq!(reopened.len(), 11);
+        assert_eq!(reopened.root(), reference(11).root());
+        reopened.verify_integrity().unwrap();
+
+        reopened.insert(Fr::from(12u64)).unwrap();
+        assert_eq!(reopened.root(), reference(12).root());
+        assert!(reopened.tree().generate_proof(11).unwrap().verify(&Fr::from(12u64), &reopened.root()));
+
+        let batch: Vec<Fr> = (13..=15u64).map(Fr::from).collect();
+        assert_eq!(reopened.insert_batch(&batch).unwrap(), 12);
+        assert_eq!(reopened.root(), reference(15).root());
+        drop(reopened);
+        let reopened = PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(reopened.root(), reference(15).root());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_append_is_dropped() {
+        let dir = temp_dir("torn");
+        let mut tree = PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).unwrap();
+        for i in 0..3 {
+            tree.insert(Fr::from(i + 1)).unwrap();
+        }
+        drop(tree);
+
+        // A crash in the middle of writing a fourth record
+        let mut file = OpenOptions::new().append(true).open(dir.join("leaves.bin")).unwrap();
+        file.write_all(&[0xab; LEAF_RECORD_SIZE / 2]).unwrap();
+        drop(file);
+
+        let mut tree = PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(tree.len(), 3);
+        tree.insert(Fr::from(4u64)).unwrap();
+        assert_eq!(tree.root(), reference(4).root());
+        tree.verify_integrity().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_ignored() {
+        let mut storage = MemoryStorage::default();
+        storage.append_leaves(&[fr_to_bytes(&Fr::from(9u64))]).unwrap();
+        storage.save_snapshot(&encode_snapshot(&reference(1))).unwrap();
+
+        // The snapshot's leaf disagrees with the stored one
+        let mut tree = PersistentMerkleTree::open(storage).unwrap();
+        let mut expected = PoseidonMerkleTree::new();
+        expected.insert(Fr::from(9u64)).unwrap();
+        assert_eq!(tree.root(), expected.root());
+        assert!(matches!(tree.verify_integrity(), Err(MerkleError::Corrupt(_))));
+
+        // A fresh checkpoint repairs it
+        tree.checkpoint().unwrap();
+        tree.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_leaf_detected() {
+        let dir = temp_dir("corrupt");
+        let mut tree = PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).unwrap();
+        tree.insert(Fr::from(1u64)).unwrap();
+        tree.insert(Fr::from(2u64)).unwrap();
+
+        let path = dir.join("leaves.bin");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[LEAF_RECORD_SIZE] ^= 1;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(tree.verify_integrity(), Err(MerkleError::Corrupt(_))));
+        assert!(PersistentMerkleTree::open(FileStorage::open(&dir).unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
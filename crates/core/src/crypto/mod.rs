@@ -1,16 +1,66 @@
 //! Cryptographic primitives for privacy operations
+//!
+//! Only `poseidon_constants` is available without the `std` feature.
 
+#[cfg(feature = "std")]
+pub mod address;
+#[cfg(feature = "std")]
 pub mod commitment;
+#[cfg(feature = "std")]
+pub mod ct;
+#[cfg(feature = "std")]
 pub mod encryption;
+#[cfg(feature = "std")]
+pub mod frontier_checkpoint;
+#[cfg(feature = "std")]
 pub mod merkle;
+#[cfg(feature = "std")]
+pub mod merkle_store;
+#[cfg(feature = "std")]
 pub mod nullifier;
+#[cfg(feature = "std")]
+pub mod nullifier_set;
+#[cfg(feature = "std")]
 pub mod poseidon;
+#[cfg(feature = "std")]
+pub mod poseidon2;
+#[cfg(feature = "std")]
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod serialization;
+#[cfg(feature = "std")]
+pub mod test_vectors;
 pub mod poseidon_constants;
 
+#[cfg(feature = "std")]
+pub use address::{Diversifier, DiversifiedAddress};
+#[cfg(feature = "std")]
 pub use commitment::{Commitment, CommitmentPoint};
-pub use encryption::{decrypt_note, encrypt_note, EncryptedNote, EncryptionKeypair, NoteData};
-pub use merkle::{MerklePath, PoseidonMerkleTree};
+#[cfg(feature = "std")]
+pub use ct::{ct_eq, ct_eq_field};
+#[cfg(feature = "std")]
+pub use encryption::{
+    decrypt_note, decrypt_outgoing, encrypt_note, encrypt_note_to_address, encrypt_note_with_ovk,
+    EncryptedNote, EncryptionKeypair, NoteCiphertext, NoteData, OutgoingNote, SentNoteData,
+};
+#[cfg(feature = "std")]
+pub use frontier_checkpoint::FrontierCheckpoint;
+#[cfg(feature = "std")]
+pub use merkle::{
+    zero_hashes, IncrementalWitness, MerkleFrontier, MerklePath, PoseidonMerkleTree, ZERO_HASHES,
+};
+#[cfg(feature = "std")]
+pub use merkle_store::{FileStorage, MemoryStorage, PersistentMerkleTree, TreeStorage};
+#[cfg(feature = "std")]
 #[allow(deprecated)]
 pub use nullifier::generate_nullifier_hash;
-pub use nullifier::{Note, Nullifier, SpendingKey};
+#[cfg(feature = "std")]
+pub use nullifier::{Note, Nullifier, OutgoingViewingKey, SpendingKey, ViewingKey};
+#[cfg(feature = "std")]
+pub use nullifier_set::{NullifierDiff, NullifierSet};
+#[cfg(feature = "std")]
 pub use poseidon::{poseidon_hash2, poseidon_hash_bytes, poseidon_hash_fields};
+#[cfg(feature = "std")]
+pub use poseidon2::poseidon2_hash2;
+#[cfg(feature = "std")]
+pub use protocol::ProtocolVersion;
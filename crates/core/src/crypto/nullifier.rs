@@ -13,17 +13,38 @@
 //! - Given a nullifier, an attacker cannot recover the secret
 //! - Given a spending_key, an attacker cannot recover the secret
 //! - Different leaf indices produce different nullifiers (even for same secret)
+//!
+//! The incoming viewing key, viewing_key = Poseidon(secret, viewing_domain),
+//! is the note encryption key (see `EncryptionKeypair::from_viewing_key`).
+//! It decrypts incoming notes but cannot derive the spending key, so it can
+//! be shared with auditors or watch-only wallets.
+//!
+//! The outgoing viewing key, Poseidon(secret, outgoing_domain), lets a sender
+//! recover notes they sent (see `encrypt_note_with_ovk`).
+//!
+//! Keys and notes zeroize their secrets on drop.
+//!
+//! Commitments and nullifiers use Poseidon unless built for another
+//! `ProtocolVersion` (`commitment_with`, `nullifier_with`); keys always use
+//! Poseidon.
 
 use ark_bn254::Fr;
 use ark_ff::{BigInteger, PrimeField};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
-use super::poseidon::poseidon_hash2;
+use super::ct::ct_eq_field;
+use super::poseidon::{poseidon_hash2, poseidon_hash4};
+use super::protocol::ProtocolVersion;
 
 /// Domain separator for spending key derivation
 const SPENDING_KEY_DOMAIN: &[u8] = b"NYX_SPENDING_KEY";
 /// Domain separator for nullifier derivation
 const NULLIFIER_DOMAIN: &[u8] = b"NYX_NULLIFIER";
+/// Domain separator for viewing key derivation
+const VIEWING_KEY_DOMAIN: &[u8] = b"NYX_VIEWING_KEY";
+/// Domain separator for outgoing viewing key derivation
+const OUTGOING_VIEWING_KEY_DOMAIN: &[u8] = b"NYX_OUTGOING_VIEWING_KEY";
 
 #[derive(Error, Debug)]
 pub enum NullifierError {
@@ -67,7 +88,92 @@ impl SpendingKey {
 
     /// Serialize to 32 bytes
     pub fn to_bytes(&self) -> [u8; 32] {
-        let bytes = self.key.into_bigint().to_bytes_le();
+        let bytes = Zeroizing::new(self.key.into_bigint().to_bytes_le());
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&bytes[..32]);
+        result
+    }
+
+    /// Deserialize from 32 bytes
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        let key = Fr::from_le_bytes_mod_order(bytes);
+        Self { key }
+    }
+}
+
+/// Incoming viewing key derived from a secret
+///
+/// Decrypts notes sent to the secret's owner without granting the ability
+/// to spend them.
+#[derive(Clone, Debug)]
+pub struct ViewingKey {
+    key: Fr,
+}
+
+impl ViewingKey {
+    /// Derive viewing key from a 32-byte secret
+    pub fn from_secret(secret: &[u8; 32]) -> Self {
+        let secret_fr = Fr::from_le_bytes_mod_order(secret);
+        let domain_fr = Fr::from_le_bytes_mod_order(VIEWING_KEY_DOMAIN);
+
+        let key = poseidon_hash2(&secret_fr, &domain_fr);
+
+        Self { key }
+    }
+
+    /// Create from an existing field element
+    pub fn from_field(key: Fr) -> Self {
+        Self { key }
+    }
+
+    /// Get the underlying field element
+    pub fn as_field(&self) -> &Fr {
+        &self.key
+    }
+
+    /// Serialize to 32 bytes
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let bytes = Zeroizing::new(self.key.into_bigint().to_bytes_le());
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&bytes[..32]);
+        result
+    }
+
+    /// Deserialize from 32 bytes
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        let key = Fr::from_le_bytes_mod_order(bytes);
+        Self { key }
+    }
+}
+
+/// Outgoing viewing key derived from a secret
+///
+/// Decrypts the sender's copy of notes the secret's owner sent, recovering
+/// recipient, amount and blinding after the fact.
+#[derive(Clone, Debug)]
+pub struct OutgoingViewingKey {
+    key: Fr,
+}
+
+impl OutgoingViewingKey {
+    /// Derive outgoing viewing key from a 32-byte secret
+    pub fn from_secret(secret: &[u8; 32]) -> Self {
+        let secret_fr = Fr::from_le_bytes_mod_order(secret);
+        let domain_fr = Fr::from_le_bytes_mod_order(OUTGOING_VIEWING_KEY_DOMAIN);
+
+        let key = poseidon_hash2(&secret_fr, &domain_fr);
+
+        Self { key }
+    }
+
+    /// Get the underlying field element
+    pub fn as_field(&self) -> &Fr {
+        &self.key
+    }
+
+    /// Serialize to 32 bytes
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let bytes = Zeroizing::new(self.key.into_bigint().to_bytes_le());
         let mut result = [0u8; 32];
         result.copy_from_slice(&bytes[..32]);
         result
@@ -80,8 +186,63 @@ impl SpendingKey {
     }
 }
 
+// Keys and nullifiers compare in constant time
+impl PartialEq for ViewingKey {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq_field(&self.key, &other.key)
+    }
+}
+
+impl Eq for ViewingKey {}
+
+impl PartialEq for OutgoingViewingKey {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq_field(&self.key, &other.key)
+    }
+}
+
+impl Eq for OutgoingViewingKey {}
+
+// zeroize is held below 1.4 (no `ZeroizeOnDrop`), so each secret-bearing
+// type wipes itself in `Drop`
+impl Zeroize for SpendingKey {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Drop for SpendingKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Zeroize for ViewingKey {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Drop for ViewingKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Zeroize for OutgoingViewingKey {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Drop for OutgoingViewingKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// A nullifier that can be used to prevent double-spending
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct Nullifier {
     value: Fr,
 }
@@ -91,6 +252,15 @@ impl Nullifier {
     ///
     /// nullifier = Poseidon(spending_key, leaf_index || domain)
     pub fn derive(spending_key: &SpendingKey, leaf_index: u64) -> Self {
+        Self::derive_with(ProtocolVersion::V1, spending_key, leaf_index)
+    }
+
+    /// Derive the nullifier for a pool on `version`
+    pub fn derive_with(
+        version: ProtocolVersion,
+        spending_key: &SpendingKey,
+        leaf_index: u64,
+    ) -> Self {
         // Combine leaf index with domain separator
         let index_with_domain = {
             let mut hasher = blake3::Hasher::new();
@@ -100,7 +270,7 @@ impl Nullifier {
             Fr::from_le_bytes_mod_order(hash.as_bytes())
         };
 
-        let value = poseidon_hash2(&spending_key.key, &index_with_domain);
+        let value = version.hash2(&spending_key.key, &index_with_domain);
 
         Self { value }
     }
@@ -140,6 +310,14 @@ impl Nullifier {
     }
 }
 
+impl PartialEq for Nullifier {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq_field(&self.value, &other.value)
+    }
+}
+
+impl Eq for Nullifier {}
+
 /// Note: a complete representation of a shielded note
 ///
 /// Contains all the information needed to spend a note:
@@ -198,35 +376,66 @@ impl Note {
         SpendingKey::from_secret(&self.secret)
     }
 
+    /// Get the viewing key for this note's owner
+    pub fn viewing_key(&self) -> ViewingKey {
+        ViewingKey::from_secret(&self.secret)
+    }
+
+    /// Get the outgoing viewing key for this note's owner
+    pub fn outgoing_viewing_key(&self) -> OutgoingViewingKey {
+        OutgoingViewingKey::from_secret(&self.secret)
+    }
+
     /// Get the nullifier for this note
     ///
     /// Panics if leaf_index is not set
     pub fn nullifier(&self) -> Nullifier {
+        self.nullifier_with(ProtocolVersion::V1)
+    }
+
+    /// Get the nullifier for this note in a pool on `version`
+    ///
+    /// Panics if leaf_index is not set
+    pub fn nullifier_with(&self, version: ProtocolVersion) -> Nullifier {
         let leaf_index = self.leaf_index
             .expect("Cannot compute nullifier without leaf_index");
-        Nullifier::from_secret(&self.secret, leaf_index)
+        Nullifier::derive_with(version, &self.spending_key(), leaf_index)
     }
 
     /// Compute the note commitment using Poseidon
     ///
-    /// commitment = Poseidon(spending_key, amount, blinding, asset_id)
+    /// commitment = Poseidon(spending_key, amount, blinding, asset_id), one
+    /// t = 5 permutation
     pub fn commitment(&self) -> Fr {
-        let spending_key = self.spending_key();
+        self.commitment_with(ProtocolVersion::V1)
+    }
 
-        // Hash the note components
-        // Using multiple hash2 calls to handle 4 inputs
+    /// Compute the note commitment for a pool on `version`
+    ///
+    /// Poseidon2 has no wider instance here, so version 2 chains three
+    /// two-input hashes: H(H(spending_key, amount), H(blinding, asset_id)).
+    pub fn commitment_with(&self, version: ProtocolVersion) -> Fr {
+        let spending_key = self.spending_key();
         let amount_fr = Fr::from(self.amount);
 
-        let h1 = poseidon_hash2(spending_key.as_field(), &amount_fr);
-        let h2 = poseidon_hash2(&self.blinding, &self.asset_id);
-        poseidon_hash2(&h1, &h2)
+        match version {
+            ProtocolVersion::V1 => {
+                poseidon_hash4(spending_key.as_field(), &amount_fr, &self.blinding, &self.asset_id)
+            }
+            _ => {
+                let h1 = version.hash2(spending_key.as_field(), &amount_fr);
+                let h2 = version.hash2(&self.blinding, &self.asset_id);
+                version.hash2(&h1, &h2)
+            }
+        }
     }
 
-    /// Serialize note to bytes (for storage)
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(32 + 32 + 8 + 32 + 8);
+    /// Serialize note to bytes (for storage), wiped when dropped
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(32 + 32 + 8 + 32 + 8));
         bytes.extend_from_slice(&self.secret);
-        bytes.extend_from_slice(&self.blinding.into_bigint().to_bytes_le()[..32]);
+        let blinding = Zeroizing::new(self.blinding.into_bigint().to_bytes_le());
+        bytes.extend_from_slice(&blinding[..32]);
         bytes.extend_from_slice(&self.amount.to_le_bytes());
         bytes.extend_from_slice(&self.asset_id.into_bigint().to_bytes_le()[..32]);
         if let Some(idx) = self.leaf_index {
@@ -234,6 +443,37 @@ impl Note {
         }
         bytes
     }
+
+    /// Parse a note serialized with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 104 && bytes.len() != 112 {
+            return None;
+        }
+        let mut note = Self::new(
+            bytes[..32].try_into().ok()?,
+            u64::from_le_bytes(bytes[64..72].try_into().ok()?),
+            Fr::from_le_bytes_mod_order(&bytes[72..104]),
+            Fr::from_le_bytes_mod_order(&bytes[32..64]),
+        );
+        if bytes.len() == 112 {
+            note.set_leaf_index(u64::from_le_bytes(bytes[104..].try_into().ok()?));
+        }
+        Some(note)
+    }
+}
+
+impl Zeroize for Note {
+    fn zeroize(&mut self) {
+        self.secret.zeroize();
+        self.blinding.zeroize();
+        self.amount.zeroize();
+    }
+}
+
+impl Drop for Note {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
 }
 
 // ============================================================================
@@ -386,6 +626,35 @@ mod tests {
         assert_eq!(nullifier.to_bytes().len(), 32);
     }
 
+    #[test]
+    fn test_protocol_versions() {
+        let mut note = Note::new([3u8; 32], 1000, Fr::from(0u64), Fr::from(5u64));
+        note.set_leaf_index(7);
+
+        assert_eq!(note.commitment_with(ProtocolVersion::V1), note.commitment());
+        assert_ne!(note.commitment_with(ProtocolVersion::V2), note.commitment());
+        assert_eq!(note.nullifier_with(ProtocolVersion::V1), note.nullifier());
+        assert_ne!(note.nullifier_with(ProtocolVersion::V2), note.nullifier());
+        assert_eq!(
+            note.nullifier_with(ProtocolVersion::V2),
+            Nullifier::derive_with(ProtocolVersion::V2, &note.spending_key(), 7)
+        );
+    }
+
+    #[test]
+    fn test_note_serialization() {
+        let mut note = Note::new_random(1000, Fr::from(7u64), Fr::rand(&mut OsRng));
+        let unplaced = Note::from_bytes(&note.to_bytes()).unwrap();
+        assert_eq!(unplaced.commitment(), note.commitment());
+        assert_eq!(unplaced.leaf_index, None);
+
+        note.set_leaf_index(42);
+        let placed = Note::from_bytes(&note.to_bytes()).unwrap();
+        assert_eq!(placed.leaf_index, Some(42));
+        assert_eq!(placed.nullifier().to_bytes(), note.nullifier().to_bytes());
+        assert!(Note::from_bytes(&[0u8; 100]).is_none());
+    }
+
     #[test]
     fn test_spending_key_hidden() {
         let secret = [1u8; 32];
@@ -398,4 +667,41 @@ mod tests {
         // Spending key should not be derivable back to secret
         // (Poseidon is a one-way function)
     }
+
+    #[test]
+    fn test_zeroize() {
+        let mut note = Note::new([9u8; 32], 5, Fr::from(0u64), Fr::from(7u64));
+        note.zeroize();
+        assert_eq!(note.secret, [0u8; 32]);
+        assert_eq!(note.blinding, Fr::from(0u64));
+        assert_eq!(note.amount, 0);
+
+        let mut sk = SpendingKey::from_secret(&[9u8; 32]);
+        sk.zeroize();
+        assert_eq!(sk.to_bytes(), [0u8; 32]);
+        let mut vk = ViewingKey::from_secret(&[9u8; 32]);
+        vk.zeroize();
+        assert_eq!(vk.to_bytes(), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_viewing_key_derivation() {
+        let secret = [1u8; 32];
+        let vk = ViewingKey::from_secret(&secret);
+        assert_eq!(vk, ViewingKey::from_secret(&secret));
+        assert_ne!(vk, ViewingKey::from_secret(&[2u8; 32]));
+
+        // Domain-separated from the spending key
+        assert_ne!(vk.to_bytes(), SpendingKey::from_secret(&secret).to_bytes());
+        assert_ne!(vk.to_bytes(), secret);
+        assert_eq!(ViewingKey::from_bytes(&vk.to_bytes()), vk);
+
+        let note = Note::new(secret, 1, Fr::from(0u64), Fr::from(2u64));
+        assert_eq!(note.viewing_key(), vk);
+
+        let ovk = note.outgoing_viewing_key();
+        assert_eq!(ovk, OutgoingViewingKey::from_secret(&secret));
+        assert_ne!(ovk.to_bytes(), vk.to_bytes());
+        assert_eq!(OutgoingViewingKey::from_bytes(&ovk.to_bytes()), ovk);
+    }
 }
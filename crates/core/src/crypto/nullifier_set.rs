@@ -0,0 +1,427 @@
+//! Spent Nullifier Set
+//!
+//! `NullifierSet` holds the nullifiers seen spent on chain, so a wallet can
+//! tell which of its notes are gone without fetching a PDA per note.
+//!
+//! The set is a compact sparse Merkle tree over the nullifiers' 256 bits,
+//! most significant first. Only branching nodes are stored: a subtree with
+//! one member is just that leaf, and an empty one is all zeros. The shape
+//! depends only on the members, so equal sets have equal roots whatever
+//! order they were built in.
+//!
+//! The root commits to the whole set. Two sync points can compare roots and,
+//! where they differ, `diff` walks only the subtrees that changed. Hashing
+//! is blake3, as the set never enters a circuit.
+
+use std::collections::BTreeSet;
+
+use super::merkle::MerkleError;
+
+/// Magic bytes leading a serialized set
+const SET_MAGIC: &[u8; 4] = b"VNUL";
+/// Version of the serialized layout
+const SET_VERSION: u8 = 1;
+
+/// Domain tags for leaf and branch hashes
+const LEAF_TAG: u8 = 0;
+const BRANCH_TAG: u8 = 1;
+
+/// Hash of an empty subtree
+const EMPTY_HASH: [u8; 32] = [0u8; 32];
+
+#[derive(Clone, Debug, Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf([u8; 32]),
+    Branch {
+        hash: [u8; 32],
+        children: Box<[Node; 2]>,
+    },
+}
+
+/// Bit of `key` choosing the child at `depth`
+fn bit(key: &[u8; 32], depth: usize) -> usize {
+    ((key[depth / 8] >> (7 - depth % 8)) & 1) as usize
+}
+
+impl Node {
+    fn hash(&self) -> [u8; 32] {
+        match self {
+            Node::Empty => EMPTY_HASH,
+            Node::Leaf(key) => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&[LEAF_TAG]);
+                hasher.update(key);
+                hasher.finalize().into()
+            }
+            Node::Branch { hash, .. } => *hash,
+        }
+    }
+
+    /// The node over `children`, collapsed if it holds at most one leaf
+    fn branch(children: [Node; 2]) -> Node {
+        match children {
+            [Node::Empty, Node::Empty] => Node::Empty,
+            [Node::Leaf(key), Node::Empty] | [Node::Empty, Node::Leaf(key)] => Node::Leaf(key),
+            children => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&[BRANCH_TAG]);
+                hasher.update(&children[0].hash());
+                hasher.update(&children[1].hash());
+                Node::Branch {
+                    hash: hasher.finalize().into(),
+                    children: Box::new(children),
+                }
+            }
+        }
+    }
+
+    /// Smallest subtree at `depth` holding two distinct keys
+    fn split(a: [u8; 32], b: [u8; 32], depth: usize) -> Node {
+        let (side_a, side_b) = (bit(&a, depth), bit(&b, depth));
+        let mut children = [Node::Empty, Node::Empty];
+        if side_a == side_b {
+            children[side_a] = Node::split(a, b, depth + 1);
+        } else {
+            children[side_a] = Node::Leaf(a);
+            children[side_b] = Node::Leaf(b);
+        }
+        Node::branch(children)
+    }
+
+    fn insert(&mut self, key: [u8; 32], depth: usize) -> bool {
+        match self {
+            Node::Empty => {
+                *self = Node::Leaf(key);
+                true
+            }
+            Node::Leaf(existing) if *existing == key => false,
+            Node::Leaf(existing) => {
+                *self = Node::split(*existing, key, depth);
+                true
+            }
+            Node::Branch { children, .. } => {
+                if !children[bit(&key, depth)].insert(key, depth + 1) {
+                    return false;
+                }
+                let children = std::mem::take(&mut **children);
+                *self = Node::branch(children);
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8; 32], depth: usize) -> bool {
+        match self {
+            Node::Empty => false,
+            Node::Leaf(existing) => {
+                if existing != key {
+                    return false;
+                }
+                *self = Node::Empty;
+                true
+            }
+            Node::Branch { children, .. } => {
+                if !children[bit(key, depth)].remove(key, depth + 1) {
+                    return false;
+                }
+                let children = std::mem::take(&mut **children);
+                *self = Node::branch(children);
+                true
+            }
+        }
+    }
+
+    fn contains(&self, key: &[u8; 32], depth: usize) -> bool {
+        match self {
+            Node::Empty => false,
+            Node::Leaf(existing) => existing == key,
+            Node::Branch { children, .. } => children[bit(key, depth)].contains(key, depth + 1),
+        }
+    }
+}
+
+/// Nullifiers added and removed between two versions of a set
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NullifierDiff {
+    /// In the newer set only, in ascending order
+    pub added: Vec<[u8; 32]>,
+    /// In the older set only, e.g. after a rollback; ascending
+    pub removed: Vec<[u8; 32]>,
+}
+
+impl NullifierDiff {
+    /// Whether the two sets were equal
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Spent nullifiers as a compact sparse Merkle tree
+#[derive(Clone, Debug, Default)]
+pub struct NullifierSet {
+    root: Node,
+    len: usize,
+}
+
+impl NullifierSet {
+    /// An empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a nullifier; false if it was already present
+    pub fn insert(&mut self, nullifier: [u8; 32]) -> bool {
+        let inserted = self.root.insert(nullifier, 0);
+        self.len += inserted as usize;
+        inserted
+    }
+
+    /// Drop a nullifier; false if it was absent
+    pub fn remove(&mut self, nullifier: &[u8; 32]) -> bool {
+        let removed = self.root.remove(nullifier, 0);
+        self.len -= removed as usize;
+        removed
+    }
+
+    /// Whether a nullifier has been spent
+    pub fn contains(&self, nullifier: &[u8; 32]) -> bool {
+        self.root.contains(nullifier, 0)
+    }
+
+    /// Number of nullifiers held
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Commitment to the whole set; all zeros when empty
+    pub fn root(&self) -> [u8; 32] {
+        self.root.hash()
+    }
+
+    /// Nullifiers in ascending order
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            stack: vec![&self.root],
+        }
+    }
+
+    /// What changed from `self` to `newer`
+    ///
+    /// Subtrees with equal hashes are skipped, so the cost follows the size
+    /// of the change rather than of the sets.
+    pub fn diff(&self, newer: &NullifierSet) -> NullifierDiff {
+        let mut diff = NullifierDiff::default();
+        diff_nodes(&self.root, &newer.root, &mut diff);
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff
+    }
+
+    /// Bring the set forward by a diff taken from it
+    pub fn apply(&mut self, diff: &NullifierDiff) {
+        for nullifier in &diff.removed {
+            self.remove(nullifier);
+        }
+        for nullifier in &diff.added {
+            self.insert(*nullifier);
+        }
+    }
+
+    /// Serialize as magic || version || count (u64 LE) || nullifiers in
+    /// ascending order || root
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(5 + 8 + self.len * 32 + 32);
+        bytes.extend_from_slice(SET_MAGIC);
+        bytes.push(SET_VERSION);
+        bytes.extend_from_slice(&(self.len as u64).to_le_bytes());
+        for nullifier in self.iter() {
+            bytes.extend_from_slice(nullifier);
+        }
+        bytes.extend_from_slice(&self.root());
+        bytes
+    }
+
+    /// Parse a set serialized with `to_bytes`, checking the stored root
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let corrupt = |what: &str| MerkleError::Corrupt(format!("nullifier set {}", what));
+        if bytes.len() < 5 + 8 + 32 || &bytes[..4] != SET_MAGIC || bytes[4] != SET_VERSION {
+            return Err(corrupt("header"));
+        }
+        let count = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+        let body = &bytes[13..bytes.len() - 32];
+        if Some(body.len() as u64) != count.checked_mul(32) {
+            return Err(corrupt("length"));
+        }
+
+        let mut set = Self::new();
+        let mut previous: Option<&[u8]> = None;
+        for nullifier in body.chunks(32) {
+            if previous.is_some_and(|p| p >= nullifier) {
+                return Err(corrupt("order"));
+            }
+            set.insert(nullifier.try_into().unwrap());
+            previous = Some(nullifier);
+        }
+        if set.root()[..] != bytes[bytes.len() - 32..] {
+            return Err(corrupt("root"));
+        }
+        Ok(set)
+    }
+}
+
+impl Extend<[u8; 32]> for NullifierSet {
+    fn extend<I: IntoIterator<Item = [u8; 32]>>(&mut self, iter: I) {
+        for nullifier in iter {
+            self.insert(nullifier);
+        }
+    }
+}
+
+impl FromIterator<[u8; 32]> for NullifierSet {
+    fn from_iter<I: IntoIterator<Item = [u8; 32]>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> IntoIterator for &'a NullifierSet {
+    type Item = &'a [u8; 32];
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Ascending iterator over a `NullifierSet`
+pub struct Iter<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a [u8; 32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Empty => {}
+                Node::Leaf(key) => return Some(key),
+                Node::Branch { children, .. } => {
+                    self.stack.push(&children[1]);
+                    self.stack.push(&children[0]);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn diff_nodes(old: &Node, new: &Node, diff: &mut NullifierDiff) {
+    if old.hash() == new.hash() {
+        return;
+    }
+    if let (Node::Branch { children: a, .. }, Node::Branch { children: b, .. }) = (old, new) {
+        diff_nodes(&a[0], &b[0], diff);
+        diff_nodes(&a[1], &b[1], diff);
+        return;
+    }
+
+    // Shapes differ here: compare the members below directly
+    let members = |node: &Node| -> BTreeSet<[u8; 32]> {
+        Iter { stack: vec![node] }.copied().collect()
+    };
+    let (old, new) = (members(old), members(new));
+    diff.added.extend(new.difference(&old));
+    diff.removed.extend(old.difference(&new));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u32) -> [u8; 32] {
+        *blake3::hash(&n.to_le_bytes()).as_bytes()
+    }
+
+    #[test]
+    fn test_membership_and_canonical_root() {
+        let mut set = NullifierSet::new();
+        assert_eq!(set.root(), EMPTY_HASH);
+        for n in 0..200 {
+            assert!(set.insert(key(n)));
+        }
+        assert!(!set.insert(key(7)));
+        assert_eq!(set.len(), 200);
+        assert!(set.contains(&key(199)));
+        assert!(!set.contains(&key(200)));
+
+        // Order of insertion does not matter
+        let reversed: NullifierSet = (0..200).rev().map(key).collect();
+        assert_eq!(reversed.root(), set.root());
+
+        // Neighbours sharing a long prefix
+        let mut close = [0xabu8; 32];
+        set.insert(close);
+        close[31] ^= 1;
+        set.insert(close);
+        assert!(set.contains(&close));
+
+        let sorted: Vec<_> = set.iter().copied().collect();
+        assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sorted.len(), set.len());
+
+        // Removing everything added returns to the earlier root
+        set.remove(&close);
+        close[31] ^= 1;
+        set.remove(&close);
+        assert_eq!(set.root(), reversed.root());
+        for n in 0..200 {
+            assert!(set.remove(&key(n)));
+        }
+        assert!(set.is_empty());
+        assert_eq!(set.root(), EMPTY_HASH);
+    }
+
+    #[test]
+    fn test_diff_between_sync_points() {
+        let older: NullifierSet = (0..100).map(key).collect();
+        let mut newer = older.clone();
+        newer.extend((100..110).map(key));
+        newer.remove(&key(3));
+
+        let diff = older.diff(&newer);
+        let mut added: Vec<_> = (100..110).map(key).collect();
+        added.sort_unstable();
+        assert_eq!(diff.added, added);
+        assert_eq!(diff.removed, vec![key(3)]);
+        assert!(newer.diff(&newer).is_empty());
+
+        let mut caught_up = older.clone();
+        caught_up.apply(&diff);
+        assert_eq!(caught_up.root(), newer.root());
+        assert_eq!(caught_up.len(), newer.len());
+    }
+
+    #[test]
+    fn test_serialization() {
+        let set: NullifierSet = (0..50).map(key).collect();
+        let bytes = set.to_bytes();
+        let decoded = NullifierSet::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.root(), set.root());
+        assert_eq!(decoded.len(), 50);
+        assert!(NullifierSet::from_bytes(&NullifierSet::new().to_bytes()).unwrap().is_empty());
+
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        assert!(NullifierSet::from_bytes(&flipped).is_err());
+        assert!(NullifierSet::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
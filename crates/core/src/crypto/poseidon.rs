@@ -10,9 +10,14 @@
 //! - Full rounds: 8 (4 at start, 4 at end)
 //! - Partial rounds: 57
 //! - S-box: x^5
+//!
+//! The constants are circomlib's, so `poseidon_hash2(a, b)` equals
+//! circomlib's `Poseidon(2)([a, b])`. `poseidon_hash3` and `poseidon_hash4`
+//! use circomlib's widths 4 and 5 (56 and 60 partial rounds), hashing three
+//! or four elements in one permutation.
 
 use ark_bn254::Fr;
-use ark_ff::{BigInteger, Field, PrimeField};
+use ark_ff::{BigInteger, PrimeField};
 use ark_serialize::CanonicalSerialize;
 use thiserror::Error;
 
@@ -48,7 +53,7 @@ impl Default for PoseidonParams {
 
 impl PoseidonParams {
     /// Create Poseidon parameters for BN254 with t=3
-    /// Uses standard constants from poseidon_constants module
+    /// Uses circomlib's constants from the poseidon_constants module
     pub fn new() -> Self {
         use super::poseidon_constants;
 
@@ -69,26 +74,25 @@ impl PoseidonParams {
         }
     }
 
-    /// Create with custom parameters (for testing only)
-    #[cfg(test)]
+    /// Parameters with the Blake3-derived constants used before circomlib's
+    #[cfg(any(test, feature = "legacy-poseidon"))]
     pub fn with_generated_constants() -> Self {
-        let width = 3;
-        let full_rounds = 8;
-        let partial_rounds = 57;
-
-        let round_constants = generate_round_constants(width, full_rounds, partial_rounds);
-        let mds_matrix = generate_mds_matrix(width);
+        use super::poseidon_constants::{self, legacy};
 
         Self {
-            full_rounds,
-            partial_rounds,
-            width,
-            round_constants,
-            mds_matrix,
+            full_rounds: poseidon_constants::FULL_ROUNDS,
+            partial_rounds: poseidon_constants::PARTIAL_ROUNDS,
+            width: poseidon_constants::WIDTH,
+            round_constants: legacy::get_round_constants(),
+            mds_matrix: legacy::get_mds_matrix(),
         }
     }
 }
 
+/// Smallest share of a batch handed to one rayon task
+#[cfg(feature = "parallel")]
+const MIN_PAIRS_PER_TASK: usize = 256;
+
 /// Poseidon hasher instance
 pub struct Poseidon {
     params: PoseidonParams,
@@ -115,7 +119,31 @@ impl Poseidon {
         state[0]
     }
 
+    /// Hash many pairs, sharing this hasher's parameters across them
+    ///
+    /// With the `parallel` feature, large batches are spread over rayon's
+    /// thread pool.
+    pub fn hash2_many(&self, pairs: &[(Fr, Fr)]) -> Vec<Fr> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+
+            pairs
+                .par_iter()
+                .with_min_len(MIN_PAIRS_PER_TASK)
+                .map(|(a, b)| self.hash2(a, b))
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            pairs.iter().map(|(a, b)| self.hash2(a, b)).collect()
+        }
+    }
+
     /// Hash a variable number of field elements
+    ///
+    /// A single input is zero-padded to two, so it does not match
+    /// circomlib's one-input `Poseidon(1)`.
     pub fn hash(&self, inputs: &[Fr]) -> Result<Fr, PoseidonError> {
         if inputs.is_empty() {
             return Err(PoseidonError::EmptyInput);
@@ -146,128 +174,16 @@ impl Poseidon {
 
     /// Apply the Poseidon permutation to the state
     fn permute(&self, state: &mut [Fr; 3]) {
-        let t = self.params.width;
-        let rf = self.params.full_rounds;
-        let rp = self.params.partial_rounds;
-
-        let mut round_ctr = 0;
-
-        // First half of full rounds
-        for _ in 0..(rf / 2) {
-            self.full_round(state, round_ctr);
-            round_ctr += t;
-        }
-
-        // Partial rounds
-        for _ in 0..rp {
-            self.partial_round(state, round_ctr);
-            round_ctr += t;
-        }
-
-        // Second half of full rounds
-        for _ in 0..(rf / 2) {
-            self.full_round(state, round_ctr);
-            round_ctr += t;
-        }
-    }
-
-    /// Full round: S-box on all elements, then MDS
-    fn full_round(&self, state: &mut [Fr; 3], round_ctr: usize) {
-        // Add round constants
-        for i in 0..3 {
-            state[i] += self.params.round_constants[round_ctr + i];
-        }
-
-        // S-box (x^5) on all elements
-        for elem in state.iter_mut() {
-            *elem = sbox(*elem);
-        }
-
-        // MDS matrix multiplication
-        self.mds_multiply(state);
+        let mds = &self.params.mds_matrix;
+        let mds = [0, 1, 2].map(|i| [mds[i][0], mds[i][1], mds[i][2]]);
+        crate::verify::poseidon::permute(
+            state,
+            self.params.full_rounds,
+            self.params.partial_rounds,
+            &self.params.round_constants,
+            &mds,
+        );
     }
-
-    /// Partial round: S-box on first element only, then MDS
-    fn partial_round(&self, state: &mut [Fr; 3], round_ctr: usize) {
-        // Add round constants
-        for i in 0..3 {
-            state[i] += self.params.round_constants[round_ctr + i];
-        }
-
-        // S-box only on first element
-        state[0] = sbox(state[0]);
-
-        // MDS matrix multiplication
-        self.mds_multiply(state);
-    }
-
-    /// Multiply state by MDS matrix
-    fn mds_multiply(&self, state: &mut [Fr; 3]) {
-        let mut new_state = [Fr::from(0u64); 3];
-
-        for i in 0..3 {
-            for j in 0..3 {
-                new_state[i] += self.params.mds_matrix[i][j] * state[j];
-            }
-        }
-
-        *state = new_state;
-    }
-}
-
-/// S-box function: x^5
-#[inline]
-fn sbox(x: Fr) -> Fr {
-    let x2 = x.square();
-    let x4 = x2.square();
-    x4 * x
-}
-
-/// Generate round constants using a deterministic process
-/// In production, use the standard Poseidon constants
-fn generate_round_constants(width: usize, full_rounds: usize, partial_rounds: usize) -> Vec<Fr> {
-    let num_constants = width * (full_rounds + partial_rounds);
-    let mut constants = Vec::with_capacity(num_constants);
-
-    // Use a simple deterministic generator based on the Grain LFSR approach
-    // For production, use the official Poseidon constants for BN254
-    let seed = b"Poseidon_BN254_t3";
-    let mut hasher_state = blake3::Hasher::new();
-    hasher_state.update(seed);
-
-    for i in 0..num_constants {
-        // Generate each constant deterministically
-        let mut h = hasher_state.clone();
-        h.update(&(i as u64).to_le_bytes());
-        let hash = h.finalize();
-
-        // Convert to field element
-        let bytes = hash.as_bytes();
-        let constant = Fr::from_le_bytes_mod_order(bytes);
-        constants.push(constant);
-    }
-
-    constants
-}
-
-/// Generate MDS matrix
-/// Uses a simple Cauchy matrix construction
-fn generate_mds_matrix(width: usize) -> Vec<Vec<Fr>> {
-    let mut matrix = vec![vec![Fr::from(0u64); width]; width];
-
-    // Create x and y vectors for Cauchy matrix
-    let x: Vec<Fr> = (0..width).map(|i| Fr::from(i as u64)).collect();
-    let y: Vec<Fr> = (width..(2 * width)).map(|i| Fr::from(i as u64)).collect();
-
-    for i in 0..width {
-        for j in 0..width {
-            // M[i][j] = 1 / (x[i] + y[j])
-            let sum = x[i] + y[j];
-            matrix[i][j] = sum.inverse().unwrap_or(Fr::from(1u64));
-        }
-    }
-
-    matrix
 }
 
 // ============================================================================
@@ -284,9 +200,28 @@ pub fn poseidon_hash2(a: &Fr, b: &Fr) -> Fr {
     POSEIDON.with(|p| p.hash2(a, b))
 }
 
-/// Hash field elements using Poseidon
+/// Hash three field elements using Poseidon with t = 4
+pub fn poseidon_hash3(a: &Fr, b: &Fr, c: &Fr) -> Fr {
+    crate::verify::poseidon::hash3(a, b, c)
+}
+
+/// Hash four field elements using Poseidon with t = 5
+pub fn poseidon_hash4(a: &Fr, b: &Fr, c: &Fr, d: &Fr) -> Fr {
+    crate::verify::poseidon::hash4(a, b, c, d)
+}
+
+/// Hash one to four field elements using Poseidon, with the width that
+/// fits them
 pub fn poseidon_hash_fields(inputs: &[Fr]) -> Result<Fr, PoseidonError> {
-    POSEIDON.with(|p| p.hash(inputs))
+    match inputs {
+        [a, b, c] => Ok(poseidon_hash3(a, b, c)),
+        [a, b, c, d] => Ok(poseidon_hash4(a, b, c, d)),
+        _ if inputs.len() > 4 => Err(PoseidonError::InvalidLength {
+            expected: 4,
+            got: inputs.len(),
+        }),
+        _ => POSEIDON.with(|p| p.hash(inputs)),
+    }
 }
 
 /// Poseidon hash for byte arrays
@@ -365,6 +300,19 @@ mod tests {
         assert_ne!(hash1, hash2);
     }
 
+    #[test]
+    fn test_hash2_many() {
+        let pairs: Vec<(Fr, Fr)> = (0..1000u64)
+            .map(|i| (Fr::from(i), Fr::from(i * 7 + 1)))
+            .collect();
+        let hashes = Poseidon::new().hash2_many(&pairs);
+        assert_eq!(hashes.len(), pairs.len());
+        for ((a, b), hash) in pairs.iter().zip(&hashes) {
+            assert_eq!(*hash, poseidon_hash2(a, b));
+        }
+        assert!(Poseidon::new().hash2_many(&[]).is_empty());
+    }
+
     #[test]
     fn test_poseidon_hash_bytes() {
         let inputs = vec![vec![1u8; 32], vec![2u8; 32]];
@@ -397,11 +345,56 @@ mod tests {
     }
 
     #[test]
-    fn test_sbox() {
-        let x = Fr::from(2u64);
-        let result = sbox(x);
-        let expected = Fr::from(32u64); // 2^5 = 32
-        assert_eq!(result, expected);
+    fn test_circomlib_vectors() {
+        use std::str::FromStr;
+
+        // circomlibjs poseidon([a, b]) / light-poseidon `new_circom(2)`
+        let vectors = [
+            (
+                Fr::from(1u64),
+                Fr::from(2u64),
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+            ),
+            (
+                Fr::from(0u64),
+                Fr::from(0u64),
+                "14744269619966411208579211824598458697587494354926760081771325075741142829156",
+            ),
+            (
+                Fr::from_be_bytes_mod_order(&[1u8; 32]),
+                Fr::from_be_bytes_mod_order(&[2u8; 32]),
+                "6030039056180688046538272648816150486962996124743416649616624837554616801680",
+            ),
+        ];
+        for (a, b, expected) in vectors {
+            assert_eq!(poseidon_hash2(&a, &b), Fr::from_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_wider_hashes() {
+        use std::str::FromStr;
+
+        let [a, b, c, d] = [1u64, 2, 3, 4].map(Fr::from);
+        // circomlib's reference vector for poseidonperm_x5_254_5
+        let expected = Fr::from_str(
+            "18821383157269793795438455681495246036402687001665670618754263018637548127333",
+        )
+        .unwrap();
+        assert_eq!(poseidon_hash4(&a, &b, &c, &d), expected);
+        assert_eq!(poseidon_hash_fields(&[a, b, c, d]).unwrap(), expected);
+        assert_eq!(poseidon_hash_fields(&[a, b, c]).unwrap(), poseidon_hash3(&a, &b, &c));
+        assert_ne!(poseidon_hash3(&a, &b, &c), poseidon_hash4(&a, &b, &c, &Fr::from(0u64)));
+        assert!(poseidon_hash_fields(&[a; 5]).is_err());
+    }
+
+    #[test]
+    fn test_generated_constants_differ() {
+        let legacy = Poseidon {
+            params: PoseidonParams::with_generated_constants(),
+        };
+        let (a, b) = (Fr::from(1u64), Fr::from(2u64));
+        assert_ne!(legacy.hash2(&a, &b), poseidon_hash2(&a, &b));
     }
 
     #[test]
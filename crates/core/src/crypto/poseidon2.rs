@@ -0,0 +1,205 @@
+//! Poseidon2 Hash Function
+//!
+//! Poseidon2 (https://eprint.iacr.org/2023/323) keeps Poseidon's S-box and
+//! round structure but replaces the dense MDS matrix with cheap linear
+//! layers and adds one round constant per partial round instead of `t`.
+//! Natively that saves most of the work of the linear layers; in R1CS the
+//! S-boxes dominate either way, and the gadget saves one partial round.
+//!
+//! Parameters, as HorizenLabs' reference implementation for BN254:
+//! - Field: BN254 scalar field (Fr)
+//! - Width: t = 3
+//! - Full rounds: 8 (4 at start, 4 at end)
+//! - Partial rounds: 56
+//! - S-box: x^5
+//! - External matrix: circ(2, 1, 1); internal matrix: 1 + diag(1, 1, 2)
+//!
+//! The round constants come from the Poseidon paper's Grain LFSR, seeded as
+//! `generate_parameters_grain.sage 1 0 254 3 8 56`, and are generated on
+//! first use. Pools opt into Poseidon2 through `ProtocolVersion::V2`; it is
+//! not interchangeable with Poseidon and changes every root, commitment and
+//! nullifier.
+
+use std::sync::OnceLock;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, BigInteger256, Field, PrimeField};
+
+/// State width
+pub const WIDTH: usize = 3;
+
+/// Number of full rounds
+pub const FULL_ROUNDS: usize = 8;
+
+/// Number of partial rounds
+pub const PARTIAL_ROUNDS: usize = 56;
+
+/// Internal matrix diagonal minus one
+const INTERNAL_DIAG_M1: [u64; WIDTH] = [1, 1, 2];
+
+/// Round constants, `WIDTH` per round; partial rounds only use the first
+pub fn round_constants() -> &'static [[Fr; WIDTH]] {
+    static CONSTANTS: OnceLock<Vec<[Fr; WIDTH]>> = OnceLock::new();
+    CONSTANTS.get_or_init(generate_round_constants)
+}
+
+/// Grain LFSR seeded with the field, S-box and round parameters
+struct Grain {
+    state: [bool; 80],
+}
+
+impl Grain {
+    fn new() -> Self {
+        // field = 1 (prime), sbox = 0 (x^alpha), field size, t, R_F, R_P,
+        // MSB first, then ones
+        let fields: [(u64, usize); 6] = [
+            (1, 2),
+            (0, 4),
+            (Fr::MODULUS_BIT_SIZE as u64, 12),
+            (WIDTH as u64, 12),
+            (FULL_ROUNDS as u64, 10),
+            (PARTIAL_ROUNDS as u64, 10),
+        ];
+        let mut state = [true; 80];
+        let mut pos = 0;
+        for (value, bits) in fields {
+            for i in (0..bits).rev() {
+                state[pos] = (value >> i) & 1 == 1;
+                pos += 1;
+            }
+        }
+        let mut grain = Self { state };
+        for _ in 0..160 {
+            grain.clock();
+        }
+        grain
+    }
+
+    fn clock(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.copy_within(1.., 0);
+        self.state[79] = bit;
+        bit
+    }
+
+    /// Self-shrinking output: keep the second bit of each pair whose first
+    /// bit is set
+    fn next_bit(&mut self) -> bool {
+        while !self.clock() {
+            self.clock();
+        }
+        self.clock()
+    }
+
+    /// Next field element, rejecting samples at or above the modulus
+    fn next_field(&mut self) -> Fr {
+        loop {
+            let bits: Vec<bool> = (0..Fr::MODULUS_BIT_SIZE).map(|_| self.next_bit()).collect();
+            if let Some(value) = Fr::from_bigint(BigInteger256::from_bits_be(&bits)) {
+                return value;
+            }
+        }
+    }
+}
+
+fn generate_round_constants() -> Vec<[Fr; WIDTH]> {
+    let mut grain = Grain::new();
+    let half_full = FULL_ROUNDS / 2;
+    (0..FULL_ROUNDS + PARTIAL_ROUNDS)
+        .map(|round| {
+            if round < half_full || round >= half_full + PARTIAL_ROUNDS {
+                [0; WIDTH].map(|_| grain.next_field())
+            } else {
+                [grain.next_field(), Fr::from(0u64), Fr::from(0u64)]
+            }
+        })
+        .collect()
+}
+
+/// Apply the Poseidon2 permutation
+pub fn permute(state: &mut [Fr; WIDTH]) {
+    let half_full = FULL_ROUNDS / 2;
+    external_layer(state);
+    for (round, constants) in round_constants().iter().enumerate() {
+        if round < half_full || round >= half_full + PARTIAL_ROUNDS {
+            for (elem, constant) in state.iter_mut().zip(constants) {
+                *elem = sbox(*elem + constant);
+            }
+            external_layer(state);
+        } else {
+            state[0] = sbox(state[0] + constants[0]);
+            internal_layer(state);
+        }
+    }
+}
+
+/// Hash two field elements: the first element of the permuted
+/// `[0, a, b]`, as `poseidon_hash2` lays out its state
+pub fn poseidon2_hash2(a: &Fr, b: &Fr) -> Fr {
+    let mut state = [Fr::from(0u64), *a, *b];
+    permute(&mut state);
+    state[0]
+}
+
+/// Multiply by circ(2, 1, 1): add the sum of the state to each element
+fn external_layer(state: &mut [Fr; WIDTH]) {
+    let sum: Fr = state.iter().sum();
+    for elem in state.iter_mut() {
+        *elem += sum;
+    }
+}
+
+/// Multiply by 1 + diag(INTERNAL_DIAG_M1)
+fn internal_layer(state: &mut [Fr; WIDTH]) {
+    let sum: Fr = state.iter().sum();
+    for (elem, diag) in state.iter_mut().zip(INTERNAL_DIAG_M1) {
+        *elem = *elem * Fr::from(diag) + sum;
+    }
+}
+
+/// S-box function: x^5
+#[inline]
+fn sbox(x: Fr) -> Fr {
+    let x2 = x.square();
+    x2.square() * x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::MontFp;
+
+    #[test]
+    fn test_reference_permutation() {
+        // HorizenLabs' poseidon2 known-answer test for BN254, t = 3
+        let mut state = [Fr::from(0u64), Fr::from(1u64), Fr::from(2u64)];
+        permute(&mut state);
+        let expected: [Fr; WIDTH] = [
+            MontFp!("5297208644449048816064511434384511824916970985131888684874823260532015509555"),
+            MontFp!("21816030159894113985964609355246484851575571273661473159848781012394295965040"),
+            MontFp!("13940986381491601233448981668101586453321811870310341844570924906201623195336"),
+        ];
+        assert_eq!(state, expected);
+    }
+
+    #[test]
+    fn test_round_constants() {
+        let constants = round_constants();
+        assert_eq!(constants.len(), FULL_ROUNDS + PARTIAL_ROUNDS);
+        let expected: Fr = MontFp!(
+            "13128406282895484157369354038809433636203389051939936481821261911791933663254"
+        );
+        assert_eq!(constants[0][0], expected);
+        // Partial rounds add a single constant
+        assert_eq!(constants[FULL_ROUNDS / 2][1], Fr::from(0u64));
+    }
+
+    #[test]
+    fn test_hash2_differs_from_poseidon() {
+        let (a, b) = (Fr::from(1u64), Fr::from(2u64));
+        assert_eq!(poseidon2_hash2(&a, &b), poseidon2_hash2(&a, &b));
+        assert_ne!(poseidon2_hash2(&a, &b), poseidon2_hash2(&b, &a));
+        assert_ne!(poseidon2_hash2(&a, &b), super::super::poseidon::poseidon_hash2(&a, &b));
+    }
+}
@@ -0,0 +1,287 @@
+//! Versioned Encodings
+//!
+//! Borsh and serde formats for the structures a wallet persists or hands to
+//! another device: `Note`, `SpendingKey`, `MerklePath` and `MerkleFrontier`.
+//!
+//! Borsh is the canonical form: a version byte followed by the fields, with
+//! field elements as 32 little-endian bytes. Decoding rejects unknown
+//! versions, non-canonical field elements and trailing bytes. Serde carries
+//! the same bytes, as a hex string in human-readable formats such as JSON
+//! and as raw bytes otherwise, so both round-trip to identical values.
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroizing;
+
+use super::merkle::{MerkleFrontier, MerklePath};
+use super::nullifier::{Note, SpendingKey};
+use super::protocol::ProtocolVersion;
+
+/// Version byte leading every encoding in this module
+pub const ENCODING_VERSION: u8 = 1;
+
+/// Version byte of frontiers followed by their protocol version; frontiers
+/// of `ProtocolVersion::V1` trees keep the `ENCODING_VERSION` layout
+const VERSIONED_FRONTIER: u8 = 2;
+
+fn invalid(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, what.to_string())
+}
+
+fn read_version<R: Read>(reader: &mut R) -> Result<()> {
+    match u8::deserialize_reader(reader)? {
+        ENCODING_VERSION => Ok(()),
+        version => Err(invalid(&format!(
+            "unsupported encoding version {}",
+            version
+        ))),
+    }
+}
+
+fn write_fr<W: Write>(value: &Fr, writer: &mut W) -> Result<()> {
+    let bytes = Zeroizing::new(value.into_bigint().to_bytes_le());
+    writer.write_all(&bytes[..32])
+}
+
+fn read_fr<R: Read>(reader: &mut R) -> Result<Fr> {
+    let bytes = Zeroizing::new(<[u8; 32]>::deserialize_reader(reader)?);
+    let value = Fr::from_le_bytes_mod_order(&*bytes);
+    let canonical = Zeroizing::new(value.into_bigint().to_bytes_le());
+    if canonical[..32] != bytes[..] {
+        return Err(invalid("non-canonical field element"));
+    }
+    Ok(value)
+}
+
+pub(super) fn write_frs<W: Write>(values: &[Fr], writer: &mut W) -> Result<()> {
+    BorshSerialize::serialize(&(values.len() as u32), writer)?;
+    values.iter().try_for_each(|v| write_fr(v, writer))
+}
+
+pub(super) fn read_frs<R: Read>(reader: &mut R) -> Result<Vec<Fr>> {
+    let len = u32::deserialize_reader(reader)?;
+    (0..len).map(|_| read_fr(reader)).collect()
+}
+
+impl BorshSerialize for Note {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(&ENCODING_VERSION, writer)?;
+        writer.write_all(&self.secret)?;
+        write_fr(&self.blinding, writer)?;
+        BorshSerialize::serialize(&self.amount, writer)?;
+        write_fr(&self.asset_id, writer)?;
+        BorshSerialize::serialize(&self.leaf_index, writer)
+    }
+}
+
+impl BorshDeserialize for Note {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        read_version(reader)?;
+        let secret = Zeroizing::new(<[u8; 32]>::deserialize_reader(reader)?);
+        let blinding = read_fr(reader)?;
+        let amount = u64::deserialize_reader(reader)?;
+        let asset_id = read_fr(reader)?;
+        let mut note = Note::new(*secret, amount, asset_id, blinding);
+        note.leaf_index = Option::<u64>::deserialize_reader(reader)?;
+        Ok(note)
+    }
+}
+
+impl BorshSerialize for SpendingKey {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(&ENCODING_VERSION, writer)?;
+        write_fr(self.as_field(), writer)
+    }
+}
+
+impl BorshDeserialize for SpendingKey {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        read_version(reader)?;
+        Ok(SpendingKey::from_field(read_fr(reader)?))
+    }
+}
+
+impl BorshSerialize for MerklePath {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(&ENCODING_VERSION, writer)?;
+        BorshSerialize::serialize(&self.leaf_index, writer)?;
+        write_frs(&self.siblings, writer)?;
+        BorshSerialize::serialize(&self.indices, writer)
+    }
+}
+
+impl BorshDeserialize for MerklePath {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        read_version(reader)?;
+        let leaf_index = u64::deserialize_reader(reader)?;
+        let siblings = read_frs(reader)?;
+        let indices = Vec::<bool>::deserialize_reader(reader)?;
+        if siblings.len() != indices.len() {
+            return Err(invalid("sibling and index counts differ"));
+        }
+        Ok(MerklePath {
+            siblings,
+            indices,
+            leaf_index,
+        })
+    }
+}
+
+impl BorshSerialize for MerkleFrontier {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        match self.version() {
+            ProtocolVersion::V1 => BorshSerialize::serialize(&ENCODING_VERSION, writer)?,
+            version => {
+                BorshSerialize::serialize(&VERSIONED_FRONTIER, writer)?;
+                BorshSerialize::serialize(&version.as_u8(), writer)?;
+            }
+        }
+        BorshSerialize::serialize(&self.len(), writer)?;
+        write_fr(&self.last_leaf(), writer)?;
+        write_frs(self.filled_subtrees(), writer)
+    }
+}
+
+impl BorshDeserialize for MerkleFrontier {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let version = match u8::deserialize_reader(reader)? {
+            ENCODING_VERSION => ProtocolVersion::V1,
+            VERSIONED_FRONTIER => ProtocolVersion::from_u8(u8::deserialize_reader(reader)?)
+                .ok_or_else(|| invalid("unknown protocol version"))?,
+            version => {
+                return Err(invalid(&format!("unsupported encoding version {}", version)))
+            }
+        };
+        let next_index = u64::deserialize_reader(reader)?;
+        let last_leaf = read_fr(reader)?;
+        let filled_subtrees = read_frs(reader)?;
+        MerkleFrontier::from_versioned_parts(version, next_index, last_leaf, filled_subtrees)
+            .map_err(|e| invalid(&e.to_string()))
+    }
+}
+
+/// Serde impls carrying the Borsh encoding
+macro_rules! serde_via_borsh {
+    ($($ty:ty),*) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                let bytes = Zeroizing::new(self.try_to_vec().map_err(S::Error::custom)?);
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(&Zeroizing::new(hex::encode(&*bytes)))
+                } else {
+                    serializer.serialize_bytes(&bytes)
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                let bytes = if deserializer.is_human_readable() {
+                    let text = Zeroizing::new(<String as Deserialize>::deserialize(deserializer)?);
+                    Zeroizing::new(hex::decode(&*text).map_err(D::Error::custom)?)
+                } else {
+                    Zeroizing::new(<Vec<u8> as Deserialize>::deserialize(deserializer)?)
+                };
+                Self::try_from_slice(&bytes).map_err(D::Error::custom)
+            }
+        }
+    )*};
+}
+
+serde_via_borsh!(Note, SpendingKey, MerklePath, MerkleFrontier);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::merkle::PoseidonMerkleTree;
+
+    fn note() -> Note {
+        let mut note = Note::new([7u8; 32], 1_000, Fr::from(3u64), Fr::from(11u64));
+        note.set_leaf_index(42);
+        note
+    }
+
+    #[test]
+    fn test_note_round_trip() {
+        let note = note();
+        let bytes = note.try_to_vec().unwrap();
+        assert_eq!(bytes[0], ENCODING_VERSION);
+        let decoded = Note::try_from_slice(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), note.to_bytes());
+
+        let json = serde_json::to_string(&note).unwrap();
+        let decoded: Note = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), note.to_bytes());
+
+        let mut unplaced = note.clone();
+        unplaced.leaf_index = None;
+        let decoded = Note::try_from_slice(&unplaced.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.leaf_index, None);
+    }
+
+    #[test]
+    fn test_spending_key_round_trip() {
+        let key = SpendingKey::from_secret(&[9u8; 32]);
+        let decoded = SpendingKey::try_from_slice(&key.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.to_bytes(), key.to_bytes());
+
+        let json = serde_json::to_string(&key).unwrap();
+        let decoded: SpendingKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), key.to_bytes());
+    }
+
+    #[test]
+    fn test_path_and_frontier_round_trip() {
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..7u64 {
+            tree.insert(Fr::from(i)).unwrap();
+        }
+
+        let path = tree.generate_proof(5).unwrap();
+        let decoded = MerklePath::try_from_slice(&path.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.siblings, path.siblings);
+        assert_eq!(decoded.indices, path.indices);
+        assert_eq!(decoded.leaf_index, 5);
+
+        let frontier = tree.frontier();
+        let json = serde_json::to_string(&frontier).unwrap();
+        let mut decoded: MerkleFrontier = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, frontier);
+        assert_eq!(decoded.root(), tree.root());
+
+        decoded.append(Fr::from(7u64)).unwrap();
+        tree.insert(Fr::from(7u64)).unwrap();
+        assert_eq!(decoded.root(), tree.root());
+
+        let mut v2 = MerkleFrontier::with_version(20, ProtocolVersion::V2).unwrap();
+        v2.append(Fr::from(1u64)).unwrap();
+        let bytes = v2.try_to_vec().unwrap();
+        assert_eq!(bytes[..2], [VERSIONED_FRONTIER, 2]);
+        assert_eq!(MerkleFrontier::try_from_slice(&bytes).unwrap(), v2);
+    }
+
+    #[test]
+    fn test_rejects_bad_encodings() {
+        let mut bytes = note().try_to_vec().unwrap();
+
+        // Unknown version
+        bytes[0] = ENCODING_VERSION + 1;
+        assert!(Note::try_from_slice(&bytes).is_err());
+        bytes[0] = ENCODING_VERSION;
+
+        // Trailing bytes
+        let mut long = bytes.clone();
+        long.push(0);
+        assert!(Note::try_from_slice(&long).is_err());
+
+        // Blinding at or above the field modulus
+        bytes[33..65].copy_from_slice(&[0xff; 32]);
+        assert!(Note::try_from_slice(&bytes).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! Cross-Implementation Test Vectors
+//!
+//! Fixed inputs with the Poseidon hashes, note commitments and nullifiers
+//! this crate computes for them, kept as JSON in
+//! `fixtures/test_vectors.json` so external provers (circom circuits,
+//! iden3's circomlibjs) can check themselves against the same file. Field
+//! elements are decimal strings, as circom writes them; secrets are hex.
+//!
+//! The Poseidon hashes of [1, 2], [1, 2, 3] and [1, 2, 3, 4] are
+//! circomlibjs's own test values and are asserted independently; everything
+//! else is generated by `veil-test-vectors`. The native tests here and the
+//! gadget tests in `proof::gadgets` check every entry of the fixture.
+
+use std::str::FromStr;
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::nullifier::Note;
+use super::poseidon::poseidon_hash_fields;
+use super::protocol::ProtocolVersion;
+
+/// The checked-in fixture
+pub const FIXTURE: &str = include_str!("../../fixtures/test_vectors.json");
+
+#[derive(Error, Debug)]
+pub enum VectorError {
+    #[error("Invalid field element: {0}")]
+    InvalidField(String),
+    #[error("Invalid secret: {0}")]
+    InvalidSecret(String),
+    #[error("Unsupported protocol version {0}")]
+    UnsupportedVersion(u8),
+    #[error("{kind} vector {index}: expected {expected}, computed {computed}")]
+    Mismatch {
+        kind: &'static str,
+        index: usize,
+        expected: String,
+        computed: String,
+    },
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A Poseidon hash of two to four inputs, one circomlib permutation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoseidonVector {
+    pub inputs: Vec<String>,
+    pub hash: String,
+}
+
+/// A note commitment in a pool on `version`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentVector {
+    pub version: u8,
+    pub secret: String,
+    pub amount: u64,
+    pub asset_id: String,
+    pub blinding: String,
+    /// The spending key derived from `secret`, as the circuits take it
+    pub spending_key: String,
+    pub commitment: String,
+}
+
+/// A nullifier in a pool on `version`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NullifierVector {
+    pub version: u8,
+    pub secret: String,
+    pub leaf_index: u64,
+    pub nullifier: String,
+}
+
+/// Every vector in the fixture
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub poseidon: Vec<PoseidonVector>,
+    pub commitments: Vec<CommitmentVector>,
+    pub nullifiers: Vec<NullifierVector>,
+}
+
+impl TestVectors {
+    /// Compute the vectors for the fixed inputs
+    pub fn generate() -> Self {
+        let max = Fr::from(0u64) - Fr::from(1u64);
+        let poseidon_inputs: Vec<Vec<Fr>> = vec![
+            vec![Fr::from(1u64), Fr::from(2u64)],
+            vec![Fr::from(0u64), Fr::from(0u64)],
+            vec![max, max],
+            vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)],
+            vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64), Fr::from(4u64)],
+            vec![max, Fr::from(0u64), max, Fr::from(u64::MAX)],
+        ];
+        let poseidon = poseidon_inputs
+            .iter()
+            .map(|inputs| PoseidonVector {
+                inputs: inputs.iter().map(field_to_decimal).collect(),
+                // At most four inputs
+                hash: field_to_decimal(&poseidon_hash_fields(inputs).unwrap()),
+            })
+            .collect();
+
+        let notes = [
+            Note::new([1u8; 32], 0, Fr::from(0u64), Fr::from(0u64)),
+            Note::new([7u8; 32], 1_000_000_000, Fr::from(0u64), Fr::from(42u64)),
+            Note::new([0xabu8; 32], u64::MAX, Fr::from(3u64), max),
+        ];
+        let mut commitments = Vec::new();
+        let mut nullifiers = Vec::new();
+        for version in [ProtocolVersion::V1, ProtocolVersion::V2] {
+            for (i, note) in notes.iter().enumerate() {
+                commitments.push(CommitmentVector {
+                    version: version.as_u8(),
+                    secret: hex::encode(note.secret),
+                    amount: note.amount,
+                    asset_id: field_to_decimal(&note.asset_id),
+                    blinding: field_to_decimal(&note.blinding),
+                    spending_key: field_to_decimal(note.spending_key().as_field()),
+                    commitment: field_to_decimal(&note.commitment_with(version)),
+                });
+                let mut note = note.clone();
+                note.set_leaf_index([0, 1, 1 << 20][i]);
+                nullifiers.push(NullifierVector {
+                    version: version.as_u8(),
+                    secret: hex::encode(note.secret),
+                    leaf_index: note.leaf_index.unwrap_or_default(),
+                    nullifier: field_to_decimal(note.nullifier_with(version).as_field()),
+                });
+            }
+        }
+
+        Self {
+            poseidon,
+            commitments,
+            nullifiers,
+        }
+    }
+
+    /// Parse the checked-in fixture
+    pub fn fixture() -> Result<Self, VectorError> {
+        Self::from_json(FIXTURE)
+    }
+
+    /// Parse vectors from JSON
+    pub fn from_json(json: &str) -> Result<Self, VectorError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Pretty-printed JSON, as the fixture is written
+    pub fn to_json(&self) -> String {
+        // Plain strings and integers always serialize
+        let mut json = serde_json::to_string_pretty(self).unwrap();
+        json.push('\n');
+        json
+    }
+
+    /// Recompute every vector natively and compare
+    pub fn check(&self) -> Result<(), VectorError> {
+        for (index, vector) in self.poseidon.iter().enumerate() {
+            let inputs = parse_fields(&vector.inputs)?;
+            let hash = poseidon_hash_fields(&inputs)
+                .map_err(|e| VectorError::InvalidField(e.to_string()))?;
+            expect("poseidon", index, &vector.hash, &hash)?;
+        }
+        for (index, vector) in self.commitments.iter().enumerate() {
+            let note = vector.note()?;
+            let version = parse_version(vector.version)?;
+            expect("spending key", index, &vector.spending_key, note.spending_key().as_field())?;
+            expect("commitment", index, &vector.commitment, &note.commitment_with(version))?;
+        }
+        for (index, vector) in self.nullifiers.iter().enumerate() {
+            let secret = parse_secret(&vector.secret)?;
+            let mut note = Note::new(secret, 0, Fr::from(0u64), Fr::from(0u64));
+            note.set_leaf_index(vector.leaf_index);
+            let nullifier = note.nullifier_with(parse_version(vector.version)?);
+            expect("nullifier", index, &vector.nullifier, nullifier.as_field())?;
+        }
+        Ok(())
+    }
+}
+
+impl CommitmentVector {
+    /// The note the vector commits to
+    pub fn note(&self) -> Result<Note, VectorError> {
+        Ok(Note::new(
+            parse_secret(&self.secret)?,
+            self.amount,
+            parse_field(&self.asset_id)?,
+            parse_field(&self.blinding)?,
+        ))
+    }
+}
+
+/// Decimal string of a field element
+pub fn field_to_decimal(value: &Fr) -> String {
+    value.into_bigint().to_string()
+}
+
+/// Parse a decimal field element, rejecting values at or above the modulus
+pub fn parse_field(value: &str) -> Result<Fr, VectorError> {
+    let invalid = || VectorError::InvalidField(value.to_string());
+    let field = Fr::from_str(value).map_err(|_| invalid())?;
+    if field_to_decimal(&field) != value {
+        return Err(invalid());
+    }
+    Ok(field)
+}
+
+/// Parse decimal field elements
+pub fn parse_fields(values: &[String]) -> Result<Vec<Fr>, VectorError> {
+    values.iter().map(|value| parse_field(value)).collect()
+}
+
+/// Parse a hex secret
+pub fn parse_secret(secret: &str) -> Result<[u8; 32], VectorError> {
+    hex::decode(secret)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| VectorError::InvalidSecret(secret.to_string()))
+}
+
+/// Parse a protocol version number
+pub fn parse_version(version: u8) -> Result<ProtocolVersion, VectorError> {
+    ProtocolVersion::from_u8(version).ok_or(VectorError::UnsupportedVersion(version))
+}
+
+fn expect(
+    kind: &'static str,
+    index: usize,
+    expected: &str,
+    computed: &Fr,
+) -> Result<(), VectorError> {
+    let computed = field_to_decimal(computed);
+    if computed != expected {
+        return Err(VectorError::Mismatch {
+            kind,
+            index,
+            expected: expected.to_string(),
+            computed,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_matches_native() {
+        let fixture = TestVectors::fixture().unwrap();
+        fixture.check().unwrap();
+        // Regenerate with `cargo run -p veil-core --bin veil-test-vectors`
+        assert_eq!(fixture, TestVectors::generate());
+    }
+
+    #[test]
+    fn test_circomlibjs_values() {
+        // From circomlibjs's poseidon tests
+        let circomlibjs = [
+            (
+                "1,2",
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+            ),
+            (
+                "1,2,3",
+                "6542985608222806190361240322586112750744169038454362455181422643027100751666",
+            ),
+            (
+                "1,2,3,4",
+                "18821383157269793795438455681495246036402687001665670618754263018637548127333",
+            ),
+        ];
+        let fixture = TestVectors::fixture().unwrap();
+        for (inputs, hash) in circomlibjs {
+            let vector = fixture
+                .poseidon
+                .iter()
+                .find(|v| v.inputs.join(",") == inputs)
+                .unwrap();
+            assert_eq!(vector.hash, hash);
+        }
+    }
+
+    #[test]
+    fn test_check_reports_mismatch() {
+        let mut vectors = TestVectors::generate();
+        vectors.nullifiers[2].leaf_index += 1;
+        assert!(matches!(
+            vectors.check(),
+            Err(VectorError::Mismatch { kind: "nullifier", index: 2, .. })
+        ));
+        assert!(parse_field("not a number").is_err());
+        // Not reduced
+        let modulus = Fr::MODULUS.to_string();
+        assert!(parse_field(&modulus).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Pool Epochs
+//!
+//! A note can only be spent with the parameters it was created under. After a
+//! migration (hash change, new commitment scheme, tree rotation) notes from
+//! the old epoch fail deep inside witness generation or on-chain verification
+//! with nothing pointing at the real cause.
+//!
+//! Each note is tagged with the `PoolEpoch` it was received in (see
+//! `wallet::StoredNote`), and `WitnessBuilder` checks it against the current
+//! pool state before proving. A mismatch is reported as
+//! `VeilError::NoteFromPreviousEpoch`, or its `ProofError` counterpart from
+//! the builder.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::Note;
+use crate::error::{VeilError, VeilResult};
+
+/// Hash identifier for Poseidon over BN254 with the current constants
+pub const HASH_ID_POSEIDON_BN254: u16 = 1;
+
+/// Commitment scheme version: Poseidon(spending_key, amount, blinding, asset_id)
+pub const SCHEME_VERSION_V1: u16 = 1;
+
+/// Parameters a note is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PoolEpoch {
+    /// Merkle/commitment hash function
+    pub hash_id: u16,
+    /// Commitment scheme version
+    pub scheme_version: u16,
+    /// Tree the commitment was inserted into
+    pub tree_id: u32,
+}
+
+impl PoolEpoch {
+    /// Epoch for this build's hash and scheme on the given tree
+    pub fn current(tree_id: u32) -> Self {
+        Self {
+            hash_id: HASH_ID_POSEIDON_BN254,
+            scheme_version: SCHEME_VERSION_V1,
+            tree_id,
+        }
+    }
+
+    /// Ensure a note from `self` can be spent against `pool`
+    pub fn ensure_compatible(&self, pool: &PoolEpoch) -> VeilResult<()> {
+        if self == pool {
+            Ok(())
+        } else {
+            Err(VeilError::NoteFromPreviousEpoch {
+                note: *self,
+                pool: *pool,
+            })
+        }
+    }
+}
+
+impl fmt::Display for PoolEpoch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hash {} / scheme v{} / tree {}",
+            self.hash_id, self.scheme_version, self.tree_id
+        )
+    }
+}
+
+/// A note together with the epoch it was received in
+#[derive(Clone, Debug)]
+pub struct EpochNote {
+    pub note: Note,
+    pub epoch: PoolEpoch,
+}
+
+impl EpochNote {
+    /// Tag a note with the epoch it was received in
+    pub fn new(note: Note, epoch: PoolEpoch) -> Self {
+        Self { note, epoch }
+    }
+
+    /// The note, if it can be spent against `pool`
+    pub fn spendable(&self, pool: &PoolEpoch) -> VeilResult<&Note> {
+        self.epoch.ensure_compatible(pool)?;
+        Ok(&self.note)
+    }
+}
+
+/// Split notes into those spendable against `pool` and those needing migration
+pub fn partition_by_epoch<'a>(
+    notes: &'a [EpochNote],
+    pool: &PoolEpoch,
+) -> (Vec<&'a EpochNote>, Vec<&'a EpochNote>) {
+    notes.iter().partition(|n| n.epoch == *pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    fn note(amount: u64) -> Note {
+        Note::new([amount as u8; 32], amount, Fr::from(0u64), Fr::from(amount))
+    }
+
+    #[test]
+    fn test_same_epoch_is_spendable() {
+        let epoch = PoolEpoch::current(0);
+        let tagged = EpochNote::new(note(10), epoch);
+        assert_eq!(tagged.spendable(&epoch).unwrap().amount, 10);
+    }
+
+    #[test]
+    fn test_previous_epoch_rejected() {
+        let pool = PoolEpoch::current(1);
+        let old_tree = EpochNote::new(note(10), PoolEpoch::current(0));
+        let old_hash = EpochNote::new(
+            note(20),
+            PoolEpoch {
+                hash_id: 0,
+                ..pool
+            },
+        );
+
+        for tagged in [&old_tree, &old_hash] {
+            match tagged.spendable(&pool) {
+                Err(VeilError::NoteFromPreviousEpoch { note, pool: current }) => {
+                    assert_eq!(note, tagged.epoch);
+                    assert_eq!(current, pool);
+                }
+                other => panic!("expected epoch error, got {:?}", other),
+            }
+        }
+
+        let message = old_tree.spendable(&pool).unwrap_err().to_string();
+        assert!(message.contains("tree 0"));
+        assert!(message.contains("migration"));
+    }
+
+    #[test]
+    fn test_mixed_epoch_selection() {
+        let pool = PoolEpoch::current(2);
+        let v2_scheme = PoolEpoch {
+            scheme_version: 2,
+            ..pool
+        };
+        let notes = vec![
+            EpochNote::new(note(1), PoolEpoch::current(0)),
+            EpochNote::new(note(2), pool),
+            EpochNote::new(note(3), v2_scheme),
+            EpochNote::new(note(4), pool),
+            EpochNote::new(note(5), PoolEpoch::current(1)),
+        ];
+
+        let (spendable, stale) = partition_by_epoch(&notes, &pool);
+        let amounts = |v: &[&EpochNote]| v.iter().map(|n| n.note.amount).collect::<Vec<_>>();
+        assert_eq!(amounts(&spendable), vec![2, 4]);
+        assert_eq!(amounts(&stale), vec![1, 3, 5]);
+    }
+}
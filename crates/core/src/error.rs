@@ -30,6 +30,29 @@ pub enum VeilError {
     /// Configuration error
     #[error("Configuration error: {0}")]
     Configuration(String),
+
+    /// Solana RPC request failed or returned an error
+    #[error("RPC error: {0}")]
+    Rpc(String),
+
+    /// Serialized transaction exceeds the packet size budget
+    #[error(
+        "Transaction too large: {size} bytes exceeds the {limit} byte limit by {} bytes; \
+         split it across transactions or move optional data (e.g. the encrypted note) \
+         out of band",
+        size - limit
+    )]
+    TransactionTooLarge { size: usize, limit: usize },
+
+    /// Note was created under different pool parameters
+    #[error(
+        "Note from previous epoch ({note}) cannot be spent against the current pool ({pool}); \
+         import it through the migration path"
+    )]
+    NoteFromPreviousEpoch {
+        note: crate::epoch::PoolEpoch,
+        pool: crate::epoch::PoolEpoch,
+    },
 }
 
 /// Result type alias for Veil operations
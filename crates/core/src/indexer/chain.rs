@@ -0,0 +1,539 @@
+//! Chain Sync
+//!
+//! Follows a pool program over RPC: pages through the program's confirmed
+//! transactions with `getSignaturesForAddress`, decodes the Anchor events in
+//! each one's logs, and feeds commitments to a `CommitmentIndexer` and spent
+//! nullifiers to a `NullifierSet`.
+//!
+//! Progress is saved as a `ChainCheckpoint`: the last synced slot, the tree
+//! epoch, the current tree's leaves and the spent nullifiers. A sync resumes
+//! at the checkpoint slot, re-reading that slot's transactions in case some
+//! landed after the checkpoint was taken; the indexer and the nullifier set
+//! ignore anything they already hold.
+//!
+//! The program emits commitments and nullifiers as big-endian field
+//! elements. `CommitmentEvent` holds commitments little-endian, so they are
+//! reversed on the way in; nullifiers are kept as emitted.
+
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{CommitmentEvent, CommitmentIndexer, IndexerConfig, IndexerError};
+use crate::crypto::nullifier_set::NullifierSet;
+use crate::rpc::{RpcClient, SignatureInfo, MAX_SIGNATURES_PER_PAGE};
+use crate::transaction::Pubkey;
+
+/// Prefix of the log line carrying an emitted event
+pub const EVENT_LOG_PREFIX: &str = "Program data: ";
+
+/// A pool event decoded from a transaction's logs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoolEvent {
+    /// A commitment was added to the tree
+    CommitmentInserted {
+        /// Commitment (big-endian field element)
+        commitment: [u8; 32],
+        /// Leaf index it was inserted at
+        leaf_index: u64,
+        /// Tree root after the insertion
+        new_root: [u8; 32],
+        /// Encrypted note, possibly empty
+        encrypted_note: Vec<u8>,
+    },
+    /// A nullifier was spent
+    NullifierSpent {
+        /// Nullifier (big-endian field element)
+        nullifier: [u8; 32],
+        /// Slot it was spent in
+        slot: u64,
+    },
+    /// A full tree was archived; leaf indices restart at 0
+    TreeRotated {
+        /// Epoch of the archived tree
+        archived_epoch: u32,
+        /// Its final root
+        final_root: [u8; 32],
+        /// Epoch of the new tree
+        tree_epoch: u32,
+    },
+}
+
+/// Anchor event discriminator: the first 8 bytes of SHA-256("event:<name>")
+fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("event:{}", name).as_bytes());
+    hash[..8].try_into().unwrap()
+}
+
+/// Little-endian Borsh reader over an event body
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn bytes32(&mut self) -> Option<[u8; 32]> {
+        self.take(32)?.try_into().ok()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn vec(&mut self) -> Option<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Some(self.take(len)?.to_vec())
+    }
+}
+
+impl PoolEvent {
+    /// Decode an event from its `Program data` payload
+    ///
+    /// Returns `None` for events other than the ones above.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (tag, body) = data.split_at(8);
+        let mut r = Reader(body);
+        if tag == discriminator("CommitmentInserted") {
+            Some(Self::CommitmentInserted {
+                commitment: r.bytes32()?,
+                leaf_index: r.u64()?,
+                new_root: r.bytes32()?,
+                encrypted_note: r.vec()?,
+            })
+        } else if tag == discriminator("NullifierSpent") {
+            Some(Self::NullifierSpent {
+                nullifier: r.bytes32()?,
+                slot: r.u64()?,
+            })
+        } else if tag == discriminator("TreeRotated") {
+            Some(Self::TreeRotated {
+                archived_epoch: r.u32()?,
+                final_root: r.bytes32()?,
+                tree_epoch: r.u32()?,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Events emitted by `program_id` in a transaction's logs, in order
+///
+/// Only events logged while `program_id` itself is executing count, so
+/// programs it calls or that call it cannot inject events.
+pub fn parse_events(logs: &[String], program_id: &Pubkey) -> Vec<PoolEvent> {
+    let program = bs58::encode(program_id).into_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs {
+        if let Some(data) = line.strip_prefix(EVENT_LOG_PREFIX) {
+            if stack.last() == Some(&program.as_str()) {
+                if let Some(event) = BASE64.decode(data).ok().and_then(|d| PoolEvent::decode(&d)) {
+                    events.push(event);
+                }
+            }
+            continue;
+        }
+        match line.split_whitespace().take(3).collect::<Vec<_>>()[..] {
+            // "Program log:", "Program return:" and the like are not frames
+            ["Program", id, _] if id.ends_with(':') => {}
+            ["Program", id, "invoke"] => stack.push(id),
+            ["Program", _, "success" | "failed:"] => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Saved sync progress
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainCheckpoint {
+    /// Highest slot synced
+    pub slot: u64,
+    /// Epoch of the current tree
+    pub tree_epoch: u32,
+    /// Leaves of the current tree (hex, little-endian)
+    pub leaves: Vec<String>,
+    /// Spent nullifiers (hex, big-endian)
+    pub nullifiers: Vec<String>,
+}
+
+impl ChainCheckpoint {
+    /// Load a checkpoint from a JSON file, or start fresh if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self, IndexerError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let bytes = std::fs::read(path).map_err(|e| IndexerError::Checkpoint(e.to_string()))?;
+        serde_json::from_slice(&bytes).map_err(|e| IndexerError::Checkpoint(e.to_string()))
+    }
+
+    /// Persist the checkpoint as JSON, replacing the file atomically
+    pub fn save(&self, path: &Path) -> Result<(), IndexerError> {
+        let json =
+            serde_json::to_vec(self).map_err(|e| IndexerError::Checkpoint(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json).map_err(|e| IndexerError::Checkpoint(e.to_string()))?;
+        std::fs::rename(&tmp, path).map_err(|e| IndexerError::Checkpoint(e.to_string()))
+    }
+}
+
+/// Counts from one `sync`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    /// Successful transactions read
+    pub transactions: u64,
+    /// Pool events applied
+    pub events: u64,
+}
+
+/// Commitment tree and spent nullifiers of one pool, synced over RPC
+#[derive(Clone, Debug)]
+pub struct ChainIndexer {
+    program_id: Pubkey,
+    config: IndexerConfig,
+    slot: u64,
+    tree_epoch: u32,
+    commitments: CommitmentIndexer,
+    nullifiers: NullifierSet,
+}
+
+impl ChainIndexer {
+    /// Start from an empty pool
+    pub fn new(program_id: Pubkey, config: IndexerConfig) -> Self {
+        Self {
+            program_id,
+            commitments: CommitmentIndexer::new(config.clone()),
+            config,
+            slot: 0,
+            tree_epoch: 0,
+            nullifiers: NullifierSet::new(),
+        }
+    }
+
+    /// Resume from a saved checkpoint
+    pub fn from_checkpoint(
+        program_id: Pubkey,
+        config: IndexerConfig,
+        checkpoint: &ChainCheckpoint,
+    ) -> Result<Self, IndexerError> {
+        let decode = |s: &String| -> Result<[u8; 32], IndexerError> {
+            hex::decode(s)
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .ok_or_else(|| IndexerError::Checkpoint(format!("invalid entry '{}'", s)))
+        };
+        let leaves = checkpoint.leaves.iter().map(decode).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            program_id,
+            commitments: CommitmentIndexer::from_leaves(config.clone(), &leaves)?,
+            config,
+            slot: checkpoint.slot,
+            tree_epoch: checkpoint.tree_epoch,
+            nullifiers: checkpoint.nullifiers.iter().map(decode).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Current progress, for saving
+    pub fn checkpoint(&self) -> ChainCheckpoint {
+        ChainCheckpoint {
+            slot: self.slot,
+            tree_epoch: self.tree_epoch,
+            leaves: self.commitments.leaves().iter().map(hex::encode).collect(),
+            nullifiers: self.nullifiers.iter().map(hex::encode).collect(),
+        }
+    }
+
+    /// Highest slot synced
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Epoch of the current tree
+    pub fn tree_epoch(&self) -> u32 {
+        self.tree_epoch
+    }
+
+    /// The current tree's indexer
+    pub fn commitments(&self) -> &CommitmentIndexer {
+        &self.commitments
+    }
+
+    /// Whether a nullifier (big-endian, as emitted) has been spent
+    pub fn is_spent(&self, nullifier: &[u8; 32]) -> bool {
+        self.nullifiers.contains(nullifier)
+    }
+
+    /// All spent nullifiers seen (big-endian)
+    pub fn spent_nullifiers(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.nullifiers.iter()
+    }
+
+    /// The spent nullifiers as a set, e.g. to persist or diff against an
+    /// earlier sync
+    pub fn nullifiers(&self) -> &NullifierSet {
+        &self.nullifiers
+    }
+
+    /// Apply the events in one successful transaction's logs
+    ///
+    /// Returns the decoded events, e.g. for trial-decrypting new notes.
+    pub fn apply_transaction(
+        &mut self,
+        slot: u64,
+        signature: &str,
+        logs: &[String],
+    ) -> Result<Vec<PoolEvent>, IndexerError> {
+        let events = parse_events(logs, &self.program_id);
+        for (event_index, event) in events.iter().enumerate() {
+            self.apply_event(slot, signature, event_index as u32, event)?;
+        }
+        self.slot = self.slot.max(slot);
+        Ok(events)
+    }
+
+    /// Apply one event, the `event_index`th of its transaction
+    ///
+    /// For events delivered one at a time, e.g. by `pubsub::LogSubscription`.
+    /// Redelivered events are ignored.
+    pub fn apply_event(
+        &mut self,
+        slot: u64,
+        signature: &str,
+        event_index: u32,
+        event: &PoolEvent,
+    ) -> Result<(), IndexerError> {
+        match event {
+            PoolEvent::CommitmentInserted { commitment, leaf_index, .. } => {
+                let mut commitment = *commitment;
+                commitment.reverse();
+                self.commitments.ingest(CommitmentEvent {
+                    slot,
+                    signature: signature.to_owned(),
+                    event_index,
+                    leaf_index: *leaf_index,
+                    commitment,
+                })?;
+            }
+            PoolEvent::NullifierSpent { nullifier, .. } => {
+                self.nullifiers.insert(*nullifier);
+            }
+            PoolEvent::TreeRotated { tree_epoch, .. } if *tree_epoch > self.tree_epoch => {
+                self.tree_epoch = *tree_epoch;
+                self.commitments = CommitmentIndexer::new(self.config.clone());
+            }
+            PoolEvent::TreeRotated { .. } => {}
+        }
+        self.slot = self.slot.max(slot);
+        Ok(())
+    }
+
+    /// Catch up with the chain
+    ///
+    /// Reads every confirmed transaction of the program from the checkpoint
+    /// slot on, oldest first, passing each applied event to `on_event`.
+    /// Fails if leaves are still missing once the tip is reached.
+    pub fn sync<F>(&mut self, rpc: &RpcClient, mut on_event: F) -> Result<SyncStats, IndexerError>
+    where
+        F: FnMut(&PoolEvent),
+    {
+        let rpc_error = |e: crate::error::VeilError| IndexerError::Rpc(e.to_string());
+        let pending = signatures_since(rpc, &self.program_id, self.slot)?;
+
+        let mut stats = SyncStats::default();
+        for info in pending.into_iter().filter(|s| !s.failed) {
+            let logs = rpc.get_transaction_logs(&info.signature).map_err(rpc_error)?;
+            let events = self.apply_transaction(info.slot, &info.signature, &logs)?;
+            stats.transactions += 1;
+            stats.events += events.len() as u64;
+            events.iter().for_each(&mut on_event);
+        }
+
+        self.commitments.ensure_contiguous()?;
+        Ok(stats)
+    }
+}
+
+/// Confirmed transactions of `program_id` from `slot` on, oldest first
+///
+/// Includes failed transactions; check `SignatureInfo::failed`.
+pub fn signatures_since(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    slot: u64,
+) -> Result<Vec<SignatureInfo>, IndexerError> {
+    // Page backwards from the tip to `slot`
+    let mut pending = Vec::new();
+    let mut before: Option<String> = None;
+    loop {
+        let page = rpc
+            .get_signatures_for_address(program_id, before.as_deref(), MAX_SIGNATURES_PER_PAGE)
+            .map_err(|e| IndexerError::Rpc(e.to_string()))?;
+        let full = page.len() == MAX_SIGNATURES_PER_PAGE;
+        before = page.last().map(|s| s.signature.clone());
+
+        let mut reached = false;
+        for info in page {
+            if info.slot < slot {
+                reached = true;
+                break;
+            }
+            pending.push(info);
+        }
+        if reached || !full {
+            break;
+        }
+    }
+    pending.reverse();
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::merkle::PoseidonMerkleTree;
+    use ark_bn254::Fr;
+    use ark_ff::PrimeField;
+
+    const PROGRAM: Pubkey = [7u8; 32];
+
+    fn program() -> String {
+        bs58::encode(PROGRAM).into_string()
+    }
+
+    fn data_line(name: &str, body: &[u8]) -> String {
+        let mut data = discriminator(name).to_vec();
+        data.extend_from_slice(body);
+        format!("{}{}", EVENT_LOG_PREFIX, BASE64.encode(data))
+    }
+
+    fn commitment_line(leaf_index: u64) -> String {
+        let mut body = commitment(leaf_index).to_vec();
+        body.extend_from_slice(&leaf_index.to_le_bytes());
+        body.extend_from_slice(&[0u8; 32]);
+        body.extend_from_slice(&3u32.to_le_bytes());
+        body.extend_from_slice(&[1, 2, 3]);
+        data_line("CommitmentInserted", &body)
+    }
+
+    fn nullifier_line(nullifier: u8) -> String {
+        let mut body = [nullifier; 32].to_vec();
+        body.extend_from_slice(&9u64.to_le_bytes());
+        data_line("NullifierSpent", &body)
+    }
+
+    /// Big-endian commitment of a leaf
+    fn commitment(leaf_index: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&(leaf_index + 1).to_be_bytes());
+        bytes
+    }
+
+    fn transaction(lines: Vec<String>) -> Vec<String> {
+        let mut logs = vec![format!("Program {} invoke [1]", program())];
+        logs.extend(lines);
+        logs.push(format!("Program {} success", program()));
+        logs
+    }
+
+    #[test]
+    fn test_parse_events_only_from_program() {
+        let other = bs58::encode([8u8; 32]).into_string();
+        let logs = vec![
+            format!("Program {} invoke [1]", program()),
+            "Program log: Instruction: Transfer".to_string(),
+            nullifier_line(4),
+            format!("Program {} invoke [2]", other),
+            nullifier_line(5),
+            format!("Program {} success", other),
+            commitment_line(0),
+            data_line("Unshielded", &[0u8; 40]),
+            format!("Program {} success", program()),
+            nullifier_line(6),
+        ];
+
+        let events = parse_events(&logs, &PROGRAM);
+        assert_eq!(
+            events,
+            vec![
+                PoolEvent::NullifierSpent { nullifier: [4u8; 32], slot: 9 },
+                PoolEvent::CommitmentInserted {
+                    commitment: commitment(0),
+                    leaf_index: 0,
+                    new_root: [0u8; 32],
+                    encrypted_note: vec![1, 2, 3],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_and_resume() {
+        let mut indexer = ChainIndexer::new(PROGRAM, IndexerConfig::default());
+        indexer
+            .apply_transaction(10, "a", &transaction(vec![commitment_line(0), commitment_line(1)]))
+            .unwrap();
+        indexer
+            .apply_transaction(11, "b", &transaction(vec![nullifier_line(4), commitment_line(2)]))
+            .unwrap();
+
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..3 {
+            tree.insert(Fr::from_be_bytes_mod_order(&commitment(i))).unwrap();
+        }
+        assert_eq!(indexer.commitments().root(), tree.root());
+        assert!(indexer.is_spent(&[4u8; 32]));
+        assert!(!indexer.is_spent(&[5u8; 32]));
+
+        // A restored indexer re-reads the checkpoint slot without harm
+        let path = std::env::temp_dir().join(format!("veil_chain_{}.json", std::process::id()));
+        indexer.checkpoint().save(&path).unwrap();
+        let checkpoint = ChainCheckpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut resumed =
+            ChainIndexer::from_checkpoint(PROGRAM, IndexerConfig::default(), &checkpoint).unwrap();
+        assert_eq!(resumed.slot(), 11);
+        resumed
+            .apply_transaction(11, "b", &transaction(vec![nullifier_line(4), commitment_line(2)]))
+            .unwrap();
+        resumed.apply_transaction(12, "c", &transaction(vec![commitment_line(3)])).unwrap();
+        tree.insert(Fr::from_be_bytes_mod_order(&commitment(3))).unwrap();
+        assert_eq!(resumed.commitments().root(), tree.root());
+        assert_eq!(resumed.spent_nullifiers().count(), 1);
+    }
+
+    #[test]
+    fn test_tree_rotation_restarts_leaves() {
+        let mut indexer = ChainIndexer::new(PROGRAM, IndexerConfig::default());
+        indexer.apply_transaction(10, "a", &transaction(vec![commitment_line(0)])).unwrap();
+
+        let mut body = 0u32.to_le_bytes().to_vec();
+        body.extend_from_slice(&[0u8; 32]);
+        body.extend_from_slice(&1u32.to_le_bytes());
+        let rotate = data_line("TreeRotated", &body);
+        indexer.apply_transaction(11, "b", &transaction(vec![rotate, commitment_line(0)])).unwrap();
+
+        assert_eq!(indexer.tree_epoch(), 1);
+        assert_eq!(indexer.commitments().next_leaf_index(), 1);
+    }
+}
@@ -0,0 +1,399 @@
+//! Commitment Indexer
+//!
+//! Rebuilds the client-side commitment tree from `CommitmentInserted` events.
+//!
+//! Log streaming transports (websocket subscriptions, geyser plugins) deliver
+//! events at least once and may reorder them within a slot, so the indexer:
+//! - Drops events it has already seen, keyed by (slot, signature, event index),
+//!   remembering keys for a sliding window of slots
+//! - Buffers events that arrive ahead of the next expected leaf index and
+//!   applies them in order once the gap fills
+//! - Fails hard with the missing leaf index if the gap outlives the buffer
+//!
+//! With the `rpc` feature, `chain::ChainIndexer` feeds it from the program's
+//! transaction history and tracks spent nullifiers alongside. With `pubsub`,
+//! `pubsub::LogSubscription` streams new events over a websocket instead.
+
+use std::collections::{BTreeMap, HashSet};
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use thiserror::Error;
+
+use crate::crypto::merkle::{MerkleError, PoseidonMerkleTree};
+
+#[cfg(feature = "rpc")]
+pub mod chain;
+#[cfg(feature = "pubsub")]
+pub mod pubsub;
+
+/// Default number of slots for which processed event keys are remembered
+pub const DEFAULT_DEDUP_WINDOW_SLOTS: u64 = 150;
+
+/// Default number of out-of-order leaves buffered before a gap is fatal
+pub const DEFAULT_REORDER_WINDOW: usize = 256;
+
+/// Errors that can occur while indexing
+#[derive(Error, Debug)]
+pub enum IndexerError {
+    #[error("Missing commitment for leaf {missing} ({buffered} later leaves buffered)")]
+    Gap { missing: u64, buffered: usize },
+    #[error("Conflicting commitment for leaf {0}")]
+    ConflictingLeaf(u64),
+    #[error("Merkle error: {0}")]
+    Merkle(#[from] MerkleError),
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(String),
+}
+
+/// A `CommitmentInserted` event as delivered by the transport
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentEvent {
+    /// Slot the transaction landed in
+    pub slot: u64,
+    /// Transaction signature (base58)
+    pub signature: String,
+    /// Position of the event within the transaction's logs
+    pub event_index: u32,
+    /// Leaf index assigned by the program
+    pub leaf_index: u64,
+    /// Commitment bytes (little-endian field element)
+    pub commitment: [u8; 32],
+}
+
+impl CommitmentEvent {
+    fn key(&self) -> EventKey {
+        (self.slot, self.signature.clone(), self.event_index)
+    }
+}
+
+type EventKey = (u64, String, u32);
+
+/// What happened to an ingested event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IngestOutcome {
+    /// Already processed, ignored
+    Duplicate,
+    /// Held until earlier leaves arrive
+    Buffered,
+    /// Inserted into the tree together with this many leaves in total
+    Applied(usize),
+}
+
+/// Indexer configuration
+#[derive(Clone, Debug)]
+pub struct IndexerConfig {
+    /// Slots behind the newest seen slot for which event keys are kept
+    pub dedup_window_slots: u64,
+    /// Maximum number of leaves buffered ahead of a missing one
+    pub reorder_window: usize,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        Self {
+            dedup_window_slots: DEFAULT_DEDUP_WINDOW_SLOTS,
+            reorder_window: DEFAULT_REORDER_WINDOW,
+        }
+    }
+}
+
+/// Idempotent, order-tolerant builder of the commitment tree
+#[derive(Clone, Debug)]
+pub struct CommitmentIndexer {
+    tree: PoseidonMerkleTree,
+    config: IndexerConfig,
+    /// Processed event keys
+    seen: HashSet<EventKey>,
+    /// Processed event keys grouped by slot, for pruning
+    seen_by_slot: BTreeMap<u64, Vec<EventKey>>,
+    /// Commitments received ahead of the next expected leaf index
+    pending: BTreeMap<u64, [u8; 32]>,
+    /// Commitments applied to the tree, by leaf index
+    applied: Vec<[u8; 32]>,
+    highest_slot: u64,
+}
+
+impl Default for CommitmentIndexer {
+    fn default() -> Self {
+        Self::new(IndexerConfig::default())
+    }
+}
+
+impl CommitmentIndexer {
+    /// Create an indexer over an empty tree
+    pub fn new(config: IndexerConfig) -> Self {
+        Self {
+            tree: PoseidonMerkleTree::new(),
+            config,
+            seen: HashSet::new(),
+            seen_by_slot: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            applied: Vec::new(),
+            highest_slot: 0,
+        }
+    }
+
+    /// Resume from the leaves of an earlier run, in leaf order
+    pub fn from_leaves(config: IndexerConfig, leaves: &[[u8; 32]]) -> Result<Self, IndexerError> {
+        let mut indexer = Self::new(config);
+        indexer.pending.extend((0u64..).zip(leaves.iter().copied()));
+        indexer.apply_pending()?;
+        Ok(indexer)
+    }
+
+    /// Process one event
+    ///
+    /// Duplicates are ignored, early leaves are buffered and every leaf that
+    /// becomes contiguous is inserted into the tree.
+    pub fn ingest(&mut self, event: CommitmentEvent) -> Result<IngestOutcome, IndexerError> {
+        let key = event.key();
+        if self.seen.contains(&key) {
+            return Ok(IngestOutcome::Duplicate);
+        }
+
+        // Redelivery after its key left the dedup window, or a conflicting
+        // event for a leaf we already hold
+        let known = if event.leaf_index < self.next_leaf_index() {
+            Some(self.applied[event.leaf_index as usize])
+        } else {
+            self.pending.get(&event.leaf_index).copied()
+        };
+        if let Some(commitment) = known {
+            if commitment != event.commitment {
+                return Err(IndexerError::ConflictingLeaf(event.leaf_index));
+            }
+            self.remember(key);
+            return Ok(IngestOutcome::Duplicate);
+        }
+
+        self.remember(key);
+        self.pending.insert(event.leaf_index, event.commitment);
+
+        let applied = self.apply_pending()?;
+        if applied > 0 {
+            return Ok(IngestOutcome::Applied(applied));
+        }
+
+        if self.pending.len() > self.config.reorder_window {
+            return Err(IndexerError::Gap {
+                missing: self.next_leaf_index(),
+                buffered: self.pending.len(),
+            });
+        }
+
+        Ok(IngestOutcome::Buffered)
+    }
+
+    /// Fail if any leaves are still waiting on a missing predecessor
+    ///
+    /// Call once the transport has caught up to the chain tip.
+    pub fn ensure_contiguous(&self) -> Result<(), IndexerError> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(IndexerError::Gap {
+                missing: self.next_leaf_index(),
+                buffered: self.pending.len(),
+            })
+        }
+    }
+
+    /// The tree built from all contiguous leaves
+    pub fn tree(&self) -> &PoseidonMerkleTree {
+        &self.tree
+    }
+
+    /// Current tree root
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Leaf index the indexer expects next
+    pub fn next_leaf_index(&self) -> u64 {
+        self.tree.len()
+    }
+
+    /// Commitments applied to the tree, by leaf index
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.applied
+    }
+
+    /// Number of leaves buffered ahead of a gap
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn apply_pending(&mut self) -> Result<usize, IndexerError> {
+        let start = self.next_leaf_index();
+        let mut ready = Vec::new();
+        while let Some(commitment) = self.pending.remove(&(start + ready.len() as u64)) {
+            ready.push(commitment);
+        }
+        let leaves: Vec<Fr> = ready.iter().map(|c| Fr::from_le_bytes_mod_order(c)).collect();
+        self.tree.insert_batch(&leaves)?;
+        self.applied.extend_from_slice(&ready);
+        Ok(ready.len())
+    }
+
+    fn remember(&mut self, key: EventKey) {
+        let slot = key.0;
+        self.highest_slot = self.highest_slot.max(slot);
+        self.seen_by_slot.entry(slot).or_default().push(key.clone());
+        self.seen.insert(key);
+
+        // Forget keys from slots that fell out of the window
+        let cutoff = self.highest_slot.saturating_sub(self.config.dedup_window_slots);
+        let retained = self.seen_by_slot.split_off(&cutoff);
+        for keys in std::mem::replace(&mut self.seen_by_slot, retained).into_values() {
+            for key in keys {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commitment(i: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&(i + 1).to_le_bytes());
+        bytes
+    }
+
+    /// One event per leaf, two events per transaction, one transaction per slot pair
+    fn canonical(n: u64) -> Vec<CommitmentEvent> {
+        (0..n)
+            .map(|i| CommitmentEvent {
+                slot: 100 + i / 4,
+                signature: format!("sig{}", i / 2),
+                event_index: (i % 2) as u32,
+                leaf_index: i,
+                commitment: commitment(i),
+            })
+            .collect()
+    }
+
+    fn canonical_root(n: u64) -> Fr {
+        let mut tree = PoseidonMerkleTree::new();
+        for i in 0..n {
+            tree.insert(Fr::from_le_bytes_mod_order(&commitment(i))).unwrap();
+        }
+        tree.root()
+    }
+
+    #[test]
+    fn test_in_order_stream() {
+        let mut indexer = CommitmentIndexer::default();
+        for event in canonical(8) {
+            assert_eq!(indexer.ingest(event).unwrap(), IngestOutcome::Applied(1));
+        }
+        assert_eq!(indexer.next_leaf_index(), 8);
+        assert_eq!(indexer.root(), canonical_root(8));
+    }
+
+    #[test]
+    fn test_duplicated_stream() {
+        let mut indexer = CommitmentIndexer::default();
+        for event in canonical(8) {
+            indexer.ingest(event.clone()).unwrap();
+            assert_eq!(indexer.ingest(event).unwrap(), IngestOutcome::Duplicate);
+        }
+        // Replaying the whole stream changes nothing
+        for event in canonical(8) {
+            assert_eq!(indexer.ingest(event).unwrap(), IngestOutcome::Duplicate);
+        }
+        assert_eq!(indexer.root(), canonical_root(8));
+    }
+
+    #[test]
+    fn test_reordered_stream() {
+        let events = canonical(8);
+        let order = [1, 0, 3, 2, 2, 5, 7, 4, 6, 1];
+
+        let mut indexer = CommitmentIndexer::default();
+        for &i in &order {
+            indexer.ingest(events[i].clone()).unwrap();
+        }
+        indexer.ensure_contiguous().unwrap();
+        assert_eq!(indexer.next_leaf_index(), 8);
+        assert_eq!(indexer.root(), canonical_root(8));
+    }
+
+    #[test]
+    fn test_buffered_until_gap_fills() {
+        let events = canonical(3);
+        let mut indexer = CommitmentIndexer::default();
+
+        assert_eq!(indexer.ingest(events[2].clone()).unwrap(), IngestOutcome::Buffered);
+        assert_eq!(indexer.ingest(events[1].clone()).unwrap(), IngestOutcome::Buffered);
+        assert_eq!(indexer.pending_len(), 2);
+        assert!(matches!(
+            indexer.ensure_contiguous(),
+            Err(IndexerError::Gap { missing: 0, buffered: 2 })
+        ));
+
+        assert_eq!(indexer.ingest(events[0].clone()).unwrap(), IngestOutcome::Applied(3));
+        assert_eq!(indexer.pending_len(), 0);
+        assert_eq!(indexer.root(), canonical_root(3));
+    }
+
+    #[test]
+    fn test_gap_beyond_window_is_fatal() {
+        let config = IndexerConfig {
+            reorder_window: 3,
+            ..Default::default()
+        };
+        let mut indexer = CommitmentIndexer::new(config);
+        let events = canonical(8);
+
+        indexer.ingest(events[0].clone()).unwrap();
+        // Leaf 1 never arrives
+        for event in &events[2..5] {
+            assert_eq!(indexer.ingest(event.clone()).unwrap(), IngestOutcome::Buffered);
+        }
+        match indexer.ingest(events[5].clone()) {
+            Err(IndexerError::Gap { missing, buffered }) => {
+                assert_eq!(missing, 1);
+                assert_eq!(buffered, 4);
+            }
+            other => panic!("expected gap error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_redelivery_after_dedup_window() {
+        let config = IndexerConfig {
+            dedup_window_slots: 1,
+            ..Default::default()
+        };
+        let mut indexer = CommitmentIndexer::new(config);
+        let events = canonical(12);
+        for event in &events {
+            indexer.ingest(event.clone()).unwrap();
+        }
+
+        // Key for the first event has been pruned, but its leaf is known
+        assert!(!indexer.seen.contains(&events[0].key()));
+        assert_eq!(indexer.ingest(events[0].clone()).unwrap(), IngestOutcome::Duplicate);
+        assert_eq!(indexer.root(), canonical_root(12));
+    }
+
+    #[test]
+    fn test_conflicting_leaf() {
+        let mut indexer = CommitmentIndexer::default();
+        let events = canonical(2);
+        indexer.ingest(events[0].clone()).unwrap();
+
+        let mut forged = events[1].clone();
+        forged.leaf_index = 0;
+        assert!(matches!(
+            indexer.ingest(forged),
+            Err(IndexerError::ConflictingLeaf(0))
+        ));
+    }
+}
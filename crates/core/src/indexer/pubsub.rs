@@ -0,0 +1,467 @@
+//! Log Subscriptions
+//!
+//! Pushes pool events to consumers as they land instead of polling for them.
+//! A background thread holds a `logsSubscribe` websocket subscription for
+//! transactions mentioning the program, decodes each notification with
+//! `parse_events` and sends the events down a channel.
+//!
+//! Nodes keep no subscription history, so whatever lands while the socket is
+//! down would be lost. After every (re)subscription the thread backfills over
+//! RPC from the last slot it delivered, re-reading that slot, before passing
+//! on live notifications. Events can therefore arrive more than once, which
+//! `CommitmentIndexer` and `ChainIndexer` already ignore.
+
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+use super::chain::{parse_events, signatures_since, PoolEvent};
+use super::IndexerError;
+use crate::rpc::RpcClient;
+use crate::transaction::Pubkey;
+
+/// Public devnet websocket endpoint
+pub const DEVNET_WS_URL: &str = "wss://api.devnet.solana.com";
+
+/// How long a blocking read waits before the thread checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Idle time after which a ping keeps the connection open
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// JSON-RPC id of the subscribe request
+const SUBSCRIBE_ID: u64 = 1;
+
+/// Subscriber configuration
+#[derive(Clone, Debug)]
+pub struct SubscriberConfig {
+    /// Wait before the first reconnection attempt
+    pub reconnect_delay: Duration,
+    /// Cap on the wait, which doubles after each failed attempt
+    pub max_reconnect_delay: Duration,
+    /// Consecutive failed connections before giving up; 0 retries forever
+    pub max_attempts: u32,
+}
+
+impl Default for SubscriberConfig {
+    fn default() -> Self {
+        Self {
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+            max_attempts: 0,
+        }
+    }
+}
+
+/// A pool event with the position it was emitted at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamedEvent {
+    /// Slot the transaction landed in
+    pub slot: u64,
+    /// Transaction signature (base58)
+    pub signature: String,
+    /// Position of the event among the transaction's pool events
+    pub event_index: u32,
+    /// The event
+    pub event: PoolEvent,
+}
+
+/// A running subscription to one pool's events
+///
+/// Receive events from `receiver()`. An error on the channel is final: the
+/// thread gave up reconnecting and the channel closes after it. Dropping the
+/// subscription stops the thread.
+pub struct LogSubscription {
+    receiver: Receiver<Result<StreamedEvent, IndexerError>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LogSubscription {
+    /// Subscribe to `program_id`'s events at the websocket endpoint `ws_url`
+    ///
+    /// With `rpc`, gaps are backfilled after each reconnect, and the first
+    /// connection also backfills from `from_slot` if given. Without it only
+    /// live events are delivered.
+    pub fn start(
+        ws_url: impl Into<String>,
+        rpc: Option<RpcClient>,
+        program_id: Pubkey,
+        from_slot: Option<u64>,
+        config: SubscriberConfig,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            ws_url: ws_url.into(),
+            rpc,
+            program_id,
+            config,
+            stop: stop.clone(),
+            sender,
+            resume_slot: from_slot,
+            subscribed: false,
+        };
+        Self {
+            receiver,
+            stop,
+            handle: Some(thread::spawn(move || worker.run())),
+        }
+    }
+
+    /// Channel the events arrive on
+    pub fn receiver(&self) -> &Receiver<Result<StreamedEvent, IndexerError>> {
+        &self.receiver
+    }
+}
+
+impl Drop for LogSubscription {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Why a session ended
+enum Exit {
+    /// Shutdown was requested or the receiver is gone
+    Stopped,
+    /// The connection or backfill failed; reconnect
+    Failed(IndexerError),
+}
+
+struct Worker {
+    ws_url: String,
+    rpc: Option<RpcClient>,
+    program_id: Pubkey,
+    config: SubscriberConfig,
+    stop: Arc<AtomicBool>,
+    sender: Sender<Result<StreamedEvent, IndexerError>>,
+    /// Slot to backfill from on the next subscription
+    resume_slot: Option<u64>,
+    /// Whether the current session got as far as subscribing
+    subscribed: bool,
+}
+
+impl Worker {
+    fn run(mut self) {
+        let mut failures = 0;
+        let mut delay = self.config.reconnect_delay;
+        loop {
+            self.subscribed = false;
+            let error = match self.session() {
+                Exit::Stopped => return,
+                Exit::Failed(error) => error,
+            };
+
+            // A session that subscribed counts as a success
+            if self.subscribed {
+                failures = 0;
+                delay = self.config.reconnect_delay;
+            }
+            failures += 1;
+            if self.config.max_attempts != 0 && failures >= self.config.max_attempts {
+                let _ = self.sender.send(Err(error));
+                return;
+            }
+
+            let wake = Instant::now() + delay;
+            while Instant::now() < wake {
+                if self.stopped() {
+                    return;
+                }
+                thread::sleep(POLL_INTERVAL.min(wake - Instant::now()));
+            }
+            delay = (delay * 2).min(self.config.max_reconnect_delay);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    fn session(&mut self) -> Exit {
+        let (mut socket, _) = match tungstenite::connect(self.ws_url.as_str()) {
+            Ok(connection) => connection,
+            Err(e) => return Exit::Failed(ws_error(e)),
+        };
+        let stream = match socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream,
+            MaybeTlsStream::Rustls(stream) => stream.get_ref(),
+            _ => unreachable!("only rustls is enabled"),
+        };
+        if let Err(e) = TcpStream::set_read_timeout(stream, Some(POLL_INTERVAL)) {
+            return Exit::Failed(IndexerError::Rpc(e.to_string()));
+        }
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": SUBSCRIBE_ID,
+            "method": "logsSubscribe",
+            "params": [
+                { "mentions": [bs58::encode(self.program_id).into_string()] },
+                { "commitment": "confirmed" }
+            ]
+        });
+        if let Err(e) = socket.send(Message::Text(request.to_string())) {
+            return Exit::Failed(ws_error(e));
+        }
+
+        let mut last_activity = Instant::now();
+        loop {
+            if self.stopped() {
+                let _ = socket.close(None);
+                return Exit::Stopped;
+            }
+            let message = match socket.read() {
+                Ok(message) => message,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if last_activity.elapsed() >= PING_INTERVAL {
+                        if let Err(e) = socket.send(Message::Ping(Vec::new())) {
+                            return Exit::Failed(ws_error(e));
+                        }
+                        last_activity = Instant::now();
+                    }
+                    continue;
+                }
+                Err(e) => return Exit::Failed(ws_error(e)),
+            };
+            last_activity = Instant::now();
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => {
+                    return Exit::Failed(IndexerError::Rpc("websocket closed by server".into()))
+                }
+                _ => continue,
+            };
+            let Ok(value) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+
+            if value["id"] == SUBSCRIBE_ID {
+                if let Some(error) = value.get("error") {
+                    return Exit::Failed(IndexerError::Rpc(format!("logsSubscribe: {}", error)));
+                }
+                self.subscribed = true;
+                if let Err(exit) = self.backfill() {
+                    return exit;
+                }
+                continue;
+            }
+            if let Some(events) = parse_notification(&value, &self.program_id) {
+                if let Err(exit) = deliver(&self.sender, &mut self.resume_slot, events) {
+                    return exit;
+                }
+            }
+        }
+    }
+
+    /// Re-read everything from the resume slot over RPC
+    fn backfill(&mut self) -> Result<(), Exit> {
+        let Some(rpc) = &self.rpc else {
+            return Ok(());
+        };
+        let rpc_error = |e: crate::error::VeilError| Exit::Failed(IndexerError::Rpc(e.to_string()));
+
+        // Nothing delivered yet: anything from now on is covered by the
+        // subscription, but a later reconnect must resume from here
+        let Some(slot) = self.resume_slot else {
+            self.resume_slot = Some(rpc.get_slot().map_err(rpc_error)?);
+            return Ok(());
+        };
+
+        let signatures = signatures_since(rpc, &self.program_id, slot).map_err(Exit::Failed)?;
+        for info in signatures.into_iter().filter(|s| !s.failed) {
+            let logs = rpc.get_transaction_logs(&info.signature).map_err(rpc_error)?;
+            let events = stream_events(info.slot, &info.signature, &logs, &self.program_id);
+            deliver(&self.sender, &mut self.resume_slot, events)?;
+        }
+        Ok(())
+    }
+}
+
+/// Send events on, advancing the resume slot past them
+fn deliver(
+    sender: &Sender<Result<StreamedEvent, IndexerError>>,
+    resume_slot: &mut Option<u64>,
+    events: Vec<StreamedEvent>,
+) -> Result<(), Exit> {
+    for event in events {
+        *resume_slot = Some(resume_slot.unwrap_or(0).max(event.slot));
+        sender.send(Ok(event)).map_err(|_| Exit::Stopped)?;
+    }
+    Ok(())
+}
+
+fn ws_error(e: tungstenite::Error) -> IndexerError {
+    IndexerError::Rpc(format!("websocket: {}", e))
+}
+
+fn stream_events(
+    slot: u64,
+    signature: &str,
+    logs: &[String],
+    program_id: &Pubkey,
+) -> Vec<StreamedEvent> {
+    parse_events(logs, program_id)
+        .into_iter()
+        .enumerate()
+        .map(|(event_index, event)| StreamedEvent {
+            slot,
+            signature: signature.to_owned(),
+            event_index: event_index as u32,
+            event,
+        })
+        .collect()
+}
+
+/// Events in a `logsNotification`, or `None` for any other message
+///
+/// Failed transactions yield no events.
+fn parse_notification(message: &Value, program_id: &Pubkey) -> Option<Vec<StreamedEvent>> {
+    if message["method"] != "logsNotification" {
+        return None;
+    }
+    let result = &message["params"]["result"];
+    let slot = result["context"]["slot"].as_u64()?;
+    let value = &result["value"];
+    if !value["err"].is_null() {
+        return Some(Vec::new());
+    }
+    let signature = value["signature"].as_str()?;
+    let logs: Vec<String> = value["logs"]
+        .as_array()?
+        .iter()
+        .filter_map(|l| l.as_str().map(str::to_owned))
+        .collect();
+    Some(stream_events(slot, signature, &logs, program_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+
+    const PROGRAM: Pubkey = [7u8; 32];
+
+    fn nullifier_line(nullifier: u8) -> String {
+        let mut data = Sha256::digest(b"event:NullifierSpent")[..8].to_vec();
+        data.extend_from_slice(&[nullifier; 32]);
+        data.extend_from_slice(&9u64.to_le_bytes());
+        format!("Program data: {}", BASE64.encode(data))
+    }
+
+    fn notification(slot: u64, signature: &str, nullifier: u8, failed: bool) -> Value {
+        let program = bs58::encode(PROGRAM).into_string();
+        json!({
+            "jsonrpc": "2.0",
+            "method": "logsNotification",
+            "params": {
+                "result": {
+                    "context": { "slot": slot },
+                    "value": {
+                        "signature": signature,
+                        "err": if failed { json!({ "InstructionError": [0, "Custom"] }) } else { Value::Null },
+                        "logs": [
+                            format!("Program {} invoke [1]", program),
+                            nullifier_line(nullifier),
+                            format!("Program {} success", program),
+                        ]
+                    }
+                },
+                "subscription": 3
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_notification() {
+        let events = parse_notification(&notification(42, "sig", 5, false), &PROGRAM).unwrap();
+        assert_eq!(
+            events,
+            vec![StreamedEvent {
+                slot: 42,
+                signature: "sig".into(),
+                event_index: 0,
+                event: PoolEvent::NullifierSpent { nullifier: [5u8; 32], slot: 9 },
+            }]
+        );
+
+        assert_eq!(parse_notification(&notification(42, "sig", 5, true), &PROGRAM), Some(vec![]));
+        assert_eq!(parse_notification(&json!({ "id": 1, "result": 3 }), &PROGRAM), None);
+    }
+
+    #[test]
+    fn test_resubscribes_after_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        // Each connection gets one notification and is then dropped
+        let server = thread::spawn(move || {
+            for (slot, nullifier) in [(10, 1u8), (11, 2u8)] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut socket = tungstenite::accept(stream).unwrap();
+                let request: Value = match socket.read().unwrap() {
+                    Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                    other => panic!("unexpected {:?}", other),
+                };
+                assert_eq!(request["method"], "logsSubscribe");
+                let reply = json!({ "jsonrpc": "2.0", "result": 3, "id": request["id"] });
+                socket.send(Message::Text(reply.to_string())).unwrap();
+                let note = notification(slot, &format!("sig{}", slot), nullifier, false);
+                socket.send(Message::Text(note.to_string())).unwrap();
+                socket.close(None).unwrap();
+                let _ = socket.read();
+            }
+        });
+
+        let config = SubscriberConfig {
+            reconnect_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let subscription = LogSubscription::start(url, None, PROGRAM, None, config);
+        let timeout = Duration::from_secs(10);
+        for (slot, nullifier) in [(10, 1u8), (11, 2u8)] {
+            let event = subscription.receiver().recv_timeout(timeout).unwrap().unwrap();
+            assert_eq!(event.slot, slot);
+            assert_eq!(event.event, PoolEvent::NullifierSpent { nullifier: [nullifier; 32], slot: 9 });
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        // Nothing listens on a port that was just released
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("ws://{}", listener.local_addr().unwrap())
+        };
+        let config = SubscriberConfig {
+            reconnect_delay: Duration::from_millis(1),
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let subscription = LogSubscription::start(url, None, PROGRAM, None, config);
+        let item = subscription.receiver().recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(matches!(item, Err(IndexerError::Rpc(_))));
+    }
+}
@@ -0,0 +1,471 @@
+//! Hierarchical Deterministic Keys
+//!
+//! Derives every wallet key from a single seed, in the style of ZIP32:
+//! - `ExtendedSpendingKey::master` turns the seed into a secret and a chain
+//!   code
+//! - `derive_child` derives a child from both; all derivation is hardened,
+//!   so a child key reveals nothing about its parent or siblings
+//! - Accounts live at `m/32'/501'/account'` (purpose 32 as in ZIP32, coin
+//!   type 501 for Solana)
+//!
+//! Each extended key's secret is an ordinary 32-byte note secret, so the
+//! spending key, viewing keys, encryption keypair and diversified addresses
+//! all follow from it. Backing up the seed backs up every account.
+//!
+//! Derivation uses BLAKE3 in derive-key mode for the master key and keyed by
+//! the chain code for children, with 64 bytes of output split into secret
+//! and chain code.
+//!
+//! Extended keys, seeds and intermediate hash output are zeroized on drop.
+//!
+//! Seeds are backed up as 24-word BIP39 phrases: `generate_mnemonic` makes
+//! one and `from_mnemonic` restores the master key from it, with an optional
+//! passphrase. The seed is the standard BIP39 seed, so any BIP39 tool can
+//! check a phrase, but only this module derives Veil keys from it.
+//!
+//! Browser-wallet users can skip the seed entirely: `from_wallet_signature`
+//! hashes the wallet's ed25519 signature of `WALLET_SIGNATURE_MESSAGE` into
+//! the seed. Ed25519 signatures are deterministic, so the same wallet always
+//! recovers the same keys.
+
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::address::DiversifiedAddress;
+use crate::crypto::ct::ct_eq;
+use crate::crypto::encryption::EncryptionKeypair;
+use crate::crypto::nullifier::{OutgoingViewingKey, SpendingKey, ViewingKey};
+
+/// BLAKE3 context for the master key
+const MASTER_CONTEXT: &str = "veil hd master key v1";
+
+/// BLAKE3 context for seeds from wallet signatures
+const WALLET_SEED_CONTEXT: &str = "veil wallet signature seed v1";
+
+/// Message a Solana wallet signs to derive its Veil keys
+///
+/// Changing it changes every wallet-derived key.
+pub const WALLET_SIGNATURE_MESSAGE: &[u8] = b"Veil key derivation v1\n\n\
+Sign this message to unlock your Veil shielded account. \
+Only sign it on sites you trust: the signature controls your shielded funds.";
+
+/// Set on every child index: all derivation is hardened
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// Purpose field of account paths (as in ZIP32)
+pub const PURPOSE: u32 = 32;
+
+/// Coin type of account paths (Solana's SLIP-44 coin type)
+pub const COIN_TYPE: u32 = 501;
+
+/// Minimum seed length in bytes
+pub const MIN_SEED_SIZE: usize = 32;
+
+/// Maximum seed length in bytes
+pub const MAX_SEED_SIZE: usize = 252;
+
+/// Words in a backup phrase
+pub const MNEMONIC_WORDS: usize = 24;
+
+/// Size of a serialized extended key:
+/// depth(1) + parent fingerprint(4) + child index(4) + chain code(32) + secret(32)
+pub const EXTENDED_KEY_SIZE: usize = 73;
+
+/// Errors for key derivation
+#[derive(Error, Debug)]
+pub enum KeyError {
+    #[error("Seed must be {MIN_SEED_SIZE} to {MAX_SEED_SIZE} bytes, got {0}")]
+    InvalidSeedLength(usize),
+    #[error("Extended key must be {EXTENDED_KEY_SIZE} bytes, got {0}")]
+    InvalidLength(usize),
+    #[error("Maximum derivation depth reached")]
+    MaxDepth,
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+}
+
+/// A spending secret with the chain code to derive children from it
+#[derive(Clone, Debug)]
+pub struct ExtendedSpendingKey {
+    /// Number of derivation steps from the master key
+    pub depth: u8,
+    /// First 4 bytes of the parent's fingerprint (zero for the master key)
+    pub parent_fingerprint: [u8; 4],
+    /// Index this key was derived at, hardened bit included (zero for the
+    /// master key)
+    pub child_index: u32,
+    chain_code: [u8; 32],
+    secret: [u8; 32],
+}
+
+impl ExtendedSpendingKey {
+    /// Master key from a seed
+    pub fn master(seed: &[u8]) -> Result<Self, KeyError> {
+        if !(MIN_SEED_SIZE..=MAX_SEED_SIZE).contains(&seed.len()) {
+            return Err(KeyError::InvalidSeedLength(seed.len()));
+        }
+        let mut hasher = blake3::Hasher::new_derive_key(MASTER_CONTEXT);
+        hasher.update(seed);
+        let (secret, chain_code) = split_output(&hasher);
+        Ok(Self {
+            depth: 0,
+            parent_fingerprint: [0u8; 4],
+            child_index: 0,
+            chain_code,
+            secret,
+        })
+    }
+
+    /// Hardened child at `index` (the hardened bit is added if missing)
+    pub fn derive_child(&self, index: u32) -> Result<Self, KeyError> {
+        let depth = self.depth.checked_add(1).ok_or(KeyError::MaxDepth)?;
+        let child_index = index | HARDENED;
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.chain_code);
+        hasher.update(&self.secret);
+        hasher.update(&child_index.to_le_bytes());
+        let (secret, chain_code) = split_output(&hasher);
+
+        let mut parent_fingerprint = [0u8; 4];
+        parent_fingerprint.copy_from_slice(&self.fingerprint()[..4]);
+        Ok(Self {
+            depth,
+            parent_fingerprint,
+            child_index,
+            chain_code,
+            secret,
+        })
+    }
+
+    /// Descendant along `path`, one `derive_child` per index
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, KeyError> {
+        path.iter().try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    /// Key of `account` under a seed: `m/32'/501'/account'`
+    pub fn account(seed: &[u8], account: u32) -> Result<Self, KeyError> {
+        Self::master(seed)?.derive_path(&[PURPOSE, COIN_TYPE, account])
+    }
+
+    /// Identifies this key without revealing it: a hash of its viewing key
+    pub fn fingerprint(&self) -> [u8; 32] {
+        *blake3::hash(&self.viewing_key().to_bytes()).as_bytes()
+    }
+
+    /// The 32-byte note secret
+    pub fn secret(&self) -> &[u8; 32] {
+        &self.secret
+    }
+
+    /// Spending key for notes owned by this key
+    pub fn spending_key(&self) -> SpendingKey {
+        SpendingKey::from_secret(&self.secret)
+    }
+
+    /// Incoming viewing key
+    pub fn viewing_key(&self) -> ViewingKey {
+        ViewingKey::from_secret(&self.secret)
+    }
+
+    /// Outgoing viewing key
+    pub fn outgoing_viewing_key(&self) -> OutgoingViewingKey {
+        OutgoingViewingKey::from_secret(&self.secret)
+    }
+
+    /// Note encryption keypair, from the incoming viewing key
+    pub fn encryption_keypair(&self) -> EncryptionKeypair {
+        EncryptionKeypair::from_viewing_key(&self.viewing_key())
+    }
+
+    /// The `index`-th diversified address
+    pub fn address(&self, index: u64) -> DiversifiedAddress {
+        DiversifiedAddress::derive(&self.viewing_key(), index)
+    }
+
+    /// Serialize to bytes:
+    /// depth || parent fingerprint || child index (LE) || chain code || secret
+    pub fn to_bytes(&self) -> [u8; EXTENDED_KEY_SIZE] {
+        let mut bytes = [0u8; EXTENDED_KEY_SIZE];
+        bytes[0] = self.depth;
+        bytes[1..5].copy_from_slice(&self.parent_fingerprint);
+        bytes[5..9].copy_from_slice(&self.child_index.to_le_bytes());
+        bytes[9..41].copy_from_slice(&self.chain_code);
+        bytes[41..].copy_from_slice(&self.secret);
+        bytes
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyError> {
+        if bytes.len() != EXTENDED_KEY_SIZE {
+            return Err(KeyError::InvalidLength(bytes.len()));
+        }
+        Ok(Self {
+            depth: bytes[0],
+            parent_fingerprint: bytes[1..5].try_into().unwrap(),
+            child_index: u32::from_le_bytes(bytes[5..9].try_into().unwrap()),
+            chain_code: bytes[9..41].try_into().unwrap(),
+            secret: bytes[41..].try_into().unwrap(),
+        })
+    }
+}
+
+// Chain code and secret compare in constant time
+impl PartialEq for ExtendedSpendingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.depth == other.depth
+            && self.parent_fingerprint == other.parent_fingerprint
+            && self.child_index == other.child_index
+            && ct_eq(&self.chain_code, &other.chain_code)
+            && ct_eq(&self.secret, &other.secret)
+    }
+}
+
+impl Eq for ExtendedSpendingKey {}
+
+impl Zeroize for ExtendedSpendingKey {
+    fn zeroize(&mut self) {
+        self.chain_code.zeroize();
+        self.secret.zeroize();
+    }
+}
+
+impl Drop for ExtendedSpendingKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// A fresh 24-word backup phrase (BIP39, English wordlist)
+pub fn generate_mnemonic() -> String {
+    let mut entropy = Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(&mut *entropy);
+    Mnemonic::from_entropy(&*entropy, Language::English)
+        .expect("32 bytes of entropy is a 24-word phrase")
+        .into_phrase()
+}
+
+/// Check that a phrase has 24 known words and a valid checksum
+pub fn validate_mnemonic(phrase: &str) -> Result<(), KeyError> {
+    parse_mnemonic(phrase).map(|_| ())
+}
+
+/// The 64-byte BIP39 seed of a phrase and passphrase (empty for none)
+pub fn mnemonic_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], KeyError> {
+    let mnemonic = parse_mnemonic(phrase)?;
+    let mut seed = [0u8; 64];
+    seed.copy_from_slice(Seed::new(&mnemonic, passphrase).as_bytes());
+    Ok(seed)
+}
+
+/// Master key from a backup phrase and passphrase (empty for none)
+///
+/// A wrong passphrase is not an error: it restores a different, empty
+/// wallet, as in BIP39.
+pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<ExtendedSpendingKey, KeyError> {
+    ExtendedSpendingKey::master(&*Zeroizing::new(mnemonic_seed(phrase, passphrase)?))
+}
+
+fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, KeyError> {
+    let mnemonic = Mnemonic::from_phrase(phrase, Language::English)
+        .map_err(|e| KeyError::InvalidMnemonic(e.to_string()))?;
+    if MnemonicType::for_phrase(mnemonic.phrase()).map(|t| t.word_count()).ok()
+        != Some(MNEMONIC_WORDS)
+    {
+        return Err(KeyError::InvalidMnemonic(format!(
+            "expected {} words",
+            MNEMONIC_WORDS
+        )));
+    }
+    Ok(mnemonic)
+}
+
+/// Master key from a Solana wallet's signature of `WALLET_SIGNATURE_MESSAGE`
+///
+/// Only wallets that sign deterministically (standard ed25519, as all Solana
+/// wallets do) recover the same keys every time; check the signature against
+/// the wallet's public key first so a wrong message cannot derive keys.
+pub fn from_wallet_signature(signature: &[u8; 64]) -> Result<ExtendedSpendingKey, KeyError> {
+    let seed = Zeroizing::new(blake3::derive_key(WALLET_SEED_CONTEXT, signature));
+    ExtendedSpendingKey::master(&*seed)
+}
+
+/// Master key from a Solana keypair, signing `WALLET_SIGNATURE_MESSAGE`
+/// itself
+#[cfg(feature = "rpc")]
+pub fn from_wallet_keypair(
+    keypair: &ed25519_dalek::Keypair,
+) -> Result<ExtendedSpendingKey, KeyError> {
+    use ed25519_dalek::Signer;
+
+    from_wallet_signature(&keypair.sign(WALLET_SIGNATURE_MESSAGE).to_bytes())
+}
+
+/// Split 64 bytes of hash output into (secret, chain code)
+fn split_output(hasher: &blake3::Hasher) -> ([u8; 32], [u8; 32]) {
+    let mut output = Zeroizing::new([0u8; 64]);
+    hasher.finalize_xof().fill(&mut *output);
+    let mut secret = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    secret.copy_from_slice(&output[..32]);
+    chain_code.copy_from_slice(&output[32..]);
+    (secret, chain_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u8; 32] = [7u8; 32];
+
+    #[test]
+    fn test_derivation_deterministic() {
+        let a = ExtendedSpendingKey::account(&SEED, 0).unwrap();
+        assert_eq!(a, ExtendedSpendingKey::account(&SEED, 0).unwrap());
+        assert_eq!(a.depth, 3);
+        assert_eq!(a.child_index, HARDENED);
+
+        // Same path step by step
+        let master = ExtendedSpendingKey::master(&SEED).unwrap();
+        let stepwise = master
+            .derive_child(PURPOSE)
+            .and_then(|k| k.derive_child(COIN_TYPE))
+            .and_then(|k| k.derive_child(0))
+            .unwrap();
+        assert_eq!(stepwise, a);
+
+        // Hardened and unhardened indices name the same child
+        assert_eq!(master.derive_child(5).unwrap(), master.derive_child(5 | HARDENED).unwrap());
+    }
+
+    #[test]
+    fn test_accounts_independent() {
+        let a = ExtendedSpendingKey::account(&SEED, 0).unwrap();
+        let b = ExtendedSpendingKey::account(&SEED, 1).unwrap();
+        let other_seed = ExtendedSpendingKey::account(&[8u8; 32], 0).unwrap();
+
+        assert_ne!(a.secret(), b.secret());
+        assert_ne!(a.secret(), other_seed.secret());
+        assert_ne!(a.viewing_key(), b.viewing_key());
+        assert_ne!(
+            a.encryption_keypair().public_key_bytes(),
+            b.encryption_keypair().public_key_bytes()
+        );
+        assert_eq!(a.parent_fingerprint, b.parent_fingerprint);
+        assert_ne!(a.fingerprint(), b.fingerprint());
+
+        // The account's keys follow from its secret
+        assert_eq!(a.spending_key().to_bytes(), SpendingKey::from_secret(a.secret()).to_bytes());
+        assert_eq!(a.address(0), DiversifiedAddress::derive(&a.viewing_key(), 0));
+        assert_ne!(a.outgoing_viewing_key().to_bytes(), a.viewing_key().to_bytes());
+    }
+
+    #[test]
+    fn test_extended_key_serialization() {
+        let key = ExtendedSpendingKey::account(&SEED, 3).unwrap();
+        let bytes = key.to_bytes();
+        assert_eq!(ExtendedSpendingKey::from_bytes(&bytes).unwrap(), key);
+
+        // A restored key keeps deriving the same children
+        let restored = ExtendedSpendingKey::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.derive_child(0).unwrap(), key.derive_child(0).unwrap());
+
+        assert!(matches!(
+            ExtendedSpendingKey::from_bytes(&bytes[1..]),
+            Err(KeyError::InvalidLength(72))
+        ));
+
+        let mut key = key;
+        key.zeroize();
+        assert_eq!(key.to_bytes()[9..], [0u8; 64]);
+    }
+
+    #[test]
+    fn test_wallet_signature_vector() {
+        let key = from_wallet_signature(&[1u8; 64]).unwrap();
+        assert_eq!(key.depth, 0);
+        assert_eq!(
+            hex::encode(key.secret()),
+            "e0ac0b845c864e421d47283eeb973326717c05fee94ebd76a6e46d9931d95cb4"
+        );
+        assert_ne!(from_wallet_signature(&[2u8; 64]).unwrap(), key);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_wallet_keypair_vector() {
+        use ed25519_dalek::{PublicKey, SecretKey, Signer};
+
+        let secret = SecretKey::from_bytes(&[9u8; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let keypair = ed25519_dalek::Keypair { secret, public };
+
+        // What a browser wallet holding this keypair would return
+        let signature = keypair.sign(WALLET_SIGNATURE_MESSAGE).to_bytes();
+        assert_eq!(hex::encode(&signature[..8]), "0b7824f57cef5f59");
+
+        let key = from_wallet_keypair(&keypair).unwrap();
+        assert_eq!(key, from_wallet_signature(&signature).unwrap());
+        assert_eq!(
+            hex::encode(key.secret()),
+            "df012b70c798d77ffa83825c6eb47313a4060d832116fcf34ac45f9fcaca0453"
+        );
+    }
+
+    /// The 24-word "abandon ... art" vector from the BIP39 reference tests
+    const BIP39_PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon abandon abandon abandon art";
+
+    #[test]
+    fn test_mnemonic_vector() {
+        let seed = mnemonic_seed(BIP39_PHRASE, "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(seed),
+            "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd30971\
+             70af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8"
+        );
+        let key = from_mnemonic(BIP39_PHRASE, "TREZOR").unwrap();
+        assert_eq!(key, ExtendedSpendingKey::master(&seed).unwrap());
+
+        // The passphrase selects a different wallet
+        assert_ne!(from_mnemonic(BIP39_PHRASE, "").unwrap(), key);
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip() {
+        let phrase = generate_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), MNEMONIC_WORDS);
+        validate_mnemonic(&phrase).unwrap();
+        assert_eq!(from_mnemonic(&phrase, "").unwrap(), from_mnemonic(&phrase, "").unwrap());
+        assert_ne!(generate_mnemonic(), phrase);
+    }
+
+    #[test]
+    fn test_invalid_mnemonic() {
+        // Bad checksum: the last word of the vector swapped
+        let bad_checksum = BIP39_PHRASE.replace(" art", " abandon");
+        assert!(matches!(
+            validate_mnemonic(&bad_checksum),
+            Err(KeyError::InvalidMnemonic(_))
+        ));
+        assert!(validate_mnemonic(&BIP39_PHRASE.replace(" art", " notaword")).is_err());
+
+        // Valid 12-word phrases are not accepted as backups
+        let twelve = "abandon abandon abandon abandon abandon abandon abandon abandon \
+            abandon abandon abandon about";
+        assert!(mnemonic_seed(twelve, "").is_err());
+    }
+
+    #[test]
+    fn test_seed_length() {
+        assert!(matches!(
+            ExtendedSpendingKey::master(&[0u8; 16]),
+            Err(KeyError::InvalidSeedLength(16))
+        ));
+        assert!(ExtendedSpendingKey::master(&[0u8; MAX_SEED_SIZE + 1]).is_err());
+        assert!(ExtendedSpendingKey::master(&[0u8; 64]).is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+//! Proving backends
+//!
+//! `ProofBackend` is what circuit code needs from a zkSNARK: keys for a
+//! circuit, proofs, verification and a byte encoding of proofs.
+//! `Groth16Backend` is the system the program verifies and needs a trusted
+//! setup per circuit; `PlonkBackend` (feature `plonk`) only needs a
+//! universal SRS.
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_relations::r1cs::ConstraintSynthesizer;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use rand::rngs::OsRng;
+
+use super::ProofError;
+
+/// A zkSNARK over BN254 for arkworks circuits
+pub trait ProofBackend {
+    type ProvingKey;
+    type VerifyingKey;
+    type Proof;
+
+    /// Keys for `circuit`'s constraint system; witness values are ignored
+    fn setup<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        circuit: C,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), ProofError>;
+
+    /// Prove `circuit`, which must be satisfied
+    fn prove<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        pk: &Self::ProvingKey,
+        circuit: C,
+    ) -> Result<Self::Proof, ProofError>;
+
+    /// Check `proof` against the circuit's public inputs, without the
+    /// leading constant
+    fn verify(
+        &self,
+        vk: &Self::VerifyingKey,
+        public_inputs: &[Fr],
+        proof: &Self::Proof,
+    ) -> Result<bool, ProofError>;
+
+    fn proof_to_bytes(&self, proof: &Self::Proof) -> Result<Vec<u8>, ProofError>;
+
+    fn proof_from_bytes(&self, bytes: &[u8]) -> Result<Self::Proof, ProofError>;
+}
+
+/// Groth16 with a circuit-specific setup
+///
+/// WARNING: `setup` samples the toxic waste locally, which is suitable only
+/// for testing; production keys come from a ceremony (see `zkey`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Groth16Backend;
+
+impl ProofBackend for Groth16Backend {
+    type ProvingKey = ProvingKey<Bn254>;
+    type VerifyingKey = VerifyingKey<Bn254>;
+    type Proof = Proof<Bn254>;
+
+    fn setup<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        circuit: C,
+    ) -> Result<(ProvingKey<Bn254>, VerifyingKey<Bn254>), ProofError> {
+        Groth16::<Bn254>::circuit_specific_setup(circuit, &mut OsRng)
+            .map_err(|e| ProofError::SetupError(e.to_string()))
+    }
+
+    fn prove<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        pk: &ProvingKey<Bn254>,
+        circuit: C,
+    ) -> Result<Proof<Bn254>, ProofError> {
+        Groth16::<Bn254>::prove(pk, circuit, &mut OsRng)
+            .map_err(|e| ProofError::GenerationFailed(e.to_string()))
+    }
+
+    fn verify(
+        &self,
+        vk: &VerifyingKey<Bn254>,
+        public_inputs: &[Fr],
+        proof: &Proof<Bn254>,
+    ) -> Result<bool, ProofError> {
+        Groth16::<Bn254>::verify(vk, public_inputs, proof)
+            .map_err(|e| ProofError::VerificationFailed(e.to_string()))
+    }
+
+    /// Compressed arkworks encoding (128 bytes)
+    fn proof_to_bytes(&self, proof: &Proof<Bn254>) -> Result<Vec<u8>, ProofError> {
+        let mut bytes = Vec::new();
+        proof
+            .serialize_compressed(&mut bytes)
+            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    fn proof_from_bytes(&self, bytes: &[u8]) -> Result<Proof<Bn254>, ProofError> {
+        Proof::deserialize_compressed(bytes)
+            .map_err(|e| ProofError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+    use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+    /// x * y = z with z public
+    struct MulCircuit {
+        x: Fr,
+        y: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let x = FpVar::new_witness(cs.clone(), || Ok(self.x))?;
+            let y = FpVar::new_witness(cs.clone(), || Ok(self.y))?;
+            let z = FpVar::new_input(cs, || Ok(self.x * self.y))?;
+            (x * y).enforce_equal(&z)
+        }
+    }
+
+    /// Prove and verify through the trait alone
+    fn check_backend<B: ProofBackend>(backend: &B) {
+        let circuit = || MulCircuit {
+            x: Fr::from(3u64),
+            y: Fr::from(5u64),
+        };
+        let (pk, vk) = backend.setup(circuit()).unwrap();
+        let proof = backend.prove(&pk, circuit()).unwrap();
+        let proof = backend
+            .proof_from_bytes(&backend.proof_to_bytes(&proof).unwrap())
+            .unwrap();
+
+        assert!(backend.verify(&vk, &[Fr::from(15u64)], &proof).unwrap());
+        assert!(!backend.verify(&vk, &[Fr::from(16u64)], &proof).unwrap());
+    }
+
+    #[test]
+    fn test_groth16_backend() {
+        check_backend(&Groth16Backend);
+    }
+
+    #[cfg(feature = "plonk")]
+    #[test]
+    fn test_plonk_backend() {
+        use crate::proof::plonk::{PlonkBackend, Srs};
+
+        let srs = Srs::insecure_setup(3 * 8 + 5, &mut OsRng);
+        check_backend(&PlonkBackend::new(std::sync::Arc::new(srs)));
+    }
+}
@@ -0,0 +1,393 @@
+//! Groth16 phase-2 trusted setup ceremony
+//!
+//! `TransferProofSystem::setup` samples every secret locally. A production
+//! key instead comes from a multi-party ceremony in which only δ is
+//! circuit-specific (Bowe, Gabizon and Miers, <https://eprint.iacr.org/2017/1050>):
+//! starting from initial parameters with δ = 1, each contributor multiplies
+//! δ by a secret and divides the H and L queries by it, then discards the
+//! secret. The key is sound as long as one contributor did so honestly.
+//!
+//! The initial parameters are the circuit's Groth16 key derived from a
+//! phase-1 powers of tau, with δ = 1; producing them is outside this module.
+//! Each contribution is published with a proof of knowledge of its secret,
+//! and `Phase2Params::verify` checks the whole transcript against the
+//! initial parameters.
+//!
+//! ```ignore
+//! let mut params = Phase2Params::new(initial.clone())?;
+//! let hash = params.contribute(&mut OsRng)?; // Each contributor, in turn
+//! let hashes = params.verify(&initial)?;     // Anyone, at the end
+//! let (pk_bytes, vk_bytes) = params.key_bytes()?;
+//! let system = TransferProofSystem::from_keys(&pk_bytes, &vk_bytes)?;
+//! ```
+
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
+use ark_groth16::ProvingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use super::ProofError;
+
+/// Domain separator for the proof-of-knowledge challenge
+const CHALLENGE_DOMAIN: &[u8] = b"veil-phase2-challenge-v1";
+
+/// A contributor's public record
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Contribution {
+    /// `[δ]₁` after this contribution
+    pub delta_after: G1Affine,
+    /// A random point `s`
+    pub s: G1Affine,
+    /// `s` times the contributor's secret
+    pub s_delta: G1Affine,
+    /// The challenge `r`, hashed from the transcript, times the secret
+    pub r_delta: G2Affine,
+}
+
+/// Parameters part-way through a ceremony, with its transcript
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Phase2Params {
+    proving_key: ProvingKey<Bn254>,
+    /// Hash of the initial parameters, which starts the transcript
+    initial_hash: [u8; 32],
+    contributions: Vec<Contribution>,
+}
+
+impl Phase2Params {
+    /// Start a ceremony from `initial`, which must have δ = 1
+    pub fn new(initial: ProvingKey<Bn254>) -> Result<Self, ProofError> {
+        if initial.delta_g1 != G1Affine::generator() || initial.vk.delta_g2 != G2Affine::generator()
+        {
+            return Err(ProofError::SetupError(
+                "initial parameters must have delta = 1".to_string(),
+            ));
+        }
+        Ok(Self {
+            initial_hash: key_hash(&initial)?,
+            proving_key: initial,
+            contributions: Vec::new(),
+        })
+    }
+
+    /// Decode parameters written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofError> {
+        Self::deserialize_compressed(bytes)
+            .map_err(|e| ProofError::SerializationError(e.to_string()))
+    }
+
+    /// Encode the parameters and transcript, to hand to the next contributor
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ProofError> {
+        let mut bytes = Vec::new();
+        self.serialize_compressed(&mut bytes)
+            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Contributions so far, in order
+    pub fn contributions(&self) -> &[Contribution] {
+        &self.contributions
+    }
+
+    /// The current proving key
+    pub fn proving_key(&self) -> &ProvingKey<Bn254> {
+        &self.proving_key
+    }
+
+    /// Compressed proving and verifying keys, as `TransferProofSystem::from_keys`
+    /// and `KeyStore` load them
+    pub fn key_bytes(&self) -> Result<(Vec<u8>, Vec<u8>), ProofError> {
+        let serialization_error =
+            |e: ark_serialize::SerializationError| ProofError::SerializationError(e.to_string());
+        let mut pk_bytes = Vec::new();
+        self.proving_key
+            .serialize_compressed(&mut pk_bytes)
+            .map_err(serialization_error)?;
+        let mut vk_bytes = Vec::new();
+        self.proving_key
+            .vk
+            .serialize_compressed(&mut vk_bytes)
+            .map_err(serialization_error)?;
+        Ok((pk_bytes, vk_bytes))
+    }
+
+    /// Apply a fresh secret from `rng`, returning the contribution's hash
+    ///
+    /// The contributor publishes the hash so that anyone can find their
+    /// contribution in the verified transcript. The secret is dropped on
+    /// return.
+    pub fn contribute<R: RngCore>(&mut self, rng: &mut R) -> Result<[u8; 32], ProofError> {
+        let transcript = self.transcript_hashes()?;
+        let previous = transcript[transcript.len() - 1];
+
+        let delta = loop {
+            let delta = Fr::rand(rng);
+            if !delta.is_zero() {
+                break delta;
+            }
+        };
+        let delta_inv = delta.inverse().ok_or(ProofError::InvalidWitness)?;
+
+        let s = G1Projective::rand(rng).into_affine();
+        let s_delta = (s * delta).into_affine();
+        let r = challenge(&previous, &s, &s_delta);
+
+        let pk = &mut self.proving_key;
+        pk.delta_g1 = (pk.delta_g1 * delta).into_affine();
+        pk.vk.delta_g2 = (pk.vk.delta_g2 * delta).into_affine();
+        pk.h_query = scale(&pk.h_query, delta_inv);
+        pk.l_query = scale(&pk.l_query, delta_inv);
+
+        let contribution = Contribution {
+            delta_after: pk.delta_g1,
+            s,
+            s_delta,
+            r_delta: (r * delta).into_affine(),
+        };
+        let hash = chain_hash(&previous, &contribution)?;
+        self.contributions.push(contribution);
+        Ok(hash)
+    }
+
+    /// Check the transcript from `initial` to these parameters, returning
+    /// every contribution's hash
+    ///
+    /// Fails with `SetupError` unless the parameters started from `initial`,
+    /// only δ and the H and L queries changed, each contribution proves
+    /// knowledge of its secret, and the final δ is the product of them all.
+    pub fn verify(&self, initial: &ProvingKey<Bn254>) -> Result<Vec<[u8; 32]>, ProofError> {
+        let fail = |reason: &str| Err(ProofError::SetupError(reason.to_string()));
+        let pk = &self.proving_key;
+        if Self::new(initial.clone())?.initial_hash != self.initial_hash {
+            return fail("parameters did not start from the initial parameters");
+        }
+        if pk.vk.alpha_g1 != initial.vk.alpha_g1
+            || pk.vk.beta_g2 != initial.vk.beta_g2
+            || pk.vk.gamma_g2 != initial.vk.gamma_g2
+            || pk.vk.gamma_abc_g1 != initial.vk.gamma_abc_g1
+            || pk.beta_g1 != initial.beta_g1
+            || pk.a_query != initial.a_query
+            || pk.b_g1_query != initial.b_g1_query
+            || pk.b_g2_query != initial.b_g2_query
+            || pk.h_query.len() != initial.h_query.len()
+            || pk.l_query.len() != initial.l_query.len()
+        {
+            return fail("parameters other than delta were modified");
+        }
+
+        let transcript = self.transcript_hashes()?;
+        let mut delta = initial.delta_g1;
+        for (contribution, previous) in self.contributions.iter().zip(&transcript) {
+            if contribution.s.is_zero() || contribution.delta_after.is_zero() {
+                return fail("contribution has a zero point");
+            }
+            let r = challenge(previous, &contribution.s, &contribution.s_delta);
+            let r_ratio = (r, contribution.r_delta);
+            if !same_ratio((contribution.s, contribution.s_delta), r_ratio) {
+                return fail("contribution does not prove knowledge of its secret");
+            }
+            if !same_ratio((delta, contribution.delta_after), r_ratio) {
+                return fail("contribution did not apply its secret to delta");
+            }
+            delta = contribution.delta_after;
+        }
+        if pk.delta_g1 != delta
+            || !same_ratio(
+                (G1Affine::generator(), delta),
+                (G2Affine::generator(), pk.vk.delta_g2),
+            )
+        {
+            return fail("delta does not match the transcript");
+        }
+
+        // Each H and L entry must be its initial value divided by δ. Checked
+        // for a random combination: e(Σ ρᵢ·initialᵢ, [1]₂) = e(Σ ρᵢ·afterᵢ, [δ]₂)
+        let initial_queries = [&initial.h_query[..], &initial.l_query[..]].concat();
+        let queries = [&pk.h_query[..], &pk.l_query[..]].concat();
+        let rho: Vec<Fr> = (0..queries.len()).map(|_| Fr::rand(&mut OsRng)).collect();
+        let initial_sum = G1Projective::msm_unchecked(&initial_queries, &rho).into_affine();
+        let sum = G1Projective::msm_unchecked(&queries, &rho).into_affine();
+        if !same_ratio((sum, initial_sum), (G2Affine::generator(), pk.vk.delta_g2)) {
+            return fail("H or L query is not divided by delta");
+        }
+
+        Ok(transcript[1..].to_vec())
+    }
+
+    /// `initial_hash`, then the hash after each contribution
+    fn transcript_hashes(&self) -> Result<Vec<[u8; 32]>, ProofError> {
+        let mut hashes = vec![self.initial_hash];
+        for contribution in &self.contributions {
+            hashes.push(chain_hash(&hashes[hashes.len() - 1], contribution)?);
+        }
+        Ok(hashes)
+    }
+}
+
+/// SHA-256 of a proving key's compressed encoding
+fn key_hash(pk: &ProvingKey<Bn254>) -> Result<[u8; 32], ProofError> {
+    let mut bytes = Vec::new();
+    pk.serialize_compressed(&mut bytes)
+        .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+    Ok(Sha256::digest(bytes).into())
+}
+
+/// The transcript hash after `contribution`
+fn chain_hash(previous: &[u8; 32], contribution: &Contribution) -> Result<[u8; 32], ProofError> {
+    let mut bytes = previous.to_vec();
+    contribution
+        .serialize_compressed(&mut bytes)
+        .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+    Ok(Sha256::digest(bytes).into())
+}
+
+/// The G2 challenge `r` for a contribution, hashed onto the curve by
+/// try-and-increment so that nobody knows its discrete log
+fn challenge(previous: &[u8; 32], s: &G1Affine, s_delta: &G1Affine) -> G2Affine {
+    let mut points = Vec::new();
+    // Serializing to a Vec cannot fail
+    s.serialize_compressed(&mut points).expect("serialize s");
+    s_delta
+        .serialize_compressed(&mut points)
+        .expect("serialize s_delta");
+
+    let coordinate = |counter: u32, half: u8| {
+        let digest = Sha256::new()
+            .chain_update(CHALLENGE_DOMAIN)
+            .chain_update(previous)
+            .chain_update(&points)
+            .chain_update(counter.to_be_bytes())
+            .chain_update([half])
+            .finalize();
+        Fq::from_be_bytes_mod_order(&digest)
+    };
+    (0u32..)
+        .find_map(|counter| {
+            let x = Fq2::new(coordinate(counter, 0), coordinate(counter, 1));
+            G2Affine::get_point_from_x_unchecked(x, false)
+                .map(|point| point.clear_cofactor())
+                .filter(|point| !point.is_zero())
+        })
+        .expect("a hash lands on the curve within a few tries")
+}
+
+/// Whether `g1.1 / g1.0 = g2.1 / g2.0` as discrete logs
+fn same_ratio(g1: (G1Affine, G1Affine), g2: (G2Affine, G2Affine)) -> bool {
+    Bn254::pairing(g1.0, g2.1) == Bn254::pairing(g1.1, g2.0)
+}
+
+/// Every point times `scalar`
+fn scale(points: &[G1Affine], scalar: Fr) -> Vec<G1Affine> {
+    let scaled: Vec<G1Projective> = points.iter().map(|point| *point * scalar).collect();
+    G1Projective::normalize_batch(&scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::Group;
+    use ark_ff::One;
+    use ark_groth16::Groth16;
+    use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::SNARK;
+
+    /// x * y = z with z public
+    struct MulCircuit {
+        x: Fr,
+        y: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let x = FpVar::new_witness(cs.clone(), || Ok(self.x))?;
+            let y = FpVar::new_witness(cs.clone(), || Ok(self.y))?;
+            let z = FpVar::new_input(cs, || Ok(self.x * self.y))?;
+            (x * y).enforce_equal(&z)
+        }
+    }
+
+    fn circuit() -> MulCircuit {
+        MulCircuit {
+            x: Fr::from(3u64),
+            y: Fr::from(5u64),
+        }
+    }
+
+    /// Stand-in for phase-1 output: random α, β, γ, τ with δ = 1
+    fn initial() -> ProvingKey<Bn254> {
+        let rng = &mut OsRng;
+        Groth16::<Bn254>::generate_parameters_with_qap(
+            circuit(),
+            Fr::rand(rng),
+            Fr::rand(rng),
+            Fr::rand(rng),
+            Fr::one(),
+            G1Projective::generator(),
+            ark_bn254::G2Projective::generator(),
+            rng,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_contributions_verify_and_prove() {
+        let initial = initial();
+        let mut params = Phase2Params::new(initial.clone()).unwrap();
+        assert!(params.verify(&initial).unwrap().is_empty());
+
+        let first = params.contribute(&mut OsRng).unwrap();
+        // Each contributor receives the previous parameters as bytes
+        let mut params = Phase2Params::from_bytes(&params.to_bytes().unwrap()).unwrap();
+        let second = params.contribute(&mut OsRng).unwrap();
+        assert_eq!(params.verify(&initial).unwrap(), vec![first, second]);
+        assert_ne!(params.proving_key().delta_g1, initial.delta_g1);
+
+        let (pk_bytes, vk_bytes) = params.key_bytes().unwrap();
+        let pk = ProvingKey::<Bn254>::deserialize_compressed(&pk_bytes[..]).unwrap();
+        let vk = ark_groth16::VerifyingKey::deserialize_compressed(&vk_bytes[..]).unwrap();
+        let proof = Groth16::<Bn254>::prove(&pk, circuit(), &mut OsRng).unwrap();
+        assert!(Groth16::<Bn254>::verify(&vk, &[Fr::from(15u64)], &proof).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_tampered_transcripts() {
+        let initial = initial();
+        let mut params = Phase2Params::new(initial.clone()).unwrap();
+        params.contribute(&mut OsRng).unwrap();
+        params.contribute(&mut OsRng).unwrap();
+        let rejects = |params: &Phase2Params| {
+            matches!(params.verify(&initial), Err(ProofError::SetupError(_)))
+        };
+
+        // A proof of knowledge for a different secret
+        let mut forged = params.clone();
+        forged.contributions[0].r_delta = (forged.contributions[0].r_delta * Fr::from(2u64)).into();
+        assert!(rejects(&forged));
+
+        // A contribution dropped from the transcript
+        let mut dropped = params.clone();
+        dropped.contributions.remove(0);
+        assert!(rejects(&dropped));
+
+        // Queries not divided by delta
+        let mut query = params.clone();
+        query.proving_key.l_query[0] = initial.l_query[0];
+        assert!(rejects(&query));
+
+        // Parameters other than delta changed
+        let mut alpha = params.clone();
+        alpha.proving_key.vk.alpha_g1 = (alpha.proving_key.vk.alpha_g1 * Fr::from(2u64)).into();
+        assert!(rejects(&alpha));
+
+        // Against a different starting point
+        assert!(params.verify(&self::initial()).is_err());
+
+        // δ must start at 1
+        assert!(Phase2Params::new(params.proving_key().clone()).is_err());
+    }
+}
@@ -5,6 +5,7 @@
 
 use ark_bn254::Fr;
 use ark_ff::PrimeField;
+use zeroize::Zeroize;
 
 /// Transfer circuit witness
 ///
@@ -55,6 +56,23 @@ impl TransferCircuitWitness {
     }
 }
 
+impl Zeroize for TransferCircuitWitness {
+    fn zeroize(&mut self) {
+        self.sender_secret.zeroize();
+        self.amount.zeroize();
+        self.sender_blinding.zeroize();
+        self.recipient_blinding.zeroize();
+        self.merkle_path.zeroize();
+        self.merkle_indices.zeroize();
+    }
+}
+
+impl Drop for TransferCircuitWitness {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Verify circuit constraints (placeholder)
 ///
 /// In production, this would be implemented using arkworks constraint system:
@@ -0,0 +1,129 @@
+//! Verifying keys as Rust source for the program
+//!
+//! `program_vk_module` renders the transfer and unshield keys as the
+//! program's `vk` module (`crates/program/src/vk.rs`, feature
+//! `embedded-vk`), so setup output reaches the program without hand-editing.
+//! The `veil-keygen` binary wraps it.
+//!
+//! Both sides check the public input counts: generation fails if a key's IC
+//! does not match the circuit, and the module fails to compile if the
+//! program's `NUM_PUBLIC_INPUTS` or `UNSHIELD_NUM_PUBLIC_INPUTS` has since
+//! changed.
+
+use std::fmt::Write;
+
+use super::{ProofError, SolanaVerifyingKey, TransferCircuit, UnshieldCircuit};
+
+/// Render the program's `vk` module
+pub fn program_vk_module(
+    transfer: &SolanaVerifyingKey,
+    unshield: &SolanaVerifyingKey,
+) -> Result<String, ProofError> {
+    check_ic("transfer", transfer, TransferCircuit::NUM_PUBLIC_INPUTS)?;
+    check_ic("unshield", unshield, UnshieldCircuit::NUM_PUBLIC_INPUTS)?;
+
+    let mut code = String::new();
+    code.push_str("//! Verifying keys from the trusted setup\n");
+    code.push_str("//!\n");
+    code.push_str("//! Generated by `veil-keygen` - DO NOT EDIT\n\n");
+    code.push_str("use crate::groth16::{\n");
+    code.push_str("    UnshieldVerifyingKeyData, VerifyingKeyData, NUM_PUBLIC_INPUTS,\n");
+    code.push_str("    UNSHIELD_NUM_PUBLIC_INPUTS,\n");
+    code.push_str("};\n\n");
+    code.push_str("// The circuits these keys were made for\n");
+    writeln!(
+        code,
+        "const _: () = assert!(NUM_PUBLIC_INPUTS == {});",
+        TransferCircuit::NUM_PUBLIC_INPUTS
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "const _: () = assert!(UNSHIELD_NUM_PUBLIC_INPUTS == {});\n",
+        UnshieldCircuit::NUM_PUBLIC_INPUTS
+    )
+    .unwrap();
+    code.push_str("/// The transfer circuit's key, for `set_verifying_key`\n");
+    push_key(&mut code, "TRANSFER", "VerifyingKeyData", transfer);
+    code.push_str("\n/// The unshield circuit's key, for `set_unshield_verifying_key`\n");
+    push_key(&mut code, "UNSHIELD", "UnshieldVerifyingKeyData", unshield);
+    Ok(code)
+}
+
+fn check_ic(
+    name: &str,
+    key: &SolanaVerifyingKey,
+    num_public_inputs: usize,
+) -> Result<(), ProofError> {
+    if key.ic.len() == num_public_inputs + 1 {
+        return Ok(());
+    }
+    Err(ProofError::SetupError(format!(
+        "{} key has {} IC points, the circuit's {} public inputs need {}",
+        name,
+        key.ic.len(),
+        num_public_inputs,
+        num_public_inputs + 1
+    )))
+}
+
+fn push_key(code: &mut String, name: &str, ty: &str, key: &SolanaVerifyingKey) {
+    writeln!(code, "pub const {}: {} = {} {{", name, ty, ty).unwrap();
+    writeln!(code, "    alpha_g1: {},", bytes(&key.alpha_g1)).unwrap();
+    writeln!(code, "    beta_g2: {},", bytes(&key.beta_g2)).unwrap();
+    writeln!(code, "    gamma_g2: {},", bytes(&key.gamma_g2)).unwrap();
+    writeln!(code, "    delta_g2: {},", bytes(&key.delta_g2)).unwrap();
+    code.push_str("    ic: [\n");
+    for point in &key.ic {
+        writeln!(code, "        {},", bytes(point)).unwrap();
+    }
+    code.push_str("    ],\n};\n");
+}
+
+/// A byte array literal, 12 bytes per line
+fn bytes(bytes: &[u8]) -> String {
+    let lines: Vec<String> = bytes
+        .chunks(12)
+        .map(|chunk| {
+            let chunk: Vec<String> = chunk.iter().map(|b| format!("0x{:02x}", b)).collect();
+            chunk.join(", ")
+        })
+        .collect();
+    format!("[\n        {},\n    ]", lines.join(",\n        "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(num_ic: usize) -> SolanaVerifyingKey {
+        SolanaVerifyingKey {
+            alpha_g1: [1u8; 64],
+            beta_g2: [2u8; 128],
+            gamma_g2: [3u8; 128],
+            delta_g2: [4u8; 128],
+            ic: vec![[5u8; 64]; num_ic],
+        }
+    }
+
+    #[test]
+    fn test_program_vk_module() {
+        let transfer = key(TransferCircuit::NUM_PUBLIC_INPUTS + 1);
+        let unshield = key(UnshieldCircuit::NUM_PUBLIC_INPUTS + 1);
+        let code = program_vk_module(&transfer, &unshield).unwrap();
+        assert!(code.contains("pub const TRANSFER: VerifyingKeyData = VerifyingKeyData {"));
+        assert!(code.contains("pub const UNSHIELD: UnshieldVerifyingKeyData ="));
+        assert!(code.contains("const _: () = assert!(NUM_PUBLIC_INPUTS == 5);"));
+        assert!(code.contains("const _: () = assert!(UNSHIELD_NUM_PUBLIC_INPUTS == 6);"));
+        // 64 + 3 * 128 bytes per key, then 6 and 7 IC points
+        assert_eq!(code.matches("0x01").count(), 2 * 64);
+        assert_eq!(code.matches("0x05").count(), (6 + 7) * 64);
+
+        let short = key(TransferCircuit::NUM_PUBLIC_INPUTS);
+        assert!(matches!(
+            program_vk_module(&short, &unshield),
+            Err(ProofError::SetupError(_))
+        ));
+        assert!(program_vk_module(&transfer, &short).is_err());
+    }
+}
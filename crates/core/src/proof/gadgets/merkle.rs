@@ -11,8 +11,7 @@ use ark_r1cs_std::{
 };
 use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
 
-use super::poseidon::poseidon_hash2_gadget;
-use crate::crypto::merkle::TREE_DEPTH;
+use super::poseidon::PoseidonGadget;
 
 /// Merkle path gadget for circuit-based verification
 pub struct MerklePathGadget {
@@ -24,12 +23,16 @@ pub struct MerklePathGadget {
 
 impl MerklePathGadget {
     /// Create a new Merkle path gadget from witness values
+    ///
+    /// `depth` is that of the pool the circuit is built for; a path of any
+    /// other length is rejected.
     pub fn new_witness(
         cs: ConstraintSystemRef<Fr>,
+        depth: usize,
         siblings: &[Fr],
         indices: &[bool],
     ) -> Result<Self, SynthesisError> {
-        if siblings.len() != TREE_DEPTH || indices.len() != TREE_DEPTH {
+        if siblings.len() != depth || indices.len() != depth {
             return Err(SynthesisError::AssignmentMissing);
         }
 
@@ -58,16 +61,52 @@ impl MerklePathGadget {
         leaf: &FpVar<Fr>,
         expected_root: &FpVar<Fr>,
     ) -> Result<(), SynthesisError> {
-        let computed_root = self.compute_root(cs.clone(), leaf)?;
+        let poseidon = PoseidonGadget::new(cs.clone())?;
+        self.verify_with(&poseidon, cs, leaf, expected_root)
+    }
+
+    /// Verify the Merkle path with the circuit's shared Poseidon gadget
+    pub fn verify_with(
+        &self,
+        poseidon: &PoseidonGadget,
+        cs: ConstraintSystemRef<Fr>,
+        leaf: &FpVar<Fr>,
+        expected_root: &FpVar<Fr>,
+    ) -> Result<(), SynthesisError> {
+        let computed_root = self.compute_root_with(poseidon, cs, leaf)?;
         computed_root.enforce_equal(expected_root)?;
         Ok(())
     }
 
+    /// The leaf index the path bits encode, `Σ index_bit_i · 2^i`
+    pub fn leaf_index(&self) -> Result<FpVar<Fr>, SynthesisError> {
+        Boolean::le_bits_to_fp_var(&self.indices)
+    }
+
+    /// Enforce that `leaf_index` is the position this path opens
+    ///
+    /// Nullifiers are derived from the leaf index; left free, one note
+    /// could be spent under as many nullifiers as there are indices.
+    pub fn enforce_leaf_index(&self, leaf_index: &FpVar<Fr>) -> Result<(), SynthesisError> {
+        self.leaf_index()?.enforce_equal(leaf_index)
+    }
+
     /// Compute the Merkle root from the leaf and path
     pub fn compute_root(
         &self,
         cs: ConstraintSystemRef<Fr>,
         leaf: &FpVar<Fr>,
+    ) -> Result<FpVar<Fr>, SynthesisError> {
+        let poseidon = PoseidonGadget::new(cs.clone())?;
+        self.compute_root_with(&poseidon, cs, leaf)
+    }
+
+    /// Compute the Merkle root with the circuit's shared Poseidon gadget
+    pub fn compute_root_with(
+        &self,
+        poseidon: &PoseidonGadget,
+        cs: ConstraintSystemRef<Fr>,
+        leaf: &FpVar<Fr>,
     ) -> Result<FpVar<Fr>, SynthesisError> {
         let mut current = leaf.clone();
 
@@ -77,13 +116,22 @@ impl MerklePathGadget {
             let left = is_right.select(sibling, &current)?;
             let right = is_right.select(&current, sibling)?;
 
-            current = poseidon_hash2_gadget(cs.clone(), &left, &right)?;
+            current = poseidon.hash2(cs.clone(), &left, &right)?;
         }
 
         Ok(current)
     }
 }
 
+/// Enforce that a leaf is not the empty-leaf value
+///
+/// Unused positions hold 0 and their siblings are the public zero hashes, so
+/// 0 opens against the current root at any index >= next_index. Forbidding
+/// it means a membership proof always refers to an inserted commitment.
+pub fn enforce_nonempty_leaf(leaf: &FpVar<Fr>) -> Result<(), SynthesisError> {
+    leaf.enforce_not_equal(&FpVar::Constant(Fr::from(0u64)))
+}
+
 /// Verify a Merkle path in a circuit
 ///
 /// This is a convenience function that:
@@ -92,12 +140,13 @@ impl MerklePathGadget {
 /// 3. Enforces the computed root equals the expected root
 pub fn verify_merkle_path_gadget(
     cs: ConstraintSystemRef<Fr>,
+    depth: usize,
     leaf: &FpVar<Fr>,
     siblings: &[Fr],
     indices: &[bool],
     expected_root: &FpVar<Fr>,
 ) -> Result<(), SynthesisError> {
-    let path = MerklePathGadget::new_witness(cs.clone(), siblings, indices)?;
+    let path = MerklePathGadget::new_witness(cs.clone(), depth, siblings, indices)?;
     path.verify(cs, leaf, expected_root)
 }
 
@@ -108,7 +157,7 @@ mod tests {
     use ark_relations::r1cs::ConstraintSystem;
     use rand::rngs::OsRng;
 
-    use crate::crypto::merkle::PoseidonMerkleTree;
+    use crate::crypto::merkle::{PoseidonMerkleTree, TREE_DEPTH};
 
     #[test]
     fn test_merkle_gadget_valid_proof() {
@@ -132,6 +181,7 @@ mod tests {
 
         let path = MerklePathGadget::new_witness(
             cs.clone(),
+            TREE_DEPTH,
             &proof.siblings,
             &proof.indices,
         ).unwrap();
@@ -160,6 +210,7 @@ mod tests {
 
         let path = MerklePathGadget::new_witness(
             cs.clone(),
+            TREE_DEPTH,
             &proof.siblings,
             &proof.indices,
         ).unwrap();
@@ -189,6 +240,7 @@ mod tests {
 
         let path = MerklePathGadget::new_witness(
             cs.clone(),
+            TREE_DEPTH,
             &proof.siblings,
             &proof.indices,
         ).unwrap();
@@ -199,6 +251,55 @@ mod tests {
         assert!(!cs.is_satisfied().unwrap());
     }
 
+    #[test]
+    fn test_empty_leaf_opening_rejected() {
+        use crate::crypto::merkle::ZERO_HASHES;
+
+        // The empty leaf opens against the empty-tree root with zero-hash siblings
+        let siblings = ZERO_HASHES[..TREE_DEPTH].to_vec();
+        let indices = vec![false; TREE_DEPTH];
+        let empty_root = PoseidonMerkleTree::new().root();
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let leaf = FpVar::new_witness(cs.clone(), || Ok(Fr::from(0u64))).unwrap();
+        let root = FpVar::new_input(cs.clone(), || Ok(empty_root)).unwrap();
+        verify_merkle_path_gadget(cs.clone(), TREE_DEPTH, &leaf, &siblings, &indices, &root).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // ...but not once the leaf must be non-empty: the check constrains
+        // leaf * inv = 1, and 0 has no inverse to assign
+        assert!(matches!(
+            enforce_nonempty_leaf(&leaf),
+            Err(SynthesisError::AssignmentMissing)
+        ));
+
+        // A real leaf passes the check
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let leaf = FpVar::new_witness(cs.clone(), || Ok(Fr::rand(&mut OsRng))).unwrap();
+        enforce_nonempty_leaf(&leaf).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_merkle_gadget_other_depth() {
+        let mut tree = PoseidonMerkleTree::with_depth(24).unwrap();
+        for i in 1..4 {
+            tree.insert(Fr::from(i as u64)).unwrap();
+        }
+        let proof = tree.generate_proof(1).unwrap();
+        let leaf = tree.get_leaf(1).unwrap();
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let leaf_var = FpVar::new_witness(cs.clone(), || Ok(leaf)).unwrap();
+        let root_var = FpVar::new_input(cs.clone(), || Ok(tree.root())).unwrap();
+        verify_merkle_path_gadget(cs.clone(), 24, &leaf_var, &proof.siblings, &proof.indices, &root_var)
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // A depth-24 path does not fit a depth-20 circuit
+        assert!(MerklePathGadget::new_witness(cs, TREE_DEPTH, &proof.siblings, &proof.indices).is_err());
+    }
+
     #[test]
     fn test_merkle_gadget_constraint_count() {
         let mut tree = PoseidonMerkleTree::new();
@@ -215,6 +316,7 @@ mod tests {
 
         let path = MerklePathGadget::new_witness(
             cs.clone(),
+            TREE_DEPTH,
             &proof.siblings,
             &proof.indices,
         ).unwrap();
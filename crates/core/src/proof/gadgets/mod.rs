@@ -1,11 +1,15 @@
 //! Circuit gadgets for zkSNARK proofs
 //!
 //! This module contains constraint system implementations for:
-//! - Poseidon hash function
+//! - Poseidon and Poseidon2 hash functions
 //! - Merkle tree path verification
+//! - Range checks
 
 pub mod merkle;
 pub mod poseidon;
+pub mod poseidon2;
+pub mod range;
 
 pub use merkle::MerklePathGadget;
 pub use poseidon::PoseidonGadget;
+pub use poseidon2::Poseidon2Gadget;
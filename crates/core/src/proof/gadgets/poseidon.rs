@@ -1,7 +1,8 @@
 //! Poseidon Hash Gadget for R1CS circuits
 //!
 //! Implements the Poseidon permutation as constraints for use in zkSNARK circuits.
-//! This gadget is compatible with the native Poseidon implementation in crypto::poseidon.
+//! This gadget is compatible with the native Poseidon implementation in crypto::poseidon,
+//! at widths 3, 4 and 5 (`poseidon_hash2`, `poseidon_hash3`, `poseidon_hash4`).
 
 use ark_bn254::Fr;
 use ark_r1cs_std::{
@@ -11,10 +12,16 @@ use ark_r1cs_std::{
 };
 use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
 
-use crate::crypto::poseidon_constants::{FULL_ROUNDS, PARTIAL_ROUNDS, WIDTH};
+use crate::crypto::poseidon_constants::{self, t4, t5, FULL_ROUNDS, PARTIAL_ROUNDS, WIDTH};
 
 /// Poseidon hash gadget for circuits
 pub struct PoseidonGadget {
+    /// Width of the state (t)
+    width: usize,
+    /// Number of full rounds
+    full_rounds: usize,
+    /// Number of partial rounds
+    partial_rounds: usize,
     /// Round constants as constraint variables
     round_constants: Vec<FpVar<Fr>>,
     /// MDS matrix as constraint variables
@@ -24,9 +31,34 @@ pub struct PoseidonGadget {
 impl PoseidonGadget {
     /// Create a new Poseidon gadget with the standard constants
     pub fn new(cs: ConstraintSystemRef<Fr>) -> Result<Self, SynthesisError> {
+        Self::with_width(cs, WIDTH)
+    }
+
+    /// Create a gadget of width 3, 4 or 5, hashing up to `width - 1`
+    /// elements per permutation
+    pub fn with_width(cs: ConstraintSystemRef<Fr>, width: usize) -> Result<Self, SynthesisError> {
         // Load constants from the standard module
-        let rc = crate::crypto::poseidon_constants::get_round_constants();
-        let mds = crate::crypto::poseidon_constants::get_mds_matrix();
+        let (full_rounds, partial_rounds, rc, mds): (_, _, &[Fr], Vec<&[Fr]>) = match width {
+            WIDTH => (
+                FULL_ROUNDS,
+                PARTIAL_ROUNDS,
+                &poseidon_constants::ROUND_CONSTANTS,
+                poseidon_constants::MDS_MATRIX.iter().map(|row| &row[..]).collect(),
+            ),
+            t4::WIDTH => (
+                t4::FULL_ROUNDS,
+                t4::PARTIAL_ROUNDS,
+                &t4::ROUND_CONSTANTS,
+                t4::MDS_MATRIX.iter().map(|row| &row[..]).collect(),
+            ),
+            t5::WIDTH => (
+                t5::FULL_ROUNDS,
+                t5::PARTIAL_ROUNDS,
+                &t5::ROUND_CONSTANTS,
+                t5::MDS_MATRIX.iter().map(|row| &row[..]).collect(),
+            ),
+            _ => return Err(SynthesisError::Unsatisfiable),
+        };
 
         // Allocate round constants as constants (not witnesses)
         let round_constants: Result<Vec<FpVar<Fr>>, _> = rc
@@ -45,6 +77,9 @@ impl PoseidonGadget {
             .collect();
 
         Ok(Self {
+            width,
+            full_rounds,
+            partial_rounds,
             round_constants: round_constants?,
             mds_matrix: mds_matrix?,
         })
@@ -57,15 +92,7 @@ impl PoseidonGadget {
         a: &FpVar<Fr>,
         b: &FpVar<Fr>,
     ) -> Result<FpVar<Fr>, SynthesisError> {
-        // Initialize state: [0, a, b]
-        let zero = FpVar::new_constant(cs.clone(), Fr::from(0u64))?;
-        let mut state = vec![zero, a.clone(), b.clone()];
-
-        // Apply permutation
-        self.permute(&mut state)?;
-
-        // Return first element
-        Ok(state[0].clone())
+        self.hash(cs, &[a.clone(), b.clone()])
     }
 
     /// Hash multiple field elements (sponge construction)
@@ -78,14 +105,14 @@ impl PoseidonGadget {
             return Err(SynthesisError::AssignmentMissing);
         }
 
-        if inputs.len() > WIDTH - 1 {
+        if inputs.len() > self.width - 1 {
             // For more inputs, use sponge construction
             return self.hash_sponge(cs, inputs);
         }
 
         // Initialize state with capacity element = 0
         let zero = FpVar::new_constant(cs.clone(), Fr::from(0u64))?;
-        let mut state = vec![zero; WIDTH];
+        let mut state = vec![zero; self.width];
 
         // Copy inputs into state (after capacity element)
         for (i, input) in inputs.iter().enumerate() {
@@ -105,11 +132,11 @@ impl PoseidonGadget {
         cs: ConstraintSystemRef<Fr>,
         inputs: &[FpVar<Fr>],
     ) -> Result<FpVar<Fr>, SynthesisError> {
-        let rate = WIDTH - 1; // Rate is t-1 for capacity 1
+        let rate = self.width - 1; // Rate is t-1 for capacity 1
 
         // Initialize state
         let zero = FpVar::new_constant(cs.clone(), Fr::from(0u64))?;
-        let mut state = vec![zero; WIDTH];
+        let mut state = vec![zero; self.width];
 
         // Absorb phase
         for chunk in inputs.chunks(rate) {
@@ -125,9 +152,9 @@ impl PoseidonGadget {
 
     /// Apply the Poseidon permutation to the state
     fn permute(&self, state: &mut [FpVar<Fr>]) -> Result<(), SynthesisError> {
-        let t = WIDTH;
-        let rf = FULL_ROUNDS;
-        let rp = PARTIAL_ROUNDS;
+        let t = self.width;
+        let rf = self.full_rounds;
+        let rp = self.partial_rounds;
 
         let mut round_ctr = 0;
 
@@ -159,7 +186,7 @@ impl PoseidonGadget {
         round_ctr: usize,
     ) -> Result<(), SynthesisError> {
         // Add round constants
-        for i in 0..WIDTH {
+        for i in 0..self.width {
             state[i] = &state[i] + &self.round_constants[round_ctr + i];
         }
 
@@ -181,7 +208,7 @@ impl PoseidonGadget {
         round_ctr: usize,
     ) -> Result<(), SynthesisError> {
         // Add round constants
-        for i in 0..WIDTH {
+        for i in 0..self.width {
             state[i] = &state[i] + &self.round_constants[round_ctr + i];
         }
 
@@ -206,11 +233,11 @@ impl PoseidonGadget {
 
     /// Multiply state by MDS matrix
     fn mds_multiply(&self, state: &mut [FpVar<Fr>]) -> Result<(), SynthesisError> {
-        let mut new_state = Vec::with_capacity(WIDTH);
+        let mut new_state = Vec::with_capacity(self.width);
 
-        for i in 0..WIDTH {
+        for i in 0..self.width {
             let mut sum = FpVar::zero();
-            for j in 0..WIDTH {
+            for j in 0..self.width {
                 sum = sum + (&self.mds_matrix[i][j] * &state[j]);
             }
             new_state.push(sum);
@@ -234,6 +261,31 @@ pub fn poseidon_hash2_gadget(
     gadget.hash2(cs, a, b)
 }
 
+/// Standalone function to hash three field element variables, as
+/// `poseidon_hash3`
+pub fn poseidon_hash3_gadget(
+    cs: ConstraintSystemRef<Fr>,
+    a: &FpVar<Fr>,
+    b: &FpVar<Fr>,
+    c: &FpVar<Fr>,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    let gadget = PoseidonGadget::with_width(cs.clone(), t4::WIDTH)?;
+    gadget.hash(cs, &[a.clone(), b.clone(), c.clone()])
+}
+
+/// Standalone function to hash four field element variables, as
+/// `poseidon_hash4`
+pub fn poseidon_hash4_gadget(
+    cs: ConstraintSystemRef<Fr>,
+    a: &FpVar<Fr>,
+    b: &FpVar<Fr>,
+    c: &FpVar<Fr>,
+    d: &FpVar<Fr>,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    let gadget = PoseidonGadget::with_width(cs.clone(), t5::WIDTH)?;
+    gadget.hash(cs, &[a.clone(), b.clone(), c.clone(), d.clone()])
+}
+
 /// Standalone function to hash multiple field element variables
 pub fn poseidon_hash_gadget(
     cs: ConstraintSystemRef<Fr>,
@@ -288,6 +340,56 @@ mod tests {
         assert!(cs.is_satisfied().unwrap());
     }
 
+    #[test]
+    fn test_wider_gadgets_match_native() {
+        use crate::crypto::poseidon::{poseidon_hash3, poseidon_hash4};
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let values = [5u64, 6, 7, 8].map(Fr::from);
+        let vars: Vec<FpVar<Fr>> = values
+            .iter()
+            .map(|v| FpVar::new_witness(cs.clone(), || Ok(*v)).unwrap())
+            .collect();
+
+        let h3 = poseidon_hash3_gadget(cs.clone(), &vars[0], &vars[1], &vars[2]).unwrap();
+        assert_eq!(h3.value().unwrap(), poseidon_hash3(&values[0], &values[1], &values[2]));
+        let before = cs.num_constraints();
+        let h4 = poseidon_hash4_gadget(cs.clone(), &vars[0], &vars[1], &vars[2], &vars[3]).unwrap();
+        assert_eq!(
+            h4.value().unwrap(),
+            poseidon_hash4(&values[0], &values[1], &values[2], &values[3])
+        );
+        assert!(cs.is_satisfied().unwrap());
+
+        // One t = 5 permutation costs less than the three t = 3 ones it replaces
+        let t5_cost = cs.num_constraints() - before;
+        let before = cs.num_constraints();
+        let h1 = poseidon_hash2_gadget(cs.clone(), &vars[0], &vars[1]).unwrap();
+        let h2 = poseidon_hash2_gadget(cs.clone(), &vars[2], &vars[3]).unwrap();
+        let _ = poseidon_hash2_gadget(cs.clone(), &h1, &h2).unwrap();
+        assert!(t5_cost < cs.num_constraints() - before);
+
+        assert!(PoseidonGadget::with_width(cs, 6).is_err());
+    }
+
+    #[test]
+    fn test_gadget_matches_test_vectors() {
+        use crate::crypto::test_vectors::{parse_field, parse_fields, TestVectors};
+
+        for vector in TestVectors::fixture().unwrap().poseidon {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let inputs: Vec<FpVar<Fr>> = parse_fields(&vector.inputs)
+                .unwrap()
+                .into_iter()
+                .map(|v| FpVar::new_witness(cs.clone(), || Ok(v)).unwrap())
+                .collect();
+            let gadget = PoseidonGadget::with_width(cs.clone(), inputs.len() + 1).unwrap();
+            let hash = gadget.hash(cs.clone(), &inputs).unwrap();
+            assert_eq!(hash.value().unwrap(), parse_field(&vector.hash).unwrap());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
     #[test]
     fn test_poseidon_gadget_constraint_count() {
         let cs = ConstraintSystem::<Fr>::new_ref();
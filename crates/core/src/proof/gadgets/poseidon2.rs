@@ -0,0 +1,180 @@
+//! Poseidon2 Hash Gadget for R1CS circuits
+//!
+//! Implements the Poseidon2 permutation as constraints, matching the native
+//! implementation in crypto::poseidon2. The linear layers are additions of
+//! the state's sum, so each round costs only its S-boxes.
+
+use ark_bn254::Fr;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+use crate::crypto::poseidon2::{round_constants, FULL_ROUNDS, PARTIAL_ROUNDS, WIDTH};
+use crate::crypto::protocol::ProtocolVersion;
+
+use super::poseidon::poseidon_hash2_gadget;
+
+/// Internal matrix diagonal minus one, as the native implementation
+const INTERNAL_DIAG_M1: [u64; WIDTH] = [1, 1, 2];
+
+/// Poseidon2 hash gadget for circuits
+pub struct Poseidon2Gadget {
+    /// Round constants as constraint variables, `WIDTH` per round
+    round_constants: Vec<[FpVar<Fr>; WIDTH]>,
+}
+
+impl Poseidon2Gadget {
+    /// Create a new Poseidon2 gadget
+    pub fn new(cs: ConstraintSystemRef<Fr>) -> Result<Self, SynthesisError> {
+        let round_constants = round_constants()
+            .iter()
+            .map(|round| {
+                let [a, b, c] = round;
+                Ok([
+                    FpVar::new_constant(cs.clone(), *a)?,
+                    FpVar::new_constant(cs.clone(), *b)?,
+                    FpVar::new_constant(cs.clone(), *c)?,
+                ])
+            })
+            .collect::<Result<_, SynthesisError>>()?;
+        Ok(Self { round_constants })
+    }
+
+    /// Hash two field elements
+    pub fn hash2(&self, a: &FpVar<Fr>, b: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+        let mut state = [FpVar::zero(), a.clone(), b.clone()];
+        self.permute(&mut state)?;
+        let [first, _, _] = state;
+        Ok(first)
+    }
+
+    /// Apply the Poseidon2 permutation to the state
+    fn permute(&self, state: &mut [FpVar<Fr>; WIDTH]) -> Result<(), SynthesisError> {
+        let half_full = FULL_ROUNDS / 2;
+        external_layer(state);
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            if round < half_full || round >= half_full + PARTIAL_ROUNDS {
+                for (elem, constant) in state.iter_mut().zip(constants) {
+                    *elem = sbox(&(&*elem + constant))?;
+                }
+                external_layer(state);
+            } else {
+                state[0] = sbox(&(&state[0] + &constants[0]))?;
+                internal_layer(state);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Multiply by circ(2, 1, 1)
+fn external_layer(state: &mut [FpVar<Fr>; WIDTH]) {
+    let sum = &state[0] + &state[1] + &state[2];
+    for elem in state.iter_mut() {
+        *elem += &sum;
+    }
+}
+
+/// Multiply by 1 + diag(INTERNAL_DIAG_M1)
+fn internal_layer(state: &mut [FpVar<Fr>; WIDTH]) {
+    let sum = &state[0] + &state[1] + &state[2];
+    for (elem, diag) in state.iter_mut().zip(INTERNAL_DIAG_M1) {
+        *elem = &*elem * Fr::from(diag) + &sum;
+    }
+}
+
+/// S-box function: x^5
+fn sbox(x: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+    let x2 = x.square()?;
+    let x4 = x2.square()?;
+    Ok(&x4 * x)
+}
+
+/// Standalone function to hash two field element variables with Poseidon2
+pub fn poseidon2_hash2_gadget(
+    cs: ConstraintSystemRef<Fr>,
+    a: &FpVar<Fr>,
+    b: &FpVar<Fr>,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    Poseidon2Gadget::new(cs)?.hash2(a, b)
+}
+
+/// Hash two field element variables with the hash of `version`
+pub fn hash2_gadget(
+    version: ProtocolVersion,
+    cs: ConstraintSystemRef<Fr>,
+    a: &FpVar<Fr>,
+    b: &FpVar<Fr>,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    match version {
+        ProtocolVersion::V1 => poseidon_hash2_gadget(cs, a, b),
+        ProtocolVersion::V2 => poseidon2_hash2_gadget(cs, a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_poseidon2_gadget_matches_native() {
+        let (a, b) = (Fr::from(1u64), Fr::from(2u64));
+        for version in [ProtocolVersion::V1, ProtocolVersion::V2] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let a_var = FpVar::new_witness(cs.clone(), || Ok(a)).unwrap();
+            let b_var = FpVar::new_witness(cs.clone(), || Ok(b)).unwrap();
+
+            let result = hash2_gadget(version, cs.clone(), &a_var, &b_var).unwrap();
+            assert_eq!(result.value().unwrap(), version.hash2(&a, &b));
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_commitments_match_test_vectors() {
+        use super::super::poseidon::poseidon_hash4_gadget;
+        use crate::crypto::test_vectors::{parse_field, parse_version, TestVectors};
+
+        for vector in TestVectors::fixture().unwrap().commitments {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let values = [
+                parse_field(&vector.spending_key).unwrap(),
+                Fr::from(vector.amount),
+                parse_field(&vector.blinding).unwrap(),
+                parse_field(&vector.asset_id).unwrap(),
+            ];
+            let [sk, amount, blinding, asset_id] =
+                values.map(|v| FpVar::new_witness(cs.clone(), || Ok(v)).unwrap());
+
+            let commitment = match parse_version(vector.version).unwrap() {
+                ProtocolVersion::V1 => {
+                    poseidon_hash4_gadget(cs.clone(), &sk, &amount, &blinding, &asset_id)
+                }
+                version => {
+                    let h1 = hash2_gadget(version, cs.clone(), &sk, &amount).unwrap();
+                    let h2 = hash2_gadget(version, cs.clone(), &blinding, &asset_id).unwrap();
+                    hash2_gadget(version, cs.clone(), &h1, &h2)
+                }
+            }
+            .unwrap();
+            assert_eq!(commitment.value().unwrap(), parse_field(&vector.commitment).unwrap());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_poseidon2_gadget_constraint_count() {
+        let count = |version| {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let a = FpVar::new_witness(cs.clone(), || Ok(Fr::from(1u64))).unwrap();
+            let b = FpVar::new_witness(cs.clone(), || Ok(Fr::from(2u64))).unwrap();
+            let _ = hash2_gadget(version, cs.clone(), &a, &b).unwrap();
+            cs.num_constraints()
+        };
+
+        // Three constraints per S-box
+        let poseidon2 = count(ProtocolVersion::V2);
+        assert_eq!(poseidon2, 3 * (WIDTH * FULL_ROUNDS + PARTIAL_ROUNDS));
+        assert!(poseidon2 <= count(ProtocolVersion::V1));
+    }
+}
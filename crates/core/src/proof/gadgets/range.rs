@@ -0,0 +1,47 @@
+//! Range Check Gadget for R1CS circuits
+//!
+//! Bounds a field element to a number of bits, so sums of amounts cannot
+//! wrap around the field modulus.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{boolean::Boolean, fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::SynthesisError;
+
+/// Enforce that `value` fits in 64 bits
+///
+/// Decomposes the value into 64 boolean witnesses and recomposes them, at
+/// a cost of 65 constraints.
+pub fn enforce_u64(value: &FpVar<Fr>) -> Result<(), SynthesisError> {
+    let cs = value.cs();
+    let bits = value.value().map(|v| v.into_bigint().to_bits_le());
+    let bit_vars = (0..64)
+        .map(|i| {
+            Boolean::new_witness(cs.clone(), || {
+                bits.as_ref().map(|b| b[i]).map_err(|_| SynthesisError::AssignmentMissing)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp_var(&bit_vars)?.enforce_equal(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn fits(value: Fr) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let var = FpVar::new_witness(cs.clone(), || Ok(value)).unwrap();
+        enforce_u64(&var).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_enforce_u64() {
+        assert!(fits(Fr::from(0u64)));
+        assert!(fits(Fr::from(u64::MAX)));
+        assert!(!fits(Fr::from(u64::MAX) + Fr::from(1u64)));
+        assert!(!fits(-Fr::from(1u64)));
+    }
+}
@@ -0,0 +1,538 @@
+//! Join-Split Circuit for Multi-Note Transfers
+//!
+//! This circuit proves that a transfer spending two notes into two new
+//! notes is valid:
+//! 1. The owner knows the preimage of each input commitment, and each
+//!    input with a non-zero amount is in the Merkle tree
+//! 2. Each nullifier is correctly derived from its input's spending key and
+//!    leaf index
+//! 3. Each output commitment is correctly formed
+//! 4. Amounts are conserved: sum(inputs) = sum(outputs) + fee
+//!
+//! A zero-amount input skips the membership check, so a single note can be
+//! spent by padding with a dummy input (see `JoinSplitCircuit::join_split`).
+//! Every amount and the fee are bounded to 64 bits, so the conservation
+//! sum cannot wrap around the field.
+//!
+//! Public Inputs:
+//! - merkle_root: The Merkle tree root the inputs are proven against
+//! - nullifiers: The nullifiers of the two inputs
+//! - output_commitments: The commitments to the two outputs
+//! - fee: The part of the inputs not carried to the outputs
+//! - asset_id: The asset of every note
+//!
+//! Private Inputs (Witness), per input:
+//! - secret, amount, blinding, leaf_index and Merkle path
+//!
+//! Private Inputs (Witness), per output:
+//! - owner's spending key, amount and blinding
+
+use ark_bn254::Fr;
+use ark_ff::{PrimeField, UniformRand};
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use rand::rngs::OsRng;
+use zeroize::Zeroize;
+
+use super::gadgets::merkle::{enforce_nonempty_leaf, MerklePathGadget};
+use super::gadgets::poseidon::PoseidonGadget;
+use super::gadgets::range::enforce_u64;
+use super::ProofError;
+use crate::crypto::merkle::{MerklePath, TREE_DEPTH};
+use crate::crypto::nullifier::Note;
+use crate::crypto::poseidon::poseidon_hash2;
+
+/// Number of input and of output notes
+pub const JOIN_SPLIT_ARITY: usize = 2;
+
+/// Join-split circuit for 2-input, 2-output transfers
+#[derive(Clone)]
+pub struct JoinSplitCircuit {
+    // ===== Public Inputs =====
+    /// Merkle root the inputs are proven against
+    pub merkle_root: Option<Fr>,
+    /// Nullifiers of the spent notes
+    pub nullifiers: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Commitments to the output notes
+    pub output_commitments: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Part of the inputs not carried to the outputs
+    pub fee: Option<Fr>,
+    /// Asset ID of every note (0 for native SOL)
+    pub asset_id: Option<Fr>,
+
+    // ===== Private Inputs (Witness) =====
+    /// Input owners' secrets (32 bytes as Fr)
+    pub input_secrets: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Amounts in the input notes (0 for a dummy input)
+    pub input_amounts: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Blinding factors of the input commitments
+    pub input_blindings: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Leaf indices of the input commitments
+    pub input_leaf_indices: [Option<u64>; JOIN_SPLIT_ARITY],
+    /// Merkle path siblings of each input
+    pub input_merkle_paths: [Option<Vec<Fr>>; JOIN_SPLIT_ARITY],
+    /// Merkle path indices (left/right) of each input
+    pub input_merkle_indices: [Option<Vec<bool>>; JOIN_SPLIT_ARITY],
+    /// Output owners' spending keys
+    pub output_spending_keys: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Amounts in the output notes
+    pub output_amounts: [Option<Fr>; JOIN_SPLIT_ARITY],
+    /// Blinding factors of the output commitments
+    pub output_blindings: [Option<Fr>; JOIN_SPLIT_ARITY],
+
+    // ===== Shape =====
+    /// Depth of the pool's tree, which fixes the circuit and so its keys
+    pub tree_depth: usize,
+}
+
+impl Default for JoinSplitCircuit {
+    fn default() -> Self {
+        Self {
+            merkle_root: None,
+            nullifiers: Default::default(),
+            output_commitments: Default::default(),
+            fee: None,
+            asset_id: None,
+            input_secrets: Default::default(),
+            input_amounts: Default::default(),
+            input_blindings: Default::default(),
+            input_leaf_indices: Default::default(),
+            input_merkle_paths: Default::default(),
+            input_merkle_indices: Default::default(),
+            output_spending_keys: Default::default(),
+            output_amounts: Default::default(),
+            output_blindings: Default::default(),
+            tree_depth: TREE_DEPTH,
+        }
+    }
+}
+
+impl JoinSplitCircuit {
+    /// Number of public inputs
+    /// (merkle_root, 2 nullifiers, 2 output commitments, fee, asset_id)
+    pub const NUM_PUBLIC_INPUTS: usize = 7;
+
+    /// Build the circuit that spends `inputs` into `outputs`
+    ///
+    /// Each `Some((note, path))` input must open under `merkle_root`; `None`
+    /// stands for a dummy zero-amount input with a random secret. The real
+    /// inputs' paths set the tree depth, which is the default without any.
+    /// The outputs' leaf indices are ignored. Every note must hold the outputs'
+    /// asset, and the inputs must cover the outputs plus `fee` exactly.
+    pub fn join_split(
+        inputs: [Option<(&Note, &MerklePath)>; JOIN_SPLIT_ARITY],
+        outputs: [&Note; JOIN_SPLIT_ARITY],
+        merkle_root: Fr,
+        fee: u64,
+    ) -> Result<Self, ProofError> {
+        let asset_id = outputs[0].asset_id;
+        let mut circuit = Self::default();
+        if let Some((_, path)) = inputs.iter().flatten().next() {
+            circuit.tree_depth = path.depth();
+        }
+        circuit.merkle_root = Some(merkle_root);
+        circuit.fee = Some(Fr::from(fee));
+        circuit.asset_id = Some(asset_id);
+
+        let mut total_in: u128 = 0;
+        for (i, input) in inputs.iter().enumerate() {
+            let dummy;
+            let (note, leaf_index, siblings, indices) = match input {
+                Some((note, path)) => {
+                    let leaf_index = note.leaf_index.ok_or(ProofError::InvalidWitness)?;
+                    if note.asset_id != asset_id
+                        || path.depth() != circuit.tree_depth
+                        || path.leaf_index != leaf_index
+                        || !path.verify(&note.commitment(), &merkle_root)
+                    {
+                        return Err(ProofError::InvalidWitness);
+                    }
+                    (*note, leaf_index, path.siblings.clone(), path.indices.clone())
+                }
+                None => {
+                    dummy = Note::new_random(0, asset_id, Fr::rand(&mut OsRng));
+                    let depth = circuit.tree_depth;
+                    (&dummy, 0, vec![Fr::from(0u64); depth], vec![false; depth])
+                }
+            };
+            total_in += note.amount as u128;
+
+            circuit.nullifiers[i] = Some(nullifier(note.spending_key().as_field(), leaf_index));
+            circuit.input_secrets[i] = Some(Fr::from_le_bytes_mod_order(&note.secret));
+            circuit.input_amounts[i] = Some(Fr::from(note.amount));
+            circuit.input_blindings[i] = Some(note.blinding);
+            circuit.input_leaf_indices[i] = Some(leaf_index);
+            circuit.input_merkle_paths[i] = Some(siblings);
+            circuit.input_merkle_indices[i] = Some(indices);
+        }
+
+        // The same note twice would publish one nullifier twice
+        if circuit.nullifiers[0] == circuit.nullifiers[1] {
+            return Err(ProofError::InvalidWitness);
+        }
+
+        let mut total_out = fee as u128;
+        for (i, output) in outputs.iter().enumerate() {
+            if output.asset_id != asset_id {
+                return Err(ProofError::InvalidWitness);
+            }
+            total_out += output.amount as u128;
+
+            circuit.output_commitments[i] = Some(output.commitment());
+            circuit.output_spending_keys[i] = Some(*output.spending_key().as_field());
+            circuit.output_amounts[i] = Some(Fr::from(output.amount));
+            circuit.output_blindings[i] = Some(output.blinding);
+        }
+        if total_in != total_out {
+            return Err(ProofError::InvalidWitness);
+        }
+
+        Ok(circuit)
+    }
+
+    /// The same circuit for a pool of depth `tree_depth`
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Public inputs in circuit order: merkle_root, nullifiers,
+    /// output_commitments, fee, asset_id
+    pub fn public_inputs(&self) -> Option<[Fr; Self::NUM_PUBLIC_INPUTS]> {
+        Some([
+            self.merkle_root?,
+            self.nullifiers[0]?,
+            self.nullifiers[1]?,
+            self.output_commitments[0]?,
+            self.output_commitments[1]?,
+            self.fee?,
+            self.asset_id?,
+        ])
+    }
+}
+
+/// Nullifier as the circuit derives it (not `Note::nullifier`)
+fn nullifier(spending_key: &Fr, leaf_index: u64) -> Fr {
+    let index_with_domain = poseidon_hash2(
+        &Fr::from(leaf_index),
+        &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+    );
+    poseidon_hash2(spending_key, &index_with_domain)
+}
+
+impl Zeroize for JoinSplitCircuit {
+    fn zeroize(&mut self) {
+        self.input_secrets.zeroize();
+        self.input_amounts.zeroize();
+        self.input_blindings.zeroize();
+        self.input_leaf_indices.zeroize();
+        self.input_merkle_paths.zeroize();
+        self.input_merkle_indices.zeroize();
+        self.output_spending_keys.zeroize();
+        self.output_amounts.zeroize();
+        self.output_blindings.zeroize();
+    }
+}
+
+impl Drop for JoinSplitCircuit {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for JoinSplitCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // One gadget per width for every hash below, rather than one per hash
+        let poseidon = PoseidonGadget::new(cs.clone())?;
+        let poseidon4 = PoseidonGadget::with_width(cs.clone(), 5)?;
+
+        // ===== Allocate Public Inputs =====
+        let merkle_root_var = FpVar::new_input(cs.clone(), || {
+            self.merkle_root.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let nullifier_vars = self
+            .nullifiers
+            .map(|n| FpVar::new_input(cs.clone(), || n.ok_or(SynthesisError::AssignmentMissing)));
+
+        let commitment_vars = self
+            .output_commitments
+            .map(|c| FpVar::new_input(cs.clone(), || c.ok_or(SynthesisError::AssignmentMissing)));
+
+        let fee_var = FpVar::new_input(cs.clone(), || {
+            self.fee.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let asset_id_var = FpVar::new_input(cs.clone(), || {
+            self.asset_id.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let spending_domain = FpVar::new_constant(
+            cs.clone(),
+            Fr::from_le_bytes_mod_order(b"NYX_SPENDING_KEY"),
+        )?;
+        let nullifier_domain = FpVar::new_constant(
+            cs.clone(),
+            Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+        )?;
+        let zero = FpVar::new_constant(cs.clone(), Fr::from(0u64))?;
+
+        // ===== Inputs =====
+        let mut total_in = zero.clone();
+        for (i, nullifier_var) in nullifier_vars.into_iter().enumerate() {
+            let secret_var = FpVar::new_witness(cs.clone(), || {
+                self.input_secrets[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let amount_var = FpVar::new_witness(cs.clone(), || {
+                self.input_amounts[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let blinding_var = FpVar::new_witness(cs.clone(), || {
+                self.input_blindings[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let leaf_index_var = FpVar::new_witness(cs.clone(), || {
+                self.input_leaf_indices[i].map(Fr::from).ok_or(SynthesisError::AssignmentMissing)
+            })?;
+
+            // Spending key and commitment, as in the transfer circuit
+            let spending_key_var =
+                poseidon.hash2(cs.clone(), &secret_var, &spending_domain)?;
+            let commitment_var = poseidon4.hash(
+                cs.clone(),
+                &[
+                    spending_key_var.clone(),
+                    amount_var.clone(),
+                    blinding_var.clone(),
+                    asset_id_var.clone(),
+                ],
+            )?;
+            enforce_nonempty_leaf(&commitment_var)?;
+
+            // Membership, unless the input carries nothing:
+            // (computed_root - merkle_root) * amount == 0
+            let (merkle_path, merkle_indices) =
+                match (self.input_merkle_paths[i].clone(), self.input_merkle_indices[i].clone()) {
+                    (Some(path), Some(indices)) => (path, indices),
+                    _ if cs.is_in_setup_mode() => {
+                        (vec![Fr::from(0u64); self.tree_depth], vec![false; self.tree_depth])
+                    }
+                    _ => return Err(SynthesisError::AssignmentMissing),
+                };
+            let path_gadget = MerklePathGadget::new_witness(
+                cs.clone(),
+                self.tree_depth,
+                &merkle_path,
+                &merkle_indices,
+            )?;
+            let computed_root = path_gadget.compute_root_with(&poseidon, cs.clone(), &commitment_var)?;
+            ((computed_root - &merkle_root_var) * &amount_var).enforce_equal(&zero)?;
+
+            // Nullifier, from the index the path opens: a free index would
+            // let one note fill both inputs under two nullifiers
+            path_gadget.enforce_leaf_index(&leaf_index_var)?;
+            let index_with_domain =
+                poseidon.hash2(cs.clone(), &leaf_index_var, &nullifier_domain)?;
+            let computed_nullifier =
+                poseidon.hash2(cs.clone(), &spending_key_var, &index_with_domain)?;
+            computed_nullifier.enforce_equal(&nullifier_var?)?;
+
+            enforce_u64(&amount_var)?;
+            total_in += amount_var;
+        }
+
+        // ===== Outputs =====
+        let mut total_out = fee_var.clone();
+        enforce_u64(&fee_var)?;
+        for (i, commitment_var) in commitment_vars.into_iter().enumerate() {
+            let spending_key_var = FpVar::new_witness(cs.clone(), || {
+                self.output_spending_keys[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let amount_var = FpVar::new_witness(cs.clone(), || {
+                self.output_amounts[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let blinding_var = FpVar::new_witness(cs.clone(), || {
+                self.output_blindings[i].ok_or(SynthesisError::AssignmentMissing)
+            })?;
+
+            let computed_commitment = poseidon4.hash(
+                cs.clone(),
+                &[
+                    spending_key_var.clone(),
+                    amount_var.clone(),
+                    blinding_var.clone(),
+                    asset_id_var.clone(),
+                ],
+            )?;
+            computed_commitment.enforce_equal(&commitment_var?)?;
+
+            enforce_u64(&amount_var)?;
+            total_out += amount_var;
+        }
+
+        // ===== Amount conservation =====
+        total_in.enforce_equal(&total_out)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    use crate::crypto::merkle::PoseidonMerkleTree;
+    use crate::crypto::poseidon::poseidon_hash4;
+
+    fn note(amount: u64) -> Note {
+        Note::new_random(amount, Fr::from(0u64), Fr::rand(&mut OsRng))
+    }
+
+    /// Insert notes of `amounts` and return them with their paths and root
+    fn shielded(amounts: &[u64]) -> (Vec<(Note, MerklePath)>, Fr) {
+        let mut tree = PoseidonMerkleTree::new();
+        let mut notes = Vec::new();
+        for &amount in amounts {
+            let mut note = note(amount);
+            note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+            notes.push(note);
+        }
+        let spent = notes
+            .into_iter()
+            .map(|note| {
+                let path = tree.generate_proof(note.leaf_index.unwrap()).unwrap();
+                (note, path)
+            })
+            .collect();
+        (spent, tree.root())
+    }
+
+    fn is_satisfied(circuit: JoinSplitCircuit) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_join_split_valid() {
+        let (spent, root) = shielded(&[600, 400]);
+        let (payment, change) = (note(700), note(290));
+        let circuit = JoinSplitCircuit::join_split(
+            [Some((&spent[0].0, &spent[0].1)), Some((&spent[1].0, &spent[1].1))],
+            [&payment, &change],
+            root,
+            10,
+        )
+        .unwrap();
+        let inputs = circuit.public_inputs().unwrap();
+        assert_eq!(inputs[3], payment.commitment());
+        assert_eq!(inputs[5], Fr::from(10u64));
+        assert!(is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_join_split_dummy_input() {
+        let (spent, root) = shielded(&[1000]);
+        let (payment, change) = (note(250), note(750));
+        let circuit = JoinSplitCircuit::join_split(
+            [Some((&spent[0].0, &spent[0].1)), None],
+            [&payment, &change],
+            root,
+            0,
+        )
+        .unwrap();
+        assert!(is_satisfied(circuit.clone()));
+
+        // A dummy cannot carry value: with an amount it needs membership
+        let mut inflated = circuit;
+        inflated.input_amounts[1] = Some(Fr::from(5u64));
+        inflated.fee = Some(Fr::from(5u64));
+        assert!(!is_satisfied(inflated));
+    }
+
+    #[test]
+    fn test_join_split_rejects_unbalanced_amounts() {
+        let (spent, root) = shielded(&[600, 400]);
+        let (payment, change) = (note(700), note(300));
+        let circuit = JoinSplitCircuit::join_split(
+            [Some((&spent[0].0, &spent[0].1)), Some((&spent[1].0, &spent[1].1))],
+            [&payment, &change],
+            root,
+            0,
+        )
+        .unwrap();
+
+        let mut greedy = circuit.clone();
+        greedy.fee = Some(Fr::from(1u64));
+        assert!(!is_satisfied(greedy));
+
+        // A field-wrapping amount balances the sum but fails the range check
+        let overflow = Fr::from(u64::MAX) + Fr::from(1u64);
+        let wrapped_amount = Fr::from(300u64) - overflow;
+        let mut wrapped = circuit;
+        wrapped.output_amounts[1] = Some(wrapped_amount);
+        wrapped.output_commitments[1] = Some(poseidon_hash4(
+            change.spending_key().as_field(),
+            &wrapped_amount,
+            &change.blinding,
+            &change.asset_id,
+        ));
+        wrapped.fee = Some(overflow);
+        assert!(!is_satisfied(wrapped));
+    }
+
+    #[test]
+    fn test_join_split_rejects_same_note_twice() {
+        let (spent, root) = shielded(&[600]);
+        let (note_in, path) = &spent[0];
+        let (payment, change) = (note(600), note(600));
+        let mut circuit = JoinSplitCircuit::join_split(
+            [Some((note_in, path)), None],
+            [&payment, &note(0)],
+            root,
+            0,
+        )
+        .unwrap();
+
+        // The second input reuses the note's path under another leaf index,
+        // so its nullifier differs from the first
+        circuit.input_secrets[1] = circuit.input_secrets[0];
+        circuit.input_amounts[1] = circuit.input_amounts[0];
+        circuit.input_blindings[1] = circuit.input_blindings[0];
+        circuit.input_merkle_paths[1] = circuit.input_merkle_paths[0].clone();
+        circuit.input_merkle_indices[1] = circuit.input_merkle_indices[0].clone();
+        circuit.input_leaf_indices[1] = Some(5);
+        circuit.nullifiers[1] = Some(nullifier(note_in.spending_key().as_field(), 5));
+        assert_ne!(circuit.nullifiers[0], circuit.nullifiers[1]);
+
+        circuit.output_commitments[1] = Some(change.commitment());
+        circuit.output_spending_keys[1] = Some(*change.spending_key().as_field());
+        circuit.output_amounts[1] = Some(Fr::from(change.amount));
+        circuit.output_blindings[1] = Some(change.blinding);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_join_split_rejects_bad_witness() {
+        let (spent, root) = shielded(&[600, 400]);
+        let input = Some((&spent[0].0, &spent[0].1));
+
+        // Unbalanced, the same note twice and a stale root
+        let unbalanced =
+            JoinSplitCircuit::join_split([input, None], [&note(600), &note(1)], root, 0);
+        assert!(matches!(unbalanced, Err(ProofError::InvalidWitness)));
+        let twice = JoinSplitCircuit::join_split([input, input], [&note(1200), &note(0)], root, 0);
+        assert!(matches!(twice, Err(ProofError::InvalidWitness)));
+        let stale = JoinSplitCircuit::join_split(
+            [input, None],
+            [&note(600), &note(0)],
+            Fr::rand(&mut OsRng),
+            0,
+        );
+        assert!(matches!(stale, Err(ProofError::InvalidWitness)));
+
+        // Outputs of another asset
+        let other_asset = Note::new_random(600, Fr::from(1u64), Fr::rand(&mut OsRng));
+        let mixed = JoinSplitCircuit::join_split([input, None], [&note(0), &other_asset], root, 0);
+        assert!(matches!(mixed, Err(ProofError::InvalidWitness)));
+    }
+}
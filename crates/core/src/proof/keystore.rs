@@ -0,0 +1,193 @@
+//! Proving and verifying key files
+//!
+//! `KeyStore` loads the transfer circuit's keys from disk, optionally
+//! checking each file against a pinned SHA-256, and keeps the deserialized
+//! proof system so repeated proofs skip the (slow) proving key decoding.
+//!
+//! Default locations are `<key dir>/<network>/transfer.pk` and
+//! `transfer.vk`, where the key directory is `$VEIL_KEY_DIR`, else
+//! `~/.veil/keys`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+use super::{ProofError, TransferProofSystem};
+use crate::crypto::ct::ct_eq;
+
+/// Environment variable overriding the default key directory
+pub const KEY_DIR_ENV: &str = "VEIL_KEY_DIR";
+
+/// Network a set of keys belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Localnet,
+    Devnet,
+    Mainnet,
+}
+
+impl Network {
+    /// Directory name for the network's keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Network::Localnet => "localnet",
+            Network::Devnet => "devnet",
+            Network::Mainnet => "mainnet",
+        }
+    }
+}
+
+/// Directory holding per-network key directories
+///
+/// `$VEIL_KEY_DIR` if set, else `~/.veil/keys`, else `./keys` without a
+/// home directory.
+pub fn default_key_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(KEY_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    match std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".veil").join("keys"),
+        None => PathBuf::from("keys"),
+    }
+}
+
+/// SHA-256 of a key file's contents, as pinned with `KeyStore::with_pinned_hashes`
+pub fn key_hash(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// Transfer circuit keys on disk, loaded at most once
+pub struct KeyStore {
+    proving_key_path: PathBuf,
+    verifying_key_path: PathBuf,
+    proving_key_hash: Option<[u8; 32]>,
+    verifying_key_hash: Option<[u8; 32]>,
+    system: Mutex<Option<Arc<TransferProofSystem>>>,
+}
+
+impl KeyStore {
+    /// Keys at the given paths
+    pub fn new(
+        proving_key_path: impl Into<PathBuf>,
+        verifying_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            proving_key_path: proving_key_path.into(),
+            verifying_key_path: verifying_key_path.into(),
+            proving_key_hash: None,
+            verifying_key_hash: None,
+            system: Mutex::new(None),
+        }
+    }
+
+    /// Keys at the default location for `network`
+    pub fn for_network(network: Network) -> Self {
+        let dir = default_key_dir().join(network.as_str());
+        Self::new(dir.join("transfer.pk"), dir.join("transfer.vk"))
+    }
+
+    /// Refuse key files whose SHA-256 differs from these
+    pub fn with_pinned_hashes(mut self, proving_key: [u8; 32], verifying_key: [u8; 32]) -> Self {
+        self.proving_key_hash = Some(proving_key);
+        self.verifying_key_hash = Some(verifying_key);
+        self
+    }
+
+    /// Path of the proving key file
+    pub fn proving_key_path(&self) -> &Path {
+        &self.proving_key_path
+    }
+
+    /// Path of the verifying key file
+    pub fn verifying_key_path(&self) -> &Path {
+        &self.verifying_key_path
+    }
+
+    /// Whether the keys have been loaded
+    pub fn is_loaded(&self) -> bool {
+        self.system.lock().unwrap().is_some()
+    }
+
+    /// The proof system for the stored keys, loading them on first use
+    ///
+    /// Fails with `KeyFile` if a file can't be read and `KeyHashMismatch`
+    /// if it doesn't match its pinned hash; a failed load is retried on the
+    /// next call.
+    pub fn transfer_system(&self) -> Result<Arc<TransferProofSystem>, ProofError> {
+        let mut system = self.system.lock().unwrap();
+        if let Some(system) = system.as_ref() {
+            return Ok(system.clone());
+        }
+        let pk_bytes = read_pinned(&self.proving_key_path, self.proving_key_hash)?;
+        let vk_bytes = read_pinned(&self.verifying_key_path, self.verifying_key_hash)?;
+        let loaded = Arc::new(TransferProofSystem::from_keys(&pk_bytes, &vk_bytes)?);
+        *system = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Write `system`'s keys to the store's paths, creating directories
+    ///
+    /// Returns the proving and verifying key hashes, for pinning.
+    pub fn save(&self, system: &TransferProofSystem) -> Result<([u8; 32], [u8; 32]), ProofError> {
+        let pk_bytes = system.serialize_proving_key()?;
+        let vk_bytes = system.serialize_verifying_key()?;
+        write_key(&self.proving_key_path, &pk_bytes)?;
+        write_key(&self.verifying_key_path, &vk_bytes)?;
+        Ok((key_hash(&pk_bytes), key_hash(&vk_bytes)))
+    }
+}
+
+/// Read a key file, checking it against `pin` if set
+fn read_pinned(path: &Path, pin: Option<[u8; 32]>) -> Result<Vec<u8>, ProofError> {
+    let bytes =
+        fs::read(path).map_err(|e| ProofError::KeyFile(format!("{}: {}", path.display(), e)))?;
+    if pin.is_some_and(|pin| !ct_eq(&pin, &key_hash(&bytes))) {
+        return Err(ProofError::KeyHashMismatch(path.display().to_string()));
+    }
+    Ok(bytes)
+}
+
+fn write_key(path: &Path, bytes: &[u8]) -> Result<(), ProofError> {
+    let key_error = |e: std::io::Error| ProofError::KeyFile(format!("{}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(key_error)?;
+    }
+    fs::write(path, bytes).map_err(key_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_locations() {
+        let store = KeyStore::for_network(Network::Devnet);
+        assert!(store.proving_key_path().ends_with("devnet/transfer.pk"));
+        assert!(store.verifying_key_path().ends_with("devnet/transfer.vk"));
+        assert!(!store.is_loaded());
+    }
+
+    #[test]
+    fn test_load_once_with_pinned_hashes() {
+        let dir = std::env::temp_dir().join(format!("veil_keys_{}", std::process::id()));
+        let store = KeyStore::new(dir.join("transfer.pk"), dir.join("transfer.vk"));
+        assert!(matches!(store.transfer_system(), Err(ProofError::KeyFile(_))));
+
+        let (pk_hash, vk_hash) = store.save(&TransferProofSystem::setup().unwrap()).unwrap();
+
+        let pinned = KeyStore::new(store.proving_key_path(), store.verifying_key_path())
+            .with_pinned_hashes(pk_hash, vk_hash);
+        let first = pinned.transfer_system().unwrap();
+        assert!(pinned.is_loaded());
+        assert!(Arc::ptr_eq(&first, &pinned.transfer_system().unwrap()));
+
+        let wrong = KeyStore::new(store.proving_key_path(), store.verifying_key_path())
+            .with_pinned_hashes(pk_hash, [0u8; 32]);
+        assert!(matches!(wrong.transfer_system(), Err(ProofError::KeyHashMismatch(_))));
+        assert!(!wrong.is_loaded());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
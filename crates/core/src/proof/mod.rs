@@ -6,21 +6,65 @@
 //! - `circuit`: Legacy circuit definitions (deprecated)
 //! - `gadgets`: R1CS constraint gadgets (Poseidon, Merkle)
 //! - `transfer_circuit`: Main transfer circuit using arkworks
+//! - `unshield_circuit`: Withdrawal circuit binding recipient, amount and fee
+//! - `joinsplit_circuit`: 2-input, 2-output transfer circuit with a fee
+//! - `witness`: Builds transfer circuits from notes
+//! - `keystore`: Loads and caches key files (not on wasm32, which loads
+//!   keys from bytes with `from_keys`)
+//! - `zkey`: Imports snarkjs `.zkey` and `verification_key.json` keys
+//! - `codegen`: Renders verifying keys as the program's `vk` module
+//! - `msm`: Groth16 proving with pluggable (e.g. GPU) MSMs (feature
+//!   `msm-backend`)
+//! - `ceremony`: Phase-2 trusted setup ceremony for production keys
+//! - `backend`: Proving backend trait, implemented for Groth16
+//! - `plonk`: PLONK backend with a universal SRS (feature `plonk`)
 //! - Proof generation and verification using ark-groth16
 
+pub mod backend;
+pub mod ceremony;
 pub mod circuit;
+pub mod codegen;
 pub mod gadgets;
+pub mod joinsplit_circuit;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keystore;
+#[cfg(feature = "msm-backend")]
+pub mod msm;
+#[cfg(feature = "plonk")]
+pub mod plonk;
 pub mod transfer_circuit;
+pub mod unshield_circuit;
+pub mod witness;
+pub mod zkey;
 
-use ark_bn254::{Bn254, Fr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use ark_bn254::{Bn254, Fr, G1Projective};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
 use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, ProvingKey, VerifyingKey};
+use ark_relations::r1cs::ConstraintSynthesizer;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use ark_snark::SNARK;
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-pub use transfer_circuit::TransferCircuit;
+pub use backend::{Groth16Backend, ProofBackend};
+pub use joinsplit_circuit::JoinSplitCircuit;
+#[cfg(not(target_arch = "wasm32"))]
+pub use keystore::{KeyStore, Network};
+pub use transfer_circuit::{asset_id_for_mint, TransferCircuit};
+pub use unshield_circuit::{recipient_hash, UnshieldCircuit};
+pub use witness::{SpendWitness, WitnessBuilder};
+
+pub use crate::verify::encoding::field_to_bytes_be;
+
+use crate::crypto::merkle::{check_depth, MerklePath, TREE_DEPTH};
+use crate::crypto::nullifier::Note;
+use crate::telemetry::{Telemetry, TelemetryEvent};
 
 #[derive(Error, Debug)]
 pub enum ProofError {
@@ -38,10 +82,31 @@ pub enum ProofError {
     InvalidProvingKey,
     #[error("Invalid verifying key")]
     InvalidVerifyingKey,
+    #[error("Key file error: {0}")]
+    KeyFile(String),
+    #[error("Key file does not match its pinned hash: {0}")]
+    KeyHashMismatch(String),
+    #[error("Keys are for tree depth {expected}, circuit has depth {actual}")]
+    TreeDepthMismatch { expected: usize, actual: usize },
+    #[error("Note from previous epoch ({note}) cannot be spent against the current pool ({pool})")]
+    NoteFromPreviousEpoch {
+        note: crate::epoch::PoolEpoch,
+        pool: crate::epoch::PoolEpoch,
+    },
+}
+
+/// Fail unless the circuit's tree depth is the one the keys were made for
+fn check_tree_depth(expected: usize, actual: usize) -> Result<(), ProofError> {
+    if expected != actual {
+        return Err(ProofError::TreeDepthMismatch { expected, actual });
+    }
+    Ok(())
 }
 
 /// Serialized Groth16 proof (256 bytes)
-/// Format: A (64) || B (128) || C (64)
+///
+/// arkworks' compressed encoding, zero-padded; this is what `verify` takes.
+/// The program takes `SolanaProof` (see `prove_for_solana`) instead.
 #[derive(Clone, Debug)]
 pub struct SerializedProof {
     pub bytes: Vec<u8>,
@@ -72,50 +137,83 @@ impl SerializedProof {
 /// Groth16 proof system for transfer circuits
 pub struct TransferProofSystem {
     proving_key: ProvingKey<Bn254>,
-    verifying_key: VerifyingKey<Bn254>,
-    prepared_vk: PreparedVerifyingKey<Bn254>,
+    verifier: TransferVerifier,
+    tree_depth: usize,
+    telemetry: Option<Arc<Telemetry>>,
+    #[cfg(feature = "msm-backend")]
+    msm: Option<Arc<dyn msm::MsmBackend>>,
 }
 
 impl TransferProofSystem {
     /// Generate proving and verifying keys for the transfer circuit
     ///
     /// WARNING: This uses a random toxic waste and is suitable only for testing.
-    /// For production, use a trusted setup ceremony.
+    /// For production, use a trusted setup ceremony (see `ceremony`).
     pub fn setup() -> Result<Self, ProofError> {
+        Self::setup_with_depth(TREE_DEPTH)
+    }
+
+    /// As `setup`, for a pool whose tree has depth `tree_depth`
+    pub fn setup_with_depth(tree_depth: usize) -> Result<Self, ProofError> {
+        check_depth(tree_depth).map_err(|e| ProofError::SetupError(e.to_string()))?;
+
         // Create a dummy circuit for setup
-        let circuit = TransferCircuit::default();
+        let circuit = TransferCircuit::default().with_tree_depth(tree_depth);
 
         let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(circuit, &mut OsRng)
             .map_err(|e| ProofError::SetupError(e.to_string()))?;
 
-        let prepared_vk = Groth16::<Bn254>::process_vk(&vk)
-            .map_err(|e| ProofError::SetupError(e.to_string()))?;
-
         Ok(Self {
             proving_key: pk,
-            verifying_key: vk,
-            prepared_vk,
+            verifier: TransferVerifier::new(vk)?,
+            tree_depth,
+            telemetry: None,
+            #[cfg(feature = "msm-backend")]
+            msm: None,
         })
     }
 
     /// Load from serialized keys
     pub fn from_keys(pk_bytes: &[u8], vk_bytes: &[u8]) -> Result<Self, ProofError> {
         let proving_key = ProvingKey::deserialize_compressed(pk_bytes)
-            .map_err(|e| ProofError::InvalidProvingKey)?;
-
-        let verifying_key = VerifyingKey::deserialize_compressed(vk_bytes)
-            .map_err(|e| ProofError::InvalidVerifyingKey)?;
-
-        let prepared_vk = Groth16::<Bn254>::process_vk(&verifying_key)
-            .map_err(|e| ProofError::SetupError(e.to_string()))?;
+            .map_err(|_| ProofError::InvalidProvingKey)?;
 
         Ok(Self {
             proving_key,
-            verifying_key,
-            prepared_vk,
+            verifier: TransferVerifier::from_vk_bytes(vk_bytes)?,
+            tree_depth: TREE_DEPTH,
+            telemetry: None,
+            #[cfg(feature = "msm-backend")]
+            msm: None,
         })
     }
 
+    /// Mark loaded keys as made for a tree of depth `tree_depth` rather than
+    /// the default; the keys themselves do not record it
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Depth of the tree the keys were made for
+    pub fn tree_depth(&self) -> usize {
+        self.tree_depth
+    }
+
+    /// Report proving time and failures to `telemetry`
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Run the proving MSMs on `msm` (e.g. `msm::GpuMsm`) instead of
+    /// arkworks' CPU implementation
+    #[cfg(feature = "msm-backend")]
+    pub fn with_msm(mut self, msm: Arc<dyn msm::MsmBackend>) -> Self {
+        self.msm = Some(msm);
+        self
+    }
+
     /// Serialize the proving key
     pub fn serialize_proving_key(&self) -> Result<Vec<u8>, ProofError> {
         let mut bytes = Vec::new();
@@ -128,7 +226,8 @@ impl TransferProofSystem {
     /// Serialize the verifying key
     pub fn serialize_verifying_key(&self) -> Result<Vec<u8>, ProofError> {
         let mut bytes = Vec::new();
-        self.verifying_key
+        self.verifier
+            .verifying_key
             .serialize_compressed(&mut bytes)
             .map_err(|e| ProofError::SerializationError(e.to_string()))?;
         Ok(bytes)
@@ -136,19 +235,39 @@ impl TransferProofSystem {
 
     /// Generate a proof for a transfer circuit
     pub fn prove(&self, circuit: TransferCircuit) -> Result<SerializedProof, ProofError> {
-        let proof = Groth16::<Bn254>::prove(&self.proving_key, circuit, &mut OsRng)
-            .map_err(|e| ProofError::GenerationFailed(e.to_string()))?;
+        serialize_proof(&self.prove_groth16(circuit)?)
+    }
 
-        // Serialize the proof
-        let mut bytes = Vec::new();
-        proof
-            .serialize_compressed(&mut bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+    /// Generate a proof in the layout the program's `Groth16Proof::from_bytes`
+    /// takes: uncompressed big-endian A | B | C with A negated
+    pub fn prove_for_solana(&self, circuit: TransferCircuit) -> Result<SolanaProof, ProofError> {
+        SolanaProof::from_arkworks(&self.prove_groth16(circuit)?)
+    }
 
-        // Pad to expected size if needed
-        bytes.resize(SerializedProof::SIZE, 0);
+    fn prove_groth16(&self, circuit: TransferCircuit) -> Result<Proof<Bn254>, ProofError> {
+        // Only timed for telemetry: `Instant` panics in the browser
+        let Some(telemetry) = &self.telemetry else {
+            return self.prove_inner(circuit);
+        };
+        let started = Instant::now();
+        let result = self.prove_inner(circuit);
+        telemetry.record(match &result {
+            Ok(_) => TelemetryEvent::ProofGenerated {
+                proving_time: started.elapsed(),
+            },
+            Err(e) => TelemetryEvent::ProofFailed(e.into()),
+        });
+        result
+    }
 
-        SerializedProof::from_bytes(bytes)
+    fn prove_inner(&self, circuit: TransferCircuit) -> Result<Proof<Bn254>, ProofError> {
+        check_tree_depth(self.tree_depth, circuit.tree_depth)?;
+        #[cfg(feature = "msm-backend")]
+        if let Some(backend) = &self.msm {
+            return msm::prove_with_msm(&self.proving_key, circuit, backend.as_ref(), &mut OsRng);
+        }
+        Groth16::<Bn254>::prove(&self.proving_key, circuit, &mut OsRng)
+            .map_err(|e| ProofError::GenerationFailed(e.to_string()))
     }
 
     /// Verify a proof with public inputs
@@ -157,24 +276,22 @@ impl TransferProofSystem {
         proof_bytes: &[u8],
         public_inputs: &[Fr],
     ) -> Result<bool, ProofError> {
-        // Deserialize proof
-        let proof = Proof::deserialize_compressed(proof_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+        self.verifier.verify(proof_bytes, public_inputs)
+    }
 
-        // Verify
-        let valid = Groth16::<Bn254>::verify_with_processed_vk(
-            &self.prepared_vk,
-            public_inputs,
-            &proof,
-        )
-        .map_err(|e| ProofError::VerificationFailed(e.to_string()))?;
+    /// As `TransferVerifier::verify_batch`
+    pub fn verify_batch(&self, batch: &[(&[u8], &[Fr])]) -> Vec<usize> {
+        self.verifier.verify_batch(batch)
+    }
 
-        Ok(valid)
+    /// The verifying half of this system, for services that only verify
+    pub fn verifier(&self) -> &TransferVerifier {
+        &self.verifier
     }
 
     /// Get the verifying key
     pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
-        &self.verifying_key
+        self.verifier.verifying_key()
     }
 
     /// Export verifying key in Solana-compatible format (big-endian)
@@ -184,90 +301,548 @@ impl TransferProofSystem {
     ///
     /// Returns a SolanaVerifyingKey struct containing all components.
     pub fn export_solana_vk(&self) -> Result<SolanaVerifyingKey, ProofError> {
-        use ark_ec::AffineRepr;
-        use ark_serialize::CanonicalSerialize;
+        self.verifier.export_solana_vk()
+    }
 
-        let vk = &self.verifying_key;
+    /// Export proof in Solana-compatible format (big-endian)
+    ///
+    /// Converts an arkworks Groth16 proof to the format expected by groth16-solana.
+    /// Note: The proof.a point must have its y-coordinate negated for groth16-solana.
+    pub fn export_solana_proof(&self, proof_bytes: &[u8]) -> Result<SolanaProof, ProofError> {
+        SolanaProof::from_compressed(proof_bytes)
+    }
 
-        // Serialize alpha_g1 (G1 point, 64 bytes compressed in arkworks)
-        let mut alpha_g1_bytes = Vec::new();
-        vk.alpha_g1.serialize_uncompressed(&mut alpha_g1_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let alpha_g1 = g1_le_to_be(&alpha_g1_bytes)?;
+    /// Prove a spend of `note` into a re-blinded output note, less `fee`
+    ///
+    /// See `TransferCircuit::spend` for the witness requirements.
+    pub fn prove_spend(
+        &self,
+        note: &Note,
+        merkle_path: &MerklePath,
+        merkle_root: Fr,
+        output_blinding: Fr,
+        fee: u64,
+    ) -> Result<SpendProof, ProofError> {
+        let circuit =
+            TransferCircuit::spend(note, merkle_path, merkle_root, output_blinding, fee)?;
+        let public_inputs = circuit.public_inputs().ok_or(ProofError::InvalidWitness)?;
+        let proof = self.prove_groth16(circuit)?;
+        Ok(SpendProof {
+            public_inputs,
+            proof: serialize_proof(&proof)?,
+            solana_proof: SolanaProof::from_arkworks(&proof)?,
+        })
+    }
+}
 
-        // Serialize beta_g2 (G2 point, 128 bytes)
-        let mut beta_g2_bytes = Vec::new();
-        vk.beta_g2.serialize_uncompressed(&mut beta_g2_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let beta_g2 = g2_le_to_be(&beta_g2_bytes)?;
+/// Verification for transfer proofs, from the verifying key alone
+///
+/// `TransferProofSystem` also holds the proving key, which is large and
+/// only needed to prove; relayers and indexers can load just this.
+#[derive(Clone, Debug)]
+pub struct TransferVerifier {
+    verifying_key: VerifyingKey<Bn254>,
+    prepared_vk: PreparedVerifyingKey<Bn254>,
+}
 
-        // Serialize gamma_g2 (G2 point, 128 bytes)
-        let mut gamma_g2_bytes = Vec::new();
-        vk.gamma_g2.serialize_uncompressed(&mut gamma_g2_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let gamma_g2 = g2_le_to_be(&gamma_g2_bytes)?;
+impl TransferVerifier {
+    pub fn new(verifying_key: VerifyingKey<Bn254>) -> Result<Self, ProofError> {
+        let prepared_vk = Groth16::<Bn254>::process_vk(&verifying_key)
+            .map_err(|e| ProofError::SetupError(e.to_string()))?;
+        Ok(Self {
+            verifying_key,
+            prepared_vk,
+        })
+    }
 
-        // Serialize delta_g2 (G2 point, 128 bytes)
-        let mut delta_g2_bytes = Vec::new();
-        vk.delta_g2.serialize_uncompressed(&mut delta_g2_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let delta_g2 = g2_le_to_be(&delta_g2_bytes)?;
+    /// Load from a verifying key serialized by
+    /// `TransferProofSystem::serialize_verifying_key`
+    pub fn from_vk_bytes(vk_bytes: &[u8]) -> Result<Self, ProofError> {
+        let verifying_key = VerifyingKey::deserialize_compressed(vk_bytes)
+            .map_err(|_| ProofError::InvalidVerifyingKey)?;
+        Self::new(verifying_key)
+    }
 
-        // Serialize IC elements (variable number of G1 points)
-        let mut ic = Vec::with_capacity(vk.gamma_abc_g1.len());
-        for point in &vk.gamma_abc_g1 {
-            let mut point_bytes = Vec::new();
-            point.serialize_uncompressed(&mut point_bytes)
-                .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-            ic.push(g1_le_to_be(&point_bytes)?);
+    /// Verify a proof with public inputs
+    pub fn verify(&self, proof_bytes: &[u8], public_inputs: &[Fr]) -> Result<bool, ProofError> {
+        verify_proof(&self.prepared_vk, proof_bytes, public_inputs)
+    }
+
+    /// Verify `(proof, public_inputs)` pairs together, returning the indices
+    /// of invalid proofs (empty if all verify)
+    ///
+    /// Cheaper than calling `verify` on each when most proofs are valid; see
+    /// `verify_proof_batch`.
+    pub fn verify_batch(&self, batch: &[(&[u8], &[Fr])]) -> Vec<usize> {
+        verify_proof_batch(&self.prepared_vk, batch)
+    }
+
+    /// Get the verifying key
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.verifying_key
+    }
+
+    /// Export verifying key in Solana-compatible format (big-endian)
+    pub fn export_solana_vk(&self) -> Result<SolanaVerifyingKey, ProofError> {
+        SolanaVerifyingKey::from_arkworks(&self.verifying_key)
+    }
+}
+
+/// A proven spend with its public inputs
+#[derive(Clone, Debug)]
+pub struct SpendProof {
+    /// merkle_root, nullifier, new_commitment, asset_id, fee
+    pub public_inputs: [Fr; TransferCircuit::NUM_PUBLIC_INPUTS],
+    /// arkworks encoding, for `TransferProofSystem::verify`
+    pub proof: SerializedProof,
+    /// groth16-solana encoding, for instruction data
+    pub solana_proof: SolanaProof,
+}
+
+impl SpendProof {
+    /// Nullifier as the program expects it
+    pub fn nullifier_bytes(&self) -> [u8; 32] {
+        field_to_bytes_be(&self.public_inputs[1])
+    }
+
+    /// Output commitment as the program expects it
+    pub fn new_commitment_bytes(&self) -> [u8; 32] {
+        field_to_bytes_be(&self.public_inputs[2])
+    }
+
+    /// Public inputs as the program expects them
+    pub fn onchain_public_inputs(&self) -> [[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS] {
+        self.public_inputs.map(|input| field_to_bytes_be(&input))
+    }
+}
+
+/// Public inputs of the transfer circuit
+///
+/// Mirrors `TransferPublicInputs` in the program, whose
+/// `from_verifier_inputs` decodes `to_onchain_bytes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferPublicInputs {
+    pub merkle_root: Fr,
+    pub nullifier: Fr,
+    pub new_commitment: Fr,
+    pub asset_id: Fr,
+    pub fee: u64,
+}
+
+impl TransferPublicInputs {
+    /// From the circuit's inputs, or `None` if the fee is not a `u64`
+    pub fn from_fields(fields: &[Fr; TransferCircuit::NUM_PUBLIC_INPUTS]) -> Option<Self> {
+        let fee = field_to_bytes_be(&fields[4]);
+        let (high, low) = fee.split_at(24);
+        if high.iter().any(|&byte| byte != 0) {
+            return None;
         }
+        Some(Self {
+            merkle_root: fields[0],
+            nullifier: fields[1],
+            new_commitment: fields[2],
+            asset_id: fields[3],
+            fee: u64::from_be_bytes(low.try_into().ok()?),
+        })
+    }
 
-        Ok(SolanaVerifyingKey {
-            alpha_g1,
-            beta_g2,
-            gamma_g2,
-            delta_g2,
-            ic,
+    /// The inputs in circuit order, for `TransferProofSystem::verify`
+    pub fn to_fields(&self) -> [Fr; TransferCircuit::NUM_PUBLIC_INPUTS] {
+        [
+            self.merkle_root,
+            self.nullifier,
+            self.new_commitment,
+            self.asset_id,
+            Fr::from(self.fee),
+        ]
+    }
+
+    /// 32-byte big-endian field elements, as groth16-solana takes them
+    pub fn to_onchain_bytes(&self) -> [[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS] {
+        self.to_fields().map(|input| field_to_bytes_be(&input))
+    }
+
+    /// Decode `to_onchain_bytes`, rejecting non-canonical field elements
+    /// and fees beyond a `u64`
+    pub fn from_onchain_bytes(
+        bytes: &[[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS],
+    ) -> Result<Self, ProofError> {
+        crate::verify::decode_public_inputs(bytes)
+            .and_then(|fields| Self::from_fields(fields.as_slice().try_into().ok()?))
+            .ok_or_else(|| {
+                ProofError::SerializationError("non-canonical public inputs".to_string())
+            })
+    }
+}
+
+/// Groth16 proof system for the unshield circuit
+///
+/// Unshields have their own circuit and keys; the pool stores this
+/// system's verifying key next to the transfer key.
+pub struct UnshieldProofSystem {
+    proving_key: ProvingKey<Bn254>,
+    verifying_key: VerifyingKey<Bn254>,
+    prepared_vk: PreparedVerifyingKey<Bn254>,
+    tree_depth: usize,
+}
+
+impl UnshieldProofSystem {
+    /// Generate proving and verifying keys for the unshield circuit
+    ///
+    /// WARNING: As with `TransferProofSystem::setup`, for testing only.
+    pub fn setup() -> Result<Self, ProofError> {
+        Self::setup_with_depth(TREE_DEPTH)
+    }
+
+    /// As `setup`, for a pool whose tree has depth `tree_depth`
+    pub fn setup_with_depth(tree_depth: usize) -> Result<Self, ProofError> {
+        check_depth(tree_depth).map_err(|e| ProofError::SetupError(e.to_string()))?;
+        let circuit = UnshieldCircuit::default().with_tree_depth(tree_depth);
+        let (proving_key, verifying_key) =
+            Groth16::<Bn254>::circuit_specific_setup(circuit, &mut OsRng)
+                .map_err(|e| ProofError::SetupError(e.to_string()))?;
+        Ok(Self::from_parts(proving_key, verifying_key)?.with_tree_depth(tree_depth))
+    }
+
+    /// Load from serialized keys
+    pub fn from_keys(pk_bytes: &[u8], vk_bytes: &[u8]) -> Result<Self, ProofError> {
+        let proving_key = ProvingKey::deserialize_compressed(pk_bytes)
+            .map_err(|_| ProofError::InvalidProvingKey)?;
+        let verifying_key = VerifyingKey::deserialize_compressed(vk_bytes)
+            .map_err(|_| ProofError::InvalidVerifyingKey)?;
+        Self::from_parts(proving_key, verifying_key)
+    }
+
+    fn from_parts(
+        proving_key: ProvingKey<Bn254>,
+        verifying_key: VerifyingKey<Bn254>,
+    ) -> Result<Self, ProofError> {
+        let prepared_vk = Groth16::<Bn254>::process_vk(&verifying_key)
+            .map_err(|e| ProofError::SetupError(e.to_string()))?;
+        Ok(Self {
+            proving_key,
+            verifying_key,
+            prepared_vk,
+            tree_depth: TREE_DEPTH,
         })
     }
 
-    /// Export proof in Solana-compatible format (big-endian)
+    /// As `TransferProofSystem::with_tree_depth`
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Depth of the tree the keys were made for
+    pub fn tree_depth(&self) -> usize {
+        self.tree_depth
+    }
+
+    /// Generate a proof for an unshield circuit
+    pub fn prove(&self, circuit: UnshieldCircuit) -> Result<SerializedProof, ProofError> {
+        check_tree_depth(self.tree_depth, circuit.tree_depth)?;
+        prove_circuit(&self.proving_key, circuit)
+    }
+
+    /// Verify a proof with public inputs
+    pub fn verify(&self, proof_bytes: &[u8], public_inputs: &[Fr]) -> Result<bool, ProofError> {
+        verify_proof(&self.prepared_vk, proof_bytes, public_inputs)
+    }
+
+    /// As `TransferVerifier::verify_batch`
+    pub fn verify_batch(&self, batch: &[(&[u8], &[Fr])]) -> Vec<usize> {
+        verify_proof_batch(&self.prepared_vk, batch)
+    }
+
+    /// Get the verifying key
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.verifying_key
+    }
+
+    /// Export verifying key in Solana-compatible format (big-endian)
+    pub fn export_solana_vk(&self) -> Result<SolanaVerifyingKey, ProofError> {
+        SolanaVerifyingKey::from_arkworks(&self.verifying_key)
+    }
+
+    /// Prove a withdrawal of `note` to `recipient`
     ///
-    /// Converts an arkworks Groth16 proof to the format expected by groth16-solana.
-    /// Note: The proof.a point must have its y-coordinate negated for groth16-solana.
-    pub fn export_solana_proof(&self, proof_bytes: &[u8]) -> Result<SolanaProof, ProofError> {
-        use ark_ec::AffineRepr;
-        use ark_serialize::CanonicalSerialize;
+    /// See `UnshieldCircuit::withdraw` for the witness requirements.
+    pub fn prove_withdrawal(
+        &self,
+        note: &Note,
+        merkle_path: &MerklePath,
+        merkle_root: Fr,
+        recipient: &[u8; 32],
+        relayer_fee: u64,
+    ) -> Result<WithdrawalProof, ProofError> {
+        let circuit =
+            UnshieldCircuit::withdraw(note, merkle_path, merkle_root, recipient, relayer_fee)?;
+        let public_inputs = circuit.public_inputs().ok_or(ProofError::InvalidWitness)?;
+        let proof = self.prove(circuit)?;
+        let solana_proof = Proof::<Bn254>::deserialize_compressed(proof.as_bytes())
+            .map_err(|e| ProofError::SerializationError(e.to_string()))
+            .and_then(|proof| SolanaProof::from_arkworks(&proof))?;
+        Ok(WithdrawalProof {
+            public_inputs,
+            proof,
+            solana_proof,
+        })
+    }
+}
 
-        // Deserialize the arkworks proof
-        let proof = Proof::<Bn254>::deserialize_compressed(proof_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+/// A proven withdrawal with its public inputs
+#[derive(Clone, Debug)]
+pub struct WithdrawalProof {
+    /// merkle_root, nullifier, recipient_hash, amount, relayer_fee, asset_id
+    pub public_inputs: [Fr; UnshieldCircuit::NUM_PUBLIC_INPUTS],
+    /// arkworks encoding, for `UnshieldProofSystem::verify`
+    pub proof: SerializedProof,
+    /// groth16-solana encoding, for instruction data
+    pub solana_proof: SolanaProof,
+}
 
-        // Serialize and convert proof_a (G1 point)
-        // Note: groth16-solana uses -A in the pairing equation
-        let mut a_bytes = Vec::new();
-        (-proof.a).serialize_uncompressed(&mut a_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let proof_a = g1_le_to_be(&a_bytes)?;
+impl WithdrawalProof {
+    /// Nullifier as the program expects it
+    pub fn nullifier_bytes(&self) -> [u8; 32] {
+        field_to_bytes_be(&self.public_inputs[1])
+    }
+}
 
-        // Serialize and convert proof_b (G2 point)
-        let mut b_bytes = Vec::new();
-        proof.b.serialize_uncompressed(&mut b_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let proof_b = g2_le_to_be(&b_bytes)?;
+/// Groth16 proof system for the join-split circuit
+///
+/// Join-splits have their own circuit and keys, separate from the transfer
+/// and unshield systems.
+pub struct JoinSplitProofSystem {
+    proving_key: ProvingKey<Bn254>,
+    verifying_key: VerifyingKey<Bn254>,
+    prepared_vk: PreparedVerifyingKey<Bn254>,
+    tree_depth: usize,
+}
 
-        // Serialize and convert proof_c (G1 point)
-        let mut c_bytes = Vec::new();
-        proof.c.serialize_uncompressed(&mut c_bytes)
-            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
-        let proof_c = g1_le_to_be(&c_bytes)?;
+impl JoinSplitProofSystem {
+    /// Generate proving and verifying keys for the join-split circuit
+    ///
+    /// WARNING: As with `TransferProofSystem::setup`, for testing only.
+    pub fn setup() -> Result<Self, ProofError> {
+        Self::setup_with_depth(TREE_DEPTH)
+    }
+
+    /// As `setup`, for a pool whose tree has depth `tree_depth`
+    pub fn setup_with_depth(tree_depth: usize) -> Result<Self, ProofError> {
+        check_depth(tree_depth).map_err(|e| ProofError::SetupError(e.to_string()))?;
+        let circuit = JoinSplitCircuit::default().with_tree_depth(tree_depth);
+        let (proving_key, verifying_key) =
+            Groth16::<Bn254>::circuit_specific_setup(circuit, &mut OsRng)
+                .map_err(|e| ProofError::SetupError(e.to_string()))?;
+        Ok(Self::from_parts(proving_key, verifying_key)?.with_tree_depth(tree_depth))
+    }
 
-        Ok(SolanaProof {
-            a: proof_a,
-            b: proof_b,
-            c: proof_c,
+    /// Load from serialized keys
+    pub fn from_keys(pk_bytes: &[u8], vk_bytes: &[u8]) -> Result<Self, ProofError> {
+        let proving_key = ProvingKey::deserialize_compressed(pk_bytes)
+            .map_err(|_| ProofError::InvalidProvingKey)?;
+        let verifying_key = VerifyingKey::deserialize_compressed(vk_bytes)
+            .map_err(|_| ProofError::InvalidVerifyingKey)?;
+        Self::from_parts(proving_key, verifying_key)
+    }
+
+    fn from_parts(
+        proving_key: ProvingKey<Bn254>,
+        verifying_key: VerifyingKey<Bn254>,
+    ) -> Result<Self, ProofError> {
+        let prepared_vk = Groth16::<Bn254>::process_vk(&verifying_key)
+            .map_err(|e| ProofError::SetupError(e.to_string()))?;
+        Ok(Self {
+            proving_key,
+            verifying_key,
+            prepared_vk,
+            tree_depth: TREE_DEPTH,
         })
     }
+
+    /// As `TransferProofSystem::with_tree_depth`
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Depth of the tree the keys were made for
+    pub fn tree_depth(&self) -> usize {
+        self.tree_depth
+    }
+
+    /// Generate a proof for a join-split circuit
+    pub fn prove(&self, circuit: JoinSplitCircuit) -> Result<SerializedProof, ProofError> {
+        check_tree_depth(self.tree_depth, circuit.tree_depth)?;
+        prove_circuit(&self.proving_key, circuit)
+    }
+
+    /// Verify a proof with public inputs
+    pub fn verify(&self, proof_bytes: &[u8], public_inputs: &[Fr]) -> Result<bool, ProofError> {
+        verify_proof(&self.prepared_vk, proof_bytes, public_inputs)
+    }
+
+    /// As `TransferVerifier::verify_batch`
+    pub fn verify_batch(&self, batch: &[(&[u8], &[Fr])]) -> Vec<usize> {
+        verify_proof_batch(&self.prepared_vk, batch)
+    }
+
+    /// Get the verifying key
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.verifying_key
+    }
+
+    /// Export verifying key in Solana-compatible format (big-endian)
+    pub fn export_solana_vk(&self) -> Result<SolanaVerifyingKey, ProofError> {
+        SolanaVerifyingKey::from_arkworks(&self.verifying_key)
+    }
+
+    /// Prove a join-split of `inputs` into `outputs`
+    ///
+    /// See `JoinSplitCircuit::join_split` for the witness requirements.
+    pub fn prove_join_split(
+        &self,
+        inputs: [Option<(&Note, &MerklePath)>; 2],
+        outputs: [&Note; 2],
+        merkle_root: Fr,
+        fee: u64,
+    ) -> Result<JoinSplitProof, ProofError> {
+        let circuit = JoinSplitCircuit::join_split(inputs, outputs, merkle_root, fee)?;
+        let public_inputs = circuit.public_inputs().ok_or(ProofError::InvalidWitness)?;
+        let proof = self.prove(circuit)?;
+        let solana_proof = Proof::<Bn254>::deserialize_compressed(proof.as_bytes())
+            .map_err(|e| ProofError::SerializationError(e.to_string()))
+            .and_then(|proof| SolanaProof::from_arkworks(&proof))?;
+        Ok(JoinSplitProof {
+            public_inputs,
+            proof,
+            solana_proof,
+        })
+    }
+}
+
+/// A proven join-split with its public inputs
+#[derive(Clone, Debug)]
+pub struct JoinSplitProof {
+    /// merkle_root, nullifiers, output_commitments, fee, asset_id
+    pub public_inputs: [Fr; JoinSplitCircuit::NUM_PUBLIC_INPUTS],
+    /// arkworks encoding, for `JoinSplitProofSystem::verify`
+    pub proof: SerializedProof,
+    /// groth16-solana encoding, for instruction data
+    pub solana_proof: SolanaProof,
+}
+
+impl JoinSplitProof {
+    /// Nullifiers as the program expects them
+    pub fn nullifier_bytes(&self) -> [[u8; 32]; 2] {
+        [field_to_bytes_be(&self.public_inputs[1]), field_to_bytes_be(&self.public_inputs[2])]
+    }
+
+    /// Output commitments as the program expects them
+    pub fn output_commitment_bytes(&self) -> [[u8; 32]; 2] {
+        [field_to_bytes_be(&self.public_inputs[3]), field_to_bytes_be(&self.public_inputs[4])]
+    }
+}
+
+/// Prove `circuit`, padding the compressed proof to `SerializedProof::SIZE`
+fn prove_circuit<C: ConstraintSynthesizer<Fr>>(
+    proving_key: &ProvingKey<Bn254>,
+    circuit: C,
+) -> Result<SerializedProof, ProofError> {
+    let proof = Groth16::<Bn254>::prove(proving_key, circuit, &mut OsRng)
+        .map_err(|e| ProofError::GenerationFailed(e.to_string()))?;
+    serialize_proof(&proof)
+}
+
+/// Compress `proof` into a `SerializedProof`
+fn serialize_proof(proof: &Proof<Bn254>) -> Result<SerializedProof, ProofError> {
+    // Serialize the proof
+    let mut bytes = Vec::new();
+    proof
+        .serialize_compressed(&mut bytes)
+        .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+
+    // Pad to expected size if needed
+    bytes.resize(SerializedProof::SIZE, 0);
+
+    SerializedProof::from_bytes(bytes)
+}
+
+/// Verify a proof produced by `prove_circuit`
+fn verify_proof(
+    prepared_vk: &PreparedVerifyingKey<Bn254>,
+    proof_bytes: &[u8],
+    public_inputs: &[Fr],
+) -> Result<bool, ProofError> {
+    let proof = Proof::deserialize_compressed(proof_bytes)
+        .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+
+    Groth16::<Bn254>::verify_with_processed_vk(prepared_vk, public_inputs, &proof)
+        .map_err(|e| ProofError::VerificationFailed(e.to_string()))
+}
+
+/// Verify many proofs at once, returning the indices of the invalid ones
+///
+/// With random `rᵢ`, the checks `e(Aᵢ, Bᵢ) = e(α, β)·e(Lᵢ, γ)·e(Cᵢ, δ)` are
+/// combined into `∏ e(rᵢ·Aᵢ, Bᵢ) = e(α, β)^Σrᵢ·e(Σ rᵢ·Lᵢ, γ)·e(Σ rᵢ·Cᵢ, δ)`,
+/// one Miller loop of `n + 2` pairs and a single final exponentiation. If
+/// that fails, each proof is checked on its own to find the culprits. A
+/// proof that does not decode, or has the wrong number of public inputs,
+/// is invalid rather than an error.
+fn verify_proof_batch(
+    prepared_vk: &PreparedVerifyingKey<Bn254>,
+    batch: &[(&[u8], &[Fr])],
+) -> Vec<usize> {
+    let mut invalid = Vec::new();
+    let mut decoded = Vec::with_capacity(batch.len());
+    for (i, (proof_bytes, public_inputs)) in batch.iter().enumerate() {
+        let proof = Proof::<Bn254>::deserialize_compressed(*proof_bytes).ok();
+        let inputs = Groth16::<Bn254>::prepare_inputs(prepared_vk, public_inputs).ok();
+        match proof.zip(inputs) {
+            Some((proof, inputs)) => decoded.push((i, proof, inputs)),
+            None => invalid.push(i),
+        }
+    }
+    if decoded.is_empty() || batch_holds(prepared_vk, &decoded) {
+        return invalid;
+    }
+
+    invalid.extend(decoded.iter().filter_map(|(i, proof, inputs)| {
+        let valid =
+            Groth16::<Bn254>::verify_proof_with_prepared_inputs(prepared_vk, proof, inputs);
+        (!valid.unwrap_or(false)).then_some(*i)
+    }));
+    invalid.sort_unstable();
+    invalid
+}
+
+/// The combined pairing check of `verify_proof_batch`
+fn batch_holds(
+    prepared_vk: &PreparedVerifyingKey<Bn254>,
+    decoded: &[(usize, Proof<Bn254>, G1Projective)],
+) -> bool {
+    let mut r_sum = Fr::zero();
+    let mut inputs_sum = G1Projective::zero();
+    let mut c_sum = G1Projective::zero();
+    let mut g1 = Vec::with_capacity(decoded.len() + 2);
+    let mut g2 = Vec::with_capacity(decoded.len() + 2);
+    for (_, proof, inputs) in decoded {
+        let r = Fr::rand(&mut OsRng);
+        r_sum += r;
+        inputs_sum += *inputs * r;
+        c_sum += proof.c * r;
+        g1.push((proof.a * r).into_affine());
+        g2.push(<Bn254 as Pairing>::G2Prepared::from(proof.b));
+    }
+    // The prepared γ and δ are negated, moving their pairings to the left
+    g1.push(inputs_sum.into_affine());
+    g2.push(prepared_vk.gamma_g2_neg_pc.clone());
+    g1.push(c_sum.into_affine());
+    g2.push(prepared_vk.delta_g2_neg_pc.clone());
+
+    let pairing = Bn254::multi_miller_loop(g1, g2);
+    Bn254::final_exponentiation(pairing)
+        .is_some_and(|out| out.0 == prepared_vk.alpha_g1_beta_g2.pow(r_sum.into_bigint()))
 }
 
 /// Solana-compatible verifying key format (big-endian)
@@ -286,6 +861,22 @@ pub struct SolanaVerifyingKey {
 }
 
 impl SolanaVerifyingKey {
+    /// Convert an arkworks verifying key
+    pub fn from_arkworks(vk: &VerifyingKey<Bn254>) -> Result<Self, ProofError> {
+        let mut ic = Vec::with_capacity(vk.gamma_abc_g1.len());
+        for point in &vk.gamma_abc_g1 {
+            ic.push(g1_le_to_be(&serialize_point(point)?)?);
+        }
+
+        Ok(Self {
+            alpha_g1: g1_le_to_be(&serialize_point(&vk.alpha_g1)?)?,
+            beta_g2: g2_le_to_be(&serialize_point(&vk.beta_g2)?)?,
+            gamma_g2: g2_le_to_be(&serialize_point(&vk.gamma_g2)?)?,
+            delta_g2: g2_le_to_be(&serialize_point(&vk.delta_g2)?)?,
+            ic,
+        })
+    }
+
     /// Export as Rust code for embedding in Solana program
     pub fn to_rust_code(&self) -> String {
         let mut code = String::new();
@@ -318,6 +909,25 @@ pub struct SolanaProof {
 }
 
 impl SolanaProof {
+    /// Convert an arkworks proof
+    ///
+    /// groth16-solana checks e(-A, B) * e(alpha, beta) * e(vk_x, gamma) * e(C, delta) = 1,
+    /// so A is negated here.
+    pub fn from_arkworks(proof: &Proof<Bn254>) -> Result<Self, ProofError> {
+        Ok(Self {
+            a: g1_le_to_be(&serialize_point(&(-proof.a))?)?,
+            b: g2_le_to_be(&serialize_point(&proof.b)?)?,
+            c: g1_le_to_be(&serialize_point(&proof.c)?)?,
+        })
+    }
+
+    /// Convert an arkworks-compressed proof, as `SerializedProof` holds it
+    pub fn from_compressed(proof_bytes: &[u8]) -> Result<Self, ProofError> {
+        let proof = Proof::<Bn254>::deserialize_compressed(proof_bytes)
+            .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+        Self::from_arkworks(&proof)
+    }
+
     /// Convert to raw bytes (256 bytes total)
     pub fn to_bytes(&self) -> [u8; 256] {
         let mut bytes = [0u8; 256];
@@ -328,6 +938,25 @@ impl SolanaProof {
     }
 }
 
+/// Serialize an affine point as x || y, each coordinate little-endian
+///
+/// The coordinates are written separately because arkworks' uncompressed
+/// point encoding stores the y-sign and infinity flags in the top bits of y,
+/// which the alt_bn128 syscalls would read as part of the coordinate. The
+/// point at infinity is all zeros, as in EIP-197.
+fn serialize_point<P: AffineRepr>(point: &P) -> Result<Vec<u8>, ProofError> {
+    let mut bytes = Vec::new();
+    match point.xy() {
+        Some((x, y)) => {
+            x.serialize_uncompressed(&mut bytes)
+                .and_then(|_| y.serialize_uncompressed(&mut bytes))
+                .map_err(|e| ProofError::SerializationError(e.to_string()))?;
+        }
+        None => bytes.resize(point.uncompressed_size(), 0),
+    }
+    Ok(bytes)
+}
+
 /// Convert G1 point from arkworks little-endian to big-endian
 fn g1_le_to_be(le_bytes: &[u8]) -> Result<[u8; 64], ProofError> {
     if le_bytes.len() != 64 {
@@ -346,6 +975,19 @@ fn g1_le_to_be(le_bytes: &[u8]) -> Result<[u8; 64], ProofError> {
 }
 
 /// Convert G2 point from arkworks little-endian to big-endian
+///
+/// arkworks writes each Fq2 coordinate as c0 || c1, both little-endian.
+/// groth16-solana (and the alt_bn128 syscalls, following EIP-197) expect
+/// c1 || c0, both big-endian. Reversing each 64-byte coordinate as a whole
+/// does both at once:
+///
+/// ```text
+/// input (LE)              output (BE)
+/// [  0.. 32] x.c0         [  0.. 32] x.c1
+/// [ 32.. 64] x.c1         [ 32.. 64] x.c0
+/// [ 64.. 96] y.c0         [ 64.. 96] y.c1
+/// [ 96..128] y.c1         [ 96..128] y.c0
+/// ```
 fn g2_le_to_be(le_bytes: &[u8]) -> Result<[u8; 128], ProofError> {
     if le_bytes.len() != 128 {
         return Err(ProofError::SerializationError(
@@ -353,18 +995,12 @@ fn g2_le_to_be(le_bytes: &[u8]) -> Result<[u8; 128], ProofError> {
         ));
     }
     let mut be = [0u8; 128];
-    // x.c0 (32 bytes)
-    be[0..32].copy_from_slice(&le_bytes[0..32]);
-    be[0..32].reverse();
-    // x.c1 (32 bytes)
-    be[32..64].copy_from_slice(&le_bytes[32..64]);
-    be[32..64].reverse();
-    // y.c0 (32 bytes)
-    be[64..96].copy_from_slice(&le_bytes[64..96]);
-    be[64..96].reverse();
-    // y.c1 (32 bytes)
-    be[96..128].copy_from_slice(&le_bytes[96..128]);
-    be[96..128].reverse();
+    // x = (c0, c1) -> c1 BE || c0 BE
+    be[0..64].copy_from_slice(&le_bytes[0..64]);
+    be[0..64].reverse();
+    // y = (c0, c1) -> c1 BE || c0 BE
+    be[64..128].copy_from_slice(&le_bytes[64..128]);
+    be[64..128].reverse();
     Ok(be)
 }
 
@@ -437,6 +1073,203 @@ pub fn verify_transfer_proof(proof: &[u8], public_inputs_json: &str) -> Result<b
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_transfer_setup_prove_verify() {
+        use ark_ff::UniformRand;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+
+        let system = TransferProofSystem::setup().unwrap();
+
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+
+        let output_blinding = Fr::rand(&mut OsRng);
+        let spend = system
+            .prove_spend(&note, &path, tree.root(), output_blinding, 10)
+            .unwrap();
+        assert!(system.verify(spend.proof.as_bytes(), &spend.public_inputs).unwrap());
+        let inputs = TransferPublicInputs::from_onchain_bytes(&spend.onchain_public_inputs());
+        assert_eq!(inputs.unwrap().to_fields(), spend.public_inputs);
+
+        // The program's encoding, checked as the program checks it
+        let solana_vk = system.export_solana_vk().unwrap();
+        let onchain_inputs = spend.onchain_public_inputs();
+        let circuit =
+            TransferCircuit::spend(&note, &path, tree.root(), output_blinding, 10).unwrap();
+        let solana_proof = system.prove_for_solana(circuit).unwrap().to_bytes();
+        assert!(eip197::verify(&solana_vk, &solana_proof, &onchain_inputs));
+        assert!(eip197::verify(&solana_vk, &spend.solana_proof.to_bytes(), &onchain_inputs));
+        let mut other_fee = onchain_inputs;
+        other_fee[4] = field_to_bytes_be(&Fr::from(20u64));
+        assert!(!eip197::verify(&solana_vk, &solana_proof, &other_fee));
+        // arkworks' compressed encoding is not the program's
+        let mut serialized = [0u8; 256];
+        serialized.copy_from_slice(spend.proof.as_bytes());
+        assert!(!eip197::verify(&solana_vk, &serialized, &onchain_inputs));
+
+        // A verifier needs only the verifying key
+        let vk_bytes = system.serialize_verifying_key().unwrap();
+        let verifier = TransferVerifier::from_vk_bytes(&vk_bytes).unwrap();
+        assert!(verifier.verify(spend.proof.as_bytes(), &spend.public_inputs).unwrap());
+        assert_eq!(
+            verifier.export_solana_vk().unwrap().to_rust_code(),
+            system.export_solana_vk().unwrap().to_rust_code()
+        );
+        assert!(matches!(
+            TransferVerifier::from_vk_bytes(&vk_bytes[1..]),
+            Err(ProofError::InvalidVerifyingKey)
+        ));
+
+        let mut wrong = spend.public_inputs;
+        wrong[1] = Fr::rand(&mut OsRng);
+        assert!(!system.verify(spend.proof.as_bytes(), &wrong).unwrap());
+
+        // A note of one asset cannot be spent in another asset's pool
+        let mut other_asset = spend.public_inputs;
+        other_asset[3] = Fr::from(1u64);
+        assert!(!system.verify(spend.proof.as_bytes(), &other_asset).unwrap());
+
+        // Nor can a relayer raise the fee
+        let mut greedy = spend.public_inputs;
+        greedy[4] = Fr::from(20u64);
+        assert!(!system.verify(spend.proof.as_bytes(), &greedy).unwrap());
+    }
+
+    #[test]
+    fn test_transfer_verify_batch() {
+        use ark_ff::UniformRand;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+
+        let system = TransferProofSystem::setup().unwrap();
+        let mut tree = PoseidonMerkleTree::new();
+        let spends: Vec<SpendProof> = (0..3u64)
+            .map(|i| {
+                let mut note = Note::new_random(1000 + i, Fr::from(0u64), Fr::rand(&mut OsRng));
+                let index = tree.insert(note.commitment()).unwrap();
+                note.set_leaf_index(index);
+                let path = tree.generate_proof(index).unwrap();
+                system.prove_spend(&note, &path, tree.root(), Fr::rand(&mut OsRng), 0).unwrap()
+            })
+            .collect();
+        let batch: Vec<(&[u8], &[Fr])> = spends
+            .iter()
+            .map(|spend| (spend.proof.as_bytes(), &spend.public_inputs[..]))
+            .collect();
+        assert!(system.verify_batch(&batch).is_empty());
+        assert!(system.verify_batch(&[]).is_empty());
+
+        // Swapped public inputs, a truncated proof and too few inputs
+        let mut bad = batch.clone();
+        bad[0].1 = &spends[1].public_inputs[..];
+        bad.push((&batch[2].0[..64], batch[2].1));
+        bad.push((batch[2].0, &batch[2].1[..2]));
+        bad.push(batch[2]);
+        assert_eq!(system.verify_batch(&bad), vec![0, 3, 4]);
+    }
+
+    #[test]
+    fn test_unshield_setup_prove_verify() {
+        use ark_ff::UniformRand;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+
+        let system = UnshieldProofSystem::setup().unwrap();
+        assert_eq!(
+            system.export_solana_vk().unwrap().ic.len(),
+            UnshieldCircuit::NUM_PUBLIC_INPUTS + 1
+        );
+
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+
+        let withdrawal = system
+            .prove_withdrawal(&note, &path, tree.root(), &[9u8; 32], 10)
+            .unwrap();
+        let proof = withdrawal.proof.as_bytes();
+        assert!(system.verify(proof, &withdrawal.public_inputs).unwrap());
+
+        // Another recipient, fee or asset does not verify
+        let mut redirected = withdrawal.public_inputs;
+        redirected[2] = recipient_hash(&[8u8; 32]);
+        assert!(!system.verify(proof, &redirected).unwrap());
+        let mut greedy = withdrawal.public_inputs;
+        greedy[4] = Fr::from(20u64);
+        assert!(!system.verify(proof, &greedy).unwrap());
+        let mut other_asset = withdrawal.public_inputs;
+        other_asset[5] = asset_id_for_mint(&[3u8; 32]);
+        assert!(!system.verify(proof, &other_asset).unwrap());
+    }
+
+    #[test]
+    fn test_unshield_deeper_pool() {
+        use ark_ff::UniformRand;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+
+        let system = UnshieldProofSystem::setup_with_depth(24).unwrap();
+        assert_eq!(system.tree_depth(), 24);
+        assert!(UnshieldProofSystem::setup_with_depth(21).is_err());
+
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::with_depth(24).unwrap();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+        let withdrawal = system
+            .prove_withdrawal(&note, &path, tree.root(), &[9u8; 32], 10)
+            .unwrap();
+        assert!(system.verify(withdrawal.proof.as_bytes(), &withdrawal.public_inputs).unwrap());
+
+        // A path from a depth-20 pool does not fit these keys
+        let mut shallow = PoseidonMerkleTree::new();
+        shallow.insert(note.commitment()).unwrap();
+        let path = shallow.generate_proof(0).unwrap();
+        assert!(matches!(
+            system.prove_withdrawal(&note, &path, shallow.root(), &[9u8; 32], 10),
+            Err(ProofError::TreeDepthMismatch { expected: 24, actual: 20 })
+        ));
+    }
+
+    #[test]
+    fn test_join_split_setup_prove_verify() {
+        use ark_ff::UniformRand;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+
+        let system = JoinSplitProofSystem::setup().unwrap();
+        assert_eq!(
+            system.export_solana_vk().unwrap().ic.len(),
+            JoinSplitCircuit::NUM_PUBLIC_INPUTS + 1
+        );
+
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+        let payment = Note::new_random(600, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let change = Note::new_random(390, Fr::from(0u64), Fr::rand(&mut OsRng));
+
+        let join_split = system
+            .prove_join_split([Some((&note, &path)), None], [&payment, &change], tree.root(), 10)
+            .unwrap();
+        let proof = join_split.proof.as_bytes();
+        assert!(system.verify(proof, &join_split.public_inputs).unwrap());
+        assert_eq!(
+            join_split.output_commitment_bytes()[0],
+            field_to_bytes_be(&payment.commitment())
+        );
+
+        // The fee is bound: a relayer cannot raise it
+        let mut greedy = join_split.public_inputs;
+        greedy[5] = Fr::from(20u64);
+        assert!(!system.verify(proof, &greedy).unwrap());
+    }
+
     #[test]
     #[allow(deprecated)]
     fn test_legacy_proof_generation() {
@@ -467,4 +1300,217 @@ mod tests {
         let proof2 = generate_transfer_proof(&witness).unwrap();
         assert_eq!(proof1, proof2);
     }
+
+    /// Decoder mirroring groth16-solana / EIP-197: coordinates are big-endian,
+    /// Fq2 elements are imaginary part (c1) first.
+    mod eip197 {
+        use ark_bn254::{Bn254, Fq, Fq12, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+        use ark_ec::pairing::Pairing;
+        use ark_ec::CurveGroup;
+        use ark_ff::{One, PrimeField};
+
+        use super::super::SolanaVerifyingKey;
+
+        fn fq(be: &[u8]) -> Fq {
+            Fq::from_be_bytes_mod_order(be)
+        }
+
+        pub fn g1(be: &[u8; 64]) -> G1Affine {
+            G1Affine::new_unchecked(fq(&be[0..32]), fq(&be[32..64]))
+        }
+
+        pub fn g2(be: &[u8; 128]) -> G2Affine {
+            let x = Fq2::new(fq(&be[32..64]), fq(&be[0..32]));
+            let y = Fq2::new(fq(&be[96..128]), fq(&be[64..96]));
+            G2Affine::new_unchecked(x, y)
+        }
+
+        /// The check `verify_groth16_transfer` has groth16-solana make, on
+        /// the program's A | B | C proof bytes:
+        /// e(-A, B) * e(alpha, beta) * e(vk_x, gamma) * e(C, delta) = 1
+        pub fn verify(vk: &SolanaVerifyingKey, proof: &[u8; 256], inputs: &[[u8; 32]]) -> bool {
+            let vk_x = inputs
+                .iter()
+                .zip(&vk.ic[1..])
+                .fold(G1Projective::from(g1(&vk.ic[0])), |acc, (input, ic)| {
+                    acc + g1(ic) * Fr::from_be_bytes_mod_order(input)
+                });
+            let a = g1(proof[0..64].try_into().unwrap());
+            let b = g2(proof[64..192].try_into().unwrap());
+            let c = g1(proof[192..256].try_into().unwrap());
+            // The alt_bn128 syscalls reject points off the curve
+            if !(a.is_on_curve() && b.is_on_curve() && c.is_on_curve()) {
+                return false;
+            }
+            let product = Bn254::multi_pairing(
+                [a, g1(&vk.alpha_g1), vk_x.into_affine(), c],
+                [b, g2(&vk.beta_g2), g2(&vk.gamma_g2), g2(&vk.delta_g2)],
+            );
+            product.0 == Fq12::one()
+        }
+    }
+
+    /// x * y = z with z public
+    #[derive(Clone)]
+    struct MulCircuit {
+        x: Option<Fr>,
+        y: Option<Fr>,
+        z: Option<Fr>,
+    }
+
+    impl ark_relations::r1cs::ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(
+            self,
+            cs: ark_relations::r1cs::ConstraintSystemRef<Fr>,
+        ) -> Result<(), ark_relations::r1cs::SynthesisError> {
+            use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+            use ark_relations::r1cs::SynthesisError;
+
+            let x = FpVar::new_witness(cs.clone(), || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = FpVar::new_witness(cs.clone(), || self.y.ok_or(SynthesisError::AssignmentMissing))?;
+            let z = FpVar::new_input(cs, || self.z.ok_or(SynthesisError::AssignmentMissing))?;
+            (x * y).enforce_equal(&z)
+        }
+    }
+
+    #[test]
+    fn test_g2_export_fixture() {
+        use ark_bn254::G2Affine;
+        use ark_ec::AffineRepr;
+
+        // EIP-197 encoding of the BN254 G2 generator: x.c1, x.c0, y.c1, y.c0
+        let expected = hex::decode(concat!(
+            "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+            "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+            "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+            "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+        ))
+        .unwrap();
+
+        let exported = g2_le_to_be(&serialize_point(&G2Affine::generator()).unwrap()).unwrap();
+        assert_eq!(exported.to_vec(), expected);
+    }
+
+    #[test]
+    fn test_field_to_bytes_be() {
+        let mut expected = [0u8; 32];
+        expected[30] = 0x01;
+        expected[31] = 0x02;
+        assert_eq!(field_to_bytes_be(&Fr::from(0x0102u64)), expected);
+    }
+
+    #[test]
+    fn test_transfer_public_inputs_onchain_bytes() {
+        let inputs = TransferPublicInputs {
+            merkle_root: Fr::from(1u64),
+            nullifier: -Fr::from(2u64),
+            new_commitment: Fr::from(3u64),
+            asset_id: Fr::from(0u64),
+            fee: 0x0102,
+        };
+        let bytes = inputs.to_onchain_bytes();
+        assert_eq!(bytes[1], field_to_bytes_be(&-Fr::from(2u64)));
+        assert_eq!(bytes[4][..30], [0u8; 30]);
+        assert_eq!(bytes[4][30..], [1, 2]);
+        assert_eq!(TransferPublicInputs::from_onchain_bytes(&bytes).unwrap(), inputs);
+        assert_eq!(TransferPublicInputs::from_fields(&inputs.to_fields()), Some(inputs));
+
+        // Field elements at least the modulus, and fees past a u64
+        let mut non_canonical = bytes;
+        non_canonical[0] = [0xff; 32];
+        assert!(TransferPublicInputs::from_onchain_bytes(&non_canonical).is_err());
+        let mut large_fee = bytes;
+        large_fee[4][23] = 1;
+        assert!(TransferPublicInputs::from_onchain_bytes(&large_fee).is_err());
+    }
+
+    #[test]
+    fn test_g1_export_clears_flags() {
+        use ark_bn254::G1Affine;
+
+        // -G1 = (1, p - 2); arkworks flags this y as negative in its own encoding
+        let exported = g1_le_to_be(&serialize_point(&(-G1Affine::generator())).unwrap()).unwrap();
+        let mut expected = [0u8; 64];
+        expected[31] = 1;
+        expected[32..].copy_from_slice(
+            &hex::decode("30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45").unwrap(),
+        );
+        assert_eq!(exported, expected);
+    }
+
+    #[test]
+    fn test_g2_export_limb_order() {
+        use ark_bn254::G2Projective;
+        use ark_ec::CurveGroup;
+        use ark_ff::UniformRand;
+
+        for _ in 0..8 {
+            let point = G2Projective::rand(&mut OsRng).into_affine();
+            let exported = g2_le_to_be(&serialize_point(&point).unwrap()).unwrap();
+            assert_eq!(eip197::g2(&exported), point);
+
+            // Reading the limbs c0-first yields a different (off-curve) point
+            let mut swapped = exported;
+            swapped[0..64].rotate_left(32);
+            swapped[64..128].rotate_left(32);
+            let misread = eip197::g2(&swapped);
+            assert_ne!(misread, point);
+            assert!(!misread.is_on_curve());
+        }
+    }
+
+    #[test]
+    fn test_exported_proof_satisfies_solana_pairing_check() {
+        use ark_bn254::{Fq12, G1Projective};
+        use ark_ec::pairing::Pairing;
+        use ark_ec::CurveGroup;
+        use ark_ff::One;
+
+        let (x, y) = (Fr::from(3u64), Fr::from(11u64));
+        let z = x * y;
+        let setup = MulCircuit { x: None, y: None, z: None };
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(setup, &mut OsRng).unwrap();
+        let proof = Groth16::<Bn254>::prove(
+            &pk,
+            MulCircuit { x: Some(x), y: Some(y), z: Some(z) },
+            &mut OsRng,
+        )
+        .unwrap();
+
+        let solana_vk = SolanaVerifyingKey::from_arkworks(&vk).unwrap();
+        let solana_proof = SolanaProof::from_arkworks(&proof).unwrap();
+
+        // Same equation groth16-solana evaluates, on the exported bytes only
+        let check = |proof_b: &[u8; 128]| {
+            let vk_x = (G1Projective::from(eip197::g1(&solana_vk.ic[0]))
+                + eip197::g1(&solana_vk.ic[1]) * z)
+                .into_affine();
+            let b = eip197::g2(proof_b);
+            if !b.is_on_curve() {
+                return false;
+            }
+            let product = Bn254::multi_pairing(
+                [
+                    eip197::g1(&solana_proof.a),
+                    eip197::g1(&solana_vk.alpha_g1),
+                    vk_x,
+                    eip197::g1(&solana_proof.c),
+                ],
+                [
+                    b,
+                    eip197::g2(&solana_vk.beta_g2),
+                    eip197::g2(&solana_vk.gamma_g2),
+                    eip197::g2(&solana_vk.delta_g2),
+                ],
+            );
+            product.0 == Fq12::one()
+        };
+
+        assert!(check(&solana_proof.b));
+
+        // Swapping x.c0/x.c1 in proof.b breaks verification
+        let mut swapped = solana_proof.b;
+        swapped[0..64].rotate_left(32);
+        assert!(!check(&swapped));
+    }
 }
@@ -0,0 +1,297 @@
+//! G1 MSMs on a GPU through wgpu (feature `gpu-msm`)
+//!
+//! `msm_g1.wgsl` runs a 4-bit-window bucket pass for every window and chunk
+//! of points in parallel; the CPU adds up the per-thread sums and combines
+//! the windows, which is cheap next to the buckets. wgpu picks Vulkan, Metal,
+//! DX12 or GL, whichever the machine has. The one G2 MSM, `b_g2_query`, stays
+//! on `CpuMsm`, as does any G1 MSM the GPU fails on.
+
+use std::sync::mpsc;
+
+use ark_bn254::{Fq, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{AffineRepr, Group};
+use ark_ff::{BigInt, BigInteger, Zero};
+use wgpu::util::DeviceExt;
+
+use super::{CpuMsm, MsmBackend, Scalar};
+use crate::proof::ProofError;
+
+/// u32 words per base: x and y, 16 limbs each
+const BASE_WORDS: usize = 32;
+/// u32 words per Jacobian sum
+const SUM_WORDS: usize = 48;
+/// Scalar bits per window, as in the shader
+const WINDOW_BITS: usize = 4;
+const WINDOWS: usize = 64;
+/// Most threads per window; more only makes the CPU's share grow
+const MAX_CHUNKS: usize = 256;
+/// Fewest points per thread, so the bucket reduction stays the smaller part
+const MIN_CHUNK: usize = 32;
+const WORKGROUP_SIZE: usize = 64;
+
+/// `MsmBackend` running G1 MSMs on the first GPU wgpu finds
+pub struct GpuMsm {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    adapter_name: String,
+    /// Most points the device takes in one dispatch
+    max_points: usize,
+}
+
+impl GpuMsm {
+    /// Open the default high-performance adapter and build the MSM pipeline
+    ///
+    /// Fails if there is no adapter or it cannot run compute shaders.
+    pub fn new() -> Result<Self, ProofError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or_else(|| ProofError::SetupError("no GPU adapter found".into()))?;
+        let downlevel = adapter.get_downlevel_capabilities();
+        if !downlevel.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            return Err(ProofError::SetupError(format!(
+                "{} cannot run compute shaders",
+                adapter.get_info().name
+            )));
+        }
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("veil msm"),
+                features: wgpu::Features::empty(),
+                limits: adapter.limits(),
+            },
+            None,
+        ))
+        .map_err(|e| ProofError::SetupError(format!("GPU device: {e}")))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("msm_g1"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("msm_g1.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("msm_g1"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+
+        let limits = device.limits();
+        let max_bytes = limits.max_buffer_size.min(limits.max_storage_buffer_binding_size.into());
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            adapter_name: adapter.get_info().name,
+            max_points: max_bytes as usize / (BASE_WORDS * 4),
+        })
+    }
+
+    /// Name of the adapter in use, e.g. for logs
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_name
+    }
+
+    /// `msm_g1` on the GPU, without the CPU fallback
+    fn try_msm_g1(&self, bases: &[G1Affine], scalars: &[Scalar]) -> Result<G1Projective, String> {
+        // Zero terms would need an infinity flag in the shader; drop them
+        let (bases, scalars): (Vec<G1Affine>, Vec<Scalar>) = bases
+            .iter()
+            .zip(scalars)
+            .filter(|(base, scalar)| !base.is_zero() && !scalar.is_zero())
+            .map(|(base, scalar)| (*base, *scalar))
+            .unzip();
+        let mut total = G1Projective::zero();
+        for (bases, scalars) in bases.chunks(self.max_points).zip(scalars.chunks(self.max_points)) {
+            total += self.dispatch(bases, scalars)?;
+        }
+        Ok(total)
+    }
+
+    /// One dispatch over at most `max_points` nonzero terms
+    fn dispatch(&self, bases: &[G1Affine], scalars: &[Scalar]) -> Result<G1Projective, String> {
+        let n = bases.len();
+        let chunk = ((n + MAX_CHUNKS - 1) / MAX_CHUNKS).max(MIN_CHUNK);
+        let chunks = (n + chunk - 1) / chunk;
+        let threads = WINDOWS * chunks;
+
+        let mut base_words = Vec::with_capacity(n * BASE_WORDS);
+        for base in bases {
+            push_fq(&mut base_words, &base.x);
+            push_fq(&mut base_words, &base.y);
+        }
+        let scalar_words: Vec<u32> = scalars
+            .iter()
+            .flat_map(|scalar| scalar.0)
+            .flat_map(|limb| [limb as u32, (limb >> 32) as u32])
+            .collect();
+        let params = [n as u32, chunk as u32, chunks as u32, 0];
+
+        let storage = |label, words: &[u32]| {
+            self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(words),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        let bases_buf = storage("msm bases", &base_words);
+        let scalars_buf = storage("msm scalars", &scalar_words);
+        let sums_size = (threads * SUM_WORDS * 4) as u64;
+        let sums_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("msm sums"),
+            size: sums_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let params_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("msm params"),
+            contents: bytemuck::cast_slice(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("msm readback"),
+            size: sums_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("msm"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                entry(0, &bases_buf),
+                entry(1, &scalars_buf),
+                entry(2, &sums_buf),
+                entry(3, &params_buf),
+            ],
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("msm") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let workgroups = (threads + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+            pass.dispatch_workgroups(workgroups as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&sums_buf, 0, &readback, 0, sums_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        let sums: Vec<u32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        readback.unmap();
+
+        // Σ_w 2^(4w)·Σ_k sums[w][k], the top window first
+        let mut acc = G1Projective::zero();
+        for window in sums.chunks(chunks * SUM_WORDS).rev() {
+            for _ in 0..WINDOW_BITS {
+                acc.double_in_place();
+            }
+            for sum in window.chunks(SUM_WORDS) {
+                acc += G1Projective::new_unchecked(
+                    read_fq(&sum[..16]),
+                    read_fq(&sum[16..32]),
+                    read_fq(&sum[32..]),
+                );
+            }
+        }
+        Ok(acc)
+    }
+}
+
+impl MsmBackend for GpuMsm {
+    fn msm_g1(&self, bases: &[G1Affine], scalars: &[Scalar]) -> G1Projective {
+        self.try_msm_g1(bases, scalars)
+            .unwrap_or_else(|_| CpuMsm.msm_g1(bases, scalars))
+    }
+
+    fn msm_g2(&self, bases: &[G2Affine], scalars: &[Scalar]) -> G2Projective {
+        CpuMsm.msm_g2(bases, scalars)
+    }
+}
+
+fn entry(binding: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding,
+        resource: buffer.as_entire_binding(),
+    }
+}
+
+/// Append the Montgomery form of `value` as 16-bit limbs
+fn push_fq(words: &mut Vec<u32>, value: &Fq) {
+    for limb in value.0 .0 {
+        words.extend((0..4).map(|i| ((limb >> (16 * i)) & 0xffff) as u32));
+    }
+}
+
+fn read_fq(words: &[u32]) -> Fq {
+    let mut limbs = [0u64; 4];
+    for (limb, words) in limbs.iter_mut().zip(words.chunks(4)) {
+        *limb = words
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &word)| acc | (u64::from(word) << (16 * i)));
+    }
+    Fq::new_unchecked(BigInt::new(limbs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+    use ark_ec::CurveGroup;
+    use ark_ff::{PrimeField, UniformRand};
+    use rand::rngs::OsRng;
+
+    fn gpu() -> Option<GpuMsm> {
+        match GpuMsm::new() {
+            Ok(gpu) => Some(gpu),
+            Err(e) => {
+                eprintln!("skipping: {e}");
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_gpu_msm_matches_cpu() {
+        let Some(gpu) = gpu() else { return };
+        for n in [1, 37, 100] {
+            let bases: Vec<G1Affine> = (0..n).map(|_| G1Affine::rand(&mut OsRng)).collect();
+            let scalars: Vec<Scalar> =
+                (0..n).map(|_| Fr::rand(&mut OsRng).into_bigint()).collect();
+            let expected = CpuMsm.msm_g1(&bases, &scalars);
+            assert_eq!(gpu.try_msm_g1(&bases, &scalars).unwrap(), expected, "n = {n}");
+        }
+    }
+
+    #[test]
+    fn test_gpu_msm_edge_cases() {
+        let Some(gpu) = gpu() else { return };
+        let g = G1Affine::generator();
+        let minus_one = (-Fr::from(1u64)).into_bigint();
+        let two = Fr::from(2u64).into_bigint();
+        // Repeated bases land in the same bucket: doubling and cancelling
+        let bases = [g, g, g, G1Affine::zero(), g];
+        let scalars = [two, two, minus_one, two, Fr::from(0u64).into_bigint()];
+        let expected = CpuMsm.msm_g1(&bases, &scalars);
+        assert_eq!(expected.into_affine(), (g * Fr::from(3u64)).into_affine());
+        assert_eq!(gpu.try_msm_g1(&bases, &scalars).unwrap(), expected);
+
+        let cancel = [g, g];
+        let scalars = [Fr::from(5u64).into_bigint(), (-Fr::from(5u64)).into_bigint()];
+        assert!(gpu.try_msm_g1(&cancel, &scalars).unwrap().is_zero());
+        assert!(gpu.try_msm_g1(&[], &[]).unwrap().is_zero());
+    }
+}
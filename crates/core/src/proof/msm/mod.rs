@@ -0,0 +1,162 @@
+//! Pluggable multi-scalar multiplication (feature `msm-backend`)
+//!
+//! Most of Groth16 proving time goes to five MSMs over the proving key: the
+//! A, B (in G1 and G2), L and H queries. `prove_with_msm` is the ark-groth16
+//! prover with those MSMs routed through an `MsmBackend`, so an accelerator
+//! (e.g. ICICLE's CUDA or Metal kernels) can take them over by implementing
+//! the trait. The witness map stays on the CPU. `CpuMsm` is the default and
+//! matches `Groth16::prove`, which uses every core through rayon; `GpuMsm`
+//! (feature `gpu-msm`) runs the G1 MSMs on a GPU through wgpu.
+
+#[cfg(feature = "gpu-msm")]
+mod gpu;
+#[cfg(feature = "gpu-msm")]
+pub use gpu::GpuMsm;
+
+use ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{CurveGroup, VariableBaseMSM};
+use ark_ff::{PrimeField, UniformRand};
+use ark_groth16::r1cs_to_qap::{LibsnarkReduction, R1CSToQAP};
+use ark_groth16::{Proof, ProvingKey};
+use ark_poly::GeneralEvaluationDomain;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, OptimizationGoal};
+use rand::RngCore;
+
+use super::ProofError;
+
+/// Scalars in the form MSM kernels take: canonical, not Montgomery
+pub type Scalar = <Fr as PrimeField>::BigInt;
+
+/// Computes `Σ scalars[i]·bases[i]`; `bases` and `scalars` have equal length
+pub trait MsmBackend: Send + Sync {
+    fn msm_g1(&self, bases: &[G1Affine], scalars: &[Scalar]) -> G1Projective;
+
+    fn msm_g2(&self, bases: &[G2Affine], scalars: &[Scalar]) -> G2Projective;
+}
+
+/// arkworks' Pippenger implementation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuMsm;
+
+impl MsmBackend for CpuMsm {
+    fn msm_g1(&self, bases: &[G1Affine], scalars: &[Scalar]) -> G1Projective {
+        G1Projective::msm_bigint(bases, scalars)
+    }
+
+    fn msm_g2(&self, bases: &[G2Affine], scalars: &[Scalar]) -> G2Projective {
+        G2Projective::msm_bigint(bases, scalars)
+    }
+}
+
+/// Prove `circuit` as `Groth16::prove` does, with its MSMs on `msm`
+pub fn prove_with_msm<C: ConstraintSynthesizer<Fr>, R: RngCore>(
+    pk: &ProvingKey<Bn254>,
+    circuit: C,
+    msm: &dyn MsmBackend,
+    rng: &mut R,
+) -> Result<Proof<Bn254>, ProofError> {
+    let failed =
+        |e: ark_relations::r1cs::SynthesisError| ProofError::GenerationFailed(e.to_string());
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    circuit.generate_constraints(cs.clone()).map_err(failed)?;
+    cs.finalize();
+    let h = LibsnarkReduction::witness_map::<Fr, GeneralEvaluationDomain<Fr>>(cs.clone())
+        .map_err(failed)?;
+
+    let prover = cs.borrow().ok_or(ProofError::InvalidWitness)?;
+    // The constant 1 is folded into the queries' first entries
+    let assignment: Vec<Scalar> = prover.instance_assignment[1..]
+        .iter()
+        .chain(&prover.witness_assignment)
+        .map(|value| value.into_bigint())
+        .collect();
+    let aux = &assignment[prover.instance_assignment.len() - 1..];
+    let h: Vec<Scalar> = h.iter().map(|value| value.into_bigint()).collect();
+    let (r, s) = (Fr::rand(rng), Fr::rand(rng));
+
+    let h_acc = msm.msm_g1(&pk.h_query, &h);
+    let l_acc = msm.msm_g1(&pk.l_query, aux);
+
+    // A = α + Σ aᵢ·Aᵢ + r·δ
+    let a = pk.vk.alpha_g1
+        + pk.a_query[0]
+        + msm.msm_g1(&pk.a_query[1..], &assignment)
+        + pk.delta_g1 * r;
+    // B = β + Σ aᵢ·Bᵢ + s·δ, in G1 for C and in G2 for the proof
+    let b_g1 = pk.beta_g1
+        + pk.b_g1_query[0]
+        + msm.msm_g1(&pk.b_g1_query[1..], &assignment)
+        + pk.delta_g1 * s;
+    let b_g2 = pk.vk.beta_g2
+        + pk.b_g2_query[0]
+        + msm.msm_g2(&pk.b_g2_query[1..], &assignment)
+        + pk.vk.delta_g2 * s;
+    // C = s·A + r·B - r·s·δ + L + H
+    let c = a * s + b_g1 * r - pk.delta_g1 * (r * s) + l_acc + h_acc;
+
+    Ok(Proof {
+        a: a.into_affine(),
+        b: b_g2.into_affine(),
+        c: c.into_affine(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ark_groth16::Groth16;
+    use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+    use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+    use ark_snark::SNARK;
+    use rand::rngs::OsRng;
+
+    /// x * y = z with z public
+    struct MulCircuit {
+        x: Fr,
+        y: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let x = FpVar::new_witness(cs.clone(), || Ok(self.x))?;
+            let y = FpVar::new_witness(cs.clone(), || Ok(self.y))?;
+            let z = FpVar::new_input(cs, || Ok(self.x * self.y))?;
+            (x * y).enforce_equal(&z)
+        }
+    }
+
+    /// `CpuMsm`, counting the MSMs it is handed
+    #[derive(Default)]
+    struct CountingMsm(AtomicUsize);
+
+    impl MsmBackend for CountingMsm {
+        fn msm_g1(&self, bases: &[G1Affine], scalars: &[Scalar]) -> G1Projective {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            CpuMsm.msm_g1(bases, scalars)
+        }
+
+        fn msm_g2(&self, bases: &[G2Affine], scalars: &[Scalar]) -> G2Projective {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            CpuMsm.msm_g2(bases, scalars)
+        }
+    }
+
+    #[test]
+    fn test_prove_with_msm_verifies() {
+        let circuit = || MulCircuit {
+            x: Fr::from(3u64),
+            y: Fr::from(5u64),
+        };
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(circuit(), &mut OsRng).unwrap();
+
+        let msm = CountingMsm::default();
+        let proof = prove_with_msm(&pk, circuit(), &msm, &mut OsRng).unwrap();
+        assert_eq!(msm.0.load(Ordering::Relaxed), 5);
+
+        assert!(Groth16::<Bn254>::verify(&vk, &[Fr::from(15u64)], &proof).unwrap());
+        assert!(!Groth16::<Bn254>::verify(&vk, &[Fr::from(16u64)], &proof).unwrap());
+    }
+}
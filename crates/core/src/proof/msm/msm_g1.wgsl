@@ -0,0 +1,296 @@
+// BN254 G1 multi-scalar multiplication, one bucket pass per thread
+//
+// Thread `id` takes window `id / chunks` (4 scalar bits) of points
+// `[k * chunk, (k + 1) * chunk)`, k = `id % chunks`, sorts them into 15
+// buckets by digit and writes Σ d·bucket[d] in Jacobian coordinates. The
+// host adds the sums up per window and combines the windows.
+//
+// Field elements are 16 limbs of 16 bits, least significant first, in
+// Montgomery form with R = 2^256 as arkworks keeps them, so the host can
+// copy them in and out without converting.
+
+const LIMBS: u32 = 16u;
+const WINDOWS: u32 = 64u;
+const BUCKETS: u32 = 15u;
+
+struct Params {
+    n: u32,
+    chunk: u32,
+    chunks: u32,
+    _pad: u32,
+}
+
+struct Jac {
+    x: array<u32, 16>,
+    y: array<u32, 16>,
+    z: array<u32, 16>,
+}
+
+// Affine bases, x then y
+@group(0) @binding(0) var<storage, read> bases: array<u32>;
+// Canonical scalars, 8 words of 32 bits each, least significant first
+@group(0) @binding(1) var<storage, read> scalars: array<u32>;
+// One Jacobian point (x, y, z) per thread
+@group(0) @binding(2) var<storage, read_write> sums: array<u32>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+// The base field modulus
+var<private> P: array<u32, 16> = array<u32, 16>(
+    0xfd47u, 0xd87cu, 0x8c16u, 0x3c20u, 0xca8du, 0x6871u, 0x6a91u, 0x9781u,
+    0x585du, 0x8181u, 0x45b6u, 0xb850u, 0xa029u, 0xe131u, 0x4e72u, 0x3064u,
+);
+// -P^-1 mod 2^16
+const P_INV: u32 = 0x6389u;
+// R mod P, i.e. 1 in Montgomery form
+var<private> ONE: array<u32, 16> = array<u32, 16>(
+    0x0d9du, 0xc58fu, 0x438du, 0xd35du, 0x0b3du, 0xf5c7u, 0xeb28u, 0x0a78u,
+    0x462cu, 0x7879u, 0xa36fu, 0x666eu, 0xdf2fu, 0x9a07u, 0x77c1u, 0x0e0au,
+);
+
+fn fp_is_zero(a_in: array<u32, 16>) -> bool {
+    var a = a_in;
+    var acc = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        acc |= a[i];
+    }
+    return acc == 0u;
+}
+
+// a - P if a >= P, else a
+fn fp_reduce(a_in: array<u32, 16>) -> array<u32, 16> {
+    var a = a_in;
+    var r: array<u32, 16>;
+    var borrow = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let d = a[i] + 0x10000u - P[i] - borrow;
+        r[i] = d & 0xffffu;
+        borrow = 1u - (d >> 16u);
+    }
+    if borrow == 0u {
+        return r;
+    }
+    return a;
+}
+
+fn fp_add(a_in: array<u32, 16>, b_in: array<u32, 16>) -> array<u32, 16> {
+    var a = a_in;
+    var b = b_in;
+    var r: array<u32, 16>;
+    var carry = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let s = a[i] + b[i] + carry;
+        r[i] = s & 0xffffu;
+        carry = s >> 16u;
+    }
+    // 2P < 2^256, so there is no carry out
+    return fp_reduce(r);
+}
+
+fn fp_sub(a_in: array<u32, 16>, b_in: array<u32, 16>) -> array<u32, 16> {
+    var a = a_in;
+    var b = b_in;
+    var r: array<u32, 16>;
+    var borrow = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let d = a[i] + 0x10000u - b[i] - borrow;
+        r[i] = d & 0xffffu;
+        borrow = 1u - (d >> 16u);
+    }
+    if borrow == 0u {
+        return r;
+    }
+    var carry = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let s = r[i] + P[i] + carry;
+        r[i] = s & 0xffffu;
+        carry = s >> 16u;
+    }
+    return r;
+}
+
+fn fp_double(a: array<u32, 16>) -> array<u32, 16> {
+    return fp_add(a, a);
+}
+
+// Montgomery product a·b·R^-1 (CIOS). Every step fits in 32 bits:
+// (2^16 - 1)^2 + 2·(2^16 - 1) = 2^32 - 1.
+fn fp_mul(a_in: array<u32, 16>, b_in: array<u32, 16>) -> array<u32, 16> {
+    var a = a_in;
+    var b = b_in;
+    var t: array<u32, 18>;
+    for (var i = 0u; i < LIMBS; i++) {
+        var c = 0u;
+        let bi = b[i];
+        for (var j = 0u; j < LIMBS; j++) {
+            let s = t[j] + a[j] * bi + c;
+            t[j] = s & 0xffffu;
+            c = s >> 16u;
+        }
+        let s = t[16] + c;
+        t[16] = s & 0xffffu;
+        t[17] = s >> 16u;
+
+        let m = (t[0] * P_INV) & 0xffffu;
+        c = (t[0] + m * P[0]) >> 16u;
+        for (var j = 1u; j < LIMBS; j++) {
+            let s = t[j] + m * P[j] + c;
+            t[j - 1u] = s & 0xffffu;
+            c = s >> 16u;
+        }
+        let top = t[16] + c;
+        t[15] = top & 0xffffu;
+        t[16] = t[17] + (top >> 16u);
+    }
+    // The result is below 2P
+    var r: array<u32, 16>;
+    for (var i = 0u; i < LIMBS; i++) {
+        r[i] = t[i];
+    }
+    return fp_reduce(r);
+}
+
+fn fp_square(a: array<u32, 16>) -> array<u32, 16> {
+    return fp_mul(a, a);
+}
+
+fn jac_zero() -> Jac {
+    var p: Jac;
+    p.x = ONE;
+    p.y = ONE;
+    return p;
+}
+
+// dbl-2009-l, for a = 0
+fn jac_double(p: Jac) -> Jac {
+    if fp_is_zero(p.z) {
+        return p;
+    }
+    let a = fp_square(p.x);
+    let b = fp_square(p.y);
+    let c = fp_square(b);
+    let d = fp_double(fp_sub(fp_sub(fp_square(fp_add(p.x, b)), a), c));
+    let e = fp_add(fp_double(a), a);
+    let f = fp_square(e);
+    var r: Jac;
+    r.x = fp_sub(f, fp_double(d));
+    let c8 = fp_double(fp_double(fp_double(c)));
+    r.y = fp_sub(fp_mul(e, fp_sub(d, r.x)), c8);
+    r.z = fp_double(fp_mul(p.y, p.z));
+    return r;
+}
+
+// p + (x2, y2), madd-2007-bl
+fn jac_add_affine(p: Jac, x2: array<u32, 16>, y2: array<u32, 16>) -> Jac {
+    if fp_is_zero(p.z) {
+        var r: Jac;
+        r.x = x2;
+        r.y = y2;
+        r.z = ONE;
+        return r;
+    }
+    let z1z1 = fp_square(p.z);
+    let u2 = fp_mul(x2, z1z1);
+    let s2 = fp_mul(y2, fp_mul(p.z, z1z1));
+    let h = fp_sub(u2, p.x);
+    let rr = fp_double(fp_sub(s2, p.y));
+    if fp_is_zero(h) {
+        if fp_is_zero(rr) {
+            return jac_double(p);
+        }
+        return jac_zero();
+    }
+    let hh = fp_square(h);
+    let i = fp_double(fp_double(hh));
+    let j = fp_mul(h, i);
+    let v = fp_mul(p.x, i);
+    var r: Jac;
+    r.x = fp_sub(fp_sub(fp_square(rr), j), fp_double(v));
+    r.y = fp_sub(fp_mul(rr, fp_sub(v, r.x)), fp_double(fp_mul(p.y, j)));
+    r.z = fp_sub(fp_sub(fp_square(fp_add(p.z, h)), z1z1), hh);
+    return r;
+}
+
+// p + q, add-2007-bl
+fn jac_add(p: Jac, q: Jac) -> Jac {
+    if fp_is_zero(p.z) {
+        return q;
+    }
+    if fp_is_zero(q.z) {
+        return p;
+    }
+    let z1z1 = fp_square(p.z);
+    let z2z2 = fp_square(q.z);
+    let u1 = fp_mul(p.x, z2z2);
+    let u2 = fp_mul(q.x, z1z1);
+    let s1 = fp_mul(p.y, fp_mul(q.z, z2z2));
+    let s2 = fp_mul(q.y, fp_mul(p.z, z1z1));
+    let h = fp_sub(u2, u1);
+    let rr = fp_double(fp_sub(s2, s1));
+    if fp_is_zero(h) {
+        if fp_is_zero(rr) {
+            return jac_double(p);
+        }
+        return jac_zero();
+    }
+    let i = fp_square(fp_double(h));
+    let j = fp_mul(h, i);
+    let v = fp_mul(u1, i);
+    var r: Jac;
+    r.x = fp_sub(fp_sub(fp_square(rr), j), fp_double(v));
+    r.y = fp_sub(fp_mul(rr, fp_sub(v, r.x)), fp_double(fp_mul(s1, j)));
+    let zz = fp_sub(fp_sub(fp_square(fp_add(p.z, q.z)), z1z1), z2z2);
+    r.z = fp_mul(zz, h);
+    return r;
+}
+
+fn load_fp(offset: u32) -> array<u32, 16> {
+    var a: array<u32, 16>;
+    for (var i = 0u; i < LIMBS; i++) {
+        a[i] = bases[offset + i];
+    }
+    return a;
+}
+
+fn store_fp(offset: u32, a_in: array<u32, 16>) {
+    var a = a_in;
+    for (var i = 0u; i < LIMBS; i++) {
+        sums[offset + i] = a[i];
+    }
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let id = gid.x;
+    let window = id / params.chunks;
+    if window >= WINDOWS {
+        return;
+    }
+    let start = (id % params.chunks) * params.chunk;
+    let end = min(start + params.chunk, params.n);
+    let word = window / 8u;
+    let shift = (window % 8u) * 4u;
+
+    var buckets: array<Jac, 15>;
+    for (var b = 0u; b < BUCKETS; b++) {
+        buckets[b] = jac_zero();
+    }
+    for (var i = start; i < end; i++) {
+        let digit = (scalars[i * 8u + word] >> shift) & 0xfu;
+        if digit != 0u {
+            let x = load_fp(i * 32u);
+            let y = load_fp(i * 32u + 16u);
+            buckets[digit - 1u] = jac_add_affine(buckets[digit - 1u], x, y);
+        }
+    }
+
+    // Σ d·bucket[d] as a sum of running sums
+    var running = jac_zero();
+    var sum = jac_zero();
+    for (var b = BUCKETS; b > 0u; b--) {
+        running = jac_add(running, buckets[b - 1u]);
+        sum = jac_add(sum, running);
+    }
+    store_fp(id * 48u, sum.x);
+    store_fp(id * 48u + 16u, sum.y);
+    store_fp(id * 48u + 32u, sum.z);
+}
@@ -0,0 +1,239 @@
+//! R1CS to PLONK gates
+//!
+//! Each gate enforces `q_m·a·b + q_l·a + q_r·b + q_o·c + q_c = 0` over its
+//! three wires. An R1CS constraint `<A, z>·<B, z> = <C, z>` becomes one
+//! multiplication gate, after each side with more than one term is folded
+//! into a fresh variable by a chain of addition gates.
+//!
+//! Rows are laid out as one gate per public input (`q_l = 1`, balanced by
+//! the public input polynomial), one pinning variable 0 to the constant 1,
+//! then the constraints. Variables keep arkworks' numbering (the constant,
+//! instance, witness), followed by the sums added here.
+
+use ark_bn254::Fr;
+use ark_ff::{One, Zero};
+use ark_relations::r1cs::ConstraintMatrices;
+
+use crate::proof::ProofError;
+
+/// One row: selector values and the variables on its wires
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Gate {
+    pub q_m: Fr,
+    pub q_l: Fr,
+    pub q_r: Fr,
+    pub q_o: Fr,
+    pub q_c: Fr,
+    pub wires: [usize; 3],
+}
+
+impl Gate {
+    fn new(wires: [usize; 3]) -> Self {
+        Self {
+            q_m: Fr::zero(),
+            q_l: Fr::zero(),
+            q_r: Fr::zero(),
+            q_o: Fr::zero(),
+            q_c: Fr::zero(),
+            wires,
+        }
+    }
+
+    /// The gate equation, given its wire values
+    #[cfg(test)]
+    fn eval(&self, [a, b, c]: [Fr; 3]) -> Fr {
+        self.q_m * a * b + self.q_l * a + self.q_r * b + self.q_o * c + self.q_c
+    }
+}
+
+/// A variable added by folding: `left.0·left.1 + right.0·right.1`
+#[derive(Clone, Debug)]
+struct Sum {
+    left: (Fr, usize),
+    right: (Fr, usize),
+}
+
+/// A circuit's gates, and how to extend its R1CS assignment to them
+#[derive(Clone, Debug)]
+pub(super) struct Arithmetization {
+    /// Public inputs, not counting the constant
+    pub num_public: usize,
+    num_witness: usize,
+    sums: Vec<Sum>,
+    pub gates: Vec<Gate>,
+}
+
+impl Arithmetization {
+    pub fn from_matrices(matrices: &ConstraintMatrices<Fr>) -> Self {
+        let num_public = matrices.num_instance_variables - 1;
+        let mut this = Self {
+            num_public,
+            num_witness: matrices.num_witness_variables,
+            sums: Vec::new(),
+            gates: Vec::new(),
+        };
+
+        for input in 1..=num_public {
+            let mut gate = Gate::new([input, 0, 0]);
+            gate.q_l = Fr::one();
+            this.gates.push(gate);
+        }
+        let mut one = Gate::new([0, 0, 0]);
+        one.q_l = Fr::one();
+        one.q_c = -Fr::one();
+        this.gates.push(one);
+
+        for ((a, b), c) in matrices.a.iter().zip(&matrices.b).zip(&matrices.c) {
+            let (a_coeff, a) = this.fold(a);
+            let (b_coeff, b) = this.fold(b);
+            let (c_coeff, c) = this.fold(c);
+            let mut gate = Gate::new([a, b, c]);
+            gate.q_m = a_coeff * b_coeff;
+            gate.q_o = -c_coeff;
+            this.gates.push(gate);
+        }
+        this
+    }
+
+    /// Variables, including the sums
+    pub fn num_vars(&self) -> usize {
+        1 + self.num_public + self.num_witness + self.sums.len()
+    }
+
+    /// Reduce a linear combination to `coeff·var`
+    fn fold(&mut self, lc: &[(Fr, usize)]) -> (Fr, usize) {
+        let Some((&first, rest)) = lc.split_first() else {
+            return (Fr::zero(), 0);
+        };
+        rest.iter().fold(first, |left, &right| {
+            let var = self.num_vars();
+            self.sums.push(Sum { left, right });
+            let mut gate = Gate::new([left.1, right.1, var]);
+            gate.q_l = left.0;
+            gate.q_r = right.0;
+            gate.q_o = -Fr::one();
+            self.gates.push(gate);
+            (Fr::one(), var)
+        })
+    }
+
+    /// Every variable's value, from arkworks' instance assignment (starting
+    /// with the constant) and witness assignment
+    pub fn assign(&self, instance: &[Fr], witness: &[Fr]) -> Result<Vec<Fr>, ProofError> {
+        if instance.len() != self.num_public + 1 || witness.len() != self.num_witness {
+            return Err(ProofError::GenerationFailed(
+                "circuit does not match the proving key".to_string(),
+            ));
+        }
+        let mut values = Vec::with_capacity(self.num_vars());
+        values.extend_from_slice(instance);
+        values.extend_from_slice(witness);
+        for Sum { left, right } in &self.sums {
+            let sum = left.0 * values[left.1] + right.0 * values[right.1];
+            values.push(sum);
+        }
+        Ok(values)
+    }
+
+    /// Wire values by column over `rows` rows; padding rows carry the
+    /// constant, like every unused wire
+    pub fn wire_values(&self, values: &[Fr], rows: usize) -> [Vec<Fr>; 3] {
+        std::array::from_fn(|column| {
+            let mut wires: Vec<Fr> = self
+                .gates
+                .iter()
+                .map(|gate| values[gate.wires[column]])
+                .collect();
+            wires.resize(rows, values[0]);
+            wires
+        })
+    }
+
+    /// The copy constraint permutation as wire labels: entry `i` of column
+    /// `k` is the label of the next wire holding the same variable, where
+    /// the wire at (`k`, `i`) is labelled `shifts[k]·roots[i]`
+    pub fn permutation(&self, roots: &[Fr], shifts: [Fr; 3]) -> [Vec<Fr>; 3] {
+        let rows = roots.len();
+        let label = |(column, row): (usize, usize)| shifts[column] * roots[row];
+
+        let mut cycles = vec![Vec::new(); self.num_vars()];
+        for column in 0..3 {
+            for row in 0..rows {
+                let var = self.gates.get(row).map_or(0, |gate| gate.wires[column]);
+                cycles[var].push((column, row));
+            }
+        }
+
+        let mut sigma: [Vec<Fr>; 3] = std::array::from_fn(|_| vec![Fr::zero(); rows]);
+        for cycle in cycles.iter().filter(|cycle| !cycle.is_empty()) {
+            for (i, &(column, row)) in cycle.iter().enumerate() {
+                sigma[column][row] = label(cycle[(i + 1) % cycle.len()]);
+            }
+        }
+        sigma
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::{ConstraintSystem, LinearCombination, Variable};
+
+    #[test]
+    fn test_gates_hold_for_assignment() {
+        // (x + 2y + 3) * y = out, with out public
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let (x, y) = (Fr::from(4u64), Fr::from(5u64));
+        let out = cs
+            .new_input_variable(|| Ok((x + y + y + Fr::from(3u64)) * y))
+            .unwrap();
+        let x_var = cs.new_witness_variable(|| Ok(x)).unwrap();
+        let y_var = cs.new_witness_variable(|| Ok(y)).unwrap();
+        let lhs = LinearCombination::from(x_var)
+            + (Fr::from(2u64), y_var)
+            + (Fr::from(3u64), Variable::One);
+        cs.enforce_constraint(lhs, y_var.into(), out.into())
+            .unwrap();
+        cs.finalize();
+
+        let arith = Arithmetization::from_matrices(&cs.to_matrices().unwrap());
+        // Input, constant, two additions, one multiplication
+        assert_eq!(arith.gates.len(), 5);
+
+        let cs = cs.borrow().unwrap();
+        let values = arith
+            .assign(&cs.instance_assignment, &cs.witness_assignment)
+            .unwrap();
+        for gate in &arith.gates {
+            let wires = gate.wires.map(|var| values[var]);
+            let public = if gate.wires == [1, 0, 0] {
+                -values[1]
+            } else {
+                Fr::zero()
+            };
+            assert_eq!(gate.eval(wires) + public, Fr::zero());
+        }
+        assert!(arith.assign(&cs.instance_assignment, &[]).is_err());
+    }
+
+    #[test]
+    fn test_permutation_cycles_each_variable() {
+        let arith = Arithmetization {
+            num_public: 0,
+            num_witness: 2,
+            sums: Vec::new(),
+            gates: vec![Gate::new([1, 2, 0]), Gate::new([2, 1, 1])],
+        };
+        let roots = [1u64, 2, 3, 4].map(Fr::from);
+        let shifts = [1u64, 10, 100].map(Fr::from);
+        let sigma = arith.permutation(&roots, shifts);
+
+        // Variable 1 sits at (0, 0), (1, 1), (2, 1): labels 1, 20, 200
+        assert_eq!(sigma[0][0], Fr::from(20u64));
+        assert_eq!(sigma[1][1], Fr::from(200u64));
+        assert_eq!(sigma[2][1], Fr::from(1u64));
+        // Variable 2 swaps (1, 0) and (0, 1)
+        assert_eq!(sigma[1][0], Fr::from(2u64));
+        assert_eq!(sigma[0][1], Fr::from(10u64));
+    }
+}
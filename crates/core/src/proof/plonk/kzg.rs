@@ -0,0 +1,153 @@
+//! KZG polynomial commitments over a universal SRS
+
+use ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::scalar_mul::fixed_base::FixedBase;
+use ark_ec::{AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{PrimeField, UniformRand, Zero};
+use ark_poly::univariate::DensePolynomial;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::RngCore;
+
+use crate::proof::ProofError;
+
+/// Structured reference string: `[x^i]₁` for `i ≤ max_degree`, and `[1]₂`,
+/// `[x]₂`
+///
+/// One SRS serves every circuit it is large enough for; a circuit of `n`
+/// rows (rounded up to a power of two) needs `max_degree ≥ 3n + 5`.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Srs {
+    powers_g1: Vec<G1Affine>,
+    g2: G2Affine,
+    x_g2: G2Affine,
+}
+
+impl Srs {
+    /// Powers of a fresh random `x`
+    ///
+    /// WARNING: `x` is known while this runs, so proofs against this SRS can
+    /// be forged by whoever ran it. For production, import the output of a
+    /// powers-of-tau ceremony with `from_powers`.
+    pub fn insecure_setup<R: RngCore>(max_degree: usize, rng: &mut R) -> Self {
+        let x = Fr::rand(rng);
+        let mut scalars = Vec::with_capacity(max_degree + 1);
+        let mut power = Fr::from(1u64);
+        for _ in 0..=max_degree {
+            scalars.push(power);
+            power *= x;
+        }
+
+        let window = FixedBase::get_mul_window_size(scalars.len());
+        let scalar_bits = Fr::MODULUS_BIT_SIZE as usize;
+        let table =
+            FixedBase::get_window_table(scalar_bits, window, G1Affine::generator().into_group());
+        let powers = FixedBase::msm::<G1Projective>(scalar_bits, window, &table, &scalars);
+
+        Self {
+            powers_g1: G1Projective::normalize_batch(&powers),
+            g2: G2Affine::generator(),
+            x_g2: (G2Affine::generator() * x).into_affine(),
+        }
+    }
+
+    /// An SRS from a ceremony's powers, checked for consistency
+    ///
+    /// Fails with `SetupError` unless each power is `x` times the previous
+    /// one, for the `x` in `x_g2`.
+    pub fn from_powers<R: RngCore>(
+        powers_g1: Vec<G1Affine>,
+        g2: G2Affine,
+        x_g2: G2Affine,
+        rng: &mut R,
+    ) -> Result<Self, ProofError> {
+        if powers_g1.len() < 2 || powers_g1[0].is_zero() || g2.is_zero() {
+            return Err(ProofError::SetupError("degenerate SRS".to_string()));
+        }
+        // e(Σ rᵢ·Pᵢ, [x]₂) = e(Σ rᵢ·Pᵢ₊₁, [1]₂) for random rᵢ
+        let r: Vec<Fr> = (1..powers_g1.len()).map(|_| Fr::rand(rng)).collect();
+        let lower = G1Projective::msm_unchecked(&powers_g1[..powers_g1.len() - 1], &r);
+        let upper = G1Projective::msm_unchecked(&powers_g1[1..], &r);
+        if Bn254::pairing(lower, x_g2) != Bn254::pairing(upper, g2) {
+            return Err(ProofError::SetupError(
+                "SRS powers are inconsistent".to_string(),
+            ));
+        }
+        Ok(Self {
+            powers_g1,
+            g2,
+            x_g2,
+        })
+    }
+
+    /// Highest polynomial degree this SRS can commit to
+    pub fn max_degree(&self) -> usize {
+        self.powers_g1.len() - 1
+    }
+
+    /// `[1]₁`
+    pub(super) fn g1(&self) -> G1Affine {
+        self.powers_g1[0]
+    }
+
+    /// `[1]₂` and `[x]₂`
+    pub(super) fn g2(&self) -> (G2Affine, G2Affine) {
+        (self.g2, self.x_g2)
+    }
+
+    /// Commit to `poly`, which must fit the SRS
+    pub(super) fn commit(&self, poly: &DensePolynomial<Fr>) -> Result<G1Affine, ProofError> {
+        if poly.coeffs.len() > self.powers_g1.len() {
+            return Err(ProofError::GenerationFailed(format!(
+                "polynomial of degree {} exceeds the SRS",
+                poly.coeffs.len() - 1
+            )));
+        }
+        Ok(G1Projective::msm_unchecked(&self.powers_g1, &poly.coeffs).into_affine())
+    }
+}
+
+/// `(p(X) - p(point)) / (X - point)`, the witness polynomial of an opening
+pub(super) fn opening_quotient(poly: &DensePolynomial<Fr>, point: Fr) -> DensePolynomial<Fr> {
+    let mut quotient = vec![Fr::zero(); poly.coeffs.len().saturating_sub(1)];
+    let mut carry = Fr::zero();
+    for (i, coeff) in poly.coeffs.iter().enumerate().skip(1).rev() {
+        carry = *coeff + carry * point;
+        quotient[i - 1] = carry;
+    }
+    DensePolynomial { coeffs: quotient }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_poly::{DenseUVPolynomial, Polynomial};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_opening_quotient() {
+        let poly = DensePolynomial::from_coefficients_vec([3u64, 0, 2, 5].map(Fr::from).to_vec());
+        let point = Fr::from(7u64);
+        let quotient = opening_quotient(&poly, point);
+
+        let x = Fr::from(11u64);
+        let expected = (poly.evaluate(&x) - poly.evaluate(&point)) / (x - point);
+        assert_eq!(quotient.evaluate(&x), expected);
+    }
+
+    #[test]
+    fn test_from_powers_checks_consistency() {
+        let srs = Srs::insecure_setup(8, &mut OsRng);
+        assert_eq!(srs.max_degree(), 8);
+
+        let imported = Srs::from_powers(srs.powers_g1.clone(), srs.g2, srs.x_g2, &mut OsRng);
+        assert!(imported.is_ok());
+
+        let mut powers = srs.powers_g1.clone();
+        powers.swap(3, 4);
+        assert!(Srs::from_powers(powers, srs.g2, srs.x_g2, &mut OsRng).is_err());
+
+        let too_high = DensePolynomial::from_coefficients_vec(vec![Fr::from(1u64); 10]);
+        assert!(srs.commit(&too_high).is_err());
+    }
+}
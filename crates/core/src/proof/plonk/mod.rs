@@ -0,0 +1,716 @@
+//! PLONK backend (feature `plonk`)
+//!
+//! PLONK over BN254 with KZG commitments. Its only trusted setup is a
+//! universal `Srs`: one powers-of-tau ceremony serves every circuit up to
+//! its size, and a circuit's keys are derived from it deterministically, so
+//! changing a circuit needs no new ceremony.
+//!
+//! It proves the same arkworks circuits as the Groth16 systems, translating
+//! their R1CS into PLONK gates (see `arithmetization`). The protocol is the
+//! one in the PLONK paper (<https://eprint.iacr.org/2019/953>) without the
+//! linearization step: the proof carries every polynomial's evaluation and
+//! opens them in one batched KZG check, at the cost of a larger proof.
+//! Challenges are SHA-256 over the transcript, which an on-chain verifier
+//! can recompute with the sha256 syscall; `PlonkProof::to_bytes` uses the
+//! big-endian encoding of the alt_bn128 syscalls.
+
+mod arithmetization;
+mod kzg;
+
+pub use kzg::Srs;
+
+use std::sync::Arc;
+
+use ark_bn254::{Bn254, Fq, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, VariableBaseMSM};
+use ark_ff::{batch_inversion, BigInteger, FftField, Field, One, PrimeField, UniformRand, Zero};
+use ark_poly::univariate::DensePolynomial;
+use ark_poly::{DenseUVPolynomial, EvaluationDomain, Polynomial, Radix2EvaluationDomain};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisMode,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use self::arithmetization::{Arithmetization, Gate};
+use super::backend::ProofBackend;
+use super::{field_to_bytes_be, ProofError};
+
+type Poly = DensePolynomial<Fr>;
+type Domain = Radix2EvaluationDomain<Fr>;
+
+/// Polynomials opened at ζ, in proof order
+const NUM_EVALS: usize = 13;
+
+/// PLONK proofs over circuits within an SRS
+pub struct PlonkBackend {
+    srs: Arc<Srs>,
+}
+
+impl PlonkBackend {
+    pub fn new(srs: Arc<Srs>) -> Self {
+        Self { srs }
+    }
+
+    /// The SRS keys are derived from
+    pub fn srs(&self) -> &Srs {
+        &self.srs
+    }
+}
+
+impl ProofBackend for PlonkBackend {
+    type ProvingKey = PlonkProvingKey;
+    type VerifyingKey = PlonkVerifyingKey;
+    type Proof = PlonkProof;
+
+    fn setup<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        circuit: C,
+    ) -> Result<(PlonkProvingKey, PlonkVerifyingKey), ProofError> {
+        let pk = setup(self.srs.clone(), circuit)?;
+        let vk = pk.vk.clone();
+        Ok((pk, vk))
+    }
+
+    fn prove<C: ConstraintSynthesizer<Fr>>(
+        &self,
+        pk: &PlonkProvingKey,
+        circuit: C,
+    ) -> Result<PlonkProof, ProofError> {
+        prove(pk, circuit, &mut OsRng)
+    }
+
+    fn verify(
+        &self,
+        vk: &PlonkVerifyingKey,
+        public_inputs: &[Fr],
+        proof: &PlonkProof,
+    ) -> Result<bool, ProofError> {
+        verify(vk, public_inputs, proof)
+    }
+
+    fn proof_to_bytes(&self, proof: &PlonkProof) -> Result<Vec<u8>, ProofError> {
+        Ok(proof.to_bytes().to_vec())
+    }
+
+    fn proof_from_bytes(&self, bytes: &[u8]) -> Result<PlonkProof, ProofError> {
+        PlonkProof::from_bytes(bytes)
+    }
+}
+
+/// A circuit's public commitments, and the SRS points verification needs
+#[derive(Clone, Debug, PartialEq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PlonkVerifyingKey {
+    /// Gate rows, a power of two
+    pub rows: usize,
+    /// Public inputs the circuit takes
+    pub num_public: usize,
+    /// Commitments to q_m, q_l, q_r, q_o, q_c
+    pub selectors: [G1Affine; 5],
+    /// Commitments to the copy permutation S_σ1, S_σ2, S_σ3
+    pub sigmas: [G1Affine; 3],
+    /// `[1]₁`, `[1]₂` and `[x]₂` from the SRS
+    pub g1: G1Affine,
+    pub g2: G2Affine,
+    pub x_g2: G2Affine,
+}
+
+/// A circuit's gates and preprocessed polynomials
+pub struct PlonkProvingKey {
+    vk: PlonkVerifyingKey,
+    circuit: Arithmetization,
+    selectors: [Poly; 5],
+    sigmas: [Poly; 3],
+    sigma_evals: [Vec<Fr>; 3],
+    srs: Arc<Srs>,
+}
+
+impl PlonkProvingKey {
+    pub fn verifying_key(&self) -> &PlonkVerifyingKey {
+        &self.vk
+    }
+}
+
+/// PLONK proof
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlonkProof {
+    /// Commitments to the wire polynomials a, b, c
+    pub wires: [G1Affine; 3],
+    /// Commitment to the permutation grand product z
+    pub z: G1Affine,
+    /// Commitment to the quotient t
+    pub t: G1Affine,
+    /// Batched opening proof at ζ
+    pub opening: G1Affine,
+    /// Opening proof of z at ζω
+    pub opening_shifted: G1Affine,
+    /// a, b, c, z, t, S_σ1..3, q_m, q_l, q_r, q_o, q_c at ζ
+    pub evals: [Fr; NUM_EVALS],
+    /// z at ζω
+    pub z_shifted: Fr,
+}
+
+impl PlonkProof {
+    /// Encoded size: 7 G1 points of 64 bytes and 14 scalars of 32
+    pub const SIZE: usize = 7 * 64 + (NUM_EVALS + 1) * 32;
+
+    /// Points as big-endian x || y (zero for infinity), then scalars as
+    /// 32-byte big-endian values, in field order
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let points = self.points();
+        let scalars = self.evals.iter().chain([&self.z_shifted]);
+
+        let mut bytes = [0u8; Self::SIZE];
+        let (point_bytes, scalar_bytes) = bytes.split_at_mut(7 * 64);
+        for (chunk, point) in point_bytes.chunks_mut(64).zip(points) {
+            chunk.copy_from_slice(&g1_to_be(&point));
+        }
+        for (chunk, scalar) in scalar_bytes.chunks_mut(32).zip(scalars) {
+            chunk.copy_from_slice(&field_to_bytes_be(scalar));
+        }
+        bytes
+    }
+
+    /// Decode `to_bytes`, rejecting points off the curve and non-canonical
+    /// field elements
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofError> {
+        if bytes.len() != Self::SIZE {
+            return Err(ProofError::SerializationError(format!(
+                "Expected {} bytes, got {}",
+                Self::SIZE,
+                bytes.len()
+            )));
+        }
+        let (point_bytes, scalar_bytes) = bytes.split_at(7 * 64);
+        let points = point_bytes
+            .chunks(64)
+            .map(g1_from_be)
+            .collect::<Result<Vec<_>, _>>()?;
+        let scalars = scalar_bytes
+            .chunks(32)
+            .map(field_from_be::<Fr>)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            wires: [points[0], points[1], points[2]],
+            z: points[3],
+            t: points[4],
+            opening: points[5],
+            opening_shifted: points[6],
+            evals: std::array::from_fn(|i| scalars[i]),
+            z_shifted: scalars[NUM_EVALS],
+        })
+    }
+
+    fn points(&self) -> [G1Affine; 7] {
+        let [a, b, c] = self.wires;
+        [a, b, c, self.z, self.t, self.opening, self.opening_shifted]
+    }
+}
+
+/// Derive keys for `circuit` from `srs`
+fn setup<C: ConstraintSynthesizer<Fr>>(
+    srs: Arc<Srs>,
+    circuit: C,
+) -> Result<PlonkProvingKey, ProofError> {
+    let setup_error = |e: &dyn std::fmt::Display| ProofError::SetupError(e.to_string());
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Setup);
+    circuit
+        .generate_constraints(cs.clone())
+        .map_err(|e| setup_error(&e))?;
+    cs.finalize();
+    let matrices = cs
+        .to_matrices()
+        .ok_or_else(|| setup_error(&"no constraint matrices"))?;
+    let circuit = Arithmetization::from_matrices(&matrices);
+
+    let domain = Domain::new(circuit.gates.len())
+        .ok_or_else(|| setup_error(&"circuit too large for the field's FFT domain"))?;
+    let rows = domain.size();
+    if srs.max_degree() < 3 * rows + 5 {
+        return Err(setup_error(&format!(
+            "circuit of {} rows needs an SRS of degree {}, got {}",
+            rows,
+            3 * rows + 5,
+            srs.max_degree()
+        )));
+    }
+
+    let column = |selector: fn(&Gate) -> Fr| {
+        let mut evals: Vec<Fr> = circuit.gates.iter().map(selector).collect();
+        evals.resize(rows, Fr::zero());
+        Poly::from_coefficients_vec(domain.ifft(&evals))
+    };
+    let selectors = [
+        column(|gate| gate.q_m),
+        column(|gate| gate.q_l),
+        column(|gate| gate.q_r),
+        column(|gate| gate.q_o),
+        column(|gate| gate.q_c),
+    ];
+    let roots: Vec<Fr> = domain.elements().collect();
+    let sigma_evals = circuit.permutation(&roots, shifts());
+    let sigmas: [Poly; 3] =
+        std::array::from_fn(|k| Poly::from_coefficients_vec(domain.ifft(&sigma_evals[k])));
+
+    let (g2, x_g2) = srs.g2();
+    let vk = PlonkVerifyingKey {
+        rows,
+        num_public: circuit.num_public,
+        selectors: commit_all(&srs, &selectors)?,
+        sigmas: commit_all(&srs, &sigmas)?,
+        g1: srs.g1(),
+        g2,
+        x_g2,
+    };
+    Ok(PlonkProvingKey {
+        vk,
+        circuit,
+        selectors,
+        sigmas,
+        sigma_evals,
+        srs,
+    })
+}
+
+fn prove<C: ConstraintSynthesizer<Fr>, R: RngCore>(
+    pk: &PlonkProvingKey,
+    circuit: C,
+    rng: &mut R,
+) -> Result<PlonkProof, ProofError> {
+    let generation_error = |e: &dyn std::fmt::Display| ProofError::GenerationFailed(e.to_string());
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    circuit
+        .generate_constraints(cs.clone())
+        .map_err(|e| generation_error(&e))?;
+    cs.finalize();
+    if !cs.is_satisfied().map_err(|e| generation_error(&e))? {
+        return Err(ProofError::InvalidWitness);
+    }
+    let cs = cs
+        .borrow()
+        .ok_or_else(|| generation_error(&"constraint system in use"))?;
+    let values = pk
+        .circuit
+        .assign(&cs.instance_assignment, &cs.witness_assignment)?;
+    let public_inputs = &cs.instance_assignment[1..];
+
+    let srs = &pk.srs;
+    let rows = pk.vk.rows;
+    let domain = Domain::new(rows).ok_or(ProofError::InvalidProvingKey)?;
+    let omega = domain.group_gen;
+    let mut transcript = Transcript::new(&pk.vk, public_inputs);
+
+    // Round 1: wire polynomials, blinded with (b₁X + b₂)·Z_H
+    let wire_evals = pk.circuit.wire_values(&values, rows);
+    let wires: [Poly; 3] =
+        std::array::from_fn(|k| blind(&domain, &wire_evals[k], &[Fr::rand(rng), Fr::rand(rng)]));
+    let wire_commitments = commit_all(srs, &wires)?;
+    wire_commitments
+        .iter()
+        .for_each(|c| transcript.append_point(c));
+    let beta = transcript.challenge(b"beta");
+    let gamma = transcript.challenge(b"gamma");
+
+    // Round 2: permutation grand product
+    let shifts = shifts();
+    let mut denominators: Vec<Fr> = (0..rows)
+        .map(|i| {
+            (0..3)
+                .map(|k| wire_evals[k][i] + beta * pk.sigma_evals[k][i] + gamma)
+                .product()
+        })
+        .collect();
+    batch_inversion(&mut denominators);
+    let mut products = Vec::with_capacity(rows);
+    let mut product = Fr::one();
+    for (i, (root, denominator)) in domain.elements().zip(&denominators).enumerate() {
+        products.push(product);
+        let numerator: Fr = (0..3)
+            .map(|k| wire_evals[k][i] + beta * shifts[k] * root + gamma)
+            .product();
+        product *= numerator * denominator;
+    }
+    let z = blind(
+        &domain,
+        &products,
+        &[Fr::rand(rng), Fr::rand(rng), Fr::rand(rng)],
+    );
+    let z_commitment = srs.commit(&z)?;
+    transcript.append_point(&z_commitment);
+    let alpha = transcript.challenge(b"alpha");
+
+    // Round 3: quotient
+    let [q_m, q_l, q_r, q_o, q_c] = &pk.selectors;
+    let [a, b, c] = &wires;
+    let mut public_evals = vec![Fr::zero(); rows];
+    for (eval, input) in public_evals.iter_mut().zip(public_inputs) {
+        *eval = -*input;
+    }
+    let public = Poly::from_coefficients_vec(domain.ifft(&public_evals));
+    let gate = &(&(&(&(&(q_m * a) * b) + &(q_l * a)) + &(q_r * b)) + &(q_o * c)) + q_c;
+
+    let constant = |value: Fr| Poly::from_coefficients_vec(vec![value]);
+    let identity =
+        |k: usize| &wires[k] + &Poly::from_coefficients_vec(vec![gamma, beta * shifts[k]]);
+    let permuted = |k: usize| &(&wires[k] + &(&pk.sigmas[k] * beta)) + &constant(gamma);
+    let z_shifted = shift(&z, omega);
+    let permutation = &(&(&(&identity(0) * &identity(1)) * &identity(2)) * &z)
+        - &(&(&(&permuted(0) * &permuted(1)) * &permuted(2)) * &z_shifted);
+
+    let mut first = vec![Fr::zero(); rows];
+    first[0] = Fr::one();
+    let l0 = Poly::from_coefficients_vec(domain.ifft(&first));
+    let boundary = &(&z - &constant(Fr::one())) * &l0;
+
+    let numerator = &(&(&gate + &public) + &(&permutation * alpha)) + &(&boundary * alpha.square());
+    let (t, remainder) = numerator
+        .divide_by_vanishing_poly(domain)
+        .ok_or_else(|| generation_error(&"quotient division failed"))?;
+    if !remainder.is_zero() {
+        return Err(generation_error(&"copy constraints do not hold"));
+    }
+    let t_commitment = srs.commit(&t)?;
+    transcript.append_point(&t_commitment);
+    let zeta = transcript.challenge(b"zeta");
+
+    // Round 4: evaluations
+    let [s1, s2, s3] = &pk.sigmas;
+    let opened: [&Poly; NUM_EVALS] = [a, b, c, &z, &t, s1, s2, s3, q_m, q_l, q_r, q_o, q_c];
+    let evals = opened.map(|poly| poly.evaluate(&zeta));
+    let z_shifted_eval = z.evaluate(&(zeta * omega));
+    evals.iter().for_each(|e| transcript.append_scalar(e));
+    transcript.append_scalar(&z_shifted_eval);
+    let v = transcript.challenge(b"v");
+
+    // Round 5: opening proofs
+    let mut combined = Poly::zero();
+    let mut power = Fr::one();
+    for poly in opened {
+        combined += (power, poly);
+        power *= v;
+    }
+    let opening = srs.commit(&kzg::opening_quotient(&combined, zeta))?;
+    let opening_shifted = srs.commit(&kzg::opening_quotient(&z, zeta * omega))?;
+
+    Ok(PlonkProof {
+        wires: wire_commitments,
+        z: z_commitment,
+        t: t_commitment,
+        opening,
+        opening_shifted,
+        evals,
+        z_shifted: z_shifted_eval,
+    })
+}
+
+fn verify(
+    vk: &PlonkVerifyingKey,
+    public_inputs: &[Fr],
+    proof: &PlonkProof,
+) -> Result<bool, ProofError> {
+    if public_inputs.len() != vk.num_public {
+        return Err(ProofError::VerificationFailed(format!(
+            "Expected {} public inputs, got {}",
+            vk.num_public,
+            public_inputs.len()
+        )));
+    }
+    let domain = Domain::new(vk.rows)
+        .filter(|domain| domain.size() == vk.rows)
+        .ok_or(ProofError::InvalidVerifyingKey)?;
+    let omega = domain.group_gen;
+
+    let mut transcript = Transcript::new(vk, public_inputs);
+    proof.wires.iter().for_each(|c| transcript.append_point(c));
+    let beta = transcript.challenge(b"beta");
+    let gamma = transcript.challenge(b"gamma");
+    transcript.append_point(&proof.z);
+    let alpha = transcript.challenge(b"alpha");
+    transcript.append_point(&proof.t);
+    let zeta = transcript.challenge(b"zeta");
+    proof.evals.iter().for_each(|e| transcript.append_scalar(e));
+    transcript.append_scalar(&proof.z_shifted);
+    let v = transcript.challenge(b"v");
+    transcript.append_point(&proof.opening);
+    transcript.append_point(&proof.opening_shifted);
+    let u = transcript.challenge(b"u");
+
+    let vanishing = domain.evaluate_vanishing_polynomial(zeta);
+    if vanishing.is_zero() {
+        return Ok(false);
+    }
+    // Lᵢ(ζ) = ωⁱ·Z_H(ζ) / (n·(ζ - ωⁱ)) for the public input rows and row 0
+    let n = Fr::from(vk.rows as u64);
+    let mut lagrange: Vec<Fr> = (0..vk.num_public.max(1))
+        .map(|i| n * (zeta - domain.element(i)))
+        .collect();
+    batch_inversion(&mut lagrange);
+    for (i, l) in lagrange.iter_mut().enumerate() {
+        *l *= domain.element(i) * vanishing;
+    }
+    let public: Fr = public_inputs
+        .iter()
+        .zip(&lagrange)
+        .map(|(x, l)| -*x * l)
+        .sum();
+
+    let [a, b, c, z, t, s1, s2, s3, q_m, q_l, q_r, q_o, q_c] = proof.evals;
+    let k = shifts();
+    let gate = q_m * a * b + q_l * a + q_r * b + q_o * c + q_c + public;
+    let permutation = (a + beta * zeta + gamma)
+        * (b + beta * k[1] * zeta + gamma)
+        * (c + beta * k[2] * zeta + gamma)
+        * z
+        - (a + beta * s1 + gamma)
+            * (b + beta * s2 + gamma)
+            * (c + beta * s3 + gamma)
+            * proof.z_shifted;
+    let boundary = (z - Fr::one()) * lagrange[0];
+    if gate + alpha * permutation + alpha.square() * boundary != t * vanishing {
+        return Ok(false);
+    }
+
+    // e(W + u·W', [x]₂) = e(F - E·[1]₁ + ζ·W + u·(ζω·W' + [z] - z(ζω)·[1]₁), [1]₂)
+    let [wa, wb, wc] = proof.wires;
+    let [s1_c, s2_c, s3_c] = vk.sigmas;
+    let [qm_c, ql_c, qr_c, qo_c, qc_c] = vk.selectors;
+    let commitments = [
+        wa, wb, wc, proof.z, proof.t, s1_c, s2_c, s3_c, qm_c, ql_c, qr_c, qo_c, qc_c,
+    ];
+    let powers: Vec<Fr> = std::iter::successors(Some(Fr::one()), |p| Some(*p * v))
+        .take(NUM_EVALS)
+        .collect();
+    let combined = G1Projective::msm_unchecked(&commitments, &powers);
+    let combined_eval: Fr = proof.evals.iter().zip(&powers).map(|(e, p)| *e * p).sum();
+
+    let lhs = proof.opening_shifted * u + proof.opening;
+    let rhs = combined - vk.g1 * combined_eval
+        + proof.opening * zeta
+        + (proof.opening_shifted * (zeta * omega) + proof.z - vk.g1 * proof.z_shifted) * u;
+    Ok(Bn254::pairing(lhs, vk.x_g2) == Bn254::pairing(rhs, vk.g2))
+}
+
+/// Labels of the a, b and c wire columns: 1, k and k², with k a generator
+/// of Fr*, so the columns' cosets of the domain are disjoint
+fn shifts() -> [Fr; 3] {
+    let k = Fr::GENERATOR;
+    [Fr::one(), k, k.square()]
+}
+
+/// Interpolate `evals` over `domain`, plus `blinding(X)·Z_H(X)`
+fn blind(domain: &Domain, evals: &[Fr], blinding: &[Fr]) -> Poly {
+    let rows = domain.size();
+    let mut coeffs = domain.ifft(evals);
+    coeffs.resize(rows + blinding.len(), Fr::zero());
+    for (j, b) in blinding.iter().enumerate() {
+        coeffs[j] -= b;
+        coeffs[rows + j] += b;
+    }
+    Poly::from_coefficients_vec(coeffs)
+}
+
+/// `p(ωX)`
+fn shift(poly: &Poly, omega: Fr) -> Poly {
+    let mut power = Fr::one();
+    let coeffs = poly
+        .coeffs
+        .iter()
+        .map(|coeff| {
+            let shifted = *coeff * power;
+            power *= omega;
+            shifted
+        })
+        .collect();
+    Poly::from_coefficients_vec(coeffs)
+}
+
+fn commit_all<const N: usize>(srs: &Srs, polys: &[Poly; N]) -> Result<[G1Affine; N], ProofError> {
+    let mut commitments = [G1Affine::zero(); N];
+    for (commitment, poly) in commitments.iter_mut().zip(polys) {
+        *commitment = srs.commit(poly)?;
+    }
+    Ok(commitments)
+}
+
+/// Fiat-Shamir transcript: a running SHA-256 of the key, public inputs and
+/// every prover message
+struct Transcript(Sha256);
+
+impl Transcript {
+    fn new(vk: &PlonkVerifyingKey, public_inputs: &[Fr]) -> Self {
+        let mut transcript = Self(Sha256::new_with_prefix(b"veil-plonk-v1"));
+        transcript.0.update((vk.rows as u64).to_be_bytes());
+        vk.selectors
+            .iter()
+            .chain(&vk.sigmas)
+            .for_each(|c| transcript.append_point(c));
+        public_inputs
+            .iter()
+            .for_each(|x| transcript.append_scalar(x));
+        transcript
+    }
+
+    fn append_point(&mut self, point: &G1Affine) {
+        self.0.update(g1_to_be(point));
+    }
+
+    fn append_scalar(&mut self, scalar: &Fr) {
+        self.0.update(field_to_bytes_be(scalar));
+    }
+
+    fn challenge(&mut self, label: &[u8]) -> Fr {
+        self.0.update(label);
+        let challenge = Fr::from_be_bytes_mod_order(&self.0.clone().finalize());
+        self.append_scalar(&challenge);
+        challenge
+    }
+}
+
+/// Big-endian x || y, all zeros for infinity
+fn g1_to_be(point: &G1Affine) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    if let Some((x, y)) = point.xy() {
+        bytes[..32].copy_from_slice(&x.into_bigint().to_bytes_be());
+        bytes[32..].copy_from_slice(&y.into_bigint().to_bytes_be());
+    }
+    bytes
+}
+
+fn g1_from_be(bytes: &[u8]) -> Result<G1Affine, ProofError> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G1Affine::zero());
+    }
+    let x = field_from_be::<Fq>(&bytes[..32])?;
+    let y = field_from_be::<Fq>(&bytes[32..])?;
+    // G1 has cofactor 1, so any point on the curve is in the subgroup
+    let point = G1Affine::new_unchecked(x, y);
+    if !point.is_on_curve() {
+        return Err(ProofError::SerializationError(
+            "G1 point not on the curve".to_string(),
+        ));
+    }
+    Ok(point)
+}
+
+fn field_from_be<F: PrimeField>(bytes: &[u8]) -> Result<F, ProofError> {
+    let mut le = bytes.to_vec();
+    le.reverse();
+    F::deserialize_uncompressed(&le[..])
+        .map_err(|_| ProofError::SerializationError("non-canonical field element".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, fields::FieldVar};
+    use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+    use crate::proof::gadgets::poseidon::PoseidonGadget;
+
+    /// Poseidon(x, y) = out with out public, and (x + 2y + 3)·y = 77·w
+    #[derive(Clone)]
+    struct HashCircuit {
+        x: Fr,
+        y: Fr,
+        out: Fr,
+    }
+
+    impl HashCircuit {
+        fn new(x: u64, y: u64) -> Self {
+            let (x, y) = (Fr::from(x), Fr::from(y));
+            let out = crate::crypto::poseidon::poseidon_hash2(&x, &y);
+            Self { x, y, out }
+        }
+    }
+
+    impl ConstraintSynthesizer<Fr> for HashCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let x = FpVar::new_witness(cs.clone(), || Ok(self.x))?;
+            let y = FpVar::new_witness(cs.clone(), || Ok(self.y))?;
+            let out = FpVar::new_input(cs.clone(), || Ok(self.out))?;
+            let w = FpVar::new_witness(cs.clone(), || {
+                Ok((self.x + self.y.double() + Fr::from(3u64)) * self.y / Fr::from(77u64))
+            })?;
+            let lhs = (&x + y.double()? + Fr::from(3u64)) * &y;
+            lhs.enforce_equal(&(w * Fr::from(77u64)))?;
+            PoseidonGadget::new(cs.clone())?
+                .hash2(cs, &x, &y)?
+                .enforce_equal(&out)
+        }
+    }
+
+    /// Shared across tests: the circuit pads to 8192 rows
+    fn backend() -> PlonkBackend {
+        static SRS: std::sync::OnceLock<Arc<Srs>> = std::sync::OnceLock::new();
+        let srs = SRS.get_or_init(|| Arc::new(Srs::insecure_setup(3 * 8192 + 5, &mut OsRng)));
+        PlonkBackend::new(srs.clone())
+    }
+
+    #[test]
+    fn test_prove_and_verify() {
+        let backend = backend();
+        let circuit = HashCircuit::new(3, 5);
+        let (pk, vk) = backend.setup(circuit.clone()).unwrap();
+        let proof = backend.prove(&pk, circuit.clone()).unwrap();
+
+        assert!(backend.verify(&vk, &[circuit.out], &proof).unwrap());
+        assert!(!backend
+            .verify(&vk, &[circuit.out + Fr::one()], &proof)
+            .unwrap());
+        assert!(backend.verify(&vk, &[], &proof).is_err());
+
+        // Keys come from the SRS and circuit alone
+        let (_, again) = backend.setup(HashCircuit::new(0, 0)).unwrap();
+        assert_eq!(again, vk);
+    }
+
+    #[test]
+    fn test_proof_bytes_roundtrip() {
+        let backend = backend();
+        let circuit = HashCircuit::new(7, 11);
+        let (pk, vk) = backend.setup(circuit.clone()).unwrap();
+        let proof = backend.prove(&pk, circuit.clone()).unwrap();
+
+        let bytes = backend.proof_to_bytes(&proof).unwrap();
+        assert_eq!(bytes.len(), PlonkProof::SIZE);
+        assert_eq!(backend.proof_from_bytes(&bytes).unwrap(), proof);
+
+        // Any changed evaluation fails the check
+        let mut tampered = proof.clone();
+        tampered.evals[3] += Fr::one();
+        assert!(!backend.verify(&vk, &[circuit.out], &tampered).unwrap());
+
+        // An off-curve point or out-of-range scalar does not decode
+        let mut bad_point = bytes.clone();
+        bad_point[63] ^= 1;
+        assert!(PlonkProof::from_bytes(&bad_point).is_err());
+        let mut bad_scalar = bytes;
+        bad_scalar[7 * 64..7 * 64 + 32].fill(0xff);
+        assert!(PlonkProof::from_bytes(&bad_scalar).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_witness_and_small_srs() {
+        let backend = backend();
+        let (pk, _) = backend.setup(HashCircuit::new(3, 5)).unwrap();
+        let mut wrong = HashCircuit::new(3, 5);
+        wrong.out += Fr::one();
+        assert!(matches!(
+            backend.prove(&pk, wrong),
+            Err(ProofError::InvalidWitness)
+        ));
+
+        let small = PlonkBackend::new(Arc::new(Srs::insecure_setup(64, &mut OsRng)));
+        assert!(matches!(
+            small.setup(HashCircuit::new(3, 5)),
+            Err(ProofError::SetupError(_))
+        ));
+    }
+}
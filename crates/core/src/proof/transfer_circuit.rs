@@ -2,14 +2,20 @@
 //!
 //! This circuit proves that a private transfer is valid:
 //! 1. The sender knows the preimage of a commitment in the Merkle tree
+//!    (and that commitment is not the empty-leaf value 0)
 //! 2. The nullifier is correctly derived from the spending key and leaf index
 //! 3. The new commitment is correctly formed
-//! 4. Amount conservation is maintained (input = output for now)
+//! 4. Amount conservation is maintained: input = output + fee, with the
+//!    output and fee bounded to 64 bits
 //!
 //! Public Inputs:
 //! - merkle_root: The current Merkle tree root
 //! - nullifier: The nullifier for the spent note
 //! - new_commitment: The commitment to the output note
+//! - asset_id: The asset of both notes, which the program derives from the
+//!   pool's mint (see `asset_id_for_mint`)
+//! - fee: The part of the input paid to the relayer, which the program pays
+//!   out of the vault
 //!
 //! Private Inputs (Witness):
 //! - sender_secret: The secret used to derive the spending key
@@ -27,9 +33,33 @@ use ark_r1cs_std::{
     fields::fp::FpVar,
 };
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
-
-use super::gadgets::merkle::MerklePathGadget;
-use super::gadgets::poseidon::poseidon_hash2_gadget;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use super::gadgets::merkle::{enforce_nonempty_leaf, MerklePathGadget};
+use super::gadgets::poseidon::PoseidonGadget;
+use super::gadgets::range::enforce_u64;
+use super::ProofError;
+use crate::crypto::merkle::{MerklePath, TREE_DEPTH};
+use crate::crypto::nullifier::Note;
+use crate::crypto::poseidon::{poseidon_hash2, poseidon_hash4};
+
+/// Wrapped SOL mint, whose pool holds native SOL
+const NATIVE_MINT: &str = "So11111111111111111111111111111111111111112";
+
+/// Asset id of the notes held by the pool for `mint`
+///
+/// 0 for native SOL, otherwise the first 8 bytes (little-endian) of the
+/// SHA-256 of the base58 mint address, as the program and the Python SDK's
+/// `AssetRegistry` compute it.
+pub fn asset_id_for_mint(mint: &[u8; 32]) -> Fr {
+    let address = bs58::encode(mint).into_string();
+    if address == NATIVE_MINT {
+        return Fr::from(0u64);
+    }
+    let digest = Sha256::digest(address.as_bytes());
+    Fr::from(u64::from_le_bytes(digest[..8].try_into().unwrap()))
+}
 
 /// Transfer circuit for private transfers
 #[derive(Clone)]
@@ -41,6 +71,10 @@ pub struct TransferCircuit {
     pub nullifier: Option<Fr>,
     /// New commitment for the output note
     pub new_commitment: Option<Fr>,
+    /// Asset ID of both notes (0 for native SOL)
+    pub asset_id: Option<Fr>,
+    /// Part of the input paid to the relayer
+    pub fee: Option<Fr>,
 
     // ===== Private Inputs (Witness) =====
     /// Sender's secret (32 bytes as Fr)
@@ -49,8 +83,6 @@ pub struct TransferCircuit {
     pub input_amount: Option<Fr>,
     /// Blinding factor for the input commitment
     pub input_blinding: Option<Fr>,
-    /// Asset ID (0 for native SOL)
-    pub asset_id: Option<Fr>,
     /// Leaf index in the Merkle tree
     pub leaf_index: Option<u64>,
     /// Merkle path siblings
@@ -59,6 +91,10 @@ pub struct TransferCircuit {
     pub merkle_indices: Option<Vec<bool>>,
     /// Output blinding factor
     pub output_blinding: Option<Fr>,
+
+    // ===== Shape =====
+    /// Depth of the pool's tree, which fixes the circuit and so its keys
+    pub tree_depth: usize,
 }
 
 impl Default for TransferCircuit {
@@ -71,16 +107,22 @@ impl Default for TransferCircuit {
             input_amount: None,
             input_blinding: None,
             asset_id: None,
+            fee: None,
             leaf_index: None,
             merkle_path: None,
             merkle_indices: None,
             output_blinding: None,
+            tree_depth: TREE_DEPTH,
         }
     }
 }
 
 impl TransferCircuit {
-    /// Create a new transfer circuit with all values
+    /// Create a new fee-free transfer circuit with all values, for a pool
+    /// of the default depth
+    ///
+    /// Set `fee` afterwards for a transfer paying a relayer; the output
+    /// commitment must then hold the input amount minus the fee.
     pub fn new(
         merkle_root: Fr,
         nullifier: Fr,
@@ -102,19 +144,109 @@ impl TransferCircuit {
             input_amount: Some(input_amount),
             input_blinding: Some(input_blinding),
             asset_id: Some(asset_id),
+            fee: Some(Fr::from(0u64)),
             leaf_index: Some(leaf_index),
             merkle_path: Some(merkle_path),
             merkle_indices: Some(merkle_indices),
             output_blinding: Some(output_blinding),
+            tree_depth: TREE_DEPTH,
         }
     }
 
+    /// The same circuit for a pool of depth `tree_depth`
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Build the circuit that spends `note` into a re-blinded output note
+    ///
+    /// `merkle_path` must open the note's commitment at its leaf index under
+    /// `merkle_root`, and sets the tree depth. The output keeps the input's
+    /// owner and asset, and its amount less `fee`, which pays the relayer.
+    pub fn spend(
+        note: &Note,
+        merkle_path: &MerklePath,
+        merkle_root: Fr,
+        output_blinding: Fr,
+        fee: u64,
+    ) -> Result<Self, ProofError> {
+        let leaf_index = note.leaf_index.ok_or(ProofError::InvalidWitness)?;
+        if merkle_path.leaf_index != leaf_index
+            || !merkle_path.verify(&note.commitment(), &merkle_root)
+        {
+            return Err(ProofError::InvalidWitness);
+        }
+        let output_amount = note.amount.checked_sub(fee).ok_or(ProofError::InvalidWitness)?;
+
+        let spending_key = *note.spending_key().as_field();
+        let amount = Fr::from(output_amount);
+
+        // Mirrors constraints 4 to 6 below
+        let index_with_domain = poseidon_hash2(
+            &Fr::from(leaf_index),
+            &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+        );
+        let nullifier = poseidon_hash2(&spending_key, &index_with_domain);
+        let new_commitment = poseidon_hash4(&spending_key, &amount, &output_blinding, &note.asset_id);
+
+        let mut circuit = Self::new(
+            merkle_root,
+            nullifier,
+            new_commitment,
+            Fr::from_le_bytes_mod_order(&note.secret),
+            Fr::from(note.amount),
+            note.blinding,
+            note.asset_id,
+            leaf_index,
+            merkle_path.siblings.clone(),
+            merkle_path.indices.clone(),
+            output_blinding,
+        );
+        circuit.fee = Some(Fr::from(fee));
+        Ok(circuit.with_tree_depth(merkle_path.depth()))
+    }
+
+    /// Public inputs in circuit order: merkle_root, nullifier, new_commitment,
+    /// asset_id, fee
+    pub fn public_inputs(&self) -> Option<[Fr; Self::NUM_PUBLIC_INPUTS]> {
+        Some([
+            self.merkle_root?,
+            self.nullifier?,
+            self.new_commitment?,
+            self.asset_id?,
+            self.fee?,
+        ])
+    }
+
     /// Number of public inputs
-    pub const NUM_PUBLIC_INPUTS: usize = 3; // merkle_root, nullifier, new_commitment
+    pub const NUM_PUBLIC_INPUTS: usize = 5; // merkle_root, nullifier, new_commitment, asset_id, fee
+}
+
+impl Zeroize for TransferCircuit {
+    fn zeroize(&mut self) {
+        self.sender_secret.zeroize();
+        self.input_amount.zeroize();
+        self.input_blinding.zeroize();
+        self.leaf_index.zeroize();
+        self.merkle_path.zeroize();
+        self.merkle_indices.zeroize();
+        self.output_blinding.zeroize();
+    }
+}
+
+impl Drop for TransferCircuit {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
 }
 
 impl ConstraintSynthesizer<Fr> for TransferCircuit {
-    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+    fn generate_constraints(mut self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // One gadget per width for every hash below, rather than one per hash
+        let poseidon = PoseidonGadget::new(cs.clone())?;
+        let poseidon4 = PoseidonGadget::with_width(cs.clone(), 5)?;
+
         // ===== Allocate Public Inputs =====
         let merkle_root_var = FpVar::new_input(cs.clone(), || {
             self.merkle_root.ok_or(SynthesisError::AssignmentMissing)
@@ -128,6 +260,14 @@ impl ConstraintSynthesizer<Fr> for TransferCircuit {
             self.new_commitment.ok_or(SynthesisError::AssignmentMissing)
         })?;
 
+        let asset_id_var = FpVar::new_input(cs.clone(), || {
+            self.asset_id.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let fee_var = FpVar::new_input(cs.clone(), || {
+            self.fee.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
         // ===== Allocate Private Inputs (Witnesses) =====
         let sender_secret_var = FpVar::new_witness(cs.clone(), || {
             self.sender_secret.ok_or(SynthesisError::AssignmentMissing)
@@ -141,13 +281,10 @@ impl ConstraintSynthesizer<Fr> for TransferCircuit {
             self.input_blinding.ok_or(SynthesisError::AssignmentMissing)
         })?;
 
-        let asset_id_var = FpVar::new_witness(cs.clone(), || {
-            self.asset_id.ok_or(SynthesisError::AssignmentMissing)
+        let leaf_index_var = FpVar::new_witness(cs.clone(), || {
+            self.leaf_index.map(Fr::from).ok_or(SynthesisError::AssignmentMissing)
         })?;
 
-        let leaf_index = self.leaf_index.ok_or(SynthesisError::AssignmentMissing)?;
-        let leaf_index_var = FpVar::new_witness(cs.clone(), || Ok(Fr::from(leaf_index)))?;
-
         let output_blinding_var = FpVar::new_witness(cs.clone(), || {
             self.output_blinding.ok_or(SynthesisError::AssignmentMissing)
         })?;
@@ -158,20 +295,40 @@ impl ConstraintSynthesizer<Fr> for TransferCircuit {
             cs.clone(),
             Fr::from_le_bytes_mod_order(b"NYX_SPENDING_KEY"),
         )?;
-        let spending_key_var = poseidon_hash2_gadget(cs.clone(), &sender_secret_var, &domain_separator)?;
+        let spending_key_var = poseidon.hash2(cs.clone(), &sender_secret_var, &domain_separator)?;
 
         // ===== Constraint 2: Compute input commitment =====
-        // commitment = Poseidon(Poseidon(spending_key, amount), Poseidon(blinding, asset_id))
-        let h1 = poseidon_hash2_gadget(cs.clone(), &spending_key_var, &input_amount_var)?;
-        let h2 = poseidon_hash2_gadget(cs.clone(), &input_blinding_var, &asset_id_var)?;
-        let input_commitment_var = poseidon_hash2_gadget(cs.clone(), &h1, &h2)?;
+        // commitment = Poseidon(spending_key, amount, blinding, asset_id)
+        let input_commitment_var = poseidon4.hash(
+            cs.clone(),
+            &[
+                spending_key_var.clone(),
+                input_amount_var.clone(),
+                input_blinding_var.clone(),
+                asset_id_var.clone(),
+            ],
+        )?;
 
         // ===== Constraint 3: Verify Merkle membership =====
-        let merkle_path = self.merkle_path.ok_or(SynthesisError::AssignmentMissing)?;
-        let merkle_indices = self.merkle_indices.ok_or(SynthesisError::AssignmentMissing)?;
-
-        let path_gadget = MerklePathGadget::new_witness(cs.clone(), &merkle_path, &merkle_indices)?;
-        path_gadget.verify(cs.clone(), &input_commitment_var, &merkle_root_var)?;
+        // Unused positions hold 0 with publicly known zero-hash siblings, so
+        // the commitment must be non-zero. A Poseidon output hitting 0 is
+        // negligible, but enforcing it keeps soundness independent of that.
+        enforce_nonempty_leaf(&input_commitment_var)?;
+
+        // Key generation only needs the path's shape, not its values
+        let path = (self.merkle_path.take(), self.merkle_indices.take());
+        let (merkle_path, merkle_indices) = match path {
+            (Some(path), Some(indices)) => (path, indices),
+            _ if cs.is_in_setup_mode() => {
+                (vec![Fr::from(0u64); self.tree_depth], vec![false; self.tree_depth])
+            }
+            _ => return Err(SynthesisError::AssignmentMissing),
+        };
+
+        let path_gadget =
+            MerklePathGadget::new_witness(cs.clone(), self.tree_depth, &merkle_path, &merkle_indices)?;
+        path_gadget.verify_with(&poseidon, cs.clone(), &input_commitment_var, &merkle_root_var)?;
+        path_gadget.enforce_leaf_index(&leaf_index_var)?;
 
         // ===== Constraint 4: Verify nullifier derivation =====
         // nullifier = Poseidon(spending_key, hash(leaf_index || domain))
@@ -179,18 +336,31 @@ impl ConstraintSynthesizer<Fr> for TransferCircuit {
             cs.clone(),
             Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
         )?;
-        let index_with_domain = poseidon_hash2_gadget(cs.clone(), &leaf_index_var, &nullifier_domain)?;
-        let computed_nullifier = poseidon_hash2_gadget(cs.clone(), &spending_key_var, &index_with_domain)?;
+        let index_with_domain = poseidon.hash2(cs.clone(), &leaf_index_var, &nullifier_domain)?;
+        let computed_nullifier = poseidon.hash2(cs.clone(), &spending_key_var, &index_with_domain)?;
 
         // Enforce nullifier matches
         computed_nullifier.enforce_equal(&nullifier_var)?;
 
-        // ===== Constraint 5: Verify new commitment =====
+        // ===== Constraint 5: Amount conservation =====
+        // output = input - fee, with both bounded so the subtraction cannot
+        // wrap around the field
+        let output_amount_var = &input_amount_var - &fee_var;
+        enforce_u64(&fee_var)?;
+        enforce_u64(&output_amount_var)?;
+
+        // ===== Constraint 6: Verify new commitment =====
         // For transfers within the pool, the output uses the same spending key
         // This ensures only the original owner can spend the output
-        let h1_out = poseidon_hash2_gadget(cs.clone(), &spending_key_var, &input_amount_var)?;
-        let h2_out = poseidon_hash2_gadget(cs.clone(), &output_blinding_var, &asset_id_var)?;
-        let computed_new_commitment = poseidon_hash2_gadget(cs.clone(), &h1_out, &h2_out)?;
+        let computed_new_commitment = poseidon4.hash(
+            cs.clone(),
+            &[
+                spending_key_var.clone(),
+                output_amount_var.clone(),
+                output_blinding_var.clone(),
+                asset_id_var.clone(),
+            ],
+        )?;
 
         // Enforce new commitment matches
         computed_new_commitment.enforce_equal(&new_commitment_var)?;
@@ -208,13 +378,11 @@ mod tests {
 
     use crate::crypto::merkle::PoseidonMerkleTree;
     use crate::crypto::nullifier::{Nullifier, SpendingKey};
-    use crate::crypto::poseidon::poseidon_hash2;
+    use crate::crypto::poseidon::{poseidon_hash2, poseidon_hash4};
 
     /// Helper to compute note commitment
     fn compute_commitment(spending_key: &Fr, amount: &Fr, blinding: &Fr, asset_id: &Fr) -> Fr {
-        let h1 = poseidon_hash2(spending_key, amount);
-        let h2 = poseidon_hash2(blinding, asset_id);
-        poseidon_hash2(&h1, &h2)
+        poseidon_hash4(spending_key, amount, blinding, asset_id)
     }
 
     #[test]
@@ -316,6 +484,59 @@ mod tests {
         assert!(!cs.is_satisfied().unwrap());
     }
 
+    #[test]
+    fn test_transfer_circuit_rejects_unused_position() {
+        use crate::crypto::merkle::{verify_merkle_proof, TREE_DEPTH, ZERO_HASHES};
+
+        // Pool with one real note at index 0
+        let mut tree = PoseidonMerkleTree::new();
+        tree.insert(Fr::rand(&mut OsRng)).unwrap();
+        let merkle_root = tree.root();
+
+        // Opening for index 1 (== next_index) built from public data only:
+        // the real neighbour at level 0, zero hashes above
+        let leaf_index = tree.len();
+        let mut siblings = vec![tree.get_leaf(0).unwrap()];
+        siblings.extend_from_slice(&ZERO_HASHES[1..TREE_DEPTH]);
+        let indices: Vec<bool> = (0..TREE_DEPTH).map(|i| (leaf_index >> i) & 1 == 1).collect();
+
+        // The opening is valid for the empty leaf
+        assert!(verify_merkle_proof(&Fr::from(0u64), leaf_index, &siblings, &merkle_root));
+
+        // Fabricated note claimed at that position
+        let sender_secret = Fr::rand(&mut OsRng);
+        let input_amount = Fr::from(1_000_000u64);
+        let input_blinding = Fr::rand(&mut OsRng);
+        let output_blinding = Fr::rand(&mut OsRng);
+        let asset_id = Fr::from(0u64);
+
+        let domain = Fr::from_le_bytes_mod_order(b"NYX_SPENDING_KEY");
+        let spending_key = poseidon_hash2(&sender_secret, &domain);
+        let nullifier_domain = Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER");
+        let index_with_domain = poseidon_hash2(&Fr::from(leaf_index), &nullifier_domain);
+        let nullifier = poseidon_hash2(&spending_key, &index_with_domain);
+        let new_commitment = compute_commitment(&spending_key, &input_amount, &output_blinding, &asset_id);
+
+        let circuit = TransferCircuit::new(
+            merkle_root,
+            nullifier,
+            new_commitment,
+            sender_secret,
+            input_amount,
+            input_blinding,
+            asset_id,
+            leaf_index,
+            siblings,
+            indices,
+            output_blinding,
+        );
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
     #[test]
     fn test_transfer_circuit_invalid_merkle_proof() {
         let sender_secret = Fr::rand(&mut OsRng);
@@ -364,4 +585,79 @@ mod tests {
         // Should NOT be satisfied with corrupted Merkle proof
         assert!(!cs.is_satisfied().unwrap());
     }
+
+    #[test]
+    fn test_spend_from_note() {
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        tree.insert(Fr::rand(&mut OsRng)).unwrap();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(1).unwrap();
+
+        let output_blinding = Fr::rand(&mut OsRng);
+        let circuit =
+            TransferCircuit::spend(&note, &path, tree.root(), output_blinding, 30).unwrap();
+        let inputs = circuit.public_inputs().unwrap();
+        assert_eq!(inputs[0], tree.root());
+        assert_eq!(inputs[4], Fr::from(30u64));
+        let output = Note::new(note.secret, 970, note.asset_id, output_blinding);
+        assert_eq!(inputs[2], output.commitment());
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // The fee is bound: a relayer cannot raise it
+        let mut greedy = circuit;
+        greedy.fee = Some(Fr::from(40u64));
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        greedy.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // A fee above the amount, a stale root and a missing leaf index are
+        // rejected before proving
+        let greedy = TransferCircuit::spend(&note, &path, tree.root(), Fr::from(1u64), 1001);
+        assert!(matches!(greedy, Err(ProofError::InvalidWitness)));
+        let stale = TransferCircuit::spend(&note, &path, Fr::rand(&mut OsRng), Fr::from(1u64), 0);
+        assert!(matches!(stale, Err(ProofError::InvalidWitness)));
+        note.leaf_index = None;
+        let unplaced = TransferCircuit::spend(&note, &path, tree.root(), Fr::from(1u64), 0);
+        assert!(matches!(unplaced, Err(ProofError::InvalidWitness)));
+    }
+
+    #[test]
+    fn test_transfer_circuit_rejects_wrapped_fee() {
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+        let output_blinding = Fr::rand(&mut OsRng);
+        let mut circuit =
+            TransferCircuit::spend(&note, &path, tree.root(), output_blinding, 0).unwrap();
+
+        // A "negative" fee would mint value into the output
+        let fee = -Fr::from(500u64);
+        let spending_key = *note.spending_key().as_field();
+        circuit.fee = Some(fee);
+        circuit.new_commitment = Some(poseidon_hash4(
+            &spending_key,
+            &(Fr::from(1000u64) - fee),
+            &output_blinding,
+            &note.asset_id,
+        ));
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_asset_id_for_mint() {
+        let mint = |address: &str| -> [u8; 32] {
+            bs58::decode(address).into_vec().unwrap().try_into().unwrap()
+        };
+        assert_eq!(asset_id_for_mint(&mint(NATIVE_MINT)), Fr::from(0u64));
+        // Same vector as the program's `asset_id` test
+        let usdc = mint("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        assert_eq!(asset_id_for_mint(&usdc), Fr::from(0x67e6_c237_f1bb_49f2u64));
+    }
 }
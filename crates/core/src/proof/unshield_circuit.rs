@@ -0,0 +1,433 @@
+//! Unshield Circuit for Withdrawals
+//!
+//! This circuit proves that a withdrawal of a note to a public account is
+//! valid:
+//! 1. The owner knows the preimage of a commitment in the Merkle tree
+//!    (and that commitment is not the empty-leaf value 0)
+//! 2. The nullifier is correctly derived from the spending key and leaf index
+//! 3. The withdrawn amount is the note's amount
+//! 4. The amount covers the relayer fee: amount = payout + relayer_fee,
+//!    with the payout and fee bounded to 64 bits
+//!
+//! The recipient and relayer fee are public inputs, so a relayer submitting
+//! the proof cannot redirect the funds or raise its fee. So is the note's
+//! asset id, which the program checks against the pool's mint, so a note of
+//! one asset cannot be withdrawn from another asset's pool.
+//!
+//! Public Inputs:
+//! - merkle_root: The Merkle tree root the note is proven against
+//! - nullifier: The nullifier for the spent note
+//! - recipient_hash: `recipient_hash(recipient)` of the receiving account
+//! - amount: The amount withdrawn, including the relayer fee
+//! - relayer_fee: The part of `amount` paid to the relayer
+//! - asset_id: The asset of the note (0 for native SOL)
+//!
+//! Private Inputs (Witness):
+//! - sender_secret: The secret used to derive the spending key
+//! - input_amount: The amount in the input note
+//! - input_blinding: The blinding factor for the input commitment
+//! - leaf_index: The index of the input commitment in the Merkle tree
+//! - merkle_path: The sibling hashes in the Merkle path
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use super::gadgets::merkle::{enforce_nonempty_leaf, MerklePathGadget};
+use super::gadgets::poseidon::PoseidonGadget;
+use super::gadgets::range::enforce_u64;
+use super::ProofError;
+use crate::crypto::merkle::{MerklePath, TREE_DEPTH};
+use crate::crypto::nullifier::Note;
+use crate::crypto::poseidon::poseidon_hash2;
+
+/// Hash of a recipient account as the unshield circuit takes it
+///
+/// SHA-256 of the 32-byte key with the first byte cleared, read big-endian,
+/// so it always fits the BN254 scalar field. The program computes the same
+/// value from the recipient account.
+pub fn recipient_hash(recipient: &[u8; 32]) -> Fr {
+    Fr::from_be_bytes_mod_order(&recipient_hash_bytes(recipient))
+}
+
+/// `recipient_hash` as the 32-byte big-endian public input
+pub fn recipient_hash_bytes(recipient: &[u8; 32]) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::digest(recipient).into();
+    hash[0] = 0;
+    hash
+}
+
+/// Unshield circuit for withdrawals
+#[derive(Clone)]
+pub struct UnshieldCircuit {
+    // ===== Public Inputs =====
+    /// Merkle root the note is proven against
+    pub merkle_root: Option<Fr>,
+    /// Nullifier for the spent note
+    pub nullifier: Option<Fr>,
+    /// Hash of the receiving account
+    pub recipient_hash: Option<Fr>,
+    /// Amount withdrawn, including the relayer fee
+    pub amount: Option<Fr>,
+    /// Part of `amount` paid to the relayer
+    pub relayer_fee: Option<Fr>,
+    /// Asset ID (0 for native SOL)
+    pub asset_id: Option<Fr>,
+
+    // ===== Private Inputs (Witness) =====
+    /// Owner's secret (32 bytes as Fr)
+    pub sender_secret: Option<Fr>,
+    /// Amount in the input note
+    pub input_amount: Option<Fr>,
+    /// Blinding factor for the input commitment
+    pub input_blinding: Option<Fr>,
+    /// Leaf index in the Merkle tree
+    pub leaf_index: Option<u64>,
+    /// Merkle path siblings
+    pub merkle_path: Option<Vec<Fr>>,
+    /// Merkle path indices (left/right)
+    pub merkle_indices: Option<Vec<bool>>,
+
+    // ===== Shape =====
+    /// Depth of the pool's tree, which fixes the circuit and so its keys
+    pub tree_depth: usize,
+}
+
+impl Default for UnshieldCircuit {
+    fn default() -> Self {
+        Self {
+            merkle_root: None,
+            nullifier: None,
+            recipient_hash: None,
+            amount: None,
+            relayer_fee: None,
+            asset_id: None,
+            sender_secret: None,
+            input_amount: None,
+            input_blinding: None,
+            leaf_index: None,
+            merkle_path: None,
+            merkle_indices: None,
+            tree_depth: TREE_DEPTH,
+        }
+    }
+}
+
+impl UnshieldCircuit {
+    /// Number of public inputs: merkle_root, nullifier, recipient_hash,
+    /// amount, relayer_fee, asset_id
+    pub const NUM_PUBLIC_INPUTS: usize = 6;
+
+    /// Build the circuit that withdraws `note` to `recipient`
+    ///
+    /// `merkle_path` must open the note's commitment at its leaf index under
+    /// `merkle_root`, and sets the tree depth. The whole note is withdrawn;
+    /// `relayer_fee` of it goes to the relayer.
+    pub fn withdraw(
+        note: &Note,
+        merkle_path: &MerklePath,
+        merkle_root: Fr,
+        recipient: &[u8; 32],
+        relayer_fee: u64,
+    ) -> Result<Self, ProofError> {
+        let leaf_index = note.leaf_index.ok_or(ProofError::InvalidWitness)?;
+        if merkle_path.leaf_index != leaf_index
+            || !merkle_path.verify(&note.commitment(), &merkle_root)
+            || relayer_fee > note.amount
+        {
+            return Err(ProofError::InvalidWitness);
+        }
+
+        // Mirrors constraint 4 below (the circuit's derivation, not `Note::nullifier`)
+        let index_with_domain = poseidon_hash2(
+            &Fr::from(leaf_index),
+            &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+        );
+        let nullifier = poseidon_hash2(note.spending_key().as_field(), &index_with_domain);
+
+        let amount = Fr::from(note.amount);
+        Ok(Self {
+            merkle_root: Some(merkle_root),
+            nullifier: Some(nullifier),
+            recipient_hash: Some(recipient_hash(recipient)),
+            amount: Some(amount),
+            relayer_fee: Some(Fr::from(relayer_fee)),
+            asset_id: Some(note.asset_id),
+            sender_secret: Some(Fr::from_le_bytes_mod_order(&note.secret)),
+            input_amount: Some(amount),
+            input_blinding: Some(note.blinding),
+            leaf_index: Some(leaf_index),
+            merkle_path: Some(merkle_path.siblings.clone()),
+            merkle_indices: Some(merkle_path.indices.clone()),
+            tree_depth: merkle_path.depth(),
+        })
+    }
+
+    /// The same circuit for a pool of depth `tree_depth`
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+
+    /// Public inputs in circuit order: merkle_root, nullifier,
+    /// recipient_hash, amount, relayer_fee, asset_id
+    pub fn public_inputs(&self) -> Option<[Fr; Self::NUM_PUBLIC_INPUTS]> {
+        Some([
+            self.merkle_root?,
+            self.nullifier?,
+            self.recipient_hash?,
+            self.amount?,
+            self.relayer_fee?,
+            self.asset_id?,
+        ])
+    }
+}
+
+impl Zeroize for UnshieldCircuit {
+    fn zeroize(&mut self) {
+        self.sender_secret.zeroize();
+        self.input_amount.zeroize();
+        self.input_blinding.zeroize();
+        self.leaf_index.zeroize();
+        self.merkle_path.zeroize();
+        self.merkle_indices.zeroize();
+    }
+}
+
+impl Drop for UnshieldCircuit {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for UnshieldCircuit {
+    fn generate_constraints(mut self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // One gadget per width for every hash below, rather than one per hash
+        let poseidon = PoseidonGadget::new(cs.clone())?;
+        let poseidon4 = PoseidonGadget::with_width(cs.clone(), 5)?;
+
+        // ===== Allocate Public Inputs =====
+        let merkle_root_var = FpVar::new_input(cs.clone(), || {
+            self.merkle_root.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let nullifier_var = FpVar::new_input(cs.clone(), || {
+            self.nullifier.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let recipient_hash_var = FpVar::new_input(cs.clone(), || {
+            self.recipient_hash.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let amount_var = FpVar::new_input(cs.clone(), || {
+            self.amount.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let relayer_fee_var = FpVar::new_input(cs.clone(), || {
+            self.relayer_fee.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let asset_id_var = FpVar::new_input(cs.clone(), || {
+            self.asset_id.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        // ===== Allocate Private Inputs (Witnesses) =====
+        let sender_secret_var = FpVar::new_witness(cs.clone(), || {
+            self.sender_secret.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let input_amount_var = FpVar::new_witness(cs.clone(), || {
+            self.input_amount.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let input_blinding_var = FpVar::new_witness(cs.clone(), || {
+            self.input_blinding.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let leaf_index_var = FpVar::new_witness(cs.clone(), || {
+            self.leaf_index.map(Fr::from).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        // ===== Constraint 1: Compute spending key =====
+        let domain_separator = FpVar::new_constant(
+            cs.clone(),
+            Fr::from_le_bytes_mod_order(b"NYX_SPENDING_KEY"),
+        )?;
+        let spending_key_var = poseidon.hash2(cs.clone(), &sender_secret_var, &domain_separator)?;
+
+        // ===== Constraint 2: Compute input commitment =====
+        let input_commitment_var = poseidon4.hash(
+            cs.clone(),
+            &[
+                spending_key_var.clone(),
+                input_amount_var.clone(),
+                input_blinding_var.clone(),
+                asset_id_var.clone(),
+            ],
+        )?;
+
+        // ===== Constraint 3: Verify Merkle membership =====
+        // As in the transfer circuit, the empty leaf cannot be spent
+        enforce_nonempty_leaf(&input_commitment_var)?;
+
+        let path = (self.merkle_path.take(), self.merkle_indices.take());
+        let (merkle_path, merkle_indices) = match path {
+            (Some(path), Some(indices)) => (path, indices),
+            _ if cs.is_in_setup_mode() => {
+                (vec![Fr::from(0u64); self.tree_depth], vec![false; self.tree_depth])
+            }
+            _ => return Err(SynthesisError::AssignmentMissing),
+        };
+
+        let path_gadget =
+            MerklePathGadget::new_witness(cs.clone(), self.tree_depth, &merkle_path, &merkle_indices)?;
+        path_gadget.verify_with(&poseidon, cs.clone(), &input_commitment_var, &merkle_root_var)?;
+        path_gadget.enforce_leaf_index(&leaf_index_var)?;
+
+        // ===== Constraint 4: Verify nullifier derivation =====
+        let nullifier_domain = FpVar::new_constant(
+            cs.clone(),
+            Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+        )?;
+        let index_with_domain = poseidon.hash2(cs.clone(), &leaf_index_var, &nullifier_domain)?;
+        let computed_nullifier = poseidon.hash2(cs.clone(), &spending_key_var, &index_with_domain)?;
+        computed_nullifier.enforce_equal(&nullifier_var)?;
+
+        // ===== Constraint 5: The whole note is withdrawn =====
+        input_amount_var.enforce_equal(&amount_var)?;
+
+        // ===== Constraint 6: The amount covers the fee =====
+        // payout = amount - relayer_fee, bounded so that a fee above the
+        // amount cannot wrap around the field
+        let payout_var = &amount_var - &relayer_fee_var;
+        enforce_u64(&relayer_fee_var)?;
+        enforce_u64(&payout_var)?;
+
+        // ===== Constraint 7: Bind recipient =====
+        // It enters no other constraint; squaring it adds one that uses it,
+        // so the proof cannot be replayed with another recipient
+        let _recipient_square = &recipient_hash_var * &recipient_hash_var;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::UniformRand;
+    use ark_relations::r1cs::ConstraintSystem;
+    use rand::rngs::OsRng;
+
+    use crate::crypto::merkle::PoseidonMerkleTree;
+    use crate::proof::asset_id_for_mint;
+
+    fn withdrawal(relayer_fee: u64) -> (UnshieldCircuit, Note) {
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        tree.insert(Fr::rand(&mut OsRng)).unwrap();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(1).unwrap();
+        let circuit =
+            UnshieldCircuit::withdraw(&note, &path, tree.root(), &[7u8; 32], relayer_fee).unwrap();
+        (circuit, note)
+    }
+
+    fn is_satisfied(circuit: UnshieldCircuit) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_unshield_circuit_valid() {
+        let (circuit, note) = withdrawal(25);
+        let inputs = circuit.public_inputs().unwrap();
+        let spending_key = *note.spending_key().as_field();
+        let index_with_domain = poseidon_hash2(
+            &Fr::from(note.leaf_index.unwrap()),
+            &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+        );
+        assert_eq!(inputs[1], poseidon_hash2(&spending_key, &index_with_domain));
+        assert_eq!(inputs[2], recipient_hash(&[7u8; 32]));
+        assert_eq!(inputs[3], Fr::from(1000u64));
+        assert_eq!(inputs[4], Fr::from(25u64));
+        assert_eq!(inputs[5], Fr::from(0u64));
+        assert!(is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_unshield_circuit_rejects_wrong_asset() {
+        // A SOL note claimed as another asset, to withdraw from its pool
+        let (mut circuit, _) = withdrawal(0);
+        circuit.asset_id = Some(asset_id_for_mint(&[3u8; 32]));
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_unshield_circuit_rejects_wrong_amount() {
+        let (mut circuit, _) = withdrawal(0);
+        circuit.amount = Some(Fr::from(2000u64));
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_unshield_circuit_rejects_fee_above_amount() {
+        let (mut circuit, _) = withdrawal(0);
+        circuit.relayer_fee = Some(Fr::from(1001u64));
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_unshield_circuit_rejects_wrong_nullifier() {
+        let (mut circuit, _) = withdrawal(0);
+        circuit.nullifier = Some(Fr::rand(&mut OsRng));
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_unshield_circuit_rejects_leaf_index_off_path() {
+        // A nullifier honestly derived from another index still needs the
+        // path to open that index
+        let (mut circuit, note) = withdrawal(0);
+        let spending_key = *note.spending_key().as_field();
+        let index_with_domain =
+            poseidon_hash2(&Fr::from(5u64), &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"));
+        circuit.leaf_index = Some(5);
+        circuit.nullifier = Some(poseidon_hash2(&spending_key, &index_with_domain));
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_withdraw_rejects_bad_witness() {
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+
+        // A fee above the note's amount and a stale root
+        let greedy = UnshieldCircuit::withdraw(&note, &path, tree.root(), &[7u8; 32], 1001);
+        assert!(matches!(greedy, Err(ProofError::InvalidWitness)));
+        let stale = UnshieldCircuit::withdraw(&note, &path, Fr::rand(&mut OsRng), &[7u8; 32], 0);
+        assert!(matches!(stale, Err(ProofError::InvalidWitness)));
+    }
+
+    #[test]
+    fn test_recipient_hash_fits_field() {
+        let bytes = recipient_hash_bytes(&[0xffu8; 32]);
+        assert_eq!(bytes[0], 0);
+        // Same vector as the program's `recipient_hash` test
+        assert_eq!(
+            hex::encode(recipient_hash_bytes(&[7u8; 32])),
+            "00b06f8e4e3a7715d201d573d0aa423762e55dabd61a2c02278fa56cc6d294e0"
+        );
+        assert_ne!(recipient_hash(&[1u8; 32]), recipient_hash(&[2u8; 32]));
+        assert_eq!(
+            super::super::field_to_bytes_be(&recipient_hash(&[0xffu8; 32])),
+            bytes
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Witness construction for the transfer circuit
+//!
+//! `WitnessBuilder` turns a note and the tree (or a stored path) holding it
+//! into a ready-to-prove `TransferCircuit`, deriving the nullifier, Merkle
+//! path and output commitment. Given the note's epoch and the pool's, it
+//! refuses notes from a previous epoch before building anything.
+//!
+//! ```text
+//! let witness = WitnessBuilder::new(&note)
+//!     .with_tree(&tree)
+//!     .with_fee(5_000)
+//!     .build()?;
+//! let proof = system.prove(witness.circuit)?;
+//! ```
+
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use rand::rngs::OsRng;
+
+use super::{field_to_bytes_be, ProofError, TransferCircuit};
+use crate::crypto::merkle::{MerklePath, PoseidonMerkleTree};
+use crate::crypto::nullifier::Note;
+use crate::epoch::PoolEpoch;
+use crate::wallet::StoredNote;
+
+/// Where the builder takes the spent note's Merkle path from
+enum PathSource<'a> {
+    Tree(&'a PoseidonMerkleTree),
+    Stored(MerklePath, Fr),
+}
+
+/// Builds the transfer circuit spending a note
+pub struct WitnessBuilder<'a> {
+    note: &'a Note,
+    path: Option<PathSource<'a>>,
+    output_blinding: Option<Fr>,
+    fee: u64,
+    /// The note's epoch and the pool's
+    epochs: Option<(PoolEpoch, PoolEpoch)>,
+}
+
+/// A transfer circuit ready to prove, with what the caller needs around it
+pub struct SpendWitness {
+    /// The circuit, for `TransferProofSystem::prove`
+    pub circuit: TransferCircuit,
+    /// merkle_root, nullifier, new_commitment, asset_id, fee as the program
+    /// expects them: 32-byte big-endian field elements
+    pub public_inputs: [[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS],
+    /// The output note, to track once its commitment is inserted
+    pub output_note: Note,
+}
+
+impl<'a> WitnessBuilder<'a> {
+    /// Start building a spend of `note`, which must have its leaf index set
+    pub fn new(note: &'a Note) -> Self {
+        Self {
+            note,
+            path: None,
+            output_blinding: None,
+            fee: 0,
+            epochs: None,
+        }
+    }
+
+    /// Start building a spend of a stored note against a pool at `pool`
+    pub fn for_stored(stored: &'a StoredNote, pool: PoolEpoch) -> Self {
+        Self::new(&stored.note).with_epochs(stored.epoch, pool)
+    }
+
+    /// Check that a note received in `note` can be spent against `pool`
+    pub fn with_epochs(mut self, note: PoolEpoch, pool: PoolEpoch) -> Self {
+        self.epochs = Some((note, pool));
+        self
+    }
+
+    /// Take the note's path and the root from `tree`
+    pub fn with_tree(mut self, tree: &'a PoseidonMerkleTree) -> Self {
+        self.path = Some(PathSource::Tree(tree));
+        self
+    }
+
+    /// Use a stored path to the note under `merkle_root`
+    pub fn with_path(mut self, merkle_path: MerklePath, merkle_root: Fr) -> Self {
+        self.path = Some(PathSource::Stored(merkle_path, merkle_root));
+        self
+    }
+
+    /// Blinding for the output note; random if not set
+    pub fn with_output_blinding(mut self, output_blinding: Fr) -> Self {
+        self.output_blinding = Some(output_blinding);
+        self
+    }
+
+    /// Part of the note's amount paid to the relayer
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Build the circuit
+    ///
+    /// Fails with `NoteFromPreviousEpoch` if the epochs were given and
+    /// differ, and with `InvalidWitness` if no path was given, the note has
+    /// no leaf index or is not under the root, or the fee exceeds its amount.
+    pub fn build(self) -> Result<SpendWitness, ProofError> {
+        if let Some((note, pool)) = self.epochs {
+            note.ensure_compatible(&pool)
+                .map_err(|_| ProofError::NoteFromPreviousEpoch { note, pool })?;
+        }
+        let leaf_index = self.note.leaf_index.ok_or(ProofError::InvalidWitness)?;
+        let (merkle_path, merkle_root) = match self.path.ok_or(ProofError::InvalidWitness)? {
+            PathSource::Tree(tree) => (
+                tree.generate_proof(leaf_index).map_err(|_| ProofError::InvalidWitness)?,
+                tree.root(),
+            ),
+            PathSource::Stored(path, root) => (path, root),
+        };
+        let output_blinding = self.output_blinding.unwrap_or_else(|| Fr::rand(&mut OsRng));
+
+        let circuit = TransferCircuit::spend(
+            self.note,
+            &merkle_path,
+            merkle_root,
+            output_blinding,
+            self.fee,
+        )?;
+        let public_inputs = circuit
+            .public_inputs()
+            .ok_or(ProofError::InvalidWitness)?
+            .map(|input| field_to_bytes_be(&input));
+        let output_note = Note::new(
+            self.note.secret,
+            self.note.amount - self.fee,
+            self.note.asset_id,
+            output_blinding,
+        );
+        Ok(SpendWitness {
+            circuit,
+            public_inputs,
+            output_note,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use crate::wallet::NoteStore;
+
+    fn note_in_tree() -> (Note, PoseidonMerkleTree) {
+        let mut tree = PoseidonMerkleTree::new();
+        tree.insert(Fr::from(7u64)).unwrap();
+        let mut note = Note::new([3u8; 32], 1000, Fr::from(0u64), Fr::from(11u64));
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        (note, tree)
+    }
+
+    #[test]
+    fn test_build_from_tree() {
+        let (note, tree) = note_in_tree();
+        let witness = WitnessBuilder::new(&note)
+            .with_tree(&tree)
+            .with_output_blinding(Fr::from(5u64))
+            .with_fee(30)
+            .build()
+            .unwrap();
+
+        assert_eq!(witness.output_note.amount, 970);
+        assert_eq!(witness.public_inputs[0], field_to_bytes_be(&tree.root()));
+        assert_eq!(
+            witness.public_inputs[2],
+            field_to_bytes_be(&witness.output_note.commitment())
+        );
+        assert_eq!(witness.public_inputs[4], field_to_bytes_be(&Fr::from(30u64)));
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        witness.circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // A stored path gives the same circuit
+        let path = tree.generate_proof(1).unwrap();
+        let stored = WitnessBuilder::new(&note)
+            .with_path(path, tree.root())
+            .with_output_blinding(Fr::from(5u64))
+            .with_fee(30)
+            .build()
+            .unwrap();
+        assert_eq!(stored.public_inputs, witness.public_inputs);
+    }
+
+    #[test]
+    fn test_build_rejects_bad_witness() {
+        let (note, tree) = note_in_tree();
+        assert!(WitnessBuilder::new(&note).build().is_err());
+        assert!(WitnessBuilder::new(&note).with_tree(&tree).with_fee(1001).build().is_err());
+
+        let path = tree.generate_proof(0).unwrap();
+        assert!(WitnessBuilder::new(&note).with_path(path, tree.root()).build().is_err());
+
+        let mut unplaced = note.clone();
+        unplaced.leaf_index = None;
+        assert!(WitnessBuilder::new(&unplaced).with_tree(&tree).build().is_err());
+    }
+
+    #[test]
+    fn test_build_checks_epoch() {
+        let (note, tree) = note_in_tree();
+        let mut store = NoteStore::new();
+        store.insert(note, PoolEpoch::current(0)).unwrap();
+        let stored = store.get(1).unwrap();
+
+        assert!(WitnessBuilder::for_stored(stored, PoolEpoch::current(0))
+            .with_tree(&tree)
+            .build()
+            .is_ok());
+        let rotated = WitnessBuilder::for_stored(stored, PoolEpoch::current(1))
+            .with_tree(&tree)
+            .build();
+        assert!(matches!(
+            rotated,
+            Err(ProofError::NoteFromPreviousEpoch { note, pool })
+                if note == PoolEpoch::current(0) && pool == PoolEpoch::current(1)
+        ));
+    }
+}
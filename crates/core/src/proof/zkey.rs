@@ -0,0 +1,520 @@
+//! snarkjs key import
+//!
+//! Parses the keys of a circom/snarkjs trusted setup: the binary `.zkey`
+//! into an arkworks `ProvingKey` plus the circuit's constraint matrices,
+//! and `verification_key.json` into a `VerifyingKey`.
+//!
+//! `.zkey` stores base field elements little-endian in Montgomery form and
+//! the matrix coefficients in Montgomery form twice over. Its A/B matrices
+//! also carry one constraint per public input (and one for the constant),
+//! which arkworks adds itself, so those rows are dropped.
+//!
+//! A verifying key can be used as is, e.g. with
+//! `SolanaVerifyingKey::from_arkworks`. The proving key's H query follows
+//! snarkjs' QAP reduction rather than ark-groth16's default, so proving with
+//! it needs a matching witness map over the returned matrices (as
+//! ark-circom's `CircomReduction` does).
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, BigInteger256, PrimeField, Zero};
+use ark_groth16::{ProvingKey, VerifyingKey};
+use ark_relations::r1cs::ConstraintMatrices;
+use serde::Deserialize;
+
+use super::ProofError;
+
+/// `.zkey` file magic
+const ZKEY_MAGIC: &[u8; 4] = b"zkey";
+/// Protocol id of Groth16 keys in the header section
+const GROTH16_PROTOCOL: u32 = 1;
+
+// Section ids
+const SECTION_HEADER: u32 = 1;
+const SECTION_GROTH16_HEADER: u32 = 2;
+const SECTION_IC: u32 = 3;
+const SECTION_COEFFS: u32 = 4;
+const SECTION_A: u32 = 5;
+const SECTION_B_G1: u32 = 6;
+const SECTION_B_G2: u32 = 7;
+const SECTION_C: u32 = 8;
+const SECTION_H: u32 = 9;
+
+/// A proving key imported from a `.zkey` file
+pub struct Zkey {
+    /// The Groth16 proving key, including the verifying key
+    pub proving_key: ProvingKey<Bn254>,
+    /// The circuit's A and B matrices, without the public input rows
+    pub matrices: ConstraintMatrices<Fr>,
+}
+
+/// Parse a snarkjs Groth16 `.zkey` file
+pub fn read_zkey(bytes: &[u8]) -> Result<Zkey, ProofError> {
+    let sections = read_sections(bytes)?;
+    let section = |id: u32| {
+        sections
+            .get(&id)
+            .map(|&range| Reader::new(range))
+            .ok_or_else(|| zkey_error(format!("missing section {}", id)))
+    };
+
+    if section(SECTION_HEADER)?.u32()? != GROTH16_PROTOCOL {
+        return Err(zkey_error("not a Groth16 key"));
+    }
+
+    let mut header = section(SECTION_GROTH16_HEADER)?;
+    let n8q = header.u32()? as usize;
+    if header.take(n8q)? != Fq::MODULUS.to_bytes_le() {
+        return Err(zkey_error("base field is not BN254's"));
+    }
+    let n8r = header.u32()? as usize;
+    if header.take(n8r)? != Fr::MODULUS.to_bytes_le() {
+        return Err(zkey_error("scalar field is not BN254's"));
+    }
+    let n_vars = header.u32()? as usize;
+    let n_public = header.u32()? as usize;
+    let domain_size = header.u32()? as usize;
+    let alpha_g1 = header.g1()?;
+    let beta_g1 = header.g1()?;
+    let beta_g2 = header.g2()?;
+    let gamma_g2 = header.g2()?;
+    let delta_g1 = header.g1()?;
+    let delta_g2 = header.g2()?;
+    if n_vars <= n_public {
+        return Err(zkey_error("fewer variables than public inputs"));
+    }
+
+    let vk = VerifyingKey {
+        alpha_g1,
+        beta_g2,
+        gamma_g2,
+        delta_g2,
+        gamma_abc_g1: section(SECTION_IC)?.g1_vec(n_public + 1)?,
+    };
+    let proving_key = ProvingKey {
+        vk,
+        beta_g1,
+        delta_g1,
+        a_query: section(SECTION_A)?.g1_vec(n_vars)?,
+        b_g1_query: section(SECTION_B_G1)?.g1_vec(n_vars)?,
+        b_g2_query: section(SECTION_B_G2)?.g2_vec(n_vars)?,
+        h_query: section(SECTION_H)?.g1_vec(domain_size)?,
+        l_query: section(SECTION_C)?.g1_vec(n_vars - n_public - 1)?,
+    };
+    let matrices = read_matrices(section(SECTION_COEFFS)?, n_vars, n_public, domain_size)?;
+
+    Ok(Zkey {
+        proving_key,
+        matrices,
+    })
+}
+
+/// Read the coefficient section into arkworks' matrix layout
+fn read_matrices(
+    mut reader: Reader,
+    n_vars: usize,
+    n_public: usize,
+    domain_size: usize,
+) -> Result<ConstraintMatrices<Fr>, ProofError> {
+    let num_coeffs = reader.u32()?;
+    let mut matrices = vec![vec![Vec::new(); domain_size]; 2];
+    let mut num_rows = 0;
+    for _ in 0..num_coeffs {
+        let matrix = reader.u32()? as usize;
+        let constraint = reader.u32()? as usize;
+        let signal = reader.u32()? as usize;
+        let value = reader.coefficient()?;
+        if matrix > 1 || constraint >= domain_size || signal >= n_vars {
+            return Err(zkey_error("coefficient out of range"));
+        }
+        num_rows = num_rows.max(constraint + 1);
+        matrices[matrix][constraint].push((value, signal));
+    }
+
+    // The last rows bind the public inputs and the constant; arkworks adds
+    // its own
+    let num_constraints = num_rows.saturating_sub(n_public + 1);
+    let mut matrices = matrices.into_iter().map(|mut m| {
+        m.truncate(num_constraints);
+        m
+    });
+    let (a, b) = (matrices.next().unwrap(), matrices.next().unwrap());
+    Ok(ConstraintMatrices {
+        num_instance_variables: n_public + 1,
+        num_witness_variables: n_vars - n_public - 1,
+        num_constraints,
+        a_num_non_zero: a.iter().map(Vec::len).sum(),
+        b_num_non_zero: b.iter().map(Vec::len).sum(),
+        c_num_non_zero: 0,
+        a,
+        b,
+        c: Vec::new(),
+    })
+}
+
+/// Split a `.zkey` file into its sections by id
+fn read_sections(bytes: &[u8]) -> Result<HashMap<u32, &[u8]>, ProofError> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(4)? != ZKEY_MAGIC {
+        return Err(zkey_error("bad magic"));
+    }
+    let _version = reader.u32()?;
+    let num_sections = reader.u32()?;
+    let mut sections = HashMap::new();
+    for _ in 0..num_sections {
+        let id = reader.u32()?;
+        let size = usize::try_from(reader.u64()?).map_err(|_| zkey_error("section too large"))?;
+        sections.insert(id, reader.take(size)?);
+    }
+    Ok(sections)
+}
+
+/// Cursor over a `.zkey` section
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProofError> {
+        if self.bytes.len() < len {
+            return Err(zkey_error("truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProofError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProofError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bigint(&mut self) -> Result<BigInteger256, ProofError> {
+        let bytes = self.take(32)?;
+        let limbs = std::array::from_fn(|i| {
+            u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap())
+        });
+        Ok(BigInteger256::new(limbs))
+    }
+
+    /// Base field element in Montgomery form
+    fn fq(&mut self) -> Result<Fq, ProofError> {
+        let repr = self.bigint()?;
+        if repr >= Fq::MODULUS {
+            return Err(zkey_error("non-canonical field element"));
+        }
+        Ok(Fq::new_unchecked(repr))
+    }
+
+    /// Matrix coefficient, in Montgomery form twice over
+    fn coefficient(&mut self) -> Result<Fr, ProofError> {
+        let repr = self.bigint()?;
+        if repr >= Fr::MODULUS {
+            return Err(zkey_error("non-canonical coefficient"));
+        }
+        Ok(Fr::new_unchecked(Fr::new_unchecked(repr).into_bigint()))
+    }
+
+    fn g1(&mut self) -> Result<G1Affine, ProofError> {
+        let (x, y) = (self.fq()?, self.fq()?);
+        g1_point(x, y).ok_or_else(|| zkey_error("G1 point not on the curve"))
+    }
+
+    fn g2(&mut self) -> Result<G2Affine, ProofError> {
+        let x = Fq2::new(self.fq()?, self.fq()?);
+        let y = Fq2::new(self.fq()?, self.fq()?);
+        g2_point(x, y).ok_or_else(|| zkey_error("G2 point not in the subgroup"))
+    }
+
+    fn g1_vec(&mut self, len: usize) -> Result<Vec<G1Affine>, ProofError> {
+        (0..len).map(|_| self.g1()).collect()
+    }
+
+    fn g2_vec(&mut self, len: usize) -> Result<Vec<G2Affine>, ProofError> {
+        (0..len).map(|_| self.g2()).collect()
+    }
+}
+
+/// snarkjs `verification_key.json`
+#[derive(Deserialize)]
+struct VerificationKeyJson {
+    protocol: String,
+    curve: String,
+    #[serde(rename = "nPublic")]
+    n_public: usize,
+    vk_alpha_1: Vec<String>,
+    vk_beta_2: Vec<Vec<String>>,
+    vk_gamma_2: Vec<Vec<String>>,
+    vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "IC")]
+    ic: Vec<Vec<String>>,
+}
+
+/// Parse a snarkjs Groth16 `verification_key.json`
+///
+/// Points are decimal coordinates with a trailing `z` of 1, or 0 for the
+/// point at infinity; G2 coordinates list c0 before c1.
+pub fn read_verification_key_json(json: &str) -> Result<VerifyingKey<Bn254>, ProofError> {
+    let key: VerificationKeyJson =
+        serde_json::from_str(json).map_err(|e| vkey_error(e.to_string()))?;
+    if key.protocol != "groth16" || key.curve != "bn128" {
+        return Err(vkey_error("not a BN254 Groth16 key"));
+    }
+    if key.ic.len() != key.n_public + 1 {
+        return Err(vkey_error("IC does not match nPublic"));
+    }
+
+    Ok(VerifyingKey {
+        alpha_g1: json_g1(&key.vk_alpha_1)?,
+        beta_g2: json_g2(&key.vk_beta_2)?,
+        gamma_g2: json_g2(&key.vk_gamma_2)?,
+        delta_g2: json_g2(&key.vk_delta_2)?,
+        gamma_abc_g1: key
+            .ic
+            .iter()
+            .map(|p| json_g1(p))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn json_fq(value: &str) -> Result<Fq, ProofError> {
+    Fq::from_str(value).map_err(|_| vkey_error(format!("bad coordinate {}", value)))
+}
+
+fn json_g1(point: &[String]) -> Result<G1Affine, ProofError> {
+    let [x, y, z] = point else {
+        return Err(vkey_error("G1 point needs 3 coordinates"));
+    };
+    match z.as_str() {
+        "0" => Ok(G1Affine::zero()),
+        "1" => g1_point(json_fq(x)?, json_fq(y)?)
+            .ok_or_else(|| vkey_error("G1 point not on the curve")),
+        _ => Err(vkey_error("G1 point is not affine")),
+    }
+}
+
+fn json_g2(point: &[Vec<String>]) -> Result<G2Affine, ProofError> {
+    let fq2 = |c: &Vec<String>| match c.as_slice() {
+        [c0, c1] => Ok(Fq2::new(json_fq(c0)?, json_fq(c1)?)),
+        _ => Err(vkey_error("Fq2 element needs 2 coordinates")),
+    };
+    let [x, y, z] = point else {
+        return Err(vkey_error("G2 point needs 3 coordinates"));
+    };
+    let z = fq2(z)?;
+    if z.is_zero() {
+        return Ok(G2Affine::zero());
+    }
+    if z != Fq2::from(1u64) {
+        return Err(vkey_error("G2 point is not affine"));
+    }
+    g2_point(fq2(x)?, fq2(y)?).ok_or_else(|| vkey_error("G2 point not in the subgroup"))
+}
+
+/// Checked G1 point, with (0, 0) as the point at infinity
+fn g1_point(x: Fq, y: Fq) -> Option<G1Affine> {
+    if x.is_zero() && y.is_zero() {
+        return Some(G1Affine::zero());
+    }
+    let point = G1Affine::new_unchecked(x, y);
+    point.is_on_curve().then_some(point)
+}
+
+/// Checked G2 point, with (0, 0) as the point at infinity
+fn g2_point(x: Fq2, y: Fq2) -> Option<G2Affine> {
+    if x.is_zero() && y.is_zero() {
+        return Some(G2Affine::zero());
+    }
+    let point = G2Affine::new_unchecked(x, y);
+    (point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve()).then_some(point)
+}
+
+fn zkey_error(message: impl std::fmt::Display) -> ProofError {
+    ProofError::SerializationError(format!("Invalid zkey: {}", message))
+}
+
+fn vkey_error(message: impl std::fmt::Display) -> ProofError {
+    ProofError::SerializationError(format!("Invalid verification key: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::SNARK;
+    use rand::rngs::OsRng;
+
+    /// x * y = z with z public
+    struct MulCircuit;
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+
+            let x = FpVar::new_witness(cs.clone(), || Ok(Fr::from(3u64)))?;
+            let y = FpVar::new_witness(cs.clone(), || Ok(Fr::from(5u64)))?;
+            let z = FpVar::new_input(cs, || Ok(Fr::from(15u64)))?;
+            (x * y).enforce_equal(&z)
+        }
+    }
+
+    fn montgomery(fq: &Fq) -> Vec<u8> {
+        fq.0.to_bytes_le()
+    }
+
+    fn g1_bytes(point: &G1Affine) -> Vec<u8> {
+        let (x, y) = point
+            .xy()
+            .map_or((Fq::zero(), Fq::zero()), |(x, y)| (*x, *y));
+        [montgomery(&x), montgomery(&y)].concat()
+    }
+
+    fn g2_bytes(point: &G2Affine) -> Vec<u8> {
+        let (x, y) = point
+            .xy()
+            .map_or((Fq2::zero(), Fq2::zero()), |(x, y)| (*x, *y));
+        [
+            montgomery(&x.c0),
+            montgomery(&x.c1),
+            montgomery(&y.c0),
+            montgomery(&y.c1),
+        ]
+        .concat()
+    }
+
+    /// Encode a key as snarkjs lays out a `.zkey`, with `coeffs` as
+    /// (matrix, constraint, signal, value)
+    fn write_zkey(pk: &ProvingKey<Bn254>, coeffs: &[(u32, u32, u32, Fr)]) -> Vec<u8> {
+        let n_public = pk.vk.gamma_abc_g1.len() - 1;
+        let n_vars = pk.a_query.len();
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&Fq::MODULUS.to_bytes_le());
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&Fr::MODULUS.to_bytes_le());
+        header.extend_from_slice(&(n_vars as u32).to_le_bytes());
+        header.extend_from_slice(&(n_public as u32).to_le_bytes());
+        header.extend_from_slice(&(pk.h_query.len() as u32).to_le_bytes());
+        header.extend(g1_bytes(&pk.vk.alpha_g1));
+        header.extend(g1_bytes(&pk.beta_g1));
+        header.extend(g2_bytes(&pk.vk.beta_g2));
+        header.extend(g2_bytes(&pk.vk.gamma_g2));
+        header.extend(g1_bytes(&pk.delta_g1));
+        header.extend(g2_bytes(&pk.vk.delta_g2));
+
+        let mut coeff_section = (coeffs.len() as u32).to_le_bytes().to_vec();
+        for (matrix, constraint, signal, value) in coeffs {
+            coeff_section.extend_from_slice(&matrix.to_le_bytes());
+            coeff_section.extend_from_slice(&constraint.to_le_bytes());
+            coeff_section.extend_from_slice(&signal.to_le_bytes());
+            coeff_section.extend(Fr::from_bigint(value.0).unwrap().0.to_bytes_le());
+        }
+
+        let sections: Vec<(u32, Vec<u8>)> = vec![
+            (SECTION_HEADER, GROTH16_PROTOCOL.to_le_bytes().to_vec()),
+            (SECTION_GROTH16_HEADER, header),
+            (
+                SECTION_IC,
+                pk.vk.gamma_abc_g1.iter().flat_map(g1_bytes).collect(),
+            ),
+            (SECTION_COEFFS, coeff_section),
+            (SECTION_A, pk.a_query.iter().flat_map(g1_bytes).collect()),
+            (
+                SECTION_B_G1,
+                pk.b_g1_query.iter().flat_map(g1_bytes).collect(),
+            ),
+            (
+                SECTION_B_G2,
+                pk.b_g2_query.iter().flat_map(g2_bytes).collect(),
+            ),
+            (SECTION_C, pk.l_query.iter().flat_map(g1_bytes).collect()),
+            (SECTION_H, pk.h_query.iter().flat_map(g1_bytes).collect()),
+        ];
+        let mut bytes = ZKEY_MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        // snarkjs does not promise section order
+        for (id, content) in sections.iter().rev() {
+            bytes.extend_from_slice(&id.to_le_bytes());
+            bytes.extend_from_slice(&(content.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(content);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_read_zkey() {
+        let (pk, _) = Groth16::<Bn254>::circuit_specific_setup(MulCircuit, &mut OsRng).unwrap();
+        // One constraint x * y, then the rows binding the public input and
+        // the constant
+        let coeffs = [
+            (0, 0, 2, Fr::from(1u64)),
+            (1, 0, 3, Fr::from(7u64)),
+            (0, 1, 1, Fr::from(1u64)),
+            (0, 2, 0, Fr::from(1u64)),
+        ];
+        let zkey = read_zkey(&write_zkey(&pk, &coeffs)).unwrap();
+
+        assert!(zkey.proving_key == pk);
+        assert_eq!(zkey.matrices.num_instance_variables, 2);
+        assert_eq!(zkey.matrices.num_witness_variables, pk.a_query.len() - 2);
+        assert_eq!(zkey.matrices.num_constraints, 1);
+        assert_eq!(zkey.matrices.a, vec![vec![(Fr::from(1u64), 2)]]);
+        assert_eq!(zkey.matrices.b, vec![vec![(Fr::from(7u64), 3)]]);
+
+        let bytes = write_zkey(&pk, &coeffs);
+        assert!(read_zkey(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read_zkey(&[b"zkez".as_slice(), &bytes[4..]].concat()).is_err());
+    }
+
+    /// A key with every point the generator, as snarkjs writes them
+    fn generator_key_json(n_public: usize, g2_x: [&str; 2]) -> String {
+        let g1 = r#"["1", "2", "1"]"#;
+        let g2 = format!(
+            r#"[["{}", "{}"], ["{}", "{}"], ["1", "0"]]"#,
+            g2_x[0], g2_x[1], G2_Y[0], G2_Y[1]
+        );
+        format!(
+            r#"{{"protocol": "groth16", "curve": "bn128", "nPublic": {n_public},
+                "vk_alpha_1": {g1}, "vk_beta_2": {g2}, "vk_gamma_2": {g2},
+                "vk_delta_2": {g2}, "IC": [{g1}, ["0", "1", "0"]]}}"#
+        )
+    }
+
+    const G2_X: [&str; 2] = [
+        "10857046999023057135944570762232829481370756359578518086990519993285655852781",
+        "11559732032986387107991004021392285783925812861821192530917403151452391805634",
+    ];
+    const G2_Y: [&str; 2] = [
+        "8495653923123431417604973247489272438418190587263600148770280649306958101930",
+        "4082367875863433681332203403145435568316851327593401208105741076214120093531",
+    ];
+
+    #[test]
+    fn test_read_verification_key_json() {
+        let vk = read_verification_key_json(&generator_key_json(1, G2_X)).unwrap();
+        assert_eq!(vk.alpha_g1, G1Affine::generator());
+        assert_eq!(vk.beta_g2, G2Affine::generator());
+        assert_eq!(
+            vk.gamma_abc_g1,
+            vec![G1Affine::generator(), G1Affine::zero()]
+        );
+
+        // Swapped Fq2 coordinates are off the curve
+        let swapped = generator_key_json(1, [G2_X[1], G2_X[0]]);
+        assert!(read_verification_key_json(&swapped).is_err());
+        assert!(read_verification_key_json(&generator_key_json(2, G2_X)).is_err());
+    }
+}
@@ -0,0 +1,1079 @@
+//! PyO3 extension module
+//!
+//! Built as `veil._rust_core` by maturin; enabled by the `python` feature.
+//!
+//! Field elements cross the boundary as 32-byte big-endian values, the
+//! encoding the program stores commitments, roots and nullifiers in, except
+//! blinding factors, which are little-endian as in encrypted notes.
+//!
+//! Proving, verifying, tree batches and hashing release the GIL, so other
+//! Python threads run meanwhile; `TransferProofSystem.prove_async` proves
+//! on a thread of its own and returns a `ProofTask` to wait on.
+
+// pyo3 0.20 expands `#[new]` into impls inside a function
+#![allow(unknown_lints, non_local_definitions)]
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField, UniformRand};
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::encryption::{
+    decrypt_note, encrypt_note, EncryptedNote, EncryptionKeypair, NoteCiphertext, NoteData,
+};
+use crate::crypto::merkle::{MerklePath, TREE_DEPTH};
+use crate::crypto::{
+    generate_nullifier_hash, poseidon_hash2, Commitment, Note, PoseidonMerkleTree, SpendingKey,
+    ViewingKey,
+};
+use crate::proof::{
+    field_to_bytes_be, KeyStore, Network, ProofError, SerializedProof, SolanaProof, SpendWitness,
+    TransferCircuit, TransferProofSystem, TransferPublicInputs, TransferVerifier, WitnessBuilder,
+};
+use crate::verify::encoding::field_from_bytes_be;
+
+/// Generate a Pedersen commitment for shielding assets
+///
+/// # Arguments
+/// * `amount` - Amount to shield (in lamports/smallest unit)
+/// * `secret` - User's secret key (32 bytes minimum)
+///
+/// # Returns
+/// * Commitment bytes (32 bytes)
+#[pyfunction]
+fn generate_commitment(py: Python, amount: u64, secret: &[u8]) -> PyResult<Py<PyBytes>> {
+    // Validate inputs
+    if secret.len() < 32 {
+        return Err(PyValueError::new_err("Secret must be at least 32 bytes"));
+    }
+
+    // Generate commitment using Rust (fast!)
+    let commitment = Commitment::new(amount, secret)
+        .map_err(|e| PyRuntimeError::new_err(format!("Commitment generation failed: {}", e)))?;
+
+    // Serialize commitment
+    let bytes = commitment.to_bytes();
+
+    // Return as Python bytes
+    Ok(PyBytes::new(py, &bytes).into())
+}
+
+/// Generate a nullifier to prevent double-spending
+///
+/// # Arguments
+/// * `commitment` - The commitment bytes
+/// * `secret` - User's secret key
+///
+/// # Returns
+/// * Nullifier hash (32 bytes)
+#[pyfunction]
+fn generate_nullifier(py: Python, commitment: &[u8], secret: &[u8]) -> PyResult<Py<PyBytes>> {
+    if commitment.len() != 32 {
+        return Err(PyValueError::new_err("Commitment must be 32 bytes"));
+    }
+    if secret.len() < 32 {
+        return Err(PyValueError::new_err("Secret must be at least 32 bytes"));
+    }
+
+    let nullifier = generate_nullifier_hash(commitment, secret)
+        .map_err(|e| PyRuntimeError::new_err(format!("Nullifier generation failed: {}", e)))?;
+
+    Ok(PyBytes::new(py, &nullifier).into())
+}
+
+/// Witness of a transfer, as `generate_proof` takes it in JSON
+///
+/// Byte strings are hex; field elements are big-endian, blinding factors
+/// little-endian.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WitnessJson {
+    /// Note secret of the spent note
+    secret: String,
+    amount: u64,
+    blinding: String,
+    #[serde(default)]
+    asset_id: u64,
+    leaf_index: u64,
+    /// Sibling hashes from the leaf up, as `PoseidonMerkleTree.path` gives
+    merkle_path: Vec<String>,
+    merkle_root: String,
+    #[serde(default)]
+    fee: u64,
+    /// Blinding of the output note; random if omitted
+    #[serde(default)]
+    output_blinding: Option<String>,
+}
+
+impl WitnessJson {
+    /// The transfer circuit spending the note
+    fn build(&self) -> PyResult<SpendWitness> {
+        let mut note = Note::new(
+            hex_array(&self.secret, "secret")?,
+            self.amount,
+            Fr::from(self.asset_id),
+            Fr::from_le_bytes_mod_order(&hex_array(&self.blinding, "blinding")?),
+        );
+        note.set_leaf_index(self.leaf_index);
+        let siblings = self
+            .merkle_path
+            .iter()
+            .map(|sibling| field_from_py(&hex_array(sibling, "merkle_path")?, "merkle_path"))
+            .collect::<PyResult<Vec<_>>>()?;
+        let path = MerklePath {
+            indices: (0..siblings.len()).map(|level| (self.leaf_index >> level) & 1 == 1).collect(),
+            siblings,
+            leaf_index: self.leaf_index,
+        };
+        let root = field_from_py(&hex_array(&self.merkle_root, "merkle_root")?, "merkle_root")?;
+
+        let mut builder = WitnessBuilder::new(&note).with_path(path, root).with_fee(self.fee);
+        if let Some(blinding) = &self.output_blinding {
+            let blinding = hex_array(blinding, "output_blinding")?;
+            builder = builder.with_output_blinding(Fr::from_le_bytes_mod_order(&blinding));
+        }
+        builder.build().map_err(|e| PyValueError::new_err(format!("Invalid witness: {}", e)))
+    }
+}
+
+/// Public inputs of a transfer, as `verify_proof` takes them in JSON and
+/// the program decodes them
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PublicInputsJson {
+    merkle_root: String,
+    nullifier: String,
+    new_commitment: String,
+    asset_id: u64,
+    fee: u64,
+}
+
+impl PublicInputsJson {
+    fn from_onchain_bytes(
+        inputs: &[[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS],
+    ) -> PyResult<Self> {
+        let decoded = TransferPublicInputs::from_onchain_bytes(inputs)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            merkle_root: hex::encode(inputs[0]),
+            nullifier: hex::encode(inputs[1]),
+            new_commitment: hex::encode(inputs[2]),
+            asset_id: asset_id_to_u64(&decoded.asset_id)?,
+            fee: decoded.fee,
+        })
+    }
+
+    /// The inputs in circuit order, decoded as the program decodes them
+    fn to_fields(&self) -> PyResult<[Fr; TransferCircuit::NUM_PUBLIC_INPUTS]> {
+        let bytes = [
+            hex_array(&self.merkle_root, "merkle_root")?,
+            hex_array(&self.nullifier, "nullifier")?,
+            hex_array(&self.new_commitment, "new_commitment")?,
+            field_to_bytes_be(&Fr::from(self.asset_id)),
+            field_to_bytes_be(&Fr::from(self.fee)),
+        ];
+        TransferPublicInputs::from_onchain_bytes(&bytes)
+            .map(|inputs| inputs.to_fields())
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Decode a hex string of 32 bytes
+fn hex_array(value: &str, what: &str) -> PyResult<[u8; 32]> {
+    let bytes = hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| PyValueError::new_err(format!("{} is not hex: {}", what, e)))?;
+    array_from_py(&bytes, what)
+}
+
+/// Parse a network name
+fn parse_network(network: &str) -> PyResult<Network> {
+    [Network::Localnet, Network::Devnet, Network::Mainnet]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == network)
+        .ok_or_else(|| PyValueError::new_err(format!("Unknown network: {}", network)))
+}
+
+/// Keys at the default location for `network`, shared so they load once
+fn default_keys(network: Network) -> Arc<KeyStore> {
+    static LOCALNET: OnceLock<Arc<KeyStore>> = OnceLock::new();
+    static DEVNET: OnceLock<Arc<KeyStore>> = OnceLock::new();
+    static MAINNET: OnceLock<Arc<KeyStore>> = OnceLock::new();
+    let keys = match network {
+        Network::Localnet => &LOCALNET,
+        Network::Devnet => &DEVNET,
+        Network::Mainnet => &MAINNET,
+    };
+    keys.get_or_init(|| Arc::new(KeyStore::for_network(network))).clone()
+}
+
+/// `keys`, else the default keys for `network`
+fn key_store(keys: Option<&PyKeyStore>, network: &str) -> PyResult<Arc<KeyStore>> {
+    match keys {
+        Some(keys) => Ok(keys.inner.clone()),
+        None => Ok(default_keys(parse_network(network)?)),
+    }
+}
+
+/// Generate a Groth16 transfer proof
+///
+/// The witness is a JSON object with `secret`, `amount`, `blinding`,
+/// `asset_id` (default 0) and `leaf_index` of the spent note,
+/// `merkle_path` (sibling hashes from the leaf up) and `merkle_root`, and
+/// optionally `fee` and `output_blinding`. Byte strings are hex.
+///
+/// # Arguments
+/// * `witness_json` - The witness
+/// * `keys` - Key files to prove with; the default keys for `network`
+///   (`$VEIL_KEY_DIR/<network>/transfer.pk`) if omitted
+/// * `network` - `localnet`, `devnet` or `mainnet`
+///
+/// # Returns
+/// * `SpendProof` with the groth16-solana proof and its public inputs
+///
+/// # Raises
+/// * `ValueError` if the witness is malformed or does not open the root
+/// * `RuntimeError` if the keys can't be loaded or proving fails
+#[pyfunction]
+#[pyo3(signature = (witness_json, keys = None, network = "devnet"))]
+fn generate_proof(
+    py: Python,
+    witness_json: &str,
+    keys: Option<&PyKeyStore>,
+    network: &str,
+) -> PyResult<PySpendProof> {
+    let witness: WitnessJson = serde_json::from_str(witness_json)
+        .map_err(|e| PyValueError::new_err(format!("Invalid witness JSON: {}", e)))?;
+    let witness = witness.build()?;
+    let keys = key_store(keys, network)?;
+
+    // Loading the proving key and proving both take seconds
+    py.allow_threads(|| ProvenSpend::prove(&*keys.transfer_system()?, witness))
+        .map_err(|e| PyRuntimeError::new_err(format!("Proof generation failed: {}", e)))?
+        .into_py(py)
+}
+
+/// Verify a Groth16 transfer proof
+///
+/// The public inputs are a JSON object with hex `merkle_root`,
+/// `nullifier` and `new_commitment` and integer `asset_id` and `fee`, as
+/// `SpendProof.public_inputs_json` holds them; they are decoded as the
+/// program decodes them.
+///
+/// # Arguments
+/// * `proof` - `SpendProof.proof` (arkworks encoding)
+/// * `public_inputs_json` - The public inputs
+/// * `keys` - Key files to verify with, as for `generate_proof`
+/// * `network` - Network whose default keys to use
+/// * `verifying_key` - Serialized verifying key, instead of key files
+///
+/// # Returns
+/// * Whether the proof is valid
+#[pyfunction]
+#[pyo3(signature = (
+    proof,
+    public_inputs_json,
+    keys = None,
+    network = "devnet",
+    verifying_key = None
+))]
+fn verify_proof(
+    py: Python,
+    proof: &[u8],
+    public_inputs_json: &str,
+    keys: Option<&PyKeyStore>,
+    network: &str,
+    verifying_key: Option<&[u8]>,
+) -> PyResult<bool> {
+    let inputs: PublicInputsJson = serde_json::from_str(public_inputs_json)
+        .map_err(|e| PyValueError::new_err(format!("Invalid public inputs JSON: {}", e)))?;
+    let inputs = inputs.to_fields()?;
+
+    let verified = match verifying_key {
+        Some(bytes) => TransferVerifier::from_vk_bytes(bytes)
+            .and_then(|verifier| py.allow_threads(|| verifier.verify(proof, &inputs))),
+        None => {
+            let keys = key_store(keys, network)?;
+            py.allow_threads(|| keys.transfer_system()?.verify(proof, &inputs))
+        }
+    };
+    verified.map_err(|e| PyRuntimeError::new_err(format!("Proof verification failed: {}", e)))
+}
+
+/// Poseidon hash function (zkSNARK-friendly)
+///
+/// # Arguments
+/// * `inputs` - Array of field elements to hash
+///
+/// # Returns
+/// * Hash output (32 bytes)
+#[pyfunction]
+fn poseidon_hash(py: Python, inputs: Vec<Vec<u8>>) -> PyResult<Py<PyBytes>> {
+    use crate::crypto::poseidon_hash_bytes;
+
+    let hash = py
+        .allow_threads(|| poseidon_hash_bytes(&inputs))
+        .map_err(|e| PyRuntimeError::new_err(format!("Poseidon hash failed: {}", e)))?;
+
+    Ok(PyBytes::new(py, &hash).into())
+}
+
+/// Poseidon hash of each input list, as `poseidon_hash` computes it
+///
+/// # Arguments
+/// * `batch` - Lists of field elements to hash
+///
+/// # Returns
+/// * One hash (32 bytes) per list, in order
+#[pyfunction]
+fn poseidon_hash_batch(py: Python, batch: Vec<Vec<Vec<u8>>>) -> PyResult<Vec<Py<PyBytes>>> {
+    use crate::crypto::poseidon_hash_bytes;
+
+    let hashes = py
+        .allow_threads(|| {
+            batch.iter().map(|inputs| poseidon_hash_bytes(inputs)).collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| PyRuntimeError::new_err(format!("Poseidon hash failed: {}", e)))?;
+
+    Ok(hashes.iter().map(|hash| PyBytes::new(py, hash).into()).collect())
+}
+
+/// Generate a 24-word backup phrase (BIP39, English)
+#[pyfunction]
+fn generate_mnemonic() -> String {
+    crate::keys::generate_mnemonic()
+}
+
+/// Check a backup phrase's words and checksum
+///
+/// # Raises
+/// * `ValueError` if the phrase is not a valid 24-word phrase
+#[pyfunction]
+fn validate_mnemonic(phrase: &str) -> PyResult<()> {
+    crate::keys::validate_mnemonic(phrase).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Restore an account's spending secret from a backup phrase
+///
+/// # Arguments
+/// * `phrase` - 24-word backup phrase
+/// * `passphrase` - Optional BIP39 passphrase
+/// * `account` - Account number (path `m/32'/501'/account'`)
+///
+/// # Returns
+/// * The account's note secret (32 bytes)
+#[pyfunction]
+#[pyo3(signature = (phrase, passphrase = "", account = 0))]
+fn secret_from_mnemonic(
+    py: Python,
+    phrase: &str,
+    passphrase: &str,
+    account: u32,
+) -> PyResult<Py<PyBytes>> {
+    use crate::keys::{mnemonic_seed, ExtendedSpendingKey};
+
+    let key = mnemonic_seed(phrase, passphrase)
+        .and_then(|seed| ExtendedSpendingKey::account(&seed, account))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    Ok(PyBytes::new(py, key.secret()).into())
+}
+
+/// Decode a 32-byte big-endian field element
+fn field_from_py(bytes: &[u8], what: &str) -> PyResult<Fr> {
+    let bytes: &[u8; 32] = bytes
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("{} must be 32 bytes", what)))?;
+    field_from_bytes_be(bytes)
+        .ok_or_else(|| PyValueError::new_err(format!("{} is not a field element", what)))
+}
+
+/// Decode a 32-byte secret or key
+fn array_from_py(bytes: &[u8], what: &str) -> PyResult<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("{} must be 32 bytes", what)))
+}
+
+/// Blinding factor as stored in encrypted notes
+fn blinding_to_bytes(blinding: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&blinding.into_bigint().to_bytes_le());
+    bytes
+}
+
+/// Asset id as a `u64`, which is all `asset_id_for_mint` produces
+fn asset_id_to_u64(asset_id: &Fr) -> PyResult<u64> {
+    let bytes = field_to_bytes_be(asset_id);
+    let (high, low) = bytes.split_at(24);
+    if high.iter().any(|&byte| byte != 0) {
+        return Err(PyValueError::new_err("asset id does not fit in 64 bits"));
+    }
+    Ok(u64::from_be_bytes(low.try_into().unwrap()))
+}
+
+/// Nullifier of the note at `leaf_index` as the spend circuits derive it,
+/// and so as the program records it
+fn spend_nullifier(spending_key: &SpendingKey, leaf_index: u64) -> [u8; 32] {
+    let index_with_domain = poseidon_hash2(
+        &Fr::from(leaf_index),
+        &Fr::from_le_bytes_mod_order(b"NYX_NULLIFIER"),
+    );
+    field_to_bytes_be(&poseidon_hash2(spending_key.as_field(), &index_with_domain))
+}
+
+/// A shielded note: its owner's secret, amount, blinding and asset
+#[pyclass(name = "Note")]
+#[derive(Clone)]
+struct PyNote {
+    inner: Note,
+}
+
+#[pymethods]
+impl PyNote {
+    /// Note of `amount` owned by `secret` (32 bytes)
+    ///
+    /// # Arguments
+    /// * `blinding` - 32 bytes, little-endian; random if omitted
+    /// * `asset_id` - 0 for native SOL
+    /// * `leaf_index` - Position in the tree, once inserted
+    #[new]
+    #[pyo3(signature = (secret, amount, blinding = None, asset_id = 0, leaf_index = None))]
+    fn new(
+        secret: &[u8],
+        amount: u64,
+        blinding: Option<&[u8]>,
+        asset_id: u64,
+        leaf_index: Option<u64>,
+    ) -> PyResult<Self> {
+        let blinding = match blinding {
+            Some(bytes) => Fr::from_le_bytes_mod_order(&array_from_py(bytes, "blinding")?),
+            None => Fr::rand(&mut OsRng),
+        };
+        let mut inner = Note::new(
+            array_from_py(secret, "secret")?,
+            amount,
+            Fr::from(asset_id),
+            blinding,
+        );
+        inner.leaf_index = leaf_index;
+        Ok(Self { inner })
+    }
+
+    /// Note of `amount` under a fresh random secret
+    #[staticmethod]
+    #[pyo3(signature = (amount, asset_id = 0))]
+    fn random(amount: u64, asset_id: u64) -> Self {
+        Self {
+            inner: Note::new_random(amount, Fr::from(asset_id), Fr::rand(&mut OsRng)),
+        }
+    }
+
+    /// Parse a note serialized with `to_bytes`
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = Note::from_bytes(bytes).ok_or_else(|| PyValueError::new_err("invalid note"))?;
+        Ok(Self { inner })
+    }
+
+    /// Serialize for storage; holds the secret
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    #[getter]
+    fn secret<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.inner.secret)
+    }
+
+    #[getter]
+    fn amount(&self) -> u64 {
+        self.inner.amount
+    }
+
+    #[getter]
+    fn blinding<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &blinding_to_bytes(&self.inner.blinding))
+    }
+
+    #[getter]
+    fn asset_id(&self) -> PyResult<u64> {
+        asset_id_to_u64(&self.inner.asset_id)
+    }
+
+    #[getter]
+    fn leaf_index(&self) -> Option<u64> {
+        self.inner.leaf_index
+    }
+
+    #[setter]
+    fn set_leaf_index(&mut self, leaf_index: Option<u64>) {
+        self.inner.leaf_index = leaf_index;
+    }
+
+    /// Commitment inserted into the tree when the note is shielded
+    fn commitment<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &field_to_bytes_be(&self.inner.commitment()))
+    }
+
+    /// Nullifier published when the note is spent
+    ///
+    /// # Raises
+    /// * `ValueError` if the note has no leaf index
+    fn nullifier<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let leaf_index = self
+            .inner
+            .leaf_index
+            .ok_or_else(|| PyValueError::new_err("note has no leaf index"))?;
+        Ok(PyBytes::new(py, &spend_nullifier(&self.inner.spending_key(), leaf_index)))
+    }
+
+    /// The owner's spending key
+    fn spending_key(&self) -> PySpendingKey {
+        PySpendingKey {
+            inner: self.inner.spending_key(),
+        }
+    }
+
+    /// Public key notes for this note's owner are encrypted to
+    fn encryption_public_key<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        let keypair = EncryptionKeypair::from_viewing_key(&self.inner.viewing_key());
+        PyBytes::new(py, &keypair.public_key_bytes())
+    }
+
+    fn __repr__(&self) -> String {
+        let leaf_index = self.inner.leaf_index.map_or("None".to_string(), |i| i.to_string());
+        format!("Note(amount={}, leaf_index={})", self.inner.amount, leaf_index)
+    }
+}
+
+/// Key that derives a note's nullifiers and commitment
+#[pyclass(name = "SpendingKey")]
+struct PySpendingKey {
+    inner: SpendingKey,
+}
+
+#[pymethods]
+impl PySpendingKey {
+    /// Spending key of the note secret `secret` (32 bytes)
+    #[new]
+    fn new(secret: &[u8]) -> PyResult<Self> {
+        Ok(Self {
+            inner: SpendingKey::from_secret(&array_from_py(secret, "secret")?),
+        })
+    }
+
+    /// Parse a key serialized with `to_bytes`
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self {
+            inner: SpendingKey::from_bytes(&array_from_py(bytes, "spending key")?),
+        })
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    /// Nullifier of the note at `leaf_index`
+    fn nullifier<'py>(&self, py: Python<'py>, leaf_index: u64) -> &'py PyBytes {
+        PyBytes::new(py, &spend_nullifier(&self.inner, leaf_index))
+    }
+}
+
+/// Poseidon Merkle tree of note commitments, mirroring the pool's
+#[pyclass(name = "PoseidonMerkleTree")]
+struct PyMerkleTree {
+    inner: PoseidonMerkleTree,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    /// Empty tree of `depth` levels
+    #[new]
+    #[pyo3(signature = (depth = TREE_DEPTH))]
+    fn new(depth: usize) -> PyResult<Self> {
+        let inner = PoseidonMerkleTree::with_depth(depth)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// Append a commitment, returning its leaf index
+    fn insert(&mut self, commitment: &[u8]) -> PyResult<u64> {
+        let leaf = field_from_py(commitment, "commitment")?;
+        self.inner.insert(leaf).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Append commitments in order, returning the first one's leaf index
+    ///
+    /// Syncing a tree from the chain inserts in batches; the GIL is released
+    /// while they hash.
+    fn insert_batch(&mut self, py: Python, commitments: Vec<Vec<u8>>) -> PyResult<u64> {
+        let leaves = commitments
+            .iter()
+            .map(|commitment| field_from_py(commitment, "commitment"))
+            .collect::<PyResult<Vec<_>>>()?;
+        let tree = &mut self.inner;
+        py.allow_threads(|| tree.insert_batch(&leaves))
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Sibling hashes from the leaf at `leaf_index` up, for a witness
+    fn path<'py>(&self, py: Python<'py>, leaf_index: u64) -> PyResult<Vec<&'py PyBytes>> {
+        let path = self
+            .inner
+            .generate_proof(leaf_index)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let siblings = path.siblings.iter();
+        Ok(siblings.map(|sibling| PyBytes::new(py, &field_to_bytes_be(sibling))).collect())
+    }
+
+    /// Current root
+    fn root<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &field_to_bytes_be(&self.inner.root()))
+    }
+
+    /// Whether `root` is the current root
+    fn is_known_root(&self, root: &[u8]) -> PyResult<bool> {
+        Ok(self.inner.is_known_root(&field_from_py(root, "root")?))
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len() as usize
+    }
+}
+
+/// A note encrypted to its recipient, as published with a shield or
+/// transfer
+#[pyclass(name = "EncryptedNote")]
+struct PyEncryptedNote {
+    inner: EncryptedNote,
+}
+
+#[pymethods]
+impl PyEncryptedNote {
+    /// Encrypt `note`'s amount, blinding and asset to `recipient`, the
+    /// recipient's `Note.encryption_public_key`
+    #[staticmethod]
+    fn encrypt(note: &PyNote, recipient: &[u8]) -> PyResult<Self> {
+        let data = NoteData::new(
+            note.inner.amount,
+            blinding_to_bytes(&note.inner.blinding),
+            asset_id_to_u64(&note.inner.asset_id)?,
+        );
+        let inner = encrypt_note(&data, &array_from_py(recipient, "recipient")?)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// Parse a published ciphertext
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner =
+            EncryptedNote::from_bytes(bytes).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    /// Decrypt as the owner of `secret`, returning the note, or `None` if
+    /// it was not encrypted to them
+    ///
+    /// # Arguments
+    /// * `leaf_index` - The note's position in the tree, if known
+    #[pyo3(signature = (secret, leaf_index = None))]
+    fn decrypt(&self, secret: &[u8], leaf_index: Option<u64>) -> PyResult<Option<PyNote>> {
+        let secret = array_from_py(secret, "secret")?;
+        let keypair = EncryptionKeypair::from_viewing_key(&ViewingKey::from_secret(&secret));
+        let ciphertext = NoteCiphertext::from(self.inner.clone());
+        let Ok(data) = decrypt_note(&ciphertext, &keypair.private_key_bytes()) else {
+            return Ok(None);
+        };
+        let mut inner = Note::new(
+            secret,
+            data.amount,
+            Fr::from(data.asset_id),
+            Fr::from_le_bytes_mod_order(&data.blinding),
+        );
+        inner.leaf_index = leaf_index;
+        Ok(Some(PyNote { inner }))
+    }
+}
+
+/// A proven spend, before it is handed to Python
+#[derive(Clone)]
+struct ProvenSpend {
+    proof: SerializedProof,
+    solana_proof: SolanaProof,
+    public_inputs: [[u8; 32]; TransferCircuit::NUM_PUBLIC_INPUTS],
+    output_note: Note,
+}
+
+impl ProvenSpend {
+    /// Prove `witness` under `system`
+    fn prove(system: &TransferProofSystem, witness: SpendWitness) -> Result<Self, ProofError> {
+        let proof = system.prove(witness.circuit)?;
+        let solana_proof = SolanaProof::from_compressed(proof.as_bytes())?;
+        Ok(Self {
+            proof,
+            solana_proof,
+            public_inputs: witness.public_inputs,
+            output_note: witness.output_note,
+        })
+    }
+
+    fn into_py(self, py: Python) -> PyResult<PySpendProof> {
+        let inputs = self.public_inputs;
+        let public_inputs = PublicInputsJson::from_onchain_bytes(&inputs)?;
+        let public_inputs_json = serde_json::to_string(&public_inputs)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PySpendProof {
+            proof: PyBytes::new(py, self.proof.as_bytes()).into(),
+            solana_proof: PyBytes::new(py, &self.solana_proof.to_bytes()).into(),
+            public_inputs: inputs.iter().map(|input| PyBytes::new(py, input).into()).collect(),
+            nullifier: PyBytes::new(py, &inputs[1]).into(),
+            new_commitment: PyBytes::new(py, &inputs[2]).into(),
+            public_inputs_json,
+            output_note: PyNote {
+                inner: self.output_note,
+            },
+        })
+    }
+}
+
+/// A proven spend, as `TransferProofSystem.prove` returns it
+#[pyclass(name = "SpendProof", get_all)]
+struct PySpendProof {
+    /// arkworks-compressed proof, for `TransferProofSystem.verify`
+    proof: Py<PyBytes>,
+    /// groth16-solana proof (256 bytes), for instruction data
+    solana_proof: Py<PyBytes>,
+    /// merkle_root, nullifier, new_commitment, asset_id, fee
+    public_inputs: Vec<Py<PyBytes>>,
+    /// Nullifier of the spent note
+    nullifier: Py<PyBytes>,
+    /// Commitment of the output note
+    new_commitment: Py<PyBytes>,
+    /// The public inputs as `verify_proof` takes them
+    public_inputs_json: String,
+    /// The output note, to insert once its commitment is on chain
+    output_note: PyNote,
+}
+
+/// A proof being generated on its own thread, from
+/// `TransferProofSystem.prove_async`
+#[pyclass(name = "ProofTask")]
+struct PyProofTask {
+    receiver: Receiver<Result<ProvenSpend, String>>,
+    outcome: Option<Result<ProvenSpend, String>>,
+}
+
+impl PyProofTask {
+    /// Store the outcome if the prover has sent it
+    fn poll(&mut self) {
+        if self.outcome.is_none() {
+            self.outcome = self.receiver.try_recv().ok();
+        }
+    }
+}
+
+#[pymethods]
+impl PyProofTask {
+    /// Whether the proof is ready (or failed)
+    fn done(&mut self) -> bool {
+        self.poll();
+        self.outcome.is_some()
+    }
+
+    /// Wait for the proof, without holding the GIL
+    ///
+    /// # Arguments
+    /// * `timeout` - Seconds to wait; forever if omitted
+    ///
+    /// # Raises
+    /// * `TimeoutError` if the proof is not ready in time
+    /// * `RuntimeError` if proving failed
+    #[pyo3(signature = (timeout = None))]
+    fn result(&mut self, py: Python, timeout: Option<f64>) -> PyResult<PySpendProof> {
+        self.poll();
+        if self.outcome.is_none() {
+            // `Receiver` is not `Sync`, so move in a unique borrow
+            let receiver = &mut self.receiver;
+            let received = py.allow_threads(move || match timeout {
+                Some(secs) => receiver.recv_timeout(Duration::from_secs_f64(secs.max(0.0))),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            });
+            self.outcome = Some(match received {
+                Ok(outcome) => outcome,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(PyTimeoutError::new_err("proof not ready"))
+                }
+                Err(RecvTimeoutError::Disconnected) => Err("prover thread panicked".to_string()),
+            });
+        }
+        match self.outcome.clone().unwrap() {
+            Ok(spend) => spend.into_py(py),
+            Err(e) => Err(PyRuntimeError::new_err(e)),
+        }
+    }
+}
+
+/// Transfer circuit key files, loaded at most once
+#[pyclass(name = "KeyStore")]
+struct PyKeyStore {
+    inner: Arc<KeyStore>,
+}
+
+#[pymethods]
+impl PyKeyStore {
+    /// Keys at the given paths, checked against their SHA-256 if pinned
+    ///
+    /// # Raises
+    /// * `ValueError` if only one hash is pinned
+    #[new]
+    #[pyo3(signature = (
+        proving_key_path,
+        verifying_key_path,
+        proving_key_sha256 = None,
+        verifying_key_sha256 = None
+    ))]
+    fn new(
+        proving_key_path: PathBuf,
+        verifying_key_path: PathBuf,
+        proving_key_sha256: Option<&[u8]>,
+        verifying_key_sha256: Option<&[u8]>,
+    ) -> PyResult<Self> {
+        let keys = KeyStore::new(proving_key_path, verifying_key_path);
+        let keys = match (proving_key_sha256, verifying_key_sha256) {
+            (Some(pk), Some(vk)) => keys.with_pinned_hashes(
+                array_from_py(pk, "proving key hash")?,
+                array_from_py(vk, "verifying key hash")?,
+            ),
+            (None, None) => keys,
+            _ => return Err(PyValueError::new_err("pin both key hashes or neither")),
+        };
+        Ok(Self {
+            inner: Arc::new(keys),
+        })
+    }
+
+    /// Keys at the default location for `network`
+    #[staticmethod]
+    fn for_network(network: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: default_keys(parse_network(network)?),
+        })
+    }
+
+    #[getter]
+    fn proving_key_path(&self) -> PathBuf {
+        self.inner.proving_key_path().to_path_buf()
+    }
+
+    #[getter]
+    fn verifying_key_path(&self) -> PathBuf {
+        self.inner.verifying_key_path().to_path_buf()
+    }
+
+    /// Write `system`'s keys to the store's paths, returning their
+    /// SHA-256s for pinning
+    fn save<'py>(
+        &self,
+        py: Python<'py>,
+        system: &PyTransferProofSystem,
+    ) -> PyResult<(&'py PyBytes, &'py PyBytes)> {
+        let (pk, vk) = self
+            .inner
+            .save(&system.inner)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok((PyBytes::new(py, &pk), PyBytes::new(py, &vk)))
+    }
+
+    /// The proof system for the stored keys, loading them on first use
+    fn transfer_system(&self, py: Python) -> PyResult<PyTransferProofSystem> {
+        let keys = &self.inner;
+        let inner = py
+            .allow_threads(|| keys.transfer_system())
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PyTransferProofSystem { inner })
+    }
+}
+
+/// Groth16 proof system for the transfer circuit
+#[pyclass(name = "TransferProofSystem")]
+struct PyTransferProofSystem {
+    inner: Arc<TransferProofSystem>,
+}
+
+#[pymethods]
+impl PyTransferProofSystem {
+    /// Generate keys for a tree of `depth` levels
+    ///
+    /// WARNING: the toxic waste is random; for testing only. Load ceremony
+    /// keys with `from_keys` in production.
+    #[staticmethod]
+    #[pyo3(signature = (depth = TREE_DEPTH))]
+    fn setup(depth: usize) -> PyResult<Self> {
+        let inner = TransferProofSystem::setup_with_depth(depth)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Load keys serialized with `serialize_proving_key` and
+    /// `serialize_verifying_key`, made for a tree of `depth` levels
+    #[staticmethod]
+    #[pyo3(signature = (proving_key, verifying_key, depth = TREE_DEPTH))]
+    fn from_keys(proving_key: &[u8], verifying_key: &[u8], depth: usize) -> PyResult<Self> {
+        let inner = TransferProofSystem::from_keys(proving_key, verifying_key)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?
+            .with_tree_depth(depth);
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    fn serialize_proving_key<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let bytes = self
+            .inner
+            .serialize_proving_key()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    fn serialize_verifying_key<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let bytes = self
+            .inner
+            .serialize_verifying_key()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    #[getter]
+    fn tree_depth(&self) -> usize {
+        self.inner.tree_depth()
+    }
+
+    /// Prove a spend of `note`, which must be in `tree`, into a re-blinded
+    /// output note less `fee`, without holding the GIL
+    ///
+    /// # Arguments
+    /// * `output_blinding` - 32 bytes, little-endian; random if omitted
+    ///
+    /// # Raises
+    /// * `ValueError` if the note has no leaf index, is not in the tree, or
+    ///   the fee exceeds its amount
+    #[pyo3(signature = (note, tree, fee = 0, output_blinding = None))]
+    fn prove(
+        &self,
+        py: Python,
+        note: &PyNote,
+        tree: &PyMerkleTree,
+        fee: u64,
+        output_blinding: Option<&[u8]>,
+    ) -> PyResult<PySpendProof> {
+        let witness = spend_witness(note, tree, fee, output_blinding)?;
+        let system = &self.inner;
+        py.allow_threads(|| ProvenSpend::prove(system, witness))
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?
+            .into_py(py)
+    }
+
+    /// As `prove`, but proving on a new thread; returns at once with a
+    /// `ProofTask` to wait on
+    ///
+    /// The witness is checked before returning, so the same `ValueError`s
+    /// are raised here rather than by the task.
+    #[pyo3(signature = (note, tree, fee = 0, output_blinding = None))]
+    fn prove_async(
+        &self,
+        note: &PyNote,
+        tree: &PyMerkleTree,
+        fee: u64,
+        output_blinding: Option<&[u8]>,
+    ) -> PyResult<PyProofTask> {
+        let witness = spend_witness(note, tree, fee, output_blinding)?;
+        let system = self.inner.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("veil-prover".to_string())
+            .spawn(move || {
+                let outcome = ProvenSpend::prove(&system, witness).map_err(|e| e.to_string());
+                let _ = sender.send(outcome);
+            })
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PyProofTask {
+            receiver,
+            outcome: None,
+        })
+    }
+
+    /// Verify an arkworks-compressed proof against its public inputs
+    fn verify(&self, py: Python, proof: &[u8], public_inputs: Vec<Vec<u8>>) -> PyResult<bool> {
+        let inputs = public_inputs
+            .iter()
+            .map(|input| field_from_py(input, "public input"))
+            .collect::<PyResult<Vec<_>>>()?;
+        let system = &self.inner;
+        py.allow_threads(|| system.verify(proof, &inputs))
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Transfer circuit spending `note` from `tree`
+fn spend_witness(
+    note: &PyNote,
+    tree: &PyMerkleTree,
+    fee: u64,
+    output_blinding: Option<&[u8]>,
+) -> PyResult<SpendWitness> {
+    let mut builder = WitnessBuilder::new(&note.inner).with_tree(&tree.inner).with_fee(fee);
+    if let Some(bytes) = output_blinding {
+        let blinding = Fr::from_le_bytes_mod_order(&array_from_py(bytes, "output blinding")?);
+        builder = builder.with_output_blinding(blinding);
+    }
+    builder.build().map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Python module definition
+#[pymodule]
+fn _rust_core(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(generate_commitment, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_nullifier, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(poseidon_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(poseidon_hash_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_mnemonic, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_mnemonic, m)?)?;
+    m.add_function(wrap_pyfunction!(secret_from_mnemonic, m)?)?;
+    m.add_class::<PyNote>()?;
+    m.add_class::<PySpendingKey>()?;
+    m.add_class::<PyMerkleTree>()?;
+    m.add_class::<PyEncryptedNote>()?;
+    m.add_class::<PySpendProof>()?;
+    m.add_class::<PyProofTask>()?;
+    m.add_class::<PyKeyStore>()?;
+    m.add_class::<PyTransferProofSystem>()?;
+
+    // Add version
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+
+    Ok(())
+}
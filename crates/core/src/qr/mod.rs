@@ -0,0 +1,681 @@
+//! QR Payload Codecs
+//!
+//! Compact, versioned text payloads for exchanging shielded addresses and
+//! note packages between wallets via QR codes. Rendering is left to the app;
+//! this module only produces and parses the strings to encode.
+//!
+//! Payloads are restricted to the QR alphanumeric character set
+//! (`0-9 A-Z $%*+-./:` and space), which packs 5.5 bits per character versus
+//! 8 in byte mode, so bodies are RFC 4648 base32 (no padding):
+//!
+//! ```text
+//! single:     VEIL-<kind><version>:<base32(body || crc32)>
+//! multi-part: VEIL-<kind><version>-<id>-<index>/<total>:<base32(chunk || crc32)>
+//! ```
+//!
+//! `kind` is `A` (address) or `N` (note package). Every part carries its own
+//! CRC-32 so a misread part is rejected on its own; `id` is the CRC-32 of the
+//! whole body, which groups parts of one payload and checks the reassembled
+//! result. Parts can be scanned in any order.
+//!
+//! Maximum sizes (alphanumeric mode, error correction level M) are listed in
+//! `QR_ALPHANUMERIC_CAPACITY_M`. A `ShieldedAddress` is 117 characters and
+//! fits version 5; a `NotePackage` without a memo is 256 characters and fits
+//! version 9. Larger packages are split with `encode_note_package_parts`.
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::crypto::encryption::{EncryptedNote, EncryptionKeypair, ENCRYPTED_NOTE_SIZE};
+use crate::crypto::nullifier::SpendingKey;
+
+/// Payload tag
+pub const PAYLOAD_TAG: &str = "VEIL";
+
+/// Current payload version
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// Alphanumeric character capacity per QR version at error correction level M
+pub const QR_ALPHANUMERIC_CAPACITY_M: [(u8, usize); 10] = [
+    (1, 20),
+    (2, 38),
+    (3, 61),
+    (4, 90),
+    (5, 122),
+    (6, 154),
+    (7, 178),
+    (8, 221),
+    (9, 262),
+    (10, 311),
+];
+
+/// Default maximum characters per part (QR version 10, level M)
+pub const DEFAULT_MAX_PART_CHARS: usize = 311;
+
+/// Maximum number of parts in a multi-part payload
+pub const MAX_PARTS: usize = 99;
+
+/// Maximum memo length in a note package
+pub const MAX_MEMO_SIZE: usize = u16::MAX as usize;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CRC_SIZE: usize = 4;
+const SHIELDED_ADDRESS_SIZE: usize = 64;
+const NOTE_PACKAGE_MIN_SIZE: usize = 8 + 32 + ENCRYPTED_NOTE_SIZE + 2;
+
+/// Errors for QR payload parsing
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum QrError {
+    #[error("Invalid payload header")]
+    InvalidHeader,
+    #[error("Unsupported payload version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Unexpected payload kind: {0}")]
+    WrongKind(char),
+    #[error("Invalid base32 encoding")]
+    InvalidEncoding,
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
+    #[error("Invalid payload length")]
+    InvalidLength,
+    #[error("Invalid part numbering")]
+    InvalidPartNumber,
+    #[error("Parts belong to different payloads")]
+    MixedParts,
+    #[error("Conflicting copies of part {0}")]
+    ConflictingPart(usize),
+    #[error("Missing part {0}")]
+    MissingPart(usize),
+    #[error("Part size too small for header")]
+    PartTooSmall,
+}
+
+/// Payload kind carried in the header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// `ShieldedAddress`
+    Address,
+    /// `NotePackage`
+    NotePackage,
+}
+
+impl PayloadKind {
+    fn tag(self) -> char {
+        match self {
+            PayloadKind::Address => 'A',
+            PayloadKind::NotePackage => 'N',
+        }
+    }
+
+    fn from_tag(tag: char) -> Result<Self, QrError> {
+        match tag {
+            'A' => Ok(PayloadKind::Address),
+            'N' => Ok(PayloadKind::NotePackage),
+            other => Err(QrError::WrongKind(other)),
+        }
+    }
+}
+
+/// Everything a sender needs to create a note for a recipient
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShieldedAddress {
+    /// Recipient spending key (binds the note commitment)
+    pub spending_key: [u8; 32],
+    /// Recipient encryption public key (for the encrypted note)
+    pub encryption_key: [u8; 32],
+}
+
+impl ShieldedAddress {
+    /// Build an address from the recipient's keys
+    pub fn from_keys(spending_key: &SpendingKey, encryption: &EncryptionKeypair) -> Self {
+        Self {
+            spending_key: spending_key.to_bytes(),
+            encryption_key: encryption.public_key_bytes(),
+        }
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> [u8; SHIELDED_ADDRESS_SIZE] {
+        let mut bytes = [0u8; SHIELDED_ADDRESS_SIZE];
+        bytes[..32].copy_from_slice(&self.spending_key);
+        bytes[32..].copy_from_slice(&self.encryption_key);
+        bytes
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, QrError> {
+        if bytes.len() != SHIELDED_ADDRESS_SIZE {
+            return Err(QrError::InvalidLength);
+        }
+        let mut spending_key = [0u8; 32];
+        let mut encryption_key = [0u8; 32];
+        spending_key.copy_from_slice(&bytes[..32]);
+        encryption_key.copy_from_slice(&bytes[32..]);
+        Ok(Self {
+            spending_key,
+            encryption_key,
+        })
+    }
+}
+
+/// A note delivered out of band, with enough context for the recipient to find it
+#[derive(Clone, Debug)]
+pub struct NotePackage {
+    /// Leaf index of the note commitment
+    pub leaf_index: u64,
+    /// Note commitment
+    pub commitment: [u8; 32],
+    /// Note contents encrypted to the recipient
+    pub encrypted_note: EncryptedNote,
+    /// Optional free-form memo
+    pub memo: Vec<u8>,
+}
+
+impl NotePackage {
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, QrError> {
+        if self.memo.len() > MAX_MEMO_SIZE {
+            return Err(QrError::InvalidLength);
+        }
+        let mut bytes = Vec::with_capacity(NOTE_PACKAGE_MIN_SIZE + self.memo.len());
+        bytes.extend_from_slice(&self.leaf_index.to_le_bytes());
+        bytes.extend_from_slice(&self.commitment);
+        bytes.extend_from_slice(&self.encrypted_note.to_bytes());
+        bytes.extend_from_slice(&(self.memo.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.memo);
+        Ok(bytes)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, QrError> {
+        if bytes.len() < NOTE_PACKAGE_MIN_SIZE {
+            return Err(QrError::InvalidLength);
+        }
+        let leaf_index = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&bytes[8..40]);
+        let note_end = 40 + ENCRYPTED_NOTE_SIZE;
+        let encrypted_note = EncryptedNote::from_bytes(&bytes[40..note_end])
+            .map_err(|_| QrError::InvalidLength)?;
+        let memo_len = u16::from_le_bytes([bytes[note_end], bytes[note_end + 1]]) as usize;
+        let memo = &bytes[NOTE_PACKAGE_MIN_SIZE..];
+        if memo.len() != memo_len {
+            return Err(QrError::InvalidLength);
+        }
+        Ok(Self {
+            leaf_index,
+            commitment,
+            encrypted_note,
+            memo: memo.to_vec(),
+        })
+    }
+}
+
+/// Encode a shielded address as a single QR payload
+pub fn encode_address(address: &ShieldedAddress) -> String {
+    encode_single(PayloadKind::Address, &address.to_bytes())
+}
+
+/// Parse a shielded address payload
+pub fn decode_address(payload: &str) -> Result<ShieldedAddress, QrError> {
+    let body = decode_single(PayloadKind::Address, payload)?;
+    ShieldedAddress::from_bytes(&body)
+}
+
+/// Encode a note package as a single QR payload
+pub fn encode_note_package(package: &NotePackage) -> Result<String, QrError> {
+    Ok(encode_single(PayloadKind::NotePackage, &package.to_bytes()?))
+}
+
+/// Parse a single-part note package payload
+pub fn decode_note_package(payload: &str) -> Result<NotePackage, QrError> {
+    let body = decode_single(PayloadKind::NotePackage, payload)?;
+    NotePackage::from_bytes(&body)
+}
+
+/// Encode a note package as one payload, or several if it exceeds `max_chars`
+pub fn encode_note_package_parts(
+    package: &NotePackage,
+    max_chars: usize,
+) -> Result<Vec<String>, QrError> {
+    let body = package.to_bytes()?;
+    let single = encode_single(PayloadKind::NotePackage, &body);
+    if single.len() <= max_chars {
+        return Ok(vec![single]);
+    }
+    split_payload(PayloadKind::NotePackage, &body, max_chars)
+}
+
+/// Reassemble a note package from parts scanned in any order
+///
+/// A single-part payload is accepted as well.
+pub fn decode_note_package_parts<S: AsRef<str>>(parts: &[S]) -> Result<NotePackage, QrError> {
+    if let [only] = parts {
+        if !only.as_ref().to_ascii_uppercase().contains('/') {
+            return decode_note_package(only.as_ref());
+        }
+    }
+    let body = join_parts(PayloadKind::NotePackage, parts)?;
+    NotePackage::from_bytes(&body)
+}
+
+/// Split a body into multi-part payloads of at most `max_chars` each
+pub fn split_payload(kind: PayloadKind, body: &[u8], max_chars: usize) -> Result<Vec<String>, QrError> {
+    let id = crc32(body);
+    // Header length is bounded by the widest part numbers
+    let header_len = part_header(kind, id, MAX_PARTS, MAX_PARTS).len() + 1;
+    // Each 5 bytes of input become 8 base32 characters
+    let groups = max_chars.saturating_sub(header_len) / 8;
+    let chunk_size = (groups * 5).saturating_sub(CRC_SIZE);
+    if chunk_size == 0 {
+        return Err(QrError::PartTooSmall);
+    }
+
+    let chunks: Vec<&[u8]> = body.chunks(chunk_size).collect();
+    if chunks.len() > MAX_PARTS {
+        return Err(QrError::InvalidLength);
+    }
+    let total = chunks.len();
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let header = part_header(kind, id, i + 1, total);
+            format!("{}:{}", header, base32_encode(&with_crc(&header, chunk)))
+        })
+        .collect())
+}
+
+/// Reassemble a body from multi-part payloads in any order
+pub fn join_parts<S: AsRef<str>>(kind: PayloadKind, parts: &[S]) -> Result<Vec<u8>, QrError> {
+    let mut group: Option<(u32, usize)> = None;
+    let mut chunks: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+
+    for part in parts {
+        let part = part.as_ref().to_ascii_uppercase();
+        let (header, encoded) = part.split_once(':').ok_or(QrError::InvalidHeader)?;
+        let (part_kind, rest) = parse_prefix(header)?;
+        if part_kind != kind {
+            return Err(QrError::WrongKind(part_kind.tag()));
+        }
+
+        // rest = -<id>-<index>/<total>
+        let rest = rest.strip_prefix('-').ok_or(QrError::InvalidHeader)?;
+        let (id, numbering) = rest.split_once('-').ok_or(QrError::InvalidHeader)?;
+        let (index, total) = numbering.split_once('/').ok_or(QrError::InvalidHeader)?;
+        let id = u32::from_str_radix(id, 16).map_err(|_| QrError::InvalidHeader)?;
+        let index: usize = index.parse().map_err(|_| QrError::InvalidPartNumber)?;
+        let total: usize = total.parse().map_err(|_| QrError::InvalidPartNumber)?;
+        if total == 0 || total > MAX_PARTS || index == 0 || index > total {
+            return Err(QrError::InvalidPartNumber);
+        }
+
+        match group {
+            None => group = Some((id, total)),
+            Some(existing) if existing != (id, total) => return Err(QrError::MixedParts),
+            Some(_) => {}
+        }
+
+        let chunk = strip_crc(header, &base32_decode(encoded)?)?;
+        match chunks.get(&index) {
+            Some(existing) if existing != &chunk => return Err(QrError::ConflictingPart(index)),
+            Some(_) => {}
+            None => {
+                chunks.insert(index, chunk);
+            }
+        }
+    }
+
+    let (id, total) = group.ok_or(QrError::MissingPart(1))?;
+    if let Some(missing) = (1..=total).find(|i| !chunks.contains_key(i)) {
+        return Err(QrError::MissingPart(missing));
+    }
+
+    let body: Vec<u8> = chunks.into_values().flatten().collect();
+    if crc32(&body) != id {
+        return Err(QrError::ChecksumMismatch);
+    }
+    Ok(body)
+}
+
+fn encode_single(kind: PayloadKind, body: &[u8]) -> String {
+    let header = format!("{}-{}{}", PAYLOAD_TAG, kind.tag(), PAYLOAD_VERSION);
+    format!("{}:{}", header, base32_encode(&with_crc(&header, body)))
+}
+
+fn decode_single(kind: PayloadKind, payload: &str) -> Result<Vec<u8>, QrError> {
+    let payload = payload.to_ascii_uppercase();
+    let (header, encoded) = payload.split_once(':').ok_or(QrError::InvalidHeader)?;
+    let (payload_kind, rest) = parse_prefix(header)?;
+    if payload_kind != kind {
+        return Err(QrError::WrongKind(payload_kind.tag()));
+    }
+    if !rest.is_empty() {
+        return Err(QrError::InvalidHeader);
+    }
+    strip_crc(header, &base32_decode(encoded)?)
+}
+
+/// Parse `VEIL-<kind><version>`, returning the kind and the remaining header
+fn parse_prefix(header: &str) -> Result<(PayloadKind, &str), QrError> {
+    let rest = header
+        .strip_prefix(PAYLOAD_TAG)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .ok_or(QrError::InvalidHeader)?;
+    let mut chars = rest.chars();
+    let kind = PayloadKind::from_tag(chars.next().ok_or(QrError::InvalidHeader)?)?;
+    let version = chars
+        .next()
+        .and_then(|c| c.to_digit(10))
+        .ok_or(QrError::InvalidHeader)? as u8;
+    if version != PAYLOAD_VERSION {
+        return Err(QrError::UnsupportedVersion(version));
+    }
+    Ok((kind, chars.as_str()))
+}
+
+fn part_header(kind: PayloadKind, id: u32, index: usize, total: usize) -> String {
+    format!(
+        "{}-{}{}-{:08X}-{}/{}",
+        PAYLOAD_TAG,
+        kind.tag(),
+        PAYLOAD_VERSION,
+        id,
+        index,
+        total
+    )
+}
+
+/// Append a CRC-32 covering the header and the data
+fn with_crc(header: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    let mut covered = header.as_bytes().to_vec();
+    covered.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&covered).to_le_bytes());
+    out
+}
+
+/// Check and remove the trailing CRC-32
+fn strip_crc(header: &str, bytes: &[u8]) -> Result<Vec<u8>, QrError> {
+    if bytes.len() < CRC_SIZE {
+        return Err(QrError::InvalidLength);
+    }
+    let (data, crc) = bytes.split_at(bytes.len() - CRC_SIZE);
+    if with_crc(header, data)[data.len()..] != *crc {
+        return Err(QrError::ChecksumMismatch);
+    }
+    Ok(data.to_vec())
+}
+
+/// CRC-32 (IEEE 802.3)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// RFC 4648 base32 without padding
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(encoded: &str) -> Result<Vec<u8>, QrError> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or(QrError::InvalidEncoding)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits must be zero padding from the encoder
+    if bits >= 5 || buffer & ((1 << bits) - 1) != 0 {
+        return Err(QrError::InvalidEncoding);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption::{encrypt_note, NoteData};
+
+    const QR_ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+    fn address() -> ShieldedAddress {
+        let spending = SpendingKey::from_secret(&[7u8; 32]);
+        let encryption = EncryptionKeypair::from_secret(&[9u8; 32]);
+        ShieldedAddress::from_keys(&spending, &encryption)
+    }
+
+    fn package(memo_len: usize) -> NotePackage {
+        let data = NoteData::new(1_000_000, [3u8; 32], 0);
+        let encrypted_note = encrypt_note(&data, &address().encryption_key).unwrap();
+        NotePackage {
+            leaf_index: 42,
+            commitment: [5u8; 32],
+            encrypted_note,
+            memo: (0..memo_len).map(|i| i as u8).collect(),
+        }
+    }
+
+    fn assert_same_package(a: &NotePackage, b: &NotePackage) {
+        assert_eq!(a.to_bytes().unwrap(), b.to_bytes().unwrap());
+    }
+
+    fn assert_alphanumeric(payload: &str) {
+        assert!(payload.chars().all(|c| QR_ALPHANUMERIC.contains(c)), "{}", payload);
+    }
+
+    #[test]
+    fn test_base32_and_crc() {
+        // RFC 4648 test vectors
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"foob"), "MZXW6YQ");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_eq!(base32_decode("MZ"), Err(QrError::InvalidEncoding));
+        assert_eq!(base32_decode("MY1"), Err(QrError::InvalidEncoding));
+
+        // Standard CRC-32 check value
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_address_roundtrip() {
+        let addr = address();
+        let payload = encode_address(&addr);
+
+        assert!(payload.starts_with("VEIL-A1:"));
+        assert_eq!(payload.len(), 117);
+        assert!(payload.len() <= QR_ALPHANUMERIC_CAPACITY_M[4].1);
+        assert_alphanumeric(&payload);
+        assert_eq!(decode_address(&payload).unwrap(), addr);
+
+        // Scanners may lowercase the text
+        assert_eq!(decode_address(&payload.to_lowercase()).unwrap(), addr);
+    }
+
+    #[test]
+    fn test_note_package_roundtrip() {
+        let pkg = package(0);
+        let payload = encode_note_package(&pkg).unwrap();
+
+        assert!(payload.starts_with("VEIL-N1:"));
+        assert_eq!(payload.len(), 256);
+        assert!(payload.len() <= QR_ALPHANUMERIC_CAPACITY_M[8].1);
+        assert_alphanumeric(&payload);
+        assert_same_package(&decode_note_package(&payload).unwrap(), &pkg);
+
+        // Small packages stay in one part
+        let parts = encode_note_package_parts(&pkg, DEFAULT_MAX_PART_CHARS).unwrap();
+        assert_eq!(parts, vec![payload]);
+        assert_same_package(&decode_note_package_parts(&parts).unwrap(), &pkg);
+    }
+
+    #[test]
+    fn test_corrupted_payloads() {
+        let payload = encode_address(&address());
+
+        // Every single-character substitution in the body is caught
+        let body_start = payload.find(':').unwrap() + 1;
+        for i in body_start..payload.len() {
+            let mut corrupted = payload.clone().into_bytes();
+            corrupted[i] = if corrupted[i] == b'A' { b'B' } else { b'A' };
+            let corrupted = String::from_utf8(corrupted).unwrap();
+            assert!(decode_address(&corrupted).is_err(), "position {}", i);
+        }
+
+        // Truncation
+        assert!(decode_address(&payload[..payload.len() - 8]).is_err());
+        assert_eq!(decode_address("VEIL-A1:"), Err(QrError::InvalidLength));
+
+        // Non-base32 characters
+        let invalid = format!("{}0{}", &payload[..body_start], &payload[body_start + 1..]);
+        assert_eq!(decode_address(&invalid), Err(QrError::InvalidEncoding));
+    }
+
+    #[test]
+    fn test_header_errors() {
+        let payload = encode_address(&address());
+        let body = payload.split_once(':').unwrap().1;
+
+        assert_eq!(decode_address(body), Err(QrError::InvalidHeader));
+        assert_eq!(
+            decode_address(&format!("NYX-A1:{}", body)),
+            Err(QrError::InvalidHeader)
+        );
+        assert_eq!(
+            decode_address(&format!("VEIL-A2:{}", body)),
+            Err(QrError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            decode_address(&format!("VEIL-X1:{}", body)),
+            Err(QrError::WrongKind('X'))
+        );
+
+        // Header is covered by the checksum
+        let note = encode_note_package(&package(0)).unwrap();
+        assert_eq!(decode_address(&note), Err(QrError::WrongKind('N')));
+        assert_eq!(
+            decode_note_package(&payload.replacen("VEIL-A1", "VEIL-N1", 1)).unwrap_err(),
+            QrError::ChecksumMismatch
+        );
+    }
+
+    #[test]
+    fn test_multipart_roundtrip() {
+        let pkg = package(600);
+        let parts = encode_note_package_parts(&pkg, QR_ALPHANUMERIC_CAPACITY_M[6].1).unwrap();
+
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.len() <= QR_ALPHANUMERIC_CAPACITY_M[6].1);
+            assert_alphanumeric(part);
+        }
+        assert!(parts[0].contains(&format!("-1/{}:", parts.len())));
+
+        // In order
+        assert_same_package(&decode_note_package_parts(&parts).unwrap(), &pkg);
+
+        // Reversed and rotated
+        let mut reversed = parts.clone();
+        reversed.reverse();
+        assert_same_package(&decode_note_package_parts(&reversed).unwrap(), &pkg);
+        let mut rotated = parts.clone();
+        rotated.rotate_left(2);
+        assert_same_package(&decode_note_package_parts(&rotated).unwrap(), &pkg);
+
+        // Scanning the same part twice is harmless
+        let mut repeated = parts.clone();
+        repeated.push(parts[1].clone());
+        repeated.insert(0, parts[2].clone());
+        assert_same_package(&decode_note_package_parts(&repeated).unwrap(), &pkg);
+    }
+
+    #[test]
+    fn test_multipart_errors() {
+        let max = QR_ALPHANUMERIC_CAPACITY_M[6].1;
+        let parts = encode_note_package_parts(&package(600), max).unwrap();
+
+        // Missing part
+        let mut missing = parts.clone();
+        missing.remove(1);
+        assert_eq!(decode_note_package_parts(&missing).unwrap_err(), QrError::MissingPart(2));
+        let empty: [&str; 0] = [];
+        assert_eq!(decode_note_package_parts(&empty).unwrap_err(), QrError::MissingPart(1));
+
+        // Parts from a different payload
+        let other = encode_note_package_parts(&package(601), max).unwrap();
+        let mut mixed = parts.clone();
+        mixed[1] = other[1].clone();
+        assert_eq!(decode_note_package_parts(&mixed).unwrap_err(), QrError::MixedParts);
+
+        // Corrupted part
+        let mut corrupted = parts.clone();
+        let last = corrupted[0].pop().unwrap();
+        corrupted[0].push(if last == 'A' { 'B' } else { 'A' });
+        assert!(decode_note_package_parts(&corrupted).is_err());
+
+        // Renumbered part fails its own checksum
+        let mut renumbered = parts.clone();
+        renumbered[0] = renumbered[0].replacen("-1/", "-2/", 1);
+        assert_eq!(
+            decode_note_package_parts(&renumbered).unwrap_err(),
+            QrError::ChecksumMismatch
+        );
+
+        // Out-of-range numbering
+        let bad_index = parts[0].replacen("-1/", "-0/", 1);
+        assert_eq!(
+            decode_note_package_parts(&[bad_index, parts[1].clone()]).unwrap_err(),
+            QrError::InvalidPartNumber
+        );
+
+        // Part size too small to carry any data
+        assert_eq!(
+            split_payload(PayloadKind::NotePackage, &[0u8; 10], 20).unwrap_err(),
+            QrError::PartTooSmall
+        );
+    }
+
+    #[test]
+    fn test_capacity_table() {
+        let mut previous = 0;
+        for (i, &(version, chars)) in QR_ALPHANUMERIC_CAPACITY_M.iter().enumerate() {
+            assert_eq!(version as usize, i + 1);
+            assert!(chars > previous);
+            previous = chars;
+        }
+        assert_eq!(DEFAULT_MAX_PART_CHARS, QR_ALPHANUMERIC_CAPACITY_M[9].1);
+    }
+}
@@ -0,0 +1,584 @@
+//! HTTP transport to relayers
+//!
+//! A relayer serves these endpoints under its `endpoint` URL:
+//! - `POST /quote` takes a `QuoteRequest` as JSON and answers with a
+//!   `SignedQuote`
+//! - `POST /relay` takes a `RelayRequest` as JSON and answers with its
+//!   `RelayResponse`
+//! - `GET /status/{request_id}` answers with the request's current
+//!   `RelayResponse`
+//! - `GET /health` answers with any 2xx while the relayer accepts requests;
+//!   a JSON list of `OperationType`s as the body replaces the operations
+//!   the client assumes the relayer supports
+//! - `GET /status/{request_id}/ws` upgrades to a websocket sending the
+//!   request's `RelayResponse` as a JSON text message whenever its status
+//!   changes, closing once the status is final
+//!
+//! Refusals are 4xx responses with the reason as the body; anything else
+//! that is not a 2xx, and every transport failure, is a network error.
+
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use super::{
+    OperationType, QuoteRequest, RelayRequest, RelayResponse, RelayStatus, RelayerError, RelayerInfo, SignedQuote,
+};
+
+/// Ask the relayer at `endpoint` for a quote
+pub(crate) async fn post_quote(
+    client: &Client,
+    endpoint: &str,
+    request: &QuoteRequest,
+    timeout: Duration,
+) -> Result<SignedQuote, RelayerError> {
+    let url = format!("{}/quote", endpoint.trim_end_matches('/'));
+    send(client.post(url).json(request).timeout(timeout)).await
+}
+
+/// Submit `request` to the relayer at `endpoint`
+pub(crate) async fn post_relay(
+    client: &Client,
+    endpoint: &str,
+    request: &RelayRequest,
+    timeout: Duration,
+) -> Result<RelayResponse, RelayerError> {
+    let url = format!("{}/relay", endpoint.trim_end_matches('/'));
+    send(client.post(url).json(request).timeout(timeout)).await
+}
+
+/// Fetch the status of `request_id` from the relayer at `endpoint`
+pub(crate) async fn get_status(
+    client: &Client,
+    endpoint: &str,
+    request_id: &str,
+    timeout: Duration,
+) -> Result<RelayResponse, RelayerError> {
+    let url = format!("{}/status/{}", endpoint.trim_end_matches('/'), request_id);
+    send(client.get(url).timeout(timeout)).await
+}
+
+/// Poll the status of `response`'s request until it is confirmed or fails
+///
+/// A failed request is `TransactionRejected` with the relayer's reason;
+/// one still pending after `timeout` is `Timeout`.
+pub(crate) async fn await_confirmation(
+    client: &Client,
+    endpoint: &str,
+    mut response: RelayResponse,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<RelayResponse, RelayerError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match &response.status {
+            RelayStatus::Confirmed { .. } => return Ok(response),
+            RelayStatus::Failed { reason } => {
+                return Err(RelayerError::TransactionRejected(reason.clone()))
+            }
+            RelayStatus::Pending | RelayStatus::Submitted { .. } => {}
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(RelayerError::Timeout);
+        }
+        tokio::time::sleep(poll_interval.min(remaining)).await;
+        response = get_status(client, endpoint, &response.request_id, remaining).await?;
+    }
+}
+
+/// Live status of a relayed request, from `RelayerClient::subscribe_status`
+pub struct StatusStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    last: Option<RelayStatus>,
+    ended: bool,
+}
+
+impl StatusStream {
+    /// The next status the request moves to
+    ///
+    /// `None` after the final status, confirmed or failed. A stream that
+    /// breaks or is closed before then yields one `NetworkError` and ends.
+    pub async fn next(&mut self) -> Option<Result<RelayStatus, RelayerError>> {
+        while !self.ended {
+            let text = match self.socket.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Close(_))) | None => {
+                    self.ended = true;
+                    let reason = "status stream closed before a final status";
+                    return Some(Err(RelayerError::NetworkError(reason.into())));
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    self.ended = true;
+                    return Some(Err(RelayerError::NetworkError(e.to_string())));
+                }
+            };
+            let status = match serde_json::from_str::<RelayResponse>(&text) {
+                Ok(response) => response.status,
+                Err(e) => return Some(Err(RelayerError::InvalidResponse(e.to_string()))),
+            };
+            if self.last.as_ref() != Some(&status) {
+                self.ended = status.is_terminal();
+                self.last = Some(status.clone());
+                return Some(Ok(status));
+            }
+        }
+        None
+    }
+}
+
+/// Open a status stream for `request_id` from the relayer at `endpoint`
+pub(crate) async fn subscribe_status(
+    endpoint: &str,
+    request_id: &str,
+    timeout: Duration,
+) -> Result<StatusStream, RelayerError> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let url = match endpoint.split_once("://") {
+        Some(("https", rest)) => format!("wss://{}", rest),
+        Some((_, rest)) => format!("ws://{}", rest),
+        None => format!("ws://{}", endpoint),
+    };
+    let url = format!("{}/status/{}/ws", url, request_id);
+    let connect = tokio_tungstenite::connect_async(url);
+    let (socket, _) = tokio::time::timeout(timeout, connect)
+        .await
+        .map_err(|_| RelayerError::Timeout)?
+        .map_err(|e| RelayerError::NetworkError(e.to_string()))?;
+    Ok(StatusStream { socket, last: None, ended: false })
+}
+
+/// Round trip of a `/health` request to the relayer at `endpoint`, with
+/// the operations it lists, or `None` if it fails or is not a 2xx
+pub(crate) async fn probe(
+    client: &Client,
+    endpoint: &str,
+    timeout: Duration,
+) -> Option<(Duration, Option<Vec<OperationType>>)> {
+    let url = format!("{}/health", endpoint.trim_end_matches('/'));
+    let start = Instant::now();
+    let response = client.get(url).timeout(timeout).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let round_trip = start.elapsed();
+    Some((round_trip, response.json().await.ok()))
+}
+
+/// Probe every relayer in `relayers` and record the results
+///
+/// The list is not locked while probing, so relayers added meanwhile wait
+/// for the next round.
+pub(crate) async fn probe_relayers(
+    client: &Client,
+    relayers: &RwLock<Vec<RelayerInfo>>,
+    timeout: Duration,
+) {
+    let endpoints: Vec<(String, String)> = relayers
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|r| (r.id.clone(), r.endpoint.clone()))
+        .collect();
+    for (id, endpoint) in endpoints {
+        let probe = probe(client, &endpoint, timeout).await;
+        let mut relayers = relayers.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(relayer) = relayers.iter_mut().find(|r| r.id == id) {
+            relayer.record_probe(probe.as_ref().map(|(round_trip, _)| *round_trip));
+            if let Some((_, Some(operations))) = probe {
+                relayer.supported_operations = operations;
+            }
+        }
+    }
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, RelayerError> {
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            RelayerError::Timeout
+        } else {
+            RelayerError::NetworkError(e.to_string())
+        }
+    })?;
+    let status = response.status();
+    if status.is_client_error() {
+        let reason = response.text().await.unwrap_or_default();
+        return Err(RelayerError::TransactionRejected(format!("{}: {}", status, reason)));
+    }
+    if !status.is_success() {
+        return Err(RelayerError::NetworkError(format!("relayer returned {}", status)));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| RelayerError::InvalidResponse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::super::quote::unix_now;
+    use super::super::{pow, FeeQuote, OperationType, RelayOutput, RelayerClient, RelayerInfo};
+    use super::*;
+    use crate::telemetry::RelayRejection;
+
+    /// Serve one `(status, body)` per connection, returning the request
+    /// lines seen
+    fn serve(responses: Vec<(u16, String)>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut seen = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                seen.push(line.trim_end().to_string());
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim_end().is_empty() {
+                        break;
+                    }
+                    let header = header.to_ascii_lowercase();
+                    if let Some(value) = header.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            seen
+        });
+        (endpoint, server)
+    }
+
+    fn response(status: RelayStatus) -> (u16, String) {
+        let response = RelayResponse {
+            request_id: "req_1".into(),
+            status,
+            fee: 3_000_000,
+            estimated_confirmation_time: Some(5),
+        };
+        (200, serde_json::to_string(&response).unwrap())
+    }
+
+    fn relayer(id: &str, endpoint: String, fee_bps: u16) -> RelayerInfo {
+        RelayerInfo {
+            id: id.into(),
+            endpoint,
+            fee_bps,
+            min_amount: 0,
+            supported_operations: vec![OperationType::UnshieldSol],
+            is_online: true,
+            avg_confirmation_time: 1,
+            stake: 0,
+            latency_ms: None,
+        }
+    }
+
+    fn client(endpoint: String) -> RelayerClient {
+        let mut client = RelayerClient::with_settings(100, 5).with_poll_interval(10);
+        client.add_relayer(relayer("local", endpoint, 30));
+        client
+    }
+
+    /// An endpoint nothing listens on
+    fn unreachable() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    fn request() -> RelayRequest {
+        RelayRequest {
+            operation: OperationType::UnshieldSol,
+            nullifier: [1; 32],
+            output: RelayOutput::Unshield {
+                recipient: "11111111111111111111111111111111".into(),
+                amount: 1_000_000_000,
+            },
+            amount: 0,
+            proof: vec![0; 256],
+            merkle_root: [2; 32],
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee: 3_000_000,
+            max_fee: 3_000_000,
+            quote_hash: None,
+            work_nonce: None,
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_submit_polls_until_confirmed() {
+        let (endpoint, server) = serve(vec![
+            response(RelayStatus::Pending),
+            response(RelayStatus::Submitted { signature: "sig".into() }),
+            response(RelayStatus::Confirmed { signature: "sig".into(), slot: 9 }),
+        ]);
+        let confirmed = block_on(client(endpoint).submit(request())).unwrap();
+        assert_eq!(confirmed.status, RelayStatus::Confirmed { signature: "sig".into(), slot: 9 });
+        assert_eq!(
+            server.join().unwrap(),
+            ["POST /relay HTTP/1.1", "GET /status/req_1 HTTP/1.1", "GET /status/req_1 HTTP/1.1"]
+        );
+    }
+
+    #[test]
+    fn test_submit_failures() {
+        let (endpoint, server) = serve(vec![
+            response(RelayStatus::Pending),
+            response(RelayStatus::Failed { reason: "blockhash expired".into() }),
+        ]);
+        let result = block_on(client(endpoint).submit(request()));
+        assert!(matches!(
+            result,
+            Err(RelayerError::TransactionRejected(r)) if r == "blockhash expired"
+        ));
+        server.join().unwrap();
+
+        let (endpoint, server) = serve(vec![(400, "unknown root".into())]);
+        let result = block_on(client(endpoint).submit(request()));
+        assert!(matches!(
+            result,
+            Err(RelayerError::TransactionRejected(r)) if r.contains("unknown root")
+        ));
+        server.join().unwrap();
+
+        let (endpoint, server) = serve(vec![(200, "not json".into())]);
+        let result = block_on(client(endpoint).submit(request()));
+        assert!(matches!(result, Err(RelayerError::InvalidResponse(_))));
+        server.join().unwrap();
+
+        // Nothing listening
+        let result = block_on(client(unreachable()).submit(request()));
+        assert!(matches!(result, Err(RelayerError::NetworkError(_))));
+    }
+
+    #[test]
+    fn test_submit_fails_over() {
+        let confirmed = RelayStatus::Confirmed { signature: "sig".into(), slot: 9 };
+        let (rejecting, first) = serve(vec![(400, "busy".into())]);
+        let (accepting, second) = serve(vec![response(confirmed.clone())]);
+        let mut client = RelayerClient::with_settings(100, 5).with_poll_interval(10);
+        client.add_relayer(relayer("cheap", rejecting, 10));
+        client.add_relayer(relayer("unreachable", unreachable(), 20));
+        client.add_relayer(relayer("accepting", accepting, 30));
+        // Charges more than the request allows
+        client.add_relayer(relayer("expensive", unreachable(), 50));
+
+        let response = block_on(client.submit(request())).unwrap();
+        assert_eq!(response.status, confirmed);
+        assert_eq!(first.join().unwrap(), ["POST /relay HTTP/1.1"]);
+        assert_eq!(second.join().unwrap(), ["POST /relay HTTP/1.1"]);
+
+        // Only the unreachable relayer was marked offline
+        let online: Vec<_> = client.relayers().into_iter().map(|r| (r.id, r.is_online)).collect();
+        assert_eq!(
+            online,
+            [
+                ("cheap".into(), true),
+                ("unreachable".into(), false),
+                ("accepting".into(), true),
+                ("expensive".into(), true),
+            ]
+        );
+
+        // Each attempt counts towards the relayer's reputation
+        let rejections = |id| client.reputation(id).rejections.into_iter().collect::<Vec<_>>();
+        assert_eq!(rejections("cheap"), [(RelayRejection::Rejected, 1)]);
+        assert_eq!(rejections("unreachable"), [(RelayRejection::Network, 1)]);
+        let accepting = client.reputation("accepting");
+        assert_eq!((accepting.submissions, accepting.successes), (1, 1));
+        assert!(accepting.confirmation_ms.is_some());
+        assert_eq!(client.reputation("expensive").submissions, 0);
+
+        // The last relayer's error when every one fails
+        let (rejecting, server) = serve(vec![(400, "busy".into())]);
+        let mut client = RelayerClient::with_settings(100, 5);
+        client.add_relayer(relayer("unreachable", unreachable(), 10));
+        client.add_relayer(relayer("rejecting", rejecting, 20));
+        let result = block_on(client.submit(request()));
+        assert!(matches!(result, Err(RelayerError::TransactionRejected(r)) if r.contains("busy")));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_health_checks() {
+        let operations = r#"["Transfer","UnshieldSol"]"#;
+        let (healthy, server) = serve(vec![(200, operations.into()), (503, String::new())]);
+        let mut client = RelayerClient::with_settings(100, 5);
+        for (id, endpoint) in [("healthy", healthy), ("down", unreachable())] {
+            client.add_relayer(RelayerInfo { is_online: false, ..relayer(id, endpoint, 30) });
+        }
+        assert!(client.select_relayer(&OperationType::UnshieldSol).is_err());
+
+        block_on(client.check_health());
+        let relayers = client.relayers();
+        assert!(relayers[0].is_online && relayers[0].latency_ms.is_some());
+        assert_eq!(
+            relayers[0].supported_operations,
+            [OperationType::Transfer, OperationType::UnshieldSol]
+        );
+        assert!(!relayers[1].is_online && relayers[1].latency_ms.is_none());
+        assert_eq!(client.select_relayer(&OperationType::UnshieldSol).unwrap().id, "healthy");
+
+        // Errors are failed probes
+        block_on(client.check_health());
+        assert!(!client.relayers()[0].is_online);
+        assert_eq!(server.join().unwrap(), ["GET /health HTTP/1.1", "GET /health HTTP/1.1"]);
+    }
+
+    fn signed_quote(seed: u8, fee: u64, expires_at: u64, work_bits: u8) -> SignedQuote {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let quote = FeeQuote {
+            operation: OperationType::UnshieldSol,
+            amount: 1_000_000_000,
+            fee_bps: 30,
+            fee,
+            network_fee: 5_000,
+            expires_at,
+            relayer: bs58::encode(public.as_bytes()).into_string(),
+            work_bits,
+        };
+        SignedQuote::sign(quote, &ed25519_dalek::Keypair { secret, public })
+    }
+
+    #[test]
+    fn test_quoted_submit() {
+        // The cheaper relayer's quote does not match its signature
+        let mut forged = signed_quote(1, 1_000_000, unix_now() + 60, 0);
+        forged.quote.fee = 1;
+        // The quoting relayer asks for work
+        let genuine = signed_quote(2, 3_000_000, unix_now() + 60, 8);
+        let confirmed = RelayStatus::Confirmed { signature: "sig".into(), slot: 9 };
+        let (cheap, first) = serve(vec![(200, serde_json::to_string(&forged).unwrap())]);
+        let (quoting, second) = serve(vec![
+            (200, serde_json::to_string(&genuine).unwrap()),
+            response(confirmed.clone()),
+        ]);
+        let mut client = RelayerClient::with_settings(100, 5).with_poll_interval(10);
+        client.add_relayer(relayer("cheap", cheap, 10));
+        client.add_relayer(relayer("quoting", quoting, 30));
+
+        let quote = block_on(client.request_quote(OperationType::UnshieldSol, 1_000_000_000));
+        assert_eq!(quote.unwrap(), genuine);
+        let request = RelayRequest { quote_hash: Some(genuine.quote.hash()), ..request() };
+
+        // Refused before reaching a relayer
+        let greedy = RelayRequest { fee: 3_000_001, ..request.clone() };
+        let result = block_on(client.submit(greedy));
+        assert!(matches!(
+            result,
+            Err(RelayerError::FeeExceedsQuote { charged: 3_000_001, quoted: 3_000_000 })
+        ));
+        let unknown = RelayRequest { quote_hash: Some([0; 32]), ..request.clone() };
+        let result = block_on(client.submit(unknown));
+        assert!(matches!(result, Err(RelayerError::InvalidQuote(_))));
+
+        // Sent to the quoting relayer only, with the work done
+        assert!(matches!(client.check_request(&request), Err(RelayerError::InvalidQuote(_))));
+        let mut worked = request.clone();
+        client.prove_work(&mut worked);
+        assert!(genuine.quote.has_work(&worked));
+        assert_eq!(client.check_request(&worked).unwrap().id, "quoting");
+        let response = block_on(client.submit(request)).unwrap();
+        assert_eq!(response.status, confirmed);
+        assert_eq!(first.join().unwrap(), ["POST /quote HTTP/1.1"]);
+        assert_eq!(second.join().unwrap(), ["POST /quote HTTP/1.1", "POST /relay HTTP/1.1"]);
+
+        // An expired quote is not accepted
+        let expired = signed_quote(2, 3_000_000, unix_now(), 0);
+        assert!(matches!(
+            client.accept_quote("quoting", &expired),
+            Err(RelayerError::InvalidQuote(_))
+        ));
+        let demanding = signed_quote(2, 3_000_000, unix_now() + 60, pow::MAX_WORK_BITS + 1);
+        assert!(matches!(
+            client.accept_quote("quoting", &demanding),
+            Err(RelayerError::InvalidQuote(_))
+        ));
+    }
+
+    #[test]
+    fn test_relay_without_waiting() {
+        let (endpoint, server) = serve(vec![response(RelayStatus::Pending)]);
+        let client = client(endpoint);
+        let response = block_on(client.relay(request())).unwrap();
+        assert_eq!(response.status, RelayStatus::Pending);
+        assert_eq!(server.join().unwrap(), ["POST /relay HTTP/1.1"]);
+
+        let result = block_on(client.subscribe_status("req_2"));
+        assert!(matches!(result, Err(RelayerError::UnknownRequest(_))));
+    }
+
+    #[test]
+    fn test_status_stream() {
+        use futures_util::SinkExt;
+
+        block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint = format!("http://{}", listener.local_addr().unwrap());
+            let submitted = RelayStatus::Submitted { signature: "sig".into() };
+            let confirmed = RelayStatus::Confirmed { signature: "sig".into(), slot: 9 };
+            // One stream to confirmation, one closed early
+            let streams = vec![
+                vec![RelayStatus::Pending, RelayStatus::Pending, submitted.clone(), confirmed.clone()],
+                vec![RelayStatus::Pending],
+            ];
+            let server = tokio::spawn(async move {
+                for statuses in streams {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    for status in statuses {
+                        socket.send(Message::Text(response(status).1)).await.unwrap();
+                    }
+                    socket.close(None).await.unwrap();
+                }
+            });
+
+            let client = client(endpoint.clone());
+            client.track("req_1", &endpoint);
+            let mut stream = client.subscribe_status("req_1").await.unwrap();
+            let mut statuses = Vec::new();
+            while let Some(status) = stream.next().await {
+                statuses.push(status.unwrap());
+            }
+            assert_eq!(statuses, [RelayStatus::Pending, submitted, confirmed]);
+
+            let mut stream = client.subscribe_status("req_1").await.unwrap();
+            assert_eq!(stream.next().await.unwrap().unwrap(), RelayStatus::Pending);
+            assert!(matches!(stream.next().await, Some(Err(RelayerError::NetworkError(_)))));
+            assert!(stream.next().await.is_none());
+            server.await.unwrap();
+        });
+    }
+}
@@ -5,24 +5,65 @@
 //! of users, paying for gas fees and receiving a fee in return.
 //!
 //! Key components:
-//! - `RelayerClient`: Client for communicating with relayers
+//! - `RelayerClient`: Client for communicating with relayers, submitting
+//!   over HTTP with the `http` feature. It health-checks relayers and fails
+//!   over to the next eligible one when a submission is rejected or times out,
+//!   and streams a request's status over a websocket
 //! - `RelayRequest`: Transaction request to be submitted by a relayer
 //! - `FeeEstimator`: Utility for estimating relayer fees
+//! - `quote`: Signed fee quotes a request can be submitted under
+//! - `pow`: Proof of work a relayer's quote can ask requests to carry
+//! - `reputation`: Per-relayer track record and the scoring that ranks
+//!   relayers by it
+//! - `registry`: Decoding of the program's staked relayer registry, which
+//!   `RelayerClient::load_from_chain` fills the relayer list from
 //!
 //! Privacy model:
 //! - Relayers can see the nullifier, new commitment, and proof
 //! - Relayers CANNOT see the sender, recipient, or amount
 //! - The user's IP address may be visible to the relayer (use Tor for anonymity)
 
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+use ark_bn254::Fr;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::proof::{recipient_hash, TransferVerifier};
+use crate::telemetry::{Telemetry, TelemetryEvent};
+use crate::verify::encoding::field_from_bytes_be;
+
+#[cfg(feature = "http")]
+mod http;
+pub mod pow;
+pub mod quote;
+pub mod registry;
+pub mod reputation;
+
+#[cfg(feature = "http")]
+pub use http::StatusStream;
+pub use quote::{FeeQuote, QuoteRequest, SignedQuote};
+pub use registry::RegistryFilter;
+pub use reputation::{Reputation, Scoring};
+
 /// Default relayer fee in basis points (0.3%)
 pub const DEFAULT_FEE_BPS: u16 = 30;
 
 /// Maximum acceptable fee in basis points (5%)
 pub const MAX_FEE_BPS: u16 = 500;
 
+/// Default interval between status polls (milliseconds)
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 1_000;
+
+/// Timeout of a health probe (seconds)
+pub const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Requests relayed with `RelayerClient::relay` whose relayer is remembered
+/// for `subscribe_status`
+pub const MAX_TRACKED_REQUESTS: usize = 256;
+
 /// Errors that can occur during relayer operations
 #[derive(Error, Debug)]
 pub enum RelayerError {
@@ -40,6 +81,16 @@ pub enum RelayerError {
     Timeout,
     #[error("Proof invalid")]
     InvalidProof,
+    #[error("Invalid quote: {0}")]
+    InvalidQuote(String),
+    #[error("Fee of {charged} lamports exceeds the quoted {quoted}")]
+    FeeExceedsQuote { charged: u64, quoted: u64 },
+    #[error("Relayer fee of {fee} exceeds the request's maximum of {max_fee}")]
+    FeeExceedsMax { fee: u64, max_fee: u64 },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Unknown request: {0}")]
+    UnknownRequest(String),
 }
 
 /// Status of a relay request
@@ -55,6 +106,13 @@ pub enum RelayStatus {
     Failed { reason: String },
 }
 
+impl RelayStatus {
+    /// Whether the status is final: confirmed or failed
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Confirmed { .. } | Self::Failed { .. })
+    }
+}
+
 /// A request to relay a private transaction
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelayRequest {
@@ -64,12 +122,66 @@ pub struct RelayRequest {
     pub nullifier: [u8; 32],
     /// New commitment (for transfers) or recipient (for unshields)
     pub output: RelayOutput,
+    /// Amount a transfer moves, for the client's fee checks (lamports, or
+    /// the token's base units); unshields carry theirs in `output`
+    ///
+    /// A transfer's amount is private, so it is never serialized: the
+    /// relayer sees only the proven `fee` and the `max_fee`.
+    #[serde(skip)]
+    pub amount: u64,
     /// zkSNARK proof (256 bytes for Groth16)
     pub proof: Vec<u8>,
     /// Merkle root the proof was generated against
     pub merkle_root: [u8; 32],
+    /// Epoch of the tree holding the spent note
+    #[serde(default)]
+    pub tree_epoch: u32,
+    /// Asset ID of the pool, bound by the proof
+    pub asset_id: [u8; 32],
+    /// Fee paid to the relayer out of the note, bound by the proof
+    pub fee: u64,
     /// Maximum fee the user is willing to pay (in lamports)
     pub max_fee: u64,
+    /// Hash of the `FeeQuote` the fee was proven under, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_hash: Option<[u8; 32]>,
+    /// Nonce proving the work the quote asks for, if it asks for any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work_nonce: Option<u64>,
+}
+
+impl RelayRequest {
+    /// Public inputs of the request's proof, in circuit order
+    ///
+    /// Transfers prove merkle_root, nullifier, new commitment, asset_id and
+    /// fee; SOL unshields merkle_root, nullifier, recipient hash, amount,
+    /// fee and asset_id. `None` for token unshields, mismatched outputs, and fields that
+    /// are not canonical field elements or keys.
+    pub fn public_inputs(&self) -> Option<Vec<Fr>> {
+        let root = field_from_bytes_be(&self.merkle_root)?;
+        let nullifier = field_from_bytes_be(&self.nullifier)?;
+        match (&self.operation, &self.output) {
+            (OperationType::Transfer, RelayOutput::Commitment(commitment)) => Some(vec![
+                root,
+                nullifier,
+                field_from_bytes_be(commitment)?,
+                field_from_bytes_be(&self.asset_id)?,
+                Fr::from(self.fee),
+            ]),
+            (OperationType::UnshieldSol, RelayOutput::Unshield { recipient, amount }) => {
+                let recipient = bs58::decode(recipient).into_vec().ok()?.try_into().ok()?;
+                Some(vec![
+                    root,
+                    nullifier,
+                    recipient_hash(&recipient),
+                    Fr::from(*amount),
+                    Fr::from(self.fee),
+                    field_from_bytes_be(&self.asset_id)?,
+                ])
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Type of relay operation
@@ -109,7 +221,7 @@ pub struct RelayResponse {
 }
 
 /// Information about a relayer
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RelayerInfo {
     /// Relayer's public identifier
     pub id: String,
@@ -125,16 +237,64 @@ pub struct RelayerInfo {
     pub is_online: bool,
     /// Average confirmation time (seconds)
     pub avg_confirmation_time: u32,
+    /// Lamports staked in the on-chain registry (0 if added by hand)
+    pub stake: u64,
+    /// Smoothed round trip of health probes (milliseconds), `None` until
+    /// probed
+    #[serde(default)]
+    pub latency_ms: Option<u32>,
+}
+
+impl RelayerInfo {
+    /// Record a health probe: its round trip, or `None` if it failed
+    ///
+    /// A failed probe marks the relayer offline and a successful one online.
+    /// Each round trip moves `latency_ms` a quarter of the way towards it.
+    pub fn record_probe(&mut self, round_trip: Option<Duration>) {
+        let Some(round_trip) = round_trip else {
+            self.is_online = false;
+            return;
+        };
+        let sample = round_trip.as_millis().min(u32::MAX as u128) as u64;
+        let latency = match self.latency_ms {
+            Some(average) => (3 * average as u64 + sample) / 4,
+            None => sample,
+        };
+        self.latency_ms = Some(latency as u32);
+        self.is_online = true;
+    }
 }
 
 /// Client for interacting with relayers
 pub struct RelayerClient {
-    /// List of known relayers
-    relayers: Vec<RelayerInfo>,
+    /// List of known relayers, shared with the health check task
+    relayers: Arc<RwLock<Vec<RelayerInfo>>>,
     /// Maximum acceptable fee (basis points)
     max_fee_bps: u16,
     /// Request timeout (seconds)
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
     timeout_secs: u32,
+    /// Interval between status polls (milliseconds)
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    poll_interval_ms: u64,
+    /// HTTP client, shared across requests
+    #[cfg(feature = "http")]
+    http: reqwest::Client,
+    /// Opt-in telemetry
+    telemetry: Option<Arc<Telemetry>>,
+    /// Checks transfer proofs before they are sent
+    verifier: Option<Arc<TransferVerifier>>,
+    /// Accepted quotes by hash, with the id of the relayer that signed them
+    quotes: Mutex<HashMap<[u8; 32], (String, FeeQuote)>>,
+    /// Ranks eligible relayers
+    scoring: Scoring,
+    /// Track record of each relayer submitted to, by id
+    reputations: Mutex<HashMap<String, Reputation>>,
+    /// Latest requests relayed without waiting, with their relayer's
+    /// endpoint
+    tracked: Mutex<VecDeque<(String, String)>>,
+    /// Registered relayers used when loading from the chain
+    registry_filter: RegistryFilter,
 }
 
 impl Default for RelayerClient {
@@ -146,43 +306,189 @@ impl Default for RelayerClient {
 impl RelayerClient {
     /// Create a new relayer client
     pub fn new() -> Self {
-        Self {
-            relayers: Vec::new(),
-            max_fee_bps: MAX_FEE_BPS,
-            timeout_secs: 60,
-        }
+        Self::with_settings(MAX_FEE_BPS, 60)
     }
 
     /// Create client with custom settings
     pub fn with_settings(max_fee_bps: u16, timeout_secs: u32) -> Self {
         Self {
-            relayers: Vec::new(),
+            relayers: Arc::default(),
             max_fee_bps,
             timeout_secs,
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            #[cfg(feature = "http")]
+            http: reqwest::Client::new(),
+            telemetry: None,
+            verifier: None,
+            quotes: Mutex::default(),
+            scoring: Scoring::default(),
+            reputations: Mutex::default(),
+            tracked: Mutex::default(),
+            registry_filter: RegistryFilter::default(),
         }
     }
 
+    /// Poll a submitted request's status every `poll_interval_ms`
+    pub fn with_poll_interval(mut self, poll_interval_ms: u64) -> Self {
+        self.poll_interval_ms = poll_interval_ms;
+        self
+    }
+
+    /// Report submission outcomes to `telemetry`
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Verify transfer proofs with `verifier` before submitting them, so an
+    /// invalid proof fails here instead of costing a relayer a transaction
+    pub fn with_verifier(mut self, verifier: Arc<TransferVerifier>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Rank relayers with `scoring`
+    pub fn with_scoring(mut self, scoring: Scoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    /// Use only the registered relayers `filter` admits when loading from
+    /// the chain
+    pub fn with_registry_filter(mut self, filter: RegistryFilter) -> Self {
+        self.registry_filter = filter;
+        self
+    }
+
     /// Add a relayer to the client
     pub fn add_relayer(&mut self, relayer: RelayerInfo) {
-        self.relayers.push(relayer);
-    }
-
-    /// Add the default mainnet relayers
-    pub fn add_default_relayers(&mut self) {
-        // TODO: Add actual relayer endpoints when deployed
-        // For now, this is a placeholder for production relayers
-        self.relayers.push(RelayerInfo {
-            id: "nyx-relayer-1".to_string(),
-            endpoint: "https://relayer1.nyx.network".to_string(),
-            fee_bps: DEFAULT_FEE_BPS,
-            min_amount: 10_000, // 0.00001 SOL
-            supported_operations: vec![
-                OperationType::Transfer,
-                OperationType::UnshieldSol,
-            ],
-            is_online: false, // Will be updated on health check
-            avg_confirmation_time: 5,
-        });
+        self.write_relayers().push(relayer);
+    }
+
+    /// The known relayers, with their latest health
+    pub fn relayers(&self) -> Vec<RelayerInfo> {
+        self.read_relayers().clone()
+    }
+
+    /// What the client has seen of the relayer `id`
+    pub fn reputation(&self, id: &str) -> Reputation {
+        self.lock_reputations().get(id).cloned().unwrap_or_default()
+    }
+
+    /// Record a health probe of the relayer `id`; see
+    /// `RelayerInfo::record_probe`
+    pub fn record_probe(&self, id: &str, round_trip: Option<Duration>) {
+        if let Some(relayer) = self.write_relayers().iter_mut().find(|r| r.id == id) {
+            relayer.record_probe(round_trip);
+        }
+    }
+
+    /// Probe every relayer's `/health` endpoint once, marking each online
+    /// or offline
+    #[cfg(feature = "http")]
+    pub async fn check_health(&self) {
+        http::probe_relayers(&self.http, &self.relayers, health_check_timeout()).await
+    }
+
+    /// Probe every relayer every `interval` on the current tokio runtime,
+    /// until the returned task is aborted
+    #[cfg(feature = "http")]
+    pub fn spawn_health_checks(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let (http, relayers) = (self.http.clone(), self.relayers.clone());
+        tokio::spawn(async move {
+            loop {
+                http::probe_relayers(&http, &relayers, health_check_timeout()).await;
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Add the relayers registered with the program at `program_id`
+    ///
+    /// Fetches every `RelayerEntry` account over `rpc`. Relayers start
+    /// offline until health-checked. Returns the number added.
+    #[cfg(feature = "rpc")]
+    pub fn add_default_relayers(
+        &mut self,
+        rpc: &crate::rpc::RpcClient,
+        program_id: &crate::transaction::Pubkey,
+    ) -> crate::error::VeilResult<usize> {
+        let accounts = registry::fetch_entries(rpc, program_id)?;
+        Ok(self.add_registry_entries(accounts.iter().map(Vec::as_slice)))
+    }
+
+    /// Replace the registered relayers with those of the program at
+    /// `program_id`, read over the RPC endpoint at `rpc_url`
+    ///
+    /// Only relayers the client's `RegistryFilter` admits are used; see
+    /// `registry::sync_relayers`. New relayers start offline until
+    /// health-checked. Returns the number of registered relayers.
+    #[cfg(feature = "rpc")]
+    pub fn load_from_chain(
+        &self,
+        rpc_url: &str,
+        program_id: &crate::transaction::Pubkey,
+    ) -> crate::error::VeilResult<usize> {
+        let accounts = registry::fetch_entries(&crate::rpc::RpcClient::new(rpc_url), program_id)?;
+        Ok(self.sync_registry(accounts.iter().map(Vec::as_slice)))
+    }
+
+    /// Reload the registered relayers like `load_from_chain` every
+    /// `interval` on the current tokio runtime, until the returned task is
+    /// aborted
+    ///
+    /// A failed reload keeps the current relayers.
+    #[cfg(all(feature = "rpc", feature = "http"))]
+    pub fn spawn_registry_refresh(
+        &self,
+        rpc_url: &str,
+        program_id: crate::transaction::Pubkey,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let (relayers, filter) = (self.relayers.clone(), self.registry_filter.clone());
+        let rpc = Arc::new(crate::rpc::RpcClient::new(rpc_url));
+        tokio::spawn(async move {
+            loop {
+                let fetch = rpc.clone();
+                let fetched =
+                    tokio::task::spawn_blocking(move || registry::fetch_entries(&fetch, &program_id))
+                        .await;
+                if let Ok(Ok(accounts)) = fetched {
+                    let mut relayers = relayers.write().unwrap_or_else(|e| e.into_inner());
+                    registry::sync_relayers(&mut relayers, accounts.iter().map(Vec::as_slice), &filter);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Replace the registered relayers with those of raw `RelayerEntry`
+    /// account data the client's `RegistryFilter` admits
+    ///
+    /// See `registry::sync_relayers`. Returns the number of registered
+    /// relayers.
+    pub fn sync_registry<'a>(&self, accounts: impl IntoIterator<Item = &'a [u8]>) -> usize {
+        registry::sync_relayers(&mut self.write_relayers(), accounts, &self.registry_filter)
+    }
+
+    /// Add relayers from raw `RelayerEntry` account data
+    ///
+    /// Accounts that do not decode, or stake less than
+    /// `registry::MIN_RELAYER_STAKE`, are skipped. Returns the number added.
+    pub fn add_registry_entries<'a>(
+        &mut self,
+        accounts: impl IntoIterator<Item = &'a [u8]>,
+    ) -> usize {
+        let mut relayers = self.write_relayers();
+        let before = relayers.len();
+        relayers.extend(
+            accounts
+                .into_iter()
+                .filter_map(|data| registry::RelayerEntry::decode(data).ok())
+                .filter(|entry| entry.stake >= registry::MIN_RELAYER_STAKE)
+                .map(registry::RelayerEntry::into_info),
+        );
+        relayers.len() - before
     }
 
     /// Select the best relayer for a given operation
@@ -191,79 +497,419 @@ impl RelayerClient {
     /// 1. Must support the operation type
     /// 2. Must be online
     /// 3. Fee must be within acceptable range
-    /// 4. Prefer lower fees and faster confirmation
-    pub fn select_relayer(&self, operation: &OperationType) -> Result<&RelayerInfo, RelayerError> {
-        let eligible: Vec<_> = self.relayers.iter()
+    /// 4. Prefer the highest `Scoring` score, blending fee, success rate,
+    ///    confirmation time and latency; or draw at random, weighted by
+    ///    score, when the scoring randomizes
+    pub fn select_relayer(&self, operation: &OperationType) -> Result<RelayerInfo, RelayerError> {
+        self.eligible_relayers(operation)
+            .into_iter()
+            .next()
+            .ok_or(RelayerError::NoRelayersAvailable)
+    }
+
+    /// Every relayer `select_relayer` could pick, best first
+    pub fn eligible_relayers(&self, operation: &OperationType) -> Vec<RelayerInfo> {
+        let mut eligible: Vec<_> = self.read_relayers().iter()
             .filter(|r| r.is_online)
             .filter(|r| r.supported_operations.contains(operation))
             .filter(|r| r.fee_bps <= self.max_fee_bps)
+            .cloned()
             .collect();
 
-        if eligible.is_empty() {
-            return Err(RelayerError::NoRelayersAvailable);
-        }
-
-        // Select by lowest fee, then fastest confirmation
-        eligible.into_iter()
-            .min_by_key(|r| (r.fee_bps, r.avg_confirmation_time))
-            .ok_or(RelayerError::NoRelayersAvailable)
+        // Equal scores go to the lowest fee, then fastest confirmation,
+        // then lowest probe latency
+        eligible.sort_by_key(|r| {
+            (r.fee_bps, r.avg_confirmation_time, r.latency_ms.unwrap_or(u32::MAX))
+        });
+        let reputations = self.lock_reputations();
+        let scored = eligible
+            .into_iter()
+            .map(|r| {
+                let reputation = reputations.get(&r.id).cloned().unwrap_or_default();
+                (self.scoring.score(&r, &reputation, self.max_fee_bps), r)
+            })
+            .collect();
+        self.scoring.rank(scored)
     }
 
     /// Estimate fee for a relay operation
     ///
-    /// Returns (relayer_fee, network_fee) in lamports
+    /// Returns (relayer_fee, network_fee): the relayer's fee in the units
+    /// of `amount` (lamports, or a token's base units), the network fee in
+    /// lamports
     pub fn estimate_fee(&self, operation: &OperationType, amount: u64) -> Result<(u64, u64), RelayerError> {
         let relayer = self.select_relayer(operation)?;
+        Ok((relayer_fee(&relayer, amount), network_fee(operation)))
+    }
+
+    /// Estimate the fees of `request` from the amount it moves, like
+    /// `estimate_fee`
+    ///
+    /// A transfer's `amount` must be set.
+    pub fn estimate_request_fee(&self, request: &RelayRequest) -> Result<(u64, u64), RelayerError> {
+        self.estimate_fee(&request.operation, self.get_amount(request)?)
+    }
+
+    /// Submit a relay request and wait for it to confirm
+    ///
+    /// Posts the request to the selected relayer, then polls its status
+    /// until the transaction is confirmed or fails, for at most the
+    /// client's timeout. A failed transaction is `TransactionRejected`.
+    /// Without the `http` feature every submission is a `NetworkError`.
+    ///
+    /// A relayer that rejects the request or times out is skipped for the
+    /// next eligible one whose fee the request still covers; an unreachable
+    /// one is also marked offline. The last relayer's error is returned. A
+    /// timed-out transaction may still land, in which case the next
+    /// relayer's fails on the spent nullifier.
+    ///
+    /// A request with a `quote_hash` goes only to the relayer that signed
+    /// the quote, and only if the quote was accepted by this client, still
+    /// applies, and covers the request's fee. The work the quote asks for
+    /// is done first if the request lacks it; see `prove_work`.
+    pub async fn submit(&self, request: RelayRequest) -> Result<RelayResponse, RelayerError> {
+        let result = self.submit_inner(request, true).await;
+        self.record_submission(result)
+    }
+
+    /// Submit a relay request without waiting for it to confirm
+    ///
+    /// Makes the same checks and fails over like `submit`, up to the first
+    /// relayer that accepts the request, and returns its response. Follow
+    /// the request with `subscribe_status`.
+    pub async fn relay(&self, request: RelayRequest) -> Result<RelayResponse, RelayerError> {
+        let result = self.submit_inner(request, false).await;
+        self.record_submission(result)
+    }
+
+    /// Stream the status of a request sent with `relay` from its relayer
+    ///
+    /// The client remembers the relayers of its last
+    /// `MAX_TRACKED_REQUESTS` relayed requests; others are
+    /// `UnknownRequest`.
+    #[cfg(feature = "http")]
+    pub async fn subscribe_status(&self, request_id: &str) -> Result<StatusStream, RelayerError> {
+        let endpoint = self
+            .lock_tracked()
+            .iter()
+            .find(|(id, _)| id == request_id)
+            .map(|(_, endpoint)| endpoint.clone())
+            .ok_or_else(|| RelayerError::UnknownRequest(request_id.to_string()))?;
+        let timeout = Duration::from_secs(self.timeout_secs.into());
+        http::subscribe_status(&endpoint, request_id, timeout).await
+    }
 
-        // Relayer fee = amount * fee_bps / 10000
-        let relayer_fee = (amount as u128 * relayer.fee_bps as u128 / 10000) as u64;
+    fn record_submission(
+        &self,
+        result: Result<RelayResponse, RelayerError>,
+    ) -> Result<RelayResponse, RelayerError> {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record(match &result {
+                Ok(_) => TelemetryEvent::RelaySubmitted,
+                Err(e) => TelemetryEvent::RelayRejected(e.into()),
+            });
+        }
+        result
+    }
 
-        // Estimated network fee (transaction + account creation)
-        let network_fee = match operation {
-            OperationType::Transfer => 5_000, // ~5000 lamports
-            OperationType::UnshieldSol => 5_000,
-            OperationType::UnshieldToken { .. } => 10_000, // Includes ATA creation
+    async fn submit_inner(
+        &self,
+        mut request: RelayRequest,
+        wait: bool,
+    ) -> Result<RelayResponse, RelayerError> {
+        self.prove_work(&mut request);
+        let selected = self.check_request(&request)?;
+        let quoted = request.quote_hash.is_some();
+        let relayers = if quoted {
+            vec![selected]
+        } else {
+            self.eligible_relayers(&request.operation)
         };
+        // Checked above unless quoted, when the quote fixes the fee
+        let amount = self.get_amount(&request).unwrap_or_default();
+        let mut result = Err(RelayerError::NoRelayersAvailable);
+        for relayer in relayers {
+            if !quoted && relayer_fee(&relayer, amount) > request.max_fee {
+                continue;
+            }
+            let start = Instant::now();
+            result = self.send(&relayer, &request, wait).await;
+            let mut reputations = self.lock_reputations();
+            let reputation = reputations.entry(relayer.id.clone()).or_default();
+            match &result {
+                Ok(response) => {
+                    // Only confirmations say how fast the relayer is
+                    if response.status.is_terminal() {
+                        reputation.record_success(start.elapsed());
+                    }
+                    if !wait {
+                        self.track(&response.request_id, &relayer.endpoint);
+                    }
+                    break;
+                }
+                Err(e) => reputation.record_failure(e),
+            }
+            drop(reputations);
+            if let Err(RelayerError::NetworkError(_)) = &result {
+                self.record_probe(&relayer.id, None);
+            }
+        }
+        result
+    }
+
+    /// Run the checks `submit` makes before sending a request
+    ///
+    /// Verifies the proof (with a verifier set) and the fee, against the
+    /// request's quote if it has one, and returns the relayer the request
+    /// would go to.
+    pub fn check_request(&self, request: &RelayRequest) -> Result<RelayerInfo, RelayerError> {
+        self.precheck_proof(request)?;
+        if let Some(hash) = &request.quote_hash {
+            return self.check_quoted(request, hash);
+        }
 
-        Ok((relayer_fee, network_fee))
+        let amount = self.get_amount(request)?;
+        let relayer = self.select_relayer(&request.operation)?;
+        let fee = relayer_fee(&relayer, amount);
+        if fee > request.max_fee {
+            return Err(RelayerError::FeeExceedsMax {
+                fee,
+                max_fee: request.max_fee,
+            });
+        }
+        Ok(relayer)
     }
 
-    /// Submit a relay request (mock implementation)
+    /// The relayer that signed the quote `hash`, if the quote covers
+    /// `request`
+    fn check_quoted(
+        &self,
+        request: &RelayRequest,
+        hash: &[u8; 32],
+    ) -> Result<RelayerInfo, RelayerError> {
+        let (relayer_id, quote) = self
+            .lock_quotes()
+            .get(hash)
+            .cloned()
+            .ok_or_else(|| RelayerError::InvalidQuote("unknown quote".into()))?;
+        if let Some(reason) = quote.mismatch(request, quote::unix_now()) {
+            return Err(RelayerError::InvalidQuote(reason.into()));
+        }
+        if request.fee > quote.fee {
+            return Err(RelayerError::FeeExceedsQuote {
+                charged: request.fee,
+                quoted: quote.fee,
+            });
+        }
+        if !quote.has_work(request) {
+            return Err(RelayerError::InvalidQuote("missing proof of work".into()));
+        }
+        self.read_relayers()
+            .iter()
+            .find(|r| r.id == relayer_id)
+            .cloned()
+            .ok_or(RelayerError::NoRelayersAvailable)
+    }
+
+    /// Set the request's `work_nonce` to the work its quote asks for
     ///
-    /// In production, this would:
-    /// 1. Select a relayer
-    /// 2. Send the request to the relayer's API
-    /// 3. Wait for submission confirmation
-    /// 4. Return the transaction signature
-    pub async fn submit(&self, request: RelayRequest) -> Result<RelayResponse, RelayerError> {
-        // Validate fee
-        let (relayer_fee, _network_fee) = self.estimate_fee(&request.operation, self.get_amount(&request))?;
-        if relayer_fee > request.max_fee {
-            return Err(RelayerError::FeeTooHigh(
-                (relayer_fee * 10000 / self.get_amount(&request)) as u16,
-                self.max_fee_bps,
-            ));
+    /// Does nothing if the request already carries the work, has no quote
+    /// or a quote this client has not accepted. Blocks for about
+    /// `2^work_bits` hashes, at most `pow::MAX_WORK_BITS` bits.
+    pub fn prove_work(&self, request: &mut RelayRequest) {
+        let Some(hash) = request.quote_hash else {
+            return;
+        };
+        let Some((_, quote)) = self.lock_quotes().get(&hash).cloned() else {
+            return;
+        };
+        if !quote.has_work(request) {
+            request.work_nonce = Some(pow::solve_work(&hash, &request.nullifier, quote.work_bits));
         }
+    }
 
-        // Select relayer
-        let _relayer = self.select_relayer(&request.operation)?;
+    /// Accept a quote the relayer `relayer_id` signed, so `submit` honours
+    /// requests carrying its hash
+    ///
+    /// The signature must verify, the quote must not have expired, its fee
+    /// must be within the client's `max_fee_bps`, and the work it asks for
+    /// at most `pow::MAX_WORK_BITS`. Returns the hash to embed in the
+    /// request.
+    #[cfg(any(feature = "rpc", feature = "http"))]
+    pub fn accept_quote(
+        &self,
+        relayer_id: &str,
+        signed: &SignedQuote,
+    ) -> Result<[u8; 32], RelayerError> {
+        let quote = &signed.quote;
+        let now = quote::unix_now();
+        if !signed.verify() {
+            return Err(RelayerError::InvalidQuote("bad signature".into()));
+        }
+        if quote.is_expired(now) {
+            return Err(RelayerError::InvalidQuote("quote expired".into()));
+        }
+        if quote.fee_bps > self.max_fee_bps {
+            return Err(RelayerError::FeeTooHigh(quote.fee_bps, self.max_fee_bps));
+        }
+        if quote.work_bits > pow::MAX_WORK_BITS {
+            return Err(RelayerError::InvalidQuote("asks for too much work".into()));
+        }
+        let hash = quote.hash();
+        let mut quotes = self.lock_quotes();
+        quotes.retain(|_, (_, quote)| !quote.is_expired(now));
+        quotes.insert(hash, (relayer_id.to_string(), quote.clone()));
+        Ok(hash)
+    }
 
-        // In production, this would make an HTTP request to the relayer
-        // For now, return a mock response
-        Ok(RelayResponse {
-            request_id: format!("req_{}", hex::encode(&request.nullifier[..8])),
-            status: RelayStatus::Pending,
-            fee: relayer_fee,
-            estimated_confirmation_time: Some(5),
-        })
+    /// Ask the best eligible relayer for a signed quote on `operation`
+    /// moving `amount`, and accept it
+    ///
+    /// Relayers that fail to answer with an acceptable quote are skipped
+    /// like in `submit`. Prove the quote's `fee`, and set the request's
+    /// `quote_hash` to the quote's hash.
+    #[cfg(feature = "http")]
+    pub async fn request_quote(
+        &self,
+        operation: OperationType,
+        amount: u64,
+    ) -> Result<SignedQuote, RelayerError> {
+        let request = QuoteRequest { operation, amount };
+        let timeout = Duration::from_secs(self.timeout_secs.into());
+        let mut result = Err(RelayerError::NoRelayersAvailable);
+        for relayer in self.eligible_relayers(&request.operation) {
+            result = http::post_quote(&self.http, &relayer.endpoint, &request, timeout).await;
+            let signed = match &result {
+                Ok(signed) => signed,
+                Err(RelayerError::NetworkError(_)) => {
+                    self.record_probe(&relayer.id, None);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            if signed.quote.operation != request.operation || signed.quote.amount != amount {
+                result = Err(RelayerError::InvalidQuote("quote is for another request".into()));
+                continue;
+            }
+            if let Err(e) = self.accept_quote(&relayer.id, signed) {
+                result = Err(e);
+                continue;
+            }
+            break;
+        }
+        result
+    }
+
+    /// Post `request` to `relayer`, then wait for it to confirm if `wait`
+    #[cfg(feature = "http")]
+    async fn send(
+        &self,
+        relayer: &RelayerInfo,
+        request: &RelayRequest,
+        wait: bool,
+    ) -> Result<RelayResponse, RelayerError> {
+        let timeout = Duration::from_secs(self.timeout_secs.into());
+        let response = http::post_relay(&self.http, &relayer.endpoint, request, timeout).await?;
+        if !wait {
+            return Ok(response);
+        }
+        http::await_confirmation(
+            &self.http,
+            &relayer.endpoint,
+            response,
+            timeout,
+            Duration::from_millis(self.poll_interval_ms),
+        )
+        .await
+    }
+
+    #[cfg(not(feature = "http"))]
+    async fn send(
+        &self,
+        _relayer: &RelayerInfo,
+        _request: &RelayRequest,
+        _wait: bool,
+    ) -> Result<RelayResponse, RelayerError> {
+        Err(RelayerError::NetworkError("built without the `http` feature".into()))
+    }
+
+    /// Verify a transfer's proof against the request's public inputs
+    ///
+    /// Passes requests of other operations, and every request when no
+    /// verifier is set.
+    pub fn precheck_proof(&self, request: &RelayRequest) -> Result<(), RelayerError> {
+        let (Some(verifier), OperationType::Transfer) = (&self.verifier, &request.operation) else {
+            return Ok(());
+        };
+        let Some(public_inputs) = request.public_inputs() else {
+            return Err(RelayerError::InvalidProof);
+        };
+        match verifier.verify(&request.proof, &public_inputs) {
+            Ok(true) => Ok(()),
+            _ => Err(RelayerError::InvalidProof),
+        }
     }
 
-    /// Get the amount from a relay request
-    fn get_amount(&self, request: &RelayRequest) -> u64 {
+    /// Amount the request moves, which relayer fees are charged on
+    fn get_amount(&self, request: &RelayRequest) -> Result<u64, RelayerError> {
         match &request.output {
-            RelayOutput::Commitment(_) => 1_000_000_000, // Default 1 SOL for transfers
-            RelayOutput::Unshield { amount, .. } => *amount,
+            RelayOutput::Commitment(_) if request.amount == 0 => Err(
+                RelayerError::InvalidRequest("transfer amount not set".into()),
+            ),
+            RelayOutput::Commitment(_) => Ok(request.amount),
+            RelayOutput::Unshield { amount, .. } => Ok(*amount),
+        }
+    }
+
+    fn read_relayers(&self) -> RwLockReadGuard<'_, Vec<RelayerInfo>> {
+        // Relayer updates are single assignments, so a poisoned list is whole
+        self.relayers.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_relayers(&self) -> RwLockWriteGuard<'_, Vec<RelayerInfo>> {
+        self.relayers.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_quotes(&self) -> MutexGuard<'_, HashMap<[u8; 32], (String, FeeQuote)>> {
+        self.quotes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_reputations(&self) -> MutexGuard<'_, HashMap<String, Reputation>> {
+        self.reputations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_tracked(&self) -> MutexGuard<'_, VecDeque<(String, String)>> {
+        self.tracked.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Remember the relayer `request_id` was relayed by, forgetting the
+    /// oldest request beyond `MAX_TRACKED_REQUESTS`
+    fn track(&self, request_id: &str, endpoint: &str) {
+        let mut tracked = self.lock_tracked();
+        if tracked.len() >= MAX_TRACKED_REQUESTS {
+            tracked.pop_front();
         }
+        tracked.push_back((request_id.to_string(), endpoint.to_string()));
+    }
+}
+
+/// Relayer fee = amount * fee_bps / 10000
+fn relayer_fee(relayer: &RelayerInfo, amount: u64) -> u64 {
+    (amount as u128 * relayer.fee_bps as u128 / 10000) as u64
+}
+
+#[cfg(feature = "http")]
+fn health_check_timeout() -> Duration {
+    Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS)
+}
+
+/// Estimated network fee of an operation (transaction + account creation),
+/// in lamports
+pub fn network_fee(operation: &OperationType) -> u64 {
+    match operation {
+        OperationType::Transfer => 5_000, // ~5000 lamports
+        OperationType::UnshieldSol => 5_000,
+        OperationType::UnshieldToken { .. } => 10_000, // Includes ATA creation
     }
 }
 
@@ -315,6 +961,7 @@ impl FeeEstimator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proof::field_to_bytes_be;
 
     #[test]
     fn test_fee_estimation() {
@@ -354,6 +1001,8 @@ mod tests {
             supported_operations: vec![OperationType::Transfer],
             is_online: false,
             avg_confirmation_time: 5,
+            stake: 0,
+            latency_ms: None,
         });
 
         // Still no available relayers
@@ -368,10 +1017,164 @@ mod tests {
             supported_operations: vec![OperationType::Transfer],
             is_online: true,
             avg_confirmation_time: 5,
+            stake: 0,
+            latency_ms: None,
         });
 
         // Now we can select
         let relayer = client.select_relayer(&OperationType::Transfer).unwrap();
         assert_eq!(relayer.id, "online");
     }
+
+    #[test]
+    fn test_fee_checks() {
+        let mint = "So11111111111111111111111111111111111111112";
+        let token = OperationType::UnshieldToken { mint: mint.into() };
+        let mut client = RelayerClient::new();
+        client.add_relayer(RelayerInfo {
+            id: "relayer".to_string(),
+            endpoint: "https://relayer.example.com".to_string(),
+            fee_bps: 30,
+            min_amount: 0,
+            supported_operations: vec![OperationType::Transfer, token.clone()],
+            is_online: true,
+            avg_confirmation_time: 5,
+            stake: 0,
+            latency_ms: None,
+        });
+
+        // Transfers are charged on their private amount
+        let mut transfer = RelayRequest {
+            operation: OperationType::Transfer,
+            nullifier: [1; 32],
+            output: RelayOutput::Commitment([2; 32]),
+            amount: 0,
+            proof: vec![],
+            merkle_root: [3; 32],
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee: 3_000_000,
+            max_fee: 3_000_000,
+            quote_hash: None,
+            work_nonce: None,
+        };
+        assert!(matches!(client.check_request(&transfer), Err(RelayerError::InvalidRequest(_))));
+        transfer.amount = 1_000_000_000;
+        assert_eq!(client.estimate_request_fee(&transfer).unwrap(), (3_000_000, 5_000));
+        assert_eq!(client.check_request(&transfer).unwrap().id, "relayer");
+        transfer.amount = 2_000_000_000;
+        assert!(matches!(
+            client.check_request(&transfer),
+            Err(RelayerError::FeeExceedsMax { fee: 6_000_000, max_fee: 3_000_000 })
+        ));
+        // Never sent to the relayer
+        let json = serde_json::to_string(&transfer).unwrap();
+        assert!(!json.contains("2000000000"));
+        let received: RelayRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.amount, 0);
+
+        // Token unshields are charged on the output amount, in token units
+        let mut unshield = RelayRequest {
+            operation: token.clone(),
+            output: RelayOutput::Unshield { recipient: "r".into(), amount: 2_000_000 },
+            fee: 6_000,
+            max_fee: 6_000,
+            ..transfer
+        };
+        assert_eq!(client.estimate_request_fee(&unshield).unwrap(), (6_000, 10_000));
+        assert_eq!(client.estimate_fee(&token, 1_000).unwrap(), (3, 10_000));
+        assert!(client.check_request(&unshield).is_ok());
+        unshield.max_fee = 5_999;
+        assert!(matches!(
+            client.check_request(&unshield),
+            Err(RelayerError::FeeExceedsMax { fee: 6_000, max_fee: 5_999 })
+        ));
+        let other = OperationType::UnshieldToken { mint: "other".into() };
+        assert!(matches!(
+            client.estimate_fee(&other, 1_000),
+            Err(RelayerError::NoRelayersAvailable)
+        ));
+    }
+
+    #[test]
+    fn test_record_probe() {
+        let mut client = RelayerClient::new();
+        for (id, fee_bps) in [("slow", 30), ("fast", 30), ("cheap", 10)] {
+            client.add_relayer(RelayerInfo {
+                id: id.to_string(),
+                endpoint: format!("https://{}.example.com", id),
+                fee_bps,
+                min_amount: 0,
+                supported_operations: vec![OperationType::Transfer],
+                is_online: false,
+                avg_confirmation_time: 5,
+                stake: 0,
+                latency_ms: None,
+            });
+        }
+        client.record_probe("slow", Some(Duration::from_millis(400)));
+        client.record_probe("fast", Some(Duration::from_millis(100)));
+        client.record_probe("fast", Some(Duration::from_millis(300)));
+        client.record_probe("unknown", Some(Duration::from_millis(1)));
+
+        // Fee first, then latency; the cheap relayer was never reached
+        let eligible: Vec<_> = client
+            .eligible_relayers(&OperationType::Transfer)
+            .into_iter()
+            .map(|r| (r.id, r.latency_ms))
+            .collect();
+        assert_eq!(eligible, [("fast".into(), Some(150)), ("slow".into(), Some(400))]);
+
+        client.record_probe("fast", None);
+        let relayer = client.select_relayer(&OperationType::Transfer).unwrap();
+        assert_eq!(relayer.id, "slow");
+    }
+
+    #[test]
+    fn test_precheck_proof() {
+        use ark_ff::UniformRand;
+        use rand::rngs::OsRng;
+
+        use crate::crypto::merkle::PoseidonMerkleTree;
+        use crate::crypto::nullifier::Note;
+        use crate::proof::TransferProofSystem;
+
+        let system = TransferProofSystem::setup().unwrap();
+        let mut note = Note::new_random(1000, Fr::from(0u64), Fr::rand(&mut OsRng));
+        let mut tree = PoseidonMerkleTree::new();
+        let index = tree.insert(note.commitment()).unwrap();
+        note.set_leaf_index(index);
+        let path = tree.generate_proof(index).unwrap();
+        let spend = system
+            .prove_spend(&note, &path, tree.root(), Fr::rand(&mut OsRng), 10)
+            .unwrap();
+
+        let client = RelayerClient::new().with_verifier(Arc::new(system.verifier().clone()));
+        let mut request = RelayRequest {
+            operation: OperationType::Transfer,
+            nullifier: spend.nullifier_bytes(),
+            output: RelayOutput::Commitment(spend.new_commitment_bytes()),
+            amount: 1000,
+            proof: spend.proof.as_bytes().to_vec(),
+            merkle_root: field_to_bytes_be(&tree.root()),
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee: 10,
+            max_fee: 10,
+            quote_hash: None,
+            work_nonce: None,
+        };
+        assert!(client.precheck_proof(&request).is_ok());
+
+        // A fee other than the one proven
+        request.fee = 20;
+        assert!(matches!(client.precheck_proof(&request), Err(RelayerError::InvalidProof)));
+        // Unchecked without a verifier
+        assert!(RelayerClient::new().precheck_proof(&request).is_ok());
+
+        // An asset ID that is not a canonical field element
+        request.fee = 10;
+        request.asset_id = [0xff; 32];
+        assert!(matches!(client.precheck_proof(&request), Err(RelayerError::InvalidProof)));
+    }
 }
@@ -0,0 +1,84 @@
+//! Proof-of-work admission for relay requests
+//!
+//! A relayer pays rent for each nullifier account its transactions open and
+//! burns a proof verification on every request, so invalid requests cost it
+//! far more than they cost their sender. A relayer can ask for work in its
+//! quotes: `FeeQuote::work_bits` leading zero bits of
+//!
+//! ```text
+//! blake3_derive_key("veil relayer work v1", quote hash || nullifier || nonce)
+//! ```
+//!
+//! for a `nonce` the request carries. Work is bound to the quote and the
+//! nullifier, so each request needs its own, and the relayer checks it
+//! before verifying the proof.
+
+/// Domain of work hashes
+const WORK_CONTEXT: &str = "veil relayer work v1";
+
+/// Most leading zero bits a client does (about 16 million hashes)
+pub const MAX_WORK_BITS: u8 = 24;
+
+/// Work hash of `nonce` for a request spending `nullifier` under the quote
+/// `quote_hash`
+pub fn work_hash(quote_hash: &[u8; 32], nullifier: &[u8; 32], nonce: u64) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(WORK_CONTEXT);
+    hasher
+        .update(quote_hash)
+        .update(nullifier)
+        .update(&nonce.to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// Leading zero bits of `hash`
+pub fn leading_zero_bits(hash: &[u8; 32]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Whether `nonce` is `bits` of work for the request
+pub fn verify_work(quote_hash: &[u8; 32], nullifier: &[u8; 32], nonce: u64, bits: u8) -> bool {
+    leading_zero_bits(&work_hash(quote_hash, nullifier, nonce)) >= u32::from(bits)
+}
+
+/// First nonce that is `bits` of work for the request
+///
+/// Takes about `2^bits` hashes; the caller bounds `bits`.
+pub fn solve_work(quote_hash: &[u8; 32], nullifier: &[u8; 32], bits: u8) -> u64 {
+    (0..=u64::MAX)
+        .find(|nonce| verify_work(quote_hash, nullifier, *nonce, bits))
+        .expect("a nonce below 2^64")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff; 32]), 0);
+        let mut hash = [0u8; 32];
+        assert_eq!(leading_zero_bits(&hash), 256);
+        hash[1] = 0x10;
+        assert_eq!(leading_zero_bits(&hash), 11);
+    }
+
+    #[test]
+    fn test_solve_and_verify() {
+        let (quote, nullifier) = ([1; 32], [2; 32]);
+        assert!(verify_work(&quote, &nullifier, 0, 0));
+
+        let nonce = solve_work(&quote, &nullifier, 12);
+        assert!(verify_work(&quote, &nullifier, nonce, 12));
+        assert!((0..nonce).all(|n| !verify_work(&quote, &nullifier, n, 12)));
+        // Bound to the quote and the nullifier
+        assert_ne!(work_hash(&quote, &nullifier, nonce), work_hash(&[3; 32], &nullifier, nonce));
+        assert_ne!(work_hash(&quote, &nullifier, nonce), work_hash(&quote, &[3; 32], nonce));
+    }
+}
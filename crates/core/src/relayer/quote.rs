@@ -0,0 +1,235 @@
+//! Signed fee quotes
+//!
+//! A client asks a relayer for a quote on an operation and amount; the
+//! relayer answers with the exact fee it will accept, valid until
+//! `expires_at` and signed with its key. The client proves that fee, embeds
+//! `FeeQuote::hash` in the `RelayRequest`, and the relayer honours the quote
+//! until it expires, even if its fee schedule changes meanwhile.
+//!
+//! The signature makes the quote a receipt: a relayer that refuses a request
+//! within its own quote can be shown to have broken it.
+//!
+//! A quote may also ask for proof of work, see `pow`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{pow, OperationType, RelayOutput, RelayRequest};
+
+/// Domain of quote hashes
+const QUOTE_CONTEXT: &str = "veil relayer fee quote v1";
+
+/// Request for a relayer's fee on an operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRequest {
+    pub operation: OperationType,
+    /// Amount the operation moves (lamports)
+    pub amount: u64,
+}
+
+/// A relayer's fee for an operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeQuote {
+    pub operation: OperationType,
+    /// Amount quoted for (lamports)
+    pub amount: u64,
+    /// Fee in basis points
+    pub fee_bps: u16,
+    /// Fee the request's `fee` must prove (lamports)
+    pub fee: u64,
+    /// Estimated network fee the relayer pays (lamports)
+    pub network_fee: u64,
+    /// Unix time (seconds) from which the relayer no longer honours the quote
+    pub expires_at: u64,
+    /// The relayer's signing key (base58)
+    pub relayer: String,
+    /// Leading zero bits of work a request under the quote must carry
+    #[serde(default)]
+    pub work_bits: u8,
+}
+
+impl FeeQuote {
+    /// Hash a request embeds to claim the quote, and the relayer signs
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_derive_key(QUOTE_CONTEXT);
+        match &self.operation {
+            OperationType::Transfer => hasher.update(&[0]),
+            OperationType::UnshieldSol => hasher.update(&[1]),
+            OperationType::UnshieldToken { mint } => hasher
+                .update(&[2])
+                .update(&(mint.len() as u32).to_le_bytes())
+                .update(mint.as_bytes()),
+        };
+        hasher
+            .update(&self.amount.to_le_bytes())
+            .update(&self.fee_bps.to_le_bytes())
+            .update(&self.fee.to_le_bytes())
+            .update(&self.network_fee.to_le_bytes())
+            .update(&self.expires_at.to_le_bytes())
+            .update(self.relayer.as_bytes())
+            .update(&[self.work_bits]);
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Whether the quote has expired at `now` (unix seconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Why the quote does not apply to `request` at `now`, if it does not
+    ///
+    /// Checks the operation, the amount of unshields (a transfer's is
+    /// private) and expiry. The fee is left to each side: the relayer
+    /// requires at least the quoted fee, the client at most.
+    pub fn mismatch(&self, request: &RelayRequest, now: u64) -> Option<&'static str> {
+        if self.operation != request.operation {
+            return Some("quote is for another operation");
+        }
+        if let RelayOutput::Unshield { amount, .. } = &request.output {
+            if *amount != self.amount {
+                return Some("quote is for another amount");
+            }
+        }
+        if self.is_expired(now) {
+            return Some("quote expired");
+        }
+        None
+    }
+
+    /// Whether `request` carries the work the quote asks for
+    pub fn has_work(&self, request: &RelayRequest) -> bool {
+        if self.work_bits == 0 {
+            return true;
+        }
+        request.work_nonce.is_some_and(|nonce| {
+            pow::verify_work(&self.hash(), &request.nullifier, nonce, self.work_bits)
+        })
+    }
+}
+
+/// A `FeeQuote` with the relayer's signature of its hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedQuote {
+    #[serde(flatten)]
+    pub quote: FeeQuote,
+    /// Ed25519 signature of `quote.hash()` by `quote.relayer` (base58)
+    pub signature: String,
+}
+
+#[cfg(any(feature = "rpc", feature = "http"))]
+impl SignedQuote {
+    /// Sign `quote`, whose `relayer` must be `keypair`'s public key
+    pub fn sign(quote: FeeQuote, keypair: &ed25519_dalek::Keypair) -> Self {
+        use ed25519_dalek::Signer;
+
+        let signature = keypair.sign(&quote.hash());
+        Self {
+            signature: bs58::encode(signature.to_bytes()).into_string(),
+            quote,
+        }
+    }
+
+    /// Whether the signature is `quote.relayer`'s over the quote
+    pub fn verify(&self) -> bool {
+        let decode = |s: &str| bs58::decode(s).into_vec().ok();
+        let (Some(key), Some(signature)) = (decode(&self.quote.relayer), decode(&self.signature))
+        else {
+            return false;
+        };
+        let (Ok(key), Ok(signature)) = (
+            ed25519_dalek::PublicKey::from_bytes(&key),
+            ed25519_dalek::Signature::from_bytes(&signature),
+        ) else {
+            return false;
+        };
+        key.verify_strict(&self.quote.hash(), &signature).is_ok()
+    }
+}
+
+/// Current unix time in seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> FeeQuote {
+        FeeQuote {
+            operation: OperationType::UnshieldSol,
+            amount: 1_000_000_000,
+            fee_bps: 30,
+            fee: 3_000_000,
+            network_fee: 5_000,
+            expires_at: 1_000,
+            relayer: "relayer".into(),
+            work_bits: 0,
+        }
+    }
+
+    #[test]
+    fn test_quote_hash_and_mismatch() {
+        let quote = quote();
+        let other = FeeQuote { fee: 3_000_001, ..quote.clone() };
+        assert_ne!(quote.hash(), other.hash());
+        let token = OperationType::UnshieldToken { mint: "mint".into() };
+        assert_ne!(quote.hash(), FeeQuote { operation: token, ..quote.clone() }.hash());
+
+        let mut request = RelayRequest {
+            operation: OperationType::UnshieldSol,
+            nullifier: [1; 32],
+            output: RelayOutput::Unshield { recipient: "r".into(), amount: 1_000_000_000 },
+            amount: 0,
+            proof: vec![],
+            merkle_root: [0; 32],
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee: 3_000_000,
+            max_fee: 3_000_000,
+            quote_hash: Some(quote.hash()),
+            work_nonce: None,
+        };
+        assert_eq!(quote.mismatch(&request, 999), None);
+        assert_eq!(quote.mismatch(&request, 1_000), Some("quote expired"));
+        request.output = RelayOutput::Unshield { recipient: "r".into(), amount: 1 };
+        assert_eq!(quote.mismatch(&request, 0), Some("quote is for another amount"));
+        request.operation = OperationType::Transfer;
+        assert_eq!(quote.mismatch(&request, 0), Some("quote is for another operation"));
+
+        // Work, when asked for, must be for this quote
+        assert!(quote.has_work(&request));
+        let demanding = FeeQuote { work_bits: 8, ..quote.clone() };
+        assert!(!demanding.has_work(&request));
+        let nonce = pow::solve_work(&demanding.hash(), &request.nullifier, 8);
+        request.work_nonce = Some(nonce);
+        assert!(demanding.has_work(&request));
+        assert!(!FeeQuote { work_bits: 8, fee: 1, ..quote }.has_work(&request));
+    }
+
+    #[cfg(any(feature = "rpc", feature = "http"))]
+    #[test]
+    fn test_sign_and_verify() {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let keypair = ed25519_dalek::Keypair { secret, public };
+        let relayer = bs58::encode(public.as_bytes()).into_string();
+
+        let signed = SignedQuote::sign(FeeQuote { relayer, ..quote() }, &keypair);
+        assert!(signed.verify());
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(serde_json::from_str::<SignedQuote>(&json).unwrap(), signed);
+
+        // Any change to the quote breaks the signature
+        let mut raised = signed.clone();
+        raised.quote.fee += 1;
+        assert!(!raised.verify());
+        let mut forged = signed;
+        forged.quote.relayer = bs58::encode([8; 32]).into_string();
+        assert!(!forged.verify());
+    }
+}
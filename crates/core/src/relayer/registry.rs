@@ -0,0 +1,280 @@
+//! On-chain relayer registry
+//!
+//! Relayers register `RelayerEntry` accounts with the program, staking at
+//! least `MIN_RELAYER_STAKE` lamports and advertising a fee and endpoint.
+//! This module decodes those accounts into `RelayerInfo`s, and keeps a
+//! client's relayer list in step with them (`sync_relayers`).
+//!
+//! Entries do not say which operations a relayer supports; each is assumed
+//! to support transfers and SOL unshields until its `/health` probe answers.
+//!
+//! Account layout (Anchor/borsh):
+//! `discriminator (8) | relayer (32) | stake (u64) | fee_bps (u16)
+//! | endpoint (u32 length + UTF-8) | registered_at (u64) | bump (u8)`
+
+use sha2::{Digest, Sha256};
+
+use super::{OperationType, RelayerError, RelayerInfo};
+use crate::transaction::Pubkey;
+
+/// Seeds prefix for relayer entry PDAs, followed by the relayer's key
+pub const RELAYER_SEED: &[u8] = b"relayer";
+
+/// Minimum stake the program requires of a registered relayer (1 SOL)
+pub const MIN_RELAYER_STAKE: u64 = 1_000_000_000;
+
+/// Minimum transaction amount assumed for registered relayers
+const DEFAULT_MIN_AMOUNT: u64 = 10_000;
+
+/// Confirmation time assumed until a relayer has been measured (seconds)
+const DEFAULT_CONFIRMATION_TIME: u32 = 5;
+
+/// Anchor account discriminator of `RelayerEntry`: `sha256("account:RelayerEntry")[..8]`
+pub fn relayer_entry_discriminator() -> [u8; 8] {
+    let hash = Sha256::digest(b"account:RelayerEntry");
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
+
+/// A decoded `RelayerEntry` account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayerEntry {
+    /// The relayer's signing key
+    pub relayer: Pubkey,
+    /// Lamports staked
+    pub stake: u64,
+    /// Advertised fee, in basis points
+    pub fee_bps: u16,
+    /// API endpoint URL
+    pub endpoint: String,
+    /// Slot the relayer registered in
+    pub registered_at: u64,
+}
+
+impl RelayerEntry {
+    /// Decode an entry from its account data
+    pub fn decode(data: &[u8]) -> Result<Self, RelayerError> {
+        let invalid =
+            |what: &str| RelayerError::InvalidResponse(format!("relayer entry: {}", what));
+
+        let mut reader = Reader(data);
+        if reader.take(8)? != relayer_entry_discriminator() {
+            return Err(invalid("wrong discriminator"));
+        }
+        let relayer: Pubkey = reader.array()?;
+        let stake = u64::from_le_bytes(reader.array()?);
+        let fee_bps = u16::from_le_bytes(reader.array()?);
+        let len = u32::from_le_bytes(reader.array()?) as usize;
+        let endpoint = String::from_utf8(reader.take(len)?.to_vec())
+            .map_err(|_| invalid("endpoint is not UTF-8"))?;
+        let registered_at = u64::from_le_bytes(reader.array()?);
+
+        Ok(Self {
+            relayer,
+            stake,
+            fee_bps,
+            endpoint,
+            registered_at,
+        })
+    }
+
+    /// Client view of the entry; offline until health-checked
+    pub fn into_info(self) -> RelayerInfo {
+        RelayerInfo {
+            id: bs58::encode(self.relayer).into_string(),
+            endpoint: self.endpoint,
+            fee_bps: self.fee_bps,
+            min_amount: DEFAULT_MIN_AMOUNT,
+            supported_operations: vec![OperationType::Transfer, OperationType::UnshieldSol],
+            is_online: false,
+            avg_confirmation_time: DEFAULT_CONFIRMATION_TIME,
+            stake: self.stake,
+            latency_ms: None,
+        }
+    }
+}
+
+/// Which registered relayers a client uses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryFilter {
+    /// Least stake (lamports)
+    pub min_stake: u64,
+    /// Operations a relayer must support one of; any relayer if empty
+    pub operations: Vec<OperationType>,
+}
+
+impl Default for RegistryFilter {
+    fn default() -> Self {
+        Self {
+            min_stake: MIN_RELAYER_STAKE,
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl RegistryFilter {
+    /// Whether the client uses `relayer`
+    pub fn admits(&self, relayer: &RelayerInfo) -> bool {
+        relayer.stake >= self.min_stake.max(MIN_RELAYER_STAKE)
+            && (self.operations.is_empty()
+                || self.operations.iter().any(|op| relayer.supported_operations.contains(op)))
+    }
+}
+
+/// Replace the registered relayers in `relayers` with those of `accounts`
+/// (raw `RelayerEntry` data) that `filter` admits
+///
+/// A relayer still registered keeps its health, latency and probed
+/// operations, and takes the entry's fee, endpoint and stake. Relayers
+/// added by hand (no stake) stay unless registered meanwhile. Accounts that
+/// do not decode are skipped. Returns the number of registered relayers.
+pub fn sync_relayers<'a>(
+    relayers: &mut Vec<RelayerInfo>,
+    accounts: impl IntoIterator<Item = &'a [u8]>,
+    filter: &RegistryFilter,
+) -> usize {
+    let mut registered: Vec<RelayerInfo> = accounts
+        .into_iter()
+        .filter_map(|data| RelayerEntry::decode(data).ok())
+        .map(|entry| {
+            let mut info = entry.into_info();
+            if let Some(known) = relayers.iter().find(|r| r.id == info.id) {
+                info.is_online = known.is_online;
+                info.latency_ms = known.latency_ms;
+                info.avg_confirmation_time = known.avg_confirmation_time;
+                info.supported_operations = known.supported_operations.clone();
+            }
+            info
+        })
+        .filter(|info| filter.admits(info))
+        .collect();
+    relayers.retain(|r| r.stake == 0 && !registered.iter().any(|e| e.id == r.id));
+    let count = registered.len();
+    relayers.append(&mut registered);
+    count
+}
+
+/// Data of every `RelayerEntry` account of the program at `program_id`
+#[cfg(feature = "rpc")]
+pub fn fetch_entries(
+    rpc: &crate::rpc::RpcClient,
+    program_id: &Pubkey,
+) -> crate::error::VeilResult<Vec<Vec<u8>>> {
+    let accounts = rpc.get_program_accounts(program_id, &relayer_entry_discriminator())?;
+    Ok(accounts.into_iter().map(|(_, data)| data).collect())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], RelayerError> {
+        if self.0.len() < n {
+            return Err(RelayerError::InvalidResponse("relayer entry: truncated".into()));
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RelayerError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u8, stake: u64, fee_bps: u16) -> RelayerEntry {
+        RelayerEntry {
+            relayer: [key; 32],
+            stake,
+            fee_bps,
+            endpoint: format!("https://relayer{}.example.com", key),
+            registered_at: 42,
+        }
+    }
+
+    fn encode(entry: &RelayerEntry) -> Vec<u8> {
+        let mut data = relayer_entry_discriminator().to_vec();
+        data.extend_from_slice(&entry.relayer);
+        data.extend_from_slice(&entry.stake.to_le_bytes());
+        data.extend_from_slice(&entry.fee_bps.to_le_bytes());
+        data.extend_from_slice(&(entry.endpoint.len() as u32).to_le_bytes());
+        data.extend_from_slice(entry.endpoint.as_bytes());
+        data.extend_from_slice(&entry.registered_at.to_le_bytes());
+        data.push(254);
+        // Entries are allocated for the longest endpoint
+        data.resize(data.len() + 40, 0);
+        data
+    }
+
+    #[test]
+    fn test_decode_relayer_entry() {
+        let entry = RelayerEntry {
+            relayer: [7u8; 32],
+            stake: MIN_RELAYER_STAKE,
+            fee_bps: 25,
+            endpoint: "https://relayer.example.com".into(),
+            registered_at: 42,
+        };
+        let data = encode(&entry);
+        assert_eq!(RelayerEntry::decode(&data).unwrap(), entry);
+
+        let info = entry.into_info();
+        assert_eq!(info.id, bs58::encode([7u8; 32]).into_string());
+        assert_eq!(info.fee_bps, 25);
+        assert_eq!(info.stake, MIN_RELAYER_STAKE);
+        assert!(!info.is_online);
+
+        // Other account types and truncated data are rejected
+        let mut other = data.clone();
+        other[0] ^= 1;
+        assert!(RelayerEntry::decode(&other).is_err());
+        assert!(RelayerEntry::decode(&data[..50]).is_err());
+    }
+
+    #[test]
+    fn test_sync_relayers() {
+        let manual = RelayerInfo { stake: 0, ..entry(9, 0, 10).into_info() };
+        let mut relayers = vec![manual.clone()];
+        let accounts = [
+            encode(&entry(1, MIN_RELAYER_STAKE, 20)),
+            encode(&entry(2, 5 * MIN_RELAYER_STAKE, 30)),
+            encode(&entry(3, MIN_RELAYER_STAKE - 1, 10)),
+            vec![0; 10],
+        ];
+        let filter = RegistryFilter::default();
+        assert_eq!(sync_relayers(&mut relayers, accounts.iter().map(Vec::as_slice), &filter), 2);
+        let stakes: Vec<_> = relayers.iter().map(|r| r.stake).collect();
+        assert_eq!(stakes, [0, MIN_RELAYER_STAKE, 5 * MIN_RELAYER_STAKE]);
+
+        // Health survives a refresh that raises the fee; deregistered
+        // relayers go
+        relayers[1].record_probe(Some(std::time::Duration::from_millis(80)));
+        relayers[1].supported_operations = vec![OperationType::Transfer];
+        let accounts = [encode(&entry(1, MIN_RELAYER_STAKE, 40))];
+        sync_relayers(&mut relayers, accounts.iter().map(Vec::as_slice), &filter);
+        assert_eq!(relayers.len(), 2);
+        assert_eq!(relayers[0], manual);
+        assert!(relayers[1].is_online);
+        assert_eq!((relayers[1].fee_bps, relayers[1].latency_ms), (40, Some(80)));
+
+        // By stake and probed operations
+        let unshields = RegistryFilter {
+            min_stake: MIN_RELAYER_STAKE,
+            operations: vec![OperationType::UnshieldSol],
+        };
+        assert_eq!(sync_relayers(&mut relayers, accounts.iter().map(Vec::as_slice), &unshields), 0);
+        assert_eq!(relayers, [manual]);
+        let staked = RegistryFilter { min_stake: 2 * MIN_RELAYER_STAKE, ..filter };
+        let accounts = [
+            encode(&entry(1, MIN_RELAYER_STAKE, 20)),
+            encode(&entry(2, 5 * MIN_RELAYER_STAKE, 30)),
+        ];
+        assert_eq!(sync_relayers(&mut relayers, accounts.iter().map(Vec::as_slice), &staked), 1);
+    }
+}
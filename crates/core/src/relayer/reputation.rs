@@ -0,0 +1,241 @@
+//! Relayer reputation and selection scoring
+//!
+//! `RelayerClient` keeps a `Reputation` per relayer from its own
+//! submissions: how many succeeded, how long confirmed ones took, and why
+//! the others failed. `Scoring` blends it with each relayer's advertised fee
+//! and probed latency into one score, best first.
+//!
+//! Always picking the top score would send every user to one relayer, which
+//! then sees most of the pool's traffic. With `Scoring::randomize` the first
+//! relayer tried is drawn at random, weighted by score.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::{RelayerError, RelayerInfo};
+use crate::telemetry::RelayRejection;
+
+/// Confirmation time at which the confirmation term halves (seconds)
+const CONFIRMATION_SCALE_SECS: f64 = 10.0;
+
+/// Probe latency at which the latency term halves (milliseconds)
+const LATENCY_SCALE_MS: f64 = 200.0;
+
+/// What the client has seen of a relayer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reputation {
+    /// Requests sent to the relayer
+    pub submissions: u64,
+    /// Requests it confirmed
+    pub successes: u64,
+    /// Smoothed time from sending to confirmation (milliseconds), `None`
+    /// until one confirms
+    pub confirmation_ms: Option<u32>,
+    /// Why the other requests failed
+    pub rejections: BTreeMap<RelayRejection, u64>,
+}
+
+impl Reputation {
+    /// Record a request confirmed `elapsed` after it was sent
+    ///
+    /// Each confirmation moves `confirmation_ms` a quarter of the way
+    /// towards it.
+    pub fn record_success(&mut self, elapsed: Duration) {
+        self.submissions += 1;
+        self.successes += 1;
+        let sample = elapsed.as_millis().min(u32::MAX as u128) as u64;
+        let average = match self.confirmation_ms {
+            Some(average) => (3 * average as u64 + sample) / 4,
+            None => sample,
+        };
+        self.confirmation_ms = Some(average as u32);
+    }
+
+    /// Record a request that failed with `error`
+    pub fn record_failure(&mut self, error: &RelayerError) {
+        self.submissions += 1;
+        *self.rejections.entry(error.into()).or_default() += 1;
+    }
+
+    /// Share of requests confirmed, counting one success and one failure
+    /// in advance so a new relayer starts at one half
+    pub fn success_rate(&self) -> f64 {
+        (self.successes + 1) as f64 / (self.submissions + 2) as f64
+    }
+}
+
+/// How `RelayerClient` ranks eligible relayers
+///
+/// Each term is in [0, 1], higher is better, and weighted:
+/// - fee: one less the relayer's fee as a share of the client's maximum
+/// - success: `Reputation::success_rate`
+/// - confirmation: the observed confirmation time, or the advertised one
+///   until a request confirms, halving the term every
+///   `CONFIRMATION_SCALE_SECS`
+/// - latency: the probed latency, halving the term every
+///   `LATENCY_SCALE_MS`; 0 while unprobed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scoring {
+    pub fee_weight: f64,
+    pub success_weight: f64,
+    pub confirmation_weight: f64,
+    pub latency_weight: f64,
+    /// Draw the first relayer at random, weighted by score, instead of
+    /// always the best one
+    pub randomize: bool,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            fee_weight: 0.4,
+            success_weight: 0.3,
+            confirmation_weight: 0.2,
+            latency_weight: 0.1,
+            randomize: false,
+        }
+    }
+}
+
+impl Scoring {
+    /// Score of `relayer` with `reputation`, for a client accepting fees up
+    /// to `max_fee_bps`
+    pub fn score(&self, relayer: &RelayerInfo, reputation: &Reputation, max_fee_bps: u16) -> f64 {
+        let fee = 1.0 - (f64::from(relayer.fee_bps) / f64::from(max_fee_bps.max(1))).min(1.0);
+        let confirmation_secs = match reputation.confirmation_ms {
+            Some(ms) => f64::from(ms) / 1000.0,
+            None => f64::from(relayer.avg_confirmation_time),
+        };
+        let confirmation = 1.0 / (1.0 + confirmation_secs / CONFIRMATION_SCALE_SECS);
+        let latency = relayer
+            .latency_ms
+            .map_or(0.0, |ms| 1.0 / (1.0 + f64::from(ms) / LATENCY_SCALE_MS));
+        self.fee_weight * fee
+            + self.success_weight * reputation.success_rate()
+            + self.confirmation_weight * confirmation
+            + self.latency_weight * latency
+    }
+
+    /// Order `scored` relayers best first, drawing the first at random
+    /// when randomizing
+    pub fn rank(&self, mut scored: Vec<(f64, RelayerInfo)>) -> Vec<RelayerInfo> {
+        // Stable, so ties keep the order given
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        if self.randomize && scored.len() > 1 {
+            let first = draw(&scored, rand::thread_rng().gen());
+            let chosen = scored.remove(first);
+            scored.insert(0, chosen);
+        }
+        scored.into_iter().map(|(_, relayer)| relayer).collect()
+    }
+}
+
+/// Index of the relayer `point` in [0, 1) lands on, each taking a share of
+/// the interval proportional to its score
+fn draw(scored: &[(f64, RelayerInfo)], point: f64) -> usize {
+    let total: f64 = scored.iter().map(|(score, _)| score.max(0.0)).sum();
+    if total <= 0.0 {
+        return ((point * scored.len() as f64) as usize).min(scored.len() - 1);
+    }
+    let mut remaining = point * total;
+    for (i, (score, _)) in scored.iter().enumerate() {
+        remaining -= score.max(0.0);
+        if remaining < 0.0 {
+            return i;
+        }
+    }
+    scored.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::OperationType;
+    use super::*;
+
+    fn relayer(id: &str, fee_bps: u16, latency_ms: Option<u32>) -> RelayerInfo {
+        RelayerInfo {
+            id: id.into(),
+            endpoint: format!("https://{}.example.com", id),
+            fee_bps,
+            min_amount: 0,
+            supported_operations: vec![OperationType::Transfer],
+            is_online: true,
+            avg_confirmation_time: 5,
+            stake: 0,
+            latency_ms,
+        }
+    }
+
+    #[test]
+    fn test_reputation() {
+        let mut reputation = Reputation::default();
+        assert_eq!(reputation.success_rate(), 0.5);
+        reputation.record_success(Duration::from_millis(4_000));
+        reputation.record_success(Duration::from_millis(8_000));
+        reputation.record_failure(&RelayerError::Timeout);
+        reputation.record_failure(&RelayerError::Timeout);
+        reputation.record_failure(&RelayerError::NetworkError("refused".into()));
+
+        assert_eq!((reputation.submissions, reputation.successes), (5, 2));
+        assert_eq!(reputation.confirmation_ms, Some(5_000));
+        assert_eq!(reputation.success_rate(), 3.0 / 7.0);
+        let rejections: Vec<_> = reputation.rejections.into_iter().collect();
+        assert_eq!(rejections, [(RelayRejection::Network, 1), (RelayRejection::Timeout, 2)]);
+    }
+
+    #[test]
+    fn test_scoring() {
+        let scoring = Scoring::default();
+        let fresh = Reputation::default();
+        let cheap = relayer("cheap", 10, Some(100));
+        let dear = relayer("dear", 50, Some(100));
+        assert!(scoring.score(&cheap, &fresh, 100) > scoring.score(&dear, &fresh, 100));
+
+        // Failures outweigh a lower fee
+        let mut unreliable = Reputation::default();
+        for _ in 0..20 {
+            unreliable.record_failure(&RelayerError::Timeout);
+        }
+        let mut reliable = Reputation::default();
+        for _ in 0..20 {
+            reliable.record_success(Duration::from_secs(2));
+        }
+        let ranked = scoring.rank(vec![
+            (scoring.score(&cheap, &unreliable, 100), cheap.clone()),
+            (scoring.score(&dear, &reliable, 100), dear.clone()),
+        ]);
+        assert_eq!(ranked[0].id, "dear");
+
+        // Fee only
+        let fees = Scoring {
+            fee_weight: 1.0,
+            success_weight: 0.0,
+            confirmation_weight: 0.0,
+            latency_weight: 0.0,
+            randomize: false,
+        };
+        assert_eq!(fees.score(&cheap, &unreliable, 100), 0.9);
+        assert_eq!(fees.score(&dear, &reliable, 50), 0.0);
+    }
+
+    #[test]
+    fn test_randomized_selection() {
+        let scored: Vec<_> =
+            [(3.0, "a"), (1.0, "b"), (0.0, "c")].map(|(s, id)| (s, relayer(id, 30, None))).into();
+        assert_eq!(draw(&scored, 0.0), 0);
+        assert_eq!(draw(&scored, 0.74), 0);
+        assert_eq!(draw(&scored, 0.75), 1);
+        assert_eq!(draw(&scored, 0.999), 1);
+        let unscored: Vec<_> = scored.iter().map(|(_, r)| (0.0, r.clone())).collect();
+        assert_eq!(draw(&unscored, 0.5), 1);
+
+        // Both scored relayers come first some of the time
+        let scoring = Scoring { randomize: true, ..Scoring::default() };
+        let firsts: std::collections::HashSet<_> =
+            (0..200).map(|_| scoring.rank(scored.clone())[0].id.clone()).collect();
+        assert_eq!(firsts.len(), 2);
+        assert!(!firsts.contains("c"));
+    }
+}
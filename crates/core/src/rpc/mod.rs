@@ -0,0 +1,444 @@
+//! Solana RPC Access
+//!
+//! A small blocking JSON-RPC client plus the two pieces of Solana plumbing
+//! needed to land transactions built by `transaction`: program address
+//! derivation and message signing. Enabled by the `rpc` feature.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{PublicKey, Signer};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::{VeilError, VeilResult};
+use crate::transaction::lookup_table::ADDRESS_LOOKUP_TABLE_PROGRAM_ID;
+use crate::transaction::{
+    AddressLookupTable, AssembledTransaction, Pubkey, MESSAGE_VERSION_PREFIX, PUBKEY_SIZE,
+};
+
+pub use ed25519_dalek::Keypair;
+
+/// Public devnet endpoint
+pub const DEVNET_URL: &str = "https://api.devnet.solana.com";
+
+/// Request timeout for RPC calls
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum page size of `getSignaturesForAddress`
+pub const MAX_SIGNATURES_PER_PAGE: usize = 1000;
+
+/// A transaction signature returned by `get_signatures_for_address`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureInfo {
+    /// Transaction signature (base58)
+    pub signature: String,
+    /// Slot the transaction landed in
+    pub slot: u64,
+    /// Whether the transaction failed (its logs carry no state changes)
+    pub failed: bool,
+}
+
+/// Status of a sent transaction, returned by `get_signature_status`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionStatus {
+    /// Slot the transaction landed in
+    pub slot: u64,
+    /// Whether the cluster has confirmed (or finalized) it
+    pub confirmed: bool,
+    /// The transaction's error, if it failed
+    pub error: Option<String>,
+}
+
+/// Blocking JSON-RPC client
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcClient {
+    /// Client for the endpoint at `url`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            agent: ureq::AgentBuilder::new().timeout(DEFAULT_TIMEOUT).build(),
+        }
+    }
+
+    /// Endpoint URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Latest blockhash at `confirmed` commitment
+    pub fn get_latest_blockhash(&self) -> VeilResult<[u8; 32]> {
+        let result = self.call("getLatestBlockhash", json!([{ "commitment": "confirmed" }]))?;
+        let blockhash = result["value"]["blockhash"]
+            .as_str()
+            .ok_or_else(|| VeilError::Rpc("getLatestBlockhash: missing blockhash".into()))?;
+        decode_pubkey(blockhash)
+    }
+
+    /// Current slot at `confirmed` commitment
+    pub fn get_slot(&self) -> VeilResult<u64> {
+        let result = self.call("getSlot", json!([{ "commitment": "confirmed" }]))?;
+        result
+            .as_u64()
+            .ok_or_else(|| VeilError::Rpc("getSlot: missing slot".into()))
+    }
+
+    /// Balance of `pubkey` in lamports
+    pub fn get_balance(&self, pubkey: &Pubkey) -> VeilResult<u64> {
+        let result = self.call("getBalance", json!([bs58::encode(pubkey).into_string()]))?;
+        result["value"]
+            .as_u64()
+            .ok_or_else(|| VeilError::Rpc("getBalance: missing value".into()))
+    }
+
+    /// Data of the account at `pubkey`, `None` if it does not exist
+    pub fn get_account_data(&self, pubkey: &Pubkey) -> VeilResult<Option<Vec<u8>>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([bs58::encode(pubkey).into_string(), { "encoding": "base64" }]),
+        )?;
+        if result["value"].is_null() {
+            return Ok(None);
+        }
+        let data = result["value"]["data"][0]
+            .as_str()
+            .ok_or_else(|| VeilError::Rpc("getAccountInfo: missing data".into()))?;
+        let data = BASE64
+            .decode(data)
+            .map_err(|e| VeilError::Rpc(format!("getAccountInfo: {}", e)))?;
+        Ok(Some(data))
+    }
+
+    /// The address lookup table at `address`
+    pub fn get_lookup_table(&self, address: &Pubkey) -> VeilResult<AddressLookupTable> {
+        let data = self.get_account_data(address)?.ok_or_else(|| {
+            VeilError::Rpc(format!("no lookup table at {}", bs58::encode(address).into_string()))
+        })?;
+        AddressLookupTable::from_account_data(*address, &data)
+    }
+
+    /// Submit a signed transaction, returning its signature
+    pub fn send_transaction(&self, transaction: &[u8]) -> VeilResult<String> {
+        let result = self.call(
+            "sendTransaction",
+            json!([bs58::encode(transaction).into_string(), { "encoding": "base58" }]),
+        )?;
+        result
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| VeilError::Rpc("sendTransaction: missing signature".into()))
+    }
+
+    /// Prices (micro-lamports per compute unit) that transactions locking
+    /// any of `accounts` paid in recent slots, one per slot
+    pub fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> VeilResult<Vec<u64>> {
+        let accounts: Vec<String> =
+            accounts.iter().map(|a| bs58::encode(a).into_string()).collect();
+        let result = self.call("getRecentPrioritizationFees", json!([accounts]))?;
+        let missing = || VeilError::Rpc("getRecentPrioritizationFees: missing fees".into());
+        result
+            .as_array()
+            .ok_or_else(missing)?
+            .iter()
+            .map(|entry| entry["prioritizationFee"].as_u64().ok_or_else(missing))
+            .collect()
+    }
+
+    /// Accounts owned by `program_id` whose data starts with `prefix`
+    ///
+    /// Returns each account's address and data.
+    pub fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        prefix: &[u8],
+    ) -> VeilResult<Vec<(Pubkey, Vec<u8>)>> {
+        let result = self.call(
+            "getProgramAccounts",
+            json!([
+                bs58::encode(program_id).into_string(),
+                {
+                    "encoding": "base64",
+                    "filters": [{
+                        "memcmp": { "offset": 0, "bytes": bs58::encode(prefix).into_string() }
+                    }]
+                }
+            ]),
+        )?;
+        let missing = |what: &str| VeilError::Rpc(format!("getProgramAccounts: missing {}", what));
+        result
+            .as_array()
+            .ok_or_else(|| missing("result"))?
+            .iter()
+            .map(|entry| {
+                let address = entry["pubkey"].as_str().ok_or_else(|| missing("pubkey"))?;
+                let address = decode_pubkey(address)?;
+                let data = entry["account"]["data"][0].as_str().ok_or_else(|| missing("data"))?;
+                let data = BASE64
+                    .decode(data)
+                    .map_err(|e| VeilError::Rpc(format!("getProgramAccounts: {}", e)))?;
+                Ok((address, data))
+            })
+            .collect()
+    }
+
+    /// Confirmed transactions touching `address`, newest first
+    ///
+    /// Returns at most `limit` (capped at `MAX_SIGNATURES_PER_PAGE`) entries,
+    /// starting below `before` if given; page backwards by passing the last
+    /// signature of one page as `before` of the next.
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<&str>,
+        limit: usize,
+    ) -> VeilResult<Vec<SignatureInfo>> {
+        let mut config = json!({
+            "commitment": "confirmed",
+            "limit": limit.clamp(1, MAX_SIGNATURES_PER_PAGE),
+        });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        let result = self.call(
+            "getSignaturesForAddress",
+            json!([bs58::encode(address).into_string(), config]),
+        )?;
+        let missing =
+            |what: &str| VeilError::Rpc(format!("getSignaturesForAddress: missing {}", what));
+        result
+            .as_array()
+            .ok_or_else(|| missing("result"))?
+            .iter()
+            .map(|entry| {
+                Ok(SignatureInfo {
+                    signature: entry["signature"]
+                        .as_str()
+                        .ok_or_else(|| missing("signature"))?
+                        .to_owned(),
+                    slot: entry["slot"].as_u64().ok_or_else(|| missing("slot"))?,
+                    failed: !entry["err"].is_null(),
+                })
+            })
+            .collect()
+    }
+
+    /// Status of the transaction with `signature`, `None` if the cluster
+    /// has not seen it (or no longer remembers it)
+    pub fn get_signature_status(&self, signature: &str) -> VeilResult<Option<TransactionStatus>> {
+        let result = self.call(
+            "getSignatureStatuses",
+            json!([[signature], { "searchTransactionHistory": true }]),
+        )?;
+        let status = &result["value"][0];
+        if status.is_null() {
+            return Ok(None);
+        }
+        let slot = status["slot"]
+            .as_u64()
+            .ok_or_else(|| VeilError::Rpc("getSignatureStatuses: missing slot".into()))?;
+        Ok(Some(TransactionStatus {
+            slot,
+            confirmed: matches!(
+                status["confirmationStatus"].as_str(),
+                Some("confirmed" | "finalized")
+            ),
+            error: (!status["err"].is_null()).then(|| status["err"].to_string()),
+        }))
+    }
+
+    /// Log messages of a confirmed transaction
+    pub fn get_transaction_logs(&self, signature: &str) -> VeilResult<Vec<String>> {
+        let result = self.call(
+            "getTransaction",
+            json!([
+                signature,
+                {
+                    "encoding": "json",
+                    "commitment": "confirmed",
+                    "maxSupportedTransactionVersion": 0
+                }
+            ]),
+        )?;
+        let logs = result["meta"]["logMessages"].as_array().ok_or_else(|| {
+            VeilError::Rpc(format!("getTransaction: no logs for {}", signature))
+        })?;
+        Ok(logs.iter().filter_map(|l| l.as_str().map(str::to_owned)).collect())
+    }
+
+    fn call(&self, method: &str, params: Value) -> VeilResult<Value> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(request)
+            .map_err(|e| VeilError::Rpc(format!("{}: {}", method, e)))?
+            .into_json()
+            .map_err(|e| VeilError::Rpc(format!("{}: {}", method, e)))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(VeilError::Rpc(format!("{}: {}", method, error)));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+/// Derive a program address and its bump seed, as `Pubkey::find_program_address`
+pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Option<(Pubkey, u8)> {
+    (0..=u8::MAX).rev().find_map(|bump| {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update([bump]);
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        let address: Pubkey = hasher.finalize().into();
+
+        // A program address must not be a valid ed25519 public key
+        PublicKey::from_bytes(&address).is_err().then_some((address, bump))
+    })
+}
+
+/// Address and bump seed of the lookup table `authority` creates at
+/// `recent_slot`, for `lookup_table::create_lookup_table`
+pub fn lookup_table_address(authority: &Pubkey, recent_slot: u64) -> Option<(Pubkey, u8)> {
+    find_program_address(
+        &[authority, &recent_slot.to_le_bytes()],
+        &ADDRESS_LOOKUP_TABLE_PROGRAM_ID,
+    )
+}
+
+/// Decode a base58 address or blockhash
+pub fn decode_pubkey(s: &str) -> VeilResult<Pubkey> {
+    let bytes = bs58::decode(s)
+        .into_vec()
+        .map_err(|e| VeilError::InvalidInput(format!("invalid base58 '{}': {}", s, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| VeilError::InvalidInput(format!("'{}' is not {} bytes", s, PUBKEY_SIZE)))
+}
+
+/// Load a keypair from a Solana CLI keypair file (JSON array of 64 bytes)
+pub fn read_keypair_file(path: impl AsRef<Path>) -> VeilResult<Keypair> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|e| VeilError::Configuration(format!("{}: {}", path.display(), e)))?;
+    let bytes: Vec<u8> = serde_json::from_str(&contents)
+        .map_err(|e| VeilError::Serialization(format!("{}: {}", path.display(), e)))?;
+    Keypair::from_bytes(&bytes)
+        .map_err(|e| VeilError::InvalidInput(format!("{}: {}", path.display(), e)))
+}
+
+/// Sign an assembled message, returning the wire-format transaction
+///
+/// Every required signer of the message must be among `signers`; extra
+/// keypairs are ignored.
+pub fn sign_transaction(
+    transaction: &AssembledTransaction,
+    signers: &[&Keypair],
+) -> VeilResult<Vec<u8>> {
+    let message = &transaction.message;
+    let mut wire = Vec::with_capacity(transaction.serialized_size());
+    wire.push(transaction.num_signatures as u8);
+
+    // Signer keys are the first `num_signatures` account keys, after the
+    // version prefix of a v0 message and the header. With at most 256 keys
+    // the compact-u16 key count is one or two bytes.
+    let prefix = usize::from(message.first().is_some_and(|b| b & MESSAGE_VERSION_PREFIX != 0));
+    let wide_count = message.get(prefix + 3).is_some_and(|b| b & 0x80 != 0);
+    let keys_offset = prefix + if wide_count { 5 } else { 4 };
+    for i in 0..transaction.num_signatures {
+        let start = keys_offset + i * PUBKEY_SIZE;
+        let key = message
+            .get(start..start + PUBKEY_SIZE)
+            .ok_or_else(|| VeilError::Serialization("truncated message".into()))?;
+        let signer = signers
+            .iter()
+            .find(|kp| kp.public.as_bytes() == key)
+            .ok_or_else(|| {
+                VeilError::InvalidInput(format!(
+                    "missing signer {}",
+                    bs58::encode(key).into_string()
+                ))
+            })?;
+        wire.extend_from_slice(&signer.sign(message).to_bytes());
+    }
+
+    wire.extend_from_slice(message);
+    Ok(wire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{
+        AccountMeta, Instruction, TransactionAssembler, NATIVE_MINT, NULLIFIER_SEED, POOL_SEED,
+    };
+
+    #[test]
+    fn test_find_program_address() {
+        // Vectors from solana_program::pubkey::Pubkey::find_program_address
+        let program_id = decode_pubkey("Vei1111111111111111111111111111111111111111").unwrap();
+        assert_eq!(
+            bs58::encode(NATIVE_MINT).into_string(),
+            "So11111111111111111111111111111111111111112"
+        );
+        let (pool, bump) = find_program_address(&[POOL_SEED, &NATIVE_MINT], &program_id).unwrap();
+        assert_eq!(bs58::encode(pool).into_string(), "9HhrkXeKCtJivSh5jkgsBDNoeQuaGvozSxY2jRzHDGV");
+        assert_eq!(bump, 254);
+
+        // Bumps 255 through 252 land on the curve for this one
+        let (marker, bump) =
+            find_program_address(&[NULLIFIER_SEED, &pool, &[1u8; 32]], &program_id).unwrap();
+        assert_eq!(bs58::encode(marker).into_string(), "GXVvYzSgPB14uYCmZst6bTbVMbBSzBaqFJJitsN3KbLs");
+        assert_eq!(bump, 251);
+    }
+
+    #[test]
+    fn test_sign_transaction() {
+        let payer = keypair(7);
+        let mut asm = TransactionAssembler::new(payer.public.to_bytes());
+        asm.add_instruction(Instruction {
+            program_id: [1u8; 32],
+            accounts: vec![AccountMeta::new([2u8; 32], false)],
+            data: vec![1, 2, 3],
+        });
+        let tx = asm.assemble([0xaa; 32]).unwrap();
+
+        let wire = sign_transaction(&tx, &[&keypair(8), &payer]).unwrap();
+        assert_eq!(wire.len(), tx.serialized_size());
+        let signature = ed25519_dalek::Signature::from_bytes(&wire[1..65]).unwrap();
+        assert!(payer.public.verify_strict(&tx.message, &signature).is_ok());
+
+        assert!(sign_transaction(&tx, &[&keypair(8)]).is_err());
+
+        // v0: the signer keys follow the version prefix
+        let table = AddressLookupTable::new([3u8; 32], vec![[2u8; 32]]);
+        let v0 = asm.with_lookup_tables(vec![table]).assemble([0xaa; 32]).unwrap();
+        assert_eq!(v0.message[0], MESSAGE_VERSION_PREFIX);
+        let wire = sign_transaction(&v0, &[&payer]).unwrap();
+        let signature = ed25519_dalek::Signature::from_bytes(&wire[1..65]).unwrap();
+        assert!(payer.public.verify_strict(&v0.message, &signature).is_ok());
+    }
+
+    #[test]
+    fn test_lookup_table_address() {
+        let authority = [7u8; 32];
+        let (table, _) = lookup_table_address(&authority, 300).unwrap();
+        assert!(PublicKey::from_bytes(&table).is_err());
+        assert_ne!(lookup_table_address(&authority, 301).unwrap().0, table);
+    }
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+}
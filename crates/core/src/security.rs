@@ -0,0 +1,73 @@
+//! Release Build Guard
+//!
+//! Several constructions in this crate are placeholders that are fine for
+//! development but must not ship silently. Each one is listed in
+//! `KNOWN_WEAK`, and a release build (no `debug_assertions`) fails to compile
+//! while the list is non-empty unless the `allow-insecure` feature is enabled
+//! to acknowledge them explicitly.
+//!
+//! When an item gets its vetted replacement, remove its entry here; once the
+//! list is empty the guard no longer requires the feature.
+
+/// A known-weak construction still reachable from production code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeakItem {
+    /// Short identifier
+    pub id: &'static str,
+    /// Where the construction lives
+    pub location: &'static str,
+    /// Why it is not production-grade
+    pub reason: &'static str,
+}
+
+/// Weak constructions compiled into this build
+pub const KNOWN_WEAK: &[WeakItem] = &[
+    WeakItem {
+        id: "pedersen-h",
+        location: "crypto::commitment",
+        reason: "H is a known scalar multiple of G, so commitments are not binding",
+    },
+];
+
+/// Whether the guard rejects a build with the given configuration
+pub const fn guard_triggers(debug_assertions: bool, allow_insecure: bool) -> bool {
+    !KNOWN_WEAK.is_empty() && !debug_assertions && !allow_insecure
+}
+
+/// Whether this build was compiled with the weak items acknowledged
+pub const fn insecure_build_allowed() -> bool {
+    cfg!(debug_assertions) || cfg!(feature = "allow-insecure")
+}
+
+const _: () = assert!(
+    !guard_triggers(cfg!(debug_assertions), cfg!(feature = "allow-insecure")),
+    "veil-core still contains known-weak constructions (see security::KNOWN_WEAK); \
+     release builds must enable the `allow-insecure` feature to acknowledge them"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_triggers_in_insecure_release() {
+        // Release build without acknowledgement is rejected
+        assert!(guard_triggers(false, false));
+
+        // Debug builds and acknowledged release builds are accepted
+        assert!(!guard_triggers(true, false));
+        assert!(!guard_triggers(false, true));
+    }
+
+    #[test]
+    fn test_current_build_passed_guard() {
+        assert!(insecure_build_allowed());
+        assert!(!guard_triggers(cfg!(debug_assertions), cfg!(feature = "allow-insecure")));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_release_build_requires_acknowledgement() {
+        assert!(cfg!(feature = "allow-insecure"));
+    }
+}
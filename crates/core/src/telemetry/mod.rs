@@ -0,0 +1,523 @@
+//! Opt-in Telemetry
+//!
+//! Aggregate reliability data (proof failure rates, relayer rejection reasons,
+//! proving time) for SDK developers, without anything that could identify a
+//! user or a transaction.
+//!
+//! Privacy rules:
+//! - Event types are a closed enum; there are no free-form strings
+//! - Events never carry amounts, keys, nullifiers, commitments or addresses
+//! - Events are aggregated locally into counters; only the aggregate
+//!   `TelemetryReport` ever reaches a sink
+//! - Reports are rate limited and sent after a randomized delay, so their
+//!   timing does not line up with individual transactions
+//!
+//! Telemetry is disabled by default. Clients take an optional
+//! `Arc<Telemetry>` and record nothing without one.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::proof::ProofError;
+use crate::relayer::RelayerError;
+
+/// Version of the serialized report schema
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Default minimum time between reports
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Default maximum random delay added to each report
+pub const DEFAULT_MAX_JITTER: Duration = Duration::from_secs(15 * 60);
+
+/// Why proof generation failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum ProofFailure {
+    InvalidWitness,
+    Generation,
+    Verification,
+    Serialization,
+    Setup,
+    Parameters,
+}
+
+impl From<&ProofError> for ProofFailure {
+    fn from(err: &ProofError) -> Self {
+        match err {
+            ProofError::InvalidWitness | ProofError::NoteFromPreviousEpoch { .. } => {
+                ProofFailure::InvalidWitness
+            }
+            ProofError::GenerationFailed(_) => ProofFailure::Generation,
+            ProofError::VerificationFailed(_) => ProofFailure::Verification,
+            ProofError::SerializationError(_) => ProofFailure::Serialization,
+            ProofError::SetupError(_) => ProofFailure::Setup,
+            ProofError::InvalidProvingKey
+            | ProofError::InvalidVerifyingKey
+            | ProofError::KeyFile(_)
+            | ProofError::KeyHashMismatch(_)
+            | ProofError::TreeDepthMismatch { .. } => ProofFailure::Parameters,
+        }
+    }
+}
+
+/// Why a relayer rejected or failed a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum RelayRejection {
+    NoRelayers,
+    FeeTooHigh,
+    Network,
+    InvalidResponse,
+    Rejected,
+    Timeout,
+    InvalidProof,
+}
+
+impl From<&RelayerError> for RelayRejection {
+    fn from(err: &RelayerError) -> Self {
+        match err {
+            RelayerError::NoRelayersAvailable => RelayRejection::NoRelayers,
+            RelayerError::FeeTooHigh(..) => RelayRejection::FeeTooHigh,
+            RelayerError::NetworkError(_) => RelayRejection::Network,
+            RelayerError::InvalidResponse(_) => RelayRejection::InvalidResponse,
+            RelayerError::TransactionRejected(_) => RelayRejection::Rejected,
+            RelayerError::Timeout => RelayRejection::Timeout,
+            RelayerError::InvalidProof => RelayRejection::InvalidProof,
+            RelayerError::InvalidQuote(_) => RelayRejection::InvalidResponse,
+            RelayerError::FeeExceedsQuote { .. } => RelayRejection::FeeTooHigh,
+            RelayerError::FeeExceedsMax { .. } => RelayRejection::FeeTooHigh,
+            RelayerError::InvalidRequest(_) => RelayRejection::Rejected,
+            RelayerError::UnknownRequest(_) => RelayRejection::InvalidResponse,
+        }
+    }
+}
+
+/// Something worth counting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TelemetryEvent {
+    ProofGenerated { proving_time: Duration },
+    ProofFailed(ProofFailure),
+    RelaySubmitted,
+    RelayRejected(RelayRejection),
+    TransactionAssembled,
+    TransactionTooLarge,
+}
+
+/// Aggregated counters, the only thing sinks receive
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TelemetryReport {
+    pub schema_version: u32,
+    pub proofs_generated: u64,
+    pub average_proving_time_ms: u64,
+    pub proof_failures: BTreeMap<ProofFailure, u64>,
+    pub relay_submissions: u64,
+    pub relay_rejections: BTreeMap<RelayRejection, u64>,
+    pub transactions_assembled: u64,
+    pub transactions_too_large: u64,
+}
+
+impl TelemetryReport {
+    fn is_empty(&self) -> bool {
+        self.proofs_generated == 0
+            && self.proof_failures.is_empty()
+            && self.relay_submissions == 0
+            && self.relay_rejections.is_empty()
+            && self.transactions_assembled == 0
+            && self.transactions_too_large == 0
+    }
+}
+
+/// Destination for aggregated reports
+pub trait TelemetrySink: Send + Sync {
+    fn report(&self, report: &TelemetryReport) -> io::Result<()>;
+}
+
+/// Sink that discards everything
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopSink;
+
+impl TelemetrySink for NoopSink {
+    fn report(&self, _report: &TelemetryReport) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sink that appends one JSON object per report to a file
+#[derive(Clone, Debug)]
+pub struct JsonLinesSink {
+    path: PathBuf,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TelemetrySink for JsonLinesSink {
+    fn report(&self, report: &TelemetryReport) -> io::Result<()> {
+        let mut line = serde_json::to_vec(report)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+}
+
+/// Telemetry configuration
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    /// Opt-in flag; nothing is recorded unless set
+    pub enabled: bool,
+    /// Minimum time between reports
+    pub report_interval: Duration,
+    /// Maximum random delay added to each report
+    pub max_jitter: Duration,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            report_interval: DEFAULT_REPORT_INTERVAL,
+            max_jitter: DEFAULT_MAX_JITTER,
+        }
+    }
+}
+
+struct State {
+    report: TelemetryReport,
+    proving_time_total: Duration,
+    next_report_at: Instant,
+}
+
+/// Local aggregator that periodically hands reports to a sink
+pub struct Telemetry {
+    config: TelemetryConfig,
+    sink: Box<dyn TelemetrySink>,
+    state: Mutex<State>,
+}
+
+impl fmt::Debug for Telemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Telemetry")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Telemetry {
+    /// Create a telemetry collector reporting to `sink`
+    pub fn new(config: TelemetryConfig, sink: impl TelemetrySink + 'static) -> Self {
+        let next_report_at = Instant::now() + next_delay(&config);
+        Self {
+            config,
+            sink: Box::new(sink),
+            state: Mutex::new(State {
+                report: TelemetryReport::default(),
+                proving_time_total: Duration::ZERO,
+                next_report_at,
+            }),
+        }
+    }
+
+    /// A collector that records nothing
+    pub fn disabled() -> Self {
+        Self::new(TelemetryConfig::default(), NoopSink)
+    }
+
+    /// Whether events are being recorded
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Record an event, reporting the aggregate if the next report is due
+    pub fn record(&self, event: TelemetryEvent) {
+        self.record_at(event, Instant::now());
+    }
+
+    /// Counters aggregated since the last report
+    pub fn snapshot(&self) -> TelemetryReport {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).report.clone()
+    }
+
+    /// Report whatever has been aggregated so far
+    pub fn flush(&self) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.report(&mut state, Instant::now());
+    }
+
+    fn record_at(&self, event: TelemetryEvent, now: Instant) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match event {
+            TelemetryEvent::ProofGenerated { proving_time } => {
+                state.report.proofs_generated += 1;
+                state.proving_time_total += proving_time;
+            }
+            TelemetryEvent::ProofFailed(reason) => {
+                *state.report.proof_failures.entry(reason).or_default() += 1;
+            }
+            TelemetryEvent::RelaySubmitted => state.report.relay_submissions += 1,
+            TelemetryEvent::RelayRejected(reason) => {
+                *state.report.relay_rejections.entry(reason).or_default() += 1;
+            }
+            TelemetryEvent::TransactionAssembled => state.report.transactions_assembled += 1,
+            TelemetryEvent::TransactionTooLarge => state.report.transactions_too_large += 1,
+        }
+
+        if now >= state.next_report_at {
+            self.report(&mut state, now);
+        }
+    }
+
+    fn report(&self, state: &mut State, now: Instant) {
+        state.next_report_at = now + next_delay(&self.config);
+        if state.report.is_empty() {
+            return;
+        }
+
+        let mut report = std::mem::take(&mut state.report);
+        let proving_time_total = std::mem::take(&mut state.proving_time_total);
+        report.schema_version = REPORT_SCHEMA_VERSION;
+        if report.proofs_generated > 0 {
+            report.average_proving_time_ms =
+                (proving_time_total.as_millis() / report.proofs_generated as u128) as u64;
+        }
+
+        // Telemetry must never affect the operation being measured
+        let _ = self.sink.report(&report);
+    }
+}
+
+fn next_delay(config: &TelemetryConfig) -> Duration {
+    let jitter_ms = config.max_jitter.as_millis() as u64;
+    let jitter = if jitter_ms == 0 {
+        0
+    } else {
+        rand::thread_rng().gen_range(0..=jitter_ms)
+    };
+    config.report_interval + Duration::from_millis(jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Sink that keeps reports in memory
+    #[derive(Clone, Default)]
+    struct MemorySink(Arc<Mutex<Vec<TelemetryReport>>>);
+
+    impl TelemetrySink for MemorySink {
+        fn report(&self, report: &TelemetryReport) -> io::Result<()> {
+            self.0.lock().unwrap().push(report.clone());
+            Ok(())
+        }
+    }
+
+    fn enabled(interval: Duration) -> TelemetryConfig {
+        TelemetryConfig {
+            enabled: true,
+            report_interval: interval,
+            max_jitter: Duration::ZERO,
+        }
+    }
+
+    /// Every event variant; the match fails to compile when a variant is
+    /// added, forcing it through the schema review below.
+    fn all_events() -> Vec<TelemetryEvent> {
+        let events = vec![
+            TelemetryEvent::ProofGenerated {
+                proving_time: Duration::from_millis(1500),
+            },
+            TelemetryEvent::ProofFailed(ProofFailure::Generation),
+            TelemetryEvent::RelaySubmitted,
+            TelemetryEvent::RelayRejected(RelayRejection::FeeTooHigh),
+            TelemetryEvent::TransactionAssembled,
+            TelemetryEvent::TransactionTooLarge,
+        ];
+        for event in &events {
+            match event {
+                TelemetryEvent::ProofGenerated { .. }
+                | TelemetryEvent::ProofFailed(_)
+                | TelemetryEvent::RelaySubmitted
+                | TelemetryEvent::RelayRejected(_)
+                | TelemetryEvent::TransactionAssembled
+                | TelemetryEvent::TransactionTooLarge => {}
+            }
+        }
+        events
+    }
+
+    fn full_report() -> TelemetryReport {
+        let sink = MemorySink::default();
+        let telemetry = Telemetry::new(enabled(DEFAULT_REPORT_INTERVAL), sink.clone());
+        for event in all_events() {
+            telemetry.record(event);
+        }
+        for reason in [
+            ProofFailure::InvalidWitness,
+            ProofFailure::Generation,
+            ProofFailure::Verification,
+            ProofFailure::Serialization,
+            ProofFailure::Setup,
+            ProofFailure::Parameters,
+        ] {
+            telemetry.record(TelemetryEvent::ProofFailed(reason));
+        }
+        for reason in [
+            RelayRejection::NoRelayers,
+            RelayRejection::FeeTooHigh,
+            RelayRejection::Network,
+            RelayRejection::InvalidResponse,
+            RelayRejection::Rejected,
+            RelayRejection::Timeout,
+            RelayRejection::InvalidProof,
+        ] {
+            telemetry.record(TelemetryEvent::RelayRejected(reason));
+        }
+        telemetry.flush();
+        let reports = sink.0.lock().unwrap();
+        reports[0].clone()
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let sink = MemorySink::default();
+        let telemetry = Telemetry::new(TelemetryConfig::default(), sink.clone());
+        assert!(!telemetry.is_enabled());
+
+        for event in all_events() {
+            telemetry.record(event);
+        }
+        telemetry.flush();
+        assert!(sink.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let report = full_report();
+        assert_eq!(report.schema_version, REPORT_SCHEMA_VERSION);
+        assert_eq!(report.proofs_generated, 1);
+        assert_eq!(report.average_proving_time_ms, 1500);
+        assert_eq!(report.proof_failures[&ProofFailure::Generation], 2);
+        assert_eq!(report.proof_failures[&ProofFailure::Setup], 1);
+        assert_eq!(report.relay_submissions, 1);
+        assert_eq!(report.relay_rejections[&RelayRejection::FeeTooHigh], 2);
+        assert_eq!(report.transactions_assembled, 1);
+        assert_eq!(report.transactions_too_large, 1);
+    }
+
+    #[test]
+    fn test_rate_limited_reporting() {
+        let sink = MemorySink::default();
+        let telemetry = Telemetry::new(enabled(Duration::from_secs(60)), sink.clone());
+        let start = Instant::now();
+
+        // Nothing is reported before the interval elapses
+        for i in 0..10 {
+            telemetry.record_at(TelemetryEvent::RelaySubmitted, start + Duration::from_secs(i));
+        }
+        assert!(sink.0.lock().unwrap().is_empty());
+
+        // First event after the interval triggers one aggregated report
+        telemetry.record_at(TelemetryEvent::RelaySubmitted, start + Duration::from_secs(61));
+        telemetry.record_at(TelemetryEvent::RelaySubmitted, start + Duration::from_secs(62));
+        let reports = sink.0.lock().unwrap().clone();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].relay_submissions, 11);
+    }
+
+    #[test]
+    fn test_jitter_within_bounds() {
+        let config = TelemetryConfig {
+            enabled: true,
+            report_interval: Duration::from_secs(10),
+            max_jitter: Duration::from_secs(5),
+        };
+        for _ in 0..100 {
+            let delay = next_delay(&config);
+            assert!(delay >= Duration::from_secs(10));
+            assert!(delay <= Duration::from_secs(15));
+        }
+    }
+
+    #[test]
+    fn test_schema_has_no_sensitive_fields() {
+        let json = serde_json::to_value(full_report()).unwrap();
+        let object = json.as_object().unwrap();
+
+        // Exact field list: a new field must be reviewed and added here
+        let mut fields: Vec<&str> = object.keys().map(|k| k.as_str()).collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            vec![
+                "average_proving_time_ms",
+                "proof_failures",
+                "proofs_generated",
+                "relay_rejections",
+                "relay_submissions",
+                "schema_version",
+                "transactions_assembled",
+                "transactions_too_large",
+            ]
+        );
+
+        const FORBIDDEN: &[&str] = &[
+            "amount", "key", "nullifier", "commitment", "secret", "address", "recipient",
+            "signature", "sender", "blinding", "memo",
+        ];
+        fn check(value: &serde_json::Value, path: &str) {
+            match value {
+                // Only counters; no strings, so no free-form data
+                serde_json::Value::Number(n) => assert!(n.is_u64(), "{}", path),
+                serde_json::Value::Object(map) => {
+                    for (name, inner) in map {
+                        let lower = name.to_lowercase();
+                        for word in FORBIDDEN {
+                            assert!(!lower.contains(word), "{}.{}", path, name);
+                        }
+                        check(inner, &format!("{}.{}", path, name));
+                    }
+                }
+                other => panic!("unexpected value at {}: {}", path, other),
+            }
+        }
+        check(&json, "report");
+    }
+
+    #[test]
+    fn test_json_lines_sink() {
+        let path = std::env::temp_dir().join(format!(
+            "veil_telemetry_{}.jsonl",
+            rand::thread_rng().gen::<u64>()
+        ));
+        let sink = JsonLinesSink::new(&path);
+        let report = full_report();
+        sink.report(&report).unwrap();
+        sink.report(&TelemetryReport::default()).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed["relay_rejections"]["FeeTooHigh"], 2);
+    }
+}
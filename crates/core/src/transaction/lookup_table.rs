@@ -0,0 +1,169 @@
+//! Address lookup tables
+//!
+//! A legacy message spells out every account it touches, 32 bytes each. A
+//! joinsplit or a sweep over several notes, or a token unshield that also
+//! creates the recipient's token account and logs a memo, references enough
+//! accounts to overflow `PACKET_DATA_SIZE` before its proof is counted.
+//!
+//! A v0 message can instead name an account by its index in an on-chain
+//! address lookup table: one byte instead of 32. Signers and invoked
+//! programs must still be listed in full. `TransactionAssembler` compiles a
+//! v0 message once given tables (`with_lookup_tables`); this module builds
+//! the instructions that create and extend a table and reads one back from
+//! its account data.
+//!
+//! A table's address is a program address of its authority and a recent
+//! slot; derive it with `rpc::lookup_table_address`. Addresses added to a
+//! table can be looked up from the slot after they were added.
+
+use super::{AccountMeta, Instruction, Pubkey, PUBKEY_SIZE, SYSTEM_PROGRAM_ID};
+use crate::error::{VeilError, VeilResult};
+
+/// Address lookup table program id (`AddressLookupTab1e1111111111111111111111111`)
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: Pubkey = [
+    2, 119, 166, 175, 151, 51, 155, 122, 200, 141, 24, 146, 201, 4, 70, 245, 0, 2, 48, 146, 102,
+    246, 46, 83, 193, 24, 36, 73, 130, 0, 0, 0,
+];
+
+/// Most addresses a table holds
+pub const LOOKUP_TABLE_MAX_ADDRESSES: usize = 256;
+
+/// Size of a table account's header, before its addresses
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Most addresses one `extend_lookup_table` instruction adds and still fits
+/// in a transaction, with room for compute budget instructions
+pub const MAX_EXTEND_ADDRESSES: usize = 20;
+
+/// Account type tag of an initialized table
+const LOOKUP_TABLE_TYPE: u32 = 1;
+
+/// An address lookup table and the addresses it holds
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressLookupTable {
+    /// The table's address
+    pub key: Pubkey,
+    /// Its addresses, in table order
+    pub addresses: Vec<Pubkey>,
+}
+
+impl AddressLookupTable {
+    /// Table at `key` holding `addresses`
+    pub fn new(key: Pubkey, addresses: Vec<Pubkey>) -> Self {
+        Self { key, addresses }
+    }
+
+    /// Decode the table stored at `key` from its account data
+    pub fn from_account_data(key: Pubkey, data: &[u8]) -> VeilResult<Self> {
+        let invalid = |reason: &str| VeilError::Serialization(format!("lookup table: {}", reason));
+        let header = data.get(..LOOKUP_TABLE_META_SIZE).ok_or_else(|| invalid("truncated"))?;
+        if u32::from_le_bytes(header[..4].try_into().unwrap()) != LOOKUP_TABLE_TYPE {
+            return Err(invalid("not an initialized table"));
+        }
+        let body = &data[LOOKUP_TABLE_META_SIZE..];
+        if body.len() % PUBKEY_SIZE != 0 {
+            return Err(invalid("partial address"));
+        }
+        let addresses = body
+            .chunks_exact(PUBKEY_SIZE)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        Ok(Self { key, addresses })
+    }
+
+    /// Index of `address` in the table
+    pub fn index_of(&self, address: &Pubkey) -> Option<u8> {
+        let index = self.addresses.iter().position(|a| a == address)?;
+        u8::try_from(index).ok()
+    }
+}
+
+/// `CreateLookupTable`: create the table at `table`, which
+/// `rpc::lookup_table_address` derives with `bump` from `authority` and
+/// `recent_slot`, with its rent paid by `payer`
+pub fn create_lookup_table(
+    table: Pubkey,
+    bump: u8,
+    authority: Pubkey,
+    payer: Pubkey,
+    recent_slot: u64,
+) -> Instruction {
+    let mut data = 0u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&recent_slot.to_le_bytes());
+    data.push(bump);
+    Instruction {
+        program_id: ADDRESS_LOOKUP_TABLE_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(table, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+        data,
+    }
+}
+
+/// `ExtendLookupTable`: append `addresses` to `table`, with the extra rent
+/// paid by `payer`
+///
+/// At most `MAX_EXTEND_ADDRESSES` fit in one transaction; split longer
+/// lists across several.
+pub fn extend_lookup_table(
+    table: Pubkey,
+    authority: Pubkey,
+    payer: Pubkey,
+    addresses: &[Pubkey],
+) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&(addresses.len() as u64).to_le_bytes());
+    for address in addresses {
+        data.extend_from_slice(address);
+    }
+    Instruction {
+        program_id: ADDRESS_LOOKUP_TABLE_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(table, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_data() {
+        let mut data = vec![0u8; LOOKUP_TABLE_META_SIZE];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&[7; 32]);
+        data.extend_from_slice(&[8; 32]);
+        let table = AddressLookupTable::from_account_data([1; 32], &data).unwrap();
+        assert_eq!(table, AddressLookupTable::new([1; 32], vec![[7; 32], [8; 32]]));
+        assert_eq!((table.index_of(&[8; 32]), table.index_of(&[9; 32])), (Some(1), None));
+
+        assert!(AddressLookupTable::from_account_data([1; 32], &data[..40]).is_err());
+        assert!(AddressLookupTable::from_account_data([1; 32], &data[..100]).is_err());
+        data[0] = 0;
+        assert!(AddressLookupTable::from_account_data([1; 32], &data).is_err());
+    }
+
+    #[test]
+    fn test_instructions() {
+        let create = create_lookup_table([1; 32], 254, [2; 32], [3; 32], 300);
+        assert_eq!(create.data, [&[0, 0, 0, 0][..], &300u64.to_le_bytes(), &[254]].concat());
+        assert_eq!(create.accounts[0], AccountMeta::new([1; 32], false));
+
+        let extend = extend_lookup_table([1; 32], [2; 32], [3; 32], &[[7; 32], [8; 32]]);
+        assert_eq!(extend.data.len(), 4 + 8 + 2 * 32);
+        assert_eq!(extend.data[4..12], 2u64.to_le_bytes());
+        assert_eq!(extend.data[12..44], [7; 32]);
+        assert_eq!(
+            bs58::encode(extend.program_id).into_string(),
+            "AddressLookupTab1e1111111111111111111111111"
+        );
+    }
+}
@@ -0,0 +1,1068 @@
+//! Transaction Assembly
+//!
+//! Compiles instructions into a Solana message and enforces the packet size
+//! budget before anything is signed or sent.
+//!
+//! Solana transactions are capped at 1232 bytes including signatures. A
+//! transfer with a 256-byte proof plus compute-budget instructions already
+//! sits close to the limit, so `TransactionAssembler::assemble` computes the
+//! exact serialized size and fails with `VeilError::TransactionTooLarge`
+//! instead of letting the RPC node reject the transaction later.
+//!
+//! Messages are legacy unless the assembler is given address lookup tables
+//! (`with_lookup_tables`), which compiles a v0 message naming the tables'
+//! accounts by index; see `lookup_table`.
+
+pub mod lookup_table;
+
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{VeilError, VeilResult};
+use crate::telemetry::{Telemetry, TelemetryEvent};
+
+pub use lookup_table::AddressLookupTable;
+
+/// Maximum serialized transaction size (IPv6 MTU minus headers)
+pub const PACKET_DATA_SIZE: usize = 1232;
+
+/// Size of an ed25519 signature
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Size of a public key
+pub const PUBKEY_SIZE: usize = 32;
+
+/// First byte of a versioned message: the high bit, then the version
+pub const MESSAGE_VERSION_PREFIX: u8 = 0x80;
+
+/// Size of a serialized Groth16 proof (A: 64, B: 128, C: 64)
+pub const PROOF_SIZE: usize = 256;
+
+/// Compute budget program id
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+/// System program id
+pub const SYSTEM_PROGRAM_ID: Pubkey = [0u8; PUBKEY_SIZE];
+
+/// Memo program id (`MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr`)
+pub const MEMO_PROGRAM_ID: Pubkey = [
+    5, 74, 83, 90, 153, 41, 33, 6, 77, 36, 232, 113, 96, 218, 56, 124, 124, 53, 181, 221, 188,
+    146, 187, 129, 228, 31, 168, 64, 65, 5, 68, 141,
+];
+
+/// Seed of a pool PDA, followed by the pool's mint
+pub const POOL_SEED: &[u8] = b"privacy_pool";
+
+/// Mint that keys the native SOL pool (`So11111111111111111111111111111111111111112`)
+pub const NATIVE_MINT: Pubkey = [
+    6, 155, 136, 87, 254, 171, 129, 132, 251, 104, 127, 99, 70, 24, 192, 53, 218, 196, 57, 220,
+    26, 235, 59, 85, 152, 160, 240, 0, 0, 0, 0, 1,
+];
+
+/// Seed of the SOL vault PDA, followed by the pool address
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// Seed of a pool's verifying key PDA, followed by the pool address
+pub const VK_SEED: &[u8] = b"verifying_key";
+
+/// Seed of a nullifier marker PDA, followed by the pool address, the tree
+/// epoch (u32, little-endian) and the nullifier
+pub const NULLIFIER_SEED: &[u8] = b"nullifier";
+
+/// Seed of a nullifier archive bucket PDA, followed by the pool address, the
+/// tree epoch and the bucket (the nullifier's last byte)
+pub const ARCHIVE_SEED: &[u8] = b"nullifier_archive";
+
+/// Seed of an archived tree root PDA, followed by the pool address and the
+/// tree epoch
+pub const ARCHIVED_ROOT_SEED: &[u8] = b"archived_root";
+
+/// Seed of a pool's root history PDA, followed by the pool address
+pub const ROOT_HISTORY_SEED: &[u8] = b"root_history";
+
+/// Seed of a pool's treasury PDA, followed by the pool address
+pub const TREASURY_SEED: &[u8] = b"treasury";
+
+/// Seed of a pending withdrawal PDA, followed by the pool address, the tree
+/// epoch and the nullifier
+pub const PENDING_WITHDRAWAL_SEED: &[u8] = b"pending_withdrawal";
+
+/// A 32-byte public key
+pub type Pubkey = [u8; PUBKEY_SIZE];
+
+/// Account referenced by an instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    /// Writable account
+    pub fn new(pubkey: Pubkey, is_signer: bool) -> Self {
+        Self {
+            pubkey,
+            is_signer,
+            is_writable: true,
+        }
+    }
+
+    /// Read-only account
+    pub fn new_readonly(pubkey: Pubkey, is_signer: bool) -> Self {
+        Self {
+            pubkey,
+            is_signer,
+            is_writable: false,
+        }
+    }
+}
+
+/// A single instruction before compilation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+impl Instruction {
+    /// System program `Transfer` of `lamports` from `from`, which signs, to
+    /// `to`
+    pub fn system_transfer(from: Pubkey, to: Pubkey, lamports: u64) -> Self {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&lamports.to_le_bytes());
+        Self {
+            program_id: SYSTEM_PROGRAM_ID,
+            accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+            data,
+        }
+    }
+}
+
+/// A compiled message ready for signing
+#[derive(Clone, Debug)]
+pub struct AssembledTransaction {
+    /// Serialized legacy or v0 message
+    pub message: Vec<u8>,
+    /// Number of signatures the message requires
+    pub num_signatures: usize,
+}
+
+impl AssembledTransaction {
+    /// Size of the signed transaction on the wire
+    pub fn serialized_size(&self) -> usize {
+        transaction_size(self.num_signatures, self.message.len())
+    }
+}
+
+/// Collects instructions and compiles them into a size-checked message
+#[derive(Clone, Debug)]
+pub struct TransactionAssembler {
+    payer: Pubkey,
+    instructions: Vec<Instruction>,
+    size_limit: usize,
+    telemetry: Option<Arc<Telemetry>>,
+    /// Tables of a v0 message; `None` compiles a legacy one
+    lookup_tables: Option<Vec<AddressLookupTable>>,
+}
+
+impl TransactionAssembler {
+    /// Create an assembler with `payer` as the fee payer
+    pub fn new(payer: Pubkey) -> Self {
+        Self {
+            payer,
+            instructions: Vec::new(),
+            size_limit: PACKET_DATA_SIZE,
+            telemetry: None,
+            lookup_tables: None,
+        }
+    }
+
+    /// Override the size budget (defaults to `PACKET_DATA_SIZE`)
+    pub fn with_size_limit(mut self, size_limit: usize) -> Self {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// Report assembled and oversized transactions to `telemetry`
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Compile a v0 message, naming accounts held by `tables` by their
+    /// index instead of their address
+    ///
+    /// Signers and invoked programs are always listed in full. An account in
+    /// several tables is looked up in the first.
+    pub fn with_lookup_tables(mut self, tables: Vec<AddressLookupTable>) -> Self {
+        self.lookup_tables = Some(tables);
+        self
+    }
+
+    /// Append an instruction
+    pub fn add_instruction(&mut self, instruction: Instruction) -> &mut Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Append a compute budget `SetComputeUnitLimit` instruction
+    pub fn set_compute_unit_limit(&mut self, units: u32) -> &mut Self {
+        let mut data = vec![2u8];
+        data.extend_from_slice(&units.to_le_bytes());
+        self.add_instruction(compute_budget_instruction(data))
+    }
+
+    /// Append a compute budget `SetComputeUnitPrice` instruction
+    pub fn set_compute_unit_price(&mut self, micro_lamports: u64) -> &mut Self {
+        let mut data = vec![3u8];
+        data.extend_from_slice(&micro_lamports.to_le_bytes());
+        self.add_instruction(compute_budget_instruction(data))
+    }
+
+    /// Serialized size of the signed transaction
+    pub fn serialized_size(&self) -> VeilResult<usize> {
+        let (message, num_signatures) = self.compile([0u8; 32])?;
+        Ok(transaction_size(num_signatures, message.len()))
+    }
+
+    /// Compile the message, failing if the signed transaction exceeds the budget
+    pub fn assemble(&self, recent_blockhash: [u8; 32]) -> VeilResult<AssembledTransaction> {
+        let (message, num_signatures) = self.compile(recent_blockhash)?;
+        let size = transaction_size(num_signatures, message.len());
+        let too_large = size > self.size_limit;
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record(if too_large {
+                TelemetryEvent::TransactionTooLarge
+            } else {
+                TelemetryEvent::TransactionAssembled
+            });
+        }
+        if too_large {
+            return Err(VeilError::TransactionTooLarge {
+                size,
+                limit: self.size_limit,
+            });
+        }
+
+        Ok(AssembledTransaction {
+            message,
+            num_signatures,
+        })
+    }
+
+    /// Compile into a legacy or v0 message, returning it with the signature
+    /// count
+    fn compile(&self, recent_blockhash: [u8; 32]) -> VeilResult<(Vec<u8>, usize)> {
+        // Collect unique keys, merging signer/writable flags. The payer is
+        // always the first key and a writable signer.
+        let mut keys: Vec<AccountMeta> = vec![AccountMeta::new(self.payer, true)];
+        let mut merge = |meta: AccountMeta| match keys.iter_mut().find(|k| k.pubkey == meta.pubkey) {
+            Some(existing) => {
+                existing.is_signer |= meta.is_signer;
+                existing.is_writable |= meta.is_writable;
+            }
+            None => keys.push(meta),
+        };
+        for ix in &self.instructions {
+            for meta in &ix.accounts {
+                merge(meta.clone());
+            }
+            merge(AccountMeta::new_readonly(ix.program_id, false));
+        }
+
+        // Move what the tables hold out of the static keys, as (table,
+        // writable indexes, readonly indexes)
+        let tables = self.lookup_tables.as_deref().unwrap_or_default();
+        let mut lookups: Vec<(&AddressLookupTable, Vec<u8>, Vec<u8>)> =
+            tables.iter().map(|t| (t, Vec::new(), Vec::new())).collect();
+        if !tables.is_empty() {
+            let is_program =
+                |key: &Pubkey| self.instructions.iter().any(|ix| &ix.program_id == key);
+            keys.retain(|k| {
+                if k.is_signer || is_program(&k.pubkey) {
+                    return true;
+                }
+                let found = tables
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, t)| t.index_of(&k.pubkey).map(|index| (i, index)));
+                match found {
+                    Some((i, index)) if k.is_writable => lookups[i].1.push(index),
+                    Some((i, index)) => lookups[i].2.push(index),
+                    None => return true,
+                }
+                false
+            });
+        }
+        lookups.retain(|(_, writable, readonly)| !writable.is_empty() || !readonly.is_empty());
+
+        // Order: writable signers, readonly signers, writable, readonly.
+        // The sort is stable, so the payer stays first.
+        keys.sort_by_key(|k| (!k.is_signer, !k.is_writable));
+
+        // Instructions index the static keys, then every table's writable
+        // accounts, then every table's readonly accounts
+        let mut order: Vec<Pubkey> = keys.iter().map(|k| k.pubkey).collect();
+        for (table, writable, _) in &lookups {
+            order.extend(writable.iter().map(|&i| table.addresses[usize::from(i)]));
+        }
+        for (table, _, readonly) in &lookups {
+            order.extend(readonly.iter().map(|&i| table.addresses[usize::from(i)]));
+        }
+        if order.len() > u8::MAX as usize + 1 {
+            return Err(VeilError::InvalidInput(format!(
+                "Transaction references {} accounts (max 256)",
+                order.len()
+            )));
+        }
+
+        let num_signatures = keys.iter().filter(|k| k.is_signer).count();
+        let num_readonly_signed = keys.iter().filter(|k| k.is_signer && !k.is_writable).count();
+        let num_readonly_unsigned = keys.iter().filter(|k| !k.is_signer && !k.is_writable).count();
+        let index_of = |pubkey: &Pubkey| order.iter().position(|k| k == pubkey).unwrap() as u8;
+
+        let mut message = Vec::new();
+        if self.lookup_tables.is_some() {
+            message.push(MESSAGE_VERSION_PREFIX);
+        }
+        message.extend_from_slice(&[
+            num_signatures as u8,
+            num_readonly_signed as u8,
+            num_readonly_unsigned as u8,
+        ]);
+        encode_length(&mut message, keys.len());
+        for key in &keys {
+            message.extend_from_slice(&key.pubkey);
+        }
+        message.extend_from_slice(&recent_blockhash);
+
+        encode_length(&mut message, self.instructions.len());
+        for ix in &self.instructions {
+            message.push(index_of(&ix.program_id));
+            encode_length(&mut message, ix.accounts.len());
+            for meta in &ix.accounts {
+                message.push(index_of(&meta.pubkey));
+            }
+            encode_length(&mut message, ix.data.len());
+            message.extend_from_slice(&ix.data);
+        }
+
+        if self.lookup_tables.is_some() {
+            encode_length(&mut message, lookups.len());
+            for (table, writable, readonly) in &lookups {
+                message.extend_from_slice(&table.key);
+                encode_length(&mut message, writable.len());
+                message.extend_from_slice(writable);
+                encode_length(&mut message, readonly.len());
+                message.extend_from_slice(readonly);
+            }
+        }
+
+        Ok((message, num_signatures))
+    }
+}
+
+/// Size of a signed transaction with the given message
+pub fn transaction_size(num_signatures: usize, message_len: usize) -> usize {
+    short_vec_len(num_signatures) + num_signatures * SIGNATURE_SIZE + message_len
+}
+
+/// Anchor instruction discriminator: `sha256("global:<name>")[..8]`
+pub fn instruction_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{}", name).as_bytes());
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
+
+/// Instruction data for `shield_sol` / `shield`
+///
+/// `encrypted_note` is at most `crypto::encryption::ENCRYPTED_NOTE_SIZE`
+/// bytes; empty if the note is delivered off-chain.
+pub fn shield_data(name: &str, commitment: &[u8; 32], amount: u64, encrypted_note: &[u8]) -> Vec<u8> {
+    let mut data = instruction_discriminator(name).to_vec();
+    data.extend_from_slice(commitment);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&(encrypted_note.len() as u32).to_le_bytes());
+    data.extend_from_slice(encrypted_note);
+    data
+}
+
+/// Instruction data for `transfer`
+///
+/// `extra_nullifiers` are swept into the output alongside `nullifier`;
+/// empty for a plain transfer.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_data(
+    tree_epoch: u32,
+    root: &[u8; 32],
+    nullifier: &[u8; 32],
+    new_commitment: &[u8; 32],
+    fee: u64,
+    proof: &[u8],
+    encrypted_note: &[u8],
+    extra_nullifiers: &[[u8; 32]],
+) -> Vec<u8> {
+    let mut data = instruction_discriminator("transfer").to_vec();
+    data.extend_from_slice(&tree_epoch.to_le_bytes());
+    data.extend_from_slice(root);
+    data.extend_from_slice(nullifier);
+    data.extend_from_slice(new_commitment);
+    data.extend_from_slice(&fee.to_le_bytes());
+    data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+    data.extend_from_slice(proof);
+    data.extend_from_slice(&(encrypted_note.len() as u32).to_le_bytes());
+    data.extend_from_slice(encrypted_note);
+    data.extend_from_slice(&(extra_nullifiers.len() as u32).to_le_bytes());
+    for extra in extra_nullifiers {
+        data.extend_from_slice(extra);
+    }
+    data
+}
+
+/// Instruction data for `transfer_joinsplit`
+///
+/// `encrypted_notes` holds up to one encrypted note per output, in order.
+pub fn transfer_joinsplit_data(
+    tree_epoch: u32,
+    root: &[u8; 32],
+    nullifiers: &[[u8; 32]; 2],
+    new_commitments: &[[u8; 32]; 2],
+    proof: &[u8],
+    encrypted_notes: &[&[u8]],
+) -> Vec<u8> {
+    let mut data = instruction_discriminator("transfer_joinsplit").to_vec();
+    data.extend_from_slice(&tree_epoch.to_le_bytes());
+    data.extend_from_slice(root);
+    for value in nullifiers.iter().chain(new_commitments.iter()) {
+        data.extend_from_slice(value);
+    }
+    data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+    data.extend_from_slice(proof);
+    data.extend_from_slice(&(encrypted_notes.len() as u32).to_le_bytes());
+    for note in encrypted_notes {
+        data.extend_from_slice(&(note.len() as u32).to_le_bytes());
+        data.extend_from_slice(note);
+    }
+    data
+}
+
+/// Instruction data for `unshield_sol` / `unshield`
+///
+/// `memo` is logged through the Memo program when non-empty.
+pub fn unshield_data(
+    name: &str,
+    tree_epoch: u32,
+    root: &[u8; 32],
+    nullifier: &[u8; 32],
+    amount: u64,
+    proof: &[u8],
+    memo: &[u8],
+) -> Vec<u8> {
+    let mut data = instruction_discriminator(name).to_vec();
+    data.extend_from_slice(&tree_epoch.to_le_bytes());
+    data.extend_from_slice(root);
+    data.extend_from_slice(nullifier);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+    data.extend_from_slice(proof);
+    data.extend_from_slice(&(memo.len() as u32).to_le_bytes());
+    data.extend_from_slice(memo);
+    data
+}
+
+/// Addresses of a deployed privacy pool
+///
+/// Builds the program's SOL instructions with accounts in the order the
+/// program's `Accounts` structs declare them. PDAs are passed in rather than
+/// derived so this module needs no curve arithmetic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolAccounts {
+    pub program_id: Pubkey,
+    /// SOL pool state PDA (`POOL_SEED`, `NATIVE_MINT`)
+    pub pool: Pubkey,
+    /// SOL vault PDA (`VAULT_SEED`, pool)
+    pub vault: Pubkey,
+    /// Verifying key PDA (`VK_SEED`, pool)
+    pub verifying_key: Pubkey,
+    /// Root history PDA (`ROOT_HISTORY_SEED`, pool)
+    pub root_history: Pubkey,
+    /// Treasury PDA (`TREASURY_SEED`, pool), credited with the protocol's
+    /// share of unshield fees
+    pub treasury: Pubkey,
+}
+
+/// Accounts a spend touches for one nullifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NullifierAccounts {
+    /// Epoch of the tree holding the spent note (the pool's current one
+    /// unless its tree was rotated since)
+    pub tree_epoch: u32,
+    /// Root the proof is against: the live tree's current root or one in
+    /// the pool's root history, or the final root of a rotated-out tree
+    pub root: [u8; 32],
+    /// Marker PDA (`NULLIFIER_SEED`, pool, tree epoch, nullifier), created
+    /// by the spend
+    pub marker: Pubkey,
+    /// Archive bucket PDA (`ARCHIVE_SEED`, pool, tree epoch, `[nullifier[31]]`)
+    pub archive: Pubkey,
+    /// Archived root PDA (`ARCHIVED_ROOT_SEED`, pool, tree epoch); only read
+    /// for a rotated-out tree
+    pub archived_root: Pubkey,
+    /// Pending withdrawal PDA (`PENDING_WITHDRAWAL_SEED`, pool, tree epoch,
+    /// nullifier); only used by unshields from pools with a withdrawal delay
+    pub pending_withdrawal: Pubkey,
+}
+
+impl PoolAccounts {
+    /// Accounts every spend from the pool passes, for an address lookup
+    /// table; the program itself is invoked and always listed in full
+    pub fn lookup_addresses(&self) -> Vec<Pubkey> {
+        vec![
+            self.pool,
+            self.vault,
+            self.verifying_key,
+            self.root_history,
+            self.treasury,
+            SYSTEM_PROGRAM_ID,
+            MEMO_PROGRAM_ID,
+        ]
+    }
+
+    /// `shield_sol`: deposit `amount` lamports from `depositor` under `commitment`
+    ///
+    /// `encrypted_note` (from `crypto::encrypt_note`, or empty) is published
+    /// with the commitment.
+    pub fn shield_sol(
+        &self,
+        depositor: Pubkey,
+        commitment: &[u8; 32],
+        amount: u64,
+        encrypted_note: &[u8],
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.pool, false),
+                AccountMeta::new(self.root_history, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(depositor, true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+            data: shield_data("shield_sol", commitment, amount, encrypted_note),
+        }
+    }
+
+    /// `transfer`: spend `nullifier` into `new_commitment`, paid by `relayer`
+    ///
+    /// `encrypted_note` delivers the output note to its recipient. `fee` is
+    /// paid to the relayer from the SOL vault and must match the proof.
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer(
+        &self,
+        relayer: Pubkey,
+        nullifier_accounts: NullifierAccounts,
+        nullifier: &[u8; 32],
+        new_commitment: &[u8; 32],
+        fee: u64,
+        proof: &[u8],
+        encrypted_note: &[u8],
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.pool, false),
+                AccountMeta::new_readonly(self.verifying_key, false),
+                AccountMeta::new(self.root_history, false),
+                AccountMeta::new(nullifier_accounts.marker, false),
+                AccountMeta::new_readonly(nullifier_accounts.archive, false),
+                AccountMeta::new_readonly(nullifier_accounts.archived_root, false),
+                AccountMeta::new(relayer, true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new(self.vault, false),
+                // No mint, token accounts or token program
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+            data: transfer_data(
+                nullifier_accounts.tree_epoch,
+                &nullifier_accounts.root,
+                nullifier,
+                new_commitment,
+                fee,
+                proof,
+                encrypted_note,
+                &[],
+            ),
+        }
+    }
+
+    /// `transfer` sweeping several notes into `new_commitment`
+    ///
+    /// `inputs` pairs each nullifier with its accounts; all must be from the
+    /// same tree, whose epoch and root are taken from the first. The program
+    /// accepts at most 8 inputs, and the proof must cover all of them.
+    ///
+    /// # Panics
+    ///
+    /// If `inputs` is empty.
+    pub fn transfer_sweep(
+        &self,
+        relayer: Pubkey,
+        inputs: &[(NullifierAccounts, [u8; 32])],
+        new_commitment: &[u8; 32],
+        fee: u64,
+        proof: &[u8],
+        encrypted_note: &[u8],
+    ) -> Instruction {
+        let (first, nullifier) = &inputs[0];
+        let mut ix =
+            self.transfer(relayer, *first, nullifier, new_commitment, fee, proof, encrypted_note);
+        let extras: Vec<[u8; 32]> = inputs[1..].iter().map(|(_, n)| *n).collect();
+        for (accounts, _) in &inputs[1..] {
+            ix.accounts.push(AccountMeta::new(accounts.marker, false));
+            ix.accounts.push(AccountMeta::new_readonly(accounts.archive, false));
+        }
+        ix.data = transfer_data(
+            first.tree_epoch,
+            &first.root,
+            nullifier,
+            new_commitment,
+            fee,
+            proof,
+            encrypted_note,
+            &extras,
+        );
+        ix
+    }
+
+    /// `transfer_joinsplit`: spend two nullifiers into two new commitments
+    ///
+    /// `nullifier_accounts[i]` belongs to `nullifiers[i]`; both inputs must be
+    /// from the same tree, whose epoch and root are taken from the first.
+    pub fn transfer_joinsplit(
+        &self,
+        relayer: Pubkey,
+        nullifier_accounts: [NullifierAccounts; 2],
+        nullifiers: &[[u8; 32]; 2],
+        new_commitments: &[[u8; 32]; 2],
+        proof: &[u8],
+        encrypted_notes: &[&[u8]],
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.pool, false),
+                AccountMeta::new_readonly(self.verifying_key, false),
+                AccountMeta::new(self.root_history, false),
+                AccountMeta::new(nullifier_accounts[0].marker, false),
+                AccountMeta::new(nullifier_accounts[1].marker, false),
+                AccountMeta::new_readonly(nullifier_accounts[0].archive, false),
+                AccountMeta::new_readonly(nullifier_accounts[1].archive, false),
+                AccountMeta::new_readonly(nullifier_accounts[0].archived_root, false),
+                AccountMeta::new(relayer, true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+            data: transfer_joinsplit_data(
+                nullifier_accounts[0].tree_epoch,
+                &nullifier_accounts[0].root,
+                nullifiers,
+                new_commitments,
+                proof,
+                encrypted_notes,
+            ),
+        }
+    }
+
+    /// `unshield_sol`: spend `nullifier` and withdraw `amount` lamports to `recipient`
+    ///
+    /// A non-empty `memo` (UTF-8, such as an exchange deposit reference) is
+    /// logged with the withdrawal.
+    #[allow(clippy::too_many_arguments)]
+    pub fn unshield_sol(
+        &self,
+        relayer: Pubkey,
+        nullifier_accounts: NullifierAccounts,
+        recipient: Pubkey,
+        nullifier: &[u8; 32],
+        amount: u64,
+        proof: &[u8],
+        memo: &[u8],
+    ) -> Instruction {
+        // The program ID stands in for the Memo program when there is no memo
+        let memo_program = if memo.is_empty() { self.program_id } else { MEMO_PROGRAM_ID };
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.pool, false),
+                AccountMeta::new_readonly(self.verifying_key, false),
+                AccountMeta::new_readonly(self.root_history, false),
+                AccountMeta::new(nullifier_accounts.marker, false),
+                AccountMeta::new_readonly(nullifier_accounts.archive, false),
+                AccountMeta::new_readonly(nullifier_accounts.archived_root, false),
+                AccountMeta::new(nullifier_accounts.pending_withdrawal, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.treasury, false),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new(relayer, true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(memo_program, false),
+            ],
+            data: unshield_data(
+                "unshield_sol",
+                nullifier_accounts.tree_epoch,
+                &nullifier_accounts.root,
+                nullifier,
+                amount,
+                proof,
+                memo,
+            ),
+        }
+    }
+}
+
+fn compute_budget_instruction(data: Vec<u8>) -> Instruction {
+    let mut program_id = [0u8; 32];
+    let decoded = bs58::decode(COMPUTE_BUDGET_PROGRAM_ID)
+        .into_vec()
+        .expect("compute budget program id is valid base58");
+    program_id.copy_from_slice(&decoded);
+    Instruction {
+        program_id,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+/// Append a compact-u16 length
+fn encode_length(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        byte |= 0x80;
+        out.push(byte);
+    }
+}
+
+fn short_vec_len(len: usize) -> usize {
+    let mut buf = Vec::new();
+    encode_length(&mut buf, len);
+    buf.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption::ENCRYPTED_NOTE_SIZE;
+
+    const PROGRAM: Pubkey = [1u8; 32];
+    const SYSTEM: Pubkey = [0u8; 32];
+    const TOKEN: Pubkey = [2u8; 32];
+    const POOL: Pubkey = [3u8; 32];
+    const VAULT: Pubkey = [4u8; 32];
+    const PAYER: Pubkey = [5u8; 32];
+    const RECIPIENT: Pubkey = [6u8; 32];
+    const TOKEN_ACCOUNT_A: Pubkey = [7u8; 32];
+    const TOKEN_ACCOUNT_B: Pubkey = [8u8; 32];
+    const VERIFYING_KEY: Pubkey = [10u8; 32];
+    const ARCHIVED_ROOT: Pubkey = [11u8; 32];
+    const PENDING_WITHDRAWAL: Pubkey = [12u8; 32];
+    const TREASURY: Pubkey = [13u8; 32];
+    const ROOT_HISTORY: Pubkey = [14u8; 32];
+
+    fn marker(nullifier: u8) -> Pubkey {
+        [0x40 + nullifier; 32]
+    }
+
+    fn archive(nullifier: u8) -> Pubkey {
+        [0x80 + nullifier; 32]
+    }
+
+    fn nullifier_accounts(nullifier: u8) -> NullifierAccounts {
+        NullifierAccounts {
+            tree_epoch: 0,
+            root: [0x20; 32],
+            marker: marker(nullifier),
+            archive: archive(nullifier),
+            archived_root: ARCHIVED_ROOT,
+            pending_withdrawal: PENDING_WITHDRAWAL,
+        }
+    }
+
+    fn with_budget() -> TransactionAssembler {
+        let mut asm = TransactionAssembler::new(PAYER);
+        asm.set_compute_unit_limit(400_000).set_compute_unit_price(1_000);
+        asm
+    }
+
+    const ACCOUNTS: PoolAccounts = PoolAccounts {
+        program_id: PROGRAM,
+        pool: POOL,
+        vault: VAULT,
+        verifying_key: VERIFYING_KEY,
+        root_history: ROOT_HISTORY,
+        treasury: TREASURY,
+    };
+
+    fn shield_sol_ix() -> Instruction {
+        ACCOUNTS.shield_sol(PAYER, &[9u8; 32], 1_000_000, &[0u8; ENCRYPTED_NOTE_SIZE])
+    }
+
+    fn shield_ix() -> Instruction {
+        Instruction {
+            program_id: PROGRAM,
+            accounts: vec![
+                AccountMeta::new(POOL, false),
+                AccountMeta::new(ROOT_HISTORY, false),
+                AccountMeta::new_readonly(VAULT, false),
+                AccountMeta::new(TOKEN_ACCOUNT_A, false),
+                AccountMeta::new(TOKEN_ACCOUNT_B, false),
+                AccountMeta::new(PAYER, true),
+                AccountMeta::new_readonly(TOKEN, false),
+            ],
+            data: shield_data("shield", &[9u8; 32], 1_000_000, &[0u8; ENCRYPTED_NOTE_SIZE]),
+        }
+    }
+
+    fn transfer_ix(nullifier: u8) -> Instruction {
+        ACCOUNTS.transfer(
+            PAYER,
+            nullifier_accounts(nullifier),
+            &[nullifier; 32],
+            &[9u8; 32],
+            0,
+            &[0u8; PROOF_SIZE],
+            &[0u8; ENCRYPTED_NOTE_SIZE],
+        )
+    }
+
+    fn joinsplit_ix() -> Instruction {
+        let note = [0u8; ENCRYPTED_NOTE_SIZE];
+        ACCOUNTS.transfer_joinsplit(
+            PAYER,
+            [nullifier_accounts(1), nullifier_accounts(2)],
+            &[[1u8; 32], [2u8; 32]],
+            &[[9u8; 32], [10u8; 32]],
+            &[0u8; PROOF_SIZE],
+            &[&note, &note],
+        )
+    }
+
+    fn unshield_sol_ix() -> Instruction {
+        ACCOUNTS.unshield_sol(
+            PAYER,
+            nullifier_accounts(1),
+            RECIPIENT,
+            &[1u8; 32],
+            1_000_000,
+            &[0u8; PROOF_SIZE],
+            &[],
+        )
+    }
+
+    fn unshield_ix() -> Instruction {
+        Instruction {
+            program_id: PROGRAM,
+            accounts: vec![
+                AccountMeta::new(POOL, false),
+                AccountMeta::new_readonly(VERIFYING_KEY, false),
+                AccountMeta::new_readonly(ROOT_HISTORY, false),
+                AccountMeta::new(marker(1), false),
+                AccountMeta::new_readonly(archive(1), false),
+                AccountMeta::new_readonly(ARCHIVED_ROOT, false),
+                AccountMeta::new(PENDING_WITHDRAWAL, false),
+                AccountMeta::new_readonly(VAULT, false),
+                AccountMeta::new(TOKEN_ACCOUNT_A, false),
+                AccountMeta::new(TOKEN_ACCOUNT_B, false),
+                AccountMeta::new_readonly(TREASURY, false),
+                // No treasury token account
+                AccountMeta::new_readonly(PROGRAM, false),
+                AccountMeta::new(PAYER, true),
+                AccountMeta::new_readonly(TOKEN, false),
+                AccountMeta::new_readonly(SYSTEM, false),
+                // No memo
+                AccountMeta::new_readonly(PROGRAM, false),
+            ],
+            data: unshield_data(
+                "unshield",
+                0,
+                &[0x20; 32],
+                &[1u8; 32],
+                1_000_000,
+                &[0u8; PROOF_SIZE],
+                &[],
+            ),
+        }
+    }
+
+    fn size_of(ix: Instruction) -> usize {
+        let mut asm = with_budget();
+        asm.add_instruction(ix);
+        let tx = asm.assemble([0xaa; 32]).unwrap();
+        assert_eq!(tx.serialized_size(), asm.serialized_size().unwrap());
+        tx.serialized_size()
+    }
+
+    #[test]
+    fn test_operation_sizes() {
+        assert_eq!(size_of(shield_sol_ix()), 516);
+        assert_eq!(size_of(shield_ix()), 582);
+        assert_eq!(size_of(transfer_ix(1)), 984);
+        assert_eq!(size_of(joinsplit_ix()), 1182);
+        assert_eq!(size_of(unshield_sol_ix()), 935);
+        assert_eq!(size_of(unshield_ix()), 1002);
+    }
+
+    #[test]
+    fn test_unshield_memo() {
+        let plain = unshield_sol_ix();
+        assert_eq!(plain.accounts.last(), Some(&AccountMeta::new_readonly(PROGRAM, false)));
+        assert_eq!(plain.data[plain.data.len() - 4..], [0u8; 4]);
+
+        let memo = b"deposit-ref 4821";
+        let ix = ACCOUNTS.unshield_sol(
+            PAYER,
+            nullifier_accounts(1),
+            RECIPIENT,
+            &[1u8; 32],
+            1_000_000,
+            &[0u8; PROOF_SIZE],
+            memo,
+        );
+        assert_eq!(ix.accounts.last(), Some(&AccountMeta::new_readonly(MEMO_PROGRAM_ID, false)));
+        assert_eq!(ix.data.len(), plain.data.len() + memo.len());
+        assert!(ix.data.ends_with(memo));
+    }
+
+    #[test]
+    fn test_transfer_sweep() {
+        let inputs: Vec<_> = (1..=3).map(|n| (nullifier_accounts(n), [n; 32])).collect();
+        let ix = ACCOUNTS.transfer_sweep(PAYER, &inputs, &[9u8; 32], 25, &[0u8; 96], &[]);
+
+        // Base transfer accounts, then a (marker, archive) pair per extra input
+        assert_eq!(ix.accounts.len(), 13 + 4);
+        // The first input's tree epoch and root, and the fee
+        assert_eq!(&ix.data[8..12], &0u32.to_le_bytes());
+        assert_eq!(&ix.data[12..44], &[0x20; 32]);
+        assert_eq!(&ix.data[108..116], &25u64.to_le_bytes());
+        assert_eq!(ix.accounts[8], AccountMeta::new(VAULT, false));
+        assert_eq!(ix.accounts[13], AccountMeta::new(marker(2), false));
+        assert_eq!(ix.accounts[14], AccountMeta::new_readonly(archive(2), false));
+        assert_eq!(ix.accounts[16], AccountMeta::new_readonly(archive(3), false));
+
+        let tail = &ix.data[ix.data.len() - 4 - 64..];
+        assert_eq!(&tail[..4], &2u32.to_le_bytes());
+        assert_eq!(&tail[4..36], &[2u8; 32]);
+        assert_eq!(&tail[36..], &[3u8; 32]);
+    }
+
+    #[test]
+    fn test_system_transfer() {
+        let ix = Instruction::system_transfer(PAYER, [7; 32], 10_000);
+        assert_eq!(ix.data, [2, 0, 0, 0, 0x10, 0x27, 0, 0, 0, 0, 0, 0]);
+        assert!(ix.accounts[0].is_signer && ix.accounts[0].is_writable);
+        assert!(!ix.accounts[1].is_signer && ix.accounts[1].is_writable);
+    }
+
+    #[test]
+    fn test_message_layout() {
+        let mut asm = TransactionAssembler::new(PAYER);
+        asm.add_instruction(shield_sol_ix());
+        let tx = asm.assemble([0xaa; 32]).unwrap();
+
+        // Header: 1 signer, 0 readonly signed, 2 readonly unsigned (system, program)
+        assert_eq!(&tx.message[..3], &[1, 0, 2]);
+        // Payer is the first account key
+        assert_eq!(tx.message[3], 6);
+        assert_eq!(&tx.message[4..36], &PAYER);
+        assert_eq!(tx.num_signatures, 1);
+    }
+
+    #[test]
+    fn test_lookup_tables() {
+        let table = AddressLookupTable::new([0x30; 32], ACCOUNTS.lookup_addresses());
+        let payer_table = AddressLookupTable::new([0x31; 32], vec![PAYER]);
+        let mut asm = with_budget().with_lookup_tables(vec![table, payer_table]);
+        asm.add_instruction(joinsplit_ix());
+        let tx = asm.assemble([0xaa; 32]).unwrap();
+        assert_eq!(tx.serialized_size(), 1094);
+        assert!(tx.serialized_size() < size_of(joinsplit_ix()));
+
+        // Versioned; the payer signs, so stays listed in full
+        assert_eq!(&tx.message[..4], &[MESSAGE_VERSION_PREFIX, 1, 0, 5]);
+        assert_eq!(&tx.message[5..37], &PAYER);
+        // One lookup: pool and root history writable, verifying key and
+        // system program readonly
+        let lookups = [&[1][..], &[0x30; 32], &[2, 0, 3], &[2, 2, 5]].concat();
+        assert!(tx.message.ends_with(&lookups));
+    }
+
+    #[test]
+    fn test_oversized_batch_rejected() {
+        let mut asm = with_budget();
+        for nullifier in 1..=3 {
+            asm.add_instruction(transfer_ix(nullifier));
+        }
+
+        let size = asm.serialized_size().unwrap();
+        assert_eq!(size, 2132);
+        match asm.assemble([0xaa; 32]) {
+            Err(VeilError::TransactionTooLarge { size: got, limit }) => {
+                assert_eq!(got, size);
+                assert_eq!(limit, PACKET_DATA_SIZE);
+                assert!(got > limit);
+            }
+            other => panic!("expected TransactionTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_custom_size_limit() {
+        let mut asm = TransactionAssembler::new(PAYER).with_size_limit(256);
+        asm.add_instruction(shield_sol_ix());
+        let err = asm.assemble([0u8; 32]).unwrap_err();
+        assert!(err.to_string().contains("exceeds the 256 byte limit by 208 bytes"));
+    }
+
+    #[test]
+    fn test_telemetry_counts_outcomes() {
+        use crate::telemetry::{TelemetryConfig, NoopSink};
+
+        let telemetry = Arc::new(Telemetry::new(
+            TelemetryConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            NoopSink,
+        ));
+        let mut asm = TransactionAssembler::new(PAYER)
+            .with_size_limit(512)
+            .with_telemetry(telemetry.clone());
+        asm.add_instruction(shield_sol_ix());
+        assert!(asm.assemble([0u8; 32]).is_ok());
+        asm.add_instruction(transfer_ix(1));
+        assert!(asm.assemble([0u8; 32]).is_err());
+
+        let report = telemetry.snapshot();
+        assert_eq!(report.transactions_assembled, 1);
+        assert_eq!(report.transactions_too_large, 1);
+    }
+
+    #[test]
+    fn test_short_vec_encoding() {
+        let mut buf = Vec::new();
+        encode_length(&mut buf, 0x7f);
+        encode_length(&mut buf, 0x80);
+        encode_length(&mut buf, 0x3fff);
+        assert_eq!(buf, vec![0x7f, 0x80, 0x01, 0xff, 0x7f]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Field element encodings
+
+use alloc::vec::Vec;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+
+/// Encode a field element as the 32-byte big-endian value groth16-solana
+/// expects for public inputs (and the program stores for commitments)
+pub fn field_to_bytes_be(value: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&value.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// Decode a 32-byte big-endian field element, or `None` if it is not
+/// canonical (at least the modulus)
+pub fn field_from_bytes_be(bytes: &[u8; 32]) -> Option<Fr> {
+    let value = Fr::from_be_bytes_mod_order(bytes);
+    (field_to_bytes_be(&value) == *bytes).then_some(value)
+}
+
+/// Public inputs in the program's encoding
+pub fn encode_public_inputs(inputs: &[Fr]) -> Vec<[u8; 32]> {
+    inputs.iter().map(field_to_bytes_be).collect()
+}
+
+/// Public inputs from the program's encoding, or `None` if any is not
+/// canonical
+pub fn decode_public_inputs(bytes: &[[u8; 32]]) -> Option<Vec<Fr>> {
+    bytes.iter().map(field_from_bytes_be).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_field_bytes_roundtrip() {
+        let value = Fr::from(0x0102_0304u64);
+        let bytes = field_to_bytes_be(&value);
+        assert_eq!(bytes[28..], [1, 2, 3, 4]);
+        assert_eq!(field_from_bytes_be(&bytes), Some(value));
+
+        // -1 is the largest canonical value; the modulus itself is not
+        let max = field_to_bytes_be(&-Fr::from(1u64));
+        assert_eq!(field_from_bytes_be(&max), Some(-Fr::from(1u64)));
+        let mut modulus = max;
+        modulus[31] += 1;
+        assert_eq!(field_from_bytes_be(&modulus), None);
+        assert_eq!(field_from_bytes_be(&[0xff; 32]), None);
+    }
+
+    #[test]
+    fn test_public_inputs_roundtrip() {
+        let inputs = vec![Fr::from(1u64), Fr::from(2u64), -Fr::from(3u64)];
+        let bytes = encode_public_inputs(&inputs);
+        assert_eq!(decode_public_inputs(&bytes), Some(inputs));
+
+        let mut bad = bytes;
+        bad[1] = [0xff; 32];
+        assert_eq!(decode_public_inputs(&bad), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Merkle path verification over the Poseidon tree
+
+use ark_bn254::Fr;
+
+use super::poseidon::hash2;
+
+/// Merkle tree depth (20 levels = 2^20 = ~1 million leaves)
+pub const TREE_DEPTH: usize = 20;
+
+/// Hash `leaf` up to the root through `siblings`, leaf level first;
+/// `indices` says whether each node is a right child
+pub fn compute_root(leaf: &Fr, siblings: &[Fr], indices: impl IntoIterator<Item = bool>) -> Fr {
+    siblings
+        .iter()
+        .zip(indices)
+        .fold(*leaf, |current, (sibling, is_right)| {
+            if is_right {
+                hash2(sibling, &current)
+            } else {
+                hash2(&current, sibling)
+            }
+        })
+}
+
+/// Whether `siblings` (`TREE_DEPTH` of them) lead from the leaf at
+/// `leaf_index` to `root`
+pub fn verify_path(leaf: &Fr, leaf_index: u64, siblings: &[Fr], root: &Fr) -> bool {
+    if siblings.len() != TREE_DEPTH {
+        return false;
+    }
+
+    let indices = (0..TREE_DEPTH).map(|level| (leaf_index >> level) & 1 == 1);
+    compute_root(leaf, siblings, indices) == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_path() {
+        // The rightmost leaf of an otherwise empty tree
+        let leaf = Fr::from(7u64);
+        let index = (1u64 << TREE_DEPTH) - 1;
+        let mut zeros = [Fr::from(0u64); TREE_DEPTH];
+        let mut root = leaf;
+        for level in 0..TREE_DEPTH {
+            if level > 0 {
+                zeros[level] = hash2(&zeros[level - 1], &zeros[level - 1]);
+            }
+            root = hash2(&zeros[level], &root);
+        }
+
+        assert!(verify_path(&leaf, index, &zeros, &root));
+        assert!(!verify_path(&leaf, index - 1, &zeros, &root));
+        assert!(!verify_path(&Fr::from(8u64), index, &zeros, &root));
+        assert!(!verify_path(&leaf, index, &zeros[1..], &root));
+    }
+}
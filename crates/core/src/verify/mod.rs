@@ -0,0 +1,22 @@
+//! Verification core without std
+//!
+//! The parts of proof and Merkle verification that embedded and SVM-adjacent
+//! consumers need, written against `core` and `alloc` only so they build
+//! with `--no-default-features`. The std modules (`crypto`, `proof`) use
+//! these rather than their own copies, so both agree by construction.
+//!
+//! Components:
+//! - `encoding`: Field elements and public inputs as 32-byte big-endian
+//!   values, as the program and groth16-solana take them
+//! - `poseidon`: The Poseidon permutation over circomlib's constants
+//! - `merkle`: Merkle path verification
+
+pub mod encoding;
+pub mod merkle;
+pub mod poseidon;
+
+pub use encoding::{
+    decode_public_inputs, encode_public_inputs, field_from_bytes_be, field_to_bytes_be,
+};
+pub use merkle::{compute_root, verify_path, TREE_DEPTH};
+pub use poseidon::hash2;
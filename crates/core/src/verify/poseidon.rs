@@ -0,0 +1,119 @@
+//! Poseidon permutation (t = 3, 4 or 5, x^5 S-box)
+//!
+//! `hash2`, `hash3` and `hash4` use circomlib's constants from
+//! `crypto::poseidon_constants`; `crypto::poseidon::Poseidon` runs `permute`
+//! with its configured ones.
+
+use ark_bn254::Fr;
+use ark_ff::Field;
+
+use crate::crypto::poseidon_constants::{
+    t4, t5, FULL_ROUNDS, MDS_MATRIX, PARTIAL_ROUNDS, ROUND_CONSTANTS,
+};
+
+/// Hash two field elements, as circomlib's `Poseidon(2)([a, b])`
+pub fn hash2(a: &Fr, b: &Fr) -> Fr {
+    let mut state = [Fr::from(0u64), *a, *b];
+    permute(
+        &mut state,
+        FULL_ROUNDS,
+        PARTIAL_ROUNDS,
+        &ROUND_CONSTANTS,
+        &MDS_MATRIX,
+    );
+    state[0]
+}
+
+/// Hash three field elements, as circomlib's `Poseidon(3)([a, b, c])`
+pub fn hash3(a: &Fr, b: &Fr, c: &Fr) -> Fr {
+    let mut state = [Fr::from(0u64), *a, *b, *c];
+    permute(
+        &mut state,
+        t4::FULL_ROUNDS,
+        t4::PARTIAL_ROUNDS,
+        &t4::ROUND_CONSTANTS,
+        &t4::MDS_MATRIX,
+    );
+    state[0]
+}
+
+/// Hash four field elements, as circomlib's `Poseidon(4)([a, b, c, d])`
+pub fn hash4(a: &Fr, b: &Fr, c: &Fr, d: &Fr) -> Fr {
+    let mut state = [Fr::from(0u64), *a, *b, *c, *d];
+    permute(
+        &mut state,
+        t5::FULL_ROUNDS,
+        t5::PARTIAL_ROUNDS,
+        &t5::ROUND_CONSTANTS,
+        &t5::MDS_MATRIX,
+    );
+    state[0]
+}
+
+/// Apply the permutation: `full_rounds / 2` full rounds, `partial_rounds`
+/// partial rounds, then the other full rounds, taking `T` round constants
+/// per round
+pub fn permute<const T: usize>(
+    state: &mut [Fr; T],
+    full_rounds: usize,
+    partial_rounds: usize,
+    round_constants: &[Fr],
+    mds: &[[Fr; T]; T],
+) {
+    let half_full = full_rounds / 2;
+    let rounds = round_constants
+        .chunks_exact(T)
+        .take(full_rounds + partial_rounds);
+    for (round, constants) in rounds.enumerate() {
+        for (elem, constant) in state.iter_mut().zip(constants) {
+            *elem += constant;
+        }
+
+        // Full rounds apply the S-box to every element, partial rounds to
+        // the first only
+        if round < half_full || round >= half_full + partial_rounds {
+            for elem in state.iter_mut() {
+                *elem = sbox(*elem);
+            }
+        } else {
+            state[0] = sbox(state[0]);
+        }
+
+        let mut mixed = [Fr::from(0u64); T];
+        for (out, row) in mixed.iter_mut().zip(mds) {
+            for (coeff, elem) in row.iter().zip(state.iter()) {
+                *out += *coeff * elem;
+            }
+        }
+        *state = mixed;
+    }
+}
+
+/// S-box function: x^5
+#[inline]
+fn sbox(x: Fr) -> Fr {
+    let x2 = x.square();
+    let x4 = x2.square();
+    x4 * x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::MontFp;
+
+    #[test]
+    fn test_circomlib_vector() {
+        let expected: Fr =
+            MontFp!("7853200120776062878684798364095072458815029376092732009249414926327459813530");
+        assert_eq!(hash2(&Fr::from(1u64), &Fr::from(2u64)), expected);
+    }
+
+    #[test]
+    fn test_sbox() {
+        let x = Fr::from(2u64);
+        let result = sbox(x);
+        let expected = Fr::from(32u64); // 2^5 = 32
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,548 @@
+//! Wallet State
+//!
+//! `NoteStore` is the client's record of the notes it owns and what happened
+//! to them:
+//! - Unspent: found by the scanner and spendable
+//! - Pending: selected for a transaction that has not confirmed yet, so coin
+//!   selection must not pick it again
+//! - Spent: its nullifier has appeared on chain
+//!
+//! Notes are keyed by leaf index, so re-adding a note found by a replayed
+//! scan is a no-op. Each note's nullifier is computed on insert; feeding the
+//! store every nullifier the pool publishes marks ours spent, whoever spent
+//! them (including another device restored from the same seed).
+//!
+//! Each note is tagged with the `PoolEpoch` it was received in, which
+//! `WitnessBuilder::for_stored` checks before proving.
+//!
+//! `restore` rebuilds a store from the pool's published notes with the
+//! checkpointed scanner, so an interrupted recovery resumes where it left off.
+//!
+//! The store is saved as a single ChaCha20-Poly1305 encrypted file:
+//! magic || version || nonce || ciphertext of the JSON state. The file key is
+//! derived from the owner's secret with `NoteStore::file_key`, and files are
+//! replaced atomically.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use ark_bn254::Fr;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::encryption::{EncryptionKeypair, NoteCiphertext};
+use crate::crypto::nullifier::{Note, Nullifier, ViewingKey};
+use crate::epoch::PoolEpoch;
+use crate::scanner::{owned_note, scan_stream, ScanCheckpoint, ScanConfig, ScanError, ScanStats};
+
+/// Leading bytes of a wallet file
+const FILE_MAGIC: &[u8; 4] = b"VWLT";
+
+/// Version of the wallet file format
+const FILE_VERSION: u8 = 1;
+
+/// Size of the file nonce
+const NONCE_SIZE: usize = 12;
+
+/// BLAKE3 context for the file key
+const FILE_KEY_CONTEXT: &str = "veil wallet file key v1";
+
+/// Errors from the note store
+#[derive(Error, Debug)]
+pub enum WalletError {
+    #[error("Note has no leaf index")]
+    MissingLeafIndex,
+    #[error("No note at leaf {0}")]
+    UnknownNote(u64),
+    #[error("Note at leaf {0} is already spent")]
+    AlreadySpent(u64),
+    #[error("Wallet file I/O error: {0}")]
+    Io(String),
+    #[error("Wallet file serialization error: {0}")]
+    Serialization(String),
+    #[error("Wallet file could not be decrypted (wrong key or corrupted)")]
+    Decryption,
+}
+
+/// What has happened to a note
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteStatus {
+    /// Spendable
+    Unspent,
+    /// Used by a transaction that has not confirmed
+    Pending,
+    /// Nullifier seen on chain
+    Spent,
+}
+
+/// A note with its nullifier and status
+#[derive(Clone, Debug)]
+pub struct StoredNote {
+    /// The note, with its leaf index set
+    pub note: Note,
+    /// Nullifier that spending the note publishes
+    pub nullifier: Nullifier,
+    /// Current status
+    pub status: NoteStatus,
+    /// Pool parameters the note was received under
+    pub epoch: PoolEpoch,
+}
+
+/// The client's notes, by leaf index
+#[derive(Clone, Debug, Default)]
+pub struct NoteStore {
+    notes: BTreeMap<u64, StoredNote>,
+    /// Nullifier bytes to leaf index
+    nullifiers: BTreeMap<[u8; 32], u64>,
+}
+
+/// On-disk form of one note
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    /// Hex of `Note::to_bytes`
+    note: String,
+    status: NoteStatus,
+    /// Absent from files written before notes were tagged, all of which
+    /// hold notes of the first tree
+    #[serde(default = "first_epoch")]
+    epoch: PoolEpoch,
+}
+
+fn first_epoch() -> PoolEpoch {
+    PoolEpoch::current(0)
+}
+
+impl Drop for FileEntry {
+    fn drop(&mut self) {
+        self.note.zeroize();
+    }
+}
+
+impl NoteStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an owned note (e.g. from `scanner::scan_owned`), received in
+    /// `epoch`, as unspent
+    ///
+    /// Returns false if a note at the same leaf index is already stored.
+    pub fn insert(&mut self, note: Note, epoch: PoolEpoch) -> Result<bool, WalletError> {
+        let leaf_index = note.leaf_index.ok_or(WalletError::MissingLeafIndex)?;
+        if self.notes.contains_key(&leaf_index) {
+            return Ok(false);
+        }
+        self.insert_with_status(note, leaf_index, NoteStatus::Unspent, epoch);
+        Ok(true)
+    }
+
+    /// Add the notes owned by `secret` from a stream of published notes
+    ///
+    /// # Arguments
+    /// * `source` - Iterator of (leaf_index, commitment, encrypted note) in
+    ///   leaf order, starting at `checkpoint.resume_from`
+    /// * `secret` - Owner's note secret
+    /// * `epoch` - Epoch the stream's notes were received in
+    /// * `checkpoint` - Resume cursor, advanced chunk by chunk
+    /// * `config` - Chunking configuration
+    /// * `commit` - Called after each chunk with the store and checkpoint
+    ///   covering it, to persist both; an error stops the restore without
+    ///   advancing `checkpoint`
+    ///
+    /// Notes found in a chunk are in the store before its commit, so after a
+    /// failed commit the store may be ahead of `checkpoint`. Inserts are
+    /// idempotent, so resuming from either is safe.
+    pub fn restore<I, F>(
+        &mut self,
+        source: I,
+        secret: &[u8; 32],
+        epoch: PoolEpoch,
+        checkpoint: &mut ScanCheckpoint,
+        config: &ScanConfig,
+        mut commit: F,
+    ) -> Result<ScanStats, ScanError>
+    where
+        I: IntoIterator<Item = (u64, Fr, NoteCiphertext)>,
+        F: FnMut(&NoteStore, &ScanCheckpoint) -> Result<(), ScanError>,
+    {
+        let keys = [EncryptionKeypair::from_viewing_key(&ViewingKey::from_secret(secret))];
+        // Commitments of the chunk being scanned, which `scan_stream` pulls
+        // whole before committing it
+        let commitments = RefCell::new(BTreeMap::new());
+        let stream = source.into_iter().map(|(leaf_index, commitment, ciphertext)| {
+            commitments.borrow_mut().insert(leaf_index, commitment);
+            (leaf_index, ciphertext)
+        });
+
+        scan_stream(stream, &keys, checkpoint, config, |batch| {
+            let chunk = std::mem::take(&mut *commitments.borrow_mut());
+            for scanned in &batch.notes {
+                let owned = chunk
+                    .get(&scanned.leaf_index)
+                    .and_then(|c| owned_note(secret, scanned.leaf_index, c, &scanned.data));
+                if let Some(note) = owned {
+                    self.insert(note, epoch)
+                        .map_err(|e| ScanError::Interrupted(e.to_string()))?;
+                }
+            }
+            commit(self, batch.checkpoint)
+        })
+    }
+
+    fn insert_with_status(
+        &mut self,
+        note: Note,
+        leaf_index: u64,
+        status: NoteStatus,
+        epoch: PoolEpoch,
+    ) {
+        let nullifier = note.nullifier();
+        self.nullifiers.insert(nullifier.to_bytes(), leaf_index);
+        self.notes.insert(leaf_index, StoredNote { note, nullifier, status, epoch });
+    }
+
+    /// The note at a leaf index
+    pub fn get(&self, leaf_index: u64) -> Option<&StoredNote> {
+        self.notes.get(&leaf_index)
+    }
+
+    /// All stored notes, in leaf order
+    pub fn notes(&self) -> impl Iterator<Item = &StoredNote> {
+        self.notes.values()
+    }
+
+    /// Number of stored notes
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Whether the store holds no notes
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Reserve a note for a transaction being built
+    pub fn mark_pending(&mut self, leaf_index: u64) -> Result<(), WalletError> {
+        self.set_unspent_status(leaf_index, NoteStatus::Unspent, NoteStatus::Pending)
+    }
+
+    /// Release a pending note whose transaction failed or expired
+    pub fn release_pending(&mut self, leaf_index: u64) -> Result<(), WalletError> {
+        self.set_unspent_status(leaf_index, NoteStatus::Pending, NoteStatus::Unspent)
+    }
+
+    fn set_unspent_status(
+        &mut self,
+        leaf_index: u64,
+        from: NoteStatus,
+        to: NoteStatus,
+    ) -> Result<(), WalletError> {
+        let stored = self.notes.get_mut(&leaf_index).ok_or(WalletError::UnknownNote(leaf_index))?;
+        match stored.status {
+            NoteStatus::Spent => Err(WalletError::AlreadySpent(leaf_index)),
+            status if status == from => {
+                stored.status = to;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a nullifier published on chain
+    ///
+    /// Returns the leaf index of our note it spends, if any.
+    pub fn mark_spent(&mut self, nullifier: &Nullifier) -> Option<u64> {
+        let leaf_index = *self.nullifiers.get(&nullifier.to_bytes())?;
+        let stored = self.notes.get_mut(&leaf_index)?;
+        stored.status = NoteStatus::Spent;
+        Some(leaf_index)
+    }
+
+    /// Record a batch of on-chain nullifiers; returns how many were ours
+    pub fn apply_nullifiers<'a, I>(&mut self, nullifiers: I) -> usize
+    where
+        I: IntoIterator<Item = &'a Nullifier>,
+    {
+        nullifiers.into_iter().filter_map(|n| self.mark_spent(n)).count()
+    }
+
+    /// Unspent notes of an asset, in leaf order, for coin selection
+    pub fn unspent(&self, asset_id: Fr) -> impl Iterator<Item = &Note> {
+        self.with_status(asset_id, NoteStatus::Unspent)
+    }
+
+    fn with_status(&self, asset_id: Fr, status: NoteStatus) -> impl Iterator<Item = &Note> {
+        self.notes
+            .values()
+            .filter(move |s| s.status == status && s.note.asset_id == asset_id)
+            .map(|s| &s.note)
+    }
+
+    /// Spendable balance of an asset: the sum of its unspent notes
+    pub fn balance(&self, asset_id: Fr) -> u64 {
+        self.unspent(asset_id).fold(0u64, |sum, n| sum.saturating_add(n.amount))
+    }
+
+    /// Balance of an asset locked in pending transactions
+    pub fn pending_balance(&self, asset_id: Fr) -> u64 {
+        self.with_status(asset_id, NoteStatus::Pending)
+            .fold(0u64, |sum, n| sum.saturating_add(n.amount))
+    }
+
+    /// Key for the wallet file of the owner of `secret`
+    pub fn file_key(secret: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(blake3::derive_key(FILE_KEY_CONTEXT, secret))
+    }
+
+    /// Serialize and encrypt under `key`
+    pub fn to_encrypted_bytes(&self, key: &[u8; 32]) -> Result<Vec<u8>, WalletError> {
+        let entries: Vec<FileEntry> = self
+            .notes
+            .values()
+            .map(|s| FileEntry {
+                note: hex::encode(s.note.to_bytes().as_slice()),
+                status: s.status,
+                epoch: s.epoch,
+            })
+            .collect();
+        let json = Zeroizing::new(
+            serde_json::to_vec(&entries).map_err(|e| WalletError::Serialization(e.to_string()))?,
+        );
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = ChaCha20Poly1305::new(key.into())
+            .encrypt(&nonce.into(), json.as_slice())
+            .map_err(|_| WalletError::Serialization("encryption failed".to_string()))?;
+
+        let mut bytes = Vec::with_capacity(FILE_MAGIC.len() + 1 + NONCE_SIZE + ciphertext.len());
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.push(FILE_VERSION);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        Ok(bytes)
+    }
+
+    /// Decrypt and deserialize bytes written by `to_encrypted_bytes`
+    pub fn from_encrypted_bytes(bytes: &[u8], key: &[u8; 32]) -> Result<Self, WalletError> {
+        let header = FILE_MAGIC.len() + 1;
+        if bytes.len() < header + NONCE_SIZE || &bytes[..FILE_MAGIC.len()] != FILE_MAGIC {
+            return Err(WalletError::Serialization("not a wallet file".to_string()));
+        }
+        if bytes[FILE_MAGIC.len()] != FILE_VERSION {
+            return Err(WalletError::Serialization(format!(
+                "unsupported wallet file version {}",
+                bytes[FILE_MAGIC.len()]
+            )));
+        }
+        let (nonce, ciphertext) = bytes[header..].split_at(NONCE_SIZE);
+        let json = ChaCha20Poly1305::new(key.into())
+            .decrypt(nonce.into(), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| WalletError::Decryption)?;
+        let entries: Vec<FileEntry> = serde_json::from_slice(&json)
+            .map_err(|e| WalletError::Serialization(e.to_string()))?;
+
+        let mut store = Self::new();
+        for entry in entries {
+            let note_bytes = Zeroizing::new(
+                hex::decode(&entry.note).map_err(|e| WalletError::Serialization(e.to_string()))?,
+            );
+            let note = Note::from_bytes(&note_bytes)
+                .ok_or_else(|| WalletError::Serialization("invalid note".to_string()))?;
+            let leaf_index = note.leaf_index.ok_or(WalletError::MissingLeafIndex)?;
+            store.insert_with_status(note, leaf_index, entry.status, entry.epoch);
+        }
+        Ok(store)
+    }
+
+    /// Load a wallet file, or start empty if it doesn't exist
+    pub fn load(path: &Path, key: &[u8; 32]) -> Result<Self, WalletError> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let bytes = std::fs::read(path).map_err(|e| WalletError::Io(e.to_string()))?;
+        Self::from_encrypted_bytes(&bytes, key)
+    }
+
+    /// Encrypt and write the wallet file
+    ///
+    /// Writes to a temporary file and renames it, so a crash mid-write
+    /// leaves the previous file intact.
+    pub fn save(&self, path: &Path, key: &[u8; 32]) -> Result<(), WalletError> {
+        let bytes = self.to_encrypted_bytes(key)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes).map_err(|e| WalletError::Io(e.to_string()))?;
+        std::fs::rename(&tmp, path).map_err(|e| WalletError::Io(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{BigInteger, PrimeField};
+
+    use crate::crypto::address::DiversifiedAddress;
+    use crate::crypto::encryption::{encrypt_note, encrypt_note_to_address, NoteData};
+
+    const SECRET: [u8; 32] = [6u8; 32];
+
+    fn note(leaf_index: u64, amount: u64, asset: u64) -> Note {
+        let mut note = Note::new(SECRET, amount, Fr::from(asset), Fr::from(leaf_index + 1));
+        note.set_leaf_index(leaf_index);
+        note
+    }
+
+    fn store() -> NoteStore {
+        let mut store = NoteStore::new();
+        for (i, amount) in [100u64, 250, 40].into_iter().enumerate() {
+            store.insert(note(i as u64, amount, 0), PoolEpoch::current(0)).unwrap();
+        }
+        store.insert(note(3, 7, 9), PoolEpoch::current(1)).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_balances_follow_status() {
+        let mut store = store();
+        assert_eq!(store.balance(Fr::from(0u64)), 390);
+        assert_eq!(store.balance(Fr::from(9u64)), 7);
+
+        // Re-adding a scanned note changes nothing
+        assert!(!store.insert(note(1, 250, 0), PoolEpoch::current(0)).unwrap());
+        assert!(matches!(
+            store.insert(Note::new(SECRET, 1, Fr::from(0u64), Fr::from(1u64)), first_epoch()),
+            Err(WalletError::MissingLeafIndex)
+        ));
+
+        store.mark_pending(1).unwrap();
+        assert_eq!(store.balance(Fr::from(0u64)), 140);
+        assert_eq!(store.pending_balance(Fr::from(0u64)), 250);
+        store.release_pending(1).unwrap();
+        assert_eq!(store.balance(Fr::from(0u64)), 390);
+
+        // Only our nullifiers count
+        let spent = [note(0, 100, 0).nullifier(), Nullifier::from_secret(&[1u8; 32], 0)];
+        assert_eq!(store.apply_nullifiers(&spent), 1);
+        assert_eq!(store.get(0).unwrap().status, NoteStatus::Spent);
+        assert_eq!(store.balance(Fr::from(0u64)), 290);
+        assert!(matches!(store.mark_pending(0), Err(WalletError::AlreadySpent(0))));
+        assert!(matches!(store.mark_pending(8), Err(WalletError::UnknownNote(8))));
+
+        let unspent: Vec<u64> =
+            store.unspent(Fr::from(0u64)).filter_map(|n| n.leaf_index).collect();
+        assert_eq!(unspent, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_encrypted_file_roundtrip() {
+        let mut store = store();
+        store.mark_pending(2).unwrap();
+        store.mark_spent(&note(3, 7, 9).nullifier());
+
+        let key = NoteStore::file_key(&SECRET);
+        let path = std::env::temp_dir().join(format!("veil_wallet_{}.bin", std::process::id()));
+        store.save(&path, &key).unwrap();
+        let loaded = NoteStore::load(&path, &key).unwrap();
+        let wrong = NoteStore::load(&path, &NoteStore::file_key(&[7u8; 32]));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(wrong, Err(WalletError::Decryption)));
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded.get(2).unwrap().status, NoteStatus::Pending);
+        assert_eq!(loaded.get(3).unwrap().status, NoteStatus::Spent);
+        assert_eq!(loaded.get(3).unwrap().epoch, PoolEpoch::current(1));
+        assert_eq!(loaded.balance(Fr::from(0u64)), 350);
+        let restored = &loaded.get(1).unwrap().note;
+        assert_eq!(restored.commitment(), store.get(1).unwrap().note.commitment());
+
+        // Spent tracking survives the round trip
+        let mut loaded = loaded;
+        assert_eq!(loaded.mark_spent(&note(1, 250, 0).nullifier()), Some(1));
+        assert!(NoteStore::load(&path, &key).unwrap().is_empty());
+
+        // Entries from before epochs were stored are of the first tree
+        let legacy: FileEntry = serde_json::from_str(r#"{"note":"","status":"Unspent"}"#).unwrap();
+        assert_eq!(legacy.epoch, PoolEpoch::current(0));
+    }
+
+    /// Published notes where every fourth is ours and the rest a stranger's
+    fn published(n: u64) -> Vec<(u64, Fr, NoteCiphertext)> {
+        let address = DiversifiedAddress::derive(&ViewingKey::from_secret(&SECRET), 0);
+        let stranger = EncryptionKeypair::generate();
+        (0..n)
+            .map(|i| {
+                let note = note(i, 10 + i, 0);
+                let mut blinding = [0u8; 32];
+                blinding.copy_from_slice(&note.blinding.into_bigint().to_bytes_le());
+                let data = NoteData::new(note.amount, blinding, 0);
+                let encrypted = if i % 4 == 0 {
+                    encrypt_note_to_address(&data, &address)
+                } else {
+                    encrypt_note(&data, &stranger.public_key_bytes())
+                };
+                (i, note.commitment(), encrypted.unwrap().into())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_restore_resumes_from_checkpoint() {
+        let stream = published(40);
+        let epoch = PoolEpoch::current(2);
+        let config = ScanConfig { chunk_size: 6 };
+
+        // The first run is interrupted while committing its third chunk,
+        // after persisting the first two
+        let mut store = NoteStore::new();
+        let mut checkpoint = ScanCheckpoint::new();
+        let mut saved = (NoteStore::new(), ScanCheckpoint::new());
+        let interrupted =
+            store.restore(stream.clone(), &SECRET, epoch, &mut checkpoint, &config, |s, c| {
+                if s.len() > 3 {
+                    return Err(ScanError::Interrupted("simulated crash".to_string()));
+                }
+                saved = (s.clone(), c.clone());
+                Ok(())
+            });
+        assert!(interrupted.is_err());
+        let (mut store, mut checkpoint) = saved;
+        assert_eq!(store.len(), 3);
+
+        // The second run only reads what the checkpoint has not covered
+        let keys = [EncryptionKeypair::from_viewing_key(&ViewingKey::from_secret(&SECRET))];
+        let resume = checkpoint.resume_from(&keys);
+        assert_eq!(resume, 12);
+        let stats = store
+            .restore(
+                stream.iter().skip(resume as usize).cloned(),
+                &SECRET,
+                epoch,
+                &mut checkpoint,
+                &config,
+                |_, _| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(stats.notes_read, 40 - resume);
+
+        let leaves: Vec<u64> = store.notes().filter_map(|s| s.note.leaf_index).collect();
+        assert_eq!(leaves, (0..40).step_by(4).collect::<Vec<_>>());
+        assert!(store.notes().all(|s| s.epoch == epoch && s.status == NoteStatus::Unspent));
+        let nullifier = &store.get(8).unwrap().nullifier;
+        assert_eq!(nullifier.to_bytes(), note(8, 18, 0).nullifier().to_bytes());
+
+        // Replaying the whole stream adds nothing
+        let stats = store
+            .restore(stream, &SECRET, epoch, &mut checkpoint, &config, |_, _| Ok(()))
+            .unwrap();
+        assert_eq!(stats.decryptions, 0);
+        assert_eq!(store.len(), 10);
+    }
+}
@@ -0,0 +1,104 @@
+//! Browser Bindings
+//!
+//! wasm-bindgen wrappers over note handling and transfer proving, enabled
+//! by the `wasm` feature. Browsers have no filesystem for `KeyStore`, so
+//! keys are passed in as bytes; randomness comes from
+//! `crypto.getRandomValues` through getrandom.
+//!
+//! Notes and Merkle paths use the `Note::to_bytes` and `MerklePath::to_bytes`
+//! encodings; values for the program are 32-byte big-endian field elements.
+
+use ark_bn254::Fr;
+use ark_ff::{PrimeField, UniformRand};
+use rand::rngs::OsRng;
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::merkle::MerklePath;
+use crate::crypto::nullifier::Note;
+use crate::proof::{asset_id_for_mint, field_to_bytes_be, TransferProofSystem, WitnessBuilder};
+
+/// A note holding `amount` of `mint`'s pool asset, with a random secret
+/// and blinding
+#[wasm_bindgen(js_name = newNote)]
+pub fn new_note(amount: u64, mint: &[u8]) -> Result<Vec<u8>, JsError> {
+    let mint: &[u8; 32] = mint.try_into().map_err(|_| JsError::new("Mint must be 32 bytes"))?;
+    Ok(Note::new_random(amount, asset_id_for_mint(mint), Fr::rand(&mut OsRng)).to_bytes().to_vec())
+}
+
+/// Record the leaf index a note's commitment was inserted at
+#[wasm_bindgen(js_name = setLeafIndex)]
+pub fn set_leaf_index(note: &[u8], leaf_index: u64) -> Result<Vec<u8>, JsError> {
+    let mut note = parse_note(note)?;
+    note.set_leaf_index(leaf_index);
+    Ok(note.to_bytes().to_vec())
+}
+
+/// A note's commitment, as shielded to the program
+#[wasm_bindgen(js_name = noteCommitment)]
+pub fn note_commitment(note: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(field_to_bytes_be(&parse_note(note)?.commitment()).to_vec())
+}
+
+/// Transfer prover for a pool's keys
+#[wasm_bindgen]
+pub struct TransferProver {
+    system: TransferProofSystem,
+}
+
+#[wasm_bindgen]
+impl TransferProver {
+    /// Load keys serialized with `TransferProofSystem::serialize_*_key`
+    #[wasm_bindgen(constructor)]
+    pub fn new(proving_key: &[u8], verifying_key: &[u8]) -> Result<TransferProver, JsError> {
+        Ok(Self {
+            system: TransferProofSystem::from_keys(proving_key, verifying_key)?,
+        })
+    }
+
+    /// Prove a spend of `note` into a re-blinded output, less `fee`
+    ///
+    /// `path` opens the note under `root`, given little-endian as the
+    /// `transfer` instruction takes it.
+    #[wasm_bindgen(js_name = proveSpend)]
+    pub fn prove_spend(
+        &self,
+        note: &[u8],
+        path: &[u8],
+        root: &[u8],
+        fee: u64,
+    ) -> Result<SpendOutput, JsError> {
+        let note = parse_note(note)?;
+        let path = MerklePath::from_bytes(path).ok_or_else(|| JsError::new("Invalid Merkle path"))?;
+        if root.len() != 32 {
+            return Err(JsError::new("Root must be 32 bytes"));
+        }
+
+        let witness = WitnessBuilder::new(&note)
+            .with_path(path, Fr::from_le_bytes_mod_order(root))
+            .with_fee(fee)
+            .build()?;
+        let proof = self.system.prove(witness.circuit)?;
+        Ok(SpendOutput {
+            proof: self.system.export_solana_proof(proof.as_bytes())?.to_bytes().to_vec(),
+            public_inputs: witness.public_inputs.concat(),
+            output_note: witness.output_note.to_bytes().to_vec(),
+        })
+    }
+}
+
+/// A proven spend
+#[wasm_bindgen(getter_with_clone)]
+pub struct SpendOutput {
+    /// groth16-solana proof for the `transfer` instruction
+    pub proof: Vec<u8>,
+    /// merkle_root, nullifier, new_commitment, asset_id, fee
+    #[wasm_bindgen(js_name = publicInputs)]
+    pub public_inputs: Vec<u8>,
+    /// The output note, to place with `setLeafIndex` once inserted
+    #[wasm_bindgen(js_name = outputNote)]
+    pub output_note: Vec<u8>,
+}
+
+fn parse_note(note: &[u8]) -> Result<Note, JsError> {
+    Note::from_bytes(note).ok_or_else(|| JsError::new("Invalid note"))
+}
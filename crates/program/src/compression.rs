@@ -0,0 +1,199 @@
+//! Compressed commitment trees
+//!
+//! A pool may append its commitments to an spl-account-compression
+//! concurrent Merkle tree instead of the `IncrementalMerkleTree` stored in
+//! `PrivacyPool`. The pool PDA is the tree's authority; the pool keeps only
+//! the latest roots, read back from the tree account after each append, so
+//! trees of depth 24 to 30 fit.
+//!
+//! Concurrent Merkle trees hash with keccak256, not Poseidon, so the
+//! Groth16 circuit cannot prove membership in them: compressed pools only
+//! accept MVP proofs.
+//!
+//! The instructions are built by hand to avoid depending on the
+//! spl-account-compression crate.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::pubkey;
+
+use crate::instructions::NyxError;
+
+/// spl-account-compression program
+pub const ACCOUNT_COMPRESSION_ID: Pubkey =
+    pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+
+/// spl-noop program, which the compression program logs changes through
+pub const NOOP_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// Shallowest compressed tree a pool may use
+pub const MIN_COMPRESSED_TREE_DEPTH: u8 = 24;
+
+/// Deepest tree spl-account-compression supports
+pub const MAX_COMPRESSED_TREE_DEPTH: u8 = 30;
+
+/// Accounts an insertion into a compressed tree takes, in order: the tree,
+/// the noop program and the compression program
+pub const TREE_ACCOUNTS_LEN: usize = 3;
+
+/// `sha256("global:init_empty_merkle_tree")[..8]`
+const INIT_EMPTY_MERKLE_TREE_DISCRIMINATOR: [u8; 8] = [191, 11, 119, 7, 180, 107, 220, 110];
+
+/// `sha256("global:append")[..8]`
+const APPEND_DISCRIMINATOR: [u8; 8] = [149, 120, 18, 222, 236, 225, 88, 203];
+
+/// Account type, header version and V1 header (buffer size, depth,
+/// authority, creation slot, padding)
+const HEADER_SIZE: usize = 2 + 4 + 4 + 32 + 8 + 6;
+
+/// Sequence number, active changelog index and buffer size
+const TREE_PREFIX_SIZE: usize = 8 + 8 + 8;
+
+/// Bytes of one changelog entry: root, path and leaf index (with padding)
+fn changelog_size(depth: u8) -> usize {
+    32 + 32 * depth as usize + 8
+}
+
+/// Fail unless `depth` is a supported compressed tree depth
+pub fn validate_depth(depth: u8) -> Result<()> {
+    require!(
+        (MIN_COMPRESSED_TREE_DEPTH..=MAX_COMPRESSED_TREE_DEPTH).contains(&depth),
+        NyxError::InvalidCompressedTree
+    );
+    Ok(())
+}
+
+/// Initialize `tree` (allocated and owned by the compression program) with
+/// the pool as its authority
+pub fn init_tree<'info>(
+    tree: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    noop: &AccountInfo<'info>,
+    compression_program: &AccountInfo<'info>,
+    depth: u8,
+    max_buffer_size: u32,
+    pool_seeds: &[&[u8]],
+) -> Result<()> {
+    let mut data = INIT_EMPTY_MERKLE_TREE_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&(depth as u32).to_le_bytes());
+    data.extend_from_slice(&max_buffer_size.to_le_bytes());
+    invoke_tree(tree, pool, noop, compression_program, data, pool_seeds)
+}
+
+/// Append `leaf` to the pool's tree
+///
+/// `tree_accounts` are the instruction's trailing (tree, noop, compression
+/// program) accounts. Returns the new root and the leaf's index.
+pub fn append<'info>(
+    tree_accounts: &[AccountInfo<'info>],
+    pool: &AccountInfo<'info>,
+    tree_key: &Pubkey,
+    depth: u8,
+    leaf: [u8; 32],
+    pool_seeds: &[&[u8]],
+) -> Result<([u8; 32], u64)> {
+    let [tree, noop, compression_program] = tree_accounts else {
+        return err!(NyxError::InvalidCompressedTree);
+    };
+    require!(tree.key() == *tree_key, NyxError::InvalidCompressedTree);
+
+    let mut data = APPEND_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&leaf);
+    invoke_tree(tree, pool, noop, compression_program, data, pool_seeds)?;
+
+    read_latest(&tree.try_borrow_data()?, depth)
+}
+
+fn invoke_tree<'info>(
+    tree: &AccountInfo<'info>,
+    pool: &AccountInfo<'info>,
+    noop: &AccountInfo<'info>,
+    compression_program: &AccountInfo<'info>,
+    data: Vec<u8>,
+    pool_seeds: &[&[u8]],
+) -> Result<()> {
+    require!(
+        compression_program.key() == ACCOUNT_COMPRESSION_ID && noop.key() == NOOP_ID,
+        NyxError::InvalidCompressedTree
+    );
+    let ix = Instruction {
+        program_id: ACCOUNT_COMPRESSION_ID,
+        accounts: vec![
+            AccountMeta::new(tree.key(), false),
+            AccountMeta::new_readonly(pool.key(), true),
+            AccountMeta::new_readonly(NOOP_ID, false),
+        ],
+        data,
+    };
+    invoke_signed(
+        &ix,
+        &[tree.clone(), pool.clone(), noop.clone(), compression_program.clone()],
+        &[pool_seeds],
+    )?;
+    Ok(())
+}
+
+/// Root and leaf index of the tree's latest change
+pub fn read_latest(data: &[u8], depth: u8) -> Result<([u8; 32], u64)> {
+    let word = |offset: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+    };
+    let active_index = word(HEADER_SIZE + 8).ok_or(NyxError::InvalidCompressedTree)?;
+
+    let entry = usize::try_from(active_index)
+        .ok()
+        .and_then(|index| index.checked_mul(changelog_size(depth)))
+        .and_then(|offset| offset.checked_add(HEADER_SIZE + TREE_PREFIX_SIZE))
+        .and_then(|start| data.get(start..start + changelog_size(depth)))
+        .ok_or(NyxError::InvalidCompressedTree)?;
+
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&entry[..32]);
+    let index_offset = 32 + 32 * depth as usize;
+    let leaf_index = u32::from_le_bytes(entry[index_offset..index_offset + 4].try_into().unwrap());
+    Ok((root, leaf_index as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    #[test]
+    fn test_discriminators() {
+        assert_eq!(
+            hash(b"global:init_empty_merkle_tree").to_bytes()[..8],
+            INIT_EMPTY_MERKLE_TREE_DISCRIMINATOR
+        );
+        assert_eq!(hash(b"global:append").to_bytes()[..8], APPEND_DISCRIMINATOR);
+    }
+
+    #[test]
+    fn test_read_latest() {
+        let depth = 24;
+        let buffer = 4;
+        let mut data = vec![0u8; HEADER_SIZE + TREE_PREFIX_SIZE + buffer * changelog_size(depth)];
+        data[HEADER_SIZE + 8..HEADER_SIZE + 16].copy_from_slice(&2u64.to_le_bytes());
+
+        let entry = HEADER_SIZE + TREE_PREFIX_SIZE + 2 * changelog_size(depth);
+        data[entry..entry + 32].copy_from_slice(&[7u8; 32]);
+        let index = entry + 32 + 32 * depth as usize;
+        data[index..index + 4].copy_from_slice(&41u32.to_le_bytes());
+
+        assert_eq!(read_latest(&data, depth).unwrap(), ([7u8; 32], 41));
+
+        // An active index past the buffer is rejected rather than read
+        data[HEADER_SIZE + 8..HEADER_SIZE + 16].copy_from_slice(&4u64.to_le_bytes());
+        assert!(read_latest(&data, depth).is_err());
+        assert!(read_latest(&data[..HEADER_SIZE], depth).is_err());
+    }
+
+    #[test]
+    fn test_validate_depth() {
+        assert!(validate_depth(MIN_COMPRESSED_TREE_DEPTH).is_ok());
+        assert!(validate_depth(MAX_COMPRESSED_TREE_DEPTH).is_ok());
+        assert!(validate_depth(20).is_err());
+        assert!(validate_depth(31).is_err());
+    }
+}
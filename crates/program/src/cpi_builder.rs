@@ -0,0 +1,132 @@
+//! Typed CPI builders for composing programs
+//!
+//! DAOs, payroll programs and other callers deposit into the native SOL
+//! pool through `shield_sol_cpi`, moving lamports out of a system-owned PDA
+//! they sign for with `invoke_signed`. Anchor callers can instead enable the
+//! `cpi` feature and use the generated `veil_program::cpi` module.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::{InstructionData, ToAccountMetas};
+
+use crate::state::{NATIVE_MINT, POOL_SEED, ROOT_HISTORY_SEED};
+use crate::token::VAULT_SEED;
+
+/// Native SOL pool and vault addresses for `program_id`
+pub fn sol_pool_addresses(program_id: &Pubkey) -> (Pubkey, Pubkey) {
+    let (pool, _) = Pubkey::find_program_address(&[POOL_SEED, NATIVE_MINT.as_ref()], program_id);
+    let (vault, _) = Pubkey::find_program_address(&[VAULT_SEED, pool.as_ref()], program_id);
+    (pool, vault)
+}
+
+/// Root history address of `pool`
+pub fn root_history_address(program_id: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ROOT_HISTORY_SEED, pool.as_ref()], program_id).0
+}
+
+/// `shield_sol_cpi` instruction depositing `amount` lamports from `source`
+pub fn shield_sol_cpi(
+    program_id: &Pubkey,
+    source: &Pubkey,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+) -> Instruction {
+    let (pool, vault) = sol_pool_addresses(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::ShieldSolCpi {
+            pool,
+            root_history: root_history_address(program_id, &pool),
+            vault,
+            source: *source,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: crate::instruction::ShieldSolCpi {
+            commitment,
+            amount,
+            encrypted_note,
+        }
+        .data(),
+    }
+}
+
+/// Accounts for `invoke_shield_sol_cpi`, in the caller's account list
+pub struct ShieldSolCpiAccounts<'a, 'info> {
+    /// The Veil program
+    pub program: &'a AccountInfo<'info>,
+    pub pool: &'a AccountInfo<'info>,
+    pub root_history: &'a AccountInfo<'info>,
+    pub vault: &'a AccountInfo<'info>,
+    /// Calling program's system-owned PDA holding the lamports
+    pub source: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+}
+
+/// Invoke `shield_sol_cpi`, signing for `source` with `signer_seeds`
+pub fn invoke_shield_sol_cpi(
+    accounts: ShieldSolCpiAccounts,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let ix = shield_sol_cpi(
+        accounts.program.key,
+        accounts.source.key,
+        commitment,
+        amount,
+        encrypted_note,
+    );
+    invoke_signed(
+        &ix,
+        &[
+            accounts.pool.clone(),
+            accounts.root_history.clone(),
+            accounts.vault.clone(),
+            accounts.source.clone(),
+            accounts.system_program.clone(),
+            accounts.program.clone(),
+        ],
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hashv;
+
+    #[test]
+    fn test_shield_sol_cpi_instruction() {
+        let source = Pubkey::new_unique();
+        let ix = shield_sol_cpi(&crate::ID, &source, [7u8; 32], 1_000, vec![1, 2, 3]);
+        let (pool, vault) = sol_pool_addresses(&crate::ID);
+        let root_history = root_history_address(&crate::ID, &pool);
+
+        let keys: Vec<_> = ix
+            .accounts
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (pool, false, true),
+                (root_history, false, true),
+                (vault, false, true),
+                (source, true, true),
+                (System::id(), false, false),
+            ]
+        );
+
+        let disc = &hashv(&[b"global:shield_sol_cpi"]).to_bytes()[..8];
+        assert_eq!(&ix.data[..8], disc);
+        assert_eq!(&ix.data[8..40], &[7u8; 32]);
+        assert_eq!(&ix.data[40..48], &1_000u64.to_le_bytes());
+        assert_eq!(&ix.data[48..], &[3, 0, 0, 0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Program events
+//!
+//! Emitted with `emit!` so indexers and wallets can follow pool activity
+//! without parsing `msg!` logs. Each event is logged as
+//! `Program data: base64(discriminator || borsh(event))`, with the
+//! discriminator `sha256("event:<Name>")[..8]`.
+//!
+//! Commitments, roots and nullifiers are 32-byte big-endian field elements,
+//! as stored in the pool's tree.
+
+use anchor_lang::prelude::*;
+
+/// A commitment was appended to a pool's tree (shield or transfer)
+///
+/// `encrypted_note` carries the note encrypted to its owner, if the sender
+/// attached one; wallets trial-decrypt it to discover incoming notes.
+#[event]
+pub struct CommitmentInserted {
+    /// The inserted commitment
+    pub commitment: [u8; 32],
+    /// Leaf index it was inserted at
+    pub leaf_index: u64,
+    /// Tree root after the insertion
+    pub new_root: [u8; 32],
+    /// Encrypted note (at most `ENCRYPTED_NOTE_SIZE` bytes, may be empty)
+    pub encrypted_note: Vec<u8>,
+}
+
+/// A nullifier was spent (transfer or unshield)
+#[event]
+pub struct NullifierSpent {
+    /// The spent nullifier
+    pub nullifier: [u8; 32],
+    /// Slot it was spent in
+    pub slot: u64,
+}
+
+/// Funds left a pool
+#[event]
+pub struct Unshielded {
+    /// Recipient wallet (the token account owner for SPL pools)
+    pub recipient: Pubkey,
+    /// Amount withdrawn, including the relayer fee
+    pub amount: u64,
+}
+
+/// A relayer fee change was scheduled
+#[event]
+pub struct RelayerFeeUpdated {
+    /// Fee in force when the change was made, in basis points
+    pub old_fee_bps: u16,
+    /// New fee, in basis points
+    pub new_fee_bps: u16,
+    /// Slot from which the new fee applies
+    pub effective_slot: u64,
+}
+
+/// A full tree was archived and a fresh one started
+#[event]
+pub struct TreeRotated {
+    /// Epoch of the archived tree
+    pub archived_epoch: u32,
+    /// Its final root, still accepted for its notes
+    pub final_root: [u8; 32],
+    /// Epoch of the new tree; leaf indices restart at 0
+    pub tree_epoch: u32,
+}
+
+/// An unshield was queued behind the pool's withdrawal delay
+#[event]
+pub struct WithdrawalQueued {
+    /// The `PendingWithdrawal` account to finalize
+    pub pending_withdrawal: Pubkey,
+    /// Recipient wallet (the token account for SPL pools)
+    pub recipient: Pubkey,
+    /// Amount withdrawn, including the relayer fee
+    pub amount: u64,
+    /// First slot the withdrawal can be finalized in
+    pub unlock_slot: u64,
+}
+
+/// The pool's withdrawal delay changed
+#[event]
+pub struct WithdrawalDelayUpdated {
+    /// New delay, in slots (0 pays out immediately)
+    pub delay_slots: u64,
+}
+
+/// The pool moved its commitments to a compressed tree
+#[event]
+pub struct CompressedTreeEnabled {
+    /// The spl-account-compression tree account
+    pub tree: Pubkey,
+    /// Tree depth
+    pub depth: u8,
+}
+
+/// The pool's deposit caps changed
+#[event]
+pub struct DepositCapsUpdated {
+    /// Largest single deposit (0 for no cap)
+    pub max_deposit_per_tx: u64,
+    /// Largest vault balance (0 for no cap)
+    pub max_pool_tvl: u64,
+}
+
+/// The protocol's share of relayer fees changed
+#[event]
+pub struct ProtocolFeeUpdated {
+    /// New share, in basis points of each relayer fee
+    pub fee_bps: u16,
+}
+
+/// The pool's root history was resized
+#[event]
+pub struct RootHistoryResized {
+    /// Number of replaced roots now accepted
+    pub size: u16,
+}
+
+/// The authority swept the pool treasury
+#[event]
+pub struct FeesCollected {
+    /// Receiver of the lamports
+    pub destination: Pubkey,
+    /// Lamports swept, above the treasury's rent-exempt minimum
+    pub lamports: u64,
+    /// Tokens swept from the treasury token account (0 if none was given)
+    pub tokens: u64,
+}
+
+/// A relayer joined the registry
+#[event]
+pub struct RelayerRegistered {
+    /// The relayer's signing key
+    pub relayer: Pubkey,
+    /// Lamports staked
+    pub stake: u64,
+    /// Advertised fee, in basis points
+    pub fee_bps: u16,
+    /// Advertised API endpoint
+    pub endpoint: String,
+}
+
+/// A relayer left the registry and withdrew its stake
+#[event]
+pub struct RelayerDeregistered {
+    /// The relayer's signing key
+    pub relayer: Pubkey,
+    /// Lamports returned, stake and rent
+    pub refunded: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::ENCRYPTED_NOTE_SIZE;
+    use anchor_lang::{Discriminator, Event};
+    use solana_program::hash::hashv;
+
+    #[test]
+    fn test_event_discriminators() {
+        let expected = |name: &str| {
+            let mut disc = [0u8; 8];
+            disc.copy_from_slice(&hashv(&[format!("event:{}", name).as_bytes()]).to_bytes()[..8]);
+            disc
+        };
+        assert_eq!(CommitmentInserted::DISCRIMINATOR, expected("CommitmentInserted"));
+        assert_eq!(NullifierSpent::DISCRIMINATOR, expected("NullifierSpent"));
+        assert_eq!(Unshielded::DISCRIMINATOR, expected("Unshielded"));
+        assert_eq!(RelayerFeeUpdated::DISCRIMINATOR, expected("RelayerFeeUpdated"));
+        assert_eq!(TreeRotated::DISCRIMINATOR, expected("TreeRotated"));
+        assert_eq!(RelayerRegistered::DISCRIMINATOR, expected("RelayerRegistered"));
+        assert_eq!(WithdrawalQueued::DISCRIMINATOR, expected("WithdrawalQueued"));
+        assert_eq!(WithdrawalDelayUpdated::DISCRIMINATOR, expected("WithdrawalDelayUpdated"));
+        assert_eq!(RelayerDeregistered::DISCRIMINATOR, expected("RelayerDeregistered"));
+        assert_eq!(DepositCapsUpdated::DISCRIMINATOR, expected("DepositCapsUpdated"));
+        assert_eq!(CompressedTreeEnabled::DISCRIMINATOR, expected("CompressedTreeEnabled"));
+        assert_eq!(ProtocolFeeUpdated::DISCRIMINATOR, expected("ProtocolFeeUpdated"));
+        assert_eq!(FeesCollected::DISCRIMINATOR, expected("FeesCollected"));
+        assert_eq!(RootHistoryResized::DISCRIMINATOR, expected("RootHistoryResized"));
+    }
+
+    #[test]
+    fn test_commitment_inserted_layout() {
+        let event = CommitmentInserted {
+            commitment: [1u8; 32],
+            leaf_index: 7,
+            new_root: [2u8; 32],
+            encrypted_note: vec![3u8; ENCRYPTED_NOTE_SIZE],
+        };
+        let data = event.data();
+        assert_eq!(data.len(), 8 + 32 + 8 + 32 + 4 + ENCRYPTED_NOTE_SIZE);
+        assert_eq!(&data[8..40], &[1u8; 32]);
+        assert_eq!(&data[40..48], &7u64.to_le_bytes());
+        assert_eq!(&data[48..80], &[2u8; 32]);
+        assert_eq!(&data[80..84], &(ENCRYPTED_NOTE_SIZE as u32).to_le_bytes());
+        assert_eq!(&data[84..], &[3u8; ENCRYPTED_NOTE_SIZE][..]);
+    }
+}
@@ -15,8 +15,15 @@
 //! - merkle_root
 //! - nullifier
 //! - new_commitment
+//! - asset_id
+//! - fee
+//!
+//! Unshields are proven with a separate circuit and key, whose public
+//! inputs are merkle_root, nullifier, recipient_hash, amount, relayer_fee
+//! and asset_id.
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
 use groth16_solana::groth16::{Groth16Verifier, Groth16Verifyingkey};
 
 /// Groth16 proof size in bytes (compressed)
@@ -26,55 +33,111 @@ pub const PROOF_SIZE: usize = 256;
 pub const PUBLIC_INPUT_SIZE: usize = 32;
 
 /// Number of public inputs for the transfer circuit
-pub const NUM_PUBLIC_INPUTS: usize = 3;
+pub const NUM_PUBLIC_INPUTS: usize = 5;
 
 /// Total size of all public inputs
 pub const PUBLIC_INPUTS_SIZE: usize = NUM_PUBLIC_INPUTS * PUBLIC_INPUT_SIZE;
 
+/// Number of IC points (one per public input, plus one)
+pub const NUM_IC: usize = NUM_PUBLIC_INPUTS + 1;
+
+/// Number of public inputs for the unshield circuit
+pub const UNSHIELD_NUM_PUBLIC_INPUTS: usize = 6;
+
+/// Number of IC points of the unshield circuit's key
+pub const UNSHIELD_NUM_IC: usize = UNSHIELD_NUM_PUBLIC_INPUTS + 1;
+
+/// Seeds prefix for verifying key PDAs, followed by the pool address
+pub const VK_SEED: &[u8] = b"verifying_key";
+
 /// Verifying key for the transfer circuit
 ///
-/// This key is generated during the trusted setup and must match
-/// the proving key used to generate proofs off-chain.
-///
-/// The key is stored as a constant array of bytes in big-endian format.
-/// It can be generated from arkworks VerifyingKey using the export functions
-/// in the Rust SDK.
-///
-/// For now, this is a placeholder that will be replaced with the actual
-/// verifying key after the trusted setup ceremony.
-pub mod vk {
-    /// Placeholder verifying key structure
-    /// This will be replaced with actual key data after trusted setup
-    ///
-    /// The verifying key contains:
-    /// - alpha_g1: 64 bytes
-    /// - beta_g2: 128 bytes
-    /// - gamma_g2: 128 bytes
-    /// - delta_g2: 128 bytes
-    /// - ic: variable length (NUM_PUBLIC_INPUTS + 1) * 64 bytes
-    ///
-    /// Total for 3 public inputs: 64 + 128 + 128 + 128 + (4 * 64) = 704 bytes
-
+/// Generated during the trusted setup; must match the proving key used to
+/// generate proofs off-chain. Points are big-endian as groth16-solana
+/// expects (see `SolanaVerifyingKey::from_arkworks` in the Rust SDK).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifyingKeyData {
     /// Alpha * G1 (64 bytes)
-    pub const ALPHA_G1: [u8; 64] = [0u8; 64];
-
+    pub alpha_g1: [u8; 64],
     /// Beta * G2 (128 bytes)
-    pub const BETA_G2: [u8; 128] = [0u8; 128];
-
+    pub beta_g2: [u8; 128],
     /// Gamma * G2 (128 bytes)
-    pub const GAMMA_G2: [u8; 128] = [0u8; 128];
-
+    pub gamma_g2: [u8; 128],
     /// Delta * G2 (128 bytes)
-    pub const DELTA_G2: [u8; 128] = [0u8; 128];
+    pub delta_g2: [u8; 128],
+    /// IC elements (one for capacity + one per public input)
+    pub ic: [[u8; 64]; NUM_IC],
+}
+
+impl VerifyingKeyData {
+    /// Serialized size: 64 + 128 + 128 + 128 + (6 * 64) = 832 bytes
+    pub const SIZE: usize = 64 + 128 * 3 + 64 * NUM_IC;
 
+    /// Check if the key is set (not all zeros)
+    pub fn is_initialized(&self) -> bool {
+        self.alpha_g1.iter().any(|&b| b != 0)
+    }
+}
+
+/// Verifying key for the unshield circuit
+///
+/// Same encoding as `VerifyingKeyData`, with one IC point per unshield
+/// public input.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnshieldVerifyingKeyData {
+    /// Alpha * G1 (64 bytes)
+    pub alpha_g1: [u8; 64],
+    /// Beta * G2 (128 bytes)
+    pub beta_g2: [u8; 128],
+    /// Gamma * G2 (128 bytes)
+    pub gamma_g2: [u8; 128],
+    /// Delta * G2 (128 bytes)
+    pub delta_g2: [u8; 128],
     /// IC elements (one for capacity + one per public input)
-    /// For 3 public inputs: 4 * 64 = 256 bytes
-    pub const IC: [[u8; 64]; 4] = [[0u8; 64]; 4];
+    pub ic: [[u8; 64]; UNSHIELD_NUM_IC],
+}
+
+impl UnshieldVerifyingKeyData {
+    /// Serialized size: 64 + 128 + 128 + 128 + (7 * 64) = 896 bytes
+    pub const SIZE: usize = 64 + 128 * 3 + 64 * UNSHIELD_NUM_IC;
+
+    /// Check if the key is set (not all zeros)
+    pub fn is_initialized(&self) -> bool {
+        self.alpha_g1.iter().any(|&b| b != 0)
+    }
+}
+
+/// Verifying key account for a pool (`[VK_SEED, pool]`)
+///
+/// Created zeroed with the pool and written by the pool authority with
+/// `set_verifying_key` and `set_unshield_verifying_key`. Once `locked`,
+/// neither key can change.
+#[account]
+pub struct VerifyingKeyAccount {
+    /// Pool this key verifies proofs for
+    pub pool: Pubkey,
+
+    /// The transfer circuit's verifying key
+    pub key: VerifyingKeyData,
+
+    /// The unshield circuit's verifying key
+    pub unshield_key: UnshieldVerifyingKeyData,
+
+    /// Set once the authority has fixed the key for good
+    pub locked: bool,
+
+    /// Bump seed for PDA
+    pub bump: u8,
 }
 
-/// Check if verifying key is initialized (not all zeros)
-fn is_vk_initialized() -> bool {
-    vk::ALPHA_G1.iter().any(|&b| b != 0)
+impl VerifyingKeyAccount {
+    /// Account size (without discriminator)
+    pub const SIZE: usize = 32 + VerifyingKeyData::SIZE + UnshieldVerifyingKeyData::SIZE + 1 + 1;
+
+    /// Whether both keys are set, as Groth16 proofs require
+    pub fn is_initialized(&self) -> bool {
+        self.key.is_initialized() && self.unshield_key.is_initialized()
+    }
 }
 
 /// Groth16 proof structure
@@ -125,13 +188,105 @@ pub struct TransferPublicInputs {
     pub nullifier: [u8; 32],
     /// New commitment being created
     pub new_commitment: [u8; 32],
+    /// `asset_id` of the pool's mint
+    pub asset_id: [u8; 32],
+    /// Part of the input paid to the relayer
+    pub fee: u64,
 }
 
 impl TransferPublicInputs {
     /// Convert to the format expected by the verifier (big-endian field elements)
     pub fn to_verifier_inputs(&self) -> [[u8; 32]; NUM_PUBLIC_INPUTS] {
-        [self.merkle_root, self.nullifier, self.new_commitment]
+        [
+            self.merkle_root,
+            self.nullifier,
+            self.new_commitment,
+            self.asset_id,
+            u64_to_field(self.fee),
+        ]
+    }
+
+    /// Inverse of `to_verifier_inputs`, or `None` if the fee is not a `u64`
+    ///
+    /// Mirrors `TransferPublicInputs::from_onchain_bytes` in the Rust SDK.
+    pub fn from_verifier_inputs(inputs: &[[u8; 32]; NUM_PUBLIC_INPUTS]) -> Option<Self> {
+        Some(Self {
+            merkle_root: inputs[0],
+            nullifier: inputs[1],
+            new_commitment: inputs[2],
+            asset_id: inputs[3],
+            fee: field_to_u64(&inputs[4])?,
+        })
+    }
+}
+
+/// Public inputs for the unshield circuit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct UnshieldPublicInputs {
+    /// Merkle root the note is proven against
+    pub merkle_root: [u8; 32],
+    /// Nullifier being spent
+    pub nullifier: [u8; 32],
+    /// `recipient_hash` of the receiving account
+    pub recipient_hash: [u8; 32],
+    /// Amount withdrawn, including the relayer fee
+    pub amount: u64,
+    /// Part of `amount` paid to the relayer
+    pub relayer_fee: u64,
+    /// `asset_id` of the pool's mint
+    pub asset_id: [u8; 32],
+}
+
+impl UnshieldPublicInputs {
+    /// Convert to the format expected by the verifier (big-endian field elements)
+    pub fn to_verifier_inputs(&self) -> [[u8; 32]; UNSHIELD_NUM_PUBLIC_INPUTS] {
+        [
+            self.merkle_root,
+            self.nullifier,
+            self.recipient_hash,
+            u64_to_field(self.amount),
+            u64_to_field(self.relayer_fee),
+            self.asset_id,
+        ]
+    }
+}
+
+/// Hash of a recipient account as the unshield circuit takes it
+///
+/// SHA-256 of the key with the first byte cleared, so it is always a valid
+/// field element. Mirrors `recipient_hash` in the Rust SDK.
+pub fn recipient_hash(recipient: &Pubkey) -> [u8; 32] {
+    let mut digest = hash(recipient.as_ref()).to_bytes();
+    digest[0] = 0;
+    digest
+}
+
+/// Asset id of the notes a pool for `mint` holds
+///
+/// 0 for native SOL, otherwise the first 8 bytes (little-endian) of the
+/// SHA-256 of the base58 mint address. Mirrors `AssetRegistry.get_asset_id`
+/// in the Python SDK and `asset_id_for_mint` in the Rust SDK.
+pub fn asset_id(mint: &Pubkey) -> [u8; 32] {
+    if *mint == crate::state::NATIVE_MINT {
+        return [0u8; 32];
+    }
+    let digest = hash(mint.to_string().as_bytes()).to_bytes();
+    u64_to_field(u64::from_le_bytes(digest[..8].try_into().unwrap()))
+}
+
+/// Encode an integer as a big-endian field element
+pub fn u64_to_field(value: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// Decode a big-endian field element that fits in a `u64`
+pub fn field_to_u64(bytes: &[u8; 32]) -> Option<u64> {
+    if bytes[..24].iter().any(|&byte| byte != 0) {
+        return None;
     }
+    Some(u64::from_be_bytes(bytes[24..].try_into().unwrap()))
 }
 
 /// Errors for Groth16 verification
@@ -145,6 +300,8 @@ pub enum Groth16Error {
     VerificationFailed,
     #[msg("Verifying key not initialized")]
     VkNotInitialized,
+    #[msg("Verifying key is locked")]
+    VkLocked,
 }
 
 /// Verify a Groth16 proof for a transfer
@@ -155,59 +312,82 @@ pub enum Groth16Error {
 /// - The spending key for that note
 /// - Correct nullifier derivation
 /// - Correct new commitment formation
+/// - Both notes holding the asset `asset_id` stands for
+/// - The output holding the input amount less `fee`
 ///
 /// # Arguments
 /// * `proof` - The 256-byte Groth16 proof
-/// * `merkle_root` - The Merkle root public input
-/// * `nullifier` - The nullifier public input
-/// * `new_commitment` - The new commitment public input
+/// * `vk` - The pool's verifying key
+/// * `inputs` - The public inputs
 ///
 /// # Returns
 /// * `Ok(true)` if the proof is valid
 /// * `Ok(false)` if the proof is invalid
-/// * `Err(...)` if there's a format error
+/// * `Err(...)` if there's a format error or the key is not set
 pub fn verify_groth16_transfer(
     proof_bytes: &[u8],
-    merkle_root: &[u8; 32],
-    nullifier: &[u8; 32],
-    new_commitment: &[u8; 32],
+    vk: &VerifyingKeyData,
+    inputs: &TransferPublicInputs,
 ) -> Result<bool> {
-    // Parse proof
-    let proof = Groth16Proof::from_bytes(proof_bytes)
-        .ok_or(Groth16Error::InvalidProofSize)?;
-
-    // Check if verifying key is initialized
-    if !is_vk_initialized() {
-        // VK not initialized - for development, return true
-        // TODO: Remove this bypass and require proper VK initialization
-        msg!("WARNING: Verifying key not initialized, skipping proof verification");
-        return Ok(true);
-    }
-
-    // Prepare public inputs as fixed-size array
-    let public_inputs: [[u8; 32]; NUM_PUBLIC_INPUTS] = [
-        *merkle_root,
-        *nullifier,
-        *new_commitment,
-    ];
+    // A zeroed key would verify nothing
+    require!(vk.is_initialized(), Groth16Error::VkNotInitialized);
 
     // Create verifying key struct
     let verifying_key = Groth16Verifyingkey {
         nr_pubinputs: NUM_PUBLIC_INPUTS,
-        vk_alpha_g1: vk::ALPHA_G1,
-        vk_beta_g2: vk::BETA_G2,
-        vk_gamme_g2: vk::GAMMA_G2,
-        vk_delta_g2: vk::DELTA_G2,
-        vk_ic: &vk::IC,
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamme_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+        vk_ic: &vk.ic,
     };
 
+    verify_with_key(proof_bytes, &inputs.to_verifier_inputs(), &verifying_key)
+}
+
+/// Verify a Groth16 proof for an unshield
+///
+/// The proof demonstrates knowledge of a valid, unspent-by-construction
+/// note in the tree whose whole amount is withdrawn to the account hashing
+/// to `recipient_hash`, of which `relayer_fee` goes to the relayer. The
+/// recipient and fee are recomputed from the instruction, so a relayer
+/// cannot change either without invalidating the proof.
+pub fn verify_groth16_unshield(
+    proof_bytes: &[u8],
+    vk: &UnshieldVerifyingKeyData,
+    inputs: &UnshieldPublicInputs,
+) -> Result<bool> {
+    require!(vk.is_initialized(), Groth16Error::VkNotInitialized);
+
+    let verifying_key = Groth16Verifyingkey {
+        nr_pubinputs: UNSHIELD_NUM_PUBLIC_INPUTS,
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamme_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+        vk_ic: &vk.ic,
+    };
+
+    verify_with_key(proof_bytes, &inputs.to_verifier_inputs(), &verifying_key)
+}
+
+/// Check `proof_bytes` against `public_inputs` under `verifying_key`
+fn verify_with_key<const N: usize>(
+    proof_bytes: &[u8],
+    public_inputs: &[[u8; 32]; N],
+    verifying_key: &Groth16Verifyingkey,
+) -> Result<bool> {
+    // Parse proof
+    let proof = Groth16Proof::from_bytes(proof_bytes)
+        .ok_or(Groth16Error::InvalidProofSize)?;
+
     // Create verifier with the proof and public inputs
-    let mut verifier = Groth16Verifier::<NUM_PUBLIC_INPUTS>::new(
+    let mut verifier = Groth16Verifier::<N>::new(
         &proof.a,
         &proof.b,
         &proof.c,
-        &public_inputs,
-        &verifying_key,
+        public_inputs,
+        verifying_key,
     ).map_err(|_| Groth16Error::VerificationFailed)?;
 
     // Perform verification
@@ -242,16 +422,17 @@ pub fn le_to_be_g1(le_bytes: &[u8; 64]) -> [u8; 64] {
 
 /// Convert a 128-byte little-endian G2 point to big-endian
 ///
-/// G2 points are represented as (x, y) where each coordinate is 64 bytes (Fq2)
-/// Each Fq2 element is (c0, c1) where each is 32 bytes
+/// G2 points are represented as (x, y) where each coordinate is 64 bytes (Fq2).
+/// arkworks writes each Fq2 as c0 || c1 little-endian; groth16-solana and the
+/// alt_bn128 syscalls (EIP-197) expect c1 || c0 big-endian, which is each
+/// 64-byte coordinate reversed as a whole:
+///
+/// - input:  [0..32] x.c0, [32..64] x.c1, [64..96] y.c0, [96..128] y.c1
+/// - output: [0..32] x.c1, [32..64] x.c0, [64..96] y.c1, [96..128] y.c0
 pub fn le_to_be_g2(le_bytes: &[u8; 128]) -> [u8; 128] {
-    let mut be_bytes = [0u8; 128];
-    // x.c0, x.c1, y.c0, y.c1 - each 32 bytes, needs to be reversed
-    for i in 0..4 {
-        let start = i * 32;
-        be_bytes[start..start + 32].copy_from_slice(&le_bytes[start..start + 32]);
-        be_bytes[start..start + 32].reverse();
-    }
+    let mut be_bytes = *le_bytes;
+    be_bytes[0..64].reverse();
+    be_bytes[64..128].reverse();
     be_bytes
 }
 
@@ -259,6 +440,114 @@ pub fn le_to_be_g2(le_bytes: &[u8; 128]) -> [u8; 128] {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_verifying_key_size() {
+        let key = VerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; NUM_IC],
+        };
+        assert_eq!(key.try_to_vec().unwrap().len(), VerifyingKeyData::SIZE);
+        assert_eq!(VerifyingKeyData::SIZE, 832);
+        assert!(!key.is_initialized());
+
+        let unshield_key = UnshieldVerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; UNSHIELD_NUM_IC],
+        };
+        assert_eq!(unshield_key.try_to_vec().unwrap().len(), UnshieldVerifyingKeyData::SIZE);
+        assert_eq!(UnshieldVerifyingKeyData::SIZE, 896);
+
+        let mut account = VerifyingKeyAccount {
+            pool: Pubkey::default(),
+            key,
+            unshield_key,
+            locked: false,
+            bump: 0,
+        };
+        assert_eq!(account.try_to_vec().unwrap().len(), VerifyingKeyAccount::SIZE);
+
+        // Groth16 proofs need both keys
+        account.key.alpha_g1[0] = 1;
+        assert!(!account.is_initialized());
+        account.unshield_key.alpha_g1[0] = 1;
+        assert!(account.is_initialized());
+    }
+
+    #[test]
+    fn test_unshield_public_inputs() {
+        let recipient = Pubkey::new_unique();
+        let inputs = UnshieldPublicInputs {
+            merkle_root: [1u8; 32],
+            nullifier: [2u8; 32],
+            recipient_hash: recipient_hash(&recipient),
+            amount: 1_000_000,
+            relayer_fee: 0x0102,
+            asset_id: asset_id(&crate::state::NATIVE_MINT),
+        };
+        let encoded = inputs.to_verifier_inputs();
+        assert_eq!(encoded[2][0], 0);
+        assert_eq!(encoded[3][24..], 1_000_000u64.to_be_bytes());
+        assert_eq!(encoded[4][..30], [0u8; 30]);
+        assert_eq!(encoded[4][30..], [1, 2]);
+        assert_eq!(encoded[5], [0u8; 32]);
+        assert_ne!(recipient_hash(&recipient), recipient_hash(&Pubkey::new_unique()));
+        // Same vector as the Rust SDK's `recipient_hash` test
+        assert_eq!(
+            recipient_hash(&Pubkey::new_from_array([7u8; 32])),
+            hex32("00b06f8e4e3a7715d201d573d0aa423762e55dabd61a2c02278fa56cc6d294e0")
+        );
+
+        let unset = UnshieldVerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; UNSHIELD_NUM_IC],
+        };
+        let result = verify_groth16_unshield(&[1u8; PROOF_SIZE], &unset, &inputs);
+        assert_eq!(result.unwrap_err(), Groth16Error::VkNotInitialized.into());
+    }
+
+    #[test]
+    fn test_uninitialized_key_rejected() {
+        let key = VerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; NUM_IC],
+        };
+        let inputs = TransferPublicInputs {
+            merkle_root: [0u8; 32],
+            nullifier: [0u8; 32],
+            new_commitment: [0u8; 32],
+            asset_id: [0u8; 32],
+            fee: 5,
+        };
+        assert_eq!(inputs.to_verifier_inputs()[4], u64_to_field(5));
+        let decoded = TransferPublicInputs::from_verifier_inputs(&inputs.to_verifier_inputs());
+        assert_eq!(decoded.unwrap().fee, 5);
+        let mut large_fee = inputs.to_verifier_inputs();
+        large_fee[4][0] = 1;
+        assert!(TransferPublicInputs::from_verifier_inputs(&large_fee).is_none());
+        let result = verify_groth16_transfer(&[1u8; PROOF_SIZE], &key, &inputs);
+        assert_eq!(result.unwrap_err(), Groth16Error::VkNotInitialized.into());
+    }
+
+    #[test]
+    fn test_asset_id() {
+        assert_eq!(asset_id(&crate::state::NATIVE_MINT), [0u8; 32]);
+        // Same vector as the Python SDK's `AssetRegistry.get_asset_id`
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".parse().unwrap();
+        assert_eq!(asset_id(&usdc), u64_to_field(0x67e6_c237_f1bb_49f2));
+    }
+
     #[test]
     fn test_proof_parsing() {
         let mut proof_bytes = [0u8; 256];
@@ -289,4 +578,34 @@ mod tests {
         assert_eq!(be[29], 3);
         assert_eq!(be[28], 4);
     }
+
+    #[test]
+    fn test_le_to_be_g2_generator_fixture() {
+        // BN254 G2 generator limbs, big-endian
+        let x_c0 = hex32("1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed");
+        let x_c1 = hex32("198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2");
+        let y_c0 = hex32("12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa");
+        let y_c1 = hex32("090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b");
+
+        // arkworks layout: x.c0, x.c1, y.c0, y.c1, each little-endian
+        let mut le = [0u8; 128];
+        for (i, limb) in [x_c0, x_c1, y_c0, y_c1].iter().enumerate() {
+            le[i * 32..(i + 1) * 32].copy_from_slice(&le_to_be_32(limb));
+        }
+
+        // EIP-197 layout: x.c1, x.c0, y.c1, y.c0, each big-endian
+        let be = le_to_be_g2(&le);
+        assert_eq!(be[0..32], x_c1);
+        assert_eq!(be[32..64], x_c0);
+        assert_eq!(be[64..96], y_c1);
+        assert_eq!(be[96..128], y_c0);
+    }
+
+    fn hex32(s: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        out
+    }
 }
@@ -11,6 +11,19 @@ use anchor_lang::prelude::*;
 use crate::verification::{MVP_PROOF_SIZE, ProofType};
 use crate::groth16::PROOF_SIZE as GROTH16_PROOF_SIZE;
 
+/// Maximum size of the encrypted note attached to a new commitment:
+/// version (1) + ephemeral key (32) + nonce (12) + ciphertext (48) + auth
+/// tag (16), as produced by `veil_core::crypto::encrypt_note`
+pub const ENCRYPTED_NOTE_SIZE: usize = 109;
+
+/// Maximum nullifiers a `transfer` may spend beyond its first, each with a
+/// marker and archive bucket in `remaining_accounts`
+pub const MAX_EXTRA_NULLIFIERS: usize = 7;
+
+/// Maximum size of the memo an unshield may log for the recipient, such as
+/// an exchange deposit reference
+pub const MAX_MEMO_LEN: usize = 256;
+
 /// Instruction data for Shield
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct ShieldData {
@@ -18,6 +31,8 @@ pub struct ShieldData {
     pub commitment: [u8; 32],
     /// Amount to shield (in token smallest unit)
     pub amount: u64,
+    /// Note encrypted to its owner (empty if delivered off-chain)
+    pub encrypted_note: Vec<u8>,
 }
 
 /// Instruction data for Transfer
@@ -29,6 +44,8 @@ pub struct TransferData {
     pub new_commitment: [u8; 32],
     /// Proof (MVP: 96 bytes, Groth16: 256 bytes)
     pub proof: Vec<u8>,
+    /// Output note encrypted to the recipient (empty if delivered off-chain)
+    pub encrypted_note: Vec<u8>,
 }
 
 /// Instruction data for Unshield
@@ -40,6 +57,8 @@ pub struct UnshieldData {
     pub amount: u64,
     /// Proof (MVP: 96 bytes, Groth16: 256 bytes)
     pub proof: Vec<u8>,
+    /// UTF-8 memo logged through the Memo program (empty for none)
+    pub memo: Vec<u8>,
 }
 
 /// Custom error codes for the privacy program
@@ -57,17 +76,107 @@ pub enum NyxError {
     PoolFull,
     #[msg("Proof verification failed")]
     ProofVerificationFailed,
+    #[msg("Token account mint does not match the pool's mint")]
+    MintMismatch,
+    #[msg("Native SOL uses the SOL pool; initialize it with `initialize`")]
+    NativeMintPool,
+    #[msg("Encrypted note exceeds ENCRYPTED_NOTE_SIZE bytes")]
+    EncryptedNoteTooLarge,
+    #[msg("This operation is paused for the pool")]
+    OperationPaused,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Signer is not the pending pool authority")]
+    NotPendingAuthority,
+    #[msg("Relayer fee exceeds MAX_RELAYER_FEE_BPS")]
+    FeeTooHigh,
+    #[msg("A transfer cannot spend the same nullifier twice")]
+    DuplicateNullifier,
+    #[msg("At most one encrypted note per output")]
+    TooManyEncryptedNotes,
+    #[msg("Nullifier finality window is below MIN_NULLIFIER_FINALITY_EPOCHS")]
+    FinalityWindowTooShort,
+    #[msg("Nullifier marker is still inside the finality window")]
+    NullifierNotFinal,
+    #[msg("Account is not a nullifier marker of this pool and archive bucket")]
+    InvalidNullifierMarker,
+    #[msg("Invalid nullifier archive account")]
+    InvalidNullifierArchive,
+    #[msg("The tree can only be rotated once it is full")]
+    TreeNotFull,
+    #[msg("Unknown tree epoch, or missing archived root")]
+    InvalidTreeEpoch,
+    #[msg("CPI deposit source must be a system-owned account without data")]
+    InvalidDepositSource,
+    #[msg("Too many extra nullifiers for one transfer")]
+    TooManyNullifiers,
+    #[msg("Relayer stake is below MIN_RELAYER_STAKE")]
+    StakeTooLow,
+    #[msg("Relayer endpoint must be an https:// URL of at most MAX_ENDPOINT_LEN bytes")]
+    InvalidRelayerEndpoint,
+    #[msg("Withdrawal delay exceeds MAX_WITHDRAWAL_DELAY_SLOTS")]
+    WithdrawalDelayTooLong,
+    #[msg("Pending withdrawal is still inside the pool's withdrawal delay")]
+    WithdrawalLocked,
+    #[msg("Deposit exceeds the pool's per-transaction cap")]
+    DepositTooLarge,
+    #[msg("Deposit would exceed the pool's TVL cap")]
+    PoolCapReached,
+    #[msg("Compressed tree account, program or depth is invalid")]
+    InvalidCompressedTree,
+    #[msg("Operation is not supported by pools with a compressed tree")]
+    CompressedTreeUnsupported,
+    #[msg("Pool already holds commitments")]
+    PoolNotEmpty,
+    #[msg("Protocol fee exceeds MAX_PROTOCOL_FEE_BPS")]
+    ProtocolFeeTooHigh,
+    #[msg("Treasury token accounts are missing or incomplete")]
+    MissingTreasuryAccount,
+    #[msg("Recipient token account is not the recipient's associated token account")]
+    RecipientAccountMismatch,
+    #[msg("Memo must be UTF-8 of at most MAX_MEMO_LEN bytes")]
+    InvalidMemo,
+    #[msg("A memo needs the Memo program account")]
+    MissingMemoProgram,
+    #[msg("Commitment was inserted recently")]
+    DuplicateCommitment,
+    #[msg("Root history size must be between 1 and MAX_ROOT_HISTORY_SIZE")]
+    InvalidRootHistorySize,
+    #[msg("Proof is against a root the pool no longer accepts")]
+    UnknownRoot,
+    #[msg("Vault token accounts are missing or incomplete")]
+    MissingVaultAccount,
+}
+
+/// Check an unshield memo: UTF-8 (which the Memo program requires) of at
+/// most `MAX_MEMO_LEN` bytes
+pub fn validate_memo(memo: &[u8]) -> Result<()> {
+    require!(
+        memo.len() <= MAX_MEMO_LEN && std::str::from_utf8(memo).is_ok(),
+        NyxError::InvalidMemo
+    );
+    Ok(())
+}
+
+/// Check an encrypted note payload against `ENCRYPTED_NOTE_SIZE`
+pub fn validate_encrypted_note(encrypted_note: &[u8]) -> Result<()> {
+    require!(
+        encrypted_note.len() <= ENCRYPTED_NOTE_SIZE,
+        NyxError::EncryptedNoteTooLarge
+    );
+    Ok(())
 }
 
 impl ShieldData {
     pub fn validate(&self) -> Result<()> {
         require!(self.amount > 0, NyxError::InvalidAmount);
-        Ok(())
+        validate_encrypted_note(&self.encrypted_note)
     }
 }
 
 impl TransferData {
     pub fn validate(&self) -> Result<()> {
+        validate_encrypted_note(&self.encrypted_note)?;
         // Accept both MVP (96 bytes) and Groth16 (256 bytes) proofs
         let valid_size = self.proof.len() == MVP_PROOF_SIZE
             || self.proof.len() == GROTH16_PROOF_SIZE;
@@ -84,6 +193,7 @@ impl TransferData {
 impl UnshieldData {
     pub fn validate(&self) -> Result<()> {
         require!(self.amount > 0, NyxError::InvalidAmount);
+        validate_memo(&self.memo)?;
         // Accept both MVP (96 bytes) and Groth16 (256 bytes) proofs
         let valid_size = self.proof.len() == MVP_PROOF_SIZE
             || self.proof.len() == GROTH16_PROOF_SIZE;
@@ -96,3 +206,28 @@ impl UnshieldData {
         ProofType::detect(&self.proof)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_note_bound() {
+        assert!(validate_encrypted_note(&[]).is_ok());
+        assert!(validate_encrypted_note(&[0u8; ENCRYPTED_NOTE_SIZE]).is_ok());
+        assert_eq!(
+            validate_encrypted_note(&[0u8; ENCRYPTED_NOTE_SIZE + 1]).unwrap_err(),
+            NyxError::EncryptedNoteTooLarge.into()
+        );
+    }
+
+    #[test]
+    fn test_memo_bound() {
+        assert!(validate_memo(&[]).is_ok());
+        assert!(validate_memo("deposit-ref 4821".as_bytes()).is_ok());
+        assert!(validate_memo(&[b'a'; MAX_MEMO_LEN]).is_ok());
+        for bad in [&[b'a'; MAX_MEMO_LEN + 1][..], &[0xff, 0xfe]] {
+            assert_eq!(validate_memo(bad).unwrap_err(), NyxError::InvalidMemo.into());
+        }
+    }
+}
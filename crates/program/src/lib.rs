@@ -1,269 +1,1572 @@
 //! Veil - Solana Privacy Program
 //!
 //! On-chain program for managing privacy pool state.
-//! Supports both native SOL and SPL token deposits.
+//! Supports both native SOL and SPL token deposits, with one pool (commitment
+//! tree, vault and nullifier set) per asset.
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::memo::Memo;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use instructions::NyxError;
+use state::{NATIVE_MINT, POOL_SEED};
 
 // Valid Base58 program ID (placeholder - replace with actual deployed program ID)
 // Using system program format: 32 bytes = 43-44 Base58 chars
 declare_id!("Vei1111111111111111111111111111111111111111");
 
+pub mod compression;
+pub mod cpi_builder;
+pub mod events;
 pub mod groth16;
 pub mod instructions;
 pub mod merkle;
 pub mod nullifier;
+pub mod poseidon;
+pub mod poseidon_constants;
 pub mod processor;
+pub mod relayer;
 pub mod state;
 pub mod token;
 pub mod verification;
+#[cfg(feature = "embedded-vk")]
+pub mod vk;
 
 #[program]
 pub mod veil_program {
     use super::*;
 
-    /// Initialize the privacy pool
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        processor::process_initialize(ctx)
+    /// Initialize the native SOL privacy pool
+    ///
+    /// Spends are accepted against the current root and the last
+    /// `root_history_size` roots it replaced (at most
+    /// `MAX_ROOT_HISTORY_SIZE`).
+    pub fn initialize(ctx: Context<Initialize>, root_history_size: u16) -> Result<()> {
+        processor::process_initialize(ctx, root_history_size)
+    }
+
+    /// Initialize the privacy pool for an SPL token mint
+    ///
+    /// `root_history_size` is as in `initialize`.
+    pub fn initialize_pool_for_mint(
+        ctx: Context<InitializePoolForMint>,
+        root_history_size: u16,
+    ) -> Result<()> {
+        processor::process_initialize_pool_for_mint(ctx, root_history_size)
+    }
+
+    /// Set the pool's Groth16 verifying key (pool authority only)
+    ///
+    /// With `lock` set the key becomes permanent.
+    pub fn set_verifying_key(
+        ctx: Context<SetVerifyingKey>,
+        key: groth16::VerifyingKeyData,
+        lock: bool,
+    ) -> Result<()> {
+        processor::process_set_verifying_key(ctx, key, lock)
+    }
+
+    /// Set the pool's unshield circuit verifying key (pool authority only)
+    ///
+    /// `lock` locks both keys, so set this one first.
+    pub fn set_unshield_verifying_key(
+        ctx: Context<SetVerifyingKey>,
+        key: groth16::UnshieldVerifyingKeyData,
+        lock: bool,
+    ) -> Result<()> {
+        processor::process_set_unshield_verifying_key(ctx, key, lock)
+    }
+
+    /// Start accepting Groth16 proofs (pool authority only)
+    ///
+    /// Fails while either of the pool's verifying keys is unset. From then
+    /// on the pool refuses MVP signature proofs.
+    pub fn enable_zk(ctx: Context<EnableZk>) -> Result<()> {
+        processor::process_enable_zk(ctx)
+    }
+
+    /// Pause or resume operations (pool authority only)
+    ///
+    /// `paused` is a bitmask of `state::PAUSE_*` flags and replaces the
+    /// current one, so shields can be halted while unshields stay open.
+    pub fn set_pause_state(ctx: Context<SetPauseState>, paused: u8) -> Result<()> {
+        processor::process_set_pause_state(ctx, paused)
+    }
+
+    /// Propose a new pool authority (current authority only)
+    ///
+    /// Takes effect once `new_authority` calls `accept_authority`;
+    /// `Pubkey::default()` cancels a pending proposal.
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        processor::process_propose_authority(ctx, new_authority)
+    }
+
+    /// Accept a proposed pool authority (pending authority only)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        processor::process_accept_authority(ctx)
+    }
+
+    /// Change the relayer fee (pool authority only)
+    ///
+    /// The new fee, at most `MAX_RELAYER_FEE_BPS`, applies `delay_slots`
+    /// slots from now; 0 applies it immediately.
+    pub fn update_relayer_fee(
+        ctx: Context<UpdateRelayerFee>,
+        new_fee_bps: u16,
+        delay_slots: u64,
+    ) -> Result<()> {
+        processor::process_update_relayer_fee(ctx, new_fee_bps, delay_slots)
+    }
+
+    /// Set the nullifier finality window, in epochs (pool authority only)
+    pub fn set_nullifier_finality(ctx: Context<SetNullifierFinality>, epochs: u16) -> Result<()> {
+        processor::process_set_nullifier_finality(ctx, epochs)
+    }
+
+    /// Archive final nullifier markers and reclaim their rent (pool
+    /// authority only)
+    ///
+    /// The markers, passed as writable remaining accounts, must belong to
+    /// archive `bucket` of `tree_epoch` and be older than the pool's finality window. Their
+    /// nullifiers move into the bucket and their rent, less the bucket's
+    /// growth, goes to the pool treasury.
+    pub fn compact_nullifiers(
+        ctx: Context<CompactNullifiers>,
+        tree_epoch: u32,
+        bucket: u8,
+    ) -> Result<()> {
+        processor::process_compact_nullifiers(ctx, tree_epoch, bucket)
+    }
+
+    /// Archive the full tree's root and start a fresh tree
+    ///
+    /// Anyone may call this once the tree is full; the payer funds the
+    /// archived root account. Notes in the old tree remain spendable by
+    /// passing its `tree_epoch`.
+    pub fn rotate_tree(ctx: Context<RotateTree>) -> Result<()> {
+        processor::process_rotate_tree(ctx)
+    }
+
+    /// Keep `size` replaced roots from now on (pool authority only)
+    ///
+    /// The authority pays for growing the root history account and is
+    /// refunded when it shrinks, which drops the oldest roots.
+    pub fn resize_root_history(ctx: Context<ResizeRootHistory>, size: u16) -> Result<()> {
+        processor::process_resize_root_history(ctx, size)
+    }
+
+    /// Append an empty pool's commitments to a compressed tree from now on
+    /// (pool authority only)
+    ///
+    /// `merkle_tree` must be allocated for `max_depth` and `max_buffer_size`
+    /// and owned by spl-account-compression. Afterwards every instruction
+    /// inserting commitments takes the tree, noop program and compression
+    /// program as its last remaining accounts. See `compression`.
+    pub fn use_compressed_tree(
+        ctx: Context<UseCompressedTree>,
+        max_depth: u8,
+        max_buffer_size: u32,
+    ) -> Result<()> {
+        processor::process_use_compressed_tree(ctx, max_depth, max_buffer_size)
     }
 
     /// Shield native SOL - deposit SOL and create commitment
-    pub fn shield_sol(ctx: Context<ShieldSol>, commitment: [u8; 32], amount: u64) -> Result<()> {
-        processor::process_shield_sol(ctx, commitment, amount)
+    ///
+    /// `encrypted_note` (at most `ENCRYPTED_NOTE_SIZE` bytes, may be empty)
+    /// is published in the `CommitmentInserted` event.
+    pub fn shield_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, ShieldSol<'info>>,
+        commitment: [u8; 32],
+        amount: u64,
+        encrypted_note: Vec<u8>,
+    ) -> Result<()> {
+        processor::process_shield_sol(ctx, commitment, amount, encrypted_note)
+    }
+
+    /// Shield native SOL from an account controlled by another program
+    ///
+    /// `source` is a lamport-only PDA of the calling program, which signs for
+    /// it with `invoke_signed`. See `cpi_builder` for typed builders.
+    pub fn shield_sol_cpi<'info>(
+        ctx: Context<'_, '_, '_, 'info, ShieldSolCpi<'info>>,
+        commitment: [u8; 32],
+        amount: u64,
+        encrypted_note: Vec<u8>,
+    ) -> Result<()> {
+        processor::process_shield_sol_cpi(ctx, commitment, amount, encrypted_note)
     }
 
-    /// Shield SPL tokens - deposit tokens and create commitment
-    pub fn shield(ctx: Context<Shield>, commitment: [u8; 32], amount: u64) -> Result<()> {
-        processor::process_shield(ctx, commitment, amount)
+    /// Shield SPL or Token-2022 tokens - deposit tokens and create commitment
+    ///
+    /// The vault must be credited the full `amount`, so Token-2022 mints
+    /// charging a transfer fee are rejected.
+    pub fn shield<'info>(
+        ctx: Context<'_, '_, '_, 'info, Shield<'info>>,
+        commitment: [u8; 32],
+        amount: u64,
+        encrypted_note: Vec<u8>,
+    ) -> Result<()> {
+        processor::process_shield(ctx, commitment, amount, encrypted_note)
     }
 
     /// Private transfer - spend commitment and create new one
-    pub fn transfer(
-        ctx: Context<Transfer>,
+    ///
+    /// `tree_epoch` is the epoch of the tree holding the spent note and
+    /// `root` the root the proof is against: for the live tree, its current
+    /// root or one in the pool's root history; for an older tree, its
+    /// archived root. `encrypted_note` delivers the output note to its
+    /// recipient.
+    ///
+    /// `extra_nullifiers` sweeps more notes of the same tree into the one
+    /// output: `remaining_accounts` holds a (marker, archive bucket) pair for
+    /// each, ahead of any compressed tree accounts, and the proof covers all
+    /// inputs. Pools with zk enabled reject sweeps, which have no Groth16
+    /// verifier yet.
+    ///
+    /// The proof binds `fee`, the part of the input paid from the vault to
+    /// the relayer; the output note holds the rest.
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer<'info>(
+        ctx: Context<'_, '_, '_, 'info, Transfer<'info>>,
+        tree_epoch: u32,
+        root: [u8; 32],
         nullifier: [u8; 32],
         new_commitment: [u8; 32],
+        fee: u64,
+        proof: Vec<u8>,
+        encrypted_note: Vec<u8>,
+        extra_nullifiers: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        processor::process_transfer(
+            ctx,
+            tree_epoch,
+            root,
+            nullifier,
+            new_commitment,
+            fee,
+            proof,
+            encrypted_note,
+            extra_nullifiers,
+        )
+    }
+
+    /// Join-split transfer - spend two commitments and create two new ones
+    ///
+    /// Both nullifier markers are created and both outputs inserted in the
+    /// same instruction. Both inputs must be from tree `tree_epoch`; `root`
+    /// is as in `transfer`.
+    /// `encrypted_notes` holds up to one note per output, in output order.
+    /// Rejected on pools with zk enabled until a join-split key can be set.
+    pub fn transfer_joinsplit<'info>(
+        ctx: Context<'_, '_, '_, 'info, TransferJoinSplit<'info>>,
+        tree_epoch: u32,
+        root: [u8; 32],
+        nullifiers: [[u8; 32]; 2],
+        new_commitments: [[u8; 32]; 2],
         proof: Vec<u8>,
+        encrypted_notes: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        processor::process_transfer_joinsplit(
+            ctx,
+            tree_epoch,
+            root,
+            nullifiers,
+            new_commitments,
+            proof,
+            encrypted_notes,
+        )
+    }
+
+    /// Cap single shields and the pool's vault balance (pool authority
+    /// only; 0 leaves either uncapped)
+    pub fn set_deposit_caps(
+        ctx: Context<SetDepositCaps>,
+        max_deposit_per_tx: u64,
+        max_pool_tvl: u64,
     ) -> Result<()> {
-        processor::process_transfer(ctx, nullifier, new_commitment, proof)
+        processor::process_set_deposit_caps(ctx, max_deposit_per_tx, max_pool_tvl)
     }
 
-    /// Unshield native SOL - spend commitment and withdraw SOL
+    /// Set the delay before unshielded funds are paid out, in slots (pool
+    /// authority only; 0 pays out immediately)
+    pub fn set_withdrawal_delay(ctx: Context<SetWithdrawalDelay>, delay_slots: u64) -> Result<()> {
+        processor::process_set_withdrawal_delay(ctx, delay_slots)
+    }
+
+    /// Route `fee_bps` of each relayer fee to the pool treasury (pool
+    /// authority only)
+    ///
+    /// The authority tops the treasury up to its rent-exempt minimum so
+    /// that it can accept small SOL fees.
+    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, fee_bps: u16) -> Result<()> {
+        processor::process_set_protocol_fee(ctx, fee_bps)
+    }
+
+    /// Sweep the pool treasury to `destination` (pool authority only)
+    ///
+    /// Moves the treasury's lamports above its rent-exempt minimum and, if
+    /// the optional token accounts are given, its whole token balance.
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        processor::process_collect_fees(ctx)
+    }
+
+    /// Unshield native SOL - spend commitment and withdraw SOL, less the
+    /// relayer fee
+    ///
+    /// If the pool has a withdrawal delay, the payout is queued in a pending
+    /// withdrawal instead. A non-empty `memo` (such as an exchange deposit
+    /// reference) is logged through the Memo program, which must then be
+    /// passed. `tree_epoch` and `root` are as in `transfer`.
+    #[allow(clippy::too_many_arguments)]
     pub fn unshield_sol(
         ctx: Context<UnshieldSol>,
+        tree_epoch: u32,
+        root: [u8; 32],
         nullifier: [u8; 32],
         amount: u64,
         proof: Vec<u8>,
+        memo: Vec<u8>,
     ) -> Result<()> {
-        processor::process_unshield_sol(ctx, nullifier, amount, proof)
+        processor::process_unshield_sol(ctx, tree_epoch, root, nullifier, amount, proof, memo)
     }
 
-    /// Unshield SPL tokens - spend commitment and withdraw tokens
+    /// Unshield SPL tokens - spend commitment and withdraw tokens, less the
+    /// relayer fee
+    ///
+    /// If the pool has a withdrawal delay, the payout is queued in a pending
+    /// withdrawal instead. `memo` is handled as in `unshield_sol`.
+    #[allow(clippy::too_many_arguments)]
     pub fn unshield(
         ctx: Context<Unshield>,
+        tree_epoch: u32,
+        root: [u8; 32],
+        nullifier: [u8; 32],
+        amount: u64,
+        proof: Vec<u8>,
+        memo: Vec<u8>,
+    ) -> Result<()> {
+        processor::process_unshield(ctx, tree_epoch, root, nullifier, amount, proof, memo)
+    }
+
+    /// Unshield SPL tokens to the recipient's associated token account,
+    /// creating it first if it does not exist
+    ///
+    /// The relayer pays the account's rent, which its fee is expected to
+    /// cover. Otherwise the same as `unshield`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn unshield_with_ata<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnshieldWithAta<'info>>,
+        tree_epoch: u32,
+        root: [u8; 32],
         nullifier: [u8; 32],
         amount: u64,
         proof: Vec<u8>,
+        memo: Vec<u8>,
+    ) -> Result<()> {
+        processor::process_unshield_with_ata(ctx, tree_epoch, root, nullifier, amount, proof, memo)
+    }
+
+    /// Pay out a pending SOL withdrawal once its delay has passed
+    ///
+    /// Anyone may call this; the rent goes back to whoever queued it.
+    pub fn finalize_withdrawal_sol(ctx: Context<FinalizeWithdrawalSol>) -> Result<()> {
+        processor::process_finalize_withdrawal_sol(ctx)
+    }
+
+    /// Pay out a pending SPL withdrawal once its delay has passed
+    ///
+    /// Anyone may call this; the rent goes back to whoever queued it.
+    pub fn finalize_withdrawal(ctx: Context<FinalizeWithdrawal>) -> Result<()> {
+        processor::process_finalize_withdrawal(ctx)
+    }
+
+    /// Join the relayer registry
+    ///
+    /// Locks `stake` lamports (at least `MIN_RELAYER_STAKE`) in the
+    /// relayer's entry and advertises `fee_bps` and `endpoint`.
+    pub fn register_relayer(
+        ctx: Context<RegisterRelayer>,
+        fee_bps: u16,
+        endpoint: String,
+        stake: u64,
     ) -> Result<()> {
-        processor::process_unshield(ctx, nullifier, amount, proof)
+        processor::process_register_relayer(ctx, fee_bps, endpoint, stake)
+    }
+
+    /// Leave the relayer registry, returning the stake and rent
+    pub fn deregister_relayer(ctx: Context<DeregisterRelayer>) -> Result<()> {
+        processor::process_deregister_relayer(ctx)
     }
 }
 
+/// Initialize the native SOL pool
 #[derive(Accounts)]
+#[instruction(root_history_size: u16)]
 pub struct Initialize<'info> {
     #[account(
         init,
         payer = authority,
         space = 8 + state::PrivacyPool::SIZE,
-        seeds = [b"privacy_pool"],
+        seeds = [POOL_SEED, NATIVE_MINT.as_ref()],
         bump
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
+    /// The pool's verifying key, created zeroed
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + groth16::VerifyingKeyAccount::SIZE,
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history, created empty
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + state::RootHistory::space(root_history_size),
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
-/// Shield native SOL
+/// Initialize the pool for an SPL token mint
 #[derive(Accounts)]
-pub struct ShieldSol<'info> {
+#[instruction(root_history_size: u16)]
+pub struct InitializePoolForMint<'info> {
     #[account(
-        mut,
-        seeds = [b"privacy_pool"],
-        bump = pool.bump
+        init,
+        payer = authority,
+        space = 8 + state::PrivacyPool::SIZE,
+        seeds = [POOL_SEED, mint.key().as_ref()],
+        bump
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Pool's SOL vault PDA
-    /// CHECK: Validated by seeds constraint
+    /// The pool's verifying key, created zeroed
     #[account(
-        mut,
-        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        init,
+        payer = authority,
+        space = 8 + groth16::VerifyingKeyAccount::SIZE,
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
         bump
     )]
-    pub vault: AccountInfo<'info>,
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history, created empty
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + state::RootHistory::space(root_history_size),
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Asset this pool holds (SPL Token or Token-2022 mint)
+    #[account(constraint = mint.key() != NATIVE_MINT @ NyxError::NativeMintPool)]
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(mut)]
-    pub depositor: Signer<'info>,
+    pub authority: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
-/// Shield SPL tokens
+/// Set a pool's verifying key
 #[derive(Accounts)]
-pub struct Shield<'info> {
+pub struct SetVerifyingKey<'info> {
     #[account(
-        mut,
-        seeds = [b"privacy_pool"],
-        bump = pool.bump
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Pool's vault authority PDA
-    /// CHECK: Validated by seeds constraint
     #[account(
-        seeds = [token::VAULT_SEED, pool.key().as_ref()],
-        bump
+        mut,
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
     )]
-    pub vault_authority: AccountInfo<'info>,
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
 
-    /// Pool's token account for this mint
+    pub authority: Signer<'info>,
+}
+
+/// Enable Groth16 proofs for a pool
+#[derive(Accounts)]
+pub struct EnableZk<'info> {
     #[account(
         mut,
-        constraint = vault_token_account.owner == vault_authority.key()
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Depositor's token account
+    #[account(
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Pause or resume a pool's operations
+#[derive(Accounts)]
+pub struct SetPauseState<'info> {
     #[account(
         mut,
-        constraint = depositor_token_account.mint == vault_token_account.mint
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
-    pub depositor_token_account: Account<'info, TokenAccount>,
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    #[account(mut)]
-    pub depositor: Signer<'info>,
+    pub authority: Signer<'info>,
+}
+
+/// Propose a new pool authority
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    pub token_program: Program<'info, Token>,
+    pub authority: Signer<'info>,
 }
 
+/// Accept a proposed pool authority
 #[derive(Accounts)]
-#[instruction(nullifier: [u8; 32])]
-pub struct Transfer<'info> {
+pub struct AcceptAuthority<'info> {
     #[account(
         mut,
-        seeds = [b"privacy_pool"],
+        seeds = [POOL_SEED, pool.mint.as_ref()],
         bump = pool.bump
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Nullifier marker PDA - created to mark nullifier as spent
-    /// If this account already exists, the transaction fails (double-spend prevention)
+    pub new_authority: Signer<'info>,
+}
+
+/// Change a pool's relayer fee
+#[derive(Accounts)]
+pub struct UpdateRelayerFee<'info> {
     #[account(
-        init,
-        payer = relayer,
-        space = 8 + nullifier::NullifierMarker::SIZE,
-        seeds = [nullifier::NULLIFIER_SEED, pool.key().as_ref(), &nullifier],
-        bump
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
-    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    #[account(mut)]
-    pub relayer: Signer<'info>,
+    pub authority: Signer<'info>,
+}
 
-    pub system_program: Program<'info, System>,
+/// Change a pool's nullifier finality window
+#[derive(Accounts)]
+pub struct SetNullifierFinality<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    pub authority: Signer<'info>,
 }
 
-/// Unshield native SOL
+/// Move a pool's commitments to a compressed tree
 #[derive(Accounts)]
-#[instruction(nullifier: [u8; 32])]
-pub struct UnshieldSol<'info> {
+pub struct UseCompressedTree<'info> {
     #[account(
         mut,
-        seeds = [b"privacy_pool"],
-        bump = pool.bump
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Nullifier marker PDA - created to mark nullifier as spent
+    /// Tree account, initialized with the pool as its authority
+    /// CHECK: Owner checked here; layout checked by the compression program
     #[account(
-        init,
-        payer = relayer,
-        space = 8 + nullifier::NullifierMarker::SIZE,
-        seeds = [nullifier::NULLIFIER_SEED, pool.key().as_ref(), &nullifier],
-        bump
+        mut,
+        owner = compression::ACCOUNT_COMPRESSION_ID @ NyxError::InvalidCompressedTree
     )]
-    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+    pub merkle_tree: UncheckedAccount<'info>,
 
-    /// Pool's SOL vault PDA
-    /// CHECK: Validated by seeds constraint
+    pub authority: Signer<'info>,
+
+    /// CHECK: Address checked
+    #[account(address = compression::NOOP_ID @ NyxError::InvalidCompressedTree)]
+    pub noop_program: UncheckedAccount<'info>,
+
+    /// CHECK: Address checked
+    #[account(address = compression::ACCOUNT_COMPRESSION_ID @ NyxError::InvalidCompressedTree)]
+    pub compression_program: UncheckedAccount<'info>,
+}
+
+/// Change a pool's deposit caps
+#[derive(Accounts)]
+pub struct SetDepositCaps<'info> {
     #[account(
         mut,
-        seeds = [token::VAULT_SEED, pool.key().as_ref()],
-        bump
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
-    pub vault: AccountInfo<'info>,
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Recipient receiving the SOL
-    /// CHECK: Any account can receive SOL
-    #[account(mut)]
-    pub recipient: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+}
 
-    #[account(mut)]
-    pub relayer: Signer<'info>,
+/// Change a pool's withdrawal delay
+#[derive(Accounts)]
+pub struct SetWithdrawalDelay<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
 
-    pub system_program: Program<'info, System>,
+    pub authority: Signer<'info>,
 }
 
-/// Unshield SPL tokens
+/// Change the protocol's share of relayer fees
 #[derive(Accounts)]
-#[instruction(nullifier: [u8; 32])]
-pub struct Unshield<'info> {
+pub struct SetProtocolFee<'info> {
     #[account(
         mut,
-        seeds = [b"privacy_pool"],
-        bump = pool.bump
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
     )]
     pub pool: Account<'info, state::PrivacyPool>,
 
-    /// Nullifier marker PDA - created to mark nullifier as spent
+    /// Pool treasury PDA
+    /// CHECK: Validated by seeds constraint
     #[account(
-        init,
-        payer = relayer,
-        space = 8 + nullifier::NullifierMarker::SIZE,
-        seeds = [nullifier::NULLIFIER_SEED, pool.key().as_ref(), &nullifier],
+        mut,
+        seeds = [state::TREASURY_SEED, pool.key().as_ref()],
         bump
     )]
-    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+    pub treasury: AccountInfo<'info>,
 
-    /// Pool's vault authority PDA
+    /// Funds the treasury's rent-exempt minimum
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Sweep the pool treasury
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// Pool treasury PDA
     /// CHECK: Validated by seeds constraint
     #[account(
-        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        mut,
+        seeds = [state::TREASURY_SEED, pool.key().as_ref()],
         bump
     )]
-    pub vault_authority: AccountInfo<'info>,
+    pub treasury: AccountInfo<'info>,
 
-    /// Pool's token account
+    /// Receives the swept lamports
+    /// CHECK: Any account can receive SOL
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// The pool's mint, when sweeping tokens
+    #[account(constraint = mint.key() == pool.mint @ NyxError::MintMismatch)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Treasury's token account, when sweeping tokens
     #[account(
         mut,
-        constraint = vault_token_account.owner == vault_authority.key()
+        constraint = treasury_token_account.mint == pool.mint @ NyxError::MintMismatch,
+        constraint = treasury_token_account.owner == treasury.key()
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    /// Recipient's token account
+    /// Receives the swept tokens
     #[account(
         mut,
-        constraint = recipient_token_account.mint == vault_token_account.mint
+        constraint = destination_token_account.mint == pool.mint @ NyxError::MintMismatch
     )]
-    pub recipient_token_account: Account<'info, TokenAccount>,
+    pub destination_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    #[account(mut)]
-    pub relayer: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
 
     pub system_program: Program<'info, System>,
 }
+
+/// Compact final nullifier markers into an archive bucket
+#[derive(Accounts)]
+#[instruction(tree_epoch: u32, bucket: u8)]
+pub struct CompactNullifiers<'info> {
+    #[account(
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// Archive bucket, created on first use
+    /// CHECK: Validated by seeds constraint; layout checked by the processor
+    #[account(
+        mut,
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[bucket]
+        ],
+        bump
+    )]
+    pub nullifier_archive: UncheckedAccount<'info>,
+
+    /// Pool treasury PDA, receives the reclaimed rent
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [state::TREASURY_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// Pays for creating the bucket
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Rotate a full tree
+#[derive(Accounts)]
+pub struct RotateTree<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Final root of the tree being rotated out
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + state::ArchivedRoot::SIZE,
+        seeds = [state::ARCHIVED_ROOT_SEED, pool.key().as_ref(), &pool.tree_epoch.to_le_bytes()],
+        bump
+    )]
+    pub archived_root: Account<'info, state::ArchivedRoot>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Resize a pool's root history
+#[derive(Accounts)]
+#[instruction(size: u16)]
+pub struct ResizeRootHistory<'info> {
+    #[account(
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump,
+        realloc = 8 + state::RootHistory::space(size),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Pays for growing the root history, refunded when it shrinks
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Shield native SOL
+#[derive(Accounts)]
+pub struct ShieldSol<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, NATIVE_MINT.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Pool's SOL vault PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Shield native SOL via CPI from a composing program
+#[derive(Accounts)]
+pub struct ShieldSolCpi<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, NATIVE_MINT.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Pool's SOL vault PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    /// Calling program's PDA, signed for with `invoke_signed`; the system
+    /// program can only debit system-owned accounts without data
+    #[account(
+        mut,
+        constraint = *source.owner == System::id() && source.data_is_empty()
+            @ NyxError::InvalidDepositSource
+    )]
+    pub source: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Shield SPL tokens
+#[derive(Accounts)]
+pub struct Shield<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        constraint = pool.mint != NATIVE_MINT @ NyxError::NativeMintPool
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Pool's vault authority PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    /// The pool's mint
+    #[account(constraint = mint.key() == pool.mint @ NyxError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Pool's token account for this mint
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key(),
+        constraint = vault_token_account.mint == pool.mint @ NyxError::MintMismatch
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Depositor's token account
+    #[account(
+        mut,
+        constraint = depositor_token_account.mint == vault_token_account.mint
+    )]
+    pub depositor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(tree_epoch: u32, root: [u8; 32], nullifier: [u8; 32])]
+pub struct Transfer<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's verifying key
+    #[account(
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Nullifier marker PDA - created to mark nullifier as spent
+    /// If this account already exists, the transaction fails (double-spend prevention)
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + nullifier::NullifierMarker::SIZE,
+        seeds = [
+            nullifier::NULLIFIER_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifier
+        ],
+        bump
+    )]
+    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+
+    /// Archive bucket for the nullifier; spending fails if it holds it
+    /// CHECK: Validated by seeds constraint; may not exist yet
+    #[account(
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[nullifier::archive_bucket(&nullifier)]
+        ],
+        bump
+    )]
+    pub nullifier_archive: UncheckedAccount<'info>,
+
+    /// Final root of tree `tree_epoch`, if it was rotated out
+    /// CHECK: Validated by seeds constraint; may not exist
+    #[account(
+        seeds = [state::ARCHIVED_ROOT_SEED, pool.key().as_ref(), &tree_epoch.to_le_bytes()],
+        bump
+    )]
+    pub archived_root: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Pool's SOL vault PDA, also the vault authority of a token pool
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    /// The pool's mint, when paying a fee in tokens
+    #[account(constraint = mint.key() == pool.mint @ NyxError::MintMismatch)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Pool's token account, when paying a fee in tokens
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key(),
+        constraint = vault_token_account.mint == pool.mint @ NyxError::MintMismatch
+    )]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Relayer's token account, credited with a token fee
+    #[account(
+        mut,
+        constraint = relayer_token_account.mint == pool.mint @ NyxError::MintMismatch,
+        constraint = relayer_token_account.owner == relayer.key()
+    )]
+    pub relayer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+/// Join-split transfer (2 inputs, 2 outputs)
+#[derive(Accounts)]
+#[instruction(tree_epoch: u32, root: [u8; 32], nullifiers: [[u8; 32]; 2])]
+pub struct TransferJoinSplit<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's verifying key
+    #[account(
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history
+    #[account(
+        mut,
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Marker for the first nullifier
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + nullifier::NullifierMarker::SIZE,
+        seeds = [
+            nullifier::NULLIFIER_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifiers[0]
+        ],
+        bump
+    )]
+    pub nullifier_marker_0: Account<'info, nullifier::NullifierMarker>,
+
+    /// Marker for the second nullifier
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + nullifier::NullifierMarker::SIZE,
+        seeds = [
+            nullifier::NULLIFIER_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifiers[1]
+        ],
+        bump
+    )]
+    pub nullifier_marker_1: Account<'info, nullifier::NullifierMarker>,
+
+    /// Archive bucket for the first nullifier
+    /// CHECK: Validated by seeds constraint; may not exist yet
+    #[account(
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[nullifier::archive_bucket(&nullifiers[0])]
+        ],
+        bump
+    )]
+    pub nullifier_archive_0: UncheckedAccount<'info>,
+
+    /// Archive bucket for the second nullifier
+    /// CHECK: Validated by seeds constraint; may not exist yet
+    #[account(
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[nullifier::archive_bucket(&nullifiers[1])]
+        ],
+        bump
+    )]
+    pub nullifier_archive_1: UncheckedAccount<'info>,
+
+    /// Final root of tree `tree_epoch`, if it was rotated out
+    /// CHECK: Validated by seeds constraint; may not exist
+    #[account(
+        seeds = [state::ARCHIVED_ROOT_SEED, pool.key().as_ref(), &tree_epoch.to_le_bytes()],
+        bump
+    )]
+    pub archived_root: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Unshield native SOL
+#[derive(Accounts)]
+#[instruction(tree_epoch: u32, root: [u8; 32], nullifier: [u8; 32])]
+pub struct UnshieldSol<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, NATIVE_MINT.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's verifying key
+    #[account(
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history
+    #[account(
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Nullifier marker PDA - created to mark nullifier as spent
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + nullifier::NullifierMarker::SIZE,
+        seeds = [
+            nullifier::NULLIFIER_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifier
+        ],
+        bump
+    )]
+    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+
+    /// Archive bucket for the nullifier; spending fails if it holds it
+    /// CHECK: Validated by seeds constraint; may not exist yet
+    #[account(
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[nullifier::archive_bucket(&nullifier)]
+        ],
+        bump
+    )]
+    pub nullifier_archive: UncheckedAccount<'info>,
+
+    /// Final root of tree `tree_epoch`, if it was rotated out
+    /// CHECK: Validated by seeds constraint; may not exist
+    #[account(
+        seeds = [state::ARCHIVED_ROOT_SEED, pool.key().as_ref(), &tree_epoch.to_le_bytes()],
+        bump
+    )]
+    pub archived_root: UncheckedAccount<'info>,
+
+    /// Pending withdrawal PDA, created instead of paying out while the pool
+    /// has a withdrawal delay
+    /// CHECK: Validated by seeds constraint; only created when delayed
+    #[account(
+        mut,
+        seeds = [
+            state::PENDING_WITHDRAWAL_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifier
+        ],
+        bump
+    )]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    /// Pool's SOL vault PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    /// Pool treasury PDA, receives the protocol's share of the fee
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [state::TREASURY_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// Recipient receiving the SOL
+    /// CHECK: Any account can receive SOL
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Memo program, needed only to log a memo
+    pub memo_program: Option<Program<'info, Memo>>,
+}
+
+/// Unshield SPL tokens
+#[derive(Accounts)]
+#[instruction(tree_epoch: u32, root: [u8; 32], nullifier: [u8; 32])]
+pub struct Unshield<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        constraint = pool.mint != NATIVE_MINT @ NyxError::NativeMintPool
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    /// The pool's verifying key
+    #[account(
+        seeds = [groth16::VK_SEED, pool.key().as_ref()],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, groth16::VerifyingKeyAccount>>,
+
+    /// The pool's root history
+    #[account(
+        seeds = [state::ROOT_HISTORY_SEED, pool.key().as_ref()],
+        bump = root_history.bump
+    )]
+    pub root_history: Box<Account<'info, state::RootHistory>>,
+
+    /// Nullifier marker PDA - created to mark nullifier as spent
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + nullifier::NullifierMarker::SIZE,
+        seeds = [
+            nullifier::NULLIFIER_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifier
+        ],
+        bump
+    )]
+    pub nullifier_marker: Account<'info, nullifier::NullifierMarker>,
+
+    /// Archive bucket for the nullifier; spending fails if it holds it
+    /// CHECK: Validated by seeds constraint; may not exist yet
+    #[account(
+        seeds = [
+            nullifier::ARCHIVE_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &[nullifier::archive_bucket(&nullifier)]
+        ],
+        bump
+    )]
+    pub nullifier_archive: UncheckedAccount<'info>,
+
+    /// Final root of tree `tree_epoch`, if it was rotated out
+    /// CHECK: Validated by seeds constraint; may not exist
+    #[account(
+        seeds = [state::ARCHIVED_ROOT_SEED, pool.key().as_ref(), &tree_epoch.to_le_bytes()],
+        bump
+    )]
+    pub archived_root: UncheckedAccount<'info>,
+
+    /// Pending withdrawal PDA, created instead of paying out while the pool
+    /// has a withdrawal delay
+    /// CHECK: Validated by seeds constraint; only created when delayed
+    #[account(
+        mut,
+        seeds = [
+            state::PENDING_WITHDRAWAL_SEED,
+            pool.key().as_ref(),
+            &tree_epoch.to_le_bytes(),
+            &nullifier
+        ],
+        bump
+    )]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    /// Pool's vault authority PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    /// The pool's mint
+    #[account(constraint = mint.key() == pool.mint @ NyxError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Pool's token account
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key(),
+        constraint = vault_token_account.mint == pool.mint @ NyxError::MintMismatch
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Recipient's token account
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == vault_token_account.mint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Relayer's token account, credited with the relayer fee
+    #[account(
+        mut,
+        constraint = relayer_token_account.mint == vault_token_account.mint,
+        constraint = relayer_token_account.owner == relayer.key()
+    )]
+    pub relayer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pool treasury PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        seeds = [state::TREASURY_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// Treasury's token account, credited with the protocol's share of the
+    /// fee; required while the pool takes a protocol fee
+    #[account(
+        mut,
+        constraint = treasury_token_account.mint == pool.mint @ NyxError::MintMismatch,
+        constraint = treasury_token_account.owner == treasury.key()
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Memo program, needed only to log a memo
+    pub memo_program: Option<Program<'info, Memo>>,
+}
+
+/// Create an unshield recipient's associated token account if needed
+#[derive(Accounts)]
+pub struct CreateRecipientAta<'info> {
+    /// Pays the account's rent
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    /// Wallet owning the token account
+    /// CHECK: Any account can own a token account
+    pub recipient: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Unshield SPL tokens to an associated token account created on the way
+///
+/// `create_ata` is validated, and the account created, before `unshield`
+/// reads the token account.
+#[derive(Accounts)]
+pub struct UnshieldWithAta<'info> {
+    pub create_ata: CreateRecipientAta<'info>,
+
+    pub unshield: Unshield<'info>,
+}
+
+/// Finalize a pending SOL withdrawal
+#[derive(Accounts)]
+pub struct FinalizeWithdrawalSol<'info> {
+    #[account(
+        seeds = [POOL_SEED, NATIVE_MINT.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = pool,
+        has_one = recipient,
+        has_one = fee_recipient,
+        has_one = rent_payer
+    )]
+    pub pending_withdrawal: Account<'info, state::PendingWithdrawal>,
+
+    /// Pool's SOL vault PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        mut,
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    /// CHECK: Must match the pending withdrawal
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+
+    /// Relayer credited with the fee
+    /// CHECK: Must match the pending withdrawal
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// CHECK: Must match the pending withdrawal; refunded its rent
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
+
+/// Finalize a pending SPL withdrawal
+#[derive(Accounts)]
+pub struct FinalizeWithdrawal<'info> {
+    #[account(
+        seeds = [POOL_SEED, pool.mint.as_ref()],
+        bump = pool.bump,
+        constraint = pool.mint != NATIVE_MINT @ NyxError::NativeMintPool
+    )]
+    pub pool: Account<'info, state::PrivacyPool>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = pool,
+        has_one = rent_payer
+    )]
+    pub pending_withdrawal: Account<'info, state::PendingWithdrawal>,
+
+    /// Pool's vault authority PDA
+    /// CHECK: Validated by seeds constraint
+    #[account(
+        seeds = [token::VAULT_SEED, pool.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    /// The pool's mint
+    #[account(constraint = mint.key() == pool.mint @ NyxError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Pool's token account
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key(),
+        constraint = vault_token_account.mint == pool.mint @ NyxError::MintMismatch
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Recipient's token account, as queued
+    #[account(
+        mut,
+        constraint = recipient_token_account.key() == pending_withdrawal.recipient
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Relayer's token account, credited with the fee
+    #[account(
+        mut,
+        constraint = fee_token_account.key() == pending_withdrawal.fee_recipient
+    )]
+    pub fee_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Must match the pending withdrawal; refunded its rent
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Register a relayer
+#[derive(Accounts)]
+pub struct RegisterRelayer<'info> {
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + relayer::RelayerEntry::SIZE,
+        seeds = [relayer::RELAYER_SEED, relayer.key().as_ref()],
+        bump
+    )]
+    pub relayer_entry: Account<'info, relayer::RelayerEntry>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Deregister a relayer
+#[derive(Accounts)]
+pub struct DeregisterRelayer<'info> {
+    #[account(
+        mut,
+        close = relayer,
+        seeds = [relayer::RELAYER_SEED, relayer.key().as_ref()],
+        bump = relayer_entry.bump,
+        has_one = relayer
+    )]
+    pub relayer_entry: Account<'info, relayer::RelayerEntry>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+}
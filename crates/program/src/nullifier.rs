@@ -8,6 +8,17 @@
 //! - Uses ~128 bytes per nullifier (account overhead + data)
 //! - Allows O(1) lookup via PDA derivation
 //! - Is standard practice for Solana privacy protocols
+//!
+//! Once a marker is older than the pool's finality window,
+//! `compact_nullifiers` moves its nullifier into an archive bucket and closes
+//! it, sending the rent to the pool treasury. Buckets are keyed by the
+//! nullifier's last byte and hold sorted 32-byte nullifiers (32 bytes of rent
+//! each instead of a whole account). Spends pass the bucket for their
+//! nullifier and fail if it is archived there, since the marker's `init`
+//! alone would succeed again after the close.
+//!
+//! Markers and buckets are namespaced by tree epoch (see `rotate_tree`), so a
+//! note in a fresh tree never collides with a spent one from an older tree.
 
 use anchor_lang::prelude::*;
 use solana_program::keccak;
@@ -16,8 +27,9 @@ use solana_program::keccak;
 pub const NULLIFIER_SEED: &[u8] = b"nullifier";
 
 /// Size of a nullifier marker account
-/// Discriminator (8) + pool pubkey (32) + nullifier hash (32) + spent_at slot (8)
-pub const NULLIFIER_ACCOUNT_SIZE: usize = 8 + 32 + 32 + 8;
+/// Discriminator (8) + pool pubkey (32) + tree epoch (4) + nullifier hash (32)
+/// + spent_at slot (8)
+pub const NULLIFIER_ACCOUNT_SIZE: usize = 8 + 32 + 4 + 32 + 8;
 
 /// Nullifier marker account
 /// Created when a nullifier is spent to prevent double-spending
@@ -27,6 +39,9 @@ pub struct NullifierMarker {
     /// The pool this nullifier belongs to
     pub pool: Pubkey,
 
+    /// Epoch of the tree the spent note was in
+    pub tree_epoch: u32,
+
     /// The nullifier hash (stored for verification)
     pub nullifier: [u8; 32],
 
@@ -35,7 +50,111 @@ pub struct NullifierMarker {
 }
 
 impl NullifierMarker {
-    pub const SIZE: usize = 32 + 32 + 8; // pool + nullifier + spent_at
+    pub const SIZE: usize = 32 + 4 + 32 + 8; // pool + tree_epoch + nullifier + spent_at
+}
+
+/// Seeds prefix for nullifier archive bucket PDAs, followed by the pool, the
+/// tree epoch (u32, little-endian) and the bucket number
+pub const ARCHIVE_SEED: &[u8] = b"nullifier_archive";
+
+/// Header of a nullifier archive bucket
+///
+/// Followed in the account by `count` sorted 32-byte nullifiers.
+#[account]
+#[derive(Debug)]
+pub struct NullifierArchive {
+    /// The pool these nullifiers belong to
+    pub pool: Pubkey,
+
+    /// Tree epoch the nullifiers are namespaced by
+    pub tree_epoch: u32,
+
+    /// Bucket number (last byte of every nullifier stored here)
+    pub bucket: u8,
+
+    /// Number of archived nullifiers
+    pub count: u32,
+}
+
+impl NullifierArchive {
+    pub const SIZE: usize = 32 + 4 + 1 + 4; // pool + tree_epoch + bucket + count
+
+    /// Offset of the first archived nullifier in the account data
+    pub const ENTRIES_OFFSET: usize = 8 + Self::SIZE;
+}
+
+/// Archive bucket a nullifier is compacted into
+///
+/// Nullifiers are big-endian field elements, so the last byte is the
+/// uniformly distributed one.
+pub fn archive_bucket(nullifier: &[u8; 32]) -> u8 {
+    nullifier[31]
+}
+
+/// Whether sorted `entries` (32 bytes each) contain `nullifier`
+pub fn archive_contains(entries: &[u8], nullifier: &[u8; 32]) -> bool {
+    search_entries(entries, nullifier).is_ok()
+}
+
+/// Insert `nullifier` into the first `count` sorted entries of `entries`
+///
+/// `entries` must have room for `count + 1` entries. Returns `false`, leaving
+/// `entries` untouched, if the nullifier is already present.
+pub fn archive_insert(entries: &mut [u8], count: usize, nullifier: &[u8; 32]) -> bool {
+    match search_entries(&entries[..count * 32], nullifier) {
+        Ok(_) => false,
+        Err(position) => {
+            entries.copy_within(position * 32..count * 32, (position + 1) * 32);
+            entries[position * 32..(position + 1) * 32].copy_from_slice(nullifier);
+            true
+        }
+    }
+}
+
+fn search_entries(entries: &[u8], nullifier: &[u8; 32]) -> std::result::Result<usize, usize> {
+    let (mut low, mut high) = (0, entries.len() / 32);
+    while low < high {
+        let mid = (low + high) / 2;
+        match entries[mid * 32..(mid + 1) * 32].cmp(&nullifier[..]) {
+            std::cmp::Ordering::Equal => return Ok(mid),
+            std::cmp::Ordering::Less => low = mid + 1,
+            std::cmp::Ordering::Greater => high = mid,
+        }
+    }
+    Err(low)
+}
+
+/// Fail if `nullifier` was compacted into the `archive` bucket
+///
+/// `archive` must be the bucket PDA for the nullifier (checked by the
+/// instruction's seeds constraint). A bucket that was never created holds
+/// nothing.
+pub fn require_not_archived(archive: &AccountInfo, nullifier: &[u8; 32]) -> Result<()> {
+    if archive.owner != &crate::ID || archive.data_is_empty() {
+        return Ok(());
+    }
+    let data = archive.try_borrow_data()?;
+    let header = NullifierArchive::try_deserialize(&mut &data[..])?;
+    let end = NullifierArchive::ENTRIES_OFFSET + header.count as usize * 32;
+    require!(data.len() >= end, crate::instructions::NyxError::InvalidNullifierArchive);
+    require!(
+        !archive_contains(&data[NullifierArchive::ENTRIES_OFFSET..end], nullifier),
+        crate::instructions::NyxError::NullifierSpent
+    );
+    Ok(())
+}
+
+/// Derive the PDA address for an archive bucket
+pub fn derive_archive_pda(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tree_epoch: u32,
+    bucket: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ARCHIVE_SEED, pool.as_ref(), &tree_epoch.to_le_bytes(), &[bucket]],
+        program_id,
+    )
 }
 
 /// Derive the PDA address for a nullifier
@@ -43,6 +162,7 @@ impl NullifierMarker {
 /// # Arguments
 /// * `program_id` - The program ID
 /// * `pool` - The pool pubkey
+/// * `tree_epoch` - Epoch of the tree the spent note is in
 /// * `nullifier` - The 32-byte nullifier hash
 ///
 /// # Returns
@@ -50,12 +170,14 @@ impl NullifierMarker {
 pub fn derive_nullifier_pda(
     program_id: &Pubkey,
     pool: &Pubkey,
+    tree_epoch: u32,
     nullifier: &[u8; 32],
 ) -> (Pubkey, u8) {
     Pubkey::find_program_address(
         &[
             NULLIFIER_SEED,
             pool.as_ref(),
+            &tree_epoch.to_le_bytes(),
             nullifier,
         ],
         program_id,
@@ -98,17 +220,52 @@ mod tests {
         let pool = Pubkey::new_unique();
         let nullifier = [1u8; 32];
 
-        let (pda, bump) = derive_nullifier_pda(&program_id, &pool, &nullifier);
+        let (pda, bump) = derive_nullifier_pda(&program_id, &pool, 0, &nullifier);
 
         // PDA should be deterministic
-        let (pda2, bump2) = derive_nullifier_pda(&program_id, &pool, &nullifier);
+        let (pda2, bump2) = derive_nullifier_pda(&program_id, &pool, 0, &nullifier);
         assert_eq!(pda, pda2);
         assert_eq!(bump, bump2);
 
         // Different nullifier = different PDA
         let nullifier2 = [2u8; 32];
-        let (pda3, _) = derive_nullifier_pda(&program_id, &pool, &nullifier2);
+        let (pda3, _) = derive_nullifier_pda(&program_id, &pool, 0, &nullifier2);
         assert_ne!(pda, pda3);
+
+        // Same nullifier in a later tree = different PDA
+        let (pda4, _) = derive_nullifier_pda(&program_id, &pool, 1, &nullifier);
+        assert_ne!(pda, pda4);
+    }
+
+    #[test]
+    fn test_archive_insert_keeps_entries_sorted() {
+        let nullifiers: Vec<[u8; 32]> = [9u8, 3, 7, 1, 5]
+            .iter()
+            .map(|&b| {
+                let mut n = [b; 32];
+                n[31] = 0xaa;
+                n
+            })
+            .collect();
+
+        let mut entries = vec![0u8; nullifiers.len() * 32];
+        for (count, nullifier) in nullifiers.iter().enumerate() {
+            assert!(!archive_contains(&entries[..count * 32], nullifier));
+            assert!(archive_insert(&mut entries, count, nullifier));
+            assert!(archive_contains(&entries[..(count + 1) * 32], nullifier));
+        }
+
+        let firsts: Vec<u8> = entries.chunks(32).map(|entry| entry[0]).collect();
+        assert_eq!(firsts, vec![1, 3, 5, 7, 9]);
+
+        // Duplicates are rejected without touching the entries
+        let before = entries.clone();
+        entries.extend_from_slice(&[0u8; 32]);
+        assert!(!archive_insert(&mut entries, nullifiers.len(), &nullifiers[2]));
+        assert_eq!(&entries[..before.len()], &before[..]);
+
+        assert!(!archive_contains(&before, &[2u8; 32]));
+        assert_eq!(archive_bucket(&nullifiers[0]), 0xaa);
     }
 
     #[test]
@@ -0,0 +1,119 @@
+//! Poseidon hash for the on-chain commitment tree
+//!
+//! Same permutation and parameters as `veil_core::crypto::poseidon`, so the
+//! pool's Merkle root matches the tree clients build and the paths
+//! `TransferCircuit` verifies.
+//!
+//! Field elements cross the program boundary as 32-byte big-endian values,
+//! the encoding groth16-solana uses for public inputs.
+//!
+//! The parameters are circomlib's, which the `sol_poseidon` syscall also
+//! implements; the permutation runs in BPF so the tree does not depend on
+//! the syscall being enabled on a cluster.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, Field, PrimeField};
+
+use crate::poseidon_constants::{
+    FULL_ROUNDS, MDS_MATRIX, PARTIAL_ROUNDS, ROUND_CONSTANTS, WIDTH,
+};
+
+/// Hash two field elements: `Poseidon([0, a, b])[0]`
+pub fn hash2(a: &Fr, b: &Fr) -> Fr {
+    let mut state = [Fr::from(0u64), *a, *b];
+    permute(&mut state);
+    state[0]
+}
+
+/// Hash two big-endian encoded field elements (reduced mod r)
+pub fn hash_pair_be(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let left = Fr::from_be_bytes_mod_order(left);
+    let right = Fr::from_be_bytes_mod_order(right);
+    fr_to_be(&hash2(&left, &right))
+}
+
+/// Big-endian encoding of a field element
+pub fn fr_to_be(value: &Fr) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&value.into_bigint().to_bytes_be());
+    out
+}
+
+fn permute(state: &mut [Fr; WIDTH]) {
+    let mut round_ctr = 0;
+
+    for round in 0..(FULL_ROUNDS + PARTIAL_ROUNDS) {
+        for i in 0..WIDTH {
+            state[i] += ROUND_CONSTANTS[round_ctr + i];
+        }
+        round_ctr += WIDTH;
+
+        // Partial rounds sit between FULL_ROUNDS / 2 full rounds on each side
+        let partial = (FULL_ROUNDS / 2..FULL_ROUNDS / 2 + PARTIAL_ROUNDS).contains(&round);
+        if partial {
+            state[0] = sbox(state[0]);
+        } else {
+            for elem in state.iter_mut() {
+                *elem = sbox(*elem);
+            }
+        }
+
+        mds_multiply(state);
+    }
+}
+
+/// S-box: x^5
+#[inline]
+fn sbox(x: Fr) -> Fr {
+    let x4 = x.square().square();
+    x4 * x
+}
+
+fn mds_multiply(state: &mut [Fr; WIDTH]) {
+    let mut new_state = [Fr::from(0u64); WIDTH];
+    for (row, out) in MDS_MATRIX.iter().zip(new_state.iter_mut()) {
+        for (m, s) in row.iter().zip(state.iter()) {
+            *out += *m * s;
+        }
+    }
+    *state = new_state;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex32(s: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_matches_core_poseidon() {
+        // veil_core::crypto::poseidon::poseidon_hash2(1, 2), big-endian; also
+        // circomlibjs poseidon([1, 2])
+        let expected = hex32("115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a");
+        assert_eq!(fr_to_be(&hash2(&Fr::from(1u64), &Fr::from(2u64))), expected);
+
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let mut two = [0u8; 32];
+        two[31] = 2;
+        assert_eq!(hash_pair_be(&one, &two), expected);
+    }
+
+    #[test]
+    fn test_circomlib_vectors() {
+        // circomlibjs poseidon([1, 1]) and poseidon([0, 0])
+        let one = hash2(&Fr::from(1u64), &Fr::from(1u64));
+        let expected = hex32("007af346e2d304279e79e0a9f3023f771294a78acb70e73f90afe27cad401e81");
+        assert_eq!(fr_to_be(&one), expected);
+
+        let zero = hash2(&Fr::from(0u64), &Fr::from(0u64));
+        let expected = hex32("2098f5fb9e239eab3ceac3f27b81e481dc3124d55ffed523a839ee8446b64864");
+        assert_eq!(fr_to_be(&zero), expected);
+    }
+}
@@ -4,52 +4,505 @@
 
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
-use anchor_spl::token;
+use anchor_spl::memo::{self, BuildMemo, Memo};
+use anchor_spl::token_interface::{self, TransferChecked};
 
-use crate::instructions::NyxError;
-use crate::merkle::TREE_DEPTH;
+use crate::compression;
+use crate::events::{
+    CommitmentInserted, CompressedTreeEnabled, DepositCapsUpdated, FeesCollected, NullifierSpent,
+    ProtocolFeeUpdated, RelayerDeregistered, RelayerFeeUpdated, RelayerRegistered,
+    RootHistoryResized, TreeRotated, Unshielded, WithdrawalDelayUpdated, WithdrawalQueued,
+};
+use crate::groth16::{Groth16Error, UnshieldVerifyingKeyData, VerifyingKeyData};
+use crate::instructions::{
+    validate_encrypted_note, validate_memo, NyxError, MAX_EXTRA_NULLIFIERS,
+};
+use crate::nullifier::{self, NullifierArchive, NullifierMarker, ARCHIVE_SEED};
+use crate::relayer;
 use crate::token as pool_token;
 use crate::verification::{self, MvpProof};
-use crate::{Initialize, Shield, ShieldSol, Transfer, Unshield, UnshieldSol};
-
-/// Maximum leaves in tree (2^20)
-const MAX_COMMITMENTS: u64 = 1 << TREE_DEPTH;
+use crate::state::{
+    ArchivedRoot, PendingWithdrawal, PrivacyPool, RootHistory, NATIVE_MINT, PAUSE_ALL, PAUSE_SHIELD,
+    PAUSE_TRANSFER, PAUSE_UNSHIELD, PENDING_WITHDRAWAL_SEED, POOL_SEED, TREASURY_SEED,
+};
+use crate::{
+    AcceptAuthority, CollectFees, CompactNullifiers, DeregisterRelayer, EnableZk,
+    FinalizeWithdrawal, FinalizeWithdrawalSol, Initialize, InitializePoolForMint,
+    ProposeAuthority, RegisterRelayer, ResizeRootHistory, RotateTree, SetDepositCaps,
+    SetNullifierFinality, SetPauseState, SetProtocolFee, SetVerifyingKey, SetWithdrawalDelay,
+    Shield, ShieldSol, ShieldSolCpi, Transfer, TransferJoinSplit, Unshield, UnshieldSol,
+    UnshieldWithAta, UpdateRelayerFee, UseCompressedTree,
+};
 
 /// Process Initialize instruction
-pub fn process_initialize(ctx: Context<Initialize>) -> Result<()> {
+pub fn process_initialize(ctx: Context<Initialize>, root_history_size: u16) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     // Initialize with real Merkle tree
-    pool.initialize(ctx.accounts.authority.key(), ctx.bumps.pool);
+    pool.initialize(ctx.accounts.authority.key(), NATIVE_MINT, ctx.bumps.pool);
+
+    let verifying_key = &mut ctx.accounts.verifying_key;
+    verifying_key.pool = pool.key();
+    verifying_key.bump = ctx.bumps.verifying_key;
 
-    msg!("Privacy pool initialized");
+    ctx.accounts.root_history.initialize(
+        pool.key(),
+        root_history_size,
+        ctx.bumps.root_history,
+    )?;
+
+    msg!("Privacy pool initialized for native SOL");
     msg!("Initial root: {:?}", pool.current_root());
     Ok(())
 }
 
-/// Process Shield SOL instruction
-pub fn process_shield_sol(ctx: Context<ShieldSol>, commitment: [u8; 32], amount: u64) -> Result<()> {
+/// Process InitializePoolForMint instruction
+pub fn process_initialize_pool_for_mint(
+    ctx: Context<InitializePoolForMint>,
+    root_history_size: u16,
+) -> Result<()> {
+    let mint = ctx.accounts.mint.key();
+    let pool = &mut ctx.accounts.pool;
+
+    pool.initialize(ctx.accounts.authority.key(), mint, ctx.bumps.pool);
+
+    let verifying_key = &mut ctx.accounts.verifying_key;
+    verifying_key.pool = pool.key();
+    verifying_key.bump = ctx.bumps.verifying_key;
+
+    ctx.accounts.root_history.initialize(
+        pool.key(),
+        root_history_size,
+        ctx.bumps.root_history,
+    )?;
+
+    msg!("Privacy pool initialized for mint {}", mint);
+    msg!("Initial root: {:?}", pool.current_root());
+    Ok(())
+}
+
+/// Process SetVerifyingKey instruction
+pub fn process_set_verifying_key(
+    ctx: Context<SetVerifyingKey>,
+    key: VerifyingKeyData,
+    lock: bool,
+) -> Result<()> {
+    let verifying_key = &mut ctx.accounts.verifying_key;
+
+    require!(!verifying_key.locked, Groth16Error::VkLocked);
+    require!(key.is_initialized(), Groth16Error::VkNotInitialized);
+
+    verifying_key.key = key;
+    verifying_key.locked = lock;
+
+    msg!("Verifying key set for pool {}", verifying_key.pool);
+    if lock {
+        msg!("Verifying key locked");
+    }
+    Ok(())
+}
+
+/// Process SetUnshieldVerifyingKey instruction
+pub fn process_set_unshield_verifying_key(
+    ctx: Context<SetVerifyingKey>,
+    key: UnshieldVerifyingKeyData,
+    lock: bool,
+) -> Result<()> {
+    let verifying_key = &mut ctx.accounts.verifying_key;
+
+    require!(!verifying_key.locked, Groth16Error::VkLocked);
+    require!(key.is_initialized(), Groth16Error::VkNotInitialized);
+
+    verifying_key.unshield_key = key;
+    verifying_key.locked = lock;
+
+    msg!("Unshield verifying key set for pool {}", verifying_key.pool);
+    if lock {
+        msg!("Verifying keys locked");
+    }
+    Ok(())
+}
+
+/// Process EnableZk instruction
+pub fn process_enable_zk(ctx: Context<EnableZk>) -> Result<()> {
+    // Transfers and unshields each need their key
+    require!(ctx.accounts.verifying_key.is_initialized(), Groth16Error::VkNotInitialized);
+
+    let pool = &mut ctx.accounts.pool;
+    require!(!pool.is_compressed(), NyxError::CompressedTreeUnsupported);
+    pool.zk_enabled = true;
+
+    msg!("Groth16 proofs enabled for pool {}", pool.key());
+    Ok(())
+}
+
+/// Process SetPauseState instruction
+pub fn process_set_pause_state(ctx: Context<SetPauseState>, paused: u8) -> Result<()> {
+    require!(paused & !PAUSE_ALL == 0, NyxError::InvalidPauseFlags);
+
+    let pool = &mut ctx.accounts.pool;
+    pool.paused = paused;
+
+    msg!("Pool {} pause flags set to {:#05b}", pool.key(), paused);
+    Ok(())
+}
+
+/// Process ProposeAuthority instruction
+pub fn process_propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.propose_authority(new_authority);
+
+    if new_authority == Pubkey::default() {
+        msg!("Pending authority cleared for pool {}", pool.key());
+    } else {
+        msg!("Authority {} proposed for pool {}", new_authority, pool.key());
+    }
+    Ok(())
+}
+
+/// Process AcceptAuthority instruction
+pub fn process_accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let previous = pool.authority;
+    pool.accept_authority(&ctx.accounts.new_authority.key())?;
+
+    msg!("Pool {} authority transferred from {} to {}", pool.key(), previous, pool.authority);
+    Ok(())
+}
+
+/// Process UpdateRelayerFee instruction
+pub fn process_update_relayer_fee(
+    ctx: Context<UpdateRelayerFee>,
+    new_fee_bps: u16,
+    delay_slots: u64,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let slot = Clock::get()?.slot;
+
+    // Settle an earlier change that is already due before replacing it
+    pool.apply_pending_relayer_fee(slot);
+    let old_fee_bps = pool.relayer_fee_bps;
+
+    // Slot 0 means "nothing scheduled"; a zero delay applies right away
+    let effective_slot = slot.saturating_add(delay_slots).max(1);
+    pool.schedule_relayer_fee(new_fee_bps, effective_slot)?;
+    pool.apply_pending_relayer_fee(slot);
+
+    emit!(RelayerFeeUpdated {
+        old_fee_bps,
+        new_fee_bps,
+        effective_slot,
+    });
+    msg!("Relayer fee {} -> {} bps from slot {}", old_fee_bps, new_fee_bps, effective_slot);
+    Ok(())
+}
+
+/// Process SetNullifierFinality instruction
+pub fn process_set_nullifier_finality(ctx: Context<SetNullifierFinality>, epochs: u16) -> Result<()> {
+    ctx.accounts.pool.set_nullifier_finality_epochs(epochs)?;
+    msg!("Nullifier finality window set to {} epochs", epochs);
+    Ok(())
+}
+
+/// Process CompactNullifiers instruction
+pub fn process_compact_nullifiers(
+    ctx: Context<CompactNullifiers>,
+    tree_epoch: u32,
+    bucket: u8,
+) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+    let pool_key = pool.key();
+    let archive = ctx.accounts.nullifier_archive.to_account_info();
+    let treasury = &ctx.accounts.treasury;
+    let clock = Clock::get()?;
+    let epoch_schedule = EpochSchedule::get()?;
+    let rent = Rent::get()?;
+
+    if archive.data_is_empty() {
+        create_nullifier_archive(&ctx, tree_epoch, bucket, &rent)?;
+    }
+    require!(archive.owner == &crate::ID, NyxError::InvalidNullifierArchive);
+    let mut header = NullifierArchive::try_deserialize(&mut &archive.try_borrow_data()?[..])?;
+    require!(
+        header.pool == pool_key && header.tree_epoch == tree_epoch && header.bucket == bucket,
+        NyxError::InvalidNullifierArchive
+    );
+
+    // Close each final marker into the bucket, keeping its nullifier
+    let mut nullifiers = Vec::with_capacity(ctx.remaining_accounts.len());
+    for marker_info in ctx.remaining_accounts {
+        require!(
+            marker_info.owner == &crate::ID && marker_info.is_writable,
+            NyxError::InvalidNullifierMarker
+        );
+        let marker = NullifierMarker::try_deserialize(&mut &marker_info.try_borrow_data()?[..])?;
+        require!(
+            marker.pool == pool_key
+                && marker.tree_epoch == tree_epoch
+                && nullifier::archive_bucket(&marker.nullifier) == bucket,
+            NyxError::InvalidNullifierMarker
+        );
+        require!(
+            pool.is_nullifier_final(epoch_schedule.get_epoch(marker.spent_at), clock.epoch),
+            NyxError::NullifierNotFinal
+        );
+
+        let lamports = marker_info.lamports();
+        **marker_info.try_borrow_mut_lamports()? = 0;
+        **archive.try_borrow_mut_lamports()? += lamports;
+        marker_info.assign(&system_program::ID);
+        marker_info.realloc(0, false)?;
+        nullifiers.push(marker.nullifier);
+    }
+
+    // Grow the bucket and insert, keeping it sorted
+    let count = header.count as usize;
+    archive.realloc(NullifierArchive::ENTRIES_OFFSET + (count + nullifiers.len()) * 32, false)?;
+    let mut inserted = 0;
+    {
+        let mut data = archive.try_borrow_mut_data()?;
+        let entries = &mut data[NullifierArchive::ENTRIES_OFFSET..];
+        for nullifier in &nullifiers {
+            if nullifier::archive_insert(entries, count + inserted, nullifier) {
+                inserted += 1;
+            }
+        }
+    }
+    archive.realloc(NullifierArchive::ENTRIES_OFFSET + (count + inserted) * 32, false)?;
+    header.count += inserted as u32;
+    header.try_serialize(&mut &mut archive.try_borrow_mut_data()?[..])?;
+
+    // Everything above the bucket's rent-exempt balance goes to the treasury
+    let reclaimed = archive
+        .lamports()
+        .saturating_sub(rent.minimum_balance(archive.data_len()));
+    **archive.try_borrow_mut_lamports()? -= reclaimed;
+    **treasury.try_borrow_mut_lamports()? += reclaimed;
+
+    msg!("Compacted {} nullifiers into bucket {}", inserted, bucket);
+    msg!("Reclaimed {} lamports to the treasury", reclaimed);
+    Ok(())
+}
+
+/// Create a `space`-byte account owned by this program at the PDA `seeds`
+///
+/// `payer` tops the address up to rent exemption, so lamports already sent
+/// to it do not block creation.
+fn create_program_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+    rent: &Rent,
+) -> Result<()> {
+    let top_up = rent.minimum_balance(space).saturating_sub(account.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[seeds],
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system.clone(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[seeds],
+        ),
+        &crate::ID,
+    )?;
+    Ok(())
+}
+
+/// Create an empty archive bucket, funded by the pool authority
+fn create_nullifier_archive(
+    ctx: &Context<CompactNullifiers>,
+    tree_epoch: u32,
+    bucket: u8,
+    rent: &Rent,
+) -> Result<()> {
+    let archive = ctx.accounts.nullifier_archive.to_account_info();
+    let system = ctx.accounts.system_program.to_account_info();
+    let pool_key = ctx.accounts.pool.key();
+    let bump = [ctx.bumps.nullifier_archive];
+    let epoch = tree_epoch.to_le_bytes();
+    let seeds: &[&[u8]] = &[
+        ARCHIVE_SEED,
+        pool_key.as_ref(),
+        &epoch,
+        core::slice::from_ref(&bucket),
+        &bump,
+    ];
+    create_program_account(
+        &archive,
+        &ctx.accounts.authority.to_account_info(),
+        &system,
+        NullifierArchive::ENTRIES_OFFSET,
+        seeds,
+        rent,
+    )?;
+
+    let header = NullifierArchive {
+        pool: pool_key,
+        tree_epoch,
+        bucket,
+        count: 0,
+    };
+    header.try_serialize(&mut &mut archive.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Process RotateTree instruction
+pub fn process_rotate_tree(ctx: Context<RotateTree>) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
+    let archived_root = &mut ctx.accounts.archived_root;
+
+    let old_epoch = pool.tree_epoch;
+    let final_root = pool.rotate_tree(&mut ctx.accounts.root_history)?;
+    archived_root.pool = pool.key();
+    archived_root.tree_epoch = old_epoch;
+    archived_root.root = final_root;
+
+    emit!(TreeRotated {
+        archived_epoch: old_epoch,
+        final_root,
+        tree_epoch: pool.tree_epoch,
+    });
+    msg!("Tree {} archived, now on tree {}", old_epoch, pool.tree_epoch);
+    Ok(())
+}
+
+/// Process ResizeRootHistory instruction
+pub fn process_resize_root_history(ctx: Context<ResizeRootHistory>, size: u16) -> Result<()> {
+    ctx.accounts.root_history.resize(size)?;
+    emit!(RootHistoryResized { size });
+    msg!("Root history now keeps {} roots", size);
+    Ok(())
+}
+
+/// Check the root a spend from tree `tree_epoch` is proven against
+///
+/// For the live tree, its current root or one still in `root_history`; for
+/// a rotated-out tree, its final root (`archived_root` is that tree's PDA,
+/// checked by the seeds constraint).
+fn check_spend_root(
+    pool: &PrivacyPool,
+    root_history: &RootHistory,
+    tree_epoch: u32,
+    root: &[u8; 32],
+    archived_root: &AccountInfo,
+) -> Result<()> {
+    if tree_epoch == pool.tree_epoch {
+        require!(pool.is_valid_root(root, root_history), NyxError::UnknownRoot);
+        return Ok(());
+    }
+    require!(
+        tree_epoch < pool.tree_epoch && archived_root.owner == &crate::ID,
+        NyxError::InvalidTreeEpoch
+    );
+    let archived = ArchivedRoot::try_deserialize(&mut &archived_root.try_borrow_data()?[..])?;
+    require!(archived.root == *root, NyxError::UnknownRoot);
+    Ok(())
+}
+
+/// Process Shield SOL instruction
+pub fn process_shield_sol<'info>(
+    ctx: Context<'_, '_, '_, 'info, ShieldSol<'info>>,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    deposit_sol(
+        &mut accounts.pool,
+        &mut accounts.root_history,
+        accounts.depositor.to_account_info(),
+        accounts.vault.to_account_info(),
+        accounts.system_program.to_account_info(),
+        ctx.remaining_accounts,
+        commitment,
+        amount,
+        encrypted_note,
+    )
+}
+
+/// Process Shield SOL (CPI) instruction
+pub fn process_shield_sol_cpi<'info>(
+    ctx: Context<'_, '_, '_, 'info, ShieldSolCpi<'info>>,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    deposit_sol(
+        &mut accounts.pool,
+        &mut accounts.root_history,
+        accounts.source.to_account_info(),
+        accounts.vault.to_account_info(),
+        accounts.system_program.to_account_info(),
+        ctx.remaining_accounts,
+        commitment,
+        amount,
+        encrypted_note,
+    )
+}
 
+/// Move `amount` lamports from `source` into the vault and insert `commitment`
+#[allow(clippy::too_many_arguments)]
+fn deposit_sol<'info>(
+    pool: &mut Account<'info, PrivacyPool>,
+    root_history: &mut RootHistory,
+    source: AccountInfo<'info>,
+    vault: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    tree_accounts: &[AccountInfo<'info>],
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+) -> Result<()> {
     // Validate
+    pool.require_not_paused(PAUSE_SHIELD)?;
     require!(amount > 0, NyxError::InvalidAmount);
+    validate_encrypted_note(&encrypted_note)?;
     require!(
-        pool.commitment_count() < MAX_COMMITMENTS,
+        pool.commitment_count() < pool.capacity(),
         NyxError::PoolFull
     );
+    pool.check_deposit_caps(amount, vault.lamports())?;
 
-    // Transfer SOL from depositor to vault
+    // Transfer SOL from source to vault
     let cpi_context = CpiContext::new(
-        ctx.accounts.system_program.to_account_info(),
-        system_program::Transfer {
-            from: ctx.accounts.depositor.to_account_info(),
-            to: ctx.accounts.vault.to_account_info(),
-        },
+        system_program,
+        system_program::Transfer { from: source, to: vault },
     );
     system_program::transfer(cpi_context, amount)?;
 
     // Add commitment to tree
-    let leaf_index = pool.add_commitment(commitment)?;
+    let leaf_index = insert_commitment(pool, root_history, tree_accounts, commitment)?;
+    emit!(CommitmentInserted {
+        commitment,
+        leaf_index,
+        new_root: pool.current_root(),
+        encrypted_note,
+    });
 
     msg!("Shielded {} lamports at index {}", amount, leaf_index);
     msg!("New root: {:?}", pool.current_root());
@@ -58,75 +511,248 @@ pub fn process_shield_sol(ctx: Context<ShieldSol>, commitment: [u8; 32], amount:
 }
 
 /// Process Shield SPL token instruction
-pub fn process_shield(ctx: Context<Shield>, commitment: [u8; 32], amount: u64) -> Result<()> {
+pub fn process_shield<'info>(
+    ctx: Context<'_, '_, '_, 'info, Shield<'info>>,
+    commitment: [u8; 32],
+    amount: u64,
+    encrypted_note: Vec<u8>,
+) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     // Validate
+    pool.require_not_paused(PAUSE_SHIELD)?;
     require!(amount > 0, NyxError::InvalidAmount);
+    validate_encrypted_note(&encrypted_note)?;
     require!(
-        pool.commitment_count() < MAX_COMMITMENTS,
+        pool.commitment_count() < pool.capacity(),
         NyxError::PoolFull
     );
+    pool.check_deposit_caps(amount, ctx.accounts.vault_token_account.amount)?;
 
     // Transfer SPL tokens from depositor to vault
-    let cpi_accounts = token::Transfer {
-        from: ctx.accounts.depositor_token_account.to_account_info(),
-        to: ctx.accounts.vault_token_account.to_account_info(),
-        authority: ctx.accounts.depositor.to_account_info(),
-    };
-    let cpi_context = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        cpi_accounts,
-    );
-    token::transfer(cpi_context, amount)?;
+    pool_token::transfer_spl_to_pool(
+        &ctx.accounts.depositor_token_account,
+        &mut ctx.accounts.vault_token_account,
+        &ctx.accounts.mint,
+        &ctx.accounts.depositor,
+        &ctx.accounts.token_program,
+        amount,
+    )?;
 
     // Add commitment to tree
-    let leaf_index = pool.add_commitment(commitment)?;
+    let leaf_index = insert_commitment(
+        pool,
+        &mut ctx.accounts.root_history,
+        ctx.remaining_accounts,
+        commitment,
+    )?;
+    emit!(CommitmentInserted {
+        commitment,
+        leaf_index,
+        new_root: pool.current_root(),
+        encrypted_note,
+    });
 
-    msg!("Shielded {} tokens at index {}", amount, leaf_index);
+    msg!("Shielded {} tokens of {} at index {}", amount, pool.mint, leaf_index);
     msg!("New root: {:?}", pool.current_root());
 
     Ok(())
 }
 
+/// Insert `commitment` into the pool's tree, local or compressed
+///
+/// `tree_accounts` are the instruction's trailing (tree, noop, compression
+/// program) accounts, passed only for a compressed tree. The replaced root
+/// goes to `root_history`.
+fn insert_commitment<'info>(
+    pool: &mut Account<'info, PrivacyPool>,
+    root_history: &mut RootHistory,
+    tree_accounts: &[AccountInfo<'info>],
+    commitment: [u8; 32],
+) -> Result<u64> {
+    if !pool.is_compressed() {
+        require!(tree_accounts.is_empty(), NyxError::InvalidCompressedTree);
+        return pool.add_commitment(commitment, root_history);
+    }
+    require!(pool.commitment_count() < pool.capacity(), NyxError::PoolFull);
+    pool.check_new_commitment(&commitment)?;
+
+    let mint = pool.mint;
+    let bump = [pool.bump];
+    let seeds: &[&[u8]] = &[POOL_SEED, mint.as_ref(), &bump];
+    let (new_root, leaf_index) = compression::append(
+        tree_accounts,
+        &pool.to_account_info(),
+        &pool.compressed_tree,
+        pool.compressed_tree_depth,
+        commitment,
+        seeds,
+    )?;
+    pool.record_compressed_commitment(commitment, new_root, leaf_index, root_history);
+    Ok(leaf_index)
+}
+
 /// Process Transfer instruction
-pub fn process_transfer(
-    ctx: Context<Transfer>,
+#[allow(clippy::too_many_arguments)]
+pub fn process_transfer<'info>(
+    ctx: Context<'_, '_, '_, 'info, Transfer<'info>>,
+    tree_epoch: u32,
+    root: [u8; 32],
     nullifier: [u8; 32],
     new_commitment: [u8; 32],
+    fee: u64,
     proof: Vec<u8>,
+    encrypted_note: Vec<u8>,
+    extra_nullifiers: Vec<[u8; 32]>,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     let nullifier_marker = &mut ctx.accounts.nullifier_marker;
     let clock = Clock::get()?;
 
+    pool.require_not_paused(PAUSE_TRANSFER)?;
+
     // Validate proof length (96 bytes for MVP: 64 signature + 32 pubkey)
     require!(proof.len() >= MvpProof::SIZE, NyxError::InvalidProof);
+    validate_encrypted_note(&encrypted_note)?;
+    require!(
+        extra_nullifiers.len() <= MAX_EXTRA_NULLIFIERS,
+        NyxError::TooManyNullifiers
+    );
+    let tree_accounts_len = if pool.is_compressed() {
+        compression::TREE_ACCOUNTS_LEN
+    } else {
+        0
+    };
+    require!(
+        ctx.remaining_accounts.len() == 2 * extra_nullifiers.len() + tree_accounts_len,
+        NyxError::InvalidNullifierMarker
+    );
+    let (extra_accounts, tree_accounts) =
+        ctx.remaining_accounts.split_at(2 * extra_nullifiers.len());
+    let mut nullifiers = Vec::with_capacity(1 + extra_nullifiers.len());
+    nullifiers.push(nullifier);
+    for extra in &extra_nullifiers {
+        require!(!nullifiers.contains(extra), NyxError::DuplicateNullifier);
+        nullifiers.push(*extra);
+    }
 
-    // Note: Double-spend prevention is handled by Anchor's init constraint
+    // Double-spend prevention: Anchor's init constraint on the marker, and
+    // the archive bucket for compacted nullifiers
+    nullifier::require_not_archived(&ctx.accounts.nullifier_archive, &nullifier)?;
 
-    // Get current root for verification
-    let root = pool.current_root();
+    // Root of the tree holding the spent note(s)
+    let root_history = &mut ctx.accounts.root_history;
+    check_spend_root(pool, root_history, tree_epoch, &root, &ctx.accounts.archived_root)?;
 
     // Verify the proof
-    let valid = verification::verify_transfer_proof(
-        &proof,
-        &nullifier,
-        &new_commitment,
-        &root,
-    )?;
+    let valid = if extra_nullifiers.is_empty() {
+        verification::verify_transfer_proof(
+            &proof,
+            &ctx.accounts.verifying_key.key,
+            &nullifier,
+            &new_commitment,
+            &root,
+            &pool.mint,
+            fee,
+            pool.zk_enabled,
+        )?
+    } else {
+        verification::verify_sweep_proof(
+            &proof,
+            &nullifiers,
+            &new_commitment,
+            &root,
+            fee,
+            pool.zk_enabled,
+        )?
+    };
     require!(valid, NyxError::InvalidProof);
 
     // Initialize nullifier marker (marks nullifier as spent)
     nullifier_marker.pool = pool.key();
+    nullifier_marker.tree_epoch = tree_epoch;
     nullifier_marker.nullifier = nullifier;
     nullifier_marker.spent_at = clock.slot;
 
+    // Extra inputs: check each archive bucket, then create its marker
+    let rent = Rent::get()?;
+    for (accounts, extra) in extra_accounts.chunks(2).zip(&extra_nullifiers) {
+        let (archive, _) = nullifier::derive_archive_pda(
+            &crate::ID,
+            &pool.key(),
+            tree_epoch,
+            nullifier::archive_bucket(extra),
+        );
+        require!(accounts[1].key() == archive, NyxError::InvalidNullifierArchive);
+        nullifier::require_not_archived(&accounts[1], extra)?;
+
+        let marker = NullifierMarker {
+            pool: pool.key(),
+            tree_epoch,
+            nullifier: *extra,
+            spent_at: clock.slot,
+        };
+        create_nullifier_marker(
+            &accounts[0],
+            &ctx.accounts.relayer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &marker,
+            &rent,
+        )?;
+    }
+
     // Record in pool stats
-    pool.record_nullifier_spent();
+    for nullifier in nullifiers {
+        emit!(NullifierSpent {
+            nullifier,
+            slot: clock.slot,
+        });
+        pool.record_nullifier_spent();
+    }
 
     // Add new commitment
-    let leaf_index = pool.add_commitment(new_commitment)?;
+    let leaf_index = insert_commitment(pool, root_history, tree_accounts, new_commitment)?;
+    emit!(CommitmentInserted {
+        commitment: new_commitment,
+        leaf_index,
+        new_root: pool.current_root(),
+        encrypted_note,
+    });
+
+    // Pay the relayer the fee the proof set aside from the input
+    if fee > 0 {
+        pool.record_fee_collected(fee);
+        let pool_key = pool.key();
+        let native = pool.mint == NATIVE_MINT;
+        let accounts = &ctx.accounts;
+        if native {
+            let vault = &accounts.vault;
+            require!(vault.lamports() >= fee, pool_token::TokenError::InsufficientFunds);
+            **vault.try_borrow_mut_lamports()? -= fee;
+            **accounts.relayer.try_borrow_mut_lamports()? += fee;
+        } else {
+            let token_accounts = (
+                &accounts.mint,
+                &accounts.vault_token_account,
+                &accounts.relayer_token_account,
+                &accounts.token_program,
+            );
+            let (Some(mint), Some(from), Some(to), Some(token_program)) = token_accounts else {
+                return err!(NyxError::MissingVaultAccount);
+            };
+            pool_token::transfer_spl_from_pool(
+                from,
+                to,
+                mint,
+                &accounts.vault,
+                token_program,
+                fee,
+                &pool_key,
+                ctx.bumps.vault,
+            )?;
+        }
+        msg!("Relayer fee: {}", fee);
+    }
 
     msg!("Private transfer complete");
     msg!("New commitment at index {}", leaf_index);
@@ -135,124 +761,752 @@ pub fn process_transfer(
     Ok(())
 }
 
+/// Create the marker PDA for an extra transfer input, paid by `payer`
+///
+/// Fails with `NullifierSpent` if the marker already exists.
+fn create_nullifier_marker<'info>(
+    marker_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    marker: &NullifierMarker,
+    rent: &Rent,
+) -> Result<()> {
+    let (address, bump) = nullifier::derive_nullifier_pda(
+        &crate::ID,
+        &marker.pool,
+        marker.tree_epoch,
+        &marker.nullifier,
+    );
+    require!(
+        marker_info.key() == address && marker_info.is_writable,
+        NyxError::InvalidNullifierMarker
+    );
+    require!(
+        marker_info.owner == &system_program::ID && marker_info.data_is_empty(),
+        NyxError::NullifierSpent
+    );
+
+    let epoch = marker.tree_epoch.to_le_bytes();
+    let bump = [bump];
+    let seeds: &[&[u8]] = &[
+        nullifier::NULLIFIER_SEED,
+        marker.pool.as_ref(),
+        &epoch,
+        &marker.nullifier,
+        &bump,
+    ];
+    create_program_account(
+        marker_info,
+        payer,
+        system,
+        nullifier::NULLIFIER_ACCOUNT_SIZE,
+        seeds,
+        rent,
+    )?;
+
+    marker.try_serialize(&mut &mut marker_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Process TransferJoinSplit instruction
+pub fn process_transfer_joinsplit<'info>(
+    ctx: Context<'_, '_, '_, 'info, TransferJoinSplit<'info>>,
+    tree_epoch: u32,
+    root: [u8; 32],
+    nullifiers: [[u8; 32]; 2],
+    new_commitments: [[u8; 32]; 2],
+    proof: Vec<u8>,
+    mut encrypted_notes: Vec<Vec<u8>>,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let clock = Clock::get()?;
+
+    pool.require_not_paused(PAUSE_TRANSFER)?;
+
+    // Validate
+    require!(proof.len() >= MvpProof::SIZE, NyxError::InvalidProof);
+    require!(nullifiers[0] != nullifiers[1], NyxError::DuplicateNullifier);
+    require!(encrypted_notes.len() <= 2, NyxError::TooManyEncryptedNotes);
+    for encrypted_note in &encrypted_notes {
+        validate_encrypted_note(encrypted_note)?;
+    }
+    encrypted_notes.resize(2, Vec::new());
+
+    verification::require_proof_enabled(&proof, pool.zk_enabled)?;
+
+    // Double-spend prevention: Anchor's init constraint on the markers, and
+    // the archive buckets for compacted nullifiers
+    nullifier::require_not_archived(&ctx.accounts.nullifier_archive_0, &nullifiers[0])?;
+    nullifier::require_not_archived(&ctx.accounts.nullifier_archive_1, &nullifiers[1])?;
+
+    // Root of the tree holding the spent note(s)
+    let root_history = &mut ctx.accounts.root_history;
+    check_spend_root(pool, root_history, tree_epoch, &root, &ctx.accounts.archived_root)?;
+
+    // Verify the proof
+    let valid = verification::verify_joinsplit_proof(
+        &proof,
+        &nullifiers,
+        &new_commitments,
+        &root,
+        pool.zk_enabled,
+    )?;
+    require!(valid, NyxError::InvalidProof);
+
+    // Initialize both nullifier markers (marks nullifiers as spent)
+    let markers = [
+        &mut ctx.accounts.nullifier_marker_0,
+        &mut ctx.accounts.nullifier_marker_1,
+    ];
+    for (marker, nullifier) in markers.into_iter().zip(nullifiers) {
+        marker.pool = pool.key();
+        marker.tree_epoch = tree_epoch;
+        marker.nullifier = nullifier;
+        marker.spent_at = clock.slot;
+        pool.record_nullifier_spent();
+        emit!(NullifierSpent {
+            nullifier,
+            slot: clock.slot,
+        });
+    }
+
+    // Add both outputs; a full tree fails the whole instruction
+    for (commitment, encrypted_note) in new_commitments.into_iter().zip(encrypted_notes) {
+        let leaf_index =
+            insert_commitment(pool, root_history, ctx.remaining_accounts, commitment)?;
+        emit!(CommitmentInserted {
+            commitment,
+            leaf_index,
+            new_root: pool.current_root(),
+            encrypted_note,
+        });
+        msg!("New commitment at index {}", leaf_index);
+    }
+
+    msg!("Join-split transfer complete");
+    msg!("Nullifiers spent at slot {}", clock.slot);
+
+    Ok(())
+}
+
 /// Process Unshield SOL instruction
+#[allow(clippy::too_many_arguments)]
 pub fn process_unshield_sol(
     ctx: Context<UnshieldSol>,
+    tree_epoch: u32,
+    root: [u8; 32],
     nullifier: [u8; 32],
     amount: u64,
     proof: Vec<u8>,
+    memo: Vec<u8>,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     let nullifier_marker = &mut ctx.accounts.nullifier_marker;
     let clock = Clock::get()?;
 
     // Validate
+    pool.require_not_paused(PAUSE_UNSHIELD)?;
     require!(amount > 0, NyxError::InvalidAmount);
     require!(proof.len() >= MvpProof::SIZE, NyxError::InvalidProof);
+    validate_memo(&memo)?;
 
-    // Note: Double-spend prevention is handled by Anchor's init constraint
+    // Double-spend prevention: Anchor's init constraint on the marker, and
+    // the archive bucket for compacted nullifiers
+    nullifier::require_not_archived(&ctx.accounts.nullifier_archive, &nullifier)?;
 
-    // Get current root for verification
-    let root = pool.current_root();
+    // Root of the tree holding the spent note(s)
+    check_spend_root(
+        pool,
+        &ctx.accounts.root_history,
+        tree_epoch,
+        &root,
+        &ctx.accounts.archived_root,
+    )?;
     let recipient_key = ctx.accounts.recipient.key();
 
+    // The proof binds the relayer fee, so it is fixed before verifying
+    pool.apply_pending_relayer_fee(clock.slot);
+    let (payout, fee) = pool.split_relayer_fee(amount);
+
     // Verify the proof
     let valid = verification::verify_unshield_proof(
         &proof,
+        &ctx.accounts.verifying_key.unshield_key,
         &nullifier,
         &recipient_key,
         amount,
+        fee,
         &root,
+        &pool.mint,
+        pool.zk_enabled,
     )?;
     require!(valid, NyxError::InvalidProof);
 
     // Initialize nullifier marker (marks nullifier as spent)
     nullifier_marker.pool = pool.key();
+    nullifier_marker.tree_epoch = tree_epoch;
     nullifier_marker.nullifier = nullifier;
     nullifier_marker.spent_at = clock.slot;
+    emit!(NullifierSpent {
+        nullifier,
+        slot: clock.slot,
+    });
 
     // Record in pool stats
     pool.record_nullifier_spent();
 
-    // Transfer SOL from vault to recipient
+    // Pay the recipient and the relayer from the vault
+    pool.record_fee_collected(fee);
+    let (fee, protocol_fee) = pool.split_protocol_fee(fee);
+
     let vault = &ctx.accounts.vault;
     let recipient = &ctx.accounts.recipient;
+    let relayer = &ctx.accounts.relayer;
+
+    // The protocol's share goes to the treasury even if the payout is delayed
+    if protocol_fee > 0 {
+        require!(vault.lamports() >= protocol_fee, pool_token::TokenError::InsufficientFunds);
+        **vault.try_borrow_mut_lamports()? -= protocol_fee;
+        **ctx.accounts.treasury.try_borrow_mut_lamports()? += protocol_fee;
+    }
+
+    log_memo(&ctx.accounts.memo_program, &memo)?;
+
+    // Behind a withdrawal delay, park the payout until it can be finalized
+    if let Some(unlock_slot) = pool.withdrawal_unlock_slot(clock.slot) {
+        let pool_key = pool.key();
+        let epoch = tree_epoch.to_le_bytes();
+        let bump = [ctx.bumps.pending_withdrawal];
+        let seeds: &[&[u8]] =
+            &[PENDING_WITHDRAWAL_SEED, pool_key.as_ref(), &epoch, &nullifier, &bump];
+        let pending = PendingWithdrawal {
+            pool: pool_key,
+            recipient: recipient_key,
+            fee_recipient: relayer.key(),
+            rent_payer: relayer.key(),
+            payout,
+            fee,
+            unlock_slot,
+        };
+        return queue_withdrawal(
+            &ctx.accounts.pending_withdrawal,
+            &relayer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &pending,
+            seeds,
+        );
+    }
 
     let vault_lamports = vault.lamports();
-    require!(vault_lamports >= amount, pool_token::TokenError::InsufficientFunds);
+    require!(vault_lamports >= payout + fee, pool_token::TokenError::InsufficientFunds);
 
-    **vault.try_borrow_mut_lamports()? -= amount;
-    **recipient.try_borrow_mut_lamports()? += amount;
+    **vault.try_borrow_mut_lamports()? -= payout + fee;
+    **recipient.try_borrow_mut_lamports()? += payout;
+    **relayer.try_borrow_mut_lamports()? += fee;
+
+    emit!(Unshielded {
+        recipient: recipient_key,
+        amount,
+    });
 
-    msg!("Unshielded {} lamports", amount);
+    msg!(
+        "Unshielded {} lamports (relayer fee {}, protocol fee {})",
+        amount,
+        fee,
+        protocol_fee
+    );
     msg!("Nullifier spent at slot {}", clock.slot);
 
     Ok(())
 }
 
 /// Process Unshield SPL token instruction
+#[allow(clippy::too_many_arguments)]
 pub fn process_unshield(
     ctx: Context<Unshield>,
+    tree_epoch: u32,
+    root: [u8; 32],
     nullifier: [u8; 32],
     amount: u64,
     proof: Vec<u8>,
+    memo: Vec<u8>,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     let nullifier_marker = &mut ctx.accounts.nullifier_marker;
     let clock = Clock::get()?;
 
     // Validate
+    pool.require_not_paused(PAUSE_UNSHIELD)?;
     require!(amount > 0, NyxError::InvalidAmount);
     require!(proof.len() >= MvpProof::SIZE, NyxError::InvalidProof);
+    validate_memo(&memo)?;
 
-    // Note: Double-spend prevention is handled by Anchor's init constraint
+    // Double-spend prevention: Anchor's init constraint on the marker, and
+    // the archive bucket for compacted nullifiers
+    nullifier::require_not_archived(&ctx.accounts.nullifier_archive, &nullifier)?;
 
-    // Get current root for verification
-    let root = pool.current_root();
+    // Root of the tree holding the spent note(s)
+    check_spend_root(
+        pool,
+        &ctx.accounts.root_history,
+        tree_epoch,
+        &root,
+        &ctx.accounts.archived_root,
+    )?;
     // For SPL tokens, use the token account owner as recipient
     let recipient_key = ctx.accounts.recipient_token_account.owner;
 
+    // The proof binds the relayer fee, so it is fixed before verifying
+    pool.apply_pending_relayer_fee(clock.slot);
+    let (payout, fee) = pool.split_relayer_fee(amount);
+
     // Verify the proof
     let valid = verification::verify_unshield_proof(
         &proof,
+        &ctx.accounts.verifying_key.unshield_key,
         &nullifier,
         &recipient_key,
         amount,
+        fee,
         &root,
+        &pool.mint,
+        pool.zk_enabled,
     )?;
     require!(valid, NyxError::InvalidProof);
 
     // Initialize nullifier marker (marks nullifier as spent)
     nullifier_marker.pool = pool.key();
+    nullifier_marker.tree_epoch = tree_epoch;
     nullifier_marker.nullifier = nullifier;
     nullifier_marker.spent_at = clock.slot;
+    emit!(NullifierSpent {
+        nullifier,
+        slot: clock.slot,
+    });
 
     // Record in pool stats
     pool.record_nullifier_spent();
 
-    // Transfer SPL tokens from vault to recipient
+    pool.record_fee_collected(fee);
+    let (fee, protocol_fee) = pool.split_protocol_fee(fee);
     let pool_key = pool.key();
     let vault_bump = ctx.bumps.vault_authority;
-    let signer_seeds: &[&[&[u8]]] = &[&[
-        pool_token::VAULT_SEED,
-        pool_key.as_ref(),
-        &[vault_bump],
-    ]];
 
-    let cpi_accounts = token::Transfer {
-        from: ctx.accounts.vault_token_account.to_account_info(),
-        to: ctx.accounts.recipient_token_account.to_account_info(),
-        authority: ctx.accounts.vault_authority.to_account_info(),
+    // The protocol's share goes to the treasury even if the payout is delayed
+    if protocol_fee > 0 {
+        let treasury_token_account = ctx
+            .accounts
+            .treasury_token_account
+            .as_ref()
+            .ok_or(NyxError::MissingTreasuryAccount)?;
+        pool_token::transfer_spl_from_pool(
+            &ctx.accounts.vault_token_account,
+            treasury_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.token_program,
+            protocol_fee,
+            &pool_key,
+            vault_bump,
+        )?;
+    }
+
+    log_memo(&ctx.accounts.memo_program, &memo)?;
+
+    // Behind a withdrawal delay, park the payout until it can be finalized
+    if let Some(unlock_slot) = pool.withdrawal_unlock_slot(clock.slot) {
+        let epoch = tree_epoch.to_le_bytes();
+        let bump = [ctx.bumps.pending_withdrawal];
+        let seeds: &[&[u8]] =
+            &[PENDING_WITHDRAWAL_SEED, pool_key.as_ref(), &epoch, &nullifier, &bump];
+        let pending = PendingWithdrawal {
+            pool: pool_key,
+            recipient: ctx.accounts.recipient_token_account.key(),
+            fee_recipient: ctx.accounts.relayer_token_account.key(),
+            rent_payer: ctx.accounts.relayer.key(),
+            payout,
+            fee,
+            unlock_slot,
+        };
+        return queue_withdrawal(
+            &ctx.accounts.pending_withdrawal,
+            &ctx.accounts.relayer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &pending,
+            seeds,
+        );
+    }
+
+    // Transfer SPL tokens from vault to recipient and relayer
+    let payments = [
+        (&ctx.accounts.recipient_token_account, payout),
+        (&ctx.accounts.relayer_token_account, fee),
+    ];
+    for (to, value) in payments {
+        if value == 0 {
+            continue;
+        }
+        pool_token::transfer_spl_from_pool(
+            &ctx.accounts.vault_token_account,
+            to,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.token_program,
+            value,
+            &pool_key,
+            vault_bump,
+        )?;
+    }
+
+    emit!(Unshielded {
+        recipient: recipient_key,
+        amount,
+    });
+
+    msg!(
+        "Unshielded {} tokens of {} (relayer fee {}, protocol fee {})",
+        amount,
+        pool.mint,
+        fee,
+        protocol_fee
+    );
+    msg!("Nullifier spent at slot {}", clock.slot);
+
+    Ok(())
+}
+
+/// Process UnshieldWithAta instruction
+///
+/// The account constraints have already created the recipient's associated
+/// token account; the rest is a regular unshield into it.
+#[allow(clippy::too_many_arguments)]
+pub fn process_unshield_with_ata<'info>(
+    ctx: Context<'_, '_, '_, 'info, UnshieldWithAta<'info>>,
+    tree_epoch: u32,
+    root: [u8; 32],
+    nullifier: [u8; 32],
+    amount: u64,
+    proof: Vec<u8>,
+    memo: Vec<u8>,
+) -> Result<()> {
+    require_keys_eq!(
+        ctx.accounts.create_ata.recipient_token_account.key(),
+        ctx.accounts.unshield.recipient_token_account.key(),
+        NyxError::RecipientAccountMismatch
+    );
+
+    let unshield = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.unshield,
+        ctx.remaining_accounts,
+        ctx.bumps.unshield,
+    );
+    process_unshield(unshield, tree_epoch, root, nullifier, amount, proof, memo)
+}
+
+/// Log a non-empty unshield memo through the Memo program
+///
+/// With a withdrawal delay the memo is logged when the withdrawal is queued.
+fn log_memo<'info>(memo_program: &Option<Program<'info, Memo>>, memo: &[u8]) -> Result<()> {
+    if memo.is_empty() {
+        return Ok(());
+    }
+    let memo_program = memo_program.as_ref().ok_or(NyxError::MissingMemoProgram)?;
+    memo::build_memo(CpiContext::new(memo_program.to_account_info(), BuildMemo {}), memo)
+}
+
+/// Park an unshield's payout in a `PendingWithdrawal` until its unlock slot
+fn queue_withdrawal<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    pending: &PendingWithdrawal,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let space = 8 + PendingWithdrawal::SIZE;
+    create_program_account(account, payer, system, space, seeds, &Rent::get()?)?;
+    pending.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+
+    emit!(WithdrawalQueued {
+        pending_withdrawal: account.key(),
+        recipient: pending.recipient,
+        amount: pending.payout + pending.fee,
+        unlock_slot: pending.unlock_slot,
+    });
+    msg!("Withdrawal queued until slot {}", pending.unlock_slot);
+
+    Ok(())
+}
+
+/// Process UseCompressedTree instruction
+pub fn process_use_compressed_tree(
+    ctx: Context<UseCompressedTree>,
+    max_depth: u8,
+    max_buffer_size: u32,
+) -> Result<()> {
+    compression::validate_depth(max_depth)?;
+
+    let pool = &mut ctx.accounts.pool;
+    let tree = &ctx.accounts.merkle_tree;
+    let mint = pool.mint;
+    let bump = [pool.bump];
+    let seeds: &[&[u8]] = &[POOL_SEED, mint.as_ref(), &bump];
+    compression::init_tree(
+        tree,
+        &pool.to_account_info(),
+        &ctx.accounts.noop_program,
+        &ctx.accounts.compression_program,
+        max_depth,
+        max_buffer_size,
+        seeds,
+    )?;
+
+    let (empty_root, _) = compression::read_latest(&tree.try_borrow_data()?, max_depth)?;
+    pool.use_compressed_tree(tree.key(), max_depth, empty_root)?;
+
+    emit!(CompressedTreeEnabled {
+        tree: tree.key(),
+        depth: max_depth,
+    });
+    msg!("Pool {} now appends to compressed tree {}", pool.key(), tree.key());
+    Ok(())
+}
+
+/// Process SetDepositCaps instruction
+pub fn process_set_deposit_caps(
+    ctx: Context<SetDepositCaps>,
+    max_deposit_per_tx: u64,
+    max_pool_tvl: u64,
+) -> Result<()> {
+    ctx.accounts.pool.set_deposit_caps(max_deposit_per_tx, max_pool_tvl);
+    emit!(DepositCapsUpdated {
+        max_deposit_per_tx,
+        max_pool_tvl,
+    });
+    msg!(
+        "Deposit caps set to {} per transaction, {} total",
+        max_deposit_per_tx,
+        max_pool_tvl
+    );
+    Ok(())
+}
+
+/// Process SetWithdrawalDelay instruction
+pub fn process_set_withdrawal_delay(
+    ctx: Context<SetWithdrawalDelay>,
+    delay_slots: u64,
+) -> Result<()> {
+    ctx.accounts.pool.set_withdrawal_delay(delay_slots)?;
+    emit!(WithdrawalDelayUpdated { delay_slots });
+    msg!("Withdrawal delay set to {} slots", delay_slots);
+    Ok(())
+}
+
+/// Process SetProtocolFee instruction
+pub fn process_set_protocol_fee(ctx: Context<SetProtocolFee>, fee_bps: u16) -> Result<()> {
+    ctx.accounts.pool.set_protocol_fee(fee_bps)?;
+
+    // An empty treasury could not take fees below the rent-exempt minimum
+    let treasury = &ctx.accounts.treasury;
+    let top_up = Rent::get()?.minimum_balance(0).saturating_sub(treasury.lamports());
+    if fee_bps > 0 && top_up > 0 {
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.authority.to_account_info(),
+                to: treasury.to_account_info(),
+            },
+        );
+        system_program::transfer(cpi_context, top_up)?;
+    }
+
+    emit!(ProtocolFeeUpdated { fee_bps });
+    msg!("Protocol fee set to {} bps of the relayer fee", fee_bps);
+    Ok(())
+}
+
+/// Process CollectFees instruction
+pub fn process_collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let treasury = &accounts.treasury;
+    let pool_key = accounts.pool.key();
+    let bump = [ctx.bumps.treasury];
+    let seeds: &[&[&[u8]]] = &[&[TREASURY_SEED, pool_key.as_ref(), &bump]];
+
+    // Keep the rent-exempt minimum so the treasury can go on taking fees
+    let lamports = treasury.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    if lamports > 0 {
+        let cpi_context = CpiContext::new_with_signer(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: treasury.to_account_info(),
+                to: accounts.destination.to_account_info(),
+            },
+            seeds,
+        );
+        system_program::transfer(cpi_context, lamports)?;
+    }
+
+    let token_accounts = (
+        &accounts.mint,
+        &accounts.treasury_token_account,
+        &accounts.destination_token_account,
+        &accounts.token_program,
+    );
+    let tokens = match token_accounts {
+        (Some(mint), Some(from), Some(to), Some(token_program)) => {
+            if from.amount > 0 {
+                let cpi_context = CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: from.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: to.to_account_info(),
+                        authority: treasury.to_account_info(),
+                    },
+                    seeds,
+                );
+                token_interface::transfer_checked(cpi_context, from.amount, mint.decimals)?;
+            }
+            from.amount
+        }
+        (None, None, None, None) => 0,
+        _ => return err!(NyxError::MissingTreasuryAccount),
     };
-    let cpi_context = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        cpi_accounts,
-        signer_seeds,
+
+    emit!(FeesCollected {
+        destination: accounts.destination.key(),
+        lamports,
+        tokens,
+    });
+    msg!("Collected {} lamports and {} tokens from the treasury", lamports, tokens);
+    Ok(())
+}
+
+/// Process FinalizeWithdrawalSol instruction
+///
+/// Anchor's `close` constraint refunds the pending account's rent afterwards.
+pub fn process_finalize_withdrawal_sol(ctx: Context<FinalizeWithdrawalSol>) -> Result<()> {
+    let pending = &ctx.accounts.pending_withdrawal;
+    ctx.accounts.pool.require_not_paused(PAUSE_UNSHIELD)?;
+    require!(
+        Clock::get()?.slot >= pending.unlock_slot,
+        NyxError::WithdrawalLocked
     );
-    token::transfer(cpi_context, amount)?;
 
-    msg!("Unshielded {} tokens", amount);
-    msg!("Nullifier spent at slot {}", clock.slot);
+    let amount = pending.payout + pending.fee;
+    let vault = &ctx.accounts.vault;
+    require!(vault.lamports() >= amount, pool_token::TokenError::InsufficientFunds);
+
+    **vault.try_borrow_mut_lamports()? -= amount;
+    **ctx.accounts.recipient.try_borrow_mut_lamports()? += pending.payout;
+    **ctx.accounts.fee_recipient.try_borrow_mut_lamports()? += pending.fee;
+
+    emit!(Unshielded {
+        recipient: pending.recipient,
+        amount,
+    });
+    msg!("Finalized withdrawal of {} lamports (relayer fee {})", amount, pending.fee);
+
+    Ok(())
+}
+
+/// Process FinalizeWithdrawal (SPL) instruction
+///
+/// Anchor's `close` constraint refunds the pending account's rent afterwards.
+pub fn process_finalize_withdrawal(ctx: Context<FinalizeWithdrawal>) -> Result<()> {
+    let pending = &ctx.accounts.pending_withdrawal;
+    let pool = &ctx.accounts.pool;
+    pool.require_not_paused(PAUSE_UNSHIELD)?;
+    require!(
+        Clock::get()?.slot >= pending.unlock_slot,
+        NyxError::WithdrawalLocked
+    );
+
+    let pool_key = pool.key();
+    let vault_bump = ctx.bumps.vault_authority;
+    let payments = [
+        (&ctx.accounts.recipient_token_account, pending.payout),
+        (&ctx.accounts.fee_token_account, pending.fee),
+    ];
+    for (to, value) in payments {
+        if value == 0 {
+            continue;
+        }
+        pool_token::transfer_spl_from_pool(
+            &ctx.accounts.vault_token_account,
+            to,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.token_program,
+            value,
+            &pool_key,
+            vault_bump,
+        )?;
+    }
+
+    let amount = pending.payout + pending.fee;
+    emit!(Unshielded {
+        recipient: ctx.accounts.recipient_token_account.owner,
+        amount,
+    });
+    msg!(
+        "Finalized withdrawal of {} tokens of {} (relayer fee {})",
+        amount,
+        pool.mint,
+        pending.fee
+    );
+
+    Ok(())
+}
+
+/// Process RegisterRelayer instruction
+pub fn process_register_relayer(
+    ctx: Context<RegisterRelayer>,
+    fee_bps: u16,
+    endpoint: String,
+    stake: u64,
+) -> Result<()> {
+    relayer::validate_registration(fee_bps, &endpoint, stake)?;
+
+    // Lock the stake in the entry, on top of its rent
+    let cpi_context = CpiContext::new(
+        ctx.accounts.system_program.to_account_info(),
+        system_program::Transfer {
+            from: ctx.accounts.relayer.to_account_info(),
+            to: ctx.accounts.relayer_entry.to_account_info(),
+        },
+    );
+    system_program::transfer(cpi_context, stake)?;
+
+    let entry = &mut ctx.accounts.relayer_entry;
+    entry.relayer = ctx.accounts.relayer.key();
+    entry.stake = stake;
+    entry.fee_bps = fee_bps;
+    entry.endpoint = endpoint.clone();
+    entry.registered_at = Clock::get()?.slot;
+    entry.bump = ctx.bumps.relayer_entry;
+
+    emit!(RelayerRegistered {
+        relayer: entry.relayer,
+        stake,
+        fee_bps,
+        endpoint,
+    });
+    msg!("Relayer {} registered with {} lamports staked", entry.relayer, stake);
+
+    Ok(())
+}
 
+/// Process DeregisterRelayer instruction
+///
+/// Anchor's `close` constraint returns the entry's lamports afterwards.
+pub fn process_deregister_relayer(ctx: Context<DeregisterRelayer>) -> Result<()> {
+    let entry = &ctx.accounts.relayer_entry;
+    emit!(RelayerDeregistered {
+        relayer: entry.relayer,
+        refunded: entry.to_account_info().lamports(),
+    });
+    msg!("Relayer {} deregistered", entry.relayer);
     Ok(())
 }
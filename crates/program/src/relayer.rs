@@ -0,0 +1,112 @@
+//! Relayer registry
+//!
+//! Relayers advertise themselves in `RelayerEntry` PDAs so wallets can
+//! discover them on chain instead of trusting an off-chain list. Registering
+//! locks at least `MIN_RELAYER_STAKE` lamports in the entry on top of its
+//! rent; deregistering closes the entry and returns both to the relayer.
+//!
+//! Entries are global rather than per pool: one relayer serves every pool.
+
+use anchor_lang::prelude::*;
+
+use crate::instructions::NyxError;
+use crate::state::MAX_RELAYER_FEE_BPS;
+
+/// Seeds prefix for relayer entry PDAs, followed by the relayer's key
+pub const RELAYER_SEED: &[u8] = b"relayer";
+
+/// Minimum stake a relayer locks to register (1 SOL)
+pub const MIN_RELAYER_STAKE: u64 = 1_000_000_000;
+
+/// Maximum length of an advertised endpoint URL, in bytes
+pub const MAX_ENDPOINT_LEN: usize = 128;
+
+/// A registered relayer
+#[account]
+#[derive(Debug)]
+pub struct RelayerEntry {
+    /// The relayer's signing key
+    pub relayer: Pubkey,
+
+    /// Lamports staked, held by this account above its rent
+    pub stake: u64,
+
+    /// Advertised fee, in basis points
+    pub fee_bps: u16,
+
+    /// API endpoint (`https://` URL)
+    pub endpoint: String,
+
+    /// Slot the relayer registered in
+    pub registered_at: u64,
+
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl RelayerEntry {
+    // relayer + stake + fee_bps + endpoint (len + bytes) + registered_at + bump
+    pub const SIZE: usize = 32 + 8 + 2 + 4 + MAX_ENDPOINT_LEN + 8 + 1;
+}
+
+/// Check a registration's advertised terms
+pub fn validate_registration(fee_bps: u16, endpoint: &str, stake: u64) -> Result<()> {
+    require!(fee_bps <= MAX_RELAYER_FEE_BPS, NyxError::FeeTooHigh);
+    require!(
+        endpoint.len() <= MAX_ENDPOINT_LEN
+            && endpoint.len() > "https://".len()
+            && endpoint.starts_with("https://"),
+        NyxError::InvalidRelayerEndpoint
+    );
+    require!(stake >= MIN_RELAYER_STAKE, NyxError::StakeTooLow);
+    Ok(())
+}
+
+/// Derive the registry entry PDA for `relayer`
+pub fn derive_relayer_pda(program_id: &Pubkey, relayer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RELAYER_SEED, relayer.as_ref()], program_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_registration() {
+        let endpoint = "https://relayer.example.com";
+        assert!(validate_registration(30, endpoint, MIN_RELAYER_STAKE).is_ok());
+
+        assert_eq!(
+            validate_registration(MAX_RELAYER_FEE_BPS + 1, endpoint, MIN_RELAYER_STAKE)
+                .unwrap_err(),
+            NyxError::FeeTooHigh.into()
+        );
+        assert_eq!(
+            validate_registration(30, endpoint, MIN_RELAYER_STAKE - 1).unwrap_err(),
+            NyxError::StakeTooLow.into()
+        );
+
+        let too_long = format!("https://{}", "a".repeat(MAX_ENDPOINT_LEN));
+        for bad in ["http://relayer.example.com", "https://", too_long.as_str()] {
+            assert_eq!(
+                validate_registration(30, bad, MIN_RELAYER_STAKE).unwrap_err(),
+                NyxError::InvalidRelayerEndpoint.into()
+            );
+        }
+    }
+
+    #[test]
+    fn test_entry_fits_longest_endpoint() {
+        let entry = RelayerEntry {
+            relayer: Pubkey::new_unique(),
+            stake: MIN_RELAYER_STAKE,
+            fee_bps: 30,
+            endpoint: "a".repeat(MAX_ENDPOINT_LEN),
+            registered_at: 1,
+            bump: 255,
+        };
+        let mut data = Vec::new();
+        entry.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + RelayerEntry::SIZE);
+    }
+}
@@ -7,8 +7,24 @@ use anchor_lang::prelude::*;
 use crate::instructions::NyxError;
 use crate::merkle::IncrementalMerkleTree;
 
-/// Number of recent roots to keep for validity window
-pub const ROOT_HISTORY_SIZE: usize = 30;
+/// Seeds prefix for pool PDAs, followed by the pool's mint
+pub const POOL_SEED: &[u8] = b"privacy_pool";
+
+/// Mint that keys the native SOL pool (the wrapped SOL mint)
+pub const NATIVE_MINT: Pubkey = anchor_spl::token::spl_token::native_mint::ID;
+
+/// Seeds prefix for a pool's root history PDA, followed by the pool
+pub const ROOT_HISTORY_SEED: &[u8] = b"root_history";
+
+/// Default number of replaced roots a pool still accepts proofs against
+pub const DEFAULT_ROOT_HISTORY_SIZE: u16 = 30;
+
+/// Largest root history, which keeps its account within the 10 KiB an
+/// instruction may allocate or grow it by
+pub const MAX_ROOT_HISTORY_SIZE: u16 = 300;
+
+/// Number of recent commitments kept to reject duplicate insertions
+pub const RECENT_COMMITMENTS_SIZE: usize = 16;
 
 /// Default relayer fee in basis points (0.3%)
 pub const DEFAULT_RELAYER_FEE_BPS: u16 = 30;
@@ -16,6 +32,46 @@ pub const DEFAULT_RELAYER_FEE_BPS: u16 = 30;
 /// Maximum relayer fee in basis points (5%)
 pub const MAX_RELAYER_FEE_BPS: u16 = 500;
 
+/// Largest share of each relayer fee the protocol may take, in basis
+/// points of the fee (50%)
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 5_000;
+
+/// Pause flag: `shield_sol` and `shield`
+pub const PAUSE_SHIELD: u8 = 1 << 0;
+
+/// Pause flag: `transfer`
+pub const PAUSE_TRANSFER: u8 = 1 << 1;
+
+/// Pause flag: `unshield_sol` and `unshield`
+pub const PAUSE_UNSHIELD: u8 = 1 << 2;
+
+/// All pause flags
+pub const PAUSE_ALL: u8 = PAUSE_SHIELD | PAUSE_TRANSFER | PAUSE_UNSHIELD;
+
+/// Seeds prefix for the pool treasury PDA, followed by the pool
+///
+/// A system account holding protocol lamports, such as rent reclaimed by
+/// `compact_nullifiers` and the protocol share of SOL unshield fees. SPL
+/// fees go to token accounts it owns. `collect_fees` sweeps both.
+pub const TREASURY_SEED: &[u8] = b"treasury";
+
+/// Seeds prefix for archived root PDAs, followed by the pool and the tree
+/// epoch (u32, little-endian)
+pub const ARCHIVED_ROOT_SEED: &[u8] = b"archived_root";
+
+/// Default epochs a nullifier marker must age before it can be compacted
+pub const DEFAULT_NULLIFIER_FINALITY_EPOCHS: u16 = 4;
+
+/// Minimum nullifier finality window, in epochs
+pub const MIN_NULLIFIER_FINALITY_EPOCHS: u16 = 1;
+
+/// Seeds prefix for pending withdrawal PDAs, followed by the pool, the tree
+/// epoch (u32, little-endian) and the spent nullifier
+pub const PENDING_WITHDRAWAL_SEED: &[u8] = b"pending_withdrawal";
+
+/// Longest withdrawal delay a pool may set (about a week of 400ms slots)
+pub const MAX_WITHDRAWAL_DELAY_SLOTS: u64 = 1_512_000;
+
 /// Minimum withdrawal amount (to cover fees)
 pub const MIN_WITHDRAWAL_AMOUNT: u64 = 10_000; // 0.00001 SOL
 
@@ -25,18 +81,29 @@ pub struct PrivacyPool {
     /// Pool authority
     pub authority: Pubkey,
 
+    /// Authority proposed by `propose_authority`, waiting to accept
+    /// (`Pubkey::default()` if none)
+    pub pending_authority: Pubkey,
+
+    /// Asset held by this pool (`NATIVE_MINT` for SOL)
+    pub mint: Pubkey,
+
     /// Incremental Merkle tree for commitments
     /// - next_index: u64 (8 bytes)
     /// - filled_subtrees: [[u8; 32]; 20] (640 bytes)
     /// - current_root: [u8; 32] (32 bytes)
     pub merkle_tree: IncrementalMerkleTree,
 
-    /// Recent Merkle roots (for validity window)
-    /// Allows proofs against slightly older roots during concurrent transactions
-    pub root_history: [[u8; 32]; ROOT_HISTORY_SIZE],
+    /// Number of times the tree was rotated; nullifiers are namespaced by
+    /// the epoch of the tree their note was inserted in
+    pub tree_epoch: u32,
+
+    /// Most recently inserted commitments (circular buffer); a commitment
+    /// still in this window cannot be inserted again
+    pub recent_commitments: [[u8; 32]; RECENT_COMMITMENTS_SIZE],
 
-    /// Index of the oldest root in history (circular buffer)
-    pub root_history_index: u8,
+    /// Index of the oldest entry in `recent_commitments`
+    pub recent_commitments_index: u8,
 
     /// Number of spent nullifiers (for stats)
     pub nullifier_count: u64,
@@ -44,9 +111,47 @@ pub struct PrivacyPool {
     /// Relayer fee in basis points (e.g., 30 = 0.3%)
     pub relayer_fee_bps: u16,
 
+    /// Fee scheduled by `update_relayer_fee`, in basis points
+    pub pending_relayer_fee_bps: u16,
+
+    /// Slot from which the pending fee applies (0 if none is scheduled)
+    pub relayer_fee_effective_slot: u64,
+
     /// Total fees collected (for stats)
     pub total_fees_collected: u64,
 
+    /// Share of each relayer fee routed to the treasury, in basis points of
+    /// the fee
+    pub protocol_fee_bps: u16,
+
+    /// Whether Groth16 proofs are accepted (set by the authority once the
+    /// verifying key is in place)
+    pub zk_enabled: bool,
+
+    /// Paused operations (`PAUSE_*` bitmask)
+    pub paused: u8,
+
+    /// Epochs a nullifier marker must age before `compact_nullifiers` may
+    /// archive it
+    pub nullifier_finality_epochs: u16,
+
+    /// Slots an unshield waits in a `PendingWithdrawal` before it can be
+    /// finalized (0 pays out immediately)
+    pub withdrawal_delay_slots: u64,
+
+    /// Largest amount a single shield may deposit (0 for no cap)
+    pub max_deposit_per_tx: u64,
+
+    /// Largest vault balance shields may grow the pool to (0 for no cap)
+    pub max_pool_tvl: u64,
+
+    /// spl-account-compression tree holding the commitments, if the pool
+    /// uses one (see `compression`)
+    pub compressed_tree: Pubkey,
+
+    /// Depth of `compressed_tree` (0 while the pool uses `merkle_tree`)
+    pub compressed_tree_depth: u8,
+
     /// Bump seed for PDA
     pub bump: u8,
 }
@@ -54,39 +159,203 @@ pub struct PrivacyPool {
 impl PrivacyPool {
     /// Account size calculation
     pub const SIZE: usize = 32  // authority
+        + 32  // pending_authority
+        + 32  // mint
         + IncrementalMerkleTree::SIZE  // merkle_tree (680 bytes)
-        + (32 * ROOT_HISTORY_SIZE)  // root_history (960 bytes)
-        + 1   // root_history_index
+        + 4   // tree_epoch
+        + (32 * RECENT_COMMITMENTS_SIZE)  // recent_commitments (512 bytes)
+        + 1   // recent_commitments_index
         + 8   // nullifier_count
         + 2   // relayer_fee_bps
+        + 2   // pending_relayer_fee_bps
+        + 8   // relayer_fee_effective_slot
         + 8   // total_fees_collected
+        + 2   // protocol_fee_bps
+        + 1   // zk_enabled
+        + 1   // paused
+        + 2   // nullifier_finality_epochs
+        + 8   // withdrawal_delay_slots
+        + 8   // max_deposit_per_tx
+        + 8   // max_pool_tvl
+        + 32  // compressed_tree
+        + 1   // compressed_tree_depth
         + 1;  // bump
 
-    /// Initialize a new privacy pool
-    pub fn initialize(&mut self, authority: Pubkey, bump: u8) {
+    /// Initialize a new privacy pool for `mint`
+    pub fn initialize(&mut self, authority: Pubkey, mint: Pubkey, bump: u8) {
         self.authority = authority;
+        self.pending_authority = Pubkey::default();
+        self.mint = mint;
         self.merkle_tree = IncrementalMerkleTree::new();
-        self.root_history = [[0u8; 32]; ROOT_HISTORY_SIZE];
-        self.root_history_index = 0;
+        self.tree_epoch = 0;
+        self.recent_commitments = [[0u8; 32]; RECENT_COMMITMENTS_SIZE];
+        self.recent_commitments_index = 0;
         self.nullifier_count = 0;
         self.relayer_fee_bps = DEFAULT_RELAYER_FEE_BPS;
+        self.pending_relayer_fee_bps = 0;
+        self.relayer_fee_effective_slot = 0;
         self.total_fees_collected = 0;
+        self.protocol_fee_bps = 0;
+        self.zk_enabled = false;
+        self.paused = 0;
+        self.nullifier_finality_epochs = DEFAULT_NULLIFIER_FINALITY_EPOCHS;
+        self.withdrawal_delay_slots = 0;
+        self.max_deposit_per_tx = 0;
+        self.max_pool_tvl = 0;
+        self.compressed_tree = Pubkey::default();
+        self.compressed_tree_depth = 0;
         self.bump = bump;
     }
 
+    /// Propose `new_authority` as the next authority
+    ///
+    /// Replaces any earlier proposal; `Pubkey::default()` cancels it.
+    pub fn propose_authority(&mut self, new_authority: Pubkey) {
+        self.pending_authority = new_authority;
+    }
+
+    /// Hand the pool to the pending authority, which must be `signer`
+    pub fn accept_authority(&mut self, signer: &Pubkey) -> Result<()> {
+        require!(
+            self.pending_authority != Pubkey::default() && self.pending_authority == *signer,
+            NyxError::NotPendingAuthority
+        );
+        self.authority = self.pending_authority;
+        self.pending_authority = Pubkey::default();
+        Ok(())
+    }
+
+    /// Fail if any operation in `flags` is paused
+    pub fn require_not_paused(&self, flags: u8) -> Result<()> {
+        require!(self.paused & flags == 0, NyxError::OperationPaused);
+        Ok(())
+    }
+
+    /// Set how many epochs nullifier markers must age before compaction
+    pub fn set_nullifier_finality_epochs(&mut self, epochs: u16) -> Result<()> {
+        require!(
+            epochs >= MIN_NULLIFIER_FINALITY_EPOCHS,
+            NyxError::FinalityWindowTooShort
+        );
+        self.nullifier_finality_epochs = epochs;
+        Ok(())
+    }
+
+    /// Set the withdrawal delay, in slots (0 disables it)
+    pub fn set_withdrawal_delay(&mut self, delay_slots: u64) -> Result<()> {
+        require!(
+            delay_slots <= MAX_WITHDRAWAL_DELAY_SLOTS,
+            NyxError::WithdrawalDelayTooLong
+        );
+        self.withdrawal_delay_slots = delay_slots;
+        Ok(())
+    }
+
+    /// Slot from which an unshield made at `slot` may be finalized, or `None`
+    /// if the pool pays out immediately
+    pub fn withdrawal_unlock_slot(&self, slot: u64) -> Option<u64> {
+        (self.withdrawal_delay_slots > 0).then(|| slot.saturating_add(self.withdrawal_delay_slots))
+    }
+
+    /// Set the deposit caps (0 leaves either uncapped)
+    pub fn set_deposit_caps(&mut self, max_deposit_per_tx: u64, max_pool_tvl: u64) {
+        self.max_deposit_per_tx = max_deposit_per_tx;
+        self.max_pool_tvl = max_pool_tvl;
+    }
+
+    /// Check a shield of `amount` into a vault currently holding `vault_balance`
+    /// against the deposit caps
+    pub fn check_deposit_caps(&self, amount: u64, vault_balance: u64) -> Result<()> {
+        require!(
+            self.max_deposit_per_tx == 0 || amount <= self.max_deposit_per_tx,
+            NyxError::DepositTooLarge
+        );
+        require!(
+            self.max_pool_tvl == 0
+                || vault_balance
+                    .checked_add(amount)
+                    .is_some_and(|tvl| tvl <= self.max_pool_tvl),
+            NyxError::PoolCapReached
+        );
+        Ok(())
+    }
+
+    /// Whether a nullifier spent in `spent_epoch` is final at `current_epoch`
+    pub fn is_nullifier_final(&self, spent_epoch: u64, current_epoch: u64) -> bool {
+        spent_epoch
+            .checked_add(self.nullifier_finality_epochs as u64)
+            .is_some_and(|final_epoch| final_epoch <= current_epoch)
+    }
+
     /// Calculate relayer fee for a given amount
     pub fn calculate_relayer_fee(&self, amount: u64) -> u64 {
         // fee = amount * fee_bps / 10000
         (amount as u128 * self.relayer_fee_bps as u128 / 10000) as u64
     }
 
+    /// Schedule `fee_bps` to replace the relayer fee from `effective_slot`
+    ///
+    /// Replaces any change still pending.
+    pub fn schedule_relayer_fee(&mut self, fee_bps: u16, effective_slot: u64) -> Result<()> {
+        require!(fee_bps <= MAX_RELAYER_FEE_BPS, NyxError::FeeTooHigh);
+        self.pending_relayer_fee_bps = fee_bps;
+        self.relayer_fee_effective_slot = effective_slot;
+        Ok(())
+    }
+
+    /// Apply a scheduled fee change once `slot` reaches its effective slot
+    pub fn apply_pending_relayer_fee(&mut self, slot: u64) {
+        if self.relayer_fee_effective_slot != 0 && slot >= self.relayer_fee_effective_slot {
+            self.relayer_fee_bps = self.pending_relayer_fee_bps;
+            self.pending_relayer_fee_bps = 0;
+            self.relayer_fee_effective_slot = 0;
+        }
+    }
+
+    /// Split a withdrawal into (recipient payout, relayer fee)
+    pub fn split_relayer_fee(&self, amount: u64) -> (u64, u64) {
+        let fee = self.calculate_relayer_fee(amount);
+        (amount - fee, fee)
+    }
+
+    /// Set the protocol's share of relayer fees
+    pub fn set_protocol_fee(&mut self, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_PROTOCOL_FEE_BPS, NyxError::ProtocolFeeTooHigh);
+        self.protocol_fee_bps = fee_bps;
+        Ok(())
+    }
+
+    /// Split a relayer fee into (relayer share, protocol share)
+    pub fn split_protocol_fee(&self, fee: u64) -> (u64, u64) {
+        let protocol = (fee as u128 * self.protocol_fee_bps as u128 / 10000) as u64;
+        (fee - protocol, protocol)
+    }
+
     /// Record a fee payment
     pub fn record_fee_collected(&mut self, fee: u64) {
         self.total_fees_collected = self.total_fees_collected.saturating_add(fee);
     }
 
-    /// Add a commitment to the tree
-    pub fn add_commitment(&mut self, commitment: [u8; 32]) -> Result<u64> {
+    /// Fail if `commitment` is zero (the empty leaf) or was inserted within
+    /// the last `RECENT_COMMITMENTS_SIZE` insertions
+    pub fn check_new_commitment(&self, commitment: &[u8; 32]) -> Result<()> {
+        require!(*commitment != [0u8; 32], NyxError::InvalidCommitment);
+        require!(
+            !self.recent_commitments.contains(commitment),
+            NyxError::DuplicateCommitment
+        );
+        Ok(())
+    }
+
+    /// Add a commitment to the tree, keeping the replaced root in `history`
+    pub fn add_commitment(
+        &mut self,
+        commitment: [u8; 32],
+        history: &mut RootHistory,
+    ) -> Result<u64> {
+        require!(!self.is_compressed(), NyxError::InvalidCompressedTree);
+        self.check_new_commitment(&commitment)?;
+
         // Store old root in history before updating
         let old_root = self.merkle_tree.current_root;
 
@@ -94,13 +363,96 @@ impl PrivacyPool {
         let leaf_index = self.merkle_tree.insert(commitment)
             .map_err(|_| NyxError::PoolFull)?;
 
-        // Add old root to history (circular buffer)
-        self.root_history[self.root_history_index as usize] = old_root;
-        self.root_history_index = ((self.root_history_index as usize + 1) % ROOT_HISTORY_SIZE) as u8;
-
+        history.push(old_root);
+        self.push_recent_commitment(commitment);
         Ok(leaf_index)
     }
 
+    /// Record `commitment`, appended to the compressed tree at `leaf_index`
+    /// and giving the tree `new_root`
+    ///
+    /// Only the root and leaf count are kept; `filled_subtrees` stays unused.
+    /// The caller checks the commitment with `check_new_commitment` before
+    /// appending it.
+    pub fn record_compressed_commitment(
+        &mut self,
+        commitment: [u8; 32],
+        new_root: [u8; 32],
+        leaf_index: u64,
+        history: &mut RootHistory,
+    ) {
+        let old_root = self.merkle_tree.current_root;
+        self.merkle_tree.current_root = new_root;
+        self.merkle_tree.next_index = leaf_index + 1;
+        history.push(old_root);
+        self.push_recent_commitment(commitment);
+    }
+
+    /// Remember an inserted commitment (circular buffer)
+    fn push_recent_commitment(&mut self, commitment: [u8; 32]) {
+        self.recent_commitments[self.recent_commitments_index as usize] = commitment;
+        self.recent_commitments_index =
+            ((self.recent_commitments_index as usize + 1) % RECENT_COMMITMENTS_SIZE) as u8;
+    }
+
+    /// Whether commitments go to a compressed tree
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_tree_depth != 0
+    }
+
+    /// Switch an empty pool to the compressed tree `tree` of `depth`, whose
+    /// root is `empty_root`
+    ///
+    /// Only possible before the first commitment and while Groth16 is off:
+    /// the circuit cannot prove membership in a keccak tree.
+    pub fn use_compressed_tree(
+        &mut self,
+        tree: Pubkey,
+        depth: u8,
+        empty_root: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            !self.is_compressed() && !self.zk_enabled,
+            NyxError::CompressedTreeUnsupported
+        );
+        require!(
+            self.tree_epoch == 0 && self.commitment_count() == 0,
+            NyxError::PoolNotEmpty
+        );
+        self.compressed_tree = tree;
+        self.compressed_tree_depth = depth;
+        self.merkle_tree.current_root = empty_root;
+        Ok(())
+    }
+
+    /// Leaves the current tree can hold
+    pub fn capacity(&self) -> u64 {
+        if self.is_compressed() {
+            1 << self.compressed_tree_depth
+        } else {
+            IncrementalMerkleTree::MAX_LEAVES
+        }
+    }
+
+    /// Whether the current tree has no room left
+    pub fn is_tree_full(&self) -> bool {
+        self.merkle_tree.next_index >= self.capacity()
+    }
+
+    /// Start a fresh tree once the current one is full
+    ///
+    /// Returns the final root of the old tree, which the caller archives so
+    /// its notes stay spendable. The old tree's roots leave `history`.
+    pub fn rotate_tree(&mut self, history: &mut RootHistory) -> Result<[u8; 32]> {
+        require!(!self.is_compressed(), NyxError::CompressedTreeUnsupported);
+        require!(self.is_tree_full(), NyxError::TreeNotFull);
+        let final_root = self.current_root();
+        self.tree_epoch = self.tree_epoch.checked_add(1).ok_or(NyxError::PoolFull)?;
+        self.merkle_tree = IncrementalMerkleTree::new();
+        history.clear();
+        Ok(final_root)
+    }
+
     /// Get current Merkle root
     pub fn current_root(&self) -> [u8; 32] {
         self.merkle_tree.current_root
@@ -111,14 +463,9 @@ impl PrivacyPool {
         self.merkle_tree.next_index
     }
 
-    /// Check if root is valid (current or in history)
-    pub fn is_valid_root(&self, root: &[u8; 32]) -> bool {
-        // Check current root
-        if *root == self.merkle_tree.current_root {
-            return true;
-        }
-        // Check history
-        self.root_history.iter().any(|r| r == root && *r != [0u8; 32])
+    /// Check if root is valid (current or in the pool's `history`)
+    pub fn is_valid_root(&self, root: &[u8; 32], history: &RootHistory) -> bool {
+        *root == self.merkle_tree.current_root || history.contains(root)
     }
 
     /// Check if nullifier is spent
@@ -136,6 +483,154 @@ impl PrivacyPool {
     }
 }
 
+/// Derive the pool PDA for `mint`
+///
+/// Each asset has its own pool, and with it its own commitment tree, vault
+/// and nullifier markers (both seeded by the pool address).
+pub fn derive_pool_pda(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_SEED, mint.as_ref()], program_id)
+}
+
+/// Final root of a rotated-out tree
+///
+/// Created by `rotate_tree` and never closed: notes in the old tree are
+/// spent with proofs against this root.
+#[account]
+#[derive(Debug)]
+pub struct ArchivedRoot {
+    /// The pool the tree belonged to
+    pub pool: Pubkey,
+
+    /// Epoch of the archived tree
+    pub tree_epoch: u32,
+
+    /// The tree's final root
+    pub root: [u8; 32],
+}
+
+impl ArchivedRoot {
+    pub const SIZE: usize = 32 + 4 + 32; // pool + tree_epoch + root
+}
+
+/// Recent roots of a pool's live tree, for proofs made before the latest
+/// insertions (circular buffer)
+///
+/// Kept out of `PrivacyPool` so that its size can be chosen at
+/// initialization and changed later with `resize_root_history`.
+#[account]
+#[derive(Debug)]
+pub struct RootHistory {
+    /// The pool whose roots these are
+    pub pool: Pubkey,
+
+    /// Slot of the oldest root, overwritten next
+    pub next_index: u16,
+
+    /// Replaced roots; zero slots are unused
+    pub roots: Vec<[u8; 32]>,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl RootHistory {
+    /// Account size (without discriminator) for `size` roots
+    pub fn space(size: u16) -> usize {
+        32 + 2 + 4 + 32 * size as usize + 1 // pool + next_index + roots + bump
+    }
+
+    /// Fail unless a history may hold `size` roots
+    pub fn validate_size(size: u16) -> Result<()> {
+        require!(
+            (1..=MAX_ROOT_HISTORY_SIZE).contains(&size),
+            NyxError::InvalidRootHistorySize
+        );
+        Ok(())
+    }
+
+    /// Initialize an empty history of `size` roots for `pool`
+    pub fn initialize(&mut self, pool: Pubkey, size: u16, bump: u8) -> Result<()> {
+        Self::validate_size(size)?;
+        self.pool = pool;
+        self.next_index = 0;
+        self.roots = vec![[0u8; 32]; size as usize];
+        self.bump = bump;
+        Ok(())
+    }
+
+    /// Number of roots kept
+    pub fn size(&self) -> u16 {
+        self.roots.len() as u16
+    }
+
+    /// Keep a replaced root, overwriting the oldest
+    pub fn push(&mut self, root: [u8; 32]) {
+        self.roots[self.next_index as usize] = root;
+        self.next_index = ((self.next_index as usize + 1) % self.roots.len()) as u16;
+    }
+
+    /// Whether `root` is one of the kept roots
+    pub fn contains(&self, root: &[u8; 32]) -> bool {
+        *root != [0u8; 32] && self.roots.contains(root)
+    }
+
+    /// Forget every root
+    pub fn clear(&mut self) {
+        self.roots.fill([0u8; 32]);
+        self.next_index = 0;
+    }
+
+    /// Keep `size` roots from now on
+    ///
+    /// Shrinking drops the oldest roots; the newest stay valid either way.
+    /// The caller resizes the account to `space(size)`.
+    pub fn resize(&mut self, size: u16) -> Result<()> {
+        Self::validate_size(size)?;
+        // Oldest first
+        self.roots.rotate_left(self.next_index as usize);
+        let keep = self.roots.len().min(size as usize);
+        self.roots.drain(..self.roots.len() - keep);
+        self.roots.resize(size as usize, [0u8; 32]);
+        self.next_index = (keep % size as usize) as u16;
+        Ok(())
+    }
+}
+
+/// An unshield waiting out the pool's withdrawal delay
+///
+/// Created by `unshield_sol` / `unshield` when the pool has a delay; anyone
+/// may finalize it once `unlock_slot` is reached, unless unshields are
+/// paused. Closing it refunds the rent to `rent_payer`.
+#[account]
+#[derive(Debug)]
+pub struct PendingWithdrawal {
+    /// The pool paying out
+    pub pool: Pubkey,
+
+    /// Recipient wallet (SOL) or token account (SPL)
+    pub recipient: Pubkey,
+
+    /// Relayer wallet (SOL) or token account (SPL) owed the fee
+    pub fee_recipient: Pubkey,
+
+    /// Relayer that paid the rent
+    pub rent_payer: Pubkey,
+
+    /// Amount owed to the recipient
+    pub payout: u64,
+
+    /// Relayer fee, fixed when the withdrawal was queued
+    pub fee: u64,
+
+    /// First slot the withdrawal can be finalized in
+    pub unlock_slot: u64,
+}
+
+impl PendingWithdrawal {
+    // pool + recipient + fee_recipient + rent_payer + payout + fee + unlock_slot
+    pub const SIZE: usize = 32 * 4 + 8 + 8 + 8;
+}
+
 /// Nullifier account (separate account for nullifier set)
 #[account]
 pub struct NullifierSet {
@@ -150,3 +645,352 @@ impl NullifierSet {
     /// Account size
     pub const SIZE: usize = 32 + 1024;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sol_pool() -> PrivacyPool {
+        let mut pool = PrivacyPool {
+            authority: Pubkey::default(),
+            pending_authority: Pubkey::default(),
+            mint: Pubkey::default(),
+            merkle_tree: IncrementalMerkleTree::new(),
+            tree_epoch: 0,
+            recent_commitments: [[0u8; 32]; RECENT_COMMITMENTS_SIZE],
+            recent_commitments_index: 0,
+            nullifier_count: 0,
+            relayer_fee_bps: 0,
+            pending_relayer_fee_bps: 0,
+            relayer_fee_effective_slot: 0,
+            total_fees_collected: 0,
+            protocol_fee_bps: 0,
+            zk_enabled: false,
+            paused: 0,
+            nullifier_finality_epochs: 0,
+            withdrawal_delay_slots: 0,
+            max_deposit_per_tx: 0,
+            max_pool_tvl: 0,
+            compressed_tree: Pubkey::default(),
+            compressed_tree_depth: 0,
+            bump: 0,
+        };
+        pool.initialize(Pubkey::default(), NATIVE_MINT, 255);
+        pool
+    }
+
+    fn root_history(size: u16) -> RootHistory {
+        let mut history = RootHistory {
+            pool: Pubkey::default(),
+            next_index: 0,
+            roots: Vec::new(),
+            bump: 0,
+        };
+        history.initialize(Pubkey::default(), size, 255).unwrap();
+        history
+    }
+
+    #[test]
+    fn test_split_relayer_fee() {
+        let mut pool = sol_pool();
+
+        // 0.3% of 1 SOL
+        assert_eq!(pool.split_relayer_fee(1_000_000_000), (997_000_000, 3_000_000));
+        // Rounds down in the recipient's favour
+        assert_eq!(pool.split_relayer_fee(333), (333, 0));
+
+        pool.relayer_fee_bps = MAX_RELAYER_FEE_BPS;
+        assert_eq!(pool.split_relayer_fee(u64::MAX), (u64::MAX - u64::MAX / 20, u64::MAX / 20));
+    }
+
+    #[test]
+    fn test_split_protocol_fee() {
+        let mut pool = sol_pool();
+        assert_eq!(pool.split_protocol_fee(3_000_000), (3_000_000, 0));
+
+        pool.set_protocol_fee(2_000).unwrap();
+        assert_eq!(pool.split_protocol_fee(3_000_000), (2_400_000, 600_000));
+        // Rounds down in the relayer's favour
+        assert_eq!(pool.split_protocol_fee(4), (4, 0));
+
+        assert_eq!(
+            pool.set_protocol_fee(MAX_PROTOCOL_FEE_BPS + 1).unwrap_err(),
+            NyxError::ProtocolFeeTooHigh.into()
+        );
+        assert_eq!(pool.protocol_fee_bps, 2_000);
+    }
+
+    #[test]
+    fn test_relayer_fee_update() {
+        let mut pool = sol_pool();
+        assert_eq!(
+            pool.schedule_relayer_fee(MAX_RELAYER_FEE_BPS + 1, 1).unwrap_err(),
+            NyxError::FeeTooHigh.into()
+        );
+
+        // Delayed change waits for its slot
+        pool.schedule_relayer_fee(100, 1_000).unwrap();
+        pool.apply_pending_relayer_fee(999);
+        assert_eq!(pool.relayer_fee_bps, DEFAULT_RELAYER_FEE_BPS);
+        pool.apply_pending_relayer_fee(1_000);
+        assert_eq!(pool.relayer_fee_bps, 100);
+        assert_eq!(pool.relayer_fee_effective_slot, 0);
+
+        // Nothing pending: later slots leave the fee alone
+        pool.apply_pending_relayer_fee(5_000);
+        assert_eq!(pool.relayer_fee_bps, 100);
+
+        // Zero fee is a valid target
+        pool.schedule_relayer_fee(0, 6_000).unwrap();
+        pool.apply_pending_relayer_fee(6_000);
+        assert_eq!(pool.relayer_fee_bps, 0);
+    }
+
+    #[test]
+    fn test_pause_flags() {
+        let mut pool = sol_pool();
+        assert!(pool.require_not_paused(PAUSE_ALL).is_ok());
+
+        // Halt deposits, keep withdrawals open
+        pool.paused = PAUSE_SHIELD;
+        assert_eq!(
+            pool.require_not_paused(PAUSE_SHIELD).unwrap_err(),
+            NyxError::OperationPaused.into()
+        );
+        assert!(pool.require_not_paused(PAUSE_TRANSFER).is_ok());
+        assert!(pool.require_not_paused(PAUSE_UNSHIELD).is_ok());
+    }
+
+    #[test]
+    fn test_nullifier_finality_window() {
+        let mut pool = sol_pool();
+        assert!(!pool.is_nullifier_final(10, 13));
+        assert!(pool.is_nullifier_final(10, 14));
+
+        assert_eq!(
+            pool.set_nullifier_finality_epochs(0).unwrap_err(),
+            NyxError::FinalityWindowTooShort.into()
+        );
+        pool.set_nullifier_finality_epochs(1).unwrap();
+        assert!(pool.is_nullifier_final(10, 11));
+        assert!(!pool.is_nullifier_final(u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn test_withdrawal_delay() {
+        let mut pool = sol_pool();
+        assert_eq!(pool.withdrawal_unlock_slot(100), None);
+
+        pool.set_withdrawal_delay(50).unwrap();
+        assert_eq!(pool.withdrawal_unlock_slot(100), Some(150));
+        assert_eq!(pool.withdrawal_unlock_slot(u64::MAX), Some(u64::MAX));
+
+        assert_eq!(
+            pool.set_withdrawal_delay(MAX_WITHDRAWAL_DELAY_SLOTS + 1).unwrap_err(),
+            NyxError::WithdrawalDelayTooLong.into()
+        );
+        pool.set_withdrawal_delay(0).unwrap();
+        assert_eq!(pool.withdrawal_unlock_slot(100), None);
+    }
+
+    #[test]
+    fn test_deposit_caps() {
+        let mut pool = sol_pool();
+        assert!(pool.check_deposit_caps(u64::MAX, 0).is_ok());
+
+        pool.set_deposit_caps(1_000, 5_000);
+        assert!(pool.check_deposit_caps(1_000, 4_000).is_ok());
+        assert_eq!(
+            pool.check_deposit_caps(1_001, 0).unwrap_err(),
+            NyxError::DepositTooLarge.into()
+        );
+        assert_eq!(
+            pool.check_deposit_caps(1_000, 4_001).unwrap_err(),
+            NyxError::PoolCapReached.into()
+        );
+        assert_eq!(
+            pool.check_deposit_caps(1, u64::MAX).unwrap_err(),
+            NyxError::PoolCapReached.into()
+        );
+
+        // Either cap may be lifted on its own
+        pool.set_deposit_caps(0, 5_000);
+        assert!(pool.check_deposit_caps(5_000, 0).is_ok());
+    }
+
+    #[test]
+    fn test_compressed_tree() {
+        let mut pool = sol_pool();
+        let mut history = root_history(DEFAULT_ROOT_HISTORY_SIZE);
+        let tree = Pubkey::new_unique();
+        pool.use_compressed_tree(tree, 26, [1u8; 32]).unwrap();
+        assert!(pool.is_compressed());
+        assert_eq!(pool.capacity(), 1 << 26);
+        assert_eq!(pool.current_root(), [1u8; 32]);
+
+        // Local inserts are refused; appends only record the new root
+        assert!(pool.add_commitment([9u8; 32], &mut history).is_err());
+        pool.record_compressed_commitment([5u8; 32], [2u8; 32], 0, &mut history);
+        pool.record_compressed_commitment([6u8; 32], [3u8; 32], 1, &mut history);
+        assert_eq!(pool.commitment_count(), 2);
+        assert_eq!(pool.current_root(), [3u8; 32]);
+        assert!(pool.is_valid_root(&[1u8; 32], &history));
+        assert!(pool.is_valid_root(&[2u8; 32], &history));
+
+        assert_eq!(
+            pool.use_compressed_tree(tree, 26, [0u8; 32]).unwrap_err(),
+            NyxError::CompressedTreeUnsupported.into()
+        );
+        assert_eq!(
+            pool.rotate_tree(&mut history).unwrap_err(),
+            NyxError::CompressedTreeUnsupported.into()
+        );
+
+        // A pool holding commitments keeps its local tree
+        let mut used = sol_pool();
+        used.add_commitment([1u8; 32], &mut history).unwrap();
+        assert_eq!(
+            used.use_compressed_tree(tree, 26, [0u8; 32]).unwrap_err(),
+            NyxError::PoolNotEmpty.into()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_commitments() {
+        let mut pool = sol_pool();
+        let mut history = root_history(DEFAULT_ROOT_HISTORY_SIZE);
+        pool.add_commitment([1u8; 32], &mut history).unwrap();
+        assert_eq!(
+            pool.add_commitment([1u8; 32], &mut history).unwrap_err(),
+            NyxError::DuplicateCommitment.into()
+        );
+        assert_eq!(
+            pool.add_commitment([0u8; 32], &mut history).unwrap_err(),
+            NyxError::InvalidCommitment.into()
+        );
+        assert_eq!(pool.commitment_count(), 1);
+
+        // Only the recent window is remembered
+        for i in 2..=RECENT_COMMITMENTS_SIZE as u8 + 1 {
+            pool.add_commitment([i; 32], &mut history).unwrap();
+        }
+        assert!(pool.check_new_commitment(&[1u8; 32]).is_ok());
+        assert!(pool.check_new_commitment(&[2u8; 32]).is_err());
+
+        // Compressed appends are remembered too
+        let mut compressed = sol_pool();
+        compressed.use_compressed_tree(Pubkey::new_unique(), 26, [1u8; 32]).unwrap();
+        compressed.record_compressed_commitment([5u8; 32], [2u8; 32], 0, &mut history);
+        assert_eq!(
+            compressed.check_new_commitment(&[5u8; 32]).unwrap_err(),
+            NyxError::DuplicateCommitment.into()
+        );
+    }
+
+    #[test]
+    fn test_tree_rotation() {
+        let mut pool = sol_pool();
+        let mut history = root_history(DEFAULT_ROOT_HISTORY_SIZE);
+        pool.add_commitment([1u8; 32], &mut history).unwrap();
+        assert_eq!(pool.rotate_tree(&mut history).unwrap_err(), NyxError::TreeNotFull.into());
+
+        // Pretend the tree filled up
+        pool.merkle_tree.next_index = IncrementalMerkleTree::MAX_LEAVES;
+        assert!(pool.add_commitment([2u8; 32], &mut history).is_err());
+        let final_root = pool.current_root();
+
+        assert_eq!(pool.rotate_tree(&mut history).unwrap(), final_root);
+        assert_eq!(pool.tree_epoch, 1);
+        assert_eq!(pool.commitment_count(), 0);
+        assert!(!pool.is_valid_root(&final_root, &history));
+        assert!(history.roots.iter().all(|root| *root == [0u8; 32]));
+        assert_eq!(pool.add_commitment([3u8; 32], &mut history).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_root_history_window() {
+        let mut pool = sol_pool();
+        let mut history = root_history(3);
+        let mut roots = vec![pool.current_root()];
+        for i in 1..=5u8 {
+            pool.add_commitment([i; 32], &mut history).unwrap();
+            roots.push(pool.current_root());
+        }
+
+        // The current root and the three it replaced last are valid
+        for root in &roots[2..] {
+            assert!(pool.is_valid_root(root, &history));
+        }
+        assert!(!pool.is_valid_root(&roots[1], &history));
+        assert!(!pool.is_valid_root(&[0u8; 32], &history));
+
+        // Growing keeps every root; shrinking drops the oldest
+        history.resize(5).unwrap();
+        pool.add_commitment([6u8; 32], &mut history).unwrap();
+        roots.push(pool.current_root());
+        for root in &roots[2..] {
+            assert!(pool.is_valid_root(root, &history));
+        }
+        history.resize(2).unwrap();
+        assert!(!pool.is_valid_root(&roots[3], &history));
+        assert!(pool.is_valid_root(&roots[4], &history));
+        pool.add_commitment([7u8; 32], &mut history).unwrap();
+        assert!(!pool.is_valid_root(&roots[4], &history));
+        assert!(pool.is_valid_root(&roots[5], &history));
+
+        assert_eq!(history.resize(0).unwrap_err(), NyxError::InvalidRootHistorySize.into());
+        assert!(history.resize(MAX_ROOT_HISTORY_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn test_root_history_fits_account() {
+        let history = root_history(MAX_ROOT_HISTORY_SIZE);
+        let mut data = Vec::new();
+        history.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + RootHistory::space(MAX_ROOT_HISTORY_SIZE));
+        assert!(data.len() <= 10 * 1024);
+    }
+
+    #[test]
+    fn test_authority_handover() {
+        let mut pool = sol_pool();
+        let multisig = Pubkey::new_unique();
+        let typo = Pubkey::new_unique();
+
+        // Nothing proposed: nobody can accept
+        assert!(pool.accept_authority(&multisig).is_err());
+
+        // A wrong key can be replaced before it accepts
+        pool.propose_authority(typo);
+        pool.propose_authority(multisig);
+        assert!(pool.accept_authority(&typo).is_err());
+        assert_eq!(pool.authority, Pubkey::default());
+
+        pool.accept_authority(&multisig).unwrap();
+        assert_eq!(pool.authority, multisig);
+        assert_eq!(pool.pending_authority, Pubkey::default());
+        assert!(pool.accept_authority(&multisig).is_err());
+    }
+
+    #[test]
+    fn test_pool_pda_per_mint() {
+        let program_id = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+
+        let (sol_pool, _) = derive_pool_pda(&program_id, &NATIVE_MINT);
+        let (usdc_pool, _) = derive_pool_pda(&program_id, &usdc);
+        assert_ne!(sol_pool, usdc_pool);
+        assert_eq!(derive_pool_pda(&program_id, &usdc).0, usdc_pool);
+
+        // Vaults and nullifier markers follow the pool
+        let vault = |pool: &Pubkey| {
+            Pubkey::find_program_address(&[crate::token::VAULT_SEED, pool.as_ref()], &program_id).0
+        };
+        assert_ne!(vault(&sol_pool), vault(&usdc_pool));
+        let nullifier = [7u8; 32];
+        assert_ne!(
+            crate::nullifier::derive_nullifier_pda(&program_id, &sol_pool, 0, &nullifier),
+            crate::nullifier::derive_nullifier_pda(&program_id, &usdc_pool, 0, &nullifier)
+        );
+    }
+}
@@ -2,13 +2,15 @@
 //!
 //! Provides CPI (Cross-Program Invocation) helpers for:
 //! - Native SOL transfers (via System Program)
-//! - SPL Token transfers (via Token Program)
+//! - SPL Token and Token-2022 transfers (via `transfer_checked`)
 //!
 //! The pool uses PDAs as vault authorities, enabling trustless custody.
 
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{self, Transfer as SolTransfer};
-use anchor_spl::token::{self, Transfer as TokenTransfer, Token, TokenAccount};
+use anchor_spl::token_interface::{
+    self, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 /// Seeds for the pool vault PDA (controls pool's token accounts)
 pub const VAULT_SEED: &[u8] = b"vault";
@@ -76,46 +78,69 @@ pub fn transfer_sol_from_pool<'info>(
 
 /// Transfer SPL tokens from depositor to pool vault
 ///
+/// Fails unless the vault is credited the full `amount`. Commitments are
+/// opaque on-chain, so a Token-2022 transfer fee would leave the pool owing
+/// the depositor more than it holds; mints charging one cannot be shielded.
+///
 /// # Arguments
 /// * `depositor_token_account` - Depositor's token account
 /// * `vault_token_account` - Pool's vault token account
+/// * `mint` - The pool's mint
 /// * `depositor` - Signer authority
-/// * `token_program` - SPL Token program
+/// * `token_program` - SPL Token or Token-2022 program owning `mint`
 /// * `amount` - Amount of tokens to transfer
 pub fn transfer_spl_to_pool<'info>(
-    depositor_token_account: &Account<'info, TokenAccount>,
-    vault_token_account: &Account<'info, TokenAccount>,
+    depositor_token_account: &InterfaceAccount<'info, TokenAccount>,
+    vault_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
     depositor: &Signer<'info>,
-    token_program: &Program<'info, Token>,
+    token_program: &Interface<'info, TokenInterface>,
     amount: u64,
 ) -> Result<()> {
-    let cpi_accounts = TokenTransfer {
+    let balance_before = vault_token_account.amount;
+
+    let cpi_accounts = TransferChecked {
         from: depositor_token_account.to_account_info(),
+        mint: mint.to_account_info(),
         to: vault_token_account.to_account_info(),
         authority: depositor.to_account_info(),
     };
 
     let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-    token::transfer(cpi_context, amount)
+    token_interface::transfer_checked(cpi_context, amount, mint.decimals)?;
+
+    // Measure rather than predict the fee, so any fee extension is covered
+    vault_token_account.reload()?;
+    let credited = vault_token_account
+        .amount
+        .checked_sub(balance_before)
+        .ok_or_else(|| error!(TokenError::InvalidTokenAccount))?;
+    require!(credited == amount, TokenError::TransferFeeCharged);
+
+    Ok(())
 }
 
 /// Transfer SPL tokens from pool vault to recipient
 ///
-/// Uses PDA signing for the vault authority.
+/// Uses PDA signing for the vault authority. For Token-2022 mints with a
+/// transfer fee the recipient receives `amount` less the fee.
 ///
 /// # Arguments
 /// * `vault_token_account` - Pool's vault token account
 /// * `recipient_token_account` - Recipient's token account
+/// * `mint` - The pool's mint
 /// * `vault_authority` - PDA that owns the vault token account
-/// * `token_program` - SPL Token program
+/// * `token_program` - SPL Token or Token-2022 program owning `mint`
 /// * `amount` - Amount of tokens to transfer
 /// * `pool_key` - Pool pubkey for PDA derivation
 /// * `vault_bump` - Bump seed for vault PDA
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_spl_from_pool<'info>(
-    vault_token_account: &Account<'info, TokenAccount>,
-    recipient_token_account: &Account<'info, TokenAccount>,
+    vault_token_account: &InterfaceAccount<'info, TokenAccount>,
+    recipient_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
     vault_authority: &AccountInfo<'info>,
-    token_program: &Program<'info, Token>,
+    token_program: &Interface<'info, TokenInterface>,
     amount: u64,
     pool_key: &Pubkey,
     vault_bump: u8,
@@ -124,8 +149,9 @@ pub fn transfer_spl_from_pool<'info>(
     let bump_bytes = [vault_bump];
     let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, pool_key_bytes, &bump_bytes]];
 
-    let cpi_accounts = TokenTransfer {
+    let cpi_accounts = TransferChecked {
         from: vault_token_account.to_account_info(),
+        mint: mint.to_account_info(),
         to: recipient_token_account.to_account_info(),
         authority: vault_authority.to_account_info(),
     };
@@ -136,7 +162,7 @@ pub fn transfer_spl_from_pool<'info>(
         signer_seeds,
     );
 
-    token::transfer(cpi_context, amount)
+    token_interface::transfer_checked(cpi_context, amount, mint.decimals)
 }
 
 /// Derive the vault PDA for a pool
@@ -153,6 +179,8 @@ pub enum TokenError {
     InvalidTokenAccount,
     #[msg("Token mint mismatch")]
     MintMismatch,
+    #[msg("Vault was credited less than the amount: the mint charges a transfer fee")]
+    TransferFeeCharged,
 }
 
 #[cfg(test)]
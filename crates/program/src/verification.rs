@@ -13,13 +13,20 @@
 //!    - Uses Solana's BN254 precompiles (available since 1.18.x)
 //!    - Format: [proof_a (64) | proof_b (128) | proof_c (64)]
 //!
-//! The proof type is detected automatically based on proof size.
+//! The proof type is detected automatically based on proof size. Groth16
+//! proofs are rejected until the pool authority enables them with
+//! `enable_zk`, which requires the pool's verifying key to be set; from then
+//! on MVP proofs, which prove nothing, are rejected instead.
 
 use anchor_lang::prelude::*;
 use solana_program::ed25519_program;
 use solana_program::keccak;
 
-use crate::groth16::{verify_groth16_transfer, PROOF_SIZE as GROTH16_PROOF_SIZE};
+use crate::groth16::{
+    asset_id, recipient_hash, verify_groth16_transfer, verify_groth16_unshield,
+    TransferPublicInputs, UnshieldPublicInputs, UnshieldVerifyingKeyData, VerifyingKeyData,
+    PROOF_SIZE as GROTH16_PROOF_SIZE,
+};
 
 /// MVP proof size (signature + pubkey)
 pub const MVP_PROOF_SIZE: usize = 96;
@@ -75,16 +82,55 @@ impl MvpProof {
 
 /// Build the message to be signed for a transfer proof
 ///
-/// Message = keccak256(nullifier || new_commitment || root)
+/// Message = keccak256(nullifier || new_commitment || root || fee), with
+/// the fee as u64 little-endian
 pub fn build_transfer_message(
     nullifier: &[u8; 32],
     new_commitment: &[u8; 32],
     root: &[u8; 32],
+    fee: u64,
 ) -> [u8; 32] {
-    let mut data = Vec::with_capacity(96);
+    let mut data = Vec::with_capacity(104);
     data.extend_from_slice(nullifier);
     data.extend_from_slice(new_commitment);
     data.extend_from_slice(root);
+    data.extend_from_slice(&fee.to_le_bytes());
+    keccak::hash(&data).to_bytes()
+}
+
+/// Build the message to be signed for a join-split proof
+///
+/// Message = keccak256(nullifier_0 || nullifier_1 || commitment_0 || commitment_1 || root)
+pub fn build_joinsplit_message(
+    nullifiers: &[[u8; 32]; 2],
+    new_commitments: &[[u8; 32]; 2],
+    root: &[u8; 32],
+) -> [u8; 32] {
+    let mut data = Vec::with_capacity(160);
+    for value in nullifiers.iter().chain(new_commitments.iter()) {
+        data.extend_from_slice(value);
+    }
+    data.extend_from_slice(root);
+    keccak::hash(&data).to_bytes()
+}
+
+/// Build the message to be signed for a sweep (N inputs, 1 output) proof
+///
+/// Message = keccak256(nullifier_0 || ... || nullifier_n || new_commitment || root || fee);
+/// with a single input this is the transfer message.
+pub fn build_sweep_message(
+    nullifiers: &[[u8; 32]],
+    new_commitment: &[u8; 32],
+    root: &[u8; 32],
+    fee: u64,
+) -> [u8; 32] {
+    let mut data = Vec::with_capacity(32 * (nullifiers.len() + 2) + 8);
+    for nullifier in nullifiers {
+        data.extend_from_slice(nullifier);
+    }
+    data.extend_from_slice(new_commitment);
+    data.extend_from_slice(root);
+    data.extend_from_slice(&fee.to_le_bytes());
     keccak::hash(&data).to_bytes()
 }
 
@@ -132,23 +178,54 @@ pub fn verify_signature(
     signature.iter().any(|&b| b != 0) && pubkey.iter().any(|&b| b != 0)
 }
 
+/// Reject Groth16 proofs for a pool that has not enabled them, and
+/// anything but a Groth16 proof for a pool that has
+///
+/// `verify_signature` only checks that an MVP proof is non-zero, so
+/// accepting one on a zk-enabled pool would let anyone spend from it.
+pub fn require_proof_enabled(proof: &[u8], zk_enabled: bool) -> Result<()> {
+    if zk_enabled {
+        require!(
+            ProofType::detect(proof) == Some(ProofType::Groth16),
+            VerificationError::InvalidProofFormat
+        );
+    } else if ProofType::detect(proof) == Some(ProofType::Groth16) {
+        return err!(VerificationError::ZkNotEnabled);
+    }
+    Ok(())
+}
+
 /// Verify a transfer proof
 ///
 /// Automatically detects proof type based on size:
 /// - 96 bytes: MVP signature proof
 /// - 256 bytes: Groth16 zkSNARK proof
 ///
+/// Only the proof type the pool accepts is verified; see
+/// `require_proof_enabled`.
+///
 /// # Arguments
 /// * `proof` - The proof bytes (96 or 256 bytes)
+/// * `vk` - The pool's verifying key (Groth16 only)
 /// * `nullifier` - The nullifier being spent
 /// * `new_commitment` - The new commitment being created
 /// * `root` - The Merkle root
+/// * `mint` - The pool's mint, whose asset id the proof must be for
+///   (Groth16 only)
+/// * `fee` - The part of the input paid to the relayer
+/// * `zk_enabled` - Whether the pool has enabled Groth16 proofs
+#[allow(clippy::too_many_arguments)]
 pub fn verify_transfer_proof(
     proof: &[u8],
+    vk: &VerifyingKeyData,
     nullifier: &[u8; 32],
     new_commitment: &[u8; 32],
     root: &[u8; 32],
+    mint: &Pubkey,
+    fee: u64,
+    zk_enabled: bool,
 ) -> Result<bool> {
+    require_proof_enabled(proof, zk_enabled)?;
     // Detect proof type
     let proof_type = ProofType::detect(proof)
         .ok_or(VerificationError::InvalidProofFormat)?;
@@ -158,15 +235,80 @@ pub fn verify_transfer_proof(
             // MVP: Ed25519 signature verification
             let mvp_proof = MvpProof::from_bytes(proof)
                 .ok_or(VerificationError::InvalidProofFormat)?;
-            let message = build_transfer_message(nullifier, new_commitment, root);
+            let message = build_transfer_message(nullifier, new_commitment, root, fee);
             let valid = verify_signature(&message, &mvp_proof.signature, &mvp_proof.pubkey);
             Ok(valid)
         }
         ProofType::Groth16 => {
             // Production: Groth16 zkSNARK verification
-            verify_groth16_transfer(proof, root, nullifier, new_commitment)
-                .map_err(|_| VerificationError::VerificationFailed.into())
+            // The asset id comes from the pool's mint, so a note of another
+            // asset cannot be spent here
+            let inputs = TransferPublicInputs {
+                merkle_root: *root,
+                nullifier: *nullifier,
+                new_commitment: *new_commitment,
+                asset_id: asset_id(mint),
+                fee,
+            };
+            verify_groth16_transfer(proof, vk, &inputs)
+        }
+    }
+}
+
+/// Verify a join-split (2 inputs, 2 outputs) proof
+///
+/// Only MVP signature proofs are accepted: the Groth16 verifying key a pool
+/// holds is for the single-input transfer circuit, so a 256-byte proof is
+/// rejected until the pool can hold a join-split key. MVP proofs prove
+/// nothing, so join-splits are refused outright on `zk_enabled` pools.
+pub fn verify_joinsplit_proof(
+    proof: &[u8],
+    nullifiers: &[[u8; 32]; 2],
+    new_commitments: &[[u8; 32]; 2],
+    root: &[u8; 32],
+    zk_enabled: bool,
+) -> Result<bool> {
+    require!(!zk_enabled, VerificationError::MultiInputZkUnsupported);
+    let proof_type = ProofType::detect(proof)
+        .ok_or(VerificationError::InvalidProofFormat)?;
+
+    match proof_type {
+        ProofType::Signature => {
+            let mvp_proof = MvpProof::from_bytes(proof)
+                .ok_or(VerificationError::InvalidProofFormat)?;
+            let message = build_joinsplit_message(nullifiers, new_commitments, root);
+            let valid = verify_signature(&message, &mvp_proof.signature, &mvp_proof.pubkey);
+            Ok(valid)
         }
+        ProofType::Groth16 => err!(VerificationError::UnsupportedProofType),
+    }
+}
+
+/// Verify a sweep proof spending several inputs into one output
+///
+/// Like join-split, only MVP signature proofs are accepted, and not at all
+/// on `zk_enabled` pools, until the pool can hold a multi-input key.
+pub fn verify_sweep_proof(
+    proof: &[u8],
+    nullifiers: &[[u8; 32]],
+    new_commitment: &[u8; 32],
+    root: &[u8; 32],
+    fee: u64,
+    zk_enabled: bool,
+) -> Result<bool> {
+    require!(!zk_enabled, VerificationError::MultiInputZkUnsupported);
+    let proof_type = ProofType::detect(proof)
+        .ok_or(VerificationError::InvalidProofFormat)?;
+
+    match proof_type {
+        ProofType::Signature => {
+            let mvp_proof = MvpProof::from_bytes(proof)
+                .ok_or(VerificationError::InvalidProofFormat)?;
+            let message = build_sweep_message(nullifiers, new_commitment, root, fee);
+            let valid = verify_signature(&message, &mvp_proof.signature, &mvp_proof.pubkey);
+            Ok(valid)
+        }
+        ProofType::Groth16 => err!(VerificationError::UnsupportedProofType),
     }
 }
 
@@ -176,22 +318,34 @@ pub fn verify_transfer_proof(
 /// - 96 bytes: MVP signature proof
 /// - 256 bytes: Groth16 zkSNARK proof
 ///
-/// For Groth16 proofs, the recipient and amount are derived from
-/// the public inputs embedded in the proof verification.
+/// Groth16 proofs are checked against the unshield circuit, which takes
+/// the recipient, amount, relayer fee and the asset id of the pool's mint
+/// as public inputs. Only the proof type the pool accepts is verified; see
+/// `require_proof_enabled`.
 ///
 /// # Arguments
 /// * `proof` - The proof bytes (96 or 256 bytes)
+/// * `vk` - The pool's unshield verifying key (Groth16 only)
 /// * `nullifier` - The nullifier being spent
-/// * `recipient` - The recipient pubkey (used for MVP only)
-/// * `amount` - The amount being withdrawn (used for MVP only)
+/// * `recipient` - The recipient pubkey
+/// * `amount` - The amount being withdrawn, including the relayer fee
+/// * `relayer_fee` - The part of `amount` paid to the relayer (Groth16 only)
 /// * `root` - The Merkle root
+/// * `mint` - The pool's mint, whose asset id the proof must be for
+/// * `zk_enabled` - Whether the pool has enabled Groth16 proofs
+#[allow(clippy::too_many_arguments)]
 pub fn verify_unshield_proof(
     proof: &[u8],
+    vk: &UnshieldVerifyingKeyData,
     nullifier: &[u8; 32],
     recipient: &Pubkey,
     amount: u64,
+    relayer_fee: u64,
     root: &[u8; 32],
+    mint: &Pubkey,
+    zk_enabled: bool,
 ) -> Result<bool> {
+    require_proof_enabled(proof, zk_enabled)?;
     // Detect proof type
     let proof_type = ProofType::detect(proof)
         .ok_or(VerificationError::InvalidProofFormat)?;
@@ -207,10 +361,15 @@ pub fn verify_unshield_proof(
         }
         ProofType::Groth16 => {
             // Production: Groth16 zkSNARK verification
-            // For unshield, we create a commitment to 0 (the "burn" commitment)
-            let burn_commitment = [0u8; 32];
-            verify_groth16_transfer(proof, root, nullifier, &burn_commitment)
-                .map_err(|_| VerificationError::VerificationFailed.into())
+            let inputs = UnshieldPublicInputs {
+                merkle_root: *root,
+                nullifier: *nullifier,
+                recipient_hash: recipient_hash(recipient),
+                amount,
+                relayer_fee,
+                asset_id: asset_id(mint),
+            };
+            verify_groth16_unshield(proof, vk, &inputs)
         }
     }
 }
@@ -224,11 +383,19 @@ pub enum VerificationError {
     VerificationFailed,
     #[msg("Invalid public key")]
     InvalidPublicKey,
+    #[msg("Groth16 proofs are not enabled for this pool")]
+    ZkNotEnabled,
+    #[msg("Proof type not supported for this instruction")]
+    UnsupportedProofType,
+    #[msg("Multi-input spends have no Groth16 verifier and are disabled with zk enabled")]
+    MultiInputZkUnsupported,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::groth16::{NUM_IC, UNSHIELD_NUM_IC};
+    use crate::state::NATIVE_MINT;
 
     #[test]
     fn test_build_transfer_message() {
@@ -236,16 +403,151 @@ mod tests {
         let new_commitment = [2u8; 32];
         let root = [3u8; 32];
 
-        let msg1 = build_transfer_message(&nullifier, &new_commitment, &root);
-        let msg2 = build_transfer_message(&nullifier, &new_commitment, &root);
+        let msg1 = build_transfer_message(&nullifier, &new_commitment, &root, 0);
+        let msg2 = build_transfer_message(&nullifier, &new_commitment, &root, 0);
 
         // Should be deterministic
         assert_eq!(msg1, msg2);
 
         // Different inputs should produce different messages
         let nullifier2 = [4u8; 32];
-        let msg3 = build_transfer_message(&nullifier2, &new_commitment, &root);
+        let msg3 = build_transfer_message(&nullifier2, &new_commitment, &root, 0);
         assert_ne!(msg1, msg3);
+
+        // The fee is bound
+        assert_ne!(msg1, build_transfer_message(&nullifier, &new_commitment, &root, 1));
+    }
+
+    #[test]
+    fn test_groth16_requires_zk_enabled() {
+        let groth16 = [1u8; GROTH16_PROOF_SIZE];
+        let mvp = [1u8; MVP_PROOF_SIZE];
+
+        assert_eq!(
+            require_proof_enabled(&groth16, false).unwrap_err(),
+            VerificationError::ZkNotEnabled.into()
+        );
+        assert!(require_proof_enabled(&groth16, true).is_ok());
+        assert!(require_proof_enabled(&mvp, false).is_ok());
+    }
+
+    #[test]
+    fn test_mvp_proofs_rejected_with_zk_enabled() {
+        // Any non-zero bytes pass as an MVP proof, so a zk pool refuses them
+        let mvp = [1u8; MVP_PROOF_SIZE];
+        let (nullifier, commitment, root) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let (mint, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // Never reached: the proof type is refused first
+        let vk = VerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; NUM_IC],
+        };
+        let unshield_vk = UnshieldVerifyingKeyData {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: [[0u8; 64]; UNSHIELD_NUM_IC],
+        };
+
+        let transfer = |zk_enabled| {
+            verify_transfer_proof(&mvp, &vk, &nullifier, &commitment, &root, &mint, 0, zk_enabled)
+        };
+        assert!(transfer(false).unwrap());
+        assert_eq!(transfer(true).unwrap_err(), VerificationError::InvalidProofFormat.into());
+
+        let unshield = |zk_enabled| {
+            verify_unshield_proof(
+                &mvp,
+                &unshield_vk,
+                &nullifier,
+                &recipient,
+                1_000,
+                0,
+                &root,
+                &NATIVE_MINT,
+                zk_enabled,
+            )
+        };
+        assert!(unshield(false).unwrap());
+        assert_eq!(unshield(true).unwrap_err(), VerificationError::InvalidProofFormat.into());
+
+        // Nor anything of another length
+        assert_eq!(
+            require_proof_enabled(&[1u8; 64], true).unwrap_err(),
+            VerificationError::InvalidProofFormat.into()
+        );
+    }
+
+    #[test]
+    fn test_build_joinsplit_message() {
+        let nullifiers = [[1u8; 32], [2u8; 32]];
+        let commitments = [[3u8; 32], [4u8; 32]];
+        let root = [5u8; 32];
+
+        let msg = build_joinsplit_message(&nullifiers, &commitments, &root);
+        assert_eq!(msg, build_joinsplit_message(&nullifiers, &commitments, &root));
+
+        // Output order is bound
+        let swapped = [[4u8; 32], [3u8; 32]];
+        assert_ne!(msg, build_joinsplit_message(&nullifiers, &swapped, &root));
+    }
+
+    #[test]
+    fn test_joinsplit_rejects_groth16() {
+        let result = verify_joinsplit_proof(
+            &[1u8; GROTH16_PROOF_SIZE],
+            &[[1u8; 32], [2u8; 32]],
+            &[[3u8; 32], [4u8; 32]],
+            &[5u8; 32],
+            false,
+        );
+        assert_eq!(result.unwrap_err(), VerificationError::UnsupportedProofType.into());
+    }
+
+    #[test]
+    fn test_multi_input_rejected_with_zk_enabled() {
+        // Even a well-formed MVP proof: it would prove nothing on a zk pool
+        let mvp = [1u8; MVP_PROOF_SIZE];
+        let nullifiers = [[1u8; 32], [2u8; 32]];
+        let commitments = [[3u8; 32], [4u8; 32]];
+        let root = [5u8; 32];
+
+        assert!(verify_joinsplit_proof(&mvp, &nullifiers, &commitments, &root, false).unwrap());
+        assert_eq!(
+            verify_joinsplit_proof(&mvp, &nullifiers, &commitments, &root, true).unwrap_err(),
+            VerificationError::MultiInputZkUnsupported.into()
+        );
+        assert!(verify_sweep_proof(&mvp, &nullifiers, &commitments[0], &root, 0, false).unwrap());
+        assert_eq!(
+            verify_sweep_proof(&mvp, &nullifiers, &commitments[0], &root, 0, true).unwrap_err(),
+            VerificationError::MultiInputZkUnsupported.into()
+        );
+    }
+
+    #[test]
+    fn test_build_sweep_message() {
+        let nullifiers = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let commitment = [4u8; 32];
+        let root = [5u8; 32];
+
+        // A single input signs the transfer message
+        assert_eq!(
+            build_sweep_message(&nullifiers[..1], &commitment, &root, 7),
+            build_transfer_message(&nullifiers[0], &commitment, &root, 7)
+        );
+
+        // Every input is bound
+        let msg = build_sweep_message(&nullifiers, &commitment, &root, 0);
+        assert_ne!(msg, build_sweep_message(&nullifiers[..2], &commitment, &root, 0));
+        let groth16 = [1u8; GROTH16_PROOF_SIZE];
+        assert_eq!(
+            verify_sweep_proof(&groth16, &nullifiers, &commitment, &root, 0, false).unwrap_err(),
+            VerificationError::UnsupportedProofType.into()
+        );
     }
 
     #[test]
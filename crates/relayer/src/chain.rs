@@ -0,0 +1,44 @@
+//! The cluster the relayer submits to
+//!
+//! `Chain` is the little of the RPC API the relayer needs, so tests can run
+//! it against an in-memory cluster. `RpcClient` implements it over JSON-RPC.
+
+use veil_core::rpc::{RpcClient, TransactionStatus};
+use veil_core::transaction::Pubkey;
+
+use crate::error::RelayError;
+
+/// RPC calls the relayer makes
+pub trait Chain: Send + Sync {
+    /// Blockhash to build transactions against
+    fn latest_blockhash(&self) -> Result<[u8; 32], RelayError>;
+
+    /// Send a signed transaction, returning its signature
+    fn send_transaction(&self, transaction: &[u8]) -> Result<String, RelayError>;
+
+    /// Status of a sent transaction, `None` while the cluster has not seen it
+    fn transaction_status(&self, signature: &str) -> Result<Option<TransactionStatus>, RelayError>;
+
+    /// Prices (micro-lamports per compute unit) recent transactions locking
+    /// any of `accounts` paid, one per slot
+    fn recent_priority_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>, RelayError>;
+}
+
+impl Chain for RpcClient {
+    fn latest_blockhash(&self) -> Result<[u8; 32], RelayError> {
+        self.get_latest_blockhash().map_err(|e| RelayError::Chain(e.to_string()))
+    }
+
+    fn send_transaction(&self, transaction: &[u8]) -> Result<String, RelayError> {
+        RpcClient::send_transaction(self, transaction).map_err(|e| RelayError::Chain(e.to_string()))
+    }
+
+    fn transaction_status(&self, signature: &str) -> Result<Option<TransactionStatus>, RelayError> {
+        self.get_signature_status(signature).map_err(|e| RelayError::Chain(e.to_string()))
+    }
+
+    fn recent_priority_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>, RelayError> {
+        self.get_recent_prioritization_fees(accounts)
+            .map_err(|e| RelayError::Chain(e.to_string()))
+    }
+}
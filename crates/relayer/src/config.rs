@@ -0,0 +1,175 @@
+//! Configuration from the environment
+//!
+//! - `VEIL_RELAYER_LISTEN`: address to serve on (default `127.0.0.1:8080`)
+//! - `VEIL_RPC_URL`: RPC endpoint (default: public devnet)
+//! - `VEIL_PROGRAM_ID`: deployed program (default: the program's declared id)
+//! - `VEIL_KEYPAIR`: fee payer keypair file (default: `~/.config/solana/id.json`)
+//! - `VEIL_TRANSFER_VK`, `VEIL_UNSHIELD_VK`: verifying keys of the pool's
+//!   circuits, arkworks-compressed or snarkjs `verification_key.json`; an
+//!   operation without its key is not relayed
+//! - `VEIL_RELAYER_FEE_BPS`: advertised fee (default `DEFAULT_FEE_BPS`)
+//! - `VEIL_RELAYER_MIN_FEE`: minimum fee in lamports (default 5000)
+//! - `VEIL_RELAYER_QUOTE_SECS`: time quotes are honoured for (default 60)
+//! - `VEIL_RELAYER_WORK_BITS`: proof of work asked for in quotes, in leading
+//!   zero bits (default 0: none, and unquoted requests are relayed)
+//! - `VEIL_RELAYER_CU_TRANSFER`, `VEIL_RELAYER_CU_UNSHIELD`: compute units
+//!   requested per operation (default 400000)
+//! - `VEIL_RELAYER_PRIORITY_PERCENTILE`: percentile of recent priority
+//!   prices bid (default 75)
+//! - `VEIL_RELAYER_MIN_PRIORITY_PRICE`, `VEIL_RELAYER_MAX_PRIORITY_PRICE`:
+//!   bounds of the bid in micro-lamports per compute unit (default 0 and
+//!   1000000)
+//! - `VEIL_LOOKUP_TABLES`: comma-separated address lookup tables to build
+//!   v0 transactions with (default: none, building legacy ones)
+//! - `VEIL_JITO_URL`: Jito block engine to send transactions through as
+//!   bundles (default: none, sending over RPC)
+//! - `VEIL_JITO_TIP`: tip per bundle in lamports (default 10000)
+//! - `VEIL_RELAYER_POLL_MS`: job worker interval (default 2000)
+//! - `VEIL_RELAYER_DB`: job database, with the `sqlite` feature (default
+//!   `veil-relayer.db`)
+//! - `VEIL_RELAYER_DRY_RUN`: `true` to check requests but never send them,
+//!   without any RPC call, journaling them at `/debug` (default `false`;
+//!   see `server`)
+//! - `VEIL_RELAYER_DRY_RUN_DELAY_MS`: time a dry run takes to confirm a job
+//!   (default 2000)
+
+use std::env;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use veil_core::proof::zkey::read_verification_key_json;
+use veil_core::proof::TransferVerifier;
+use veil_core::rpc::{decode_pubkey, DEVNET_URL};
+use veil_core::transaction::Pubkey;
+
+use crate::jito::DEFAULT_TIP_LAMPORTS;
+use crate::priority::{ComputeUnitLimits, PriorityFeePolicy};
+use crate::relay::{FeeSchedule, DEFAULT_QUOTE_TTL_SECS};
+use crate::server::DEFAULT_DRY_RUN_DELAY;
+
+/// The program's `declare_id!`
+pub const DEFAULT_PROGRAM_ID: &str = "Vei1111111111111111111111111111111111111111";
+
+/// Default address to serve on
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// Default job database
+pub const DEFAULT_DB: &str = "veil-relayer.db";
+
+/// Default job worker interval
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Relayer configuration
+#[derive(Clone, Debug)]
+pub struct RelayerConfig {
+    pub listen: SocketAddr,
+    pub rpc_url: String,
+    pub program_id: Pubkey,
+    pub keypair_path: PathBuf,
+    pub transfer_vk: Option<PathBuf>,
+    pub unshield_vk: Option<PathBuf>,
+    pub fees: FeeSchedule,
+    /// Seconds a quote is honoured for
+    pub quote_ttl_secs: u64,
+    /// Leading zero bits of work asked for in quotes
+    pub work_bits: u8,
+    pub compute_units: ComputeUnitLimits,
+    pub priority: PriorityFeePolicy,
+    pub lookup_tables: Vec<Pubkey>,
+    pub jito_url: Option<String>,
+    /// Tip per bundle (lamports)
+    pub jito_tip: u64,
+    pub poll_interval: Duration,
+    pub db_path: PathBuf,
+    /// Check requests but never send them
+    pub dry_run: bool,
+    /// Time a dry run takes to confirm a job
+    pub dry_run_delay: Duration,
+}
+
+impl RelayerConfig {
+    /// Read the configuration from the environment
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = FeeSchedule::default();
+        let units = ComputeUnitLimits::default();
+        let priority = PriorityFeePolicy::default();
+        Ok(Self {
+            listen: parse_var("VEIL_RELAYER_LISTEN")?
+                .unwrap_or_else(|| DEFAULT_LISTEN.parse().expect("valid default address")),
+            rpc_url: env::var("VEIL_RPC_URL").unwrap_or_else(|_| DEVNET_URL.into()),
+            program_id: decode_pubkey(
+                &env::var("VEIL_PROGRAM_ID").unwrap_or_else(|_| DEFAULT_PROGRAM_ID.into()),
+            )
+            .map_err(|e| format!("VEIL_PROGRAM_ID: {}", e))?,
+            keypair_path: env::var("VEIL_KEYPAIR").map(PathBuf::from).unwrap_or_else(|_| {
+                let home = env::var("HOME").unwrap_or_default();
+                PathBuf::from(home).join(".config/solana/id.json")
+            }),
+            transfer_vk: env::var("VEIL_TRANSFER_VK").ok().map(PathBuf::from),
+            unshield_vk: env::var("VEIL_UNSHIELD_VK").ok().map(PathBuf::from),
+            fees: FeeSchedule {
+                fee_bps: parse_var("VEIL_RELAYER_FEE_BPS")?.unwrap_or(defaults.fee_bps),
+                min_fee: parse_var("VEIL_RELAYER_MIN_FEE")?.unwrap_or(defaults.min_fee),
+            },
+            quote_ttl_secs: parse_var("VEIL_RELAYER_QUOTE_SECS")?.unwrap_or(DEFAULT_QUOTE_TTL_SECS),
+            work_bits: parse_var("VEIL_RELAYER_WORK_BITS")?.unwrap_or_default(),
+            compute_units: ComputeUnitLimits {
+                transfer: parse_var("VEIL_RELAYER_CU_TRANSFER")?.unwrap_or(units.transfer),
+                unshield: parse_var("VEIL_RELAYER_CU_UNSHIELD")?.unwrap_or(units.unshield),
+            },
+            priority: PriorityFeePolicy {
+                percentile: parse_var("VEIL_RELAYER_PRIORITY_PERCENTILE")?
+                    .unwrap_or(priority.percentile),
+                min_price: parse_var("VEIL_RELAYER_MIN_PRIORITY_PRICE")?
+                    .unwrap_or(priority.min_price),
+                max_price: parse_var("VEIL_RELAYER_MAX_PRIORITY_PRICE")?
+                    .unwrap_or(priority.max_price),
+            },
+            lookup_tables: env::var("VEIL_LOOKUP_TABLES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|table| !table.is_empty())
+                .map(decode_pubkey)
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("VEIL_LOOKUP_TABLES: {}", e))?,
+            jito_url: env::var("VEIL_JITO_URL").ok(),
+            jito_tip: parse_var("VEIL_JITO_TIP")?.unwrap_or(DEFAULT_TIP_LAMPORTS),
+            poll_interval: parse_var("VEIL_RELAYER_POLL_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_POLL_INTERVAL),
+            db_path: env::var("VEIL_RELAYER_DB").unwrap_or_else(|_| DEFAULT_DB.into()).into(),
+            dry_run: parse_var("VEIL_RELAYER_DRY_RUN")?.unwrap_or_default(),
+            dry_run_delay: parse_var("VEIL_RELAYER_DRY_RUN_DELAY_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_DRY_RUN_DELAY),
+        })
+    }
+}
+
+/// Load a verifying key file, arkworks-compressed or snarkjs JSON
+pub fn load_verifier(path: &Path) -> Result<TransferVerifier, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let bytes = std::fs::read(path).map_err(|e| error(&e))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let json = std::str::from_utf8(&bytes).map_err(|e| error(&e))?;
+        let key = read_verification_key_json(json).map_err(|e| error(&e))?;
+        TransferVerifier::new(key).map_err(|e| error(&e))
+    } else {
+        TransferVerifier::from_vk_bytes(&bytes).map_err(|e| error(&e))
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("{}: {}", name, e)),
+        Err(_) => Ok(None),
+    }
+}
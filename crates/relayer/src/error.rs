@@ -0,0 +1,49 @@
+//! Relayer errors
+//!
+//! Requests the relayer refuses are 4xx responses, failures to reach the
+//! cluster 502 and storage failures 500, each with the error message as the body.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use thiserror::Error;
+
+/// Errors while quoting, checking or submitting a request
+#[derive(Error, Debug)]
+pub enum RelayError {
+    #[error("Unsupported operation")]
+    UnsupportedOperation,
+    #[error("Fee too low: {offered} lamports, schedule requires {required}")]
+    FeeTooLow { offered: u64, required: u64 },
+    #[error("Proof invalid")]
+    InvalidProof,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Invalid quote: {0}")]
+    InvalidQuote(String),
+    #[error("Proof of work missing: quote the request and do the work the quote asks for")]
+    InsufficientWork,
+    #[error("Unknown request: {0}")]
+    UnknownRequest(String),
+    #[error("Chain error: {0}")]
+    Chain(String),
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+impl RelayError {
+    /// HTTP status the error is reported with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::UnknownRequest(_) => StatusCode::NOT_FOUND,
+            Self::Chain(_) => StatusCode::BAD_GATEWAY,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for RelayError {
+    fn into_response(self) -> Response {
+        (self.status(), self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,107 @@
+//! Jito bundle submission
+//!
+//! A transaction sent over RPC is forwarded in the open, so a searcher who
+//! sees it can order its own transactions around it against the pool's
+//! state. A Jito block engine instead takes bundles: transactions that land
+//! together, in order, or not at all, and that nobody can insert others
+//! between.
+//!
+//! With a block engine configured (`Relayer::with_block_engine`) the
+//! relayer sends each transaction in a bundle with a transfer of its tip to
+//! one of the engine's tip accounts. A bundle the engine refuses, or an
+//! engine that cannot be reached, sends the transaction over RPC instead.
+//! The tip is added to quotes and required fees like the priority fee.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use veil_core::rpc::decode_pubkey;
+use veil_core::transaction::Pubkey;
+
+use crate::error::RelayError;
+
+/// Default tip per bundle (lamports)
+pub const DEFAULT_TIP_LAMPORTS: u64 = 10_000;
+
+/// Timeout of block engine calls
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Block engine calls the relayer makes
+pub trait BlockEngine: Send + Sync {
+    /// Accounts a bundle may pay its tip to
+    fn tip_accounts(&self) -> Result<Vec<Pubkey>, RelayError>;
+
+    /// Send signed transactions as one bundle, returning the bundle's id
+    fn send_bundle(&self, transactions: &[Vec<u8>]) -> Result<String, RelayError>;
+}
+
+/// Client of a Jito block engine's JSON-RPC bundle API
+pub struct JitoClient {
+    url: String,
+    agent: ureq::Agent,
+    /// Fetched on first use; the engine's tip accounts do not change
+    tip_accounts: Mutex<Vec<Pubkey>>,
+}
+
+impl JitoClient {
+    /// Client of the block engine at `url`, such as
+    /// `https://mainnet.block-engine.jito.wtf`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: format!("{}/api/v1/bundles", url.trim_end_matches('/')),
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            tip_accounts: Mutex::default(),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, RelayError> {
+        let error = |e: &dyn std::fmt::Display| RelayError::Chain(format!("{}: {}", method, e));
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(request)
+            .map_err(|e| error(&e))?
+            .into_json()
+            .map_err(|e| error(&e))?;
+        if let Some(e) = response.get("error") {
+            return Err(error(e));
+        }
+        Ok(response["result"].take())
+    }
+
+    fn lock_tip_accounts(&self) -> MutexGuard<'_, Vec<Pubkey>> {
+        self.tip_accounts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl BlockEngine for JitoClient {
+    fn tip_accounts(&self) -> Result<Vec<Pubkey>, RelayError> {
+        let mut cached = self.lock_tip_accounts();
+        if cached.is_empty() {
+            let missing = || RelayError::Chain("getTipAccounts: missing accounts".into());
+            let result = self.call("getTipAccounts", json!([]))?;
+            *cached = result
+                .as_array()
+                .ok_or_else(missing)?
+                .iter()
+                .map(|account| {
+                    let account = account.as_str().ok_or_else(missing)?;
+                    decode_pubkey(account).map_err(|e| RelayError::Chain(e.to_string()))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(cached.clone())
+    }
+
+    fn send_bundle(&self, transactions: &[Vec<u8>]) -> Result<String, RelayError> {
+        let encoded: Vec<String> =
+            transactions.iter().map(|tx| bs58::encode(tx).into_string()).collect();
+        let result = self.call("sendBundle", json!([encoded]))?;
+        result
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| RelayError::Chain("sendBundle: missing bundle id".into()))
+    }
+}
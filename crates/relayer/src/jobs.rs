@@ -0,0 +1,330 @@
+//! Relay jobs and their storage
+//!
+//! Every accepted request becomes a job, stored before the relayer first
+//! tries to land it:
+//! - `Pending` jobs are sent once their `next_attempt_at` passes; a send
+//!   that fails to reach the cluster is retried with exponential backoff
+//!   (see `RetryPolicy`)
+//! - `Submitted` jobs are polled until the cluster confirms or rejects
+//!   them; one the cluster never sees goes back to `Pending`
+//! - `Confirmed` and `Failed` jobs are final
+//!
+//! A nullifier can only be spent once, so a request for a nullifier that
+//! already has an unfailed job is answered with that job instead of a new
+//! one. Each change of a job is appended to its history, which `/status/:id`
+//! reports.
+//!
+//! `MemoryStorage` forgets everything on restart; `SqliteStorage` (the
+//! `sqlite` feature) keeps jobs in a database file, so a restarted relayer
+//! picks up where it stopped. Other backends only need to implement
+//! `JobStorage`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use veil_core::relayer::{RelayRequest, RelayResponse, RelayStatus};
+
+use crate::error::RelayError;
+
+/// A relayed request
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub request_id: String,
+    pub request: RelayRequest,
+    pub status: RelayStatus,
+    /// Failed sends so far
+    pub attempts: u32,
+    /// When the job was accepted (unix ms)
+    pub created_at: u64,
+    /// When the status last changed (unix ms)
+    pub updated_at: u64,
+    /// Earliest time to send a pending job (unix ms)
+    pub next_attempt_at: u64,
+}
+
+impl Job {
+    /// A pending job for `request`, due immediately
+    pub fn new(request: RelayRequest, now: u64) -> Self {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        Self {
+            request_id: hex::encode(id),
+            request,
+            status: RelayStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+            next_attempt_at: now,
+        }
+    }
+
+    /// The job as `/relay` reports it
+    pub fn response(&self) -> RelayResponse {
+        RelayResponse {
+            request_id: self.request_id.clone(),
+            status: self.status.clone(),
+            fee: self.request.fee,
+            estimated_confirmation_time: None,
+        }
+    }
+
+    /// Signature of the job's transaction, until it is confirmed
+    pub fn pending_signature(&self) -> Option<&str> {
+        match &self.status {
+            RelayStatus::Submitted { signature } => Some(signature),
+            _ => None,
+        }
+    }
+
+    /// Whether the job may still land
+    pub fn is_active(&self) -> bool {
+        !matches!(self.status, RelayStatus::Failed { .. })
+    }
+
+    /// Move to `status` at `now`, returning the history entry to record
+    pub fn transition(&mut self, status: RelayStatus, now: u64, note: Option<String>) -> JobEvent {
+        self.status = status;
+        self.updated_at = now;
+        JobEvent {
+            at: now,
+            status: self.status.clone(),
+            note,
+        }
+    }
+}
+
+/// One entry of a job's history
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobEvent {
+    /// Unix ms
+    pub at: u64,
+    pub status: RelayStatus,
+    /// Why the job changed, such as the error of a failed send
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Backoff between sends of a job the cluster could not be reached for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay after the first failed send (ms), doubled after each further one
+    pub base_delay_ms: u64,
+    /// Longest delay between sends (ms)
+    pub max_delay_ms: u64,
+    /// Sends before the job fails
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+            max_attempts: 6,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the `attempts`-th failed send
+    pub fn delay_ms(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(63);
+        self.base_delay_ms
+            .saturating_mul(1 << doublings)
+            .min(self.max_delay_ms)
+    }
+}
+
+/// Where jobs are kept
+///
+/// Implementations must be safe to share between the HTTP handlers and the
+/// job worker; each method is atomic.
+pub trait JobStorage: Send + Sync {
+    /// Store `job` unless an active job spends the same nullifier, returning
+    /// whichever job is stored
+    fn enqueue(&self, job: Job) -> Result<Job, RelayError>;
+
+    /// Look up a job
+    fn get(&self, request_id: &str) -> Result<Option<Job>, RelayError>;
+
+    /// Store a changed job and append `event` to its history
+    fn update(&self, job: &Job, event: JobEvent) -> Result<(), RelayError>;
+
+    /// A job's history, oldest first
+    fn history(&self, request_id: &str) -> Result<Vec<JobEvent>, RelayError>;
+
+    /// Pending jobs due at `now`
+    fn due(&self, now: u64) -> Result<Vec<Job>, RelayError>;
+
+    /// Jobs whose transaction was sent but not yet confirmed
+    fn submitted(&self) -> Result<Vec<Job>, RelayError>;
+}
+
+/// Storage that lives and dies with the process, for tests
+#[derive(Default)]
+pub struct MemoryStorage {
+    jobs: Mutex<HashMap<String, (Job, Vec<JobEvent>)>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (Job, Vec<JobEvent>)>> {
+        // A panic mid-update leaves no job half-written
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn select(&self, filter: impl Fn(&Job) -> bool) -> Vec<Job> {
+        let mut jobs: Vec<Job> =
+            self.lock().values().map(|(job, _)| job).filter(|job| filter(job)).cloned().collect();
+        jobs.sort_by_key(|job| job.created_at);
+        jobs
+    }
+}
+
+impl JobStorage for MemoryStorage {
+    fn enqueue(&self, job: Job) -> Result<Job, RelayError> {
+        let mut jobs = self.lock();
+        let existing = jobs.values().map(|(job, _)| job).find(|stored| {
+            stored.is_active() && stored.request.nullifier == job.request.nullifier
+        });
+        if let Some(existing) = existing {
+            return Ok(existing.clone());
+        }
+        let event = JobEvent {
+            at: job.created_at,
+            status: job.status.clone(),
+            note: None,
+        };
+        jobs.insert(job.request_id.clone(), (job.clone(), vec![event]));
+        Ok(job)
+    }
+
+    fn get(&self, request_id: &str) -> Result<Option<Job>, RelayError> {
+        Ok(self.lock().get(request_id).map(|(job, _)| job.clone()))
+    }
+
+    fn update(&self, job: &Job, event: JobEvent) -> Result<(), RelayError> {
+        let mut jobs = self.lock();
+        let (stored, history) = jobs
+            .get_mut(&job.request_id)
+            .ok_or_else(|| RelayError::UnknownRequest(job.request_id.clone()))?;
+        *stored = job.clone();
+        history.push(event);
+        Ok(())
+    }
+
+    fn history(&self, request_id: &str) -> Result<Vec<JobEvent>, RelayError> {
+        Ok(self.lock().get(request_id).map(|(_, history)| history.clone()).unwrap_or_default())
+    }
+
+    fn due(&self, now: u64) -> Result<Vec<Job>, RelayError> {
+        Ok(self.select(|job| job.status == RelayStatus::Pending && job.next_attempt_at <= now))
+    }
+
+    fn submitted(&self) -> Result<Vec<Job>, RelayError> {
+        Ok(self.select(|job| job.pending_signature().is_some()))
+    }
+}
+
+/// Current time in unix ms
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use veil_core::relayer::{OperationType, RelayOutput};
+
+    use super::*;
+
+    pub(crate) fn request(nullifier: u8) -> RelayRequest {
+        RelayRequest {
+            operation: OperationType::Transfer,
+            nullifier: [nullifier; 32],
+            output: RelayOutput::Commitment([2; 32]),
+            amount: 0,
+            proof: vec![0; 256],
+            merkle_root: [3; 32],
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee: 5_000,
+            max_fee: 5_000,
+            quote_hash: None,
+            work_nonce: None,
+        }
+    }
+
+    /// Behaviour every backend must share
+    pub(crate) fn check_storage(storage: &dyn JobStorage) {
+        let job = storage.enqueue(Job::new(request(1), 100)).unwrap();
+        assert_eq!(job.request_id.len(), 32);
+        assert_eq!(storage.get(&job.request_id).unwrap().unwrap().status, RelayStatus::Pending);
+        assert!(storage.get("missing").unwrap().is_none());
+
+        // Same nullifier: the stored job
+        let duplicate = storage.enqueue(Job::new(request(1), 200)).unwrap();
+        assert_eq!(duplicate.request_id, job.request_id);
+        let other = storage.enqueue(Job::new(request(2), 300)).unwrap();
+        assert_ne!(other.request_id, job.request_id);
+
+        let due = storage.due(300).unwrap();
+        let due: Vec<&str> = due.iter().map(|j| j.request_id.as_str()).collect();
+        assert_eq!(due, [&job.request_id, &other.request_id]);
+        assert_eq!(storage.due(100).unwrap().len(), 1);
+
+        let mut job = job;
+        let submitted = RelayStatus::Submitted { signature: "sig".into() };
+        let event = job.transition(submitted, 400, None);
+        storage.update(&job, event).unwrap();
+        let submitted = storage.submitted().unwrap();
+        assert_eq!(submitted.len(), 1);
+        assert_eq!(submitted[0].pending_signature(), Some("sig"));
+        assert_eq!(submitted[0].updated_at, 400);
+        assert_eq!(storage.due(1_000).unwrap().len(), 1);
+
+        // A failed job frees its nullifier
+        let event = job.transition(
+            RelayStatus::Failed { reason: "expired".into() },
+            500,
+            Some("dropped".into()),
+        );
+        storage.update(&job, event).unwrap();
+        let retried = storage.enqueue(Job::new(request(1), 600)).unwrap();
+        assert_ne!(retried.request_id, job.request_id);
+
+        let history = storage.history(&job.request_id).unwrap();
+        let statuses: Vec<_> = history.iter().map(|e| (e.at, e.note.as_deref())).collect();
+        assert_eq!(statuses, [(100, None), (400, None), (500, Some("dropped"))]);
+        assert_eq!(history[2].status, RelayStatus::Failed { reason: "expired".into() });
+
+        let mut unknown = Job::new(request(9), 0);
+        let event = unknown.transition(RelayStatus::Pending, 0, None);
+        assert!(storage.update(&unknown, event).is_err());
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        check_storage(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            base_delay_ms: 500,
+            max_delay_ms: 5_000,
+            max_attempts: 4,
+        };
+        let delays: Vec<u64> = (1..=6).map(|n| policy.delay_ms(n)).collect();
+        assert_eq!(delays, [500, 1_000, 2_000, 4_000, 5_000, 5_000]);
+        assert_eq!(policy.delay_ms(u32::MAX), 5_000);
+    }
+}
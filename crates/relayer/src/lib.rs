@@ -0,0 +1,49 @@
+//! Veil Reference Relayer
+//!
+//! Accepts relay requests from `veil_core::relayer::RelayerClient`, checks
+//! them against the relayer's fee schedule and the pool's verifying keys,
+//! and lands them on chain, paying the network fee in exchange for the
+//! proven relayer fee.
+//!
+//! Endpoints:
+//! - `POST /quote`: `QuoteRequest` -> `SignedQuote`, honoured by `/relay`
+//!   for requests carrying its hash until it expires; a relayer asking for
+//!   proof of work relays only requests carrying a quote and its work
+//! - `POST /relay`: `RelayRequest` -> `RelayResponse`
+//! - `GET /status/:id`: the current `RelayResponse` of an earlier request,
+//!   with its history (`server::JobReport`)
+//! - `GET /status/:id/ws`: websocket sending the request's `RelayResponse`
+//!   whenever its status changes, until it is final
+//! - `GET /health`: the operations relayed; `RelayerClient` probes it to
+//!   track which relayers are online
+//! - `GET /debug`: in dry-run mode only, the journal of what the relayer
+//!   accepted, refused and did with each job (`server::JournalEntry`)
+//!
+//! Refusals are 4xx responses with the reason as the body, as
+//! `RelayerClient` expects.
+//!
+//! # Modules
+//! - `chain`: The cluster the relayer submits to
+//! - `config`: Configuration from the environment
+//! - `error`: Errors and their HTTP statuses
+//! - `jito`: Bundle submission through a Jito block engine
+//! - `jobs`: Relay jobs, their retries and storage
+//! - `priority`: Compute-unit limits and priority fee bids
+//! - `relay`: Fee schedule, request checks and transaction building
+//! - `server`: HTTP routes and the job worker
+//! - `sqlite`: SQLite job storage (`sqlite` feature)
+
+pub mod chain;
+pub mod config;
+pub mod error;
+pub mod jito;
+pub mod jobs;
+pub mod priority;
+pub mod relay;
+pub mod server;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use config::RelayerConfig;
+pub use error::RelayError;
+pub use relay::{FeeSchedule, Relayer};
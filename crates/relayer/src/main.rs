@@ -0,0 +1,135 @@
+//! Run the reference relayer
+//!
+//! ```text
+//! VEIL_TRANSFER_VK=transfer.vk VEIL_UNSHIELD_VK=unshield.vk \
+//!     cargo run --release -p veil-relayer --features allow-insecure
+//! ```
+//!
+//! See `veil_relayer::config` for the configuration variables.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use veil_core::rpc::{read_keypair_file, RpcClient};
+use veil_relayer::config::load_verifier;
+use veil_relayer::jito::JitoClient;
+use veil_relayer::relay::pool_accounts;
+use veil_relayer::server::{run_jobs, serve, AppState};
+use veil_relayer::{Relayer, RelayerConfig};
+
+/// Writes the library's log records to stderr, next to the startup lines
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("veil-relayer: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let config = RelayerConfig::from_env()?;
+    let keypair = read_keypair_file(&config.keypair_path).map_err(|e| e.to_string())?;
+    let accounts = pool_accounts(config.program_id).ok_or("no pool address")?;
+
+    let rpc = RpcClient::new(&config.rpc_url);
+    // A dry run makes no RPC call, so builds legacy transactions
+    let lookup_tables = if config.dry_run {
+        Vec::new()
+    } else {
+        config
+            .lookup_tables
+            .iter()
+            .map(|table| rpc.get_lookup_table(table))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+    };
+
+    let mut relayer = Relayer::new(rpc, keypair, accounts, config.fees)
+        .with_quote_ttl(config.quote_ttl_secs)
+        .with_work_bits(config.work_bits)
+        .with_compute_unit_limits(config.compute_units)
+        .with_priority_fees(config.priority)
+        .with_lookup_tables(lookup_tables);
+    if let Some(path) = &config.transfer_vk {
+        relayer = relayer.with_transfer_verifier(load_verifier(path)?);
+    }
+    if let Some(path) = &config.unshield_vk {
+        relayer = relayer.with_unshield_verifier(load_verifier(path)?);
+    }
+    if let Some(url) = &config.jito_url {
+        eprintln!("block engine: {} (tip {} lamports)", url, config.jito_tip);
+        relayer = relayer.with_block_engine(JitoClient::new(url), config.jito_tip);
+    }
+    if relayer.supported_operations().is_empty() {
+        return Err("no verifying keys: set VEIL_TRANSFER_VK or VEIL_UNSHIELD_VK".into());
+    }
+
+    if config.dry_run {
+        eprintln!(
+            "dry run: nothing is sent, jobs confirm after {} ms, journal at /debug",
+            config.dry_run_delay.as_millis()
+        );
+    } else {
+        eprintln!("rpc: {}", config.rpc_url);
+    }
+    eprintln!("fee payer: {}", bs58::encode(relayer.pubkey()).into_string());
+    eprintln!("operations: {:?}", relayer.supported_operations());
+    if !config.dry_run {
+        match relayer.refresh_priority_price() {
+            Ok(price) => eprintln!("priority price: {} micro-lamports per unit", price),
+            Err(e) => eprintln!("priority price: {}, bidding 0 until the worker reprices", e),
+        }
+        for table in &config.lookup_tables {
+            eprintln!("lookup table: {}", bs58::encode(table).into_string());
+        }
+    }
+    if config.work_bits > 0 {
+        eprintln!("proof of work: {} bits", config.work_bits);
+    }
+    eprintln!("listening on {}", config.listen);
+
+    #[cfg(feature = "sqlite")]
+    let jobs = {
+        eprintln!("jobs: {}", config.db_path.display());
+        veil_relayer::sqlite::SqliteStorage::open(&config.db_path).map_err(|e| e.to_string())?
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let jobs = {
+        eprintln!("jobs: in memory, lost on restart");
+        veil_relayer::jobs::MemoryStorage::new()
+    };
+
+    let mut state = AppState::new(relayer, jobs);
+    if config.dry_run {
+        state = state.with_dry_run(config.dry_run_delay);
+    }
+    let state = Arc::new(state);
+    tokio::spawn(run_jobs(state.clone(), config.poll_interval));
+    serve(config.listen, state).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,131 @@
+//! Compute budgets and priority fees
+//!
+//! During congestion leaders schedule transactions by the price they bid
+//! per compute unit, and a transaction bidding nothing is often dropped. The
+//! relayer bids a percentile of the prices recent transactions locking the
+//! pool paid (`getRecentPrioritizationFees`), within the bounds of its
+//! `PriorityFeePolicy`, and refreshes the bid on every pass of the job
+//! worker.
+//!
+//! The bid is paid on the compute units a transaction requests, not those
+//! it uses, so each operation requests its own limit (`ComputeUnitLimits`).
+//! What the bid costs is added to quotes and required fees: fees rise with
+//! congestion instead of the relayer paying for it.
+
+use veil_core::relayer::OperationType;
+
+use crate::relay::DEFAULT_COMPUTE_UNIT_LIMIT;
+
+/// Default percentile of recent prices bid
+pub const DEFAULT_PRIORITY_PERCENTILE: u8 = 75;
+
+/// Default highest price bid (micro-lamports per compute unit)
+pub const DEFAULT_MAX_PRIORITY_PRICE: u64 = 1_000_000;
+
+/// Micro-lamports in a lamport
+const MICRO_LAMPORTS: u128 = 1_000_000;
+
+/// How the relayer prices its transactions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriorityFeePolicy {
+    /// Percentile of recent prices bid, 0 to 100
+    pub percentile: u8,
+    /// Lowest price bid (micro-lamports per compute unit)
+    pub min_price: u64,
+    /// Highest price bid (micro-lamports per compute unit)
+    pub max_price: u64,
+}
+
+impl Default for PriorityFeePolicy {
+    fn default() -> Self {
+        Self {
+            percentile: DEFAULT_PRIORITY_PERCENTILE,
+            min_price: 0,
+            max_price: DEFAULT_MAX_PRIORITY_PRICE,
+        }
+    }
+}
+
+impl PriorityFeePolicy {
+    /// Price to bid given `recent` prices, `min_price` without any
+    pub fn price(&self, recent: &[u64]) -> u64 {
+        let mut recent = recent.to_vec();
+        recent.sort_unstable();
+        let percentile = usize::from(self.percentile.min(100));
+        let price = match recent.len() {
+            0 => 0,
+            n => recent[(n - 1) * percentile / 100],
+        };
+        price.clamp(self.min_price, self.max_price.max(self.min_price))
+    }
+}
+
+/// Compute units requested per operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComputeUnitLimits {
+    pub transfer: u32,
+    pub unshield: u32,
+}
+
+impl Default for ComputeUnitLimits {
+    fn default() -> Self {
+        Self::uniform(DEFAULT_COMPUTE_UNIT_LIMIT)
+    }
+}
+
+impl ComputeUnitLimits {
+    /// The same limit for every operation
+    pub fn uniform(units: u32) -> Self {
+        Self {
+            transfer: units,
+            unshield: units,
+        }
+    }
+
+    /// Units requested for `operation`
+    pub fn for_operation(&self, operation: &OperationType) -> u32 {
+        match operation {
+            OperationType::Transfer => self.transfer,
+            OperationType::UnshieldSol | OperationType::UnshieldToken { .. } => self.unshield,
+        }
+    }
+}
+
+/// Lamports a bid of `price` micro-lamports costs on `units` compute units,
+/// rounded up as the runtime does
+pub fn priority_fee(price: u64, units: u32) -> u64 {
+    let micro_lamports = u128::from(price) * u128::from(units);
+    ((micro_lamports + MICRO_LAMPORTS - 1) / MICRO_LAMPORTS).min(u64::MAX.into()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_price() {
+        let policy = PriorityFeePolicy::default();
+        assert_eq!(policy.price(&[]), 0);
+        assert_eq!(policy.price(&[5_000, 0, 0, 2_000, 1_000]), 2_000);
+        assert_eq!(PriorityFeePolicy { percentile: 100, ..policy }.price(&[7, 3]), 7);
+        assert_eq!(PriorityFeePolicy { percentile: 0, ..policy }.price(&[7, 3]), 3);
+
+        let bounded = PriorityFeePolicy { percentile: 50, min_price: 100, max_price: 1_500 };
+        assert_eq!(bounded.price(&[]), 100);
+        assert_eq!(bounded.price(&[0, 0, 0]), 100);
+        assert_eq!(bounded.price(&[2_000, 9_000, 3_000]), 1_500);
+    }
+
+    #[test]
+    fn test_priority_fee() {
+        assert_eq!(priority_fee(0, 400_000), 0);
+        assert_eq!(priority_fee(2_000, 400_000), 800);
+        assert_eq!(priority_fee(1, 400_000), 1);
+        assert_eq!(priority_fee(u64::MAX, 1), u64::MAX / 1_000_000 + 1);
+
+        let limits = ComputeUnitLimits { transfer: 300_000, unshield: 350_000 };
+        assert_eq!(limits.for_operation(&OperationType::Transfer), 300_000);
+        let token = OperationType::UnshieldToken { mint: "mint".into() };
+        assert_eq!(limits.for_operation(&token), 350_000);
+    }
+}
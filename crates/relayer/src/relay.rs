@@ -0,0 +1,742 @@
+//! Fee schedule, request checks and transaction building
+//!
+//! A request is relayed only if its proven fee covers the schedule, or the
+//! quote it carries, and its proof verifies against the pool's key for the
+//! operation. The relayer then builds the program's instruction with itself
+//! as the fee payer, signs it and sends it.
+//!
+//! Quotes are signed with the fee payer's key and remembered until they
+//! expire; a restarted relayer no longer knows the quotes it issued, so
+//! requests carrying them are refused.
+//!
+//! Transactions are v0 messages when the relayer is given address lookup
+//! tables (`Relayer::with_lookup_tables`), which leaves room for spends
+//! touching more accounts than a legacy message fits.
+//!
+//! Transactions request the operation's compute-unit limit and bid the
+//! current priority price (see `priority`), and go through a block engine
+//! when one is configured (see `jito`); what the bid and tip cost is added
+//! to the fee a request must prove.
+//!
+//! A relayer asking for proof of work (`Relayer::with_work_bits`) puts it
+//! in every quote and relays only quoted requests carrying the work. The
+//! work is checked before the proof, so spam costs its sender hashes
+//! instead of costing the relayer verifications.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use rand::seq::SliceRandom;
+use veil_core::proof::{SolanaProof, TransferVerifier};
+use veil_core::relayer::{
+    network_fee, FeeQuote, OperationType, QuoteRequest, RelayOutput, RelayRequest, SignedQuote,
+    DEFAULT_FEE_BPS,
+};
+use veil_core::rpc::{decode_pubkey, sign_transaction, Keypair};
+use veil_core::transaction::{
+    AddressLookupTable, Instruction, PoolAccounts, Pubkey, TransactionAssembler,
+};
+
+use crate::chain::Chain;
+use crate::error::RelayError;
+use crate::jito::BlockEngine;
+use crate::priority::{priority_fee, ComputeUnitLimits, PriorityFeePolicy};
+
+pub use veil_client::pda::{nullifier_accounts, pool_accounts};
+
+/// Compute units requested for proof-carrying instructions
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 400_000;
+
+/// Default minimum fee, covering the network fee (lamports)
+pub const DEFAULT_MIN_FEE: u64 = 5_000;
+
+/// Default time a quote is honoured for (seconds)
+pub const DEFAULT_QUOTE_TTL_SECS: u64 = 60;
+
+/// Fees the relayer advertises and enforces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// Fee in basis points of the amount moved
+    pub fee_bps: u16,
+    /// Minimum fee (lamports)
+    pub min_fee: u64,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            fee_bps: DEFAULT_FEE_BPS,
+            min_fee: DEFAULT_MIN_FEE,
+        }
+    }
+}
+
+impl FeeSchedule {
+    /// Fee for moving `amount` lamports
+    pub fn fee_for(&self, amount: u64) -> u64 {
+        let fee = (amount as u128 * self.fee_bps as u128 / 10_000) as u64;
+        fee.max(self.min_fee)
+    }
+
+    /// Fee a relay request must prove
+    ///
+    /// A transfer's amount is private, so transfers pay the minimum fee;
+    /// unshields pay the schedule on the amount withdrawn.
+    pub fn required_fee(&self, request: &RelayRequest) -> u64 {
+        match &request.output {
+            RelayOutput::Unshield { amount, .. } => self.fee_for(*amount),
+            RelayOutput::Commitment(_) => self.min_fee,
+        }
+    }
+}
+
+/// Checks requests and lands them on `chain`
+pub struct Relayer<C> {
+    chain: C,
+    keypair: Keypair,
+    accounts: PoolAccounts,
+    fees: FeeSchedule,
+    compute_units: ComputeUnitLimits,
+    priority: PriorityFeePolicy,
+    /// Current priority bid (micro-lamports per compute unit)
+    priority_price: AtomicU64,
+    block_engine: Option<Box<dyn BlockEngine>>,
+    /// Tip paid to the block engine per bundle (lamports)
+    tip_lamports: u64,
+    lookup_tables: Vec<AddressLookupTable>,
+    transfer_verifier: Option<TransferVerifier>,
+    unshield_verifier: Option<TransferVerifier>,
+    quote_ttl_secs: u64,
+    work_bits: u8,
+    /// Unexpired quotes issued, by hash
+    quotes: Mutex<HashMap<[u8; 32], FeeQuote>>,
+}
+
+impl<C: Chain> Relayer<C> {
+    /// Relayer for the pool at `accounts`, paying fees from `keypair`
+    ///
+    /// It supports no operation until given the verifier for it.
+    pub fn new(chain: C, keypair: Keypair, accounts: PoolAccounts, fees: FeeSchedule) -> Self {
+        Self {
+            chain,
+            keypair,
+            accounts,
+            fees,
+            compute_units: ComputeUnitLimits::default(),
+            priority: PriorityFeePolicy::default(),
+            priority_price: AtomicU64::new(0),
+            block_engine: None,
+            tip_lamports: 0,
+            lookup_tables: Vec::new(),
+            transfer_verifier: None,
+            unshield_verifier: None,
+            quote_ttl_secs: DEFAULT_QUOTE_TTL_SECS,
+            work_bits: 0,
+            quotes: Mutex::default(),
+        }
+    }
+
+    /// Relay transfers, checking their proofs with `verifier`
+    pub fn with_transfer_verifier(mut self, verifier: TransferVerifier) -> Self {
+        self.transfer_verifier = Some(verifier);
+        self
+    }
+
+    /// Relay SOL unshields, checking their proofs with `verifier` (built
+    /// from the unshield circuit's verifying key)
+    pub fn with_unshield_verifier(mut self, verifier: TransferVerifier) -> Self {
+        self.unshield_verifier = Some(verifier);
+        self
+    }
+
+    /// Request `units` compute units for each transaction
+    pub fn with_compute_unit_limit(mut self, units: u32) -> Self {
+        self.compute_units = ComputeUnitLimits::uniform(units);
+        self
+    }
+
+    /// Request `limits` compute units for each operation's transactions
+    pub fn with_compute_unit_limits(mut self, limits: ComputeUnitLimits) -> Self {
+        self.compute_units = limits;
+        self
+    }
+
+    /// Bid priority fees by `policy`
+    pub fn with_priority_fees(mut self, policy: PriorityFeePolicy) -> Self {
+        self.priority = policy;
+        self
+    }
+
+    /// Honour quotes for `secs` seconds after issuing them
+    pub fn with_quote_ttl(mut self, secs: u64) -> Self {
+        self.quote_ttl_secs = secs;
+        self
+    }
+
+    /// Ask for `bits` leading zero bits of work in quotes, and refuse
+    /// requests without it
+    ///
+    /// Clients do at most `veil_core::relayer::pow::MAX_WORK_BITS`.
+    pub fn with_work_bits(mut self, bits: u8) -> Self {
+        self.work_bits = bits;
+        self
+    }
+
+    /// Send transactions as bundles through `engine`, tipping
+    /// `tip_lamports` each, and over RPC if it fails
+    pub fn with_block_engine(
+        mut self,
+        engine: impl BlockEngine + 'static,
+        tip_lamports: u64,
+    ) -> Self {
+        self.block_engine = Some(Box::new(engine));
+        self.tip_lamports = tip_lamports;
+        self
+    }
+
+    /// Build v0 transactions looking accounts up in `tables`, such as one
+    /// holding `PoolAccounts::lookup_addresses`
+    pub fn with_lookup_tables(mut self, tables: Vec<AddressLookupTable>) -> Self {
+        self.lookup_tables = tables;
+        self
+    }
+
+    /// The cluster the relayer submits to
+    pub fn chain(&self) -> &C {
+        &self.chain
+    }
+
+    /// The fee payer's public key
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.public.to_bytes()
+    }
+
+    /// Current priority bid (micro-lamports per compute unit), 0 until
+    /// first refreshed
+    pub fn priority_price(&self) -> u64 {
+        self.priority_price.load(Ordering::Relaxed)
+    }
+
+    /// Price the priority bid from the fees recent transactions locking the
+    /// pool paid, returning the new bid
+    ///
+    /// On failure the current bid stays.
+    pub fn refresh_priority_price(&self) -> Result<u64, RelayError> {
+        let recent = self.chain.recent_priority_fees(&[self.accounts.pool])?;
+        let price = self.priority.price(&recent);
+        self.priority_price.store(price, Ordering::Relaxed);
+        Ok(price)
+    }
+
+    /// Lamports the current priority bid costs on `operation`
+    pub fn priority_fee(&self, operation: &OperationType) -> u64 {
+        priority_fee(self.priority_price(), self.compute_units.for_operation(operation))
+    }
+
+    /// Lamports sending `operation` costs beyond the network fee: the
+    /// priority fee and the bundle tip
+    pub fn submission_fee(&self, operation: &OperationType) -> u64 {
+        self.priority_fee(operation).saturating_add(self.tip_lamports)
+    }
+
+    /// Operations the relayer has a verifier for
+    pub fn supported_operations(&self) -> Vec<OperationType> {
+        let mut operations = Vec::new();
+        if self.transfer_verifier.is_some() {
+            operations.push(OperationType::Transfer);
+        }
+        if self.unshield_verifier.is_some() {
+            operations.push(OperationType::UnshieldSol);
+        }
+        operations
+    }
+
+    /// Signed quote for an operation, honoured until the quote TTL after
+    /// `now` (unix seconds)
+    ///
+    /// A transfer's amount is private, so transfers are quoted the minimum
+    /// fee whatever the amount; see `FeeSchedule::required_fee`. The
+    /// current `submission_fee` is added to both the fee and the network
+    /// fee.
+    pub fn quote(&self, request: &QuoteRequest, now: u64) -> Result<SignedQuote, RelayError> {
+        if !self.supported_operations().contains(&request.operation) {
+            return Err(RelayError::UnsupportedOperation);
+        }
+        let fee = match request.operation {
+            OperationType::Transfer => self.fees.min_fee,
+            _ => self.fees.fee_for(request.amount),
+        };
+        let surge = self.submission_fee(&request.operation);
+        let quote = FeeQuote {
+            operation: request.operation.clone(),
+            amount: request.amount,
+            fee_bps: self.fees.fee_bps,
+            fee: fee.saturating_add(surge),
+            network_fee: network_fee(&request.operation).saturating_add(surge),
+            expires_at: now.saturating_add(self.quote_ttl_secs),
+            relayer: bs58::encode(self.pubkey()).into_string(),
+            work_bits: self.work_bits,
+        };
+        let mut quotes = self.lock_quotes();
+        quotes.retain(|_, quote| !quote.is_expired(now));
+        quotes.insert(quote.hash(), quote.clone());
+        Ok(SignedQuote::sign(quote, &self.keypair))
+    }
+
+    /// Fee `request` must prove at `now`: its quote's, or without one the
+    /// schedule's plus the current `submission_fee`
+    pub fn required_fee(&self, request: &RelayRequest, now: u64) -> Result<u64, RelayError> {
+        Ok(match self.issued_quote(request, now)? {
+            Some(quote) => quote.fee,
+            None => self
+                .fees
+                .required_fee(request)
+                .saturating_add(self.submission_fee(&request.operation)),
+        })
+    }
+
+    /// Check the request carries the work its quote asks for, and has a
+    /// quote if the relayer asks for work
+    pub fn check_work(&self, request: &RelayRequest, now: u64) -> Result<(), RelayError> {
+        match self.issued_quote(request, now)? {
+            Some(quote) if quote.has_work(request) => Ok(()),
+            None if self.work_bits == 0 => Ok(()),
+            _ => Err(RelayError::InsufficientWork),
+        }
+    }
+
+    /// The quote `request` carries, which must be one the relayer issued and
+    /// that applies at `now`
+    fn issued_quote(
+        &self,
+        request: &RelayRequest,
+        now: u64,
+    ) -> Result<Option<FeeQuote>, RelayError> {
+        let Some(hash) = &request.quote_hash else {
+            return Ok(None);
+        };
+        let quotes = self.lock_quotes();
+        let quote = quotes
+            .get(hash)
+            .ok_or_else(|| RelayError::InvalidQuote("unknown quote".into()))?;
+        match quote.mismatch(request, now) {
+            Some(reason) => Err(RelayError::InvalidQuote(reason.into())),
+            None => Ok(Some(quote.clone())),
+        }
+    }
+
+    /// Check the request's fee, work and proof at `now` (unix seconds)
+    pub fn check(&self, request: &RelayRequest, now: u64) -> Result<(), RelayError> {
+        let verifier = match request.operation {
+            OperationType::Transfer => self.transfer_verifier.as_ref(),
+            OperationType::UnshieldSol => self.unshield_verifier.as_ref(),
+            OperationType::UnshieldToken { .. } => None,
+        }
+        .ok_or(RelayError::UnsupportedOperation)?;
+
+        let required = self.required_fee(request, now)?;
+        if request.fee < required {
+            return Err(RelayError::FeeTooLow {
+                offered: request.fee,
+                required,
+            });
+        }
+        self.check_work(request, now)?;
+
+        let public_inputs = request.public_inputs().ok_or_else(|| {
+            RelayError::InvalidRequest("output does not match the operation".into())
+        })?;
+        match verifier.verify(&request.proof, &public_inputs) {
+            Ok(true) => Ok(()),
+            _ => Err(RelayError::InvalidProof),
+        }
+    }
+
+    /// Build and sign the request's transaction against `blockhash`,
+    /// bidding the current priority price
+    ///
+    /// Does not check the request; see `check`.
+    pub fn build_transaction(
+        &self,
+        request: &RelayRequest,
+        blockhash: [u8; 32],
+    ) -> Result<Vec<u8>, RelayError> {
+        let proof = SolanaProof::from_compressed(&request.proof)
+            .map_err(|_| RelayError::InvalidProof)?
+            .to_bytes();
+        let nullifier_accounts = nullifier_accounts(
+            &self.accounts,
+            request.tree_epoch,
+            &request.nullifier,
+            request.merkle_root,
+        )
+        .ok_or_else(|| RelayError::InvalidRequest("no nullifier account address".into()))?;
+
+        let payer = self.pubkey();
+        let instruction = match (&request.operation, &request.output) {
+            (OperationType::Transfer, RelayOutput::Commitment(commitment)) => {
+                self.accounts.transfer(
+                    payer,
+                    nullifier_accounts,
+                    &request.nullifier,
+                    commitment,
+                    request.fee,
+                    &proof,
+                    &[],
+                )
+            }
+            (OperationType::UnshieldSol, RelayOutput::Unshield { recipient, amount }) => {
+                let recipient = decode_pubkey(recipient)
+                    .map_err(|e| RelayError::InvalidRequest(e.to_string()))?;
+                self.accounts.unshield_sol(
+                    payer,
+                    nullifier_accounts,
+                    recipient,
+                    &request.nullifier,
+                    *amount,
+                    &proof,
+                    &[],
+                )
+            }
+            _ => return Err(RelayError::UnsupportedOperation),
+        };
+
+        let mut asm = TransactionAssembler::new(payer);
+        if !self.lookup_tables.is_empty() {
+            asm = asm.with_lookup_tables(self.lookup_tables.clone());
+        }
+        asm.set_compute_unit_limit(self.compute_units.for_operation(&request.operation));
+        let price = self.priority_price();
+        if price > 0 {
+            asm.set_compute_unit_price(price);
+        }
+        asm.add_instruction(instruction);
+        let tx = asm
+            .assemble(blockhash)
+            .map_err(|e| RelayError::InvalidRequest(e.to_string()))?;
+        sign_transaction(&tx, &[&self.keypair])
+            .map_err(|e| RelayError::InvalidRequest(e.to_string()))
+    }
+
+    /// Check the request at `now`, then build, sign and send its transaction
+    ///
+    /// Returns the transaction's signature.
+    pub fn submit(&self, request: &RelayRequest, now: u64) -> Result<String, RelayError> {
+        self.check(request, now)?;
+        self.send(request)
+    }
+
+    /// Build, sign and send the transaction of a checked request, in a
+    /// bundle if the relayer has a block engine
+    ///
+    /// Returns the transaction's signature. Only `RelayError::Chain` errors
+    /// are worth retrying.
+    pub fn send(&self, request: &RelayRequest) -> Result<String, RelayError> {
+        let blockhash = self.chain.latest_blockhash()?;
+        let transaction = self.build_transaction(request, blockhash)?;
+        if let Some(engine) = &self.block_engine {
+            match self.send_bundle(engine.as_ref(), &transaction, blockhash) {
+                Ok(signature) => return Ok(signature),
+                // Unprotected rather than not at all
+                Err(e) => log::warn!("block engine: {}; sending over RPC", e),
+            }
+        }
+        self.chain.send_transaction(&transaction)
+    }
+
+    /// Send `transaction` through `engine`, followed by the tip to a random
+    /// tip account, returning the transaction's signature
+    fn send_bundle(
+        &self,
+        engine: &dyn BlockEngine,
+        transaction: &[u8],
+        blockhash: [u8; 32],
+    ) -> Result<String, RelayError> {
+        let tip_accounts = engine.tip_accounts()?;
+        let tip_account = tip_accounts
+            .choose(&mut rand::thread_rng())
+            .ok_or_else(|| RelayError::Chain("block engine has no tip accounts".into()))?;
+        let payer = self.pubkey();
+        let mut asm = TransactionAssembler::new(payer);
+        asm.add_instruction(Instruction::system_transfer(payer, *tip_account, self.tip_lamports));
+        let tip = asm
+            .assemble(blockhash)
+            .and_then(|tx| sign_transaction(&tx, &[&self.keypair]))
+            .map_err(|e| RelayError::InvalidRequest(e.to_string()))?;
+        engine.send_bundle(&[transaction.to_vec(), tip])?;
+        // One signature: its count, then the signature
+        Ok(bs58::encode(&transaction[1..65]).into_string())
+    }
+
+    fn lock_quotes(&self) -> MutexGuard<'_, HashMap<[u8; 32], FeeQuote>> {
+        self.quotes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use ark_bn254::Fr;
+    use veil_core::crypto::{Note, PoseidonMerkleTree};
+    use veil_core::proof::{field_to_bytes_be, TransferProofSystem};
+    use veil_core::relayer::pow;
+    use veil_core::rpc::TransactionStatus;
+    use veil_core::transaction::MESSAGE_VERSION_PREFIX;
+
+    use super::*;
+
+    /// In-memory cluster recording sent transactions
+    #[derive(Default)]
+    pub(crate) struct FakeChain {
+        pub sent: Mutex<Vec<Vec<u8>>>,
+        pub statuses: Mutex<Vec<(String, TransactionStatus)>>,
+        /// Sends to refuse, as an unreachable RPC node would
+        pub outages: Mutex<u32>,
+        pub priority_fees: Mutex<Vec<u64>>,
+    }
+
+    impl Chain for FakeChain {
+        fn latest_blockhash(&self) -> Result<[u8; 32], RelayError> {
+            Ok([9; 32])
+        }
+
+        fn send_transaction(&self, transaction: &[u8]) -> Result<String, RelayError> {
+            let mut outages = self.outages.lock().unwrap();
+            if *outages > 0 {
+                *outages -= 1;
+                return Err(RelayError::Chain("connection refused".into()));
+            }
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(transaction.to_vec());
+            Ok(format!("sig{}", sent.len()))
+        }
+
+        fn transaction_status(
+            &self,
+            signature: &str,
+        ) -> Result<Option<TransactionStatus>, RelayError> {
+            let statuses = self.statuses.lock().unwrap();
+            Ok(statuses.iter().find(|(s, _)| s == signature).map(|(_, status)| status.clone()))
+        }
+
+        fn recent_priority_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>, RelayError> {
+            Ok(self.priority_fees.lock().unwrap().clone())
+        }
+    }
+
+    /// Block engine recording bundles, refusing them while `down`
+    #[derive(Clone, Default)]
+    struct FakeEngine {
+        bundles: Arc<Mutex<Vec<Vec<Vec<u8>>>>>,
+        down: Arc<Mutex<bool>>,
+    }
+
+    impl BlockEngine for FakeEngine {
+        fn tip_accounts(&self) -> Result<Vec<Pubkey>, RelayError> {
+            Ok(vec![[8; 32]])
+        }
+
+        fn send_bundle(&self, transactions: &[Vec<u8>]) -> Result<String, RelayError> {
+            if *self.down.lock().unwrap() {
+                return Err(RelayError::Chain("bundle rejected".into()));
+            }
+            self.bundles.lock().unwrap().push(transactions.to_vec());
+            Ok("bundle".into())
+        }
+    }
+
+    pub(crate) fn keypair() -> Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[3; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+
+    /// A relayer for transfers with a valid transfer request
+    pub(crate) fn transfer_fixture(fee: u64) -> (Relayer<FakeChain>, RelayRequest) {
+        transfer_fixture_on(FakeChain::default(), fee)
+    }
+
+    /// `transfer_fixture` submitting to `chain`
+    pub(crate) fn transfer_fixture_on<C: Chain>(chain: C, fee: u64) -> (Relayer<C>, RelayRequest) {
+        let system = TransferProofSystem::setup().unwrap();
+        let mut note = Note::new([5u8; 32], 1_000_000, Fr::from(0u64), Fr::from(7u64));
+        let mut tree = PoseidonMerkleTree::new();
+        note.set_leaf_index(tree.insert(note.commitment()).unwrap());
+        let path = tree.generate_proof(0).unwrap();
+        let spend = system.prove_spend(&note, &path, tree.root(), Fr::from(11u64), fee).unwrap();
+
+        let accounts = pool_accounts([4u8; 32]).unwrap();
+        let fees = FeeSchedule::default();
+        let relayer = Relayer::new(chain, keypair(), accounts, fees)
+            .with_transfer_verifier(system.verifier().clone());
+        let request = RelayRequest {
+            operation: OperationType::Transfer,
+            nullifier: spend.nullifier_bytes(),
+            output: RelayOutput::Commitment(spend.new_commitment_bytes()),
+            amount: 0,
+            proof: spend.proof.bytes.clone(),
+            merkle_root: field_to_bytes_be(&tree.root()),
+            tree_epoch: 0,
+            asset_id: [0; 32],
+            fee,
+            max_fee: fee,
+            quote_hash: None,
+            work_nonce: None,
+        };
+        (relayer, request)
+    }
+
+    #[test]
+    fn test_fee_schedule() {
+        let fees = FeeSchedule { fee_bps: 30, min_fee: 5_000 };
+        assert_eq!(fees.fee_for(1_000_000_000), 3_000_000);
+        assert_eq!(fees.fee_for(1_000), 5_000);
+    }
+
+    #[test]
+    fn test_quotes() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        let relayer = relayer.with_quote_ttl(60);
+        let transfer = QuoteRequest { operation: OperationType::Transfer, amount: 1 << 40 };
+        let signed = relayer.quote(&transfer, 1_000).unwrap();
+        assert!(signed.verify());
+        let quote = signed.quote;
+        assert_eq!((quote.fee, quote.expires_at), (DEFAULT_MIN_FEE, 1_060));
+        assert_eq!(quote.relayer, bs58::encode(relayer.pubkey()).into_string());
+        let unshield = QuoteRequest { operation: OperationType::UnshieldSol, amount: 1 };
+        assert!(matches!(relayer.quote(&unshield, 0), Err(RelayError::UnsupportedOperation)));
+
+        // Honoured until it expires
+        let quoted = RelayRequest { quote_hash: Some(quote.hash()), ..request.clone() };
+        relayer.check(&quoted, 1_059).unwrap();
+        assert!(matches!(relayer.check(&quoted, 1_060), Err(RelayError::InvalidQuote(_))));
+        let unknown = RelayRequest { quote_hash: Some([0; 32]), ..request };
+        assert!(matches!(relayer.check(&unknown, 1_000), Err(RelayError::InvalidQuote(_))));
+
+        // Expired quotes are forgotten as new ones are issued
+        relayer.quote(&transfer, 2_000).unwrap();
+        assert_eq!(relayer.lock_quotes().len(), 1);
+    }
+
+    #[test]
+    fn test_work() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        let relayer = relayer.with_work_bits(8);
+        assert!(matches!(relayer.check(&request, 0), Err(RelayError::InsufficientWork)));
+
+        let transfer = QuoteRequest { operation: OperationType::Transfer, amount: 0 };
+        let quote = relayer.quote(&transfer, 0).unwrap().quote;
+        assert_eq!(quote.work_bits, 8);
+        let mut quoted = RelayRequest { quote_hash: Some(quote.hash()), ..request };
+        assert!(matches!(relayer.check(&quoted, 0), Err(RelayError::InsufficientWork)));
+        quoted.work_nonce = Some(pow::solve_work(&quote.hash(), &quoted.nullifier, 8));
+        relayer.check_work(&quoted, 0).unwrap();
+        relayer.check(&quoted, 0).unwrap();
+    }
+
+    #[test]
+    fn test_priority_fees() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        let relayer = relayer.with_compute_unit_limits(ComputeUnitLimits {
+            transfer: 300_000,
+            unshield: 400_000,
+        });
+        *relayer.chain().priority_fees.lock().unwrap() = vec![0, 5_000, 1_000, 2_000, 0];
+        assert_eq!(relayer.priority_price(), 0);
+        assert_eq!(relayer.refresh_priority_price().unwrap(), 2_000);
+        assert_eq!(relayer.priority_fee(&OperationType::Transfer), 600);
+
+        // Surge pricing: quotes and unquoted requests pay the bid
+        let transfer = QuoteRequest { operation: OperationType::Transfer, amount: 0 };
+        let quote = relayer.quote(&transfer, 0).unwrap().quote;
+        assert_eq!((quote.fee, quote.network_fee), (DEFAULT_MIN_FEE + 600, 5_600));
+        assert!(matches!(
+            relayer.check(&request, 0),
+            Err(RelayError::FeeTooLow { required, .. }) if required == DEFAULT_MIN_FEE + 600
+        ));
+
+        // The transaction requests the operation's units at the bid
+        relayer.send(&request).unwrap();
+        let sent = relayer.chain().sent.lock().unwrap();
+        let instruction = |tag: u8, value: &[u8]| [&[tag][..], value].concat();
+        let limit = instruction(2, &300_000u32.to_le_bytes());
+        let price = instruction(3, &2_000u64.to_le_bytes());
+        assert!(sent[0].windows(limit.len()).any(|w| w == limit));
+        assert!(sent[0].windows(price.len()).any(|w| w == price));
+    }
+
+    #[test]
+    fn test_block_engine() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE + 20_000);
+        let engine = FakeEngine::default();
+        let relayer = relayer.with_block_engine(engine.clone(), 20_000);
+
+        // The tip is priced in
+        let transfer = QuoteRequest { operation: OperationType::Transfer, amount: 0 };
+        assert_eq!(relayer.quote(&transfer, 0).unwrap().quote.fee, DEFAULT_MIN_FEE + 20_000);
+        relayer.check(&request, 0).unwrap();
+
+        // The transaction, then the tip to the engine's account
+        let signature = relayer.send(&request).unwrap();
+        let bundles = engine.bundles.lock().unwrap();
+        let [transaction, tip] = &bundles[0][..] else { panic!("bundle of {}", bundles[0].len()) };
+        assert_eq!(signature, bs58::encode(&transaction[1..65]).into_string());
+        let transfer = [&2u32.to_le_bytes()[..], &20_000u64.to_le_bytes()].concat();
+        assert!(tip.windows(transfer.len()).any(|w| w == transfer));
+        assert!(tip.windows(32).any(|w| w == [8; 32]));
+        assert!(relayer.chain().sent.lock().unwrap().is_empty());
+        drop(bundles);
+
+        // Over RPC when the engine refuses
+        *engine.down.lock().unwrap() = true;
+        assert_eq!(relayer.send(&request).unwrap(), "sig1");
+        assert_eq!(engine.bundles.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_lookup_tables() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        let legacy = relayer.build_transaction(&request, [9; 32]).unwrap();
+        assert_eq!(legacy[65], 1);
+
+        let addresses = relayer.accounts.lookup_addresses();
+        let table = AddressLookupTable::new([6; 32], addresses);
+        let relayer = relayer.with_lookup_tables(vec![table]);
+        relayer.submit(&request, 0).unwrap();
+        let sent = relayer.chain().sent.lock().unwrap();
+        assert_eq!(sent[0][65], MESSAGE_VERSION_PREFIX);
+        assert!(sent[0].len() < legacy.len());
+        assert!(sent[0].windows(32).any(|w| w == [6; 32]));
+    }
+
+    #[test]
+    fn test_check_and_submit() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        relayer.check(&request, 0).unwrap();
+        assert_eq!(relayer.submit(&request, 0).unwrap(), "sig1");
+
+        let sent = relayer.chain().sent.lock().unwrap();
+        // One signature, signed by the relayer
+        assert_eq!(sent[0][0], 1);
+        assert!(sent[0].windows(32).any(|w| w == relayer.pubkey()));
+        drop(sent);
+
+        // Below the schedule
+        let mut cheap = request.clone();
+        cheap.fee = DEFAULT_MIN_FEE - 1;
+        assert!(matches!(relayer.check(&cheap, 0), Err(RelayError::FeeTooLow { .. })));
+
+        // A commitment other than the proven one
+        let mut forged = request.clone();
+        forged.output = RelayOutput::Commitment([1; 32]);
+        assert!(matches!(relayer.check(&forged, 0), Err(RelayError::InvalidProof)));
+
+        // No unshield verifier
+        let mut unshield = request;
+        unshield.operation = OperationType::UnshieldSol;
+        assert!(matches!(relayer.check(&unshield, 0), Err(RelayError::UnsupportedOperation)));
+        assert_eq!(relayer.supported_operations(), vec![OperationType::Transfer]);
+    }
+}
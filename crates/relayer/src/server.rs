@@ -0,0 +1,793 @@
+//! HTTP routes and the job worker
+//!
+//! `/relay` checks a request and stores it as a job; the worker, a single
+//! task, sends due jobs and polls submitted ones, so no job is ever sent
+//! twice at once. Proof verification, storage and RPC calls block, so they
+//! run on tokio's blocking pool.
+//!
+//! The worker announces every job it changes, so `/status/:id/ws` streams a
+//! job's status as it moves instead of clients polling `/status/:id`.
+//!
+//! In dry-run mode (`AppState::with_dry_run`) requests are checked in full,
+//! proofs included, but nothing reaches the cluster: the worker signs each
+//! job's transaction against a fixed blockhash and reports that signature
+//! as submitted, then confirms the job at a made-up slot after a delay.
+//! Clients can run their whole flow against it without a validator. What
+//! the relayer accepts, refuses and does with each job is journaled in
+//! memory and served at `/debug`, a route only this mode has.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Notify;
+use veil_core::relayer::{
+    OperationType, QuoteRequest, RelayRequest, RelayResponse, RelayStatus, SignedQuote,
+};
+
+use crate::chain::Chain;
+use crate::error::RelayError;
+use crate::jobs::{now_ms, Job, JobEvent, JobStorage, RetryPolicy};
+use crate::relay::Relayer;
+
+/// Time after which a sent transaction the cluster has not seen is sent
+/// again (ms); its blockhash has expired by then
+pub const SUBMISSION_TIMEOUT_MS: u64 = 90_000;
+
+/// Job changes buffered for status streams; a stream further behind
+/// rereads its job
+const UPDATE_CAPACITY: usize = 256;
+
+/// Default time from a dry-run job's simulated send to its confirmation
+pub const DEFAULT_DRY_RUN_DELAY: Duration = Duration::from_secs(2);
+
+/// Blockhash dry-run transactions are signed against
+const DRY_RUN_BLOCKHASH: [u8; 32] = [0; 32];
+
+/// State shared by the handlers and the worker
+pub struct AppState<C> {
+    pub relayer: Relayer<C>,
+    pub jobs: Box<dyn JobStorage>,
+    pub retry: RetryPolicy,
+    /// Woken when a job is stored, so the worker sends it without waiting
+    /// for its next tick
+    wake: Notify,
+    /// Ids of jobs the worker changed, for status streams
+    updates: broadcast::Sender<String>,
+    dry_run: Option<DryRun>,
+}
+
+impl<C: Chain> AppState<C> {
+    pub fn new(relayer: Relayer<C>, jobs: impl JobStorage + 'static) -> Self {
+        Self {
+            relayer,
+            jobs: Box::new(jobs),
+            retry: RetryPolicy::default(),
+            wake: Notify::new(),
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+            dry_run: None,
+        }
+    }
+
+    /// Retry failed sends with `policy`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Check requests but never send them, confirming each job
+    /// `confirm_delay` after its simulated send; see the module docs
+    pub fn with_dry_run(mut self, confirm_delay: Duration) -> Self {
+        self.dry_run = Some(DryRun {
+            confirm_delay_ms: confirm_delay.as_millis() as u64,
+            slot: AtomicU64::new(0),
+            journal: Mutex::new(Vec::new()),
+        });
+        self
+    }
+
+    /// Whether the relayer is in dry-run mode
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// The dry-run journal, oldest first; empty outside dry-run mode
+    pub fn journal(&self) -> Vec<JournalEntry> {
+        self.dry_run.as_ref().map(DryRun::entries).unwrap_or_default()
+    }
+
+    /// Store a changed job and announce it to status streams
+    fn update(&self, job: &Job, event: JobEvent) -> Result<(), RelayError> {
+        if let Some(dry_run) = &self.dry_run {
+            let updated = JournalEvent::Updated {
+                request_id: job.request_id.clone(),
+                status: event.status.clone(),
+                note: event.note.clone(),
+            };
+            dry_run.record(event.at, &job.request.nullifier, updated);
+        }
+        self.jobs.update(job, event)?;
+        // No receivers is no error: nobody is streaming
+        let _ = self.updates.send(job.request_id.clone());
+        Ok(())
+    }
+}
+
+/// What stands in for the cluster in dry-run mode
+struct DryRun {
+    /// Time from a simulated send to its confirmation (ms)
+    confirm_delay_ms: u64,
+    /// Last simulated slot
+    slot: AtomicU64,
+    journal: Mutex<Vec<JournalEntry>>,
+}
+
+impl DryRun {
+    fn record(&self, at: u64, nullifier: &[u8; 32], event: JournalEvent) {
+        let entry = JournalEntry {
+            at,
+            nullifier: hex::encode(nullifier),
+            event,
+        };
+        self.lock_journal().push(entry);
+    }
+
+    fn entries(&self) -> Vec<JournalEntry> {
+        self.lock_journal().clone()
+    }
+
+    fn lock_journal(&self) -> std::sync::MutexGuard<'_, Vec<JournalEntry>> {
+        self.journal.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// One entry of the dry-run journal `/debug` serves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Unix ms
+    pub at: u64,
+    /// Hex of the request's nullifier
+    pub nullifier: String,
+    pub event: JournalEvent,
+}
+
+/// What the dry-run relayer did with a request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalEvent {
+    /// `/relay` refused the request
+    Refused { reason: String },
+    /// `/relay` stored the request, or found it stored, as the job
+    /// `request_id`
+    Accepted {
+        request_id: String,
+        request: Box<RelayRequest>,
+    },
+    /// The worker moved the job
+    Updated {
+        request_id: String,
+        status: RelayStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+}
+
+/// A job as `/status/:id` reports it
+///
+/// Flattens the `RelayResponse` clients expect, adding the job's history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobReport {
+    #[serde(flatten)]
+    pub response: RelayResponse,
+    /// Failed sends so far
+    pub attempts: u32,
+    pub history: Vec<JobEvent>,
+}
+
+/// The relayer's routes, with `/debug` in dry-run mode
+pub fn router<C: Chain + 'static>(state: Arc<AppState<C>>) -> Router {
+    let mut router = Router::new()
+        .route("/quote", post(quote::<C>))
+        .route("/relay", post(relay::<C>))
+        .route("/status/:id", get(status::<C>))
+        .route("/status/:id/ws", get(status_stream::<C>))
+        .route("/health", get(health::<C>));
+    if state.is_dry_run() {
+        router = router.route("/debug", get(debug::<C>));
+    }
+    router.with_state(state)
+}
+
+/// Serve the routes on `listen` until the server fails
+pub async fn serve<C: Chain + 'static>(
+    listen: SocketAddr,
+    state: Arc<AppState<C>>,
+) -> Result<(), hyper::Error> {
+    axum::Server::bind(&listen).serve(router(state).into_make_service()).await
+}
+
+/// Reprice the priority bid, send due jobs and poll submitted ones every
+/// `interval`, or as soon as a job is stored
+///
+/// A dry run keeps its bid, so the worker makes no RPC call.
+pub async fn run_jobs<C: Chain + 'static>(state: Arc<AppState<C>>, interval: Duration) {
+    loop {
+        let _ = tokio::time::timeout(interval, state.wake.notified()).await;
+        let worker = state.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            // A failed estimate keeps the last bid
+            if !worker.is_dry_run() {
+                if let Err(e) = worker.relayer.refresh_priority_price() {
+                    log::warn!("priority fees: {}", e);
+                }
+            }
+            process_due(&worker, now_ms())?;
+            update_confirmations(&worker, now_ms())
+        })
+        .await;
+        // Jobs stay stored, so a failed pass is retried on the next one
+        if let Ok(Err(e)) = result {
+            log::error!("job worker: {}", e);
+        }
+    }
+}
+
+/// Send every job due at `now`, or in dry-run mode only sign it
+pub fn process_due<C: Chain>(state: &AppState<C>, now: u64) -> Result<(), RelayError> {
+    for mut job in state.jobs.due(now)? {
+        let sent = match &state.dry_run {
+            Some(_) => dry_run_signature(&state.relayer, &job.request),
+            None => state.relayer.send(&job.request),
+        };
+        let event = match sent {
+            Ok(signature) => {
+                let note = state.dry_run.as_ref().map(|_| "dry run: not sent".to_string());
+                job.transition(RelayStatus::Submitted { signature }, now, note)
+            }
+            Err(RelayError::Chain(e)) => retry(&state.retry, &mut job, e, now),
+            Err(e) => job.transition(RelayStatus::Failed { reason: e.to_string() }, now, None),
+        };
+        state.update(&job, event)?;
+    }
+    Ok(())
+}
+
+/// Poll the status of submitted jobs, marking them confirmed or failed,
+/// or due again if the cluster has not seen them in time
+///
+/// In dry-run mode jobs are confirmed once their delay has passed instead.
+pub fn update_confirmations<C: Chain>(state: &AppState<C>, now: u64) -> Result<(), RelayError> {
+    if let Some(dry_run) = &state.dry_run {
+        return simulate_confirmations(state, dry_run, now);
+    }
+    for mut job in state.jobs.submitted()? {
+        let Some(signature) = job.pending_signature().map(str::to_string) else {
+            continue;
+        };
+        // A failed poll is retried on the next pass
+        let Ok(status) = state.relayer.chain().transaction_status(&signature) else {
+            continue;
+        };
+        let event = match status {
+            Some(status) => match status.error {
+                Some(reason) => job.transition(RelayStatus::Failed { reason }, now, None),
+                None if status.confirmed => job.transition(
+                    RelayStatus::Confirmed {
+                        signature,
+                        slot: status.slot,
+                    },
+                    now,
+                    None,
+                ),
+                None => continue,
+            },
+            None if now.saturating_sub(job.updated_at) >= SUBMISSION_TIMEOUT_MS => {
+                let reason = format!("{} not seen by the cluster", signature);
+                retry(&state.retry, &mut job, reason, now)
+            }
+            None => continue,
+        };
+        state.update(&job, event)?;
+    }
+    Ok(())
+}
+
+/// Confirm the dry-run jobs sent at least the delay before `now`, each at
+/// the next simulated slot
+fn simulate_confirmations<C: Chain>(
+    state: &AppState<C>,
+    dry_run: &DryRun,
+    now: u64,
+) -> Result<(), RelayError> {
+    for mut job in state.jobs.submitted()? {
+        let Some(signature) = job.pending_signature().map(str::to_string) else {
+            continue;
+        };
+        if now.saturating_sub(job.updated_at) < dry_run.confirm_delay_ms {
+            continue;
+        }
+        let slot = dry_run.slot.fetch_add(1, Ordering::Relaxed) + 1;
+        let event = job.transition(RelayStatus::Confirmed { signature, slot }, now, None);
+        state.update(&job, event)?;
+    }
+    Ok(())
+}
+
+/// The signature a dry-run job is reported sent with: that of its
+/// transaction signed against `DRY_RUN_BLOCKHASH`, so a request always
+/// gets the same one
+fn dry_run_signature<C: Chain>(
+    relayer: &Relayer<C>,
+    request: &RelayRequest,
+) -> Result<String, RelayError> {
+    let transaction = relayer.build_transaction(request, DRY_RUN_BLOCKHASH)?;
+    // One signature: its count, then the signature
+    Ok(bs58::encode(&transaction[1..65]).into_string())
+}
+
+/// Count a failed send, scheduling the next one or failing the job
+fn retry(policy: &RetryPolicy, job: &mut Job, reason: String, now: u64) -> JobEvent {
+    job.attempts += 1;
+    if job.attempts >= policy.max_attempts {
+        return job.transition(RelayStatus::Failed { reason }, now, None);
+    }
+    job.next_attempt_at = now + policy.delay_ms(job.attempts);
+    let note = format!("attempt {}: {}", job.attempts, reason);
+    job.transition(RelayStatus::Pending, now, Some(note))
+}
+
+/// Run `f` on the blocking pool
+async fn blocking<T, F>(f: F) -> Result<T, RelayError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, RelayError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| RelayError::Storage(e.to_string()))?
+}
+
+async fn quote<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    Json(request): Json<QuoteRequest>,
+) -> Result<Json<SignedQuote>, RelayError> {
+    state.relayer.quote(&request, now_ms() / 1000).map(Json)
+}
+
+async fn relay<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    Json(request): Json<RelayRequest>,
+) -> Result<Json<RelayResponse>, RelayError> {
+    let nullifier = request.nullifier;
+    let result = accept(&state, request).await;
+    if let Some(dry_run) = &state.dry_run {
+        let event = match &result {
+            Ok(job) => JournalEvent::Accepted {
+                request_id: job.request_id.clone(),
+                request: Box::new(job.request.clone()),
+            },
+            Err(e) => JournalEvent::Refused { reason: e.to_string() },
+        };
+        dry_run.record(now_ms(), &nullifier, event);
+    }
+    let job = result?;
+    state.wake.notify_one();
+    Ok(Json(job.response()))
+}
+
+/// Check a request and store it as a job
+async fn accept<C: Chain + 'static>(
+    state: &Arc<AppState<C>>,
+    request: RelayRequest,
+) -> Result<Job, RelayError> {
+    // Spam without the work its quote asks for does not reach a blocking
+    // thread
+    state.relayer.check_work(&request, now_ms() / 1000)?;
+    let handler = state.clone();
+    blocking(move || {
+        handler.relayer.check(&request, now_ms() / 1000)?;
+        // An active job for the same nullifier answers in place of a new one
+        handler.jobs.enqueue(Job::new(request, now_ms()))
+    })
+    .await
+}
+
+async fn status<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobReport>, RelayError> {
+    blocking(move || {
+        let job = state.jobs.get(&id)?.ok_or_else(|| RelayError::UnknownRequest(id.clone()))?;
+        Ok(Json(JobReport {
+            response: job.response(),
+            attempts: job.attempts,
+            history: state.jobs.history(&id)?,
+        }))
+    })
+    .await
+}
+
+/// Refuse unknown jobs, then upgrade to a status stream
+async fn status_stream<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+    Path(id): Path<String>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, RelayError> {
+    let jobs = state.clone();
+    let lookup = id.clone();
+    blocking(move || jobs.jobs.get(&lookup)?.ok_or(RelayError::UnknownRequest(lookup)))
+        .await?;
+    Ok(upgrade.on_upgrade(move |socket| stream_status(state, id, socket)))
+}
+
+/// Send the job's `RelayResponse` now and whenever its status changes,
+/// closing once it is final
+async fn stream_status<C: Chain + 'static>(
+    state: Arc<AppState<C>>,
+    id: String,
+    mut socket: WebSocket,
+) {
+    // Subscribed before reading, so no change falls in between
+    let mut updates = state.updates.subscribe();
+    let mut last = None;
+    loop {
+        let (jobs, lookup) = (state.clone(), id.clone());
+        let Ok(Some(job)) = blocking(move || jobs.jobs.get(&lookup)).await else {
+            break;
+        };
+        if last.as_ref() != Some(&job.status) {
+            let Ok(text) = serde_json::to_string(&job.response()) else {
+                break;
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+            last = Some(job.status.clone());
+        }
+        if job.status.is_terminal() {
+            break;
+        }
+        // Wait for this job to change; a lagging stream rereads it anyway
+        loop {
+            match updates.recv().await {
+                Ok(changed) if changed != id => continue,
+                Ok(_) | Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+    let _ = socket.close().await;
+}
+
+/// The dry-run journal, oldest first
+async fn debug<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+) -> Json<Vec<JournalEntry>> {
+    Json(state.journal())
+}
+
+/// Liveness probe for `RelayerClient`, answering the operations relayed
+async fn health<C: Chain + 'static>(
+    State(state): State<Arc<AppState<C>>>,
+) -> Json<Vec<OperationType>> {
+    Json(state.relayer.supported_operations())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+    use veil_core::relayer::{RelayerClient, RelayerInfo};
+    use veil_core::rpc::TransactionStatus;
+    use veil_core::transaction::Pubkey;
+
+    use super::*;
+    use crate::jobs::MemoryStorage;
+    use crate::relay::tests::{transfer_fixture, transfer_fixture_on, FakeChain};
+    use crate::relay::DEFAULT_MIN_FEE;
+
+    fn state() -> (Arc<AppState<FakeChain>>, RelayRequest) {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        (Arc::new(AppState::new(relayer, MemoryStorage::new())), request)
+    }
+
+    /// A cluster no dry run may reach
+    struct Unreachable;
+
+    impl Chain for Unreachable {
+        fn latest_blockhash(&self) -> Result<[u8; 32], RelayError> {
+            panic!("dry run fetched a blockhash")
+        }
+
+        fn send_transaction(&self, _transaction: &[u8]) -> Result<String, RelayError> {
+            panic!("dry run sent a transaction")
+        }
+
+        fn transaction_status(
+            &self,
+            _signature: &str,
+        ) -> Result<Option<TransactionStatus>, RelayError> {
+            panic!("dry run polled a transaction")
+        }
+
+        fn recent_priority_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>, RelayError> {
+            panic!("dry run fetched priority fees")
+        }
+    }
+
+    fn dry_run_state(delay: Duration) -> (Arc<AppState<Unreachable>>, RelayRequest) {
+        let (relayer, request) = transfer_fixture_on(Unreachable, DEFAULT_MIN_FEE);
+        let state = AppState::new(relayer, MemoryStorage::new()).with_dry_run(delay);
+        (Arc::new(state), request)
+    }
+
+    fn post_json(uri: &str, body: &impl Serialize) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    async fn body_json<T: serde::de::DeserializeOwned>(response: axum::response::Response) -> T {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn confirm(state: &AppState<FakeChain>, signature: &str, error: Option<&str>) {
+        state.relayer.chain().statuses.lock().unwrap().push((
+            signature.into(),
+            TransactionStatus {
+                slot: 42,
+                confirmed: error.is_none(),
+                error: error.map(str::to_string),
+            },
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let (state, request) = state();
+        let app = router(state.clone());
+
+        let quote = QuoteRequest { operation: OperationType::Transfer, amount: 0 };
+        let response = app.clone().oneshot(post_json("/quote", &quote)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let quote: SignedQuote = body_json(response).await;
+        assert!(quote.verify());
+        assert_eq!(quote.quote.fee, DEFAULT_MIN_FEE);
+        // Relay under the quote
+        let request = RelayRequest { quote_hash: Some(quote.quote.hash()), ..request };
+
+        let unsupported = QuoteRequest { operation: OperationType::UnshieldSol, amount: 1 };
+        let response = app.clone().oneshot(post_json("/quote", &unsupported)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut cheap = request.clone();
+        cheap.fee = 1;
+        let response = app.clone().oneshot(post_json("/relay", &cheap)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.clone().oneshot(post_json("/relay", &request)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let accepted: RelayResponse = body_json(response).await;
+        assert_eq!(accepted.status, RelayStatus::Pending);
+
+        // Resubmitting the nullifier finds the same job
+        let response = app.clone().oneshot(post_json("/relay", &request)).await.unwrap();
+        let duplicate: RelayResponse = body_json(response).await;
+        assert_eq!(duplicate.request_id, accepted.request_id);
+
+        process_due(&state, now_ms()).unwrap();
+        confirm(&state, "sig1", None);
+        update_confirmations(&state, now_ms()).unwrap();
+        assert_eq!(state.relayer.chain().sent.lock().unwrap().len(), 1);
+
+        let uri = format!("/status/{}", accepted.request_id);
+        let response =
+            app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let report: JobReport = body_json(response).await;
+        let confirmed = RelayStatus::Confirmed { signature: "sig1".into(), slot: 42 };
+        assert_eq!(report.response.status, confirmed);
+        let history: Vec<_> = report.history.into_iter().map(|e| e.status).collect();
+        assert_eq!(
+            history,
+            [RelayStatus::Pending, RelayStatus::Submitted { signature: "sig1".into() }, confirmed]
+        );
+
+        let missing = Request::get("/status/missing").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(missing).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let health = Request::get("/health").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(health).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let operations: Vec<OperationType> = body_json(response).await;
+        assert_eq!(operations, [OperationType::Transfer]);
+
+        // Only dry runs keep a journal
+        let debug = Request::get("/debug").body(Body::empty()).unwrap();
+        let response = app.oneshot(debug).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let (state, request) = dry_run_state(Duration::from_millis(1_000));
+        let app = router(state.clone());
+
+        // Requests are still checked in full
+        let mut forged = request.clone();
+        forged.proof[0] ^= 1;
+        let response = app.clone().oneshot(post_json("/relay", &forged)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.clone().oneshot(post_json("/relay", &request)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let accepted: RelayResponse = body_json(response).await;
+
+        // Signed, not sent, and signed the same way every time
+        process_due(&state, now_ms()).unwrap();
+        let job = state.jobs.get(&accepted.request_id).unwrap().unwrap();
+        let signature = job.pending_signature().unwrap().to_string();
+        assert_eq!(bs58::decode(&signature).into_vec().unwrap().len(), 64);
+        assert_eq!(dry_run_signature(&state.relayer, &request).unwrap(), signature);
+
+        // Confirmed once the delay has passed
+        let status = |id: &str| state.jobs.get(id).unwrap().unwrap().status;
+        update_confirmations(&state, job.updated_at + 999).unwrap();
+        let submitted = RelayStatus::Submitted { signature: signature.clone() };
+        assert_eq!(status(&accepted.request_id), submitted);
+        update_confirmations(&state, job.updated_at + 1_000).unwrap();
+        let confirmed = RelayStatus::Confirmed { signature, slot: 1 };
+        assert_eq!(status(&accepted.request_id), confirmed);
+
+        let debug = Request::get("/debug").body(Body::empty()).unwrap();
+        let response = app.oneshot(debug).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let journal: Vec<JournalEntry> = body_json(response).await;
+        assert!(journal.iter().all(|entry| entry.nullifier == hex::encode(request.nullifier)));
+        let events: Vec<_> = journal.into_iter().map(|entry| entry.event).collect();
+        assert!(matches!(
+            &events[0],
+            JournalEvent::Refused { reason } if *reason == RelayError::InvalidProof.to_string()
+        ));
+        assert!(matches!(
+            &events[1],
+            JournalEvent::Accepted { request_id, .. } if *request_id == accepted.request_id
+        ));
+        assert!(matches!(
+            &events[2],
+            JournalEvent::Updated { status, note: Some(_), .. } if *status == submitted
+        ));
+        assert!(matches!(&events[3], JournalEvent::Updated { status, .. } if *status == confirmed));
+        assert_eq!(events.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_client_flow() {
+        let (state, request) = dry_run_state(Duration::from_millis(50));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(state.clone()).into_make_service();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app));
+        tokio::spawn(run_jobs(state.clone(), Duration::from_millis(10)));
+
+        let mut client = RelayerClient::with_settings(100, 5).with_poll_interval(10);
+        client.add_relayer(RelayerInfo {
+            id: "dry-run".into(),
+            endpoint,
+            fee_bps: 0,
+            min_amount: 0,
+            supported_operations: vec![OperationType::Transfer],
+            is_online: true,
+            avg_confirmation_time: 1,
+            stake: 0,
+            latency_ms: None,
+        });
+        let request = RelayRequest { amount: 1_000_000, ..request };
+        let response = client.submit(request).await.unwrap();
+        assert!(matches!(response.status, RelayStatus::Confirmed { slot: 1, .. }));
+        assert_eq!(state.journal().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_status_stream() {
+        let (state, request) = state();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(state.clone()).into_make_service();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app));
+
+        let mut client = RelayerClient::new();
+        client.add_relayer(RelayerInfo {
+            id: "local".into(),
+            endpoint,
+            fee_bps: 0,
+            min_amount: 0,
+            supported_operations: vec![OperationType::Transfer],
+            is_online: true,
+            avg_confirmation_time: 1,
+            stake: 0,
+            latency_ms: None,
+        });
+        // The client checks fees on the note's value, which stays private
+        let request = RelayRequest { amount: 1_000_000, ..request };
+        let accepted = client.relay(request).await.unwrap();
+        let mut stream = client.subscribe_status(&accepted.request_id).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), RelayStatus::Pending);
+
+        // Each pass of the worker reaches the stream
+        let worker = state.clone();
+        tokio::task::spawn_blocking(move || process_due(&worker, now_ms())).await.unwrap().unwrap();
+        let submitted = RelayStatus::Submitted { signature: "sig1".into() };
+        assert_eq!(stream.next().await.unwrap().unwrap(), submitted);
+
+        confirm(&state, "sig1", None);
+        let worker = state.clone();
+        let update = move || update_confirmations(&worker, now_ms());
+        tokio::task::spawn_blocking(update).await.unwrap().unwrap();
+        let confirmed = RelayStatus::Confirmed { signature: "sig1".into(), slot: 42 };
+        assert_eq!(stream.next().await.unwrap().unwrap(), confirmed);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn test_retries_and_failures() {
+        let (relayer, request) = transfer_fixture(DEFAULT_MIN_FEE);
+        let policy = RetryPolicy { max_attempts: 4, ..RetryPolicy::default() };
+        let state = AppState::new(relayer, MemoryStorage::new()).with_retry_policy(policy);
+        let chain = state.relayer.chain();
+        let job = state.jobs.enqueue(Job::new(request.clone(), 0)).unwrap();
+        let status = |id: &str| state.jobs.get(id).unwrap().unwrap();
+
+        // Two failed sends, one second then two seconds apart
+        *chain.outages.lock().unwrap() = 2;
+        process_due(&state, 0).unwrap();
+        assert_eq!(status(&job.request_id).next_attempt_at, 1_000);
+        process_due(&state, 999).unwrap();
+        assert_eq!(status(&job.request_id).attempts, 1);
+        process_due(&state, 1_000).unwrap();
+        assert_eq!(status(&job.request_id).next_attempt_at, 3_000);
+        process_due(&state, 3_000).unwrap();
+        let sent = status(&job.request_id);
+        assert_eq!(sent.attempts, 2);
+        assert_eq!(sent.pending_signature(), Some("sig1"));
+        let history = state.jobs.history(&job.request_id).unwrap();
+        assert_eq!(history[1].note.as_deref(), Some("attempt 1: connection refused"));
+
+        // Never seen: sent again once its blockhash has expired
+        update_confirmations(&state, 3_000 + SUBMISSION_TIMEOUT_MS - 1).unwrap();
+        assert!(status(&job.request_id).pending_signature().is_some());
+        update_confirmations(&state, 3_000 + SUBMISSION_TIMEOUT_MS).unwrap();
+        assert_eq!(status(&job.request_id).status, RelayStatus::Pending);
+        process_due(&state, 10_000_000).unwrap();
+        assert_eq!(status(&job.request_id).pending_signature(), Some("sig2"));
+
+        // Rejected by the cluster; the nullifier is free again
+        confirm(&state, "sig2", Some("NullifierSpent"));
+        update_confirmations(&state, 10_000_000).unwrap();
+        let failed = RelayStatus::Failed { reason: "NullifierSpent".into() };
+        assert_eq!(status(&job.request_id).status, failed);
+
+        // Out of attempts; sends are not rechecked, so any nullifier will do
+        let mut other = request;
+        other.nullifier = [7; 32];
+        let job = state.jobs.enqueue(Job::new(other, 0)).unwrap();
+        *chain.outages.lock().unwrap() = 5;
+        for now in [0, 1_000, 3_000, 7_000] {
+            process_due(&state, now).unwrap();
+        }
+        let failed = RelayStatus::Failed { reason: "connection refused".into() };
+        assert_eq!(status(&job.request_id).status, failed);
+        assert_eq!(status(&job.request_id).attempts, 4);
+    }
+}
@@ -0,0 +1,279 @@
+//! SQLite job storage
+//!
+//! Jobs live in one row each, with the request and status as JSON; their
+//! histories in `job_events`. A `state` column holds the status variant so
+//! the worker's queries need not parse JSON.
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use veil_core::relayer::RelayStatus;
+
+use crate::error::RelayError;
+use crate::jobs::{Job, JobEvent, JobStorage};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS jobs (
+        request_id TEXT PRIMARY KEY,
+        nullifier TEXT NOT NULL,
+        request TEXT NOT NULL,
+        state TEXT NOT NULL,
+        status TEXT NOT NULL,
+        attempts INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        next_attempt_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS jobs_nullifier ON jobs (nullifier);
+    CREATE INDEX IF NOT EXISTS jobs_state ON jobs (state, next_attempt_at);
+    CREATE TABLE IF NOT EXISTS job_events (
+        request_id TEXT NOT NULL REFERENCES jobs (request_id),
+        at INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        note TEXT
+    );
+    CREATE INDEX IF NOT EXISTS job_events_request ON job_events (request_id);
+";
+
+const JOB_COLUMNS: &str =
+    "request_id, request, status, attempts, created_at, updated_at, next_attempt_at";
+
+/// Jobs in a SQLite database
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open or create the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RelayError> {
+        Self::with_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// A database in memory, for tests
+    pub fn in_memory() -> Result<Self, RelayError> {
+        Self::with_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, RelayError> {
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        // Transactions roll back on drop, so a panic leaves nothing partial
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn select(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Job>, RelayError> {
+        let conn = self.lock();
+        let sql = format!("SELECT {} FROM jobs WHERE {} ORDER BY created_at", JOB_COLUMNS, filter);
+        let mut stmt = conn.prepare(&sql).map_err(storage_error)?;
+        let rows = stmt.query_map(params, read_row).map_err(storage_error)?;
+        rows.map(|row| job_from_row(row.map_err(storage_error)?)).collect()
+    }
+}
+
+impl JobStorage for SqliteStorage {
+    fn enqueue(&self, job: Job) -> Result<Job, RelayError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let nullifier = hex::encode(job.request.nullifier);
+        let existing = tx
+            .query_row(
+                &format!(
+                    "SELECT {} FROM jobs WHERE nullifier = ?1 AND state != 'failed' LIMIT 1",
+                    JOB_COLUMNS
+                ),
+                [&nullifier],
+                read_row,
+            )
+            .optional()
+            .map_err(storage_error)?;
+        if let Some(existing) = existing {
+            return job_from_row(existing);
+        }
+
+        tx.execute(
+            "INSERT INTO jobs (request_id, nullifier, request, state, status, attempts,
+                created_at, updated_at, next_attempt_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                job.request_id,
+                nullifier,
+                to_json(&job.request)?,
+                state(&job.status),
+                to_json(&job.status)?,
+                job.attempts,
+                job.created_at as i64,
+                job.updated_at as i64,
+                job.next_attempt_at as i64,
+            ],
+        )
+        .map_err(storage_error)?;
+        insert_event(
+            &tx,
+            &job.request_id,
+            &JobEvent {
+                at: job.created_at,
+                status: job.status.clone(),
+                note: None,
+            },
+        )?;
+        tx.commit().map_err(storage_error)?;
+        Ok(job)
+    }
+
+    fn get(&self, request_id: &str) -> Result<Option<Job>, RelayError> {
+        Ok(self.select("request_id = ?1", [request_id])?.pop())
+    }
+
+    fn update(&self, job: &Job, event: JobEvent) -> Result<(), RelayError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let updated = tx
+            .execute(
+                "UPDATE jobs SET state = ?2, status = ?3, attempts = ?4, updated_at = ?5,
+                    next_attempt_at = ?6
+                 WHERE request_id = ?1",
+                params![
+                    job.request_id,
+                    state(&job.status),
+                    to_json(&job.status)?,
+                    job.attempts,
+                    job.updated_at as i64,
+                    job.next_attempt_at as i64,
+                ],
+            )
+            .map_err(storage_error)?;
+        if updated == 0 {
+            return Err(RelayError::UnknownRequest(job.request_id.clone()));
+        }
+        insert_event(&tx, &job.request_id, &event)?;
+        tx.commit().map_err(storage_error)
+    }
+
+    fn history(&self, request_id: &str) -> Result<Vec<JobEvent>, RelayError> {
+        let conn = self.lock();
+        let mut stmt = conn
+            .prepare("SELECT at, status, note FROM job_events WHERE request_id = ?1 ORDER BY rowid")
+            .map_err(storage_error)?;
+        let rows = stmt
+            .query_map([request_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get(2)?))
+            })
+            .map_err(storage_error)?;
+        rows.map(|row| {
+            let (at, status, note) = row.map_err(storage_error)?;
+            Ok(JobEvent {
+                at: at as u64,
+                status: from_json(&status)?,
+                note,
+            })
+        })
+        .collect()
+    }
+
+    fn due(&self, now: u64) -> Result<Vec<Job>, RelayError> {
+        self.select("state = 'pending' AND next_attempt_at <= ?1", [now as i64])
+    }
+
+    fn submitted(&self) -> Result<Vec<Job>, RelayError> {
+        self.select("state = 'submitted'", [])
+    }
+}
+
+/// The `state` column of a status
+fn state(status: &RelayStatus) -> &'static str {
+    match status {
+        RelayStatus::Pending => "pending",
+        RelayStatus::Submitted { .. } => "submitted",
+        RelayStatus::Confirmed { .. } => "confirmed",
+        RelayStatus::Failed { .. } => "failed",
+    }
+}
+
+/// A row of `JOB_COLUMNS`
+type JobRow = (String, String, String, u32, i64, i64, i64);
+
+fn read_row(row: &Row<'_>) -> rusqlite::Result<JobRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+fn job_from_row(row: JobRow) -> Result<Job, RelayError> {
+    let (request_id, request, status, attempts, created_at, updated_at, next_attempt_at) = row;
+    Ok(Job {
+        request_id,
+        request: from_json(&request)?,
+        status: from_json(&status)?,
+        attempts,
+        created_at: created_at as u64,
+        updated_at: updated_at as u64,
+        next_attempt_at: next_attempt_at as u64,
+    })
+}
+
+fn insert_event(conn: &Connection, request_id: &str, event: &JobEvent) -> Result<(), RelayError> {
+    conn.execute(
+        "INSERT INTO job_events (request_id, at, status, note) VALUES (?1, ?2, ?3, ?4)",
+        params![request_id, event.at as i64, to_json(&event.status)?, event.note],
+    )
+    .map(|_| ())
+    .map_err(storage_error)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, RelayError> {
+    serde_json::to_string(value).map_err(|e| RelayError::Storage(e.to_string()))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, RelayError> {
+    serde_json::from_str(json).map_err(|e| RelayError::Storage(e.to_string()))
+}
+
+fn storage_error(e: rusqlite::Error) -> RelayError {
+    RelayError::Storage(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::tests::{check_storage, request};
+
+    #[test]
+    fn test_sqlite_storage() {
+        check_storage(&SqliteStorage::in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_jobs_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("veil_jobs_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        let mut job = storage.enqueue(Job::new(request(1), 100)).unwrap();
+        job.attempts = 2;
+        job.next_attempt_at = 5_000;
+        let event = job.transition(RelayStatus::Pending, 200, Some("rpc down".into()));
+        storage.update(&job, event).unwrap();
+        drop(storage);
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        let reopened = storage.get(&job.request_id).unwrap().unwrap();
+        assert_eq!(reopened.attempts, 2);
+        assert_eq!(reopened.request.nullifier, [1; 32]);
+        assert!(storage.due(4_999).unwrap().is_empty());
+        assert_eq!(storage.due(5_000).unwrap().len(), 1);
+        assert_eq!(storage.history(&job.request_id).unwrap().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
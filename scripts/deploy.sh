@@ -10,7 +10,7 @@ set -e
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 PROJECT_ROOT="$(dirname "$SCRIPT_DIR")"
-PROGRAM_DIR="$PROJECT_ROOT/solana-program"
+PROGRAM_DIR="$PROJECT_ROOT/crates/program"
 
 # Colors for output
 RED='\033[0;31m'
@@ -53,7 +53,7 @@ build_program() {
     cd "$PROJECT_ROOT"
 
     # Build with Solana BPF target
-    cargo build-bpf --manifest-path solana-program/Cargo.toml
+    cargo build-bpf --manifest-path "$PROGRAM_DIR/Cargo.toml"
 
     log_info "Build complete"
 }
@@ -24,6 +24,7 @@ except ImportError as e:
 
 # Export main API
 from .client import PrivacyClient
+from .proving import prove_spend
 from .types import (
     CommitmentData,
     PrivateTransaction,
@@ -44,6 +45,8 @@ __all__ = [
     "PrivateTransaction",
     "CommitmentData",
     "TransactionStatus",
+    # Proving
+    "prove_spend",
     # Utilities
     "generate_secret",
     "commitment_to_hex",
@@ -19,7 +19,9 @@ class AssetRegistry:
     Registry for managing asset types in privacy pools.
 
     The asset registry converts token mint addresses to 8-byte asset IDs
-    that are used in commitments and encrypted notes.
+    that are used in commitments and encrypted notes. The program derives
+    the same ID from a pool's mint for the transfer circuit's asset_id
+    public input, so the two must stay in sync.
     """
 
     @staticmethod
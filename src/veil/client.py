@@ -27,6 +27,7 @@ def generate_mvp_proof(
     commitment: bytes,
     root: bytes,
     keypair: Keypair,
+    fee: int = 0,
 ) -> bytes:
     """
     Generate MVP proof (Ed25519 signature)
@@ -39,14 +40,15 @@ def generate_mvp_proof(
         commitment: New commitment bytes (32 bytes)
         root: Merkle root bytes (32 bytes)
         keypair: Signing keypair
+        fee: Relayer fee the transfer pays (u64)
 
     Returns:
         96-byte proof: signature (64) + pubkey (32)
     """
     import hashlib
 
-    # Build message to sign: keccak256(nullifier || commitment || root)
-    message = nullifier + commitment + root
+    # Build message to sign: keccak256(nullifier || commitment || root || fee)
+    message = nullifier + commitment + root + fee.to_bytes(8, "little")
     message_hash = hashlib.sha3_256(message).digest()
 
     # Sign with Ed25519
@@ -225,6 +227,7 @@ class PrivacyClient:
             new_commitment=recipient_commitment_bytes,
             proof=proof,
             payer_keypair=bytes(sender_keypair),
+            root=root,
         )
 
         return PrivateTransaction(
@@ -244,6 +247,7 @@ class PrivacyClient:
         owner_secret: str,
         commitment: str,
         token: str = "SOL",
+        memo: str = "",
     ) -> PrivateTransaction:
         """
         Unshield assets to make them public (submits to blockchain)
@@ -255,6 +259,8 @@ class PrivacyClient:
             owner_secret: Owner's secret key
             commitment: Commitment hex to unshield
             token: Token mint address ("SOL" for native SOL)
+            memo: Memo logged with the withdrawal, such as the deposit
+                reference an exchange requires (empty for none)
 
         Returns:
             Transaction result
@@ -271,7 +277,7 @@ class PrivacyClient:
         )
 
         # Get current Merkle root
-        root = await self.solana.get_merkle_root()
+        root = await self.solana.get_merkle_root(token)
 
         # Generate MVP proof (signature-based)
         # For unshield, we sign: keccak256(nullifier || recipient || amount || root)
@@ -294,6 +300,8 @@ class PrivacyClient:
             proof=proof,
             payer_keypair=bytes(owner_keypair),
             token=token,
+            memo=memo.encode(),
+            root=root,
         )
 
         return PrivateTransaction(
@@ -366,6 +374,10 @@ class PrivacyClient:
         """
         Generate transfer transaction data (offline)
 
+        The transaction carries no proof: proving needs the spent note's
+        leaf index and Merkle path. Prove with
+        `_rust_core.generate_proof` or `_rust_core.TransferProofSystem`.
+
         Args:
             recipient: Recipient's address
             amount: Amount to transfer
@@ -398,22 +410,11 @@ class PrivacyClient:
             secret=recipient.encode(),
         )
 
-        witness = {
-            "sender_secret": sender_secret,
-            "sender_commitment": sender_commitment,
-            "recipient": recipient,
-            "amount": amount,
-            "nullifier": nullifier_bytes.hex(),
-        }
-
-        proof_bytes = self._rust.generate_proof(witness_json=json.dumps(witness))
-
         return PrivateTransaction(
             signature="offline_" + secrets.token_hex(16),
             status=TransactionStatus.PENDING,
             nullifier=nullifier_bytes.hex(),
             commitment=recipient_commitment_bytes.hex(),
-            proof=proof_bytes,
         )
 
     def unshield_assets(
@@ -426,6 +427,8 @@ class PrivacyClient:
         """
         Generate unshield transaction data (offline)
 
+        As with `private_transfer`, the transaction carries no proof.
+
         Args:
             amount: Amount to unshield
             destination: Destination address
@@ -443,34 +446,30 @@ class PrivacyClient:
             secret=owner_secret.encode(),
         )
 
-        witness = {
-            "sender_secret": owner_secret,
-            "sender_commitment": commitment,
-            "recipient": destination,
-            "amount": amount,
-            "nullifier": nullifier_bytes.hex(),
-        }
-
-        proof_bytes = self._rust.generate_proof(witness_json=json.dumps(witness))
-
         return PrivateTransaction(
             signature="offline_" + secrets.token_hex(16),
             status=TransactionStatus.PENDING,
             nullifier=nullifier_bytes.hex(),
-            proof=proof_bytes,
         )
 
     def verify_proof(
         self,
         proof: bytes,
         public_inputs: dict[str, Any],
+        keys: Any = None,
+        network: str = "devnet",
     ) -> bool:
         """
-        Verify a zkSNARK proof
+        Verify a Groth16 transfer proof
 
         Args:
-            proof: Proof bytes
-            public_inputs: Public inputs dictionary
+            proof: Proof bytes (`SpendProof.proof`)
+            public_inputs: Hex merkle_root, nullifier and new_commitment,
+                and integer asset_id and fee, as in
+                `SpendProof.public_inputs_json`
+            keys: _rust_core.KeyStore to verify with; the network's default
+                keys if omitted
+            network: Network whose default keys to use
 
         Returns:
             True if proof is valid
@@ -478,6 +477,8 @@ class PrivacyClient:
         return self._rust.verify_proof(
             proof=proof,
             public_inputs_json=json.dumps(public_inputs),
+            keys=keys,
+            network=network,
         )
 
     async def close(self) -> None:
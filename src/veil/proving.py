@@ -0,0 +1,36 @@
+"""Proving without blocking the event loop"""
+
+import asyncio
+from typing import Any, Optional
+
+# How often to check whether a proof is ready; proofs take seconds
+POLL_INTERVAL = 0.05
+
+
+async def prove_spend(
+    system: Any,
+    note: Any,
+    tree: Any,
+    fee: int = 0,
+    output_blinding: Optional[bytes] = None,
+) -> Any:
+    """
+    Prove a spend on a prover thread and await the result
+
+    Concurrent calls prove in parallel: the prover holds neither the GIL
+    nor a thread of the event loop's executor.
+
+    Args:
+        system: _rust_core.TransferProofSystem
+        note: _rust_core.Note to spend, with its leaf index set
+        tree: _rust_core.PoseidonMerkleTree holding the note
+        fee: Part of the note's amount paid to the relayer
+        output_blinding: Blinding of the output note; random if omitted
+
+    Returns:
+        _rust_core.SpendProof
+    """
+    task = system.prove_async(note, tree, fee=fee, output_blinding=output_blinding)
+    while not task.done():
+        await asyncio.sleep(POLL_INTERVAL)
+    return task.result()
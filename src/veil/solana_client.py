@@ -7,7 +7,7 @@ This module provides low-level Solana interaction functionality:
 - On-chain program interaction
 """
 
-from typing import Optional, Tuple
+from typing import List, Optional, Tuple
 import struct
 
 from solana.rpc.async_api import AsyncClient
@@ -19,9 +19,13 @@ from solders.transaction import Transaction
 from solders.message import Message
 from solders.hash import Hash
 from solders.system_program import ID as SYSTEM_PROGRAM_ID
-from spl.token.constants import TOKEN_PROGRAM_ID
+from spl.token.constants import ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID
 
-from .token_utils import get_or_create_ata, get_associated_token_address
+from .token_utils import (
+    get_or_create_ata,
+    get_associated_token_address,
+    get_mint_token_program,
+)
 
 # Program ID - replace with actual deployed program ID
 DEFAULT_PROGRAM_ID = "Nyx1111111111111111111111111111111111111111"
@@ -30,11 +34,84 @@ DEFAULT_PROGRAM_ID = "Nyx1111111111111111111111111111111111111111"
 POOL_SEED = b"privacy_pool"
 VAULT_SEED = b"vault"
 NULLIFIER_SEED = b"nullifier"
+VK_SEED = b"verifying_key"
+ARCHIVE_SEED = b"nullifier_archive"
+TREASURY_SEED = b"treasury"
+ARCHIVED_ROOT_SEED = b"archived_root"
+RELAYER_SEED = b"relayer"
+PENDING_WITHDRAWAL_SEED = b"pending_withdrawal"
+ROOT_HISTORY_SEED = b"root_history"
 
+# Nullifier archive bucket layout: discriminator, pool, tree epoch (u32),
+# bucket (u8), count (u32), then sorted 32-byte nullifiers
+ARCHIVE_ENTRIES_OFFSET = 8 + 32 + 4 + 1 + 4
 
-def find_pool_pda(program_id: Pubkey) -> Tuple[Pubkey, int]:
-    """Derive the pool PDA address"""
-    return Pubkey.find_program_address([POOL_SEED], program_id)
+# Recent roots a pool accepts proofs against, by default and at most
+DEFAULT_ROOT_HISTORY_SIZE = 30
+MAX_ROOT_HISTORY_SIZE = 300
+
+# Maximum size of an encrypted note attached to a commitment
+ENCRYPTED_NOTE_SIZE = 109
+
+# Notes a transfer may sweep beyond its first input
+MAX_EXTRA_NULLIFIERS = 7
+
+# Minimum stake to join the relayer registry (1 SOL) and longest endpoint
+MIN_RELAYER_STAKE = 1_000_000_000
+MAX_ENDPOINT_LEN = 128
+
+# Pool pause flags (bitmask for set_pause_state)
+PAUSE_SHIELD = 1 << 0
+PAUSE_TRANSFER = 1 << 1
+PAUSE_UNSHIELD = 1 << 2
+PAUSE_ALL = PAUSE_SHIELD | PAUSE_TRANSFER | PAUSE_UNSHIELD
+
+# Maximum relayer fee in basis points (5%)
+MAX_RELAYER_FEE_BPS = 500
+
+# Largest share of each relayer fee the protocol may take, in basis points
+# of the fee (50%)
+MAX_PROTOCOL_FEE_BPS = 5_000
+
+# Longest withdrawal delay a pool may set, in slots (about a week)
+MAX_WITHDRAWAL_DELAY_SLOTS = 1_512_000
+
+# Mint that keys the native SOL pool (wrapped SOL)
+NATIVE_MINT = Pubkey.from_string("So11111111111111111111111111111111111111112")
+
+# Programs behind pools with a compressed commitment tree
+ACCOUNT_COMPRESSION_PROGRAM_ID = Pubkey.from_string(
+    "cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK"
+)
+NOOP_PROGRAM_ID = Pubkey.from_string("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV")
+# Memo program, which logs unshield memos such as exchange deposit references
+MEMO_PROGRAM_ID = Pubkey.from_string("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr")
+MAX_MEMO_LEN = 256
+
+MIN_COMPRESSED_TREE_DEPTH = 24
+MAX_COMPRESSED_TREE_DEPTH = 30
+
+
+def _encode_encrypted_note(encrypted_note: bytes) -> bytes:
+    """Borsh-encode an encrypted note payload (u32 length + bytes)"""
+    if len(encrypted_note) > ENCRYPTED_NOTE_SIZE:
+        raise ValueError(f"Encrypted note must be at most {ENCRYPTED_NOTE_SIZE} bytes")
+    return struct.pack("<I", len(encrypted_note)) + encrypted_note
+
+
+def _encode_memo(memo: bytes) -> bytes:
+    """Borsh-encode an unshield memo, checking it against MAX_MEMO_LEN"""
+    if len(memo) > MAX_MEMO_LEN:
+        raise ValueError(f"Memo must be at most {MAX_MEMO_LEN} bytes")
+    memo.decode("utf-8")
+    return struct.pack("<I", len(memo)) + memo
+
+
+def find_pool_pda(
+    program_id: Pubkey, mint: Pubkey = NATIVE_MINT
+) -> Tuple[Pubkey, int]:
+    """Derive the pool PDA address for a mint (native SOL by default)"""
+    return Pubkey.find_program_address([POOL_SEED, bytes(mint)], program_id)
 
 
 def find_vault_pda(program_id: Pubkey, pool: Pubkey) -> Tuple[Pubkey, int]:
@@ -42,25 +119,133 @@ def find_vault_pda(program_id: Pubkey, pool: Pubkey) -> Tuple[Pubkey, int]:
     return Pubkey.find_program_address([VAULT_SEED, bytes(pool)], program_id)
 
 
+def find_verifying_key_pda(program_id: Pubkey, pool: Pubkey) -> Tuple[Pubkey, int]:
+    """Derive the pool's verifying key PDA address"""
+    return Pubkey.find_program_address([VK_SEED, bytes(pool)], program_id)
+
+
 def find_nullifier_pda(
-    program_id: Pubkey, pool: Pubkey, nullifier: bytes
+    program_id: Pubkey, pool: Pubkey, nullifier: bytes, tree_epoch: int = 0
+) -> Tuple[Pubkey, int]:
+    """Derive the nullifier marker PDA address for a note in tree `tree_epoch`"""
+    return Pubkey.find_program_address(
+        [NULLIFIER_SEED, bytes(pool), struct.pack("<I", tree_epoch), nullifier],
+        program_id,
+    )
+
+
+def nullifier_archive_bucket(nullifier: bytes) -> int:
+    """Archive bucket a nullifier is compacted into (its last byte)"""
+    return nullifier[31]
+
+
+def find_nullifier_archive_pda(
+    program_id: Pubkey, pool: Pubkey, bucket: int, tree_epoch: int = 0
+) -> Tuple[Pubkey, int]:
+    """Derive a nullifier archive bucket PDA address"""
+    return Pubkey.find_program_address(
+        [ARCHIVE_SEED, bytes(pool), struct.pack("<I", tree_epoch), bytes([bucket])],
+        program_id,
+    )
+
+
+def find_archived_root_pda(
+    program_id: Pubkey, pool: Pubkey, tree_epoch: int
+) -> Tuple[Pubkey, int]:
+    """Derive the archived root PDA address of a rotated-out tree"""
+    return Pubkey.find_program_address(
+        [ARCHIVED_ROOT_SEED, bytes(pool), struct.pack("<I", tree_epoch)], program_id
+    )
+
+
+def find_root_history_pda(program_id: Pubkey, pool: Pubkey) -> Tuple[Pubkey, int]:
+    """Derive the PDA address of a pool's recent root history"""
+    return Pubkey.find_program_address([ROOT_HISTORY_SEED, bytes(pool)], program_id)
+
+
+def _check_root_history_size(size: int) -> None:
+    if not 1 <= size <= MAX_ROOT_HISTORY_SIZE:
+        raise ValueError(f"Root history size must be between 1 and {MAX_ROOT_HISTORY_SIZE}")
+
+
+def concurrent_merkle_tree_size(max_depth: int, max_buffer_size: int) -> int:
+    """Bytes to allocate for an spl-account-compression tree (no canopy)
+
+    Header, then sequence number, active index and buffer size, the
+    changelog buffer and the rightmost proof.
+    """
+    changelog = 32 + 32 * max_depth + 8
+    rightmost_proof = 32 * max_depth + 32 + 8
+    return 56 + 24 + max_buffer_size * changelog + rightmost_proof
+
+
+def compressed_tree_accounts(merkle_tree: Pubkey) -> List[AccountMeta]:
+    """Trailing accounts of instructions inserting into a compressed tree"""
+    return [
+        AccountMeta(merkle_tree, is_signer=False, is_writable=True),
+        AccountMeta(NOOP_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(ACCOUNT_COMPRESSION_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+
+
+def find_pending_withdrawal_pda(
+    program_id: Pubkey, pool: Pubkey, nullifier: bytes, tree_epoch: int = 0
 ) -> Tuple[Pubkey, int]:
-    """Derive the nullifier marker PDA address"""
+    """Derive the PDA queueing a delayed unshield of `nullifier`"""
     return Pubkey.find_program_address(
-        [NULLIFIER_SEED, bytes(pool), nullifier], program_id
+        [PENDING_WITHDRAWAL_SEED, bytes(pool), struct.pack("<I", tree_epoch), nullifier],
+        program_id,
     )
 
 
+def find_treasury_pda(program_id: Pubkey, pool: Pubkey) -> Tuple[Pubkey, int]:
+    """Derive the pool treasury PDA address"""
+    return Pubkey.find_program_address([TREASURY_SEED, bytes(pool)], program_id)
+
+
+def find_relayer_pda(program_id: Pubkey, relayer: Pubkey) -> Tuple[Pubkey, int]:
+    """Derive a relayer's registry entry PDA address"""
+    return Pubkey.find_program_address([RELAYER_SEED, bytes(relayer)], program_id)
+
+
 class InstructionBuilder:
     """Builds Veil privacy pool instructions"""
 
     # Instruction discriminators (first 8 bytes of sha256 hash of instruction name)
     INITIALIZE_DISC = bytes([175, 175, 109, 31, 13, 152, 155, 237])
+    INITIALIZE_POOL_FOR_MINT_DISC = bytes([86, 114, 81, 196, 99, 43, 122, 13])
     SHIELD_SOL_DISC = bytes([183, 4, 24, 123, 20, 45, 203, 91])
     SHIELD_DISC = bytes([112, 186, 93, 111, 79, 168, 36, 51])
     TRANSFER_DISC = bytes([163, 52, 200, 231, 140, 3, 69, 186])
     UNSHIELD_SOL_DISC = bytes([45, 127, 188, 9, 224, 78, 199, 57])
     UNSHIELD_DISC = bytes([126, 89, 240, 247, 56, 193, 126, 10])
+    SET_VERIFYING_KEY_DISC = bytes([79, 162, 161, 210, 103, 106, 246, 78])
+    ENABLE_ZK_DISC = bytes([244, 116, 230, 139, 187, 218, 123, 2])
+    SET_PAUSE_STATE_DISC = bytes([130, 225, 63, 203, 229, 214, 138, 17])
+    PROPOSE_AUTHORITY_DISC = bytes([20, 148, 236, 198, 76, 119, 99, 142])
+    ACCEPT_AUTHORITY_DISC = bytes([107, 86, 198, 91, 33, 12, 107, 160])
+    UPDATE_RELAYER_FEE_DISC = bytes([247, 4, 34, 35, 30, 149, 78, 25])
+    TRANSFER_JOINSPLIT_DISC = bytes([161, 36, 125, 186, 109, 140, 236, 61])
+    SET_NULLIFIER_FINALITY_DISC = bytes([146, 33, 199, 193, 107, 90, 225, 46])
+    COMPACT_NULLIFIERS_DISC = bytes([186, 40, 143, 158, 149, 30, 148, 57])
+    ROTATE_TREE_DISC = bytes([41, 237, 195, 168, 158, 98, 247, 94])
+    REGISTER_RELAYER_DISC = bytes([98, 213, 0, 0, 27, 134, 109, 48])
+    DEREGISTER_RELAYER_DISC = bytes([119, 161, 188, 185, 242, 252, 35, 96])
+    SET_WITHDRAWAL_DELAY_DISC = bytes([188, 153, 14, 109, 50, 127, 169, 158])
+    FINALIZE_WITHDRAWAL_SOL_DISC = bytes([220, 172, 100, 245, 27, 56, 159, 133])
+    FINALIZE_WITHDRAWAL_DISC = bytes([178, 87, 206, 68, 201, 186, 164, 232])
+    SET_DEPOSIT_CAPS_DISC = bytes([14, 5, 41, 181, 21, 175, 64, 175])
+    USE_COMPRESSED_TREE_DISC = bytes([18, 221, 187, 247, 119, 148, 84, 115])
+    SET_PROTOCOL_FEE_DISC = bytes([173, 239, 83, 242, 136, 43, 144, 217])
+    COLLECT_FEES_DISC = bytes([164, 152, 207, 99, 30, 186, 19, 182])
+    UNSHIELD_WITH_ATA_DISC = bytes([205, 0, 34, 193, 143, 213, 20, 205])
+    RESIZE_ROOT_HISTORY_DISC = bytes([181, 12, 129, 37, 90, 35, 171, 174])
+    SET_UNSHIELD_VERIFYING_KEY_DISC = bytes([100, 140, 61, 111, 125, 204, 149, 154])
+
+    # Public inputs of the transfer circuit, plus one
+    NUM_IC = 6
+    # Public inputs of the unshield circuit, plus one
+    UNSHIELD_NUM_IC = 7
 
     def __init__(self, program_id: Pubkey):
         """Initialize instruction builder.
@@ -70,306 +255,1737 @@ class InstructionBuilder:
         """
         self.program_id = program_id
 
-    def initialize(self, authority: Pubkey) -> Instruction:
-        """Build initialize instruction"""
+    def initialize(
+        self, authority: Pubkey, root_history_size: int = DEFAULT_ROOT_HISTORY_SIZE
+    ) -> Instruction:
+        """Build initialize instruction for the native SOL pool
+
+        The pool accepts proofs against its `root_history_size` most recent
+        roots.
+        """
+        _check_root_history_size(root_history_size)
         pool, _pool_bump = find_pool_pda(self.program_id)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
 
         accounts = [
             AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=True),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
             AccountMeta(authority, is_signer=True, is_writable=True),
             AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
         ]
 
-        return Instruction(self.program_id, self.INITIALIZE_DISC, accounts)
+        data = self.INITIALIZE_DISC + struct.pack("<H", root_history_size)
+        return Instruction(self.program_id, data, accounts)
 
-    def shield_sol(
+    def initialize_pool_for_mint(
         self,
-        depositor: Pubkey,
-        commitment: bytes,
-        amount: int,
+        authority: Pubkey,
+        mint: Pubkey,
+        root_history_size: int = DEFAULT_ROOT_HISTORY_SIZE,
     ) -> Instruction:
-        """Build shield SOL instruction"""
-        if len(commitment) != 32:
-            raise ValueError("Commitment must be 32 bytes")
-
-        pool, _pool_bump = find_pool_pda(self.program_id)
-        vault, _vault_bump = find_vault_pda(self.program_id, pool)
+        """Build initialize instruction for an SPL token mint's pool"""
+        _check_root_history_size(root_history_size)
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
 
         accounts = [
             AccountMeta(pool, is_signer=False, is_writable=True),
-            AccountMeta(vault, is_signer=False, is_writable=True),
-            AccountMeta(depositor, is_signer=True, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=True),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(mint, is_signer=False, is_writable=False),
+            AccountMeta(authority, is_signer=True, is_writable=True),
             AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
         ]
 
-        # Instruction data: discriminator + commitment (32 bytes) + amount (u64)
-        data = self.SHIELD_SOL_DISC + commitment + struct.pack("<Q", amount)
+        data = self.INITIALIZE_POOL_FOR_MINT_DISC + struct.pack("<H", root_history_size)
+        return Instruction(self.program_id, data, accounts)
+
+    def resize_root_history(
+        self, authority: Pubkey, size: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build resize_root_history instruction for the mint's pool
+
+        Growing the history is paid for by `authority`; the newest roots are
+        kept.
+        """
+        _check_root_history_size(size)
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
 
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        data = self.RESIZE_ROOT_HISTORY_DISC + struct.pack("<H", size)
         return Instruction(self.program_id, data, accounts)
 
-    def shield_spl(
+    def set_verifying_key(
         self,
-        depositor: Pubkey,
-        depositor_token_account: Pubkey,
-        vault_token_account: Pubkey,
-        commitment: bytes,
-        amount: int,
+        authority: Pubkey,
+        alpha_g1: bytes,
+        beta_g2: bytes,
+        gamma_g2: bytes,
+        delta_g2: bytes,
+        ic: list,
+        lock: bool = False,
+        mint: Pubkey = NATIVE_MINT,
     ) -> Instruction:
-        """Build shield SPL token instruction"""
-        if len(commitment) != 32:
-            raise ValueError("Commitment must be 32 bytes")
+        """Build set_verifying_key instruction for the mint's pool
 
-        pool, _pool_bump = find_pool_pda(self.program_id)
-        vault_authority, _vault_bump = find_vault_pda(self.program_id, pool)
+        Points use the groth16-solana encoding (big-endian, G1 64 bytes,
+        G2 128 bytes). With `lock` set the key can no longer be replaced.
+        """
+        return self._set_key(
+            self.SET_VERIFYING_KEY_DISC,
+            self.NUM_IC,
+            authority,
+            (alpha_g1, beta_g2, gamma_g2, delta_g2, ic),
+            lock,
+            mint,
+        )
+
+    def set_unshield_verifying_key(
+        self,
+        authority: Pubkey,
+        alpha_g1: bytes,
+        beta_g2: bytes,
+        gamma_g2: bytes,
+        delta_g2: bytes,
+        ic: list,
+        lock: bool = False,
+        mint: Pubkey = NATIVE_MINT,
+    ) -> Instruction:
+        """Build set_unshield_verifying_key instruction for the mint's pool
+
+        As `set_verifying_key`, for the unshield circuit's key. `lock` locks
+        both keys, so set this one first.
+        """
+        return self._set_key(
+            self.SET_UNSHIELD_VERIFYING_KEY_DISC,
+            self.UNSHIELD_NUM_IC,
+            authority,
+            (alpha_g1, beta_g2, gamma_g2, delta_g2, ic),
+            lock,
+            mint,
+        )
+
+    def _set_key(
+        self,
+        discriminator: bytes,
+        num_ic: int,
+        authority: Pubkey,
+        key: tuple,
+        lock: bool,
+        mint: Pubkey,
+    ) -> Instruction:
+        """Build a verifying key instruction for a key with `num_ic` IC points"""
+        alpha_g1, beta_g2, gamma_g2, delta_g2, ic = key
+        if len(alpha_g1) != 64:
+            raise ValueError("alpha_g1 must be 64 bytes")
+        for name, point in (("beta_g2", beta_g2), ("gamma_g2", gamma_g2), ("delta_g2", delta_g2)):
+            if len(point) != 128:
+                raise ValueError(f"{name} must be 128 bytes")
+        if len(ic) != num_ic or any(len(point) != 64 for point in ic):
+            raise ValueError(f"ic must be {num_ic} points of 64 bytes")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(verifying_key, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        # Instruction data: discriminator + key + lock (bool)
+        data = (
+            discriminator
+            + alpha_g1
+            + beta_g2
+            + gamma_g2
+            + delta_g2
+            + b"".join(ic)
+            + bytes([1 if lock else 0])
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def enable_zk(self, authority: Pubkey, mint: Pubkey = NATIVE_MINT) -> Instruction:
+        """Build enable_zk instruction for the mint's pool
+
+        The pool rejects Groth16 proofs until this succeeds, which requires
+        its transfer and unshield verifying keys to be set.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
 
         accounts = [
             AccountMeta(pool, is_signer=False, is_writable=True),
-            AccountMeta(vault_authority, is_signer=False, is_writable=False),
-            AccountMeta(vault_token_account, is_signer=False, is_writable=True),
-            AccountMeta(depositor_token_account, is_signer=False, is_writable=True),
-            AccountMeta(depositor, is_signer=True, is_writable=True),
-            AccountMeta(TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(verifying_key, is_signer=False, is_writable=False),
+            AccountMeta(authority, is_signer=True, is_writable=False),
         ]
 
-        # Instruction data: discriminator + commitment (32 bytes) + amount (u64)
-        data = self.SHIELD_DISC + commitment + struct.pack("<Q", amount)
+        return Instruction(self.program_id, self.ENABLE_ZK_DISC, accounts)
+
+    def set_pause_state(
+        self, authority: Pubkey, paused: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build set_pause_state instruction for the mint's pool
+
+        `paused` is a bitmask of PAUSE_* flags and replaces the current one.
+        """
+        if paused & ~PAUSE_ALL:
+            raise ValueError("Unknown pause flags")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = self.SET_PAUSE_STATE_DISC + bytes([paused])
 
         return Instruction(self.program_id, data, accounts)
 
-    def transfer(
+    def propose_authority(
+        self, authority: Pubkey, new_authority: Pubkey, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build propose_authority instruction for the mint's pool
+
+        Proposing the default (all-zero) key cancels a pending proposal.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = self.PROPOSE_AUTHORITY_DISC + bytes(new_authority)
+
+        return Instruction(self.program_id, data, accounts)
+
+    def accept_authority(
+        self, new_authority: Pubkey, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build accept_authority instruction for the mint's pool"""
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(new_authority, is_signer=True, is_writable=False),
+        ]
+
+        return Instruction(self.program_id, self.ACCEPT_AUTHORITY_DISC, accounts)
+
+    def update_relayer_fee(
         self,
-        relayer: Pubkey,
-        nullifier: bytes,
-        new_commitment: bytes,
-        proof: bytes,
+        authority: Pubkey,
+        new_fee_bps: int,
+        delay_slots: int = 0,
+        mint: Pubkey = NATIVE_MINT,
     ) -> Instruction:
-        """Build private transfer instruction"""
-        if len(nullifier) != 32:
-            raise ValueError("Nullifier must be 32 bytes")
-        if len(new_commitment) != 32:
-            raise ValueError("New commitment must be 32 bytes")
+        """Build update_relayer_fee instruction for the mint's pool
 
-        pool, _pool_bump = find_pool_pda(self.program_id)
-        nullifier_marker, _null_bump = find_nullifier_pda(
-            self.program_id, pool, nullifier
+        The new fee applies `delay_slots` slots after the instruction lands
+        (immediately for 0).
+        """
+        if not 0 <= new_fee_bps <= MAX_RELAYER_FEE_BPS:
+            raise ValueError(f"Relayer fee must be at most {MAX_RELAYER_FEE_BPS} bps")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = (
+            self.UPDATE_RELAYER_FEE_DISC
+            + struct.pack("<H", new_fee_bps)
+            + struct.pack("<Q", delay_slots)
         )
 
+        return Instruction(self.program_id, data, accounts)
+
+    def set_nullifier_finality(
+        self, authority: Pubkey, epochs: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build set_nullifier_finality instruction for the mint's pool"""
+        if epochs < 1:
+            raise ValueError("Finality window must be at least one epoch")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
         accounts = [
             AccountMeta(pool, is_signer=False, is_writable=True),
-            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
-            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = self.SET_NULLIFIER_FINALITY_DISC + struct.pack("<H", epochs)
+
+        return Instruction(self.program_id, data, accounts)
+
+    def use_compressed_tree(
+        self,
+        authority: Pubkey,
+        merkle_tree: Pubkey,
+        max_depth: int,
+        max_buffer_size: int,
+        mint: Pubkey = NATIVE_MINT,
+    ) -> Instruction:
+        """Build use_compressed_tree instruction for the mint's (empty) pool
+
+        `merkle_tree` must already be allocated with
+        `concurrent_merkle_tree_size` bytes and owned by the compression
+        program.
+        """
+        if not MIN_COMPRESSED_TREE_DEPTH <= max_depth <= MAX_COMPRESSED_TREE_DEPTH:
+            raise ValueError(
+                f"Tree depth must be {MIN_COMPRESSED_TREE_DEPTH} to {MAX_COMPRESSED_TREE_DEPTH}"
+            )
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(merkle_tree, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+            AccountMeta(NOOP_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(ACCOUNT_COMPRESSION_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        data = self.USE_COMPRESSED_TREE_DISC + struct.pack("<BI", max_depth, max_buffer_size)
+
+        return Instruction(self.program_id, data, accounts)
+
+    def set_deposit_caps(
+        self,
+        authority: Pubkey,
+        max_deposit_per_tx: int,
+        max_pool_tvl: int,
+        mint: Pubkey = NATIVE_MINT,
+    ) -> Instruction:
+        """Build set_deposit_caps instruction for the mint's pool
+
+        A cap of 0 leaves that limit off.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = self.SET_DEPOSIT_CAPS_DISC + struct.pack(
+            "<QQ", max_deposit_per_tx, max_pool_tvl
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def set_withdrawal_delay(
+        self, authority: Pubkey, delay_slots: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build set_withdrawal_delay instruction for the mint's pool"""
+        if not 0 <= delay_slots <= MAX_WITHDRAWAL_DELAY_SLOTS:
+            raise ValueError(
+                f"Withdrawal delay must be at most {MAX_WITHDRAWAL_DELAY_SLOTS} slots"
+            )
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=False),
+        ]
+
+        data = self.SET_WITHDRAWAL_DELAY_DISC + struct.pack("<Q", delay_slots)
+
+        return Instruction(self.program_id, data, accounts)
+
+    def set_protocol_fee(
+        self, authority: Pubkey, fee_bps: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build set_protocol_fee instruction for the mint's pool
+
+        `fee_bps` is the treasury's share of each relayer fee, in basis points
+        of the fee. The authority tops the treasury up to rent exemption.
+        """
+        if not 0 <= fee_bps <= MAX_PROTOCOL_FEE_BPS:
+            raise ValueError(f"Protocol fee must be at most {MAX_PROTOCOL_FEE_BPS} bps")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        treasury, _treasury_bump = find_treasury_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(treasury, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        data = self.SET_PROTOCOL_FEE_DISC + struct.pack("<H", fee_bps)
+
+        return Instruction(self.program_id, data, accounts)
+
+    def collect_fees(
+        self,
+        authority: Pubkey,
+        destination: Pubkey,
+        mint: Pubkey = NATIVE_MINT,
+        treasury_token_account: Optional[Pubkey] = None,
+        destination_token_account: Optional[Pubkey] = None,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+    ) -> Instruction:
+        """Build collect_fees instruction sweeping the mint's pool treasury
+
+        Lamports above the treasury's rent-exempt minimum go to `destination`.
+        Tokens are swept too when both token accounts are given.
+        """
+        if (treasury_token_account is None) != (destination_token_account is None):
+            raise ValueError("Give both treasury and destination token accounts, or neither")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        treasury, _treasury_bump = find_treasury_pda(self.program_id, pool)
+
+        # Anchor reads the program ID in place of an omitted optional account
+        sweep_tokens = treasury_token_account is not None
+        optional = [
+            (mint, False),
+            (treasury_token_account, True),
+            (destination_token_account, True),
+        ]
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(treasury, is_signer=False, is_writable=True),
+            AccountMeta(destination, is_signer=False, is_writable=True),
+        ]
+        accounts += [
+            AccountMeta(key, is_signer=False, is_writable=writable)
+            if sweep_tokens
+            else AccountMeta(self.program_id, is_signer=False, is_writable=False)
+            for key, writable in optional
+        ]
+        accounts += [
+            AccountMeta(authority, is_signer=True, is_writable=False),
+            AccountMeta(
+                token_program if sweep_tokens else self.program_id,
+                is_signer=False,
+                is_writable=False,
+            ),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        return Instruction(self.program_id, self.COLLECT_FEES_DISC, accounts)
+
+    def compact_nullifiers(
+        self,
+        authority: Pubkey,
+        bucket: int,
+        nullifiers: List[bytes],
+        mint: Pubkey = NATIVE_MINT,
+        tree_epoch: int = 0,
+    ) -> Instruction:
+        """Build compact_nullifiers instruction for the mint's pool
+
+        Every nullifier must be from tree `tree_epoch`, fall in `bucket` and
+        have been spent before the pool's finality window. Their markers are closed and the rent sent to
+        the pool treasury.
+        """
+        if any(len(n) != 32 for n in nullifiers):
+            raise ValueError("Nullifiers must be 32 bytes")
+        if any(nullifier_archive_bucket(n) != bucket for n in nullifiers):
+            raise ValueError("Nullifiers must all fall in the bucket")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        archive, _archive_bump = find_nullifier_archive_pda(
+            self.program_id, pool, bucket, tree_epoch
+        )
+        treasury, _treasury_bump = find_treasury_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(archive, is_signer=False, is_writable=True),
+            AccountMeta(treasury, is_signer=False, is_writable=True),
+            AccountMeta(authority, is_signer=True, is_writable=True),
             AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ] + [
+            AccountMeta(
+                find_nullifier_pda(self.program_id, pool, n, tree_epoch)[0],
+                is_signer=False,
+                is_writable=True,
+            )
+            for n in nullifiers
         ]
 
-        # Instruction data: discriminator + nullifier + new_commitment + proof
-        # Proof is variable length, preceded by 4-byte length
         data = (
-            self.TRANSFER_DISC
-            + nullifier
-            + new_commitment
-            + struct.pack("<I", len(proof))
-            + proof
+            self.COMPACT_NULLIFIERS_DISC + struct.pack("<I", tree_epoch) + bytes([bucket])
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def rotate_tree(
+        self, payer: Pubkey, tree_epoch: int, mint: Pubkey = NATIVE_MINT
+    ) -> Instruction:
+        """Build rotate_tree instruction for the mint's pool
+
+        `tree_epoch` is the pool's current (full) tree; `payer` funds the
+        account archiving its final root.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+        archived_root, _root_bump = find_archived_root_pda(self.program_id, pool, tree_epoch)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(archived_root, is_signer=False, is_writable=True),
+            AccountMeta(payer, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        return Instruction(self.program_id, self.ROTATE_TREE_DISC, accounts)
+
+    def register_relayer(
+        self,
+        relayer: Pubkey,
+        fee_bps: int,
+        endpoint: str,
+        stake: int = MIN_RELAYER_STAKE,
+    ) -> Instruction:
+        """Build register_relayer instruction
+
+        Locks `stake` lamports in the relayer's registry entry and advertises
+        `fee_bps` and the `https://` `endpoint`.
+        """
+        if not 0 <= fee_bps <= MAX_RELAYER_FEE_BPS:
+            raise ValueError(f"Relayer fee must be at most {MAX_RELAYER_FEE_BPS} bps")
+        encoded = endpoint.encode()
+        if not endpoint.startswith("https://") or len(encoded) > MAX_ENDPOINT_LEN:
+            raise ValueError(
+                f"Endpoint must be an https:// URL of at most {MAX_ENDPOINT_LEN} bytes"
+            )
+        if stake < MIN_RELAYER_STAKE:
+            raise ValueError(f"Stake must be at least {MIN_RELAYER_STAKE} lamports")
+
+        entry, _entry_bump = find_relayer_pda(self.program_id, relayer)
+        accounts = [
+            AccountMeta(entry, is_signer=False, is_writable=True),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+
+        # Instruction data: discriminator + fee_bps + endpoint (String) + stake
+        data = (
+            self.REGISTER_RELAYER_DISC
+            + struct.pack("<H", fee_bps)
+            + struct.pack("<I", len(encoded))
+            + encoded
+            + struct.pack("<Q", stake)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def deregister_relayer(self, relayer: Pubkey) -> Instruction:
+        """Build deregister_relayer instruction, refunding stake and rent"""
+        entry, _entry_bump = find_relayer_pda(self.program_id, relayer)
+        accounts = [
+            AccountMeta(entry, is_signer=False, is_writable=True),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+        ]
+
+        return Instruction(self.program_id, self.DEREGISTER_RELAYER_DISC, accounts)
+
+    def shield_sol(
+        self,
+        depositor: Pubkey,
+        commitment: bytes,
+        amount: int,
+        encrypted_note: bytes = b"",
+        compressed_tree: Optional[Pubkey] = None,
+    ) -> Instruction:
+        """Build shield SOL instruction
+
+        `encrypted_note` (at most ENCRYPTED_NOTE_SIZE bytes) is published
+        with the commitment so its owner can find the note. Pools with a
+        compressed tree need it as `compressed_tree`.
+        """
+        if len(commitment) != 32:
+            raise ValueError("Commitment must be 32 bytes")
+
+        pool, _pool_bump = find_pool_pda(self.program_id)
+        vault, _vault_bump = find_vault_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(vault, is_signer=False, is_writable=True),
+            AccountMeta(depositor, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+        if compressed_tree is not None:
+            accounts += compressed_tree_accounts(compressed_tree)
+
+        # Instruction data: discriminator + commitment (32 bytes) + amount (u64)
+        # + encrypted note
+        data = (
+            self.SHIELD_SOL_DISC
+            + commitment
+            + struct.pack("<Q", amount)
+            + _encode_encrypted_note(encrypted_note)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def shield_spl(
+        self,
+        depositor: Pubkey,
+        depositor_token_account: Pubkey,
+        vault_token_account: Pubkey,
+        commitment: bytes,
+        amount: int,
+        mint: Pubkey,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+        encrypted_note: bytes = b"",
+        compressed_tree: Optional[Pubkey] = None,
+    ) -> Instruction:
+        """Build shield SPL token instruction into the mint's pool
+
+        `token_program` is the program owning `mint` (SPL Token or
+        Token-2022). The program rejects deposits on which a Token-2022
+        transfer fee is charged. Pools with a compressed tree need it as
+        `compressed_tree`.
+        """
+        if len(commitment) != 32:
+            raise ValueError("Commitment must be 32 bytes")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        vault_authority, _vault_bump = find_vault_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(vault_authority, is_signer=False, is_writable=False),
+            AccountMeta(mint, is_signer=False, is_writable=False),
+            AccountMeta(vault_token_account, is_signer=False, is_writable=True),
+            AccountMeta(depositor_token_account, is_signer=False, is_writable=True),
+            AccountMeta(depositor, is_signer=True, is_writable=True),
+            AccountMeta(token_program, is_signer=False, is_writable=False),
+        ]
+        if compressed_tree is not None:
+            accounts += compressed_tree_accounts(compressed_tree)
+
+        # Instruction data: discriminator + commitment (32 bytes) + amount (u64)
+        # + encrypted note
+        data = (
+            self.SHIELD_DISC
+            + commitment
+            + struct.pack("<Q", amount)
+            + _encode_encrypted_note(encrypted_note)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def transfer(
+        self,
+        relayer: Pubkey,
+        nullifier: bytes,
+        new_commitment: bytes,
+        proof: bytes,
+        mint: Pubkey = NATIVE_MINT,
+        encrypted_note: bytes = b"",
+        tree_epoch: int = 0,
+        extra_nullifiers: Tuple[bytes, ...] = (),
+        compressed_tree: Optional[Pubkey] = None,
+        root: bytes = bytes(32),
+        fee: int = 0,
+        vault_token_account: Optional[Pubkey] = None,
+        relayer_token_account: Optional[Pubkey] = None,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+    ) -> Instruction:
+        """Build private transfer instruction within the mint's pool
+
+        `encrypted_note` delivers the output note to its recipient.
+        `tree_epoch` is the epoch of the tree holding the spent note.
+        `extra_nullifiers` sweeps more notes from the same tree into the
+        output; the proof must then cover every input. Pools with a
+        compressed tree need it as `compressed_tree`. `root` is the tree root
+        the proof is against: one of the pool's recent roots, or the final
+        root of an archived tree.
+
+        `fee` is the part of the input the proof pays to the relayer, from
+        the vault. A token pool paying a fee needs `vault_token_account` and
+        `relayer_token_account`.
+        """
+        if len(nullifier) != 32:
+            raise ValueError("Nullifier must be 32 bytes")
+        if len(root) != 32:
+            raise ValueError("Root must be 32 bytes")
+        if len(new_commitment) != 32:
+            raise ValueError("New commitment must be 32 bytes")
+        if any(len(n) != 32 for n in extra_nullifiers):
+            raise ValueError("Nullifiers must be 32 bytes")
+        if len(extra_nullifiers) > MAX_EXTRA_NULLIFIERS:
+            raise ValueError(
+                f"At most {MAX_EXTRA_NULLIFIERS} extra nullifiers per transfer"
+            )
+        if len({nullifier, *extra_nullifiers}) != 1 + len(extra_nullifiers):
+            raise ValueError("Nullifiers must be distinct")
+        if not 0 <= fee < 2**64:
+            raise ValueError("Fee must fit in a u64")
+        if (vault_token_account is None) != (relayer_token_account is None):
+            raise ValueError("Token fee accounts must be given together")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        vault, _vault_bump = find_vault_pda(self.program_id, pool)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+        nullifier_marker, _null_bump = find_nullifier_pda(
+            self.program_id, pool, nullifier, tree_epoch
+        )
+        nullifier_archive, _archive_bump = find_nullifier_archive_pda(
+            self.program_id, pool, nullifier_archive_bucket(nullifier), tree_epoch
+        )
+        archived_root, _root_bump = find_archived_root_pda(
+            self.program_id, pool, tree_epoch
+        )
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=False),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
+            AccountMeta(nullifier_archive, is_signer=False, is_writable=False),
+            AccountMeta(archived_root, is_signer=False, is_writable=False),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(vault, is_signer=False, is_writable=True),
+        ]
+        if vault_token_account is not None:
+            accounts += [
+                AccountMeta(mint, is_signer=False, is_writable=False),
+                AccountMeta(vault_token_account, is_signer=False, is_writable=True),
+                AccountMeta(relayer_token_account, is_signer=False, is_writable=True),
+                AccountMeta(token_program, is_signer=False, is_writable=False),
+            ]
+        else:
+            # Anchor reads the program ID in place of an omitted optional account
+            accounts += [
+                AccountMeta(self.program_id, is_signer=False, is_writable=False)
+            ] * 4
+        # Remaining accounts: (marker, archive bucket) per extra nullifier
+        for extra in extra_nullifiers:
+            marker, _ = find_nullifier_pda(self.program_id, pool, extra, tree_epoch)
+            archive, _ = find_nullifier_archive_pda(
+                self.program_id, pool, nullifier_archive_bucket(extra), tree_epoch
+            )
+            accounts.append(AccountMeta(marker, is_signer=False, is_writable=True))
+            accounts.append(AccountMeta(archive, is_signer=False, is_writable=False))
+        if compressed_tree is not None:
+            accounts += compressed_tree_accounts(compressed_tree)
+
+        # Instruction data: discriminator + tree epoch + root + nullifier
+        # + new_commitment + fee + proof + encrypted note + extra nullifiers.
+        # Variable-length fields are preceded by a 4-byte length
+        data = (
+            self.TRANSFER_DISC
+            + struct.pack("<I", tree_epoch)
+            + root
+            + nullifier
+            + new_commitment
+            + struct.pack("<Q", fee)
+            + struct.pack("<I", len(proof))
+            + proof
+            + _encode_encrypted_note(encrypted_note)
+            + struct.pack("<I", len(extra_nullifiers))
+            + b"".join(extra_nullifiers)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def transfer_joinsplit(
+        self,
+        relayer: Pubkey,
+        nullifiers: Tuple[bytes, bytes],
+        new_commitments: Tuple[bytes, bytes],
+        proof: bytes,
+        mint: Pubkey = NATIVE_MINT,
+        encrypted_notes: Tuple[bytes, ...] = (),
+        tree_epoch: int = 0,
+        compressed_tree: Optional[Pubkey] = None,
+        root: bytes = bytes(32),
+    ) -> Instruction:
+        """Build join-split transfer instruction (2 inputs, 2 outputs)
+
+        `encrypted_notes` holds up to one encrypted note per output, in
+        output order. Both inputs must be from tree `tree_epoch`. Pools with
+        a compressed tree need it as `compressed_tree`. `root` is as in
+        `transfer`.
+        """
+        if len(root) != 32:
+            raise ValueError("Root must be 32 bytes")
+        if len(nullifiers) != 2 or any(len(n) != 32 for n in nullifiers):
+            raise ValueError("Expected two 32-byte nullifiers")
+        if nullifiers[0] == nullifiers[1]:
+            raise ValueError("Nullifiers must be distinct")
+        if len(new_commitments) != 2 or any(len(c) != 32 for c in new_commitments):
+            raise ValueError("Expected two 32-byte commitments")
+        if len(encrypted_notes) > 2:
+            raise ValueError("At most one encrypted note per output")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+        markers = [
+            find_nullifier_pda(self.program_id, pool, nullifier, tree_epoch)[0]
+            for nullifier in nullifiers
+        ]
+        archives = [
+            find_nullifier_archive_pda(
+                self.program_id, pool, nullifier_archive_bucket(nullifier), tree_epoch
+            )[0]
+            for nullifier in nullifiers
+        ]
+        archived_root, _root_bump = find_archived_root_pda(
+            self.program_id, pool, tree_epoch
+        )
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=False),
+            AccountMeta(root_history, is_signer=False, is_writable=True),
+            AccountMeta(markers[0], is_signer=False, is_writable=True),
+            AccountMeta(markers[1], is_signer=False, is_writable=True),
+            AccountMeta(archives[0], is_signer=False, is_writable=False),
+            AccountMeta(archives[1], is_signer=False, is_writable=False),
+            AccountMeta(archived_root, is_signer=False, is_writable=False),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ]
+        if compressed_tree is not None:
+            accounts += compressed_tree_accounts(compressed_tree)
+
+        # Instruction data: discriminator + tree epoch + root + nullifiers
+        # + new_commitments + proof + encrypted notes (Vec<Vec<u8>>)
+        data = (
+            self.TRANSFER_JOINSPLIT_DISC
+            + struct.pack("<I", tree_epoch)
+            + root
+            + b"".join(nullifiers)
+            + b"".join(new_commitments)
+            + struct.pack("<I", len(proof))
+            + proof
+            + struct.pack("<I", len(encrypted_notes))
+            + b"".join(_encode_encrypted_note(note) for note in encrypted_notes)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def _memo_program(self, memo: bytes) -> AccountMeta:
+        """Memo program account, or the program ID in its place without a memo"""
+        return AccountMeta(
+            MEMO_PROGRAM_ID if memo else self.program_id, is_signer=False, is_writable=False
+        )
+
+    def unshield_sol(
+        self,
+        relayer: Pubkey,
+        recipient: Pubkey,
+        nullifier: bytes,
+        amount: int,
+        proof: bytes,
+        tree_epoch: int = 0,
+        memo: bytes = b"",
+        root: bytes = bytes(32),
+    ) -> Instruction:
+        """Build unshield SOL instruction for a note in tree `tree_epoch`
+
+        A non-empty UTF-8 `memo`, such as an exchange deposit reference, is
+        logged through the Memo program. `root` is as in `transfer`.
+        """
+        if len(nullifier) != 32:
+            raise ValueError("Nullifier must be 32 bytes")
+        if len(root) != 32:
+            raise ValueError("Root must be 32 bytes")
+
+        pool, _pool_bump = find_pool_pda(self.program_id)
+        vault, _vault_bump = find_vault_pda(self.program_id, pool)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+        nullifier_marker, _null_bump = find_nullifier_pda(
+            self.program_id, pool, nullifier, tree_epoch
+        )
+        nullifier_archive, _archive_bump = find_nullifier_archive_pda(
+            self.program_id, pool, nullifier_archive_bucket(nullifier), tree_epoch
+        )
+        archived_root, _root_bump = find_archived_root_pda(
+            self.program_id, pool, tree_epoch
+        )
+        pending_withdrawal, _pending_bump = find_pending_withdrawal_pda(
+            self.program_id, pool, nullifier, tree_epoch
+        )
+        treasury, _treasury_bump = find_treasury_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=False),
+            AccountMeta(root_history, is_signer=False, is_writable=False),
+            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
+            AccountMeta(nullifier_archive, is_signer=False, is_writable=False),
+            AccountMeta(archived_root, is_signer=False, is_writable=False),
+            AccountMeta(pending_withdrawal, is_signer=False, is_writable=True),
+            AccountMeta(vault, is_signer=False, is_writable=True),
+            AccountMeta(treasury, is_signer=False, is_writable=True),
+            AccountMeta(recipient, is_signer=False, is_writable=True),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+            self._memo_program(memo),
+        ]
+
+        # Instruction data: discriminator + tree epoch + root + nullifier
+        # + amount + proof + memo
+        data = (
+            self.UNSHIELD_SOL_DISC
+            + struct.pack("<I", tree_epoch)
+            + root
+            + nullifier
+            + struct.pack("<Q", amount)
+            + struct.pack("<I", len(proof))
+            + proof
+            + _encode_memo(memo)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def unshield_spl(
+        self,
+        relayer: Pubkey,
+        recipient_token_account: Pubkey,
+        vault_token_account: Pubkey,
+        nullifier: bytes,
+        amount: int,
+        proof: bytes,
+        mint: Pubkey,
+        relayer_token_account: Pubkey,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+        tree_epoch: int = 0,
+        treasury_token_account: Optional[Pubkey] = None,
+        memo: bytes = b"",
+        root: bytes = bytes(32),
+    ) -> Instruction:
+        """Build unshield SPL token instruction from the mint's pool
+
+        The relayer fee is paid to `relayer_token_account`. `token_program`
+        is the program owning `mint` (SPL Token or Token-2022). `tree_epoch`
+        is the epoch of the tree holding the spent note. Pools taking a
+        protocol fee need the treasury's `treasury_token_account`. `memo` and
+        `root` are as in `unshield_sol`.
+        """
+        if len(nullifier) != 32:
+            raise ValueError("Nullifier must be 32 bytes")
+        if len(root) != 32:
+            raise ValueError("Root must be 32 bytes")
+
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        vault_authority, _vault_bump = find_vault_pda(self.program_id, pool)
+        verifying_key, _vk_bump = find_verifying_key_pda(self.program_id, pool)
+        root_history, _history_bump = find_root_history_pda(self.program_id, pool)
+        nullifier_marker, _null_bump = find_nullifier_pda(
+            self.program_id, pool, nullifier, tree_epoch
+        )
+        nullifier_archive, _archive_bump = find_nullifier_archive_pda(
+            self.program_id, pool, nullifier_archive_bucket(nullifier), tree_epoch
+        )
+        archived_root, _root_bump = find_archived_root_pda(
+            self.program_id, pool, tree_epoch
+        )
+        pending_withdrawal, _pending_bump = find_pending_withdrawal_pda(
+            self.program_id, pool, nullifier, tree_epoch
+        )
+        treasury, _treasury_bump = find_treasury_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=True),
+            AccountMeta(verifying_key, is_signer=False, is_writable=False),
+            AccountMeta(root_history, is_signer=False, is_writable=False),
+            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
+            AccountMeta(nullifier_archive, is_signer=False, is_writable=False),
+            AccountMeta(archived_root, is_signer=False, is_writable=False),
+            AccountMeta(pending_withdrawal, is_signer=False, is_writable=True),
+            AccountMeta(vault_authority, is_signer=False, is_writable=False),
+            AccountMeta(mint, is_signer=False, is_writable=False),
+            AccountMeta(vault_token_account, is_signer=False, is_writable=True),
+            AccountMeta(recipient_token_account, is_signer=False, is_writable=True),
+            AccountMeta(relayer_token_account, is_signer=False, is_writable=True),
+            AccountMeta(treasury, is_signer=False, is_writable=False),
+            # Anchor reads the program ID in place of an omitted optional account
+            AccountMeta(
+                treasury_token_account or self.program_id,
+                is_signer=False,
+                is_writable=treasury_token_account is not None,
+            ),
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(token_program, is_signer=False, is_writable=False),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+            self._memo_program(memo),
+        ]
+
+        # Instruction data: discriminator + tree epoch + root + nullifier
+        # + amount + proof + memo
+        data = (
+            self.UNSHIELD_DISC
+            + struct.pack("<I", tree_epoch)
+            + root
+            + nullifier
+            + struct.pack("<Q", amount)
+            + struct.pack("<I", len(proof))
+            + proof
+            + _encode_memo(memo)
+        )
+
+        return Instruction(self.program_id, data, accounts)
+
+    def unshield_spl_with_ata(
+        self,
+        relayer: Pubkey,
+        recipient: Pubkey,
+        vault_token_account: Pubkey,
+        nullifier: bytes,
+        amount: int,
+        proof: bytes,
+        mint: Pubkey,
+        relayer_token_account: Pubkey,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+        tree_epoch: int = 0,
+        treasury_token_account: Optional[Pubkey] = None,
+        memo: bytes = b"",
+        root: bytes = bytes(32),
+    ) -> Instruction:
+        """Build unshield_with_ata instruction paying `recipient`'s associated
+        token account, which the relayer creates if it does not exist yet
+
+        Otherwise the same as `unshield_spl`.
+        """
+        recipient_token_account, _ = Pubkey.find_program_address(
+            [bytes(recipient), bytes(token_program), bytes(mint)],
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+        )
+        unshield = self.unshield_spl(
+            relayer,
+            recipient_token_account,
+            vault_token_account,
+            nullifier,
+            amount,
+            proof,
+            mint,
+            relayer_token_account,
+            token_program,
+            tree_epoch,
+            treasury_token_account,
+            memo,
+            root,
+        )
+
+        # The ATA creation accounts come first, then the unshield's own
+        accounts = [
+            AccountMeta(relayer, is_signer=True, is_writable=True),
+            AccountMeta(recipient, is_signer=False, is_writable=False),
+            AccountMeta(mint, is_signer=False, is_writable=False),
+            AccountMeta(recipient_token_account, is_signer=False, is_writable=True),
+            AccountMeta(token_program, is_signer=False, is_writable=False),
+            AccountMeta(ASSOCIATED_TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
+        ] + list(unshield.accounts)
+
+        data = self.UNSHIELD_WITH_ATA_DISC + bytes(unshield.data)[8:]
+
+        return Instruction(self.program_id, data, accounts)
+
+    def finalize_withdrawal_sol(
+        self,
+        pending_withdrawal: Pubkey,
+        recipient: Pubkey,
+        fee_recipient: Pubkey,
+        rent_payer: Pubkey,
+    ) -> Instruction:
+        """Build finalize_withdrawal_sol instruction paying out a delayed unshield
+
+        The accounts must match those recorded in the pending withdrawal.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id)
+        vault, _vault_bump = find_vault_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(pending_withdrawal, is_signer=False, is_writable=True),
+            AccountMeta(vault, is_signer=False, is_writable=True),
+            AccountMeta(recipient, is_signer=False, is_writable=True),
+            AccountMeta(fee_recipient, is_signer=False, is_writable=True),
+            AccountMeta(rent_payer, is_signer=False, is_writable=True),
+        ]
+
+        return Instruction(self.program_id, self.FINALIZE_WITHDRAWAL_SOL_DISC, accounts)
+
+    def finalize_withdrawal_spl(
+        self,
+        pending_withdrawal: Pubkey,
+        mint: Pubkey,
+        vault_token_account: Pubkey,
+        recipient_token_account: Pubkey,
+        fee_token_account: Pubkey,
+        rent_payer: Pubkey,
+        token_program: Pubkey = TOKEN_PROGRAM_ID,
+    ) -> Instruction:
+        """Build finalize_withdrawal instruction paying out a delayed SPL unshield
+
+        The token accounts must match those recorded in the pending withdrawal.
+        """
+        pool, _pool_bump = find_pool_pda(self.program_id, mint)
+        vault_authority, _vault_bump = find_vault_pda(self.program_id, pool)
+
+        accounts = [
+            AccountMeta(pool, is_signer=False, is_writable=False),
+            AccountMeta(pending_withdrawal, is_signer=False, is_writable=True),
+            AccountMeta(vault_authority, is_signer=False, is_writable=False),
+            AccountMeta(mint, is_signer=False, is_writable=False),
+            AccountMeta(vault_token_account, is_signer=False, is_writable=True),
+            AccountMeta(recipient_token_account, is_signer=False, is_writable=True),
+            AccountMeta(fee_token_account, is_signer=False, is_writable=True),
+            AccountMeta(rent_payer, is_signer=False, is_writable=True),
+            AccountMeta(token_program, is_signer=False, is_writable=False),
+        ]
+
+        return Instruction(self.program_id, self.FINALIZE_WITHDRAWAL_DISC, accounts)
+
+
+class SolanaClient:
+    """
+    Low-level Solana client for privacy operations
+
+    Handles direct blockchain interaction including:
+    - Submitting transactions
+    - Reading account state
+    - Interacting with privacy program
+    """
+
+    def __init__(
+        self,
+        rpc_url: str = "https://api.devnet.solana.com",
+        program_id: Optional[str] = None,
+    ):
+        """
+        Initialize Solana client
+
+        Args:
+            rpc_url: Solana RPC endpoint
+            program_id: Privacy program ID
+        """
+        self.rpc_url = rpc_url
+        self.client = AsyncClient(rpc_url)
+        self.program_id = Pubkey.from_string(program_id or DEFAULT_PROGRAM_ID)
+        self.instruction_builder = InstructionBuilder(self.program_id)
+        self.pool_pda, _ = find_pool_pda(self.program_id)
+
+    def _mint_for(self, token: str) -> Pubkey:
+        """Mint keying the pool for `token` ("SOL" for native SOL)"""
+        if token.upper() == "SOL":
+            return NATIVE_MINT
+        return Pubkey.from_string(token)
+
+    def pool_for(self, token: str = "SOL") -> Pubkey:
+        """Pool PDA for `token` ("SOL" or a mint address)"""
+        if token.upper() == "SOL":
+            return self.pool_pda
+        return find_pool_pda(self.program_id, self._mint_for(token))[0]
+
+    async def get_recent_blockhash(self) -> Hash:
+        """Get recent blockhash for transaction"""
+        response = await self.client.get_latest_blockhash(commitment=Confirmed)
+        return response.value.blockhash
+
+    async def send_transaction(
+        self, instruction: Instruction, payer: Keypair
+    ) -> str:
+        """Send a single instruction as a transaction"""
+        blockhash = await self.get_recent_blockhash()
+
+        message = Message.new_with_blockhash(
+            [instruction], payer.pubkey(), blockhash
+        )
+        tx = Transaction.new_unsigned(message)
+        tx.sign([payer], blockhash)
+
+        response = await self.client.send_transaction(tx, commitment=Confirmed)
+        return str(response.value)
+
+    async def get_pool_state(self, token: str = "SOL") -> Optional[dict]:
+        """
+        Get privacy pool state from blockchain
+
+        Args:
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Pool state dict if exists, None otherwise
+        """
+        response = await self.client.get_account_info(
+            self.pool_for(token), commitment=Confirmed
+        )
+        if response.value is None:
+            return None
+
+        data = bytes(response.value.data)
+        if len(data) < 8:  # Account discriminator
+            return None
+
+        # Skip 8-byte discriminator
+        data = data[8:]
+
+        # Parse pool state (simplified): authority, pending authority, mint,
+        # then the Merkle tree (next_index u64, 20 filled subtrees, current root)
+        root_offset = 32 + 32 + 32 + 8 + 20 * 32
+        return {
+            "authority": Pubkey.from_bytes(data[0:32]),
+            "pending_authority": Pubkey.from_bytes(data[32:64]),
+            "mint": Pubkey.from_bytes(data[64:96]),
+            "merkle_root": data[root_offset : root_offset + 32],
+            "tree_epoch": struct.unpack("<I", data[root_offset + 32 : root_offset + 36])[0],
+            "nullifier_count": struct.unpack("<Q", data[-92:-84])[0],
+            "relayer_fee_bps": struct.unpack("<H", data[-84:-82])[0],
+            "protocol_fee_bps": struct.unpack("<H", data[-64:-62])[0],
+            "zk_enabled": data[-62] != 0,
+            "paused": data[-61],
+            "nullifier_finality_epochs": struct.unpack("<H", data[-60:-58])[0],
+            "withdrawal_delay_slots": struct.unpack("<Q", data[-58:-50])[0],
+            "max_deposit_per_tx": struct.unpack("<Q", data[-50:-42])[0],
+            "max_pool_tvl": struct.unpack("<Q", data[-42:-34])[0],
+            # Set only for pools appending to a compressed tree
+            "compressed_tree": Pubkey.from_bytes(data[-34:-2]) if data[-2] else None,
+        }
+
+    async def _compressed_tree(self, token: str) -> Optional[Pubkey]:
+        """Compressed tree of the token's pool, if it uses one"""
+        state = await self.get_pool_state(token)
+        return state["compressed_tree"] if state is not None else None
+
+    async def get_pending_withdrawal(self, address: Pubkey) -> Optional[dict]:
+        """
+        Get a queued withdrawal from blockchain
+
+        Args:
+            address: Pending withdrawal PDA
+
+        Returns:
+            Pending withdrawal dict if it exists, None otherwise
+        """
+        response = await self.client.get_account_info(address, commitment=Confirmed)
+        if response.value is None:
+            return None
+
+        # Skip 8-byte discriminator; pool, recipient, fee recipient, rent
+        # payer, then payout, fee and unlock slot
+        data = bytes(response.value.data)[8:]
+        if len(data) < 4 * 32 + 24:
+            return None
+        payout, fee, unlock_slot = struct.unpack("<QQQ", data[128:152])
+        return {
+            "pool": Pubkey.from_bytes(data[0:32]),
+            "recipient": Pubkey.from_bytes(data[32:64]),
+            "fee_recipient": Pubkey.from_bytes(data[64:96]),
+            "rent_payer": Pubkey.from_bytes(data[96:128]),
+            "payout": payout,
+            "fee": fee,
+            "unlock_slot": unlock_slot,
+        }
+
+    async def get_merkle_root(self, token: str = "SOL") -> bytes:
+        """
+        Get current Merkle root from on-chain state
+
+        Args:
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Merkle root bytes (32 bytes)
+        """
+        state = await self.get_pool_state(token)
+        if state is None:
+            return bytes(32)
+        return state.get("merkle_root", bytes(32))
+
+    async def is_nullifier_spent(
+        self, nullifier: bytes, token: str = "SOL", tree_epoch: int = 0
+    ) -> bool:
+        """
+        Check if nullifier has been spent
+
+        Args:
+            nullifier: Nullifier bytes (32 bytes)
+            token: Token mint address ("SOL" for native SOL)
+            tree_epoch: Epoch of the tree holding the note
+
+        Returns:
+            True if spent (marker PDA exists, or the nullifier was compacted
+            into its archive bucket)
+        """
+        pool = self.pool_for(token)
+        nullifier_pda, _ = find_nullifier_pda(self.program_id, pool, nullifier, tree_epoch)
+        response = await self.client.get_account_info(
+            nullifier_pda, commitment=Confirmed
+        )
+        if response.value is not None:
+            return True
+
+        archive_pda, _ = find_nullifier_archive_pda(
+            self.program_id, pool, nullifier_archive_bucket(nullifier), tree_epoch
+        )
+        response = await self.client.get_account_info(archive_pda, commitment=Confirmed)
+        if response.value is None:
+            return False
+        data = bytes(response.value.data)
+        count = struct.unpack("<I", data[ARCHIVE_ENTRIES_OFFSET - 4 : ARCHIVE_ENTRIES_OFFSET])[0]
+        entries = data[ARCHIVE_ENTRIES_OFFSET : ARCHIVE_ENTRIES_OFFSET + 32 * count]
+        return any(entries[i : i + 32] == nullifier for i in range(0, len(entries), 32))
+
+    async def initialize_pool(
+        self, authority: Keypair, root_history_size: int = DEFAULT_ROOT_HISTORY_SIZE
+    ) -> str:
+        """
+        Initialize the privacy pool
+
+        Args:
+            authority: Pool authority keypair
+            root_history_size: Recent roots to accept proofs against
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.initialize(
+            authority.pubkey(), root_history_size
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def initialize_pool_for_mint(
+        self,
+        authority: Keypair,
+        mint: str,
+        root_history_size: int = DEFAULT_ROOT_HISTORY_SIZE,
+    ) -> str:
+        """
+        Initialize the privacy pool for an SPL token mint
+
+        Args:
+            authority: Pool authority keypair
+            mint: Token mint address
+            root_history_size: Recent roots to accept proofs against
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.initialize_pool_for_mint(
+            authority.pubkey(), Pubkey.from_string(mint), root_history_size
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def resize_root_history(
+        self, authority: Keypair, size: int, token: str = "SOL"
+    ) -> str:
+        """
+        Change how many recent roots the pool accepts proofs against
+
+        Args:
+            authority: Pool authority keypair, which pays for a larger history
+            size: New number of roots kept
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.resize_root_history(
+            authority.pubkey(), size, self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def set_verifying_key(
+        self,
+        authority: Keypair,
+        alpha_g1: bytes,
+        beta_g2: bytes,
+        gamma_g2: bytes,
+        delta_g2: bytes,
+        ic: list,
+        lock: bool = False,
+        token: str = "SOL",
+    ) -> str:
+        """
+        Set the Groth16 verifying key of a pool
+
+        Args:
+            authority: Pool authority keypair
+            alpha_g1, beta_g2, gamma_g2, delta_g2, ic: Key points
+                (groth16-solana encoding)
+            lock: Make the key permanent
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_verifying_key(
+            authority.pubkey(),
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            ic,
+            lock=lock,
+            mint=self._mint_for(token),
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def set_unshield_verifying_key(
+        self,
+        authority: Keypair,
+        alpha_g1: bytes,
+        beta_g2: bytes,
+        gamma_g2: bytes,
+        delta_g2: bytes,
+        ic: list,
+        lock: bool = False,
+        token: str = "SOL",
+    ) -> str:
+        """
+        Set the unshield circuit's verifying key of a pool
+
+        Args:
+            authority: Pool authority keypair
+            alpha_g1, beta_g2, gamma_g2, delta_g2, ic: Key points
+                (groth16-solana encoding, UNSHIELD_NUM_IC IC points)
+            lock: Make both of the pool's keys permanent
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_unshield_verifying_key(
+            authority.pubkey(),
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            ic,
+            lock=lock,
+            mint=self._mint_for(token),
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def enable_zk(self, authority: Keypair, token: str = "SOL") -> str:
+        """
+        Start accepting Groth16 proofs in a pool
+
+        Args:
+            authority: Pool authority keypair
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.enable_zk(
+            authority.pubkey(), self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, authority)
+
+    async def set_pause_state(
+        self, authority: Keypair, paused: int, token: str = "SOL"
+    ) -> str:
+        """
+        Pause or resume operations in a pool
+
+        Args:
+            authority: Pool authority keypair
+            paused: Bitmask of PAUSE_* flags (0 resumes everything)
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_pause_state(
+            authority.pubkey(), paused, self._mint_for(token)
         )
+        return await self.send_transaction(instruction, authority)
 
-        return Instruction(self.program_id, data, accounts)
+    async def propose_authority(
+        self, authority: Keypair, new_authority: str, token: str = "SOL"
+    ) -> str:
+        """
+        Propose a new pool authority (step one of two)
 
-    def unshield_sol(
-        self,
-        relayer: Pubkey,
-        recipient: Pubkey,
-        nullifier: bytes,
-        amount: int,
-        proof: bytes,
-    ) -> Instruction:
-        """Build unshield SOL instruction"""
-        if len(nullifier) != 32:
-            raise ValueError("Nullifier must be 32 bytes")
+        Args:
+            authority: Current pool authority keypair
+            new_authority: Proposed authority address
+            token: Token mint address ("SOL" for native SOL)
 
-        pool, _pool_bump = find_pool_pda(self.program_id)
-        vault, _vault_bump = find_vault_pda(self.program_id, pool)
-        nullifier_marker, _null_bump = find_nullifier_pda(
-            self.program_id, pool, nullifier
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.propose_authority(
+            authority.pubkey(), Pubkey.from_string(new_authority), self._mint_for(token)
         )
+        return await self.send_transaction(instruction, authority)
 
-        accounts = [
-            AccountMeta(pool, is_signer=False, is_writable=True),
-            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
-            AccountMeta(vault, is_signer=False, is_writable=True),
-            AccountMeta(recipient, is_signer=False, is_writable=True),
-            AccountMeta(relayer, is_signer=True, is_writable=True),
-            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
-        ]
+    async def accept_authority(self, new_authority: Keypair, token: str = "SOL") -> str:
+        """
+        Accept a proposed pool authority (step two of two)
 
-        # Instruction data: discriminator + nullifier + amount + proof
-        data = (
-            self.UNSHIELD_SOL_DISC
-            + nullifier
-            + struct.pack("<Q", amount)
-            + struct.pack("<I", len(proof))
-            + proof
-        )
+        Args:
+            new_authority: Proposed authority keypair
+            token: Token mint address ("SOL" for native SOL)
 
-        return Instruction(self.program_id, data, accounts)
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.accept_authority(
+            new_authority.pubkey(), self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, new_authority)
 
-    def unshield_spl(
+    async def update_relayer_fee(
         self,
-        relayer: Pubkey,
-        recipient_token_account: Pubkey,
-        vault_token_account: Pubkey,
-        nullifier: bytes,
-        amount: int,
-        proof: bytes,
-    ) -> Instruction:
-        """Build unshield SPL token instruction"""
-        if len(nullifier) != 32:
-            raise ValueError("Nullifier must be 32 bytes")
-
-        pool, _pool_bump = find_pool_pda(self.program_id)
-        vault_authority, _vault_bump = find_vault_pda(self.program_id, pool)
-        nullifier_marker, _null_bump = find_nullifier_pda(
-            self.program_id, pool, nullifier
-        )
+        authority: Keypair,
+        new_fee_bps: int,
+        delay_slots: int = 0,
+        token: str = "SOL",
+    ) -> str:
+        """
+        Change a pool's relayer fee
 
-        accounts = [
-            AccountMeta(pool, is_signer=False, is_writable=True),
-            AccountMeta(nullifier_marker, is_signer=False, is_writable=True),
-            AccountMeta(vault_authority, is_signer=False, is_writable=False),
-            AccountMeta(vault_token_account, is_signer=False, is_writable=True),
-            AccountMeta(recipient_token_account, is_signer=False, is_writable=True),
-            AccountMeta(relayer, is_signer=True, is_writable=True),
-            AccountMeta(TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
-            AccountMeta(SYSTEM_PROGRAM_ID, is_signer=False, is_writable=False),
-        ]
+        Args:
+            authority: Pool authority keypair
+            new_fee_bps: New fee in basis points (at most MAX_RELAYER_FEE_BPS)
+            delay_slots: Slots before the new fee applies (0: immediately)
+            token: Token mint address ("SOL" for native SOL)
 
-        # Instruction data: discriminator + nullifier + amount + proof
-        data = (
-            self.UNSHIELD_DISC
-            + nullifier
-            + struct.pack("<Q", amount)
-            + struct.pack("<I", len(proof))
-            + proof
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.update_relayer_fee(
+            authority.pubkey(), new_fee_bps, delay_slots, self._mint_for(token)
         )
+        return await self.send_transaction(instruction, authority)
 
-        return Instruction(self.program_id, data, accounts)
-
+    async def set_nullifier_finality(
+        self, authority: Keypair, epochs: int, token: str = "SOL"
+    ) -> str:
+        """
+        Change how many epochs nullifier markers age before compaction
 
-class SolanaClient:
-    """
-    Low-level Solana client for privacy operations
+        Args:
+            authority: Pool authority keypair
+            epochs: Finality window in epochs (at least 1)
+            token: Token mint address ("SOL" for native SOL)
 
-    Handles direct blockchain interaction including:
-    - Submitting transactions
-    - Reading account state
-    - Interacting with privacy program
-    """
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_nullifier_finality(
+            authority.pubkey(), epochs, self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, authority)
 
-    def __init__(
+    async def set_deposit_caps(
         self,
-        rpc_url: str = "https://api.devnet.solana.com",
-        program_id: Optional[str] = None,
-    ):
+        authority: Keypair,
+        max_deposit_per_tx: int,
+        max_pool_tvl: int,
+        token: str = "SOL",
+    ) -> str:
         """
-        Initialize Solana client
+        Cap single shields and the pool's total balance
 
         Args:
-            rpc_url: Solana RPC endpoint
-            program_id: Privacy program ID
-        """
-        self.rpc_url = rpc_url
-        self.client = AsyncClient(rpc_url)
-        self.program_id = Pubkey.from_string(program_id or DEFAULT_PROGRAM_ID)
-        self.instruction_builder = InstructionBuilder(self.program_id)
-        self.pool_pda, _ = find_pool_pda(self.program_id)
+            authority: Pool authority keypair
+            max_deposit_per_tx: Largest single deposit (0 for no cap)
+            max_pool_tvl: Largest vault balance (0 for no cap)
+            token: Token mint address ("SOL" for native SOL)
 
-    async def get_recent_blockhash(self) -> Hash:
-        """Get recent blockhash for transaction"""
-        response = await self.client.get_latest_blockhash(commitment=Confirmed)
-        return response.value.blockhash
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_deposit_caps(
+            authority.pubkey(), max_deposit_per_tx, max_pool_tvl, self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, authority)
 
-    async def send_transaction(
-        self, instruction: Instruction, payer: Keypair
+    async def set_withdrawal_delay(
+        self, authority: Keypair, delay_slots: int, token: str = "SOL"
     ) -> str:
-        """Send a single instruction as a transaction"""
-        blockhash = await self.get_recent_blockhash()
+        """
+        Change how long unshields wait before they can be paid out
 
-        message = Message.new_with_blockhash(
-            [instruction], payer.pubkey(), blockhash
-        )
-        tx = Transaction.new_unsigned(message)
-        tx.sign([payer], blockhash)
+        Args:
+            authority: Pool authority keypair
+            delay_slots: Delay in slots (0 pays out immediately)
+            token: Token mint address ("SOL" for native SOL)
 
-        response = await self.client.send_transaction(tx, commitment=Confirmed)
-        return str(response.value)
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.set_withdrawal_delay(
+            authority.pubkey(), delay_slots, self._mint_for(token)
+        )
+        return await self.send_transaction(instruction, authority)
 
-    async def get_pool_state(self) -> Optional[dict]:
+    async def set_protocol_fee(
+        self, authority: Keypair, fee_bps: int, token: str = "SOL"
+    ) -> str:
         """
-        Get privacy pool state from blockchain
+        Route a share of each relayer fee to the pool treasury
+
+        Args:
+            authority: Pool authority keypair
+            fee_bps: Treasury's share, in basis points of the relayer fee
+            token: Token mint address ("SOL" for native SOL)
 
         Returns:
-            Pool state dict if exists, None otherwise
+            Transaction signature
         """
-        response = await self.client.get_account_info(
-            self.pool_pda, commitment=Confirmed
+        instruction = self.instruction_builder.set_protocol_fee(
+            authority.pubkey(), fee_bps, self._mint_for(token)
         )
-        if response.value is None:
-            return None
+        return await self.send_transaction(instruction, authority)
 
-        data = bytes(response.value.data)
-        if len(data) < 8:  # Account discriminator
-            return None
+    async def collect_fees(
+        self, authority: Keypair, destination: str, token: str = "SOL"
+    ) -> str:
+        """
+        Sweep the pool treasury to `destination`
 
-        # Skip 8-byte discriminator
-        data = data[8:]
+        For SPL pools the treasury's token balance goes to the destination's
+        associated token account, created if needed.
 
-        # Parse pool state (simplified)
-        return {
-            "authority": Pubkey.from_bytes(data[0:32]),
-            "merkle_root": data[32 + 8 + 680 : 32 + 8 + 680 + 32],  # After tree
-            "nullifier_count": struct.unpack("<Q", data[-9:-1])[0],
-        }
+        Args:
+            authority: Pool authority keypair
+            destination: Receiving wallet (base58 pubkey)
+            token: Token mint address ("SOL" for native SOL)
 
-    async def get_merkle_root(self) -> bytes:
+        Returns:
+            Transaction signature
         """
-        Get current Merkle root from on-chain state
+        receiver = Pubkey.from_string(destination)
+        mint = self._mint_for(token)
+
+        if token.upper() == "SOL":
+            instruction = self.instruction_builder.collect_fees(
+                authority.pubkey(), receiver, mint
+            )
+        else:
+            token_program = await get_mint_token_program(self.client, mint)
+            treasury, _ = find_treasury_pda(self.program_id, self.pool_for(token))
+            treasury_ata = await get_associated_token_address(treasury, mint, token_program)
+            destination_ata = await get_or_create_ata(
+                self.client, receiver, mint, authority, token_program=token_program
+            )
+            instruction = self.instruction_builder.collect_fees(
+                authority.pubkey(),
+                receiver,
+                mint,
+                treasury_ata,
+                destination_ata,
+                token_program,
+            )
+
+        return await self.send_transaction(instruction, authority)
+
+    async def compact_nullifiers(
+        self,
+        authority: Keypair,
+        bucket: int,
+        nullifiers: List[bytes],
+        token: str = "SOL",
+        tree_epoch: int = 0,
+    ) -> str:
+        """
+        Archive final nullifiers in a bucket and reclaim their marker rent
+
+        Args:
+            authority: Pool authority keypair (pays for a new bucket)
+            bucket: Archive bucket (last byte of every nullifier)
+            nullifiers: Spent nullifiers older than the finality window
+            token: Token mint address ("SOL" for native SOL)
+            tree_epoch: Epoch of the tree the nullifiers' notes were in
 
         Returns:
-            Merkle root bytes (32 bytes)
+            Transaction signature
         """
-        state = await self.get_pool_state()
-        if state is None:
-            return bytes(32)
-        return state.get("merkle_root", bytes(32))
+        instruction = self.instruction_builder.compact_nullifiers(
+            authority.pubkey(), bucket, nullifiers, self._mint_for(token), tree_epoch
+        )
+        return await self.send_transaction(instruction, authority)
 
-    async def is_nullifier_spent(self, nullifier: bytes) -> bool:
+    async def rotate_tree(self, payer: Keypair, token: str = "SOL") -> str:
         """
-        Check if nullifier has been spent
+        Archive a full tree's root and start a fresh tree
 
         Args:
-            nullifier: Nullifier bytes (32 bytes)
+            payer: Pays for the archived root account
+            token: Token mint address ("SOL" for native SOL)
 
         Returns:
-            True if spent (PDA exists)
+            Transaction signature
         """
-        nullifier_pda, _ = find_nullifier_pda(
-            self.program_id, self.pool_pda, nullifier
+        state = await self.get_pool_state(token)
+        if state is None:
+            raise ValueError("Pool not initialized")
+        instruction = self.instruction_builder.rotate_tree(
+            payer.pubkey(), state["tree_epoch"], self._mint_for(token)
         )
-        response = await self.client.get_account_info(
-            nullifier_pda, commitment=Confirmed
+        return await self.send_transaction(instruction, payer)
+
+    async def register_relayer(
+        self,
+        relayer: Keypair,
+        fee_bps: int,
+        endpoint: str,
+        stake: int = MIN_RELAYER_STAKE,
+    ) -> str:
+        """
+        Join the on-chain relayer registry
+
+        Args:
+            relayer: Relayer keypair; pays rent and the stake
+            fee_bps: Advertised fee in basis points
+            endpoint: Relayer API endpoint (https:// URL)
+            stake: Lamports to stake (at least MIN_RELAYER_STAKE)
+
+        Returns:
+            Transaction signature
+        """
+        instruction = self.instruction_builder.register_relayer(
+            relayer.pubkey(), fee_bps, endpoint, stake
         )
-        return response.value is not None
+        return await self.send_transaction(instruction, relayer)
 
-    async def initialize_pool(self, authority: Keypair) -> str:
+    async def deregister_relayer(self, relayer: Keypair) -> str:
         """
-        Initialize the privacy pool
+        Leave the relayer registry, withdrawing the stake
 
         Args:
-            authority: Pool authority keypair
+            relayer: Registered relayer keypair
 
         Returns:
             Transaction signature
         """
-        instruction = self.instruction_builder.initialize(authority.pubkey())
-        return await self.send_transaction(instruction, authority)
+        instruction = self.instruction_builder.deregister_relayer(relayer.pubkey())
+        return await self.send_transaction(instruction, relayer)
 
     async def submit_shield_transaction(
         self,
@@ -377,15 +1993,20 @@ class SolanaClient:
         amount: int,
         token: str,
         payer_keypair: bytes,
+        encrypted_note: bytes = b"",
     ) -> str:
         """
         Submit shield transaction to blockchain
 
         Args:
-            commitment: Generated commitment (32 bytes)
+            commitment: Generated commitment (32 bytes) for `amount`; mints
+                charging a Token-2022 transfer fee are rejected.
             amount: Amount to shield (lamports or token units)
-            token: Token mint address ("SOL" for native SOL)
+            token: Token mint address ("SOL" for native SOL); SPL Token and
+                Token-2022 mints are supported
             payer_keypair: Payer's keypair bytes (64 bytes)
+            encrypted_note: Note encrypted to its owner, published with the
+                commitment (optional)
 
         Returns:
             Transaction signature
@@ -395,23 +2016,28 @@ class SolanaClient:
         if token.upper() == "SOL":
             # Native SOL shielding
             instruction = self.instruction_builder.shield_sol(
-                payer.pubkey(), commitment, amount
+                payer.pubkey(),
+                commitment,
+                amount,
+                encrypted_note,
+                await self._compressed_tree(token),
             )
         else:
             # SPL token shielding with automatic ATA management
             mint = Pubkey.from_string(token)
+            token_program = await get_mint_token_program(self.client, mint)
 
             # Get or create user's associated token account
             user_ata = await get_or_create_ata(
-                self.client, payer.pubkey(), mint, payer
+                self.client, payer.pubkey(), mint, payer, token_program=token_program
             )
 
-            # Get vault authority PDA
-            vault_authority, _ = find_vault_pda(self.program_id, self.pool_pda)
+            # Get vault authority PDA of the mint's pool
+            vault_authority, _ = find_vault_pda(self.program_id, self.pool_for(token))
 
             # Get or create vault's associated token account
             vault_ata = await get_or_create_ata(
-                self.client, vault_authority, mint, payer
+                self.client, vault_authority, mint, payer, token_program=token_program
             )
 
             instruction = self.instruction_builder.shield_spl(
@@ -420,6 +2046,10 @@ class SolanaClient:
                 vault_ata,
                 commitment,
                 amount,
+                mint,
+                token_program,
+                encrypted_note,
+                await self._compressed_tree(token),
             )
 
         return await self.send_transaction(instruction, payer)
@@ -430,6 +2060,12 @@ class SolanaClient:
         new_commitment: bytes,
         proof: bytes,
         payer_keypair: bytes,
+        token: str = "SOL",
+        encrypted_note: bytes = b"",
+        tree_epoch: int = 0,
+        extra_nullifiers: Tuple[bytes, ...] = (),
+        root: Optional[bytes] = None,
+        fee: int = 0,
     ) -> str:
         """
         Submit private transfer transaction
@@ -439,14 +2075,32 @@ class SolanaClient:
             new_commitment: New commitment for recipient (32 bytes)
             proof: Proof bytes (96 bytes for MVP)
             payer_keypair: Payer's keypair bytes (64 bytes)
+            token: Token mint address ("SOL" for native SOL)
+            encrypted_note: Output note encrypted to the recipient (optional)
+            tree_epoch: Epoch of the tree holding the spent note
+            extra_nullifiers: More notes to sweep into the output (optional)
+            root: Root the proof is against (defaults to the current root)
+            fee: Lamports the proof pays the payer as relayer (SOL pools)
 
         Returns:
             Transaction signature
         """
         payer = Keypair.from_bytes(payer_keypair)
+        if root is None:
+            root = await self.get_merkle_root(token)
 
         instruction = self.instruction_builder.transfer(
-            payer.pubkey(), nullifier, new_commitment, proof
+            payer.pubkey(),
+            nullifier,
+            new_commitment,
+            proof,
+            self._mint_for(token),
+            encrypted_note,
+            tree_epoch,
+            extra_nullifiers,
+            await self._compressed_tree(token),
+            root,
+            fee,
         )
 
         return await self.send_transaction(instruction, payer)
@@ -459,6 +2113,9 @@ class SolanaClient:
         proof: bytes,
         payer_keypair: bytes,
         token: str = "SOL",
+        tree_epoch: int = 0,
+        memo: bytes = b"",
+        root: Optional[bytes] = None,
     ) -> str:
         """
         Submit unshield transaction
@@ -470,40 +2127,120 @@ class SolanaClient:
             proof: Proof bytes (96 bytes for MVP)
             payer_keypair: Payer's keypair bytes (64 bytes)
             token: Token mint address ("SOL" for native SOL)
+            tree_epoch: Epoch of the tree holding the spent note
+            memo: UTF-8 memo for the recipient, such as an exchange deposit
+                reference (empty for none)
+            root: Root the proof is against (defaults to the current root)
 
         Returns:
             Transaction signature
         """
         payer = Keypair.from_bytes(payer_keypair)
         recipient = Pubkey.from_string(destination)
+        if root is None:
+            root = await self.get_merkle_root(token)
 
         if token.upper() == "SOL":
             # Native SOL unshielding
             instruction = self.instruction_builder.unshield_sol(
-                payer.pubkey(), recipient, nullifier, amount, proof
+                payer.pubkey(),
+                recipient,
+                nullifier,
+                amount,
+                proof,
+                tree_epoch,
+                memo,
+                root,
             )
         else:
             # SPL token unshielding with automatic ATA management
             mint = Pubkey.from_string(token)
+            token_program = await get_mint_token_program(self.client, mint)
 
-            # Get vault authority PDA
-            vault_authority, _ = find_vault_pda(self.program_id, self.pool_pda)
+            # Get vault authority PDA of the mint's pool
+            vault_authority, _ = find_vault_pda(self.program_id, self.pool_for(token))
 
             # Get vault's token account (should already exist from shield)
-            vault_ata = await get_associated_token_address(vault_authority, mint)
+            vault_ata = await get_associated_token_address(
+                vault_authority, mint, token_program
+            )
 
-            # Get or create recipient's token account
-            recipient_ata = await get_or_create_ata(
-                self.client, recipient, mint, payer
+            # Payer relays the transaction and receives the relayer fee
+            relayer_ata = await get_or_create_ata(
+                self.client, payer.pubkey(), mint, payer, token_program=token_program
             )
 
-            instruction = self.instruction_builder.unshield_spl(
+            # The treasury takes its share of the fee in its own token account
+            treasury_ata = None
+            state = await self.get_pool_state(token)
+            if state is not None and state["protocol_fee_bps"] > 0:
+                treasury, _ = find_treasury_pda(self.program_id, self.pool_for(token))
+                treasury_ata = await get_or_create_ata(
+                    self.client, treasury, mint, payer, token_program=token_program
+                )
+
+            # The program creates the recipient's token account if needed
+            instruction = self.instruction_builder.unshield_spl_with_ata(
                 payer.pubkey(),
-                recipient_ata,
+                recipient,
                 vault_ata,
                 nullifier,
                 amount,
                 proof,
+                mint,
+                relayer_ata,
+                token_program,
+                tree_epoch,
+                treasury_ata,
+                memo,
+                root,
+            )
+
+        return await self.send_transaction(instruction, payer)
+
+    async def finalize_withdrawal(
+        self, payer: Keypair, pending_withdrawal: Pubkey, token: str = "SOL"
+    ) -> str:
+        """
+        Pay out a delayed unshield once its unlock slot has passed
+
+        Anyone may finalize; the pending account's rent goes back to the
+        relayer that queued it.
+
+        Args:
+            payer: Fee payer
+            pending_withdrawal: Pending withdrawal PDA
+            token: Token mint address ("SOL" for native SOL)
+
+        Returns:
+            Transaction signature
+        """
+        pending = await self.get_pending_withdrawal(pending_withdrawal)
+        if pending is None:
+            raise ValueError("No pending withdrawal at this address")
+
+        if token.upper() == "SOL":
+            instruction = self.instruction_builder.finalize_withdrawal_sol(
+                pending_withdrawal,
+                pending["recipient"],
+                pending["fee_recipient"],
+                pending["rent_payer"],
+            )
+        else:
+            mint = Pubkey.from_string(token)
+            token_program = await get_mint_token_program(self.client, mint)
+            vault_authority, _ = find_vault_pda(self.program_id, self.pool_for(token))
+            vault_ata = await get_associated_token_address(
+                vault_authority, mint, token_program
+            )
+            instruction = self.instruction_builder.finalize_withdrawal_spl(
+                pending_withdrawal,
+                mint,
+                vault_ata,
+                pending["recipient"],
+                pending["fee_recipient"],
+                pending["rent_payer"],
+                token_program,
             )
 
         return await self.send_transaction(instruction, payer)
@@ -20,10 +20,35 @@ from spl.token.async_client import AsyncToken
 from spl.token._layouts import ACCOUNT_LAYOUT
 import spl.token.instructions as spl_token
 
+# Token-2022 program (token extensions)
+TOKEN_2022_PROGRAM_ID = Pubkey.from_string("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb")
+
+
+async def get_mint_token_program(client: AsyncClient, mint: Pubkey) -> Pubkey:
+    """
+    Get the token program (SPL Token or Token-2022) that owns a mint.
+
+    Args:
+        client: Solana RPC client
+        mint: The token mint public key
+
+    Returns:
+        The owning token program ID
+    """
+    account_info = await client.get_account_info(mint, commitment=Confirmed)
+    if account_info.value is None:
+        raise ValueError(f"Mint {mint} does not exist")
+
+    owner = account_info.value.owner
+    if owner not in (TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID):
+        raise ValueError(f"Account {mint} is not a token mint")
+    return owner
+
 
 async def get_associated_token_address(
     owner: Pubkey,
     mint: Pubkey,
+    token_program: Pubkey = TOKEN_PROGRAM_ID,
 ) -> Pubkey:
     """
     Derive the associated token account address for an owner and mint.
@@ -31,6 +56,7 @@ async def get_associated_token_address(
     Args:
         owner: The owner's public key
         mint: The token mint public key
+        token_program: Token program owning the mint
 
     Returns:
         The derived ATA public key
@@ -38,7 +64,7 @@ async def get_associated_token_address(
     # Find PDA: [owner, token_program, mint]
     seeds = [
         bytes(owner),
-        bytes(TOKEN_PROGRAM_ID),
+        bytes(token_program),
         bytes(mint),
     ]
     ata, _ = Pubkey.find_program_address(seeds, ASSOCIATED_TOKEN_PROGRAM_ID)
@@ -51,6 +77,7 @@ async def get_or_create_ata(
     mint: Pubkey,
     payer: Keypair,
     skip_confirmation: bool = False,
+    token_program: Pubkey = TOKEN_PROGRAM_ID,
 ) -> Pubkey:
     """
     Get or create an associated token account for the owner.
@@ -64,11 +91,12 @@ async def get_or_create_ata(
         mint: The token mint
         payer: Keypair that will pay for account creation
         skip_confirmation: If True, don't wait for transaction confirmation
+        token_program: Token program owning the mint
 
     Returns:
         The ATA public key
     """
-    ata = await get_associated_token_address(owner, mint)
+    ata = await get_associated_token_address(owner, mint, token_program)
 
     # Check if account exists
     try:
@@ -85,6 +113,7 @@ async def get_or_create_ata(
         payer=payer.pubkey(),
         owner=owner,
         mint=mint,
+        token_program_id=token_program,
     )
 
     # Build and send transaction
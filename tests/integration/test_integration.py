@@ -156,56 +156,6 @@ def test_nullifier_deterministic():
     assert n1 == n2
 
 
-def test_proof_generation():
-    """Test proof generation"""
-    from nyx_protocol import _rust_core
-    import json
-
-    witness = {
-        "sender_secret": "my_test_secret_key_32_chars_min!",
-        "sender_commitment": "abc123",
-        "recipient": "recipient_address",
-        "amount": 1000,
-        "nullifier": "nullifier_hash",
-    }
-
-    proof = _rust_core.generate_proof(witness_json=json.dumps(witness))
-
-    assert len(proof) == 64
-    assert isinstance(proof, bytes)
-
-
-def test_proof_verification():
-    """Test proof verification"""
-    from nyx_protocol import _rust_core
-    import json
-
-    # Generate a proof
-    witness = {
-        "sender_secret": "my_test_secret_key_32_chars_min!",
-        "sender_commitment": "abc123",
-        "recipient": "recipient_address",
-        "amount": 1000,
-        "nullifier": "nullifier_hash",
-    }
-
-    proof = _rust_core.generate_proof(witness_json=json.dumps(witness))
-
-    # Verify it
-    public_inputs = {
-        "nullifier": "nullifier_hash",
-        "new_commitment": "new_commitment_hash",
-        "root": "merkle_root",
-    }
-
-    valid = _rust_core.verify_proof(
-        proof=proof,
-        public_inputs_json=json.dumps(public_inputs),
-    )
-
-    assert valid is True
-
-
 def test_poseidon_hash():
     """Test Poseidon hash function"""
     from nyx_protocol import _rust_core
@@ -0,0 +1,45 @@
+"""Test mnemonic backup and restore"""
+
+import pytest
+
+from veil import _rust_core
+
+# The 24-word "abandon ... art" vector from the BIP39 reference tests
+PHRASE = " ".join(["abandon"] * 23 + ["art"])
+
+
+def test_generate_mnemonic():
+    """Generated phrases have 24 valid words"""
+    phrase = _rust_core.generate_mnemonic()
+
+    assert len(phrase.split()) == 24
+    _rust_core.validate_mnemonic(phrase)
+    assert _rust_core.generate_mnemonic() != phrase
+
+
+def test_restore_deterministic():
+    """The same phrase restores the same secret"""
+    s1 = _rust_core.secret_from_mnemonic(PHRASE)
+    s2 = _rust_core.secret_from_mnemonic(PHRASE, passphrase="")
+
+    assert isinstance(s1, bytes)
+    assert len(s1) == 32
+    assert s1 == s2
+
+
+def test_passphrase_and_account_select_keys():
+    """Passphrases and accounts give independent secrets"""
+    base = _rust_core.secret_from_mnemonic(PHRASE)
+
+    assert _rust_core.secret_from_mnemonic(PHRASE, passphrase="TREZOR") != base
+    assert _rust_core.secret_from_mnemonic(PHRASE, account=1) != base
+
+
+def test_invalid_mnemonic():
+    """Bad checksums and unknown words are rejected"""
+    bad_checksum = " ".join(["abandon"] * 24)
+
+    with pytest.raises(ValueError):
+        _rust_core.validate_mnemonic(bad_checksum)
+    with pytest.raises(ValueError):
+        _rust_core.secret_from_mnemonic(PHRASE.replace("art", "notaword"))
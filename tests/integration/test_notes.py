@@ -0,0 +1,241 @@
+"""Test notes, trees, note encryption and transfer proofs"""
+
+import asyncio
+import json
+
+import pytest
+
+from veil import _rust_core, prove_spend
+
+OWNER = bytes([3] * 32)
+
+
+@pytest.fixture(scope="module")
+def system():
+    """Test keys for the default tree depth"""
+    return _rust_core.TransferProofSystem.setup()
+
+
+@pytest.fixture(scope="module")
+def keys(system, tmp_path_factory):
+    """The test keys saved to disk, with their hashes pinned"""
+    key_dir = tmp_path_factory.mktemp("keys")
+    unpinned = _rust_core.KeyStore(key_dir / "transfer.pk", key_dir / "transfer.vk")
+    pk_hash, vk_hash = unpinned.save(system)
+    return _rust_core.KeyStore(
+        unpinned.proving_key_path, unpinned.verifying_key_path, pk_hash, vk_hash
+    )
+
+
+def note_in_tree():
+    """A note of 1000 at leaf 1 of a fresh tree"""
+    tree = _rust_core.PoseidonMerkleTree()
+    tree.insert(_rust_core.Note.random(5).commitment())
+    note = _rust_core.Note(OWNER, 1000)
+    note.leaf_index = tree.insert(note.commitment())
+    return note, tree
+
+
+def test_note_roundtrip():
+    """Notes serialize with their leaf index"""
+    note, _ = note_in_tree()
+    restored = _rust_core.Note.from_bytes(note.to_bytes())
+
+    assert restored.commitment() == note.commitment()
+    assert restored.nullifier() == note.nullifier()
+    assert note.nullifier() == _rust_core.SpendingKey(OWNER).nullifier(1)
+
+
+def test_nullifier_needs_leaf_index():
+    """A note not yet in the tree has no nullifier"""
+    with pytest.raises(ValueError):
+        _rust_core.Note(OWNER, 1000).nullifier()
+
+
+def test_tree():
+    """Roots change as commitments are inserted"""
+    tree = _rust_core.PoseidonMerkleTree()
+    empty = tree.root()
+    assert tree.insert_batch([bytes(32), bytes(31) + b"\x01"]) == 0
+
+    assert len(tree) == 2
+    assert tree.root() != empty
+    assert tree.is_known_root(tree.root())
+    assert not tree.is_known_root(empty)
+    with pytest.raises(ValueError):
+        tree.insert(b"\xff" * 32)
+
+
+def test_poseidon_hash_batch():
+    """Batch hashes match one-at-a-time hashes"""
+    batch = [[bytes([i]) * 32, bytes([i + 1]) * 32] for i in range(4)]
+
+    assert _rust_core.poseidon_hash_batch(batch) == [
+        _rust_core.poseidon_hash(inputs) for inputs in batch
+    ]
+
+
+def test_encrypted_note():
+    """Only the recipient decrypts a note"""
+    note, _ = note_in_tree()
+    encrypted = _rust_core.EncryptedNote.encrypt(note, note.encryption_public_key())
+    published = _rust_core.EncryptedNote.from_bytes(encrypted.to_bytes())
+
+    received = published.decrypt(OWNER, leaf_index=1)
+    assert received.commitment() == note.commitment()
+    assert received.nullifier() == note.nullifier()
+    assert published.decrypt(bytes([4] * 32)) is None
+
+
+def test_prove_and_verify(system):
+    """A spend proves and verifies under the same public inputs"""
+    note, tree = note_in_tree()
+    spend = system.prove(note, tree, fee=30)
+
+    assert spend.output_note.amount == 970
+    assert spend.public_inputs[0] == tree.root()
+    assert spend.nullifier == note.nullifier()
+    assert spend.new_commitment == spend.output_note.commitment()
+    assert len(spend.solana_proof) == 256
+    assert system.verify(spend.proof, spend.public_inputs)
+
+    wrong_fee = list(spend.public_inputs)
+    wrong_fee[4] = (31).to_bytes(32, "big")
+    assert not system.verify(spend.proof, wrong_fee)
+
+
+def test_load_keys(system):
+    """Serialized keys load into an equivalent system"""
+    note, tree = note_in_tree()
+    loaded = _rust_core.TransferProofSystem.from_keys(
+        system.serialize_proving_key(),
+        system.serialize_verifying_key(),
+    )
+
+    spend = loaded.prove(note, tree)
+    assert system.verify(spend.proof, spend.public_inputs)
+
+
+def test_prove_rejects_bad_witness(system):
+    """Fees beyond the amount and notes outside the tree are rejected"""
+    note, tree = note_in_tree()
+    with pytest.raises(ValueError):
+        system.prove(note, tree, fee=1001)
+
+    note.leaf_index = 0
+    with pytest.raises(ValueError):
+        system.prove(note, tree)
+
+
+def test_prove_task(system):
+    """Background proofs verify, and wait without blocking other threads"""
+    note, tree = note_in_tree()
+    tasks = [system.prove_async(note, tree, fee=fee) for fee in (10, 20)]
+
+    proofs = [task.result(timeout=60) for task in tasks]
+    assert all(task.done() for task in tasks)
+    assert [proof.output_note.amount for proof in proofs] == [990, 980]
+    for proof in proofs:
+        assert system.verify(proof.proof, proof.public_inputs)
+
+    # The witness is checked up front
+    with pytest.raises(ValueError):
+        system.prove_async(note, tree, fee=1001)
+
+
+def test_prove_task_timeout(system):
+    """Waiting less than proving takes times out, and the task still finishes"""
+    note, tree = note_in_tree()
+    task = system.prove_async(note, tree)
+
+    with pytest.raises(TimeoutError):
+        task.result(timeout=0)
+    assert system.verify(task.result().proof, task.result().public_inputs)
+
+
+@pytest.mark.asyncio
+async def test_prove_spend(system):
+    """Concurrent async proofs"""
+    note, tree = note_in_tree()
+    proofs = await asyncio.gather(
+        prove_spend(system, note, tree, fee=1),
+        prove_spend(system, note, tree, fee=2),
+    )
+
+    assert [proof.output_note.amount for proof in proofs] == [999, 998]
+    assert proofs[0].nullifier == proofs[1].nullifier == note.nullifier()
+
+
+def witness_json(note, tree, fee=0):
+    """The witness spending `note` from `tree`, as `generate_proof` takes it"""
+    return json.dumps(
+        {
+            "secret": note.secret.hex(),
+            "amount": note.amount,
+            "blinding": note.blinding.hex(),
+            "leaf_index": note.leaf_index,
+            "merkle_path": [sibling.hex() for sibling in tree.path(note.leaf_index)],
+            "merkle_root": tree.root().hex(),
+            "fee": fee,
+        }
+    )
+
+
+def test_generate_proof(system, keys):
+    """Proofs from key files verify against their public inputs"""
+    note, tree = note_in_tree()
+    spend = _rust_core.generate_proof(witness_json(note, tree, fee=25), keys=keys)
+
+    inputs = json.loads(spend.public_inputs_json)
+    assert inputs == {
+        "merkle_root": tree.root().hex(),
+        "nullifier": note.nullifier().hex(),
+        "new_commitment": spend.output_note.commitment().hex(),
+        "asset_id": 0,
+        "fee": 25,
+    }
+    assert _rust_core.verify_proof(spend.proof, spend.public_inputs_json, keys=keys)
+    assert _rust_core.verify_proof(
+        spend.proof,
+        spend.public_inputs_json,
+        verifying_key=system.serialize_verifying_key(),
+    )
+
+    inputs["fee"] = 26
+    assert not _rust_core.verify_proof(spend.proof, json.dumps(inputs), keys=keys)
+
+
+def test_generate_proof_rejects_bad_witness(keys):
+    """Legacy witnesses and paths that do not open the root are rejected"""
+    note, tree = note_in_tree()
+    legacy = {
+        "sender_secret": "s",
+        "sender_commitment": "c",
+        "recipient": "r",
+        "amount": 1,
+        "nullifier": "n",
+    }
+    with pytest.raises(ValueError):
+        _rust_core.generate_proof(json.dumps(legacy), keys=keys)
+
+    wrong_root = json.loads(witness_json(note, tree))
+    wrong_root["merkle_root"] = bytes(32).hex()
+    with pytest.raises(ValueError):
+        _rust_core.generate_proof(json.dumps(wrong_root), keys=keys)
+
+
+def test_key_store_pins(keys, tmp_path):
+    """Keys that do not match their pinned hashes are refused"""
+    with pytest.raises(ValueError):
+        _rust_core.KeyStore("transfer.pk", "transfer.vk", bytes(32))
+    with pytest.raises(ValueError):
+        _rust_core.KeyStore.for_network("testnet")
+
+    pinned = _rust_core.KeyStore(
+        keys.proving_key_path, keys.verifying_key_path, bytes(32), bytes(32)
+    )
+    with pytest.raises(RuntimeError):
+        pinned.transfer_system()
+    missing = _rust_core.KeyStore(tmp_path / "transfer.pk", tmp_path / "transfer.vk")
+    with pytest.raises(RuntimeError):
+        missing.transfer_system()
@@ -21,35 +21,86 @@ fn program_id() -> Pubkey {
 }
 
 fn find_pool_pda() -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[b"privacy_pool"], &program_id())
+    // Native SOL pool, keyed by the wrapped SOL mint
+    Pubkey::find_program_address(
+        &[b"privacy_pool", spl_token::native_mint::ID.as_ref()],
+        &program_id(),
+    )
 }
 
 fn find_vault_pda(pool: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[b"vault", pool.as_ref()], &program_id())
 }
 
+fn find_verifying_key_pda(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"verifying_key", pool.as_ref()], &program_id())
+}
+
+/// Tree epoch of every note in these tests (the pool's first tree)
+const TREE_EPOCH: u32 = 0;
+
 fn find_nullifier_pda(pool: &Pubkey, nullifier: &[u8; 32]) -> (Pubkey, u8) {
     Pubkey::find_program_address(
-        &[b"nullifier", pool.as_ref(), nullifier],
+        &[b"nullifier", pool.as_ref(), &TREE_EPOCH.to_le_bytes(), nullifier],
         &program_id(),
     )
 }
 
+fn find_nullifier_archive_pda(pool: &Pubkey, nullifier: &[u8; 32]) -> (Pubkey, u8) {
+    // Buckets are keyed by the nullifier's last byte
+    Pubkey::find_program_address(
+        &[b"nullifier_archive", pool.as_ref(), &TREE_EPOCH.to_le_bytes(), &nullifier[31..]],
+        &program_id(),
+    )
+}
+
+fn find_archived_root_pda(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"archived_root", pool.as_ref(), &TREE_EPOCH.to_le_bytes()],
+        &program_id(),
+    )
+}
+
+fn find_pending_withdrawal_pda(pool: &Pubkey, nullifier: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"pending_withdrawal", pool.as_ref(), &TREE_EPOCH.to_le_bytes(), nullifier],
+        &program_id(),
+    )
+}
+
+fn find_treasury_pda(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"treasury", pool.as_ref()], &program_id())
+}
+
+fn find_root_history_pda(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"root_history", pool.as_ref()], &program_id())
+}
+
+/// Recent roots the test pool accepts proofs against
+const ROOT_HISTORY_SIZE: u16 = 30;
+
 /// Create initialize instruction
 fn create_initialize_ix(authority: &Pubkey) -> Instruction {
     let (pool, _) = find_pool_pda();
+    let (verifying_key, _) = find_verifying_key_pda(&pool);
+    let (root_history, _) = find_root_history_pda(&pool);
 
     // Anchor instruction discriminator for "initialize"
     let discriminator: [u8; 8] = [175, 175, 109, 31, 13, 152, 155, 237];
 
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(&ROOT_HISTORY_SIZE.to_le_bytes());
+
     Instruction {
         program_id: program_id(),
         accounts: vec![
             AccountMeta::new(pool, false),
+            AccountMeta::new(verifying_key, false),
+            AccountMeta::new(root_history, false),
             AccountMeta::new(*authority, true),
             AccountMeta::new_readonly(system_program::ID, false),
         ],
-        data: discriminator.to_vec(),
+        data,
     }
 }
 
@@ -61,6 +112,7 @@ fn create_shield_sol_ix(
 ) -> Instruction {
     let (pool, _) = find_pool_pda();
     let (vault, _) = find_vault_pda(&pool);
+    let (root_history, _) = find_root_history_pda(&pool);
 
     // Anchor instruction discriminator for "shield_sol"
     let discriminator: [u8; 8] = [183, 4, 24, 123, 20, 45, 203, 91];
@@ -68,11 +120,14 @@ fn create_shield_sol_ix(
     let mut data = discriminator.to_vec();
     data.extend_from_slice(&commitment);
     data.extend_from_slice(&amount.to_le_bytes());
+    // No encrypted note
+    data.extend_from_slice(&0u32.to_le_bytes());
 
     Instruction {
         program_id: program_id(),
         accounts: vec![
             AccountMeta::new(pool, false),
+            AccountMeta::new(root_history, false),
             AccountMeta::new(vault, false),
             AccountMeta::new(*depositor, true),
             AccountMeta::new_readonly(system_program::ID, false),
@@ -86,26 +141,40 @@ fn create_transfer_ix(
     relayer: &Pubkey,
     nullifier: [u8; 32],
     new_commitment: [u8; 32],
+    root: [u8; 32],
     proof: Vec<u8>,
 ) -> Instruction {
     let (pool, _) = find_pool_pda();
+    let (verifying_key, _) = find_verifying_key_pda(&pool);
+    let (root_history, _) = find_root_history_pda(&pool);
     let (nullifier_marker, _) = find_nullifier_pda(&pool, &nullifier);
+    let (nullifier_archive, _) = find_nullifier_archive_pda(&pool, &nullifier);
+    let (archived_root, _) = find_archived_root_pda(&pool);
 
     // Anchor instruction discriminator for "transfer"
     let discriminator: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
 
     let mut data = discriminator.to_vec();
+    data.extend_from_slice(&TREE_EPOCH.to_le_bytes());
+    data.extend_from_slice(&root);
     data.extend_from_slice(&nullifier);
     data.extend_from_slice(&new_commitment);
     // Vec<u8> is serialized as: 4-byte length + data
     data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
     data.extend_from_slice(&proof);
+    // No encrypted note, no extra nullifiers
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
 
     Instruction {
         program_id: program_id(),
         accounts: vec![
             AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(verifying_key, false),
+            AccountMeta::new(root_history, false),
             AccountMeta::new(nullifier_marker, false),
+            AccountMeta::new_readonly(nullifier_archive, false),
+            AccountMeta::new_readonly(archived_root, false),
             AccountMeta::new(*relayer, true),
             AccountMeta::new_readonly(system_program::ID, false),
         ],
@@ -119,30 +188,49 @@ fn create_unshield_sol_ix(
     recipient: &Pubkey,
     nullifier: [u8; 32],
     amount: u64,
+    root: [u8; 32],
     proof: Vec<u8>,
 ) -> Instruction {
     let (pool, _) = find_pool_pda();
     let (vault, _) = find_vault_pda(&pool);
+    let (verifying_key, _) = find_verifying_key_pda(&pool);
+    let (root_history, _) = find_root_history_pda(&pool);
     let (nullifier_marker, _) = find_nullifier_pda(&pool, &nullifier);
+    let (nullifier_archive, _) = find_nullifier_archive_pda(&pool, &nullifier);
+    let (archived_root, _) = find_archived_root_pda(&pool);
+    let (pending_withdrawal, _) = find_pending_withdrawal_pda(&pool, &nullifier);
+    let (treasury, _) = find_treasury_pda(&pool);
 
     // Anchor instruction discriminator for "unshield_sol"
     let discriminator: [u8; 8] = [45, 127, 188, 9, 224, 78, 199, 57];
 
     let mut data = discriminator.to_vec();
+    data.extend_from_slice(&TREE_EPOCH.to_le_bytes());
+    data.extend_from_slice(&root);
     data.extend_from_slice(&nullifier);
     data.extend_from_slice(&amount.to_le_bytes());
     data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
     data.extend_from_slice(&proof);
+    // No memo
+    data.extend_from_slice(&0u32.to_le_bytes());
 
     Instruction {
         program_id: program_id(),
         accounts: vec![
             AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(verifying_key, false),
+            AccountMeta::new_readonly(root_history, false),
             AccountMeta::new(nullifier_marker, false),
+            AccountMeta::new_readonly(nullifier_archive, false),
+            AccountMeta::new_readonly(archived_root, false),
+            AccountMeta::new(pending_withdrawal, false),
             AccountMeta::new(vault, false),
+            AccountMeta::new(treasury, false),
             AccountMeta::new(*recipient, false),
             AccountMeta::new(*relayer, true),
             AccountMeta::new_readonly(system_program::ID, false),
+            // Memo program omitted
+            AccountMeta::new_readonly(program_id(), false),
         ],
         data,
     }
@@ -173,8 +261,9 @@ mod tests {
         let ix = create_initialize_ix(&authority.pubkey());
 
         assert_eq!(ix.program_id, program_id());
-        assert_eq!(ix.accounts.len(), 3);
-        assert_eq!(ix.data.len(), 8); // Just discriminator
+        assert_eq!(ix.accounts.len(), 5);
+        // Data: 8 (discriminator) + 2 (root history size)
+        assert_eq!(ix.data.len(), 10);
     }
 
     /// Test shield SOL instruction creation
@@ -204,6 +293,7 @@ mod tests {
             &relayer.pubkey(),
             nullifier,
             new_commitment,
+            [9u8; 32],
             proof.clone(),
         );
 
@@ -227,6 +317,7 @@ mod tests {
             &recipient.pubkey(),
             nullifier,
             amount,
+            [9u8; 32],
             proof.clone(),
         );
 
@@ -181,6 +181,515 @@ class TestPDADerivation:
         # Different nullifiers should produce different PDAs
         assert pda1 != pda2
 
+    def test_find_pool_pda_per_mint(self):
+        """Test each mint gets its own pool (and with it its own vault)"""
+        from veil.solana_client import (
+            NATIVE_MINT,
+            find_pool_pda,
+            find_vault_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        usdc = Pubkey.from_string("EPjFWdd5AufqSSqeM2qFJxbRBVNqn2FcWKzoHiMrnGPq")
+
+        sol_pool, bump = find_pool_pda(program_id)
+        assert (sol_pool, bump) == find_pool_pda(program_id, NATIVE_MINT)
+        # Matches the program's [b"privacy_pool", native mint] derivation
+        assert str(sol_pool) == "9HhrkXeKCtJivSh5jkgsBDNoeQuaGvozSxY2jRzHDGV"
+        assert bump == 254
+
+        usdc_pool, _ = find_pool_pda(program_id, usdc)
+        assert usdc_pool != sol_pool
+        assert find_vault_pda(program_id, usdc_pool) != find_vault_pda(
+            program_id, sol_pool
+        )
+
+    def test_set_verifying_key_instruction(self):
+        """Test set_verifying_key layout and the VK account on proof instructions"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            find_pool_pda,
+            find_verifying_key_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        vk, _ = find_verifying_key_pda(program_id, pool)
+
+        ix = builder.set_verifying_key(
+            authority,
+            bytes(64),
+            bytes(128),
+            bytes(128),
+            bytes(128),
+            [bytes(64)] * 6,
+            lock=True,
+        )
+        assert ix.data[:8] == InstructionBuilder.SET_VERIFYING_KEY_DISC
+        assert len(ix.data) == 8 + 832 + 1
+        assert ix.data[-1] == 1
+        assert [meta.pubkey for meta in ix.accounts] == [pool, vk, authority]
+
+        with pytest.raises(ValueError):
+            builder.set_verifying_key(
+                authority, bytes(64), bytes(128), bytes(128), bytes(128), [bytes(64)] * 5
+            )
+
+        unshield_key = builder.set_unshield_verifying_key(
+            authority, bytes(64), bytes(128), bytes(128), bytes(128), [bytes(64)] * 7
+        )
+        assert unshield_key.data[:8] == InstructionBuilder.SET_UNSHIELD_VERIFYING_KEY_DISC
+        assert len(unshield_key.data) == 8 + 896 + 1
+        assert [meta.pubkey for meta in unshield_key.accounts] == [pool, vk, authority]
+
+        with pytest.raises(ValueError):
+            builder.set_unshield_verifying_key(
+                authority, bytes(64), bytes(128), bytes(128), bytes(128), [bytes(64)] * 6
+            )
+
+        transfer = builder.transfer(authority, bytes(32), bytes(32), bytes(256))
+        assert transfer.accounts[1].pubkey == vk
+        assert not transfer.accounts[1].is_writable
+
+    def test_compact_nullifiers_instruction(self):
+        """Test compact_nullifiers layout and the archive account on spends"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            find_nullifier_archive_pda,
+            find_nullifier_pda,
+            find_pool_pda,
+            find_treasury_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        nullifiers = [bytes([1] * 31 + [7]), bytes([2] * 31 + [7])]
+        archive, _ = find_nullifier_archive_pda(program_id, pool, 7)
+
+        ix = builder.compact_nullifiers(authority, 7, nullifiers)
+        assert ix.data == InstructionBuilder.COMPACT_NULLIFIERS_DISC + bytes(4) + bytes([7])
+        assert [meta.pubkey for meta in ix.accounts[:4]] == [
+            pool,
+            archive,
+            find_treasury_pda(program_id, pool)[0],
+            authority,
+        ]
+        markers = ix.accounts[5:]
+        assert [meta.pubkey for meta in markers] == [
+            find_nullifier_pda(program_id, pool, n)[0] for n in nullifiers
+        ]
+        assert all(meta.is_writable for meta in markers)
+
+        # Every nullifier must fall in the bucket
+        with pytest.raises(ValueError):
+            builder.compact_nullifiers(authority, 8, nullifiers)
+
+        # Spends pass the bucket after the marker, read-only
+        transfer = builder.transfer(authority, nullifiers[0], bytes(32), bytes(96))
+        assert transfer.accounts[4].pubkey == archive
+        assert not transfer.accounts[4].is_writable
+
+    def test_tree_epoch_namespaces_spends(self):
+        """Test spends from a rotated-out tree use that tree's accounts"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            find_archived_root_pda,
+            find_nullifier_pda,
+            find_pending_withdrawal_pda,
+            find_pool_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        relayer = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        nullifier = bytes([5] * 32)
+
+        assert find_nullifier_pda(program_id, pool, nullifier, 0) != find_nullifier_pda(
+            program_id, pool, nullifier, 1
+        )
+
+        ix = builder.unshield_sol(
+            relayer, relayer, nullifier, 1000, bytes(96), tree_epoch=1, root=bytes([6] * 32)
+        )
+        assert ix.data[8:12] == (1).to_bytes(4, "little")
+        assert ix.data[12:44] == bytes([6] * 32)
+        assert ix.data[44:76] == nullifier
+        assert ix.accounts[3].pubkey == find_nullifier_pda(program_id, pool, nullifier, 1)[0]
+        assert ix.accounts[5].pubkey == find_archived_root_pda(program_id, pool, 1)[0]
+        assert ix.accounts[6].pubkey == find_pending_withdrawal_pda(
+            program_id, pool, nullifier, 1
+        )[0]
+
+        rotate = builder.rotate_tree(relayer, 0)
+        assert rotate.data == InstructionBuilder.ROTATE_TREE_DISC
+        assert rotate.accounts[2].pubkey == find_archived_root_pda(program_id, pool, 0)[0]
+
+    def test_transfer_sweeps_extra_nullifiers(self):
+        """Test extra transfer inputs and the relayer fee accounts"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            find_nullifier_archive_pda,
+            find_nullifier_pda,
+            find_pool_pda,
+            find_vault_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        relayer = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        extras = (bytes([2] * 32), bytes([3] * 32))
+
+        ix = builder.transfer(
+            relayer, bytes([1] * 32), bytes(32), bytes(96), extra_nullifiers=extras
+        )
+        assert len(ix.accounts) == 13 + 4
+        assert ix.accounts[13].pubkey == find_nullifier_pda(program_id, pool, extras[0])[0]
+        assert ix.accounts[13].is_writable
+        assert ix.accounts[14].pubkey == find_nullifier_archive_pda(program_id, pool, 2)[0]
+        assert ix.data[-68:-64] == (2).to_bytes(4, "little")
+        assert ix.data[-64:] == b"".join(extras)
+
+        plain = builder.transfer(relayer, bytes([1] * 32), bytes(32), bytes(96))
+        assert plain.data[-4:] == bytes(4)
+        assert plain.data[108:116] == bytes(8)
+        # Without token accounts the optional slots hold the program ID
+        assert plain.accounts[8].pubkey == find_vault_pda(program_id, pool)[0]
+        assert [meta.pubkey for meta in plain.accounts[9:]] == [program_id] * 4
+
+        usdc = Pubkey.new_unique()
+        vault_ata, relayer_ata = Pubkey.new_unique(), Pubkey.new_unique()
+        paid = builder.transfer(
+            relayer,
+            bytes([1] * 32),
+            bytes(32),
+            bytes(96),
+            mint=usdc,
+            fee=250,
+            vault_token_account=vault_ata,
+            relayer_token_account=relayer_ata,
+        )
+        assert paid.data[108:116] == (250).to_bytes(8, "little")
+        assert [meta.pubkey for meta in paid.accounts[9:12]] == [usdc, vault_ata, relayer_ata]
+        assert paid.accounts[11].is_writable
+
+        with pytest.raises(ValueError):
+            builder.transfer(
+                relayer, bytes([1] * 32), bytes(32), bytes(96), vault_token_account=vault_ata
+            )
+
+        with pytest.raises(ValueError):
+            builder.transfer(
+                relayer, bytes([1] * 32), bytes(32), bytes(96), extra_nullifiers=(bytes([1] * 32),)
+            )
+
+
+    def test_relayer_registry_instructions(self):
+        """Test relayer register/deregister instruction layout"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            MIN_RELAYER_STAKE,
+            find_relayer_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        relayer = Pubkey.new_unique()
+        endpoint = "https://relayer.example.com"
+        entry, _ = find_relayer_pda(program_id, relayer)
+
+        ix = builder.register_relayer(relayer, 25, endpoint)
+        assert ix.data[:8] == InstructionBuilder.REGISTER_RELAYER_DISC
+        assert ix.data[8:10] == (25).to_bytes(2, "little")
+        assert ix.data[10:14] == len(endpoint).to_bytes(4, "little")
+        assert ix.data[14:-8] == endpoint.encode()
+        assert ix.data[-8:] == MIN_RELAYER_STAKE.to_bytes(8, "little")
+        assert ix.accounts[0].pubkey == entry
+        assert ix.accounts[1].is_signer
+
+        leave = builder.deregister_relayer(relayer)
+        assert leave.data == InstructionBuilder.DEREGISTER_RELAYER_DISC
+        assert leave.accounts[0].pubkey == entry
+
+        with pytest.raises(ValueError):
+            builder.register_relayer(relayer, 25, "http://relayer.example.com")
+        with pytest.raises(ValueError):
+            builder.register_relayer(relayer, 25, endpoint, MIN_RELAYER_STAKE - 1)
+
+
+    def test_withdrawal_delay_instructions(self):
+        """Test withdrawal delay and finalize instruction layout"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            MAX_WITHDRAWAL_DELAY_SLOTS,
+            find_pool_pda,
+            find_vault_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+
+        ix = builder.set_withdrawal_delay(authority, 9000)
+        assert ix.data[:8] == InstructionBuilder.SET_WITHDRAWAL_DELAY_DISC
+        assert ix.data[8:] == (9000).to_bytes(8, "little")
+        assert ix.accounts[0].pubkey == pool
+        with pytest.raises(ValueError):
+            builder.set_withdrawal_delay(authority, MAX_WITHDRAWAL_DELAY_SLOTS + 1)
+
+        pending, recipient, relayer = (Pubkey.new_unique() for _ in range(3))
+        finalize = builder.finalize_withdrawal_sol(pending, recipient, relayer, relayer)
+        assert finalize.data == InstructionBuilder.FINALIZE_WITHDRAWAL_SOL_DISC
+        assert finalize.accounts[1].pubkey == pending
+        assert finalize.accounts[2].pubkey == find_vault_pda(program_id, pool)[0]
+        assert finalize.accounts[3].pubkey == recipient
+        assert not any(meta.is_signer for meta in finalize.accounts)
+
+
+    def test_set_deposit_caps_instruction(self):
+        """Test set_deposit_caps instruction layout"""
+        from veil.solana_client import InstructionBuilder, find_pool_pda
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+
+        ix = builder.set_deposit_caps(authority, 10**9, 10**12)
+        assert ix.data[:8] == InstructionBuilder.SET_DEPOSIT_CAPS_DISC
+        assert ix.data[8:16] == (10**9).to_bytes(8, "little")
+        assert ix.data[16:] == (10**12).to_bytes(8, "little")
+        assert ix.accounts[0].pubkey == find_pool_pda(program_id)[0]
+        assert ix.accounts[1].is_signer
+
+
+    def test_protocol_fee_instructions(self):
+        """Test set_protocol_fee, collect_fees and the treasury unshield accounts"""
+        from veil.solana_client import (
+            InstructionBuilder,
+            MAX_PROTOCOL_FEE_BPS,
+            find_pool_pda,
+            find_treasury_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        treasury, _ = find_treasury_pda(program_id, pool)
+
+        ix = builder.set_protocol_fee(authority, 2_000)
+        assert ix.data == InstructionBuilder.SET_PROTOCOL_FEE_DISC + (2_000).to_bytes(2, "little")
+        assert ix.accounts[1].pubkey == treasury
+        assert ix.accounts[2].is_signer and ix.accounts[2].is_writable
+        with pytest.raises(ValueError):
+            builder.set_protocol_fee(authority, MAX_PROTOCOL_FEE_BPS + 1)
+
+        # SOL sweeps pass the program ID for the omitted token accounts
+        destination = Pubkey.new_unique()
+        collect = builder.collect_fees(authority, destination)
+        assert collect.data == InstructionBuilder.COLLECT_FEES_DISC
+        assert collect.accounts[1].pubkey == treasury
+        assert collect.accounts[2].pubkey == destination
+        assert all(collect.accounts[i].pubkey == program_id for i in (3, 4, 5, 8))
+        with pytest.raises(ValueError):
+            builder.collect_fees(authority, destination, treasury_token_account=destination)
+
+        mint = Pubkey.new_unique()
+        treasury_ata, destination_ata = Pubkey.new_unique(), Pubkey.new_unique()
+        collect = builder.collect_fees(
+            authority, destination, mint, treasury_ata, destination_ata
+        )
+        assert [meta.pubkey for meta in collect.accounts[3:6]] == [
+            mint,
+            treasury_ata,
+            destination_ata,
+        ]
+
+        # Unshields pass the treasury, and SPL ones its optional token account
+        unshield = builder.unshield_sol(authority, destination, bytes(32), 1000, bytes(96))
+        assert unshield.accounts[8].pubkey == treasury
+        spl_accounts = [Pubkey.new_unique() for _ in range(3)]
+        unshield = builder.unshield_spl(
+            authority, *spl_accounts[:2], bytes(32), 1000, bytes(96), mint, spl_accounts[2]
+        )
+        spl_treasury, _ = find_treasury_pda(program_id, find_pool_pda(program_id, mint)[0])
+        assert unshield.accounts[12].pubkey == spl_treasury
+        assert unshield.accounts[13].pubkey == program_id
+        unshield = builder.unshield_spl(
+            authority,
+            *spl_accounts[:2],
+            bytes(32),
+            1000,
+            bytes(96),
+            mint,
+            spl_accounts[2],
+            treasury_token_account=treasury_ata,
+        )
+        assert unshield.accounts[13].pubkey == treasury_ata
+        assert unshield.accounts[13].is_writable
+
+
+    def test_unshield_with_ata_instruction(self):
+        """Test unshield_with_ata prepends the ATA creation accounts"""
+        from veil.solana_client import InstructionBuilder
+        from solders.pubkey import Pubkey
+        from spl.token.constants import ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        relayer, recipient, mint, vault_ata, relayer_ata = (
+            Pubkey.new_unique() for _ in range(5)
+        )
+        recipient_ata, _ = Pubkey.find_program_address(
+            [bytes(recipient), bytes(TOKEN_PROGRAM_ID), bytes(mint)],
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+        )
+
+        ix = builder.unshield_spl_with_ata(
+            relayer, recipient, vault_ata, bytes([4] * 32), 1000, bytes(96), mint, relayer_ata
+        )
+        plain = builder.unshield_spl(
+            relayer, recipient_ata, vault_ata, bytes([4] * 32), 1000, bytes(96), mint, relayer_ata
+        )
+        assert ix.data[:8] == InstructionBuilder.UNSHIELD_WITH_ATA_DISC
+        assert ix.data[8:] == plain.data[8:]
+        assert ix.accounts[0].is_signer and ix.accounts[0].pubkey == relayer
+        assert ix.accounts[1].pubkey == recipient
+        assert ix.accounts[3].pubkey == recipient_ata and ix.accounts[3].is_writable
+        assert ix.accounts[5].pubkey == ASSOCIATED_TOKEN_PROGRAM_ID
+        assert ix.accounts[7:] == plain.accounts
+
+
+    def test_unshield_memo(self):
+        """Test unshield memos are encoded with the Memo program account"""
+        from veil.solana_client import InstructionBuilder, MAX_MEMO_LEN, MEMO_PROGRAM_ID
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        relayer = Pubkey.new_unique()
+
+        plain = builder.unshield_sol(relayer, relayer, bytes(32), 1000, bytes(96))
+        assert plain.accounts[-1].pubkey == program_id
+        assert plain.data.endswith(bytes(4))
+
+        ix = builder.unshield_sol(
+            relayer, relayer, bytes(32), 1000, bytes(96), memo=b"deposit-ref 4821"
+        )
+        assert ix.accounts[-1].pubkey == MEMO_PROGRAM_ID
+        assert ix.data.endswith((16).to_bytes(4, "little") + b"deposit-ref 4821")
+
+        for bad in (b"a" * (MAX_MEMO_LEN + 1), b"\xff\xfe"):
+            with pytest.raises(ValueError):
+                builder.unshield_sol(relayer, relayer, bytes(32), 1000, bytes(96), memo=bad)
+
+
+    def test_compressed_tree_instructions(self):
+        """Test compressed tree setup and trailing insertion accounts"""
+        from veil.solana_client import (
+            ACCOUNT_COMPRESSION_PROGRAM_ID,
+            InstructionBuilder,
+            NOOP_PROGRAM_ID,
+            concurrent_merkle_tree_size,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        tree = Pubkey.new_unique()
+
+        ix = builder.use_compressed_tree(authority, tree, 26, 1024)
+        assert ix.data[:8] == InstructionBuilder.USE_COMPRESSED_TREE_DISC
+        assert ix.data[8] == 26
+        assert ix.data[9:] == (1024).to_bytes(4, "little")
+        assert ix.accounts[1].pubkey == tree
+        with pytest.raises(ValueError):
+            builder.use_compressed_tree(authority, tree, 20, 1024)
+
+        # depth 14, buffer 64: the smallest tree spl-account-compression lists
+        assert concurrent_merkle_tree_size(14, 64) == 31_800
+
+        shield = builder.shield_sol(authority, bytes(32), 1000, compressed_tree=tree)
+        assert [meta.pubkey for meta in shield.accounts[-3:]] == [
+            tree,
+            NOOP_PROGRAM_ID,
+            ACCOUNT_COMPRESSION_PROGRAM_ID,
+        ]
+        assert shield.accounts[-3].is_writable
+
+        # Tree accounts follow the sweep's marker/archive pairs
+        transfer = builder.transfer(
+            authority,
+            bytes([1] * 32),
+            bytes(32),
+            bytes(96),
+            extra_nullifiers=(bytes([2] * 32),),
+            compressed_tree=tree,
+        )
+        assert len(transfer.accounts) == 13 + 2 + 3
+        assert transfer.accounts[15].pubkey == tree
+
+
+    def test_root_history_instructions(self):
+        """Test the root history account on initialize, shields and spends"""
+        from veil.solana_client import (
+            DEFAULT_ROOT_HISTORY_SIZE,
+            MAX_ROOT_HISTORY_SIZE,
+            InstructionBuilder,
+            find_pool_pda,
+            find_root_history_pda,
+        )
+        from solders.pubkey import Pubkey
+
+        program_id = Pubkey.from_string("Vei1111111111111111111111111111111111111111")
+        builder = InstructionBuilder(program_id)
+        authority = Pubkey.new_unique()
+        pool, _ = find_pool_pda(program_id)
+        history, _ = find_root_history_pda(program_id, pool)
+
+        ix = builder.initialize(authority)
+        assert ix.data[8:] == DEFAULT_ROOT_HISTORY_SIZE.to_bytes(2, "little")
+        assert ix.accounts[2].pubkey == history and ix.accounts[2].is_writable
+
+        resize = builder.resize_root_history(authority, 100)
+        assert resize.data[:8] == InstructionBuilder.RESIZE_ROOT_HISTORY_DISC
+        assert resize.data[8:] == (100).to_bytes(2, "little")
+        assert resize.accounts[1].pubkey == history
+        assert resize.accounts[2].is_signer
+        for size in (0, MAX_ROOT_HISTORY_SIZE + 1):
+            with pytest.raises(ValueError):
+                builder.resize_root_history(authority, size)
+
+        # Shields push new roots; spends name the root their proof is against
+        shield = builder.shield_sol(authority, bytes(32), 1000)
+        assert shield.accounts[1].pubkey == history and shield.accounts[1].is_writable
+        transfer = builder.transfer(
+            authority, bytes([1] * 32), bytes(32), bytes(96), root=bytes([9] * 32)
+        )
+        assert transfer.accounts[2].pubkey == history
+        assert transfer.data[12:44] == bytes([9] * 32)
+        unshield = builder.unshield_sol(authority, authority, bytes(32), 1000, bytes(96))
+        assert unshield.accounts[2].pubkey == history
+        assert not unshield.accounts[2].is_writable
+        with pytest.raises(ValueError):
+            builder.transfer(authority, bytes(32), bytes(32), bytes(96), root=bytes(31))
+
 
 class TestMVPProof:
     """Test MVP proof generation"""
@@ -229,7 +738,6 @@ class TestClientOffline:
         mock = MagicMock()
         mock.generate_commitment.return_value = bytes([1] * 32)
         mock.generate_nullifier.return_value = bytes([2] * 32)
-        mock.generate_proof.return_value = bytes([3] * 64)
         mock.verify_proof.return_value = True
         return mock
 
@@ -273,7 +781,8 @@ class TestClientOffline:
 
             assert tx.status == TransactionStatus.PENDING
             assert tx.nullifier is not None
-            assert tx.proof is not None
+            assert tx.proof is None
+            mock_rust_core.generate_proof.assert_not_called()
 
 
 class TestCommitmentData: